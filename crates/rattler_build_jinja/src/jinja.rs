@@ -466,11 +466,17 @@ fn jinja_pin_function(
     Ok(internal_repr.to_json(&pin))
 }
 
-fn default_compiler(platform: Platform, language: &str) -> Option<Variable> {
+/// The default compiler package (without the `_<platform>` suffix) of a language, if the
+/// variant has no `<language>_compiler` key
+pub fn default_compiler(platform: Platform, language: &str) -> Option<Variable> {
     Some(
         match language {
-            // Platform agnostic compilers
+            "fortran" if platform.is_windows() => "flang",
             "fortran" => "gfortran",
+            // the go compiler without and with cgo (as in the global pinning of conda-forge)
+            "go" => "go-nocgo",
+            "go-cgo" => "go-cgo",
+            // Platform agnostic compilers (e.g. `rust` and `cuda`)
             lang if !["c", "cxx"].contains(&lang) => lang,
             // Platform specific compilers
             _ => {
@@ -2079,5 +2085,49 @@ mod tests {
             "cuda",
             default_compiler(platform, "cuda").unwrap().to_string()
         );
+        assert_eq!(
+            "flang",
+            default_compiler(platform, "fortran").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_default_compiler_languages() {
+        for platform in [Platform::Linux64, Platform::OsxArm64, Platform::Win64] {
+            assert_eq!(
+                "rust",
+                default_compiler(platform, "rust").unwrap().to_string()
+            );
+            assert_eq!(
+                "go-nocgo",
+                default_compiler(platform, "go").unwrap().to_string()
+            );
+            assert_eq!(
+                "go-cgo",
+                default_compiler(platform, "go-cgo").unwrap().to_string()
+            );
+        }
+        assert_eq!(
+            "gfortran",
+            default_compiler(Platform::OsxArm64, "fortran")
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_compiler_languages() {
+        let jinja = Jinja::new(JinjaConfig {
+            target_platform: Platform::LinuxAarch64,
+            ..Default::default()
+        });
+        assert_eq!(
+            jinja.render_str("${{ compiler('go-cgo') }}").unwrap(),
+            "go-cgo_linux-aarch64"
+        );
+        assert_eq!(
+            jinja.render_str("${{ compiler('rust') }}").unwrap(),
+            "rust_linux-aarch64"
+        );
     }
 }
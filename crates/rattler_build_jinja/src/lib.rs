@@ -9,7 +9,7 @@ pub use ast_variables::{
     JinjaExpression, JinjaTemplate, extract_default_guarded_variables_from_expression,
     extract_default_guarded_variables_from_template,
 };
-pub use jinja::{Jinja, JinjaConfig};
+pub use jinja::{Jinja, JinjaConfig, default_compiler};
 pub use rattler_build_types::NormalizedKey;
 pub use variable::Variable;
 
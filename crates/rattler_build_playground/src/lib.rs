@@ -6,11 +6,10 @@ use arborium::theme::builtin;
 use indexmap::IndexMap;
 use rattler_build_jinja::{JinjaConfig, Variable};
 use rattler_build_recipe::{
+    preview::{self, PreviewError, PreviewOptions, VariantFormat},
     stage0::{self, Recipe},
     stage1::{Evaluate, EvaluationContext},
-    variant_render::{RenderConfig, render_recipe_with_variant_config},
 };
-use rattler_build_variant_config::{VariantConfig, parse_conda_build_config};
 use rattler_conda_types::Platform;
 use serde::Serialize;
 use wasm_bindgen::prelude::*;
@@ -198,31 +197,6 @@ pub fn get_platforms() -> String {
     serde_json::to_string(&platforms).unwrap_or_default()
 }
 
-/// A concise summary of a rendered variant for display in the UI
-#[derive(Serialize)]
-struct VariantSummary {
-    /// Package name
-    name: String,
-    /// Package version
-    version: String,
-    /// Build string (resolved)
-    build_string: Option<String>,
-    /// Whether this output is skipped
-    skipped: bool,
-    /// Whether this is a noarch package
-    noarch: Option<String>,
-    /// Variant keys and values
-    variant: Vec<(String, String)>,
-    /// Build dependencies (just names)
-    build_deps: Vec<String>,
-    /// Host dependencies (just names)
-    host_deps: Vec<String>,
-    /// Run dependencies (display strings)
-    run_deps: Vec<String>,
-    /// Resolved context variables (key -> evaluated JSON value)
-    context: IndexMap<String, Variable>,
-}
-
 /// Render a recipe with variant configuration, producing all output variants.
 ///
 /// - `yaml_source`: The recipe YAML string
@@ -238,116 +212,58 @@ pub fn render_variants(
     target_platform: &str,
     variant_format: &str,
 ) -> String {
-    // Parse the recipe to Stage 0
-    let stage0_recipe = match stage0::parse_recipe_or_multi_from_source(yaml_source) {
-        Ok(r) => r,
-        Err(e) => return format_parse_error(&e),
-    };
-
-    // Parse platform
-    let platform = Platform::from_str(target_platform).unwrap_or(Platform::Linux64);
-
-    let jinja_config = JinjaConfig {
-        target_platform: platform,
-        build_platform: platform,
-        host_platform: platform,
-        experimental: false,
-        recipe_path: None,
+    let options = PreviewOptions {
+        target_platform: Platform::from_str(target_platform).unwrap_or(Platform::Linux64),
+        variant_config: variant_config_yaml.to_string(),
+        variant_format: VariantFormat::from_str(variant_format).unwrap_or_default(),
         ..Default::default()
     };
 
-    let variant_config = if variant_format == "conda_build_config" {
-        match parse_conda_build_config(variant_config_yaml, &jinja_config) {
-            Ok(vc) => vc,
-            Err(e) => return error_json(&format!("Invalid conda_build_config: {e}"), None, None),
-        }
-    } else {
-        match VariantConfig::from_yaml_str(variant_config_yaml) {
-            Ok(vc) => vc,
-            Err(e) => return error_json(&format!("Invalid variant config: {e}"), None, None),
-        }
-    };
-
-    let render_config = RenderConfig::new()
-        .with_target_platform(platform)
-        .with_host_platform(platform)
-        .with_build_platform(platform);
-
-    // Render with variant config
-    match render_recipe_with_variant_config(&stage0_recipe, &variant_config, render_config) {
-        Ok(rendered) => {
-            // Build concise summaries
-            let summary: Vec<VariantSummary> = rendered
-                .iter()
-                .map(|rv| {
-                    let recipe = &rv.recipe;
-                    let build_string = recipe.build.string.as_resolved().map(|s| s.to_string());
-                    let noarch = recipe.build.noarch.and_then(|n| {
-                        if n.is_none() {
-                            None
-                        } else if n.is_python() {
-                            Some("python".to_string())
-                        } else {
-                            Some("generic".to_string())
-                        }
-                    });
-
-                    let variant: Vec<(String, String)> = rv
-                        .variant
-                        .iter()
-                        .map(|(k, v)| (k.0.clone(), v.to_string()))
-                        .collect();
-
-                    let build_deps: Vec<String> = recipe
-                        .requirements
-                        .build
-                        .iter()
-                        .filter_map(|d| d.name().map(|n| n.as_normalized().to_string()))
-                        .collect();
-
-                    let host_deps: Vec<String> = recipe
-                        .requirements
-                        .host
-                        .iter()
-                        .filter_map(|d| d.name().map(|n| n.as_normalized().to_string()))
-                        .collect();
-
-                    let run_deps: Vec<String> = recipe
-                        .requirements
-                        .run
-                        .iter()
-                        .map(|d| d.to_string())
-                        .collect();
-
-                    VariantSummary {
-                        name: recipe.package.name.as_normalized().to_string(),
-                        version: recipe.package.version.to_string(),
-                        build_string,
-                        skipped: recipe.build.skip,
-                        noarch,
-                        variant,
-                        build_deps,
-                        host_deps,
-                        run_deps,
-                        context: recipe.context.clone(),
-                    }
-                })
-                .collect();
-
+    match preview::preview_recipe(yaml_source, &options) {
+        Ok(preview) => {
             // Highlight full variant data as YAML
-            let variants_html = highlight_yaml(&rendered).unwrap_or_default();
+            let variants_html = highlight_yaml(&preview.variants).unwrap_or_default();
 
             let result = serde_json::json!({
                 "ok": true,
                 "result": {
                     "variants_html": variants_html,
-                    "summary": summary,
+                    "summary": preview.variants,
                 },
             });
-            serde_json::to_string(&result)
-                .expect("serialization of ok response cannot fail")
+            serde_json::to_string(&result).expect("serialization of ok response cannot fail")
+        }
+        Err(e) => preview_error_json(&e),
+    }
+}
+
+/// Render a recipe for a live preview, returning structured (not highlighted) JSON.
+///
+/// - `yaml_source`: The recipe YAML string
+/// - `options_json`: The [`PreviewOptions`] as JSON, e.g.
+///   `{"target_platform": "linux-64", "variant_config": "python: [\"3.12\"]"}`.
+///   All fields are optional.
+///
+/// Returns `{ "ok": true, "result": { "used_variables": [...], "variants": [...] } }`, where
+/// every variant contains the variant values, a summary and the fully rendered recipe.
+#[wasm_bindgen]
+pub fn preview_recipe(yaml_source: &str, options_json: &str) -> String {
+    let options: PreviewOptions = if options_json.trim().is_empty() {
+        PreviewOptions::default()
+    } else {
+        match serde_json::from_str(options_json) {
+            Ok(options) => options,
+            Err(e) => return error_json(&format!("Invalid preview options: {e}"), None, None),
         }
-        Err(e) => error_json(&e.to_string(), None, None),
+    };
+
+    match preview::preview_recipe(yaml_source, &options) {
+        Ok(preview) => serde_json::to_string(&serde_json::json!({
+            "ok": true,
+            "result": preview,
+        }))
+        .unwrap_or_else(|e| error_json(&e.to_string(), None, None)),
+        Err(e) => preview_error_json(&e),
     }
 }
 
@@ -379,8 +295,7 @@ fn parse_variables(json: &str) -> Result<IndexMap<String, Variable>, String> {
 /// For example, `python:\n  - "3.11"\n  - "3.12"` becomes `{"python": "3.11"}`.
 #[wasm_bindgen]
 pub fn first_variant_values(variant_yaml: &str) -> String {
-    let parsed: Result<IndexMap<String, serde_yaml::Value>, _> =
-        serde_yaml::from_str(variant_yaml);
+    let parsed: Result<IndexMap<String, serde_yaml::Value>, _> = serde_yaml::from_str(variant_yaml);
 
     let map = match parsed {
         Ok(m) => m,
@@ -425,6 +340,10 @@ fn error_json(message: &str, line: Option<usize>, column: Option<usize>) -> Stri
     }
 }
 
+fn preview_error_json(e: &PreviewError) -> String {
+    error_json(&e.message, e.line, e.column)
+}
+
 fn format_parse_error(e: &rattler_build_yaml_parser::ParseError) -> String {
     let message = e.to_string();
     match e {
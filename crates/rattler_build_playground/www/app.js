@@ -141,9 +141,10 @@ function renderCard(s) {
       }).join('')
     )}</tbody></table>`;
 
-  const variantKeys = s.variant.length === 0 ? '' :
+  const variant = Object.entries(s.variant);
+  const variantKeys = variant.length === 0 ? '' :
     html`<div class="variant-keys">${safe(
-      s.variant.map(([k, v]) =>
+      variant.map(([k, v]) =>
         html`<span class="variant-key-pill"><span class="variant-key-name">${k}</span><span class="variant-key-value">${v}</span></span>`
       ).join('')
     )}</div>`;
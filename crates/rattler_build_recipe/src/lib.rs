@@ -1,4 +1,5 @@
 pub mod error;
+pub mod schema;
 pub mod source_code;
 pub mod stage0;
 pub mod stage1;
@@ -6,6 +7,9 @@ pub mod stage1;
 #[cfg(feature = "variant-config")]
 pub mod variant_render;
 
+#[cfg(feature = "variant-config")]
+pub mod preview;
+
 pub use error::{ParseError, ParseErrorWithSource, ParseResult, WithSourceCode};
 pub use stage0::Stage0Recipe;
 pub use stage1::{Evaluate, EvaluationContext, Recipe as Stage1Recipe};
@@ -16,6 +20,9 @@ pub use variant_render::{
     render_recipe_with_variant_config,
 };
 
+#[cfg(feature = "variant-config")]
+pub use preview::{Preview, PreviewError, PreviewOptions, PreviewVariant, preview_recipe};
+
 #[cfg(all(feature = "variant-config", not(target_arch = "wasm32")))]
 pub use variant_render::render_recipe_with_variants;
 
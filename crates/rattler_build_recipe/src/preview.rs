@@ -0,0 +1,350 @@
+//! Live preview of a recipe for editors.
+//!
+//! [`preview_recipe`] parses a recipe from source, renders it with a variant configuration
+//! that is passed as a string and returns the variant matrix together with the rendered
+//! outputs. Nothing is read from disk or the network, so this module compiles to
+//! `wasm32-unknown-unknown` and is what the WASM playground is built on. All results and
+//! errors are serializable, so that they can be passed to JavaScript as JSON.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use indexmap::IndexMap;
+use rattler_build_jinja::{JinjaConfig, Variable};
+use rattler_build_variant_config::{VariantConfig, parse_conda_build_config};
+use rattler_conda_types::Platform;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ParseError,
+    stage0::{self, Recipe},
+    stage1::Recipe as Stage1Recipe,
+    variant_render::{RenderConfig, RenderError, render_recipe_with_variant_config},
+};
+
+/// The format of the variant configuration of a preview
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantFormat {
+    /// A `variants.yaml` file
+    #[default]
+    VariantConfig,
+    /// A `conda_build_config.yaml` file (with `# [selector]` comments)
+    CondaBuildConfig,
+}
+
+impl FromStr for VariantFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "variant_config" | "variants" => Ok(Self::VariantConfig),
+            "conda_build_config" => Ok(Self::CondaBuildConfig),
+            _ => Err(format!("unknown variant format: {s}")),
+        }
+    }
+}
+
+/// The options of a preview
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreviewOptions {
+    /// The platform that the recipe is rendered for
+    pub target_platform: Platform,
+    /// The platform that the build runs on (defaults to the target platform)
+    pub build_platform: Option<Platform>,
+    /// The platform of the host environment (defaults to the target platform)
+    pub host_platform: Option<Platform>,
+    /// The variant configuration (empty for no variants)
+    pub variant_config: String,
+    /// The format of `variant_config`
+    pub variant_format: VariantFormat,
+    /// Whether experimental features are enabled
+    pub experimental: bool,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        Self {
+            target_platform: Platform::current(),
+            build_platform: None,
+            host_platform: None,
+            variant_config: String::new(),
+            variant_format: VariantFormat::default(),
+            experimental: false,
+        }
+    }
+}
+
+impl PreviewOptions {
+    fn jinja_config(&self) -> JinjaConfig {
+        JinjaConfig {
+            target_platform: self.target_platform,
+            build_platform: self.build_platform.unwrap_or(self.target_platform),
+            host_platform: self.host_platform.unwrap_or(self.target_platform),
+            experimental: self.experimental,
+            recipe_path: None,
+            ..Default::default()
+        }
+    }
+
+    fn render_config(&self) -> RenderConfig {
+        RenderConfig::new()
+            .with_target_platform(self.target_platform)
+            .with_build_platform(self.build_platform.unwrap_or(self.target_platform))
+            .with_host_platform(self.host_platform.unwrap_or(self.target_platform))
+            .with_experimental(self.experimental)
+    }
+}
+
+/// The result of a preview
+#[derive(Debug, Clone, Serialize)]
+pub struct Preview {
+    /// The variables that the recipe uses (the columns of the variant matrix)
+    pub used_variables: Vec<String>,
+    /// One entry per rendered output and variant (the rows of the variant matrix)
+    pub variants: Vec<PreviewVariant>,
+}
+
+/// A rendered output of a preview
+#[derive(Debug, Clone, Serialize)]
+pub struct PreviewVariant {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    /// The build string, if it could be resolved
+    pub build_string: Option<String>,
+    /// Whether the output is skipped for this variant
+    pub skipped: bool,
+    /// The kind of noarch package (`python` or `generic`), if any
+    pub noarch: Option<String>,
+    /// The variant values that this output uses
+    pub variant: BTreeMap<String, String>,
+    /// The names of the build dependencies
+    pub build_deps: Vec<String>,
+    /// The names of the host dependencies
+    pub host_deps: Vec<String>,
+    /// The run dependencies
+    pub run_deps: Vec<String>,
+    /// The evaluated context variables
+    pub context: IndexMap<String, Variable>,
+    /// The fully rendered recipe of the output
+    pub recipe: Stage1Recipe,
+}
+
+impl PreviewVariant {
+    fn new(variant: &BTreeMap<String, String>, recipe: Stage1Recipe) -> Self {
+        let names = |deps: &[crate::stage1::Dependency]| {
+            deps.iter()
+                .filter_map(|d| d.name().map(|n| n.as_normalized().to_string()))
+                .collect()
+        };
+        let noarch = recipe.build.noarch.and_then(|noarch| {
+            if noarch.is_none() {
+                None
+            } else if noarch.is_python() {
+                Some("python".to_string())
+            } else {
+                Some("generic".to_string())
+            }
+        });
+        Self {
+            name: recipe.package.name.as_normalized().to_string(),
+            version: recipe.package.version.to_string(),
+            build_string: recipe.build.string.as_resolved().map(|s| s.to_string()),
+            skipped: recipe.build.skip,
+            noarch,
+            variant: variant.clone(),
+            build_deps: names(&recipe.requirements.build),
+            host_deps: names(&recipe.requirements.host),
+            run_deps: recipe
+                .requirements
+                .run
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            context: recipe.context.clone(),
+            recipe,
+        }
+    }
+}
+
+/// An error of a preview, with the position in the recipe if known
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreviewError {
+    /// The error message
+    pub message: String,
+    /// The line of the error in the recipe (1-based)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// The column of the error in the recipe (1-based)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl PreviewError {
+    fn message(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl std::fmt::Display for PreviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for PreviewError {}
+
+impl From<&ParseError> for PreviewError {
+    fn from(e: &ParseError) -> Self {
+        let start = match e {
+            ParseError::IoError { .. } => None,
+            _ => e.span().start().copied(),
+        };
+        Self {
+            message: e.to_string(),
+            line: start.map(|start| start.line()),
+            column: start.map(|start| start.column()),
+        }
+    }
+}
+
+impl From<&RenderError> for PreviewError {
+    fn from(e: &RenderError) -> Self {
+        match e {
+            RenderError::Parse(e) => e.into(),
+            e => Self::message(e.to_string()),
+        }
+    }
+}
+
+/// Parse the variant configuration of `options`
+pub fn parse_variant_config(options: &PreviewOptions) -> Result<VariantConfig, PreviewError> {
+    if options.variant_config.trim().is_empty() {
+        return Ok(VariantConfig::default());
+    }
+    let jinja_config = options.jinja_config();
+    match options.variant_format {
+        VariantFormat::VariantConfig => {
+            VariantConfig::from_yaml_str_with_context(&options.variant_config, &jinja_config)
+                .map_err(|e| PreviewError::message(format!("invalid variant config: {e}")))
+        }
+        VariantFormat::CondaBuildConfig => {
+            parse_conda_build_config(&options.variant_config, &jinja_config)
+                .map_err(|e| PreviewError::message(format!("invalid conda_build_config: {e}")))
+        }
+    }
+}
+
+/// Parse `source` and render it with the variant configuration of `options`
+pub fn preview_recipe(source: &str, options: &PreviewOptions) -> Result<Preview, PreviewError> {
+    let recipe: Recipe =
+        stage0::parse_recipe_or_multi_from_source(source).map_err(|e| PreviewError::from(&e))?;
+    let variant_config = parse_variant_config(options)?;
+
+    let rendered =
+        render_recipe_with_variant_config(&recipe, &variant_config, options.render_config())
+            .map_err(|e| PreviewError::from(&e))?;
+
+    let variants = rendered
+        .into_iter()
+        .map(|rendered| {
+            let variant = rendered
+                .variant
+                .iter()
+                .map(|(key, value)| (key.normalize(), value.to_string()))
+                .collect();
+            PreviewVariant::new(&variant, rendered.recipe)
+        })
+        .collect();
+
+    Ok(Preview {
+        used_variables: recipe.used_variables(),
+        variants,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPE: &str = r#"
+context:
+  name: foo
+  version: "1.0"
+
+package:
+  name: ${{ name }}
+  version: ${{ version }}
+
+requirements:
+  host:
+    - python ${{ python }}.*
+    - if: win
+      then: pywin32
+  run:
+    - python
+"#;
+
+    #[test]
+    fn test_preview_variant_matrix() {
+        let options = PreviewOptions {
+            target_platform: Platform::Linux64,
+            variant_config: "python:\n  - \"3.11\"\n  - \"3.12\"\n".to_string(),
+            ..Default::default()
+        };
+        let preview = preview_recipe(RECIPE, &options).unwrap();
+        assert!(preview.used_variables.contains(&"python".to_string()));
+        assert_eq!(preview.variants.len(), 2);
+
+        let pythons = preview
+            .variants
+            .iter()
+            .map(|v| v.variant["python"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(pythons, ["3.11", "3.12"]);
+        let variant = &preview.variants[0];
+        assert_eq!(variant.name, "foo");
+        assert_eq!(variant.version, "1.0");
+        assert_eq!(variant.host_deps, ["python"]);
+        assert!(variant.build_string.is_some());
+    }
+
+    #[test]
+    fn test_preview_platform() {
+        let options = PreviewOptions {
+            target_platform: Platform::Win64,
+            variant_config: "python:\n  - \"3.12\"\n".to_string(),
+            ..Default::default()
+        };
+        let preview = preview_recipe(RECIPE, &options).unwrap();
+        assert_eq!(preview.variants[0].host_deps, ["python", "pywin32"]);
+    }
+
+    #[test]
+    fn test_preview_error_position() {
+        let err = preview_recipe(
+            "package:\n  name: foo\n  version: 1.0\nbild: {}\n",
+            &PreviewOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.line, Some(4));
+        assert!(err.message.contains("bild"), "{}", err.message);
+
+        let options = PreviewOptions {
+            variant_config: "python: [".to_string(),
+            ..Default::default()
+        };
+        let err = preview_recipe(RECIPE, &options).unwrap_err();
+        assert!(err.message.starts_with("invalid variant config"));
+        assert_eq!(err.line, None);
+    }
+}
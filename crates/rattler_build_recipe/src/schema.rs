@@ -0,0 +1,746 @@
+//! The JSON Schema of the recipe format (`rattler-build schema --format json-schema`).
+//!
+//! The schema is written next to the parser in [`crate::stage0`] and describes the same
+//! structure: the keys of every section, the conditional lists (`if`/`then`/`else`), the
+//! source types and the test types. Every definition corresponds to a parse function of the
+//! parser, so a change of the parser should come with a change of its definition here. The
+//! tests check the keys of the schema against the parser.
+//!
+//! Scalars of the recipe are read as strings by the parser, so string values also accept
+//! numbers and booleans (e.g. `version: 1.0`), and typed values (numbers, booleans and enums)
+//! also accept Jinja expressions (e.g. `number: ${{ build_number }}`).
+use serde_json::{Map, Value, json};
+
+/// The definitions of the schema (`$defs`)
+#[derive(Default)]
+struct Definitions(Map<String, Value>);
+
+/// A reference to a definition
+fn reference(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{name}") })
+}
+
+/// A mapping with the given properties and no other keys
+fn object(properties: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// A mapping with the given properties, of which `required` must be present
+fn object_with_required(properties: Value, required: &[&str]) -> Value {
+    let mut object = object(properties);
+    object["required"] = json!(required);
+    object
+}
+
+/// A value that is either `schema` or a Jinja expression
+fn or_jinja(schema: Value) -> Value {
+    json!({ "anyOf": [schema, reference("JinjaExpression")] })
+}
+
+/// One of the given strings, or a Jinja expression
+fn enumeration(values: &[&str]) -> Value {
+    or_jinja(json!({ "enum": values }))
+}
+
+impl Definitions {
+    /// Add a definition and return a reference to it
+    fn define(&mut self, name: &str, schema: Value) -> Value {
+        self.0.insert(name.to_string(), schema);
+        reference(name)
+    }
+
+    /// A list whose items are `item` or `if`/`then`/`else` conditionals, whose branches are
+    /// one or a list of the same items (`ConditionalList` of the parser)
+    fn conditional_list(&mut self, name: &str, item: Value) -> Value {
+        let item = self.conditional_item(name, item);
+        json!({ "type": "array", "items": item })
+    }
+
+    /// An item of a conditional list, see [`Self::conditional_list`]
+    fn conditional_item(&mut self, name: &str, item: Value) -> Value {
+        let item_name = format!("Conditional{name}");
+        if !self.0.contains_key(&item_name) {
+            let this = reference(&item_name);
+            let branch = json!({ "anyOf": [this, { "type": "array", "items": this }] });
+            let conditional = object_with_required(
+                json!({
+                    "if": {
+                        "type": "string",
+                        "description": "A Jinja expression, e.g. `linux and cuda`",
+                    },
+                    "then": branch,
+                    "else": branch,
+                }),
+                &["if", "then"],
+            );
+            self.define(&item_name, json!({ "anyOf": [item, conditional] }));
+        }
+        reference(&item_name)
+    }
+
+    /// A conditional list of strings, e.g. glob patterns or commands
+    fn string_list(&mut self) -> Value {
+        self.conditional_list("String", reference("String"))
+    }
+
+    /// A conditional list of strings that may also be `null` (an empty list)
+    fn nullable_string_list(&mut self) -> Value {
+        let list = self.string_list();
+        json!({ "anyOf": [list, { "type": "null" }] })
+    }
+
+    /// One string or a conditional list of strings (`ConditionalListOrItem` of the parser)
+    fn string_or_list(&mut self) -> Value {
+        let list = self.string_list();
+        json!({ "anyOf": [reference("String"), list] })
+    }
+
+    /// `true`/`false` (or a Jinja expression) or a list of glob patterns
+    fn boolean_or_patterns(&mut self) -> Value {
+        let list = self.string_list();
+        json!({ "anyOf": [reference("Boolean"), list] })
+    }
+
+    /// A list of glob patterns or a mapping with `include` and `exclude` patterns
+    fn include_exclude(&mut self) -> Value {
+        let list = self.string_list();
+        let mapping = object(json!({ "include": list, "exclude": list }));
+        self.define("IncludeExclude", json!({ "anyOf": [list, mapping] }))
+    }
+
+    fn context(&mut self) -> Value {
+        let scalar = json!({ "type": ["string", "number", "boolean"] });
+        self.define(
+            "Context",
+            json!({
+                "description": "Variables for the Jinja expressions of the recipe",
+                "type": "object",
+                "propertyNames": {
+                    "pattern": "^[^-]*$",
+                    "description": "Variable names cannot contain hyphens",
+                },
+                "additionalProperties": {
+                    "anyOf": [scalar, { "type": "array", "items": scalar }],
+                },
+            }),
+        )
+    }
+
+    fn package(&mut self) -> Value {
+        let properties = json!({
+            "name": reference("String"),
+            "version": reference("String"),
+        });
+        self.define(
+            "OutputPackage",
+            object_with_required(properties.clone(), &["name"]),
+        );
+        self.define("Recipe", object(properties.clone()));
+        self.define(
+            "Package",
+            object_with_required(properties, &["name", "version"]),
+        )
+    }
+
+    fn source(&mut self) -> Value {
+        let patches = self.string_list();
+        let string_or_strings = json!({
+            "anyOf": [reference("String"), { "type": "array", "items": reference("String") }],
+        });
+        let url = self.define(
+            "UrlSource",
+            object_with_required(
+                json!({
+                    "url": string_or_strings,
+                    "sha256": reference("String"),
+                    "md5": reference("String"),
+                    "file_name": reference("String"),
+                    "patches": patches,
+                    "target_directory": reference("String"),
+                    "attestation": object(json!({
+                        "bundle_url": reference("String"),
+                        "publishers": string_or_strings,
+                    })),
+                }),
+                &["url"],
+            ),
+        );
+
+        let mut git = object_with_required(
+            json!({
+                "git": reference("String"),
+                "rev": reference("String"),
+                "tag": reference("String"),
+                "branch": reference("String"),
+                "depth": reference("Integer"),
+                "patches": patches,
+                "target_directory": reference("String"),
+                "lfs": reference("Boolean"),
+                "submodules": reference("Boolean"),
+                "expected_commit": reference("String"),
+            }),
+            &["git"],
+        );
+        git["not"] = json!({
+            "description": "Only one of `rev`, `tag` and `branch` can be set",
+            "anyOf": [
+                { "required": ["rev", "tag"] },
+                { "required": ["rev", "branch"] },
+                { "required": ["tag", "branch"] },
+            ],
+        });
+        let git = self.define("GitSource", git);
+
+        let filter = self.include_exclude();
+        let path = self.define(
+            "PathSource",
+            object_with_required(
+                json!({
+                    "path": reference("String"),
+                    "sha256": reference("String"),
+                    "md5": reference("String"),
+                    "patches": patches,
+                    "target_directory": reference("String"),
+                    "file_name": reference("String"),
+                    "use_gitignore": { "type": "boolean" },
+                    "filter": filter,
+                }),
+                &["path"],
+            ),
+        );
+
+        let source = self.define("Source", json!({ "anyOf": [url, git, path] }));
+        let item = self.conditional_item("Source", source);
+        self.define(
+            "Sources",
+            json!({ "anyOf": [item, { "type": "array", "items": item }] }),
+        )
+    }
+
+    fn script(&mut self) -> Value {
+        let content = self.string_or_list();
+        let list = self.string_list();
+        let mapping = object(json!({
+            "interpreter": reference("String"),
+            "env": {
+                "type": "object",
+                "additionalProperties": reference("String"),
+            },
+            "secrets": { "type": "array", "items": { "type": "string" } },
+            "content": content,
+            "file": reference("String"),
+            "cwd": reference("String"),
+        }));
+        self.define(
+            "Script",
+            json!({
+                "description": "A script: the commands as a string or list, or a mapping with `interpreter`, `env`, `secrets`, `content`, `file` and `cwd`",
+                "anyOf": [reference("String"), list, mapping],
+            }),
+        )
+    }
+
+    fn build(&mut self) -> Value {
+        let list = self.string_list();
+        let script = self.script();
+        let files = self.include_exclude();
+        let boolean_or_patterns = self.boolean_or_patterns();
+        let skip = self.string_or_list();
+        let behavior = enumeration(&["ignore", "warn", "error"]);
+        let overrides = object(json!({ "ignore": list, "warn": list, "error": list }));
+
+        let python = object(json!({
+            "entry_points": list,
+            "gui_entry_points": list,
+            "entry_point_style": enumeration(&["launcher", "script"]),
+            "entry_point_check": behavior,
+            "skip_pyc_compilation": list,
+            "use_python_app_entrypoint": reference("Boolean"),
+            "version_independent": reference("Boolean"),
+            "site_packages_path": reference("String"),
+            "noarch_pyc_versions": list,
+        }));
+        let dynamic_linking = object(json!({
+            "rpaths": list,
+            "binary_relocation": boolean_or_patterns,
+            "missing_dso_allowlist": list,
+            "rpath_allowlist": list,
+            "overdepending_behavior": behavior,
+            "overlinking_behavior": behavior,
+            "overlinking_overrides": overrides,
+            "overdepending_overrides": overrides,
+            "rpath_type": enumeration(&["rpath", "runpath"]),
+            "vendor_libraries": list,
+        }));
+        let variant = object(json!({
+            "use_keys": list,
+            "ignore_keys": list,
+            "down_prioritize_variant": reference("Integer"),
+        }));
+        let prefix_detection = object(json!({
+            "force_file_type": object(json!({ "text": list, "binary": list })),
+            "ignore": boolean_or_patterns,
+            "ignore_binary_files": reference("Boolean"),
+            "placeholder_length": reference("Integer"),
+            "build_path_leaks": behavior,
+        }));
+        let post_process = self.conditional_list(
+            "PostProcess",
+            object_with_required(
+                json!({
+                    "files": list,
+                    "regex": reference("String"),
+                    "replacement": reference("String"),
+                }),
+                &["files", "regex", "replacement"],
+            ),
+        );
+        let symlinks = object(json!({
+            "default": enumeration(&["preserve", "dereference", "error"]),
+            "preserve": list,
+            "dereference": list,
+            "error": list,
+        }));
+        let strip = json!({
+            "anyOf": [
+                reference("Boolean"),
+                object(json!({ "enabled": reference("Boolean"), "exclude": list })),
+            ],
+        });
+        let autotools_cleanup = json!({
+            "anyOf": [
+                reference("Boolean"),
+                object(json!({
+                    "remove_la_files": reference("Boolean"),
+                    "relative_config_files": reference("Boolean"),
+                })),
+            ],
+        });
+
+        self.define("StagingBuild", object(json!({ "script": script.clone() })));
+        self.define(
+            "Build",
+            object(json!({
+                "number": reference("Integer"),
+                "string": reference("String"),
+                "script": script,
+                "noarch": enumeration(&["python", "generic"]),
+                "python": python,
+                "skip": skip,
+                "always_copy_files": list,
+                "always_include_files": list,
+                "merge_build_and_host_envs": reference("Boolean"),
+                "files": files,
+                "dynamic_linking": dynamic_linking,
+                "variant": variant,
+                "prefix_detection": prefix_detection,
+                "post_process": post_process,
+                "symlinks": symlinks,
+                "strip": strip,
+                "autotools_cleanup": autotools_cleanup,
+                "menu": {
+                    "type": "object",
+                    "description": "A menuinst menu definition",
+                },
+            })),
+        )
+    }
+
+    fn requirements(&mut self) -> Value {
+        let dependencies = self.nullable_string_list();
+        let list = self.string_list();
+        let run_exports = json!({
+            "anyOf": [
+                list,
+                object(json!({
+                    "noarch": dependencies,
+                    "strong": dependencies,
+                    "strong_constraints": dependencies,
+                    "weak": dependencies,
+                    "weak_constraints": dependencies,
+                })),
+            ],
+        });
+        let ignore_run_exports = object(json!({
+            "by_name": dependencies,
+            "from_package": dependencies,
+        }));
+        self.define(
+            "StagingRequirements",
+            object(json!({
+                "build": dependencies,
+                "host": dependencies,
+                "ignore_run_exports": ignore_run_exports,
+            })),
+        );
+        self.define(
+            "Requirements",
+            object(json!({
+                "build": dependencies,
+                "host": dependencies,
+                "run": dependencies,
+                "run_constraints": dependencies,
+                "run_exports": run_exports,
+                "ignore_run_exports": ignore_run_exports,
+            })),
+        )
+    }
+
+    fn about(&mut self) -> Value {
+        let license_file = self.string_or_list();
+        self.define(
+            "About",
+            object(json!({
+                "homepage": reference("String"),
+                "license": reference("String"),
+                "license_file": license_file,
+                "license_family": reference("String"),
+                "summary": reference("String"),
+                "description": reference("String"),
+                "documentation": reference("String"),
+                "repository": reference("String"),
+            })),
+        )
+    }
+
+    /// The tests, one definition per test type. The parser picks the type by the first key
+    /// that is present in the order `python`, `perl`, `r`, `ruby`, `script`, `downstream`,
+    /// `package_contents`.
+    fn tests(&mut self) -> Value {
+        let list = self.string_list();
+        let string_or_list = self.string_or_list();
+        let script = self.script();
+        let limits = json!({
+            "timeout": reference("Integer"),
+            "retries": reference("Integer"),
+            "virtual_packages": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "propertyNames": { "pattern": "^__" },
+                    "additionalProperties": reference("String"),
+                },
+            },
+        });
+        let with_limits = |key: &str, test: Value| {
+            let mut properties = limits.clone();
+            properties[key] = test;
+            object_with_required(properties, &[key])
+        };
+
+        let python = with_limits(
+            "python",
+            object(json!({
+                "imports": string_or_list,
+                "pip_check": reference("Boolean"),
+                "python_version": string_or_list,
+            })),
+        );
+        let perl = with_limits("perl", object(json!({ "uses": list })));
+        let r = with_limits("r", object(json!({ "libraries": list })));
+        let ruby = with_limits("ruby", object(json!({ "requires": list })));
+
+        let mut commands = limits.clone();
+        commands["script"] = script;
+        commands["requirements"] = object(json!({ "run": list, "build": list }));
+        commands["files"] = object(json!({ "source": list, "recipe": list }));
+        commands["expected"] = object(json!({
+            "exit_code": reference("Integer"),
+            "stdout": list,
+            "stdout_regex": list,
+        }));
+        let commands = object_with_required(commands, &["script"]);
+
+        let downstream = object_with_required(
+            json!({ "downstream": reference("String") }),
+            &["downstream"],
+        );
+
+        let check_files = json!({
+            "anyOf": [list, object(json!({ "exists": list, "not_exists": list }))],
+        });
+        // the parser ignores the other keys of a package contents test
+        let other_types = ["python", "perl", "r", "ruby", "script", "downstream"]
+            .iter()
+            .map(|key| json!({ "required": [key] }))
+            .collect::<Vec<_>>();
+        let package_contents = json!({
+            "type": "object",
+            "properties": {
+                "package_contents": object(json!({
+                    "files": check_files,
+                    "site_packages": check_files,
+                    "bin": check_files,
+                    "lib": check_files,
+                    "include": check_files,
+                    "strict": { "type": "boolean" },
+                })),
+            },
+            "required": ["package_contents"],
+            "not": { "anyOf": other_types },
+        });
+
+        let types = [
+            ("PythonTest", python),
+            ("PerlTest", perl),
+            ("RTest", r),
+            ("RubyTest", ruby),
+            ("CommandsTest", commands),
+            ("DownstreamTest", downstream),
+            ("PackageContentsTest", package_contents),
+        ]
+        .into_iter()
+        .map(|(name, test)| self.define(name, test))
+        .collect::<Vec<_>>();
+        let test = self.define("Test", json!({ "anyOf": types }));
+        let tests = self.conditional_list("Test", test);
+        self.define("Tests", tests)
+    }
+
+    fn outputs(&mut self) -> Value {
+        let inherit = json!({
+            "description": "The staging output to inherit the files from, `null` for the top-level recipe",
+            "anyOf": [
+                { "type": "null" },
+                reference("String"),
+                object_with_required(
+                    json!({
+                        "from": reference("String"),
+                        "run_exports": { "type": "boolean" },
+                    }),
+                    &["from"],
+                ),
+            ],
+        });
+        let staging = self.define(
+            "StagingOutput",
+            object_with_required(
+                json!({
+                    "staging": object_with_required(json!({ "name": reference("String") }), &["name"]),
+                    "source": reference("Sources"),
+                    "requirements": reference("StagingRequirements"),
+                    "build": reference("StagingBuild"),
+                }),
+                &["staging"],
+            ),
+        );
+        let package = self.define(
+            "PackageOutput",
+            object_with_required(
+                json!({
+                    "package": reference("OutputPackage"),
+                    "inherit": inherit,
+                    "source": reference("Sources"),
+                    "requirements": reference("Requirements"),
+                    "build": reference("Build"),
+                    "about": reference("About"),
+                    "tests": reference("Tests"),
+                }),
+                &["package"],
+            ),
+        );
+        json!({
+            "type": "array",
+            "minItems": 1,
+            "items": { "anyOf": [staging, package] },
+        })
+    }
+}
+
+/// The JSON Schema (draft 2020-12) of the recipes that the parser accepts
+pub fn recipe_schema() -> Value {
+    let mut definitions = Definitions::default();
+    definitions.define(
+        "JinjaExpression",
+        json!({
+            "type": "string",
+            "pattern": "\\$\\{\\{[\\s\\S]*\\}\\}",
+            "description": "A Jinja expression, e.g. `${{ version }}`",
+        }),
+    );
+    definitions.define(
+        "String",
+        json!({
+            "type": ["string", "number", "boolean"],
+            "description": "A string, the parser reads numbers and booleans as strings",
+        }),
+    );
+    definitions.define("Integer", or_jinja(json!({ "type": "integer" })));
+    definitions.define("Boolean", or_jinja(json!({ "type": "boolean" })));
+
+    let context = definitions.context();
+    let package = definitions.package();
+    let source = definitions.source();
+    let build = definitions.build();
+    let requirements = definitions.requirements();
+    let about = definitions.about();
+    let tests = definitions.tests();
+    let outputs = definitions.outputs();
+    let schema_version = json!({
+        "description": "The version of the recipe format",
+        "enum": [1],
+    });
+    let extra = json!({
+        "type": "object",
+        "description": "Free-form metadata, e.g. the `recipe-maintainers`",
+    });
+
+    let single = definitions.define(
+        "SingleOutputRecipe",
+        object_with_required(
+            json!({
+                "schema_version": schema_version,
+                "context": context,
+                "package": package,
+                "source": source,
+                "build": build,
+                "requirements": requirements,
+                "about": about,
+                "tests": tests,
+                "extra": extra,
+            }),
+            &["package"],
+        ),
+    );
+    let multi = definitions.define(
+        "MultiOutputRecipe",
+        object_with_required(
+            json!({
+                "schema_version": schema_version,
+                "context": context,
+                "recipe": reference("Recipe"),
+                "version": reference("String"),
+                "source": source,
+                "build": build,
+                "about": about,
+                "tests": tests,
+                "extra": extra,
+                "outputs": outputs,
+            }),
+            &["outputs"],
+        ),
+    );
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "rattler-build recipe",
+        "description": "A recipe of rattler-build (`recipe.yaml`)",
+        "oneOf": [single, multi],
+        "$defs": Value::Object(definitions.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stage0::parse_recipe_or_multi_from_source;
+
+    /// The keys of a mapping definition of the schema
+    fn properties<'a>(schema: &'a Value, definition: &str) -> Vec<&'a str> {
+        schema["$defs"][definition]["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("{definition} has no properties"))
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn is_unknown_field(recipe: &str) -> bool {
+        match parse_recipe_or_multi_from_source(recipe) {
+            Ok(_) => false,
+            Err(e) => {
+                let message = format!("{e} {e:?}");
+                message.contains("unknown field") || message.contains("unknown top-level field")
+            }
+        }
+    }
+
+    /// Every key of the schema is a key of the parser, and the parser rejects keys that are
+    /// not in the schema
+    fn check_keys(schema: &Value, definition: &str, template: &str) {
+        for key in properties(schema, definition) {
+            let recipe = template.replace("KEY", key);
+            assert!(
+                !is_unknown_field(&recipe),
+                "the parser does not know `{key}` of {definition}"
+            );
+        }
+        let recipe = template.replace("KEY", "not_a_key");
+        assert!(
+            is_unknown_field(&recipe),
+            "the parser accepts unknown keys in {definition}"
+        );
+    }
+
+    #[test]
+    fn test_schema_keys_match_parser() {
+        let schema = recipe_schema();
+        let package = "package:\n  name: foo\n  version: 1.0\n";
+        check_keys(&schema, "About", &format!("{package}about:\n  KEY: x\n"));
+        check_keys(&schema, "Build", &format!("{package}build:\n  KEY: x\n"));
+        check_keys(
+            &schema,
+            "Requirements",
+            &format!("{package}requirements:\n  KEY: []\n"),
+        );
+        check_keys(
+            &schema,
+            "UrlSource",
+            &format!("{package}source:\n  url: https://example.com\n  KEY: x\n"),
+        );
+        check_keys(
+            &schema,
+            "GitSource",
+            &format!("{package}source:\n  git: https://example.com\n  KEY: x\n"),
+        );
+        check_keys(
+            &schema,
+            "PathSource",
+            &format!("{package}source:\n  path: .\n  KEY: x\n"),
+        );
+        check_keys(
+            &schema,
+            "CommandsTest",
+            &format!("{package}tests:\n  - script: x\n    KEY: x\n"),
+        );
+        check_keys(
+            &schema,
+            "PythonTest",
+            &format!("{package}tests:\n  - python: {{}}\n    KEY: x\n"),
+        );
+        check_keys(
+            &schema,
+            "SingleOutputRecipe",
+            &format!("{package}KEY: {{}}\n"),
+        );
+        check_keys(
+            &schema,
+            "PackageOutput",
+            "outputs:\n  - package:\n      name: foo\n    KEY: {}\n",
+        );
+    }
+
+    #[test]
+    fn test_schema_structure() {
+        let schema = recipe_schema();
+        assert_eq!(
+            schema["$schema"],
+            "https://json-schema.org/draft/2020-12/schema"
+        );
+        // every reference points to a definition
+        let definitions = schema["$defs"].as_object().unwrap();
+        let text = schema.to_string();
+        for reference in text.split("\"#/$defs/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(definitions.contains_key(name), "{name} is not defined");
+        }
+        assert_eq!(
+            schema["$defs"]["Test"]["anyOf"].as_array().unwrap().len(),
+            7
+        );
+    }
+}
@@ -20,14 +20,168 @@ impl Display for LicenseParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "failed to parse SPDX license: '{}', because:\n{}\n\n\
-             See <https://spdx.org/licenses> for the list of valid licenses.\n\
-             Use 'LicenseRef-<MyLicense>' if you are using a custom license.",
+            "failed to parse SPDX license: '{}', because:\n{}\n\n",
             self.input, self.inner
+        )?;
+        let suggestions = suggest_licenses(&self.input);
+        if !suggestions.is_empty() {
+            writeln!(
+                f,
+                "Did you mean {}?",
+                suggestions.iter().map(|s| format!("'{s}'")).join(" or ")
+            )?;
+        }
+        write!(
+            f,
+            "See <https://spdx.org/licenses> for the list of valid licenses.\n\
+             Use 'LicenseRef-<MyLicense>' if you are using a custom license."
         )
     }
 }
 
+/// Common license names that are not SPDX identifiers, with the identifiers they usually mean
+const LICENSE_MISNOMERS: &[(&[&str], &[&str])] = &[
+    (&["bsd"], &["BSD-3-Clause", "BSD-2-Clause"]),
+    (
+        &[
+            "bsd3",
+            "bsd 3",
+            "bsd-3",
+            "bsd 3-clause",
+            "3-clause bsd",
+            "new bsd",
+        ],
+        &["BSD-3-Clause"],
+    ),
+    (
+        &[
+            "bsd2",
+            "bsd 2",
+            "bsd-2",
+            "bsd 2-clause",
+            "2-clause bsd",
+            "simplified bsd",
+        ],
+        &["BSD-2-Clause"],
+    ),
+    (
+        &[
+            "apache",
+            "apache 2",
+            "apache 2.0",
+            "apache-2",
+            "apache2",
+            "apache v2",
+            "asl 2.0",
+        ],
+        &["Apache-2.0"],
+    ),
+    (&["gpl"], &["GPL-3.0-or-later", "GPL-2.0-or-later"]),
+    (
+        &["gpl2", "gplv2", "gpl v2", "gpl-2", "gpl 2", "gpl-2.0"],
+        &["GPL-2.0-only", "GPL-2.0-or-later"],
+    ),
+    (
+        &["gpl3", "gplv3", "gpl v3", "gpl-3", "gpl 3", "gpl-3.0"],
+        &["GPL-3.0-only", "GPL-3.0-or-later"],
+    ),
+    (&["lgpl"], &["LGPL-3.0-or-later", "LGPL-2.1-or-later"]),
+    (
+        &["lgpl2", "lgplv2", "lgpl-2.1", "lgplv2.1", "lgpl 2.1"],
+        &["LGPL-2.1-only", "LGPL-2.1-or-later"],
+    ),
+    (
+        &["lgpl3", "lgplv3", "lgpl-3", "lgpl-3.0", "lgpl 3"],
+        &["LGPL-3.0-only", "LGPL-3.0-or-later"],
+    ),
+    (
+        &["agpl", "agpl3", "agplv3"],
+        &["AGPL-3.0-only", "AGPL-3.0-or-later"],
+    ),
+    (&["mpl", "mpl2", "mpl 2.0", "mpl-2"], &["MPL-2.0"]),
+    (&["psf", "python"], &["PSF-2.0"]),
+    (
+        &["public domain", "public-domain"],
+        &["Unlicense", "CC0-1.0", "LicenseRef-Public-Domain"],
+    ),
+    (&["proprietary"], &["LicenseRef-Proprietary"]),
+];
+
+/// Suggest SPDX license identifiers for a license that is not a valid SPDX expression, e.g.
+/// `Apache-2.0` for `Apache 2.0` or `MIT` for `MIT License`
+fn suggest_licenses(input: &str) -> Vec<&'static str> {
+    let normalized = input.trim().to_lowercase().replace('_', " ");
+    let normalized = normalized
+        .trim_end_matches(" license")
+        .trim_end_matches(" licence")
+        .trim();
+
+    if let Some((_, suggestions)) = LICENSE_MISNOMERS
+        .iter()
+        .find(|(names, _)| names.contains(&normalized))
+    {
+        return suggestions.to_vec();
+    }
+
+    // the identifier or the full name of a license in the wrong case
+    spdx::identifiers::LICENSES
+        .iter()
+        .find(|(id, full_name, _)| {
+            id.eq_ignore_ascii_case(input.trim())
+                || full_name.eq_ignore_ascii_case(input.trim())
+                || id.eq_ignore_ascii_case(normalized)
+        })
+        .map(|(id, _, _)| vec![*id])
+        .unwrap_or_default()
+}
+
+/// The conda license families, by the prefix of the SPDX identifiers that belong to them
+const LICENSE_FAMILIES: &[(&str, &str)] = &[
+    ("AGPL", "AGPL"),
+    ("LGPL", "LGPL"),
+    ("GPL-2", "GPL2"),
+    ("GPL-3", "GPL3"),
+    ("GPL", "GPL"),
+    ("0BSD", "BSD"),
+    ("BSD", "BSD"),
+    ("MIT", "MIT"),
+    ("APACHE", "APACHE"),
+    ("PSF", "PSF"),
+    ("PYTHON", "PSF"),
+    ("CC0", "CC"),
+    ("CC-", "CC"),
+    ("MPL", "MOZILLA"),
+    ("UNLICENSE", "PUBLIC-DOMAIN"),
+];
+
+impl License {
+    /// The conda license family (e.g. `BSD` or `GPL3`) of the first license of the expression,
+    /// for the `license_family` of recipes that do not set it
+    pub fn family(&self) -> Option<&'static str> {
+        let requirement = self.0.requirements().next()?;
+        let family = match &requirement.req.license {
+            spdx::LicenseItem::Spdx { id, .. } => {
+                let name = id.name.to_ascii_uppercase();
+                LICENSE_FAMILIES
+                    .iter()
+                    .find(|(prefix, _)| name.starts_with(prefix))
+                    .map_or("OTHER", |(_, family)| family)
+            }
+            spdx::LicenseItem::Other { lic_ref, .. } => {
+                let lic_ref = lic_ref.to_ascii_uppercase();
+                if lic_ref.contains("PROPRIETARY") {
+                    "PROPRIETARY"
+                } else if lic_ref.contains("PUBLIC-DOMAIN") || lic_ref.contains("PUBLICDOMAIN") {
+                    "PUBLIC-DOMAIN"
+                } else {
+                    "OTHER"
+                }
+            }
+        };
+        Some(family)
+    }
+}
+
 impl std::error::Error for LicenseParseError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         Some(&self.inner)
@@ -79,6 +79,22 @@ pub struct Build {
     /// Post-processing operations
     #[serde(default)]
     pub post_process: ConditionalList<PostProcess>,
+
+    /// A menuinst (v2) document that is rendered to `Menu/<package name>.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu: Option<serde_value::Value>,
+
+    /// How symlinks are packaged
+    #[serde(default, skip_serializing_if = "Symlinks::is_default")]
+    pub symlinks: Symlinks,
+
+    /// Strip executables and shared libraries
+    #[serde(default, skip_serializing_if = "Strip::is_default")]
+    pub strip: Strip,
+
+    /// Remove libtool archives and make config scripts relative to `${prefix}`
+    #[serde(default, skip_serializing_if = "AutotoolsCleanup::is_default")]
+    pub autotools_cleanup: AutotoolsCleanup,
 }
 
 impl Default for Build {
@@ -98,6 +114,10 @@ impl Default for Build {
             variant: VariantKeyUsage::default(),
             prefix_detection: PrefixDetection::default(),
             post_process: ConditionalList::default(),
+            menu: None,
+            symlinks: Symlinks::default(),
+            strip: Strip::default(),
+            autotools_cleanup: AutotoolsCleanup::default(),
         }
     }
 }
@@ -146,6 +166,43 @@ pub struct DynamicLinking {
     /// What to do when detecting overlinking
     #[serde(default)]
     pub overlinking_behavior: Option<Value<String>>,
+
+    /// Per-library overrides of `overlinking_behavior` (globs matching the linked library)
+    #[serde(default, skip_serializing_if = "LinkingCheckOverrides::is_default")]
+    pub overlinking_overrides: LinkingCheckOverrides,
+
+    /// Per-package overrides of `overdepending_behavior` (globs matching the package name)
+    #[serde(default, skip_serializing_if = "LinkingCheckOverrides::is_default")]
+    pub overdepending_overrides: LinkingCheckOverrides,
+
+    /// Whether to write `DT_RPATH` or `DT_RUNPATH` entries (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpath_type: Option<Value<String>>,
+
+    /// Libraries from the host prefix to copy into the package (glob patterns)
+    #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
+    pub vendor_libraries: ConditionalList<String>,
+}
+
+/// Overrides of a linking check behavior for specific libraries or packages
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct LinkingCheckOverrides {
+    /// Do not report these
+    #[serde(default)]
+    pub ignore: ConditionalList<String>,
+    /// Report these as warnings
+    #[serde(default)]
+    pub warn: ConditionalList<String>,
+    /// Fail the build for these
+    #[serde(default)]
+    pub error: ConditionalList<String>,
+}
+
+impl LinkingCheckOverrides {
+    /// Check if there are no overrides
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 /// Force file type configuration for prefix detection
@@ -187,6 +244,73 @@ pub struct PrefixDetection {
     /// Ignore binary files for prefix replacement (Unix only)
     #[serde(default)]
     pub ignore_binary_files: Value<bool>,
+    /// Length of the padded host prefix that binaries are built against (Unix only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder_length: Option<Value<u64>>,
+    /// What to do when packaged files contain paths into the build prefix or work directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_path_leaks: Option<Value<String>>,
+}
+
+/// Symlink packaging configuration
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Symlinks {
+    /// What to do with symlinks that match none of the globs below
+    /// (`preserve`, `dereference` or `error`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value<String>>,
+    /// Keep these symlinks as links
+    #[serde(default)]
+    pub preserve: ConditionalList<String>,
+    /// Replace these symlinks with a copy of the file they point to
+    #[serde(default)]
+    pub dereference: ConditionalList<String>,
+    /// Fail the build if any of these symlinks is found
+    #[serde(default)]
+    pub error: ConditionalList<String>,
+}
+
+impl Symlinks {
+    /// Check if this is the default configuration (preserve all symlinks)
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Binary stripping configuration (`strip: true` or `strip: { exclude: [...] }`)
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Strip {
+    /// Whether to strip binaries (defaults to true if `strip` is a mapping)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<Value<bool>>,
+    /// Do not strip these files
+    #[serde(default)]
+    pub exclude: ConditionalList<String>,
+}
+
+impl Strip {
+    /// Check if this is the default configuration (do not strip)
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Cleanup of autotools leftovers (`autotools_cleanup: false` disables all of it)
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct AutotoolsCleanup {
+    /// Remove libtool archives (`*.la`), defaults to true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remove_la_files: Option<Value<bool>>,
+    /// Rewrite pkg-config files and `*-config` scripts to use `${prefix}`, defaults to true
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_config_files: Option<Value<bool>>,
+}
+
+impl AutotoolsCleanup {
+    /// Check if this is the default configuration (clean up everything)
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
 }
 
 /// Post-processing operations using regex replacements
@@ -208,7 +332,7 @@ pub struct PythonBuild {
     pub entry_points: ConditionalList<EntryPoint>,
 
     /// Skip pyc compilation for these files (glob patterns)
-    /// Only relevant for non-noarch Python packages
+    /// For noarch Python packages only used together with `noarch_pyc_versions`
     #[serde(default)]
     pub skip_pyc_compilation: ConditionalList<String>,
 
@@ -224,6 +348,24 @@ pub struct PythonBuild {
     /// The relative site-packages path that a Python build exports for other packages to use
     /// This setting only makes sense for the `python` package itself
     pub site_packages_path: Option<Value<String>>,
+
+    /// GUI entry points (`gui_scripts`), launched without a console window on Windows
+    #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
+    pub gui_entry_points: ConditionalList<EntryPoint>,
+
+    /// How entry points are materialized on Windows (`launcher` or `script`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_point_style: Option<Value<String>>,
+
+    /// What to do when the declared entry points don't match the
+    /// `entry_points.txt` of the packaged dist-info (`ignore`, `warn` or `error`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_point_check: Option<Value<String>>,
+
+    /// The Python versions (e.g. `3.12`) to pre-compile `.pyc` files for.
+    /// Only relevant for noarch Python packages, if empty no `.pyc` files are packaged
+    #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
+    pub noarch_pyc_versions: ConditionalList<String>,
 }
 
 // Manual PartialEq implementation since EntryPoint doesn't implement PartialEq
@@ -236,6 +378,10 @@ impl PartialEq for PythonBuild {
             && self.use_python_app_entrypoint == other.use_python_app_entrypoint
             && self.version_independent.is_some() == other.version_independent.is_some()
             && self.site_packages_path == other.site_packages_path
+            && self.gui_entry_points.len() == other.gui_entry_points.len()
+            && self.entry_point_style == other.entry_point_style
+            && self.entry_point_check == other.entry_point_check
+            && self.noarch_pyc_versions == other.noarch_pyc_versions
     }
 }
 
@@ -277,6 +423,11 @@ impl Build {
             variant,
             prefix_detection,
             post_process,
+            // menu strings are rendered like `extra`, without tracking variables
+            menu: _,
+            symlinks,
+            strip,
+            autotools_cleanup,
         } = self;
 
         let mut vars = Vec::new();
@@ -318,6 +469,10 @@ impl Build {
             use_python_app_entrypoint,
             version_independent,
             site_packages_path,
+            gui_entry_points,
+            entry_point_style,
+            entry_point_check,
+            noarch_pyc_versions,
         } = python;
 
         vars.extend(entry_points.used_variables());
@@ -332,6 +487,18 @@ impl Build {
             vars.extend(site_packages_path.used_variables());
         }
 
+        vars.extend(gui_entry_points.used_variables());
+
+        if let Some(entry_point_style) = entry_point_style {
+            vars.extend(entry_point_style.used_variables());
+        }
+
+        if let Some(entry_point_check) = entry_point_check {
+            vars.extend(entry_point_check.used_variables());
+        }
+
+        vars.extend(noarch_pyc_versions.used_variables());
+
         vars.extend(always_copy_files.used_variables());
         vars.extend(always_include_files.used_variables());
         vars.extend(merge_build_and_host_envs.used_variables());
@@ -345,6 +512,10 @@ impl Build {
             rpath_allowlist,
             overdepending_behavior,
             overlinking_behavior,
+            overlinking_overrides,
+            overdepending_overrides,
+            rpath_type,
+            vendor_libraries,
         } = dynamic_linking;
 
         vars.extend(rpaths.used_variables());
@@ -362,6 +533,7 @@ impl Build {
 
         vars.extend(missing_dso_allowlist.used_variables());
         vars.extend(rpath_allowlist.used_variables());
+        vars.extend(vendor_libraries.used_variables());
 
         if let Some(overdepending_behavior) = overdepending_behavior {
             vars.extend(overdepending_behavior.used_variables());
@@ -371,6 +543,21 @@ impl Build {
             vars.extend(overlinking_behavior.used_variables());
         }
 
+        if let Some(rpath_type) = rpath_type {
+            vars.extend(rpath_type.used_variables());
+        }
+
+        for overrides in [overlinking_overrides, overdepending_overrides] {
+            let LinkingCheckOverrides {
+                ignore,
+                warn,
+                error,
+            } = overrides;
+            vars.extend(ignore.used_variables());
+            vars.extend(warn.used_variables());
+            vars.extend(error.used_variables());
+        }
+
         // Variant
         let VariantKeyUsage {
             use_keys,
@@ -390,6 +577,8 @@ impl Build {
             force_file_type,
             ignore,
             ignore_binary_files,
+            placeholder_length,
+            build_path_leaks,
         } = prefix_detection;
 
         let ForceFileType { text, binary } = force_file_type;
@@ -406,6 +595,46 @@ impl Build {
             }
         }
         vars.extend(ignore_binary_files.used_variables());
+        if let Some(placeholder_length) = placeholder_length {
+            vars.extend(placeholder_length.used_variables());
+        }
+        if let Some(build_path_leaks) = build_path_leaks {
+            vars.extend(build_path_leaks.used_variables());
+        }
+
+        // Symlinks
+        let Symlinks {
+            default,
+            preserve,
+            dereference,
+            error,
+        } = symlinks;
+
+        if let Some(default) = default {
+            vars.extend(default.used_variables());
+        }
+        vars.extend(preserve.used_variables());
+        vars.extend(dereference.used_variables());
+        vars.extend(error.used_variables());
+
+        // Strip
+        let Strip { enabled, exclude } = strip;
+        if let Some(enabled) = enabled {
+            vars.extend(enabled.used_variables());
+        }
+        vars.extend(exclude.used_variables());
+
+        // Autotools cleanup
+        let AutotoolsCleanup {
+            remove_la_files,
+            relative_config_files,
+        } = autotools_cleanup;
+        for value in [remove_la_files, relative_config_files]
+            .into_iter()
+            .flatten()
+        {
+            vars.extend(value.used_variables());
+        }
 
         // Post-process (handle conditional items)
         vars.extend(post_process.used_variables());
@@ -40,10 +40,12 @@ use crate::{
         Package as Stage0Package, Requirements as Stage0Requirements, Source as Stage0Source,
         Stage0Recipe, TestType as Stage0TestType,
         build::{
-            BinaryRelocation as Stage0BinaryRelocation, DynamicLinking as Stage0DynamicLinking,
-            ForceFileType as Stage0ForceFileType, PostProcess as Stage0PostProcess,
+            AutotoolsCleanup as Stage0AutotoolsCleanup, BinaryRelocation as Stage0BinaryRelocation,
+            DynamicLinking as Stage0DynamicLinking, ForceFileType as Stage0ForceFileType,
+            LinkingCheckOverrides as Stage0LinkingCheckOverrides, PostProcess as Stage0PostProcess,
             PrefixDetection as Stage0PrefixDetection, PrefixIgnore as Stage0PrefixIgnore,
-            PythonBuild as Stage0PythonBuild, VariantKeyUsage as Stage0VariantKeyUsage,
+            PythonBuild as Stage0PythonBuild, Strip as Stage0Strip, Symlinks as Stage0Symlinks,
+            VariantKeyUsage as Stage0VariantKeyUsage,
         },
         requirements::{
             IgnoreRunExports as Stage0IgnoreRunExports, RunExports as Stage0RunExports,
@@ -54,13 +56,15 @@ use crate::{
             UrlSource as Stage0UrlSource,
         },
         tests::{
-            CommandsTest as Stage0CommandsTest, CommandsTestFiles as Stage0CommandsTestFiles,
+            CommandsTest as Stage0CommandsTest, CommandsTestExpected as Stage0CommandsTestExpected,
+            CommandsTestFiles as Stage0CommandsTestFiles,
             CommandsTestRequirements as Stage0CommandsTestRequirements,
             DownstreamTest as Stage0DownstreamTest,
             PackageContentsCheckFiles as Stage0PackageContentsCheckFiles,
             PackageContentsTest as Stage0PackageContentsTest, PerlTest as Stage0PerlTest,
             PythonTest as Stage0PythonTest, PythonVersion as Stage0PythonVersion,
-            RTest as Stage0RTest, RubyTest as Stage0RubyTest,
+            RTest as Stage0RTest, RubyTest as Stage0RubyTest, TestLimits as Stage0TestLimits,
+            VirtualPackageMatrix as Stage0VirtualPackageMatrix,
         },
         types::{ConditionalList, Item, JinjaExpression, Value},
     },
@@ -69,9 +73,12 @@ use crate::{
         Extra as Stage1Extra, GlobVec, Package as Stage1Package, Recipe as Stage1Recipe,
         Requirements as Stage1Requirements, Rpaths,
         build::{
-            Build as Stage1Build, BuildString, DynamicLinking as Stage1DynamicLinking,
-            ForceFileType as Stage1ForceFileType, PostProcess as Stage1PostProcess,
+            AutotoolsCleanup as Stage1AutotoolsCleanup, Build as Stage1Build,
+            BuildPathLeaks as Stage1BuildPathLeaks, BuildString,
+            DynamicLinking as Stage1DynamicLinking, ForceFileType as Stage1ForceFileType,
+            LinkingCheckOverrides as Stage1LinkingCheckOverrides, PostProcess as Stage1PostProcess,
             PrefixDetection as Stage1PrefixDetection, PythonBuild as Stage1PythonBuild,
+            Strip as Stage1Strip, Symlinks as Stage1Symlinks,
             VariantKeyUsage as Stage1VariantKeyUsage,
         },
         requirements::{
@@ -83,13 +90,15 @@ use crate::{
             Source as Stage1Source, UrlSource as Stage1UrlSource, parse_publisher_string,
         },
         tests::{
-            CommandsTest as Stage1CommandsTest, CommandsTestFiles as Stage1CommandsTestFiles,
+            CommandsTest as Stage1CommandsTest, CommandsTestExpected as Stage1CommandsTestExpected,
+            CommandsTestFiles as Stage1CommandsTestFiles,
             CommandsTestRequirements as Stage1CommandsTestRequirements,
             DownstreamTest as Stage1DownstreamTest,
             PackageContentsCheckFiles as Stage1PackageContentsCheckFiles,
             PackageContentsTest as Stage1PackageContentsTest, PerlTest as Stage1PerlTest,
             PythonTest as Stage1PythonTest, PythonVersion as Stage1PythonVersion,
-            RTest as Stage1RTest, RubyTest as Stage1RubyTest, TestType as Stage1TestType,
+            RTest as Stage1RTest, RubyTest as Stage1RubyTest, TestLimits as Stage1TestLimits,
+            TestType as Stage1TestType, VirtualPackageMatrix as Stage1VirtualPackageMatrix,
         },
     },
 };
@@ -1537,58 +1546,6 @@ fn evaluate_md5(
     }
 }
 
-/// Macro to implement Evaluate for types that just evaluate string list fields
-///
-/// This handles the common pattern where a type contains one or more
-/// ConditionalList<String> fields that need to be evaluated to Vec<String>.
-///
-/// # Single field example:
-/// ```ignore
-/// impl_evaluate_list_fields! {
-///     Stage0PerlTest => Stage1PerlTest { uses }
-/// }
-/// ```
-///
-/// # Multiple fields example:
-/// ```ignore
-/// impl_evaluate_list_fields! {
-///     Stage0RunExports => Stage1RunExports {
-///         noarch,
-///         strong,
-///         weak
-///     }
-/// }
-/// ```
-macro_rules! impl_evaluate_list_fields {
-    // Single field case
-    ($stage0:ty => $stage1:ty { $field:ident }) => {
-        impl Evaluate for $stage0 {
-            type Output = $stage1;
-
-            fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
-                Ok(Self::Output {
-                    $field: evaluate_string_list(&self.$field, context)?,
-                })
-            }
-        }
-    };
-
-    // Multiple fields case
-    ($stage0:ty => $stage1:ty { $($field:ident),+ $(,)? }) => {
-        impl Evaluate for $stage0 {
-            type Output = $stage1;
-
-            fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
-                Ok(Self::Output {
-                    $(
-                        $field: evaluate_string_list(&self.$field, context)?,
-                    )+
-                })
-            }
-        }
-    };
-}
-
 impl Evaluate for Stage0Package {
     type Output = Stage1Package;
 
@@ -1754,6 +1711,8 @@ impl Evaluate for Stage0PythonBuild {
     type Output = Stage1PythonBuild;
 
     fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        use crate::stage1::build::{EntryPointCheck, EntryPointStyle};
+
         let skip_pyc_compilation = evaluate_glob_vec_simple(&self.skip_pyc_compilation, context)?;
 
         // Evaluate version_independent (supports Jinja templates)
@@ -1772,12 +1731,81 @@ impl Evaluate for Stage0PythonBuild {
             false,
         )?;
 
+        let entry_point_style = match &self.entry_point_style {
+            None => EntryPointStyle::Launcher,
+            Some(v) => {
+                let s = evaluate_value_to_string(v, context)?;
+                match s.as_str() {
+                    "launcher" => EntryPointStyle::Launcher,
+                    "script" => EntryPointStyle::Script,
+                    _ => {
+                        return Err(ParseError::invalid_value(
+                            "entry_point_style",
+                            format!(
+                                "Invalid entry_point_style '{}'. Expected 'launcher' or 'script'",
+                                s
+                            ),
+                            Span::new_blank(),
+                        ));
+                    }
+                }
+            }
+        };
+
+        let entry_point_check = match &self.entry_point_check {
+            None => EntryPointCheck::Warn,
+            Some(v) => {
+                let s = evaluate_value_to_string(v, context)?;
+                match s.as_str() {
+                    "ignore" => EntryPointCheck::Ignore,
+                    "warn" => EntryPointCheck::Warn,
+                    "error" => EntryPointCheck::Error,
+                    _ => {
+                        return Err(ParseError::invalid_value(
+                            "entry_point_check",
+                            format!(
+                                "Invalid entry_point_check '{}'. Expected 'ignore', 'warn' or 'error'",
+                                s
+                            ),
+                            Span::new_blank(),
+                        ));
+                    }
+                }
+            }
+        };
+
+        let noarch_pyc_versions = evaluate_string_list(&self.noarch_pyc_versions, context)?;
+        for version in &noarch_pyc_versions {
+            let valid = version.split_once('.').is_some_and(|(major, minor)| {
+                !major.is_empty()
+                    && !minor.is_empty()
+                    && major
+                        .chars()
+                        .chain(minor.chars())
+                        .all(|c| c.is_ascii_digit())
+            });
+            if !valid {
+                return Err(ParseError::invalid_value(
+                    "noarch_pyc_versions",
+                    format!(
+                        "Invalid Python version '{}'. Expected a major and minor version like '3.12'",
+                        version
+                    ),
+                    Span::new_blank(),
+                ));
+            }
+        }
+
         Ok(Stage1PythonBuild {
             entry_points: evaluate_entry_point_list(&self.entry_points, context)?,
             skip_pyc_compilation,
             use_python_app_entrypoint,
             version_independent,
             site_packages_path: evaluate_optional_string_value(&self.site_packages_path, context)?,
+            gui_entry_points: evaluate_entry_point_list(&self.gui_entry_points, context)?,
+            entry_point_style,
+            entry_point_check,
+            noarch_pyc_versions,
         })
     }
 }
@@ -1866,10 +1894,123 @@ impl Evaluate for Stage0PrefixDetection {
             false,
         )?;
 
+        let placeholder_length = match &self.placeholder_length {
+            None => None,
+            Some(val) => {
+                let s = evaluate_value_to_string(val, context)?;
+                Some(s.parse::<u64>().map_err(|_| {
+                    ParseError::invalid_value(
+                        "prefix_detection.placeholder_length",
+                        format!(
+                            "Invalid integer value for prefix_detection.placeholder_length: '{}'",
+                            s
+                        ),
+                        val.span().copied().unwrap_or_else(Span::new_blank),
+                    )
+                })?)
+            }
+        };
+
+        let build_path_leaks = match &self.build_path_leaks {
+            None => Stage1BuildPathLeaks::default(),
+            Some(v) => {
+                let s = evaluate_value_to_string(v, context)?;
+                match s.as_str() {
+                    "ignore" => Stage1BuildPathLeaks::Ignore,
+                    "warn" => Stage1BuildPathLeaks::Warn,
+                    "error" => Stage1BuildPathLeaks::Error,
+                    _ => {
+                        return Err(ParseError::invalid_value(
+                            "prefix_detection.build_path_leaks",
+                            format!(
+                                "Invalid build_path_leaks '{}'. Expected 'ignore', 'warn' or 'error'",
+                                s
+                            ),
+                            v.span().copied().unwrap_or_else(Span::new_blank),
+                        ));
+                    }
+                }
+            }
+        };
+
         Ok(Stage1PrefixDetection {
             force_file_type: self.force_file_type.evaluate(context)?,
             ignore,
             ignore_binary_files,
+            placeholder_length,
+            build_path_leaks,
+        })
+    }
+}
+
+impl Evaluate for Stage0Symlinks {
+    type Output = Stage1Symlinks;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        use crate::stage1::build::SymlinkBehavior;
+
+        let default = match &self.default {
+            None => SymlinkBehavior::Preserve,
+            Some(v) => {
+                let s = evaluate_value_to_string(v, context)?;
+                match s.as_str() {
+                    "preserve" => SymlinkBehavior::Preserve,
+                    "dereference" => SymlinkBehavior::Dereference,
+                    "error" => SymlinkBehavior::Error,
+                    _ => {
+                        return Err(ParseError::invalid_value(
+                            "symlinks.default",
+                            format!(
+                                "Invalid symlink behavior '{}'. Expected 'preserve', 'dereference' or 'error'",
+                                s
+                            ),
+                            Span::new_blank(),
+                        ));
+                    }
+                }
+            }
+        };
+
+        Ok(Stage1Symlinks {
+            default,
+            preserve: evaluate_glob_vec_simple(&self.preserve, context)?,
+            dereference: evaluate_glob_vec_simple(&self.dereference, context)?,
+            error: evaluate_glob_vec_simple(&self.error, context)?,
+        })
+    }
+}
+
+impl Evaluate for Stage0Strip {
+    type Output = Stage1Strip;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        let enabled = match &self.enabled {
+            None => false,
+            Some(v) => evaluate_bool_value(v, context, "strip", false)?,
+        };
+
+        Ok(Stage1Strip {
+            enabled,
+            exclude: evaluate_glob_vec_simple(&self.exclude, context)?,
+        })
+    }
+}
+
+impl Evaluate for Stage0AutotoolsCleanup {
+    type Output = Stage1AutotoolsCleanup;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        let evaluate = |value: &Option<Value<bool>>, field_name: &str| match value {
+            None => Ok(true),
+            Some(v) => evaluate_bool_value(v, context, field_name, true),
+        };
+
+        Ok(Stage1AutotoolsCleanup {
+            remove_la_files: evaluate(&self.remove_la_files, "autotools_cleanup.remove_la_files")?,
+            relative_config_files: evaluate(
+                &self.relative_config_files,
+                "autotools_cleanup.relative_config_files",
+            )?,
         })
     }
 }
@@ -1900,7 +2041,7 @@ impl Evaluate for Stage0DynamicLinking {
     type Output = Stage1DynamicLinking;
 
     fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
-        use crate::stage1::build::LinkingCheckBehavior;
+        use crate::stage1::build::{LinkingCheckBehavior, RpathType};
 
         // Evaluate binary_relocation
         let binary_relocation = match &self.binary_relocation {
@@ -1939,20 +2080,22 @@ impl Evaluate for Stage0DynamicLinking {
         // Evaluate and validate glob patterns
         let missing_dso_allowlist = evaluate_glob_vec_simple(&self.missing_dso_allowlist, context)?;
         let rpath_allowlist = evaluate_glob_vec_simple(&self.rpath_allowlist, context)?;
+        let vendor_libraries = evaluate_glob_vec_simple(&self.vendor_libraries, context)?;
 
         // Parse overdepending_behavior
         let overdepending_behavior = match &self.overdepending_behavior {
-            None => LinkingCheckBehavior::Ignore,
+            None => LinkingCheckBehavior::default(),
             Some(v) => {
                 let s = evaluate_value_to_string(v, context)?;
                 match s.as_str() {
                     "ignore" => LinkingCheckBehavior::Ignore,
+                    "warn" => LinkingCheckBehavior::Warn,
                     "error" => LinkingCheckBehavior::Error,
                     _ => {
                         return Err(ParseError::invalid_value(
                             "overdepending_behavior",
                             format!(
-                                "Invalid overdepending_behavior '{}'. Expected 'ignore' or 'error'",
+                                "Invalid overdepending_behavior '{}'. Expected 'ignore', 'warn' or 'error'",
                                 s
                             ),
                             Span::new_blank(),
@@ -1964,17 +2107,18 @@ impl Evaluate for Stage0DynamicLinking {
 
         // Parse overlinking_behavior
         let overlinking_behavior = match &self.overlinking_behavior {
-            None => LinkingCheckBehavior::Ignore,
+            None => LinkingCheckBehavior::default(),
             Some(v) => {
                 let s = evaluate_value_to_string(v, context)?;
                 match s.as_str() {
                     "ignore" => LinkingCheckBehavior::Ignore,
+                    "warn" => LinkingCheckBehavior::Warn,
                     "error" => LinkingCheckBehavior::Error,
                     _ => {
                         return Err(ParseError::invalid_value(
                             "overlinking_behavior",
                             format!(
-                                "Invalid overlinking_behavior '{}'. Expected 'ignore' or 'error'",
+                                "Invalid overlinking_behavior '{}'. Expected 'ignore', 'warn' or 'error'",
                                 s
                             ),
                             Span::new_blank(),
@@ -1984,6 +2128,25 @@ impl Evaluate for Stage0DynamicLinking {
             }
         };
 
+        // Parse rpath_type
+        let rpath_type = match &self.rpath_type {
+            None => RpathType::default(),
+            Some(v) => {
+                let s = evaluate_value_to_string(v, context)?;
+                match s.as_str() {
+                    "rpath" => RpathType::Rpath,
+                    "runpath" => RpathType::Runpath,
+                    _ => {
+                        return Err(ParseError::invalid_value(
+                            "rpath_type",
+                            format!("Invalid rpath_type '{}'. Expected 'rpath' or 'runpath'", s),
+                            v.span().copied().unwrap_or_else(Span::new_blank),
+                        ));
+                    }
+                }
+            }
+        };
+
         Ok(Stage1DynamicLinking {
             rpaths: Rpaths::new(evaluate_string_list(&self.rpaths, context)?),
             binary_relocation,
@@ -1991,6 +2154,22 @@ impl Evaluate for Stage0DynamicLinking {
             rpath_allowlist,
             overdepending_behavior,
             overlinking_behavior,
+            overlinking_overrides: self.overlinking_overrides.evaluate(context)?,
+            overdepending_overrides: self.overdepending_overrides.evaluate(context)?,
+            rpath_type,
+            vendor_libraries,
+        })
+    }
+}
+
+impl Evaluate for Stage0LinkingCheckOverrides {
+    type Output = Stage1LinkingCheckOverrides;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        Ok(Stage1LinkingCheckOverrides {
+            ignore: evaluate_glob_vec_simple(&self.ignore, context)?,
+            warn: evaluate_glob_vec_simple(&self.warn, context)?,
+            error: evaluate_glob_vec_simple(&self.error, context)?,
         })
     }
 }
@@ -2133,6 +2312,14 @@ impl Evaluate for Stage0Build {
             variant,
             prefix_detection,
             post_process,
+            menu: self
+                .menu
+                .as_ref()
+                .map(|menu| evaluate_serde_value(menu, context))
+                .transpose()?,
+            symlinks: self.symlinks.evaluate(context)?,
+            strip: self.strip.evaluate(context)?,
+            autotools_cleanup: self.autotools_cleanup.evaluate(context)?,
         })
     }
 }
@@ -2470,14 +2657,82 @@ impl Evaluate for Stage0PythonTest {
             imports,
             pip_check,
             python_version,
+            limits: self.limits.evaluate(context)?,
+            virtual_packages: self.virtual_packages.evaluate(context)?,
         })
     }
 }
 
-// Use macro for simple list field evaluations
-impl_evaluate_list_fields!(Stage0PerlTest => Stage1PerlTest { uses });
-impl_evaluate_list_fields!(Stage0RTest => Stage1RTest { libraries });
-impl_evaluate_list_fields!(Stage0RubyTest => Stage1RubyTest { requires });
+impl Evaluate for Stage0TestLimits {
+    type Output = Stage1TestLimits;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        Ok(Stage1TestLimits {
+            timeout: evaluate_optional_value(&self.timeout, context, "timeout")?,
+            retries: evaluate_optional_value(&self.retries, context, "retries")?.unwrap_or(0),
+        })
+    }
+}
+
+impl Evaluate for Stage0VirtualPackageMatrix {
+    type Output = Stage1VirtualPackageMatrix;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        let mut virtual_packages = Vec::with_capacity(self.0.len());
+        for overrides in &self.0 {
+            let mut evaluated = indexmap::IndexMap::new();
+            for (name, version) in overrides {
+                let version = evaluate_string_value(version, context)?;
+                if let Err(e) = rattler_conda_types::Version::from_str(&version) {
+                    return Err(ParseError::invalid_value(
+                        "virtual_packages",
+                        format!("Invalid version '{}' for {}: {}", version, name, e),
+                        Span::new_blank(),
+                    ));
+                }
+                evaluated.insert(name.clone(), version);
+            }
+            virtual_packages.push(evaluated);
+        }
+        Ok(Stage1VirtualPackageMatrix(virtual_packages))
+    }
+}
+
+impl Evaluate for Stage0PerlTest {
+    type Output = Stage1PerlTest;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        Ok(Stage1PerlTest {
+            uses: evaluate_string_list(&self.uses, context)?,
+            limits: self.limits.evaluate(context)?,
+            virtual_packages: self.virtual_packages.evaluate(context)?,
+        })
+    }
+}
+
+impl Evaluate for Stage0RTest {
+    type Output = Stage1RTest;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        Ok(Stage1RTest {
+            libraries: evaluate_string_list(&self.libraries, context)?,
+            limits: self.limits.evaluate(context)?,
+            virtual_packages: self.virtual_packages.evaluate(context)?,
+        })
+    }
+}
+
+impl Evaluate for Stage0RubyTest {
+    type Output = Stage1RubyTest;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        Ok(Stage1RubyTest {
+            requires: evaluate_string_list(&self.requires, context)?,
+            limits: self.limits.evaluate(context)?,
+            virtual_packages: self.virtual_packages.evaluate(context)?,
+        })
+    }
+}
 
 impl Evaluate for Stage0CommandsTestRequirements {
     type Output = Stage1CommandsTestRequirements;
@@ -2495,8 +2750,31 @@ impl Evaluate for Stage0CommandsTestFiles {
 
     fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
         Ok(Stage1CommandsTestFiles {
-            source: evaluate_glob_vec_simple(&self.source, context)?,
-            recipe: evaluate_glob_vec_simple(&self.recipe, context)?,
+            source: evaluate_glob_vec(&self.source, context)?,
+            recipe: evaluate_glob_vec(&self.recipe, context)?,
+        })
+    }
+}
+
+impl Evaluate for Stage0CommandsTestExpected {
+    type Output = Stage1CommandsTestExpected;
+
+    fn evaluate(&self, context: &EvaluationContext) -> Result<Self::Output, ParseError> {
+        let stdout_regex = evaluate_string_list(&self.stdout_regex, context)?;
+        for pattern in &stdout_regex {
+            regex::Regex::new(pattern).map_err(|e| {
+                ParseError::invalid_value(
+                    "stdout_regex",
+                    format!("Invalid regular expression: {}", e),
+                    Span::new_blank(),
+                )
+            })?;
+        }
+
+        Ok(Stage1CommandsTestExpected {
+            exit_code: evaluate_optional_value(&self.exit_code, context, "exit_code")?,
+            stdout: evaluate_string_list(&self.stdout, context)?,
+            stdout_regex,
         })
     }
 }
@@ -2508,11 +2786,15 @@ impl Evaluate for Stage0CommandsTest {
         let script = evaluate_script(&self.script, context)?;
         let requirements = evaluate_optional_with_default(&self.requirements, context)?;
         let files = evaluate_optional_with_default(&self.files, context)?;
+        let expected = evaluate_optional_with_default(&self.expected, context)?;
 
         Ok(Stage1CommandsTest {
             script,
             requirements,
             files,
+            expected,
+            limits: self.limits.evaluate(context)?,
+            virtual_packages: self.virtual_packages.evaluate(context)?,
         })
     }
 }
@@ -2947,6 +3229,30 @@ fn merge_stage1_build(
         output.post_process
     };
 
+    // Menu: never inherited, otherwise every output would install the same shortcuts
+    let menu = output.menu;
+
+    // Symlinks: use output if not default, otherwise inherit from top-level
+    let symlinks = if output.symlinks.is_default() {
+        toplevel.symlinks
+    } else {
+        output.symlinks
+    };
+
+    // Strip: use output if not default, otherwise inherit from top-level
+    let strip = if output.strip.is_default() {
+        toplevel.strip
+    } else {
+        output.strip
+    };
+
+    // Autotools cleanup: use output if not default, otherwise inherit from top-level
+    let autotools_cleanup = if output.autotools_cleanup.is_default() {
+        toplevel.autotools_cleanup
+    } else {
+        output.autotools_cleanup
+    };
+
     stage1::Build {
         script,
         number,
@@ -2962,6 +3268,10 @@ fn merge_stage1_build(
         variant,
         prefix_detection,
         post_process,
+        menu,
+        symlinks,
+        strip,
+        autotools_cleanup,
     }
 }
 
@@ -4257,6 +4567,243 @@ outputs:
         }
     }
 
+    #[test]
+    fn test_evaluate_test_limits() {
+        use crate::stage0::parser::parse_recipe_or_multi_from_source;
+        use crate::stage1::tests::TestLimits;
+
+        let recipe_yaml = r#"
+context:
+  retries: 3
+
+package:
+  name: mypackage
+  version: 1.0.0
+
+tests:
+  - script:
+      - ./gui_test.sh
+    timeout: 60
+    retries: ${{ retries }}
+  - python:
+      imports:
+        - mypackage
+    timeout: 120
+  - perl:
+      uses:
+        - JSON
+"#;
+
+        let parsed = parse_recipe_or_multi_from_source(recipe_yaml).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        let recipe = recipe.evaluate(&EvaluationContext::new()).unwrap();
+
+        let Stage1TestType::Commands(commands) = &recipe.tests[0] else {
+            panic!("Expected script test");
+        };
+        assert_eq!(
+            commands.limits,
+            TestLimits {
+                timeout: Some(60),
+                retries: 3
+            }
+        );
+        let Stage1TestType::Python { python } = &recipe.tests[1] else {
+            panic!("Expected python test");
+        };
+        assert_eq!(
+            python.limits,
+            TestLimits {
+                timeout: Some(120),
+                retries: 0
+            }
+        );
+        let Stage1TestType::Perl { perl } = &recipe.tests[2] else {
+            panic!("Expected perl test");
+        };
+        assert_eq!(perl.limits, TestLimits::default());
+
+        // unknown fields next to the test type are rejected
+        let invalid = recipe_yaml.replace("timeout: 120", "timeuot: 120");
+        assert!(parse_recipe_or_multi_from_source(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_virtual_packages_matrix() {
+        use crate::stage0::parser::parse_recipe_or_multi_from_source;
+
+        let recipe_yaml = r#"
+context:
+  min_glibc: "2.17"
+
+package:
+  name: mypackage
+  version: 1.0.0
+
+tests:
+  - script:
+      - mytool --version
+    virtual_packages:
+      - __glibc: ${{ min_glibc }}
+      - __glibc: "2.28"
+        __cuda: "12.0"
+  - python:
+      imports:
+        - mypackage
+    virtual_packages:
+      - __osx: "10.13"
+"#;
+
+        let parsed = parse_recipe_or_multi_from_source(recipe_yaml).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        let evaluated = recipe.evaluate(&EvaluationContext::new()).unwrap();
+        let Stage1TestType::Commands(commands) = &evaluated.tests[0] else {
+            panic!("Expected script test");
+        };
+        assert_eq!(
+            commands.virtual_packages.0,
+            vec![
+                indexmap::IndexMap::from([("__glibc".to_string(), "2.17".to_string())]),
+                indexmap::IndexMap::from([
+                    ("__glibc".to_string(), "2.28".to_string()),
+                    ("__cuda".to_string(), "12.0".to_string()),
+                ]),
+            ]
+        );
+        // the matrix is separate from the timeout and retries
+        assert_eq!(commands.limits, Stage1TestLimits::default());
+        let Stage1TestType::Python { python } = &evaluated.tests[1] else {
+            panic!("Expected python test");
+        };
+        assert_eq!(
+            python.virtual_packages.0,
+            vec![indexmap::IndexMap::from([(
+                "__osx".to_string(),
+                "10.13".to_string()
+            )])]
+        );
+
+        // only virtual packages can be overridden
+        let invalid = recipe_yaml.replace("__cuda", "cuda");
+        assert!(parse_recipe_or_multi_from_source(&invalid).is_err());
+
+        // the versions are validated
+        let invalid = recipe_yaml.replace("\"2.28\"", "\"2.28..\"");
+        let parsed = parse_recipe_or_multi_from_source(&invalid).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        assert!(recipe.evaluate(&EvaluationContext::new()).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_commands_test_expected() {
+        use crate::stage0::parser::parse_recipe_or_multi_from_source;
+        use crate::stage1::tests::CommandsTestExpected;
+
+        let recipe_yaml = r#"
+context:
+  version: 1.2.3
+
+package:
+  name: mytool
+  version: ${{ version }}
+
+tests:
+  - script:
+      - mytool --version
+    expected:
+      stdout:
+        - mytool ${{ version }}
+      stdout_regex:
+        - ^mytool \d+\.\d+
+  - script:
+      - mytool --invalid
+    expected:
+      exit_code: 2
+"#;
+
+        let parsed = parse_recipe_or_multi_from_source(recipe_yaml).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        let recipe = recipe.evaluate(&EvaluationContext::new()).unwrap();
+
+        let Stage1TestType::Commands(commands) = &recipe.tests[0] else {
+            panic!("Expected script test");
+        };
+        assert_eq!(
+            commands.expected,
+            CommandsTestExpected {
+                exit_code: None,
+                stdout: vec!["mytool 1.2.3".to_string()],
+                stdout_regex: vec![r"^mytool \d+\.\d+".to_string()],
+            }
+        );
+        let Stage1TestType::Commands(commands) = &recipe.tests[1] else {
+            panic!("Expected script test");
+        };
+        assert_eq!(commands.expected.exit_code, Some(2));
+
+        // invalid regular expressions are rejected
+        let invalid = recipe_yaml.replace(r"^mytool \d+\.\d+", "^mytool (");
+        let parsed = parse_recipe_or_multi_from_source(&invalid).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        assert!(recipe.evaluate(&EvaluationContext::new()).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_commands_test_files() {
+        use crate::stage0::parser::parse_recipe_or_multi_from_source;
+
+        let recipe_yaml = r#"
+package:
+  name: mytool
+  version: 1.0.0
+
+tests:
+  - script:
+      - pytest tests/
+    files:
+      source:
+        include:
+          - tests/
+        exclude:
+          - tests/data/**
+          - "**/*.bin"
+      recipe:
+        - run_test.py
+"#;
+
+        let parsed = parse_recipe_or_multi_from_source(recipe_yaml).unwrap();
+        let stage0::Recipe::SingleOutput(recipe) = parsed else {
+            panic!("Expected SingleOutputRecipe");
+        };
+        let recipe = recipe.evaluate(&EvaluationContext::new()).unwrap();
+
+        let Stage1TestType::Commands(commands) = &recipe.tests[0] else {
+            panic!("Expected script test");
+        };
+        let source = &commands.files.source;
+        assert_eq!(source.include_globs().len(), 1);
+        assert_eq!(source.exclude_globs().len(), 2);
+        assert!(source.is_match(std::path::Path::new("tests/test_foo.py")));
+        assert!(!source.is_match(std::path::Path::new("tests/data/large.tar")));
+        assert!(!source.is_match(std::path::Path::new("tests/model.bin")));
+        assert_eq!(commands.files.recipe.include_globs().len(), 1);
+        assert!(commands.files.recipe.exclude_globs().is_empty());
+
+        // unknown keys in the include/exclude mapping are rejected
+        let invalid = recipe_yaml.replace("exclude:", "skip:");
+        assert!(parse_recipe_or_multi_from_source(&invalid).is_err());
+    }
+
     #[test]
     fn test_multi_output_tests_inheritance() {
         use crate::stage0::parser::parse_recipe_or_multi_from_source;
@@ -238,4 +238,44 @@ mod tests {
         assert!(about.summary.is_some());
         assert!(about.repository.is_none());
     }
+
+    #[test]
+    fn test_parse_about_license_suggestions() {
+        for (license, suggestion) in [
+            ("Apache 2.0", "'Apache-2.0'"),
+            ("BSD", "'BSD-3-Clause' or 'BSD-2-Clause'"),
+            ("MIT License", "'MIT'"),
+            ("mit", "'MIT'"),
+        ] {
+            let yaml = parse_yaml_about(&format!("\n  license: {license}"));
+            let err = parse_about(&yaml).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("Did you mean {suggestion}?")),
+                "{license}: {err}"
+            );
+        }
+
+        let yaml = parse_yaml_about("\n  license: Totally Custom");
+        let err = parse_about(&yaml).unwrap_err().to_string();
+        assert!(!err.contains("Did you mean"), "{err}");
+    }
+
+    #[test]
+    fn test_license_family() {
+        for (license, family) in [
+            ("MIT", "MIT"),
+            ("BSD-3-Clause", "BSD"),
+            ("Apache-2.0 OR MIT", "APACHE"),
+            ("GPL-3.0-or-later", "GPL3"),
+            ("GPL-2.0-only WITH Classpath-exception-2.0", "GPL2"),
+            ("LGPL-2.1-only", "LGPL"),
+            ("MPL-2.0", "MOZILLA"),
+            ("CC0-1.0", "CC"),
+            ("LicenseRef-Proprietary", "PROPRIETARY"),
+            ("Zlib", "OTHER"),
+        ] {
+            let license: License = license.parse().unwrap();
+            assert_eq!(license.family(), Some(family), "{license}");
+        }
+    }
 }
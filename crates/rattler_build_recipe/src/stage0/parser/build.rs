@@ -5,10 +5,11 @@ use rattler_conda_types::NoArchType;
 use crate::stage0::{
     Conditional, ConditionalList, Item, JinjaExpression, NestedItemList,
     build::{
-        BinaryRelocation, Build, DynamicLinking, ForceFileType, PostProcess, PrefixDetection,
-        PrefixIgnore, PythonBuild, VariantKeyUsage,
+        AutotoolsCleanup, BinaryRelocation, Build, DynamicLinking, ForceFileType,
+        LinkingCheckOverrides, PostProcess, PrefixDetection, PrefixIgnore, PythonBuild, Strip,
+        Symlinks, VariantKeyUsage,
     },
-    parser::helpers::get_span,
+    parser::{extra::node_to_yaml_value, helpers::get_span},
     types::{IncludeExclude, Value},
 };
 use rattler_build_yaml_parser::{
@@ -453,10 +454,30 @@ fn parse_build_from_mapping(mapping: &MarkedMappingNode) -> Result<Build, ParseE
             "post_process" => {
                 build.post_process = parse_post_process_list(value_node)?;
             }
+            "symlinks" => {
+                build.symlinks = parse_symlinks(value_node)?;
+            }
+            "strip" => {
+                build.strip = parse_strip(value_node)?;
+            }
+            "autotools_cleanup" => {
+                build.autotools_cleanup = parse_autotools_cleanup(value_node)?;
+            }
+            "menu" => {
+                if value_node.as_mapping().is_none() {
+                    return Err(ParseError::expected_type(
+                        "mapping",
+                        "non-mapping",
+                        get_span(value_node),
+                    )
+                    .with_message("Expected 'menu' to be a menuinst mapping"));
+                }
+                build.menu = Some(node_to_yaml_value(value_node));
+            }
             _ => {
                 return Err(
                     ParseError::invalid_value("build", format!("unknown field '{}'", key), *key_node.span())
-                        .with_suggestion("Valid fields are: number, string, script, noarch, python, skip, always_copy_files, always_include_files, merge_build_and_host_envs, files, dynamic_linking, variant, prefix_detection, post_process")
+                        .with_suggestion("Valid fields are: number, string, script, noarch, python, skip, always_copy_files, always_include_files, merge_build_and_host_envs, files, dynamic_linking, variant, prefix_detection, post_process, menu, symlinks, strip, autotools_cleanup")
                 );
             }
         }
@@ -510,10 +531,25 @@ fn parse_dynamic_linking(node: &Node) -> Result<DynamicLinking, ParseError> {
                     value_node
                 ));
             }
+            "overlinking_overrides" => {
+                dynamic_linking.overlinking_overrides =
+                    parse_linking_check_overrides(value_node, "overlinking_overrides")?;
+            }
+            "overdepending_overrides" => {
+                dynamic_linking.overdepending_overrides =
+                    parse_linking_check_overrides(value_node, "overdepending_overrides")?;
+            }
+            "rpath_type" => {
+                dynamic_linking.rpath_type =
+                    Some(parse_field!("dynamic_linking.rpath_type", value_node));
+            }
+            "vendor_libraries" => {
+                dynamic_linking.vendor_libraries = parse_conditional_list(value_node)?;
+            }
             _ => {
                 return Err(
                     ParseError::invalid_value("dynamic_linking", format!("unknown field '{}'", key), *key_node.span())
-                        .with_suggestion("Valid fields are: rpaths, binary_relocation, missing_dso_allowlist, rpath_allowlist, overdepending_behavior, overlinking_behavior")
+                        .with_suggestion("Valid fields are: rpaths, binary_relocation, missing_dso_allowlist, rpath_allowlist, overdepending_behavior, overlinking_behavior, overlinking_overrides, overdepending_overrides, rpath_type, vendor_libraries")
                 );
             }
         }
@@ -522,6 +558,44 @@ fn parse_dynamic_linking(node: &Node) -> Result<DynamicLinking, ParseError> {
     Ok(dynamic_linking)
 }
 
+fn parse_linking_check_overrides(
+    node: &Node,
+    field: &str,
+) -> Result<LinkingCheckOverrides, ParseError> {
+    let mapping = node.as_mapping().ok_or_else(|| {
+        ParseError::expected_type("mapping", "non-mapping", get_span(node))
+            .with_message(format!("Expected '{}' to be a mapping", field))
+    })?;
+
+    let mut overrides = LinkingCheckOverrides::default();
+
+    for (key_node, value_node) in mapping.iter() {
+        let key = key_node.as_str();
+
+        match key {
+            "ignore" => {
+                overrides.ignore = parse_conditional_list(value_node)?;
+            }
+            "warn" => {
+                overrides.warn = parse_conditional_list(value_node)?;
+            }
+            "error" => {
+                overrides.error = parse_conditional_list(value_node)?;
+            }
+            _ => {
+                return Err(ParseError::invalid_value(
+                    field,
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion("Valid fields are: ignore, warn, error"));
+            }
+        }
+    }
+
+    Ok(overrides)
+}
+
 fn parse_python_build(node: &Node) -> Result<PythonBuild, ParseError> {
     let mapping = node.as_mapping().ok_or_else(|| {
         ParseError::expected_type("mapping", "non-mapping", get_span(node))
@@ -552,10 +626,24 @@ fn parse_python_build(node: &Node) -> Result<PythonBuild, ParseError> {
                 python.site_packages_path =
                     Some(parse_field!("python.site_packages_path", value_node));
             }
+            "gui_entry_points" => {
+                python.gui_entry_points = parse_conditional_list(value_node)?;
+            }
+            "entry_point_style" => {
+                python.entry_point_style =
+                    Some(parse_field!("python.entry_point_style", value_node));
+            }
+            "entry_point_check" => {
+                python.entry_point_check =
+                    Some(parse_field!("python.entry_point_check", value_node));
+            }
+            "noarch_pyc_versions" => {
+                python.noarch_pyc_versions = parse_conditional_list(value_node)?;
+            }
             _ => {
                 return Err(
                     ParseError::invalid_value("python", format!("unknown field '{}'", key), *key_node.span())
-                        .with_suggestion("Valid fields are: entry_points, skip_pyc_compilation, use_python_app_entrypoint, version_independent, site_packages_path")
+                        .with_suggestion("Valid fields are: entry_points, gui_entry_points, entry_point_style, entry_point_check, skip_pyc_compilation, use_python_app_entrypoint, version_independent, site_packages_path, noarch_pyc_versions")
                 );
             }
         }
@@ -665,6 +753,18 @@ fn parse_prefix_detection(node: &Node) -> Result<PrefixDetection, ParseError> {
                 prefix_detection.ignore_binary_files =
                     parse_bool_value(value_node, "ignore_binary_files")?;
             }
+            "placeholder_length" => {
+                prefix_detection.placeholder_length = Some(parse_field!(
+                    "prefix_detection.placeholder_length",
+                    value_node
+                ));
+            }
+            "build_path_leaks" => {
+                prefix_detection.build_path_leaks = Some(parse_field!(
+                    "prefix_detection.build_path_leaks",
+                    value_node
+                ));
+            }
             _ => {
                 return Err(ParseError::invalid_value(
                     "prefix_detection",
@@ -672,7 +772,7 @@ fn parse_prefix_detection(node: &Node) -> Result<PrefixDetection, ParseError> {
                     *key_node.span(),
                 )
                 .with_suggestion(
-                    "Valid fields are: force_file_type, ignore, ignore_binary_files",
+                    "Valid fields are: force_file_type, ignore, ignore_binary_files, placeholder_length, build_path_leaks",
                 ));
             }
         }
@@ -681,6 +781,134 @@ fn parse_prefix_detection(node: &Node) -> Result<PrefixDetection, ParseError> {
     Ok(prefix_detection)
 }
 
+fn parse_symlinks(node: &Node) -> Result<Symlinks, ParseError> {
+    let mapping = node.as_mapping().ok_or_else(|| {
+        ParseError::expected_type("mapping", "non-mapping", get_span(node))
+            .with_message("Expected 'symlinks' to be a mapping")
+    })?;
+
+    let mut symlinks = Symlinks::default();
+
+    for (key_node, value_node) in mapping.iter() {
+        let key = key_node.as_str();
+
+        match key {
+            "default" => {
+                symlinks.default = Some(parse_field!("symlinks.default", value_node));
+            }
+            "preserve" => {
+                symlinks.preserve = parse_conditional_list(value_node)?;
+            }
+            "dereference" => {
+                symlinks.dereference = parse_conditional_list(value_node)?;
+            }
+            "error" => {
+                symlinks.error = parse_conditional_list(value_node)?;
+            }
+            _ => {
+                return Err(ParseError::invalid_value(
+                    "symlinks",
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion("Valid fields are: default, preserve, dereference, error"));
+            }
+        }
+    }
+
+    Ok(symlinks)
+}
+
+fn parse_strip(node: &Node) -> Result<Strip, ParseError> {
+    if node.as_scalar().is_some() {
+        return Ok(Strip {
+            enabled: Some(parse_bool_value(node, "strip")?),
+            ..Default::default()
+        });
+    }
+
+    let mapping = node.as_mapping().ok_or_else(|| {
+        ParseError::expected_type("boolean or mapping", "list", get_span(node))
+            .with_message("Expected 'strip' to be a boolean or a mapping")
+    })?;
+
+    let mut strip = Strip::default();
+
+    for (key_node, value_node) in mapping.iter() {
+        let key = key_node.as_str();
+
+        match key {
+            "enabled" => {
+                strip.enabled = Some(parse_bool_value(value_node, "strip.enabled")?);
+            }
+            "exclude" => {
+                strip.exclude = parse_conditional_list(value_node)?;
+            }
+            _ => {
+                return Err(ParseError::invalid_value(
+                    "strip",
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion("Valid fields are: enabled, exclude"));
+            }
+        }
+    }
+
+    // a mapping without `enabled` turns stripping on
+    if strip.enabled.is_none() {
+        strip.enabled = Some(Value::new_concrete(true, Some(get_span(node))));
+    }
+
+    Ok(strip)
+}
+
+fn parse_autotools_cleanup(node: &Node) -> Result<AutotoolsCleanup, ParseError> {
+    if node.as_scalar().is_some() {
+        let enabled = parse_bool_value(node, "autotools_cleanup")?;
+        return Ok(AutotoolsCleanup {
+            remove_la_files: Some(enabled.clone()),
+            relative_config_files: Some(enabled),
+        });
+    }
+
+    let mapping = node.as_mapping().ok_or_else(|| {
+        ParseError::expected_type("boolean or mapping", "list", get_span(node))
+            .with_message("Expected 'autotools_cleanup' to be a boolean or a mapping")
+    })?;
+
+    let mut cleanup = AutotoolsCleanup::default();
+
+    for (key_node, value_node) in mapping.iter() {
+        let key = key_node.as_str();
+
+        match key {
+            "remove_la_files" => {
+                cleanup.remove_la_files = Some(parse_bool_value(
+                    value_node,
+                    "autotools_cleanup.remove_la_files",
+                )?);
+            }
+            "relative_config_files" => {
+                cleanup.relative_config_files = Some(parse_bool_value(
+                    value_node,
+                    "autotools_cleanup.relative_config_files",
+                )?);
+            }
+            _ => {
+                return Err(ParseError::invalid_value(
+                    "autotools_cleanup",
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion("Valid fields are: remove_la_files, relative_config_files"));
+            }
+        }
+    }
+
+    Ok(cleanup)
+}
+
 fn parse_post_process(node: &Node) -> Result<PostProcess, ParseError> {
     let mapping = node.as_mapping().ok_or_else(|| {
         ParseError::expected_type("mapping", "non-mapping", get_span(node))
@@ -954,4 +1182,243 @@ post_process:
             panic!("Expected conditional item");
         }
     }
+
+    #[test]
+    fn test_parse_python_entry_point_policy() {
+        let yaml = r#"
+python:
+  entry_points:
+    - mycli = mypkg.cli:main
+  gui_entry_points:
+    - mygui = mypkg.gui:main
+  entry_point_style: script
+  entry_point_check: error
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+
+        assert_eq!(build.python.entry_points.len(), 1);
+        assert_eq!(build.python.gui_entry_points.len(), 1);
+        assert_eq!(
+            build
+                .python
+                .entry_point_style
+                .as_ref()
+                .and_then(|v| v.as_concrete()),
+            Some(&"script".to_string())
+        );
+        assert_eq!(
+            build
+                .python
+                .entry_point_check
+                .as_ref()
+                .and_then(|v| v.as_concrete()),
+            Some(&"error".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_python_noarch_pyc_versions() {
+        let yaml = r#"
+python:
+  noarch_pyc_versions:
+    - "3.10"
+    - if: linux
+      then: "3.13"
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+
+        assert_eq!(build.python.noarch_pyc_versions.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_build_menu() {
+        let yaml = r#"
+menu:
+  menu_name: My App
+  menu_items:
+    - name: My App
+      command: ["{{ PREFIX }}/bin/my-app"]
+      platforms:
+        linux: {}
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        let Some(serde_value::Value::Map(menu)) = &build.menu else {
+            panic!("Expected menu mapping");
+        };
+        assert_eq!(
+            menu.get(&serde_value::Value::String("menu_name".to_string())),
+            Some(&serde_value::Value::String("My App".to_string()))
+        );
+
+        let node = marked_yaml::parse_yaml(0, "menu: [1, 2]").unwrap();
+        assert!(parse_build(&node).is_err());
+    }
+
+    #[test]
+    fn test_parse_build_symlinks() {
+        let yaml = r#"
+symlinks:
+  default: dereference
+  preserve:
+    - lib/*.so*
+  error:
+    - share/**
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert_eq!(
+            build
+                .symlinks
+                .default
+                .as_ref()
+                .and_then(|v| v.as_concrete()),
+            Some(&"dereference".to_string())
+        );
+        assert_eq!(build.symlinks.preserve.len(), 1);
+        assert!(build.symlinks.dereference.is_empty());
+        assert_eq!(build.symlinks.error.len(), 1);
+
+        let node = marked_yaml::parse_yaml(0, "symlinks:\n  copy: [foo]").unwrap();
+        assert!(parse_build(&node).is_err());
+    }
+
+    #[test]
+    fn test_parse_dynamic_linking_overrides() {
+        let yaml = r#"
+dynamic_linking:
+  overlinking_behavior: error
+  overlinking_overrides:
+    warn:
+      - libGL.so*
+    ignore:
+      - libEGL.so*
+  overdepending_overrides:
+    ignore:
+      - openssl
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        let dynamic_linking = &build.dynamic_linking;
+        assert_eq!(dynamic_linking.overlinking_overrides.warn.len(), 1);
+        assert_eq!(dynamic_linking.overlinking_overrides.ignore.len(), 1);
+        assert!(dynamic_linking.overlinking_overrides.error.is_empty());
+        assert_eq!(dynamic_linking.overdepending_overrides.ignore.len(), 1);
+
+        let yaml = "dynamic_linking:\n  overlinking_overrides:\n    fatal: [foo]";
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        assert!(parse_build(&node).is_err());
+    }
+
+    #[test]
+    fn test_parse_prefix_detection_build_path_leaks() {
+        let yaml = "prefix_detection:\n  build_path_leaks: error";
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert!(build.prefix_detection.build_path_leaks.is_some());
+    }
+
+    #[test]
+    fn test_parse_autotools_cleanup() {
+        let node = marked_yaml::parse_yaml(0, "autotools_cleanup: false").unwrap();
+        let build = parse_build(&node).unwrap();
+        let cleanup = &build.autotools_cleanup;
+        assert_eq!(
+            cleanup.remove_la_files.as_ref().unwrap().as_concrete(),
+            Some(&false)
+        );
+        assert_eq!(
+            cleanup
+                .relative_config_files
+                .as_ref()
+                .unwrap()
+                .as_concrete(),
+            Some(&false)
+        );
+
+        let yaml = "autotools_cleanup:\n  remove_la_files: false";
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert!(build.autotools_cleanup.remove_la_files.is_some());
+        assert!(build.autotools_cleanup.relative_config_files.is_none());
+
+        let node = marked_yaml::parse_yaml(0, "autotools_cleanup:\n  la_files: false").unwrap();
+        assert!(parse_build(&node).is_err());
+    }
+
+    #[test]
+    fn test_parse_build_strip() {
+        let node = marked_yaml::parse_yaml(0, "strip: true").unwrap();
+        let build = parse_build(&node).unwrap();
+        assert_eq!(build.strip.enabled.unwrap().as_concrete(), Some(&true));
+        assert!(build.strip.exclude.is_empty());
+
+        let yaml = r#"
+strip:
+  exclude:
+    - lib/libkeep.so*
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert_eq!(build.strip.enabled.unwrap().as_concrete(), Some(&true));
+        assert_eq!(build.strip.exclude.len(), 1);
+
+        let node = marked_yaml::parse_yaml(0, "strip:\n  include: [foo]").unwrap();
+        assert!(parse_build(&node).is_err());
+    }
+
+    #[test]
+    fn test_parse_dynamic_linking_rpath_type() {
+        let yaml = r#"
+dynamic_linking:
+  rpath_type: runpath
+  rpaths:
+    - lib/
+    - $ORIGIN/../plugins
+    - /opt/vendor/lib
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        let dynamic_linking = &build.dynamic_linking;
+        assert!(dynamic_linking.rpath_type.is_some());
+        assert_eq!(dynamic_linking.rpaths.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_dynamic_linking_vendor_libraries() {
+        let yaml = r#"
+dynamic_linking:
+  vendor_libraries:
+    - lib/libgfortran.so*
+    - if: osx
+      then: lib/libgfortran*.dylib
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert_eq!(build.dynamic_linking.vendor_libraries.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_prefix_detection_placeholder_length() {
+        let yaml = r#"
+prefix_detection:
+  placeholder_length: 512
+  force_file_type:
+    binary:
+      - lib/*.so
+"#;
+        let node = marked_yaml::parse_yaml(0, yaml).unwrap();
+        let build = parse_build(&node).unwrap();
+        assert_eq!(
+            build
+                .prefix_detection
+                .placeholder_length
+                .as_ref()
+                .and_then(|v| v.as_concrete()),
+            Some(&512)
+        );
+        assert_eq!(build.prefix_detection.force_file_type.binary.len(), 1);
+    }
 }
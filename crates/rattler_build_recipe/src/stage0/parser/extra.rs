@@ -7,7 +7,7 @@ use rattler_build_yaml_parser::helpers::get_span;
 use crate::{error::ParseResult, stage0::extra::Extra};
 
 /// Convert a marked_yaml node to serde_value::Value
-fn node_to_yaml_value(node: &MarkedNode) -> serde_value::Value {
+pub(crate) fn node_to_yaml_value(node: &MarkedNode) -> serde_value::Value {
     match node {
         MarkedNode::Scalar(s) => {
             // Try to parse as different types
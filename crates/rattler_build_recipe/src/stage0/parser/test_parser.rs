@@ -11,11 +11,11 @@ use crate::{
         Value,
         parser::helpers::get_span,
         tests::{
-            CommandsTest, CommandsTestFiles, CommandsTestRequirements, DownstreamTest,
-            PackageContentsCheckFiles, PackageContentsTest, PerlTest, PythonTest, PythonVersion,
-            RTest, RubyTest, TestType,
+            CommandsTest, CommandsTestExpected, CommandsTestFiles, CommandsTestRequirements,
+            DownstreamTest, PackageContentsCheckFiles, PackageContentsTest, PerlTest, PythonTest,
+            PythonVersion, RTest, RubyTest, TestLimits, TestType, VirtualPackageMatrix,
         },
-        types::Script,
+        types::{IncludeExclude, Script},
     },
 };
 
@@ -121,27 +121,35 @@ fn parse_single_test(node: &Node) -> Result<TestType, ParseError> {
     // Determine test type by checking which field is present
     if mapping.get("python").is_some() {
         let python_node = mapping.get("python").unwrap();
-        let python = parse_python_test(python_node.as_mapping().ok_or_else(|| {
+        let mut python = parse_python_test(python_node.as_mapping().ok_or_else(|| {
             ParseError::expected_type("mapping", "non-mapping", get_span(python_node))
         })?)?;
+        python.limits = parse_test_limits(mapping, "python test", &["python"])?;
+        python.virtual_packages = parse_test_virtual_packages(mapping)?;
         Ok(TestType::Python { python })
     } else if mapping.get("perl").is_some() {
         let perl_node = mapping.get("perl").unwrap();
-        let perl = parse_perl_test(perl_node.as_mapping().ok_or_else(|| {
+        let mut perl = parse_perl_test(perl_node.as_mapping().ok_or_else(|| {
             ParseError::expected_type("mapping", "non-mapping", get_span(perl_node))
         })?)?;
+        perl.limits = parse_test_limits(mapping, "perl test", &["perl"])?;
+        perl.virtual_packages = parse_test_virtual_packages(mapping)?;
         Ok(TestType::Perl { perl })
     } else if mapping.get("r").is_some() {
         let r_node = mapping.get("r").unwrap();
-        let r = parse_r_test(r_node.as_mapping().ok_or_else(|| {
+        let mut r = parse_r_test(r_node.as_mapping().ok_or_else(|| {
             ParseError::expected_type("mapping", "non-mapping", get_span(r_node))
         })?)?;
+        r.limits = parse_test_limits(mapping, "r test", &["r"])?;
+        r.virtual_packages = parse_test_virtual_packages(mapping)?;
         Ok(TestType::R { r })
     } else if mapping.get("ruby").is_some() {
         let ruby_node = mapping.get("ruby").unwrap();
-        let ruby = parse_ruby_test(ruby_node.as_mapping().ok_or_else(|| {
+        let mut ruby = parse_ruby_test(ruby_node.as_mapping().ok_or_else(|| {
             ParseError::expected_type("mapping", "non-mapping", get_span(ruby_node))
         })?)?;
+        ruby.limits = parse_test_limits(mapping, "ruby test", &["ruby"])?;
+        ruby.virtual_packages = parse_test_virtual_packages(mapping)?;
         Ok(TestType::Ruby { ruby })
     } else if mapping.get("script").is_some() {
         Ok(TestType::Commands(parse_commands_test(mapping)?))
@@ -163,6 +171,80 @@ fn parse_single_test(node: &Node) -> Result<TestType, ParseError> {
     }
 }
 
+/// Parse the `virtual_packages` matrix of a test element: a list of mappings from virtual package
+/// names to versions
+fn parse_virtual_packages(node: &Node) -> Result<VirtualPackageMatrix, ParseError> {
+    let seq = node.as_sequence().ok_or_else(|| {
+        ParseError::expected_type("sequence", "non-sequence", get_span(node))
+            .with_message("Expected 'virtual_packages' to be a list of mappings")
+    })?;
+
+    let mut matrix = Vec::new();
+    for item in seq.iter() {
+        let mapping = item.as_mapping().ok_or_else(|| {
+            ParseError::expected_type("mapping", "non-mapping", get_span(item))
+                .with_message("Expected a mapping of virtual package names to versions")
+                .with_suggestion("Use e.g. `- __glibc: \"2.17\"`")
+        })?;
+        let mut overrides = indexmap::IndexMap::new();
+        for (key_node, value_node) in mapping.iter() {
+            let name = key_node.as_str();
+            if !name.starts_with("__") {
+                return Err(ParseError::invalid_value(
+                    "virtual_packages",
+                    format!("'{}' is not a virtual package", name),
+                    *key_node.span(),
+                )
+                .with_suggestion("Virtual package names start with `__`, e.g. `__glibc`"));
+            }
+            overrides.insert(name.to_string(), parse_value(value_node)?);
+        }
+        matrix.push(overrides);
+    }
+    Ok(VirtualPackageMatrix(matrix))
+}
+
+/// Parse the `virtual_packages` matrix of a test element, next to the test type key
+fn parse_test_virtual_packages(
+    mapping: &marked_yaml::types::MarkedMappingNode,
+) -> Result<VirtualPackageMatrix, ParseError> {
+    mapping
+        .get("virtual_packages")
+        .map(parse_virtual_packages)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+/// Parse the `timeout` and `retries` of a test element, next to the test type key
+fn parse_test_limits(
+    mapping: &marked_yaml::types::MarkedMappingNode,
+    test_name: &str,
+    type_keys: &[&str],
+) -> Result<TestLimits, ParseError> {
+    let mut limits = TestLimits::default();
+    for (key_node, value_node) in mapping.iter() {
+        match key_node.as_str() {
+            "timeout" => limits.timeout = Some(parse_value(value_node)?),
+            "retries" => limits.retries = Some(parse_value(value_node)?),
+            // parsed separately by `parse_test_virtual_packages`
+            "virtual_packages" => {}
+            key if type_keys.contains(&key) => {}
+            key => {
+                return Err(ParseError::invalid_value(
+                    test_name,
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion(format!(
+                    "Valid fields are: {}, timeout, retries, virtual_packages",
+                    type_keys.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(limits)
+}
+
 fn parse_python_test(
     mapping: &marked_yaml::types::MarkedMappingNode,
 ) -> Result<PythonTest, ParseError> {
@@ -201,6 +283,8 @@ fn parse_python_test(
         imports,
         pip_check,
         python_version,
+        limits: TestLimits::default(),
+        virtual_packages: VirtualPackageMatrix::default(),
     })
 }
 
@@ -233,7 +317,11 @@ fn parse_perl_test(
         }
     }
 
-    Ok(PerlTest { uses })
+    Ok(PerlTest {
+        uses,
+        limits: TestLimits::default(),
+        virtual_packages: VirtualPackageMatrix::default(),
+    })
 }
 
 fn parse_r_test(mapping: &marked_yaml::types::MarkedMappingNode) -> Result<RTest, ParseError> {
@@ -252,7 +340,11 @@ fn parse_r_test(mapping: &marked_yaml::types::MarkedMappingNode) -> Result<RTest
         }
     }
 
-    Ok(RTest { libraries })
+    Ok(RTest {
+        libraries,
+        limits: TestLimits::default(),
+        virtual_packages: VirtualPackageMatrix::default(),
+    })
 }
 
 fn parse_ruby_test(
@@ -273,7 +365,11 @@ fn parse_ruby_test(
         }
     }
 
-    Ok(RubyTest { requires })
+    Ok(RubyTest {
+        requires,
+        limits: TestLimits::default(),
+        virtual_packages: VirtualPackageMatrix::default(),
+    })
 }
 
 fn parse_commands_test(
@@ -282,6 +378,9 @@ fn parse_commands_test(
     let mut script = Script::default();
     let mut requirements = None;
     let mut files = None;
+    let mut expected = None;
+    let mut limits = TestLimits::default();
+    let mut virtual_packages = VirtualPackageMatrix::default();
 
     for (key_node, value_node) in mapping.iter() {
         let key = key_node.as_str();
@@ -304,13 +403,31 @@ fn parse_commands_test(
                     })?,
                 )?);
             }
+            "expected" => {
+                expected = Some(parse_commands_test_expected(
+                    value_node.as_mapping().ok_or_else(|| {
+                        ParseError::expected_type("mapping", "non-mapping", get_span(value_node))
+                    })?,
+                )?);
+            }
+            "timeout" => {
+                limits.timeout = Some(parse_value(value_node)?);
+            }
+            "retries" => {
+                limits.retries = Some(parse_value(value_node)?);
+            }
+            "virtual_packages" => {
+                virtual_packages = parse_virtual_packages(value_node)?;
+            }
             _ => {
                 return Err(ParseError::invalid_value(
                     "commands test",
                     format!("unknown field '{}'", key),
                     *key_node.span(),
                 )
-                .with_suggestion("Valid fields are: script, requirements, files"));
+                .with_suggestion(
+                    "Valid fields are: script, requirements, files, expected, timeout, retries, virtual_packages",
+                ));
             }
         }
     }
@@ -319,9 +436,43 @@ fn parse_commands_test(
         script,
         requirements,
         files,
+        expected,
+        limits,
+        virtual_packages,
     })
 }
 
+fn parse_commands_test_expected(
+    mapping: &marked_yaml::types::MarkedMappingNode,
+) -> Result<CommandsTestExpected, ParseError> {
+    let mut expected = CommandsTestExpected::default();
+
+    for (key_node, value_node) in mapping.iter() {
+        let key = key_node.as_str();
+        match key {
+            "exit_code" => {
+                expected.exit_code = Some(parse_value(value_node)?);
+            }
+            "stdout" => {
+                expected.stdout = parse_conditional_list(value_node)?;
+            }
+            "stdout_regex" => {
+                expected.stdout_regex = parse_conditional_list(value_node)?;
+            }
+            _ => {
+                return Err(ParseError::invalid_value(
+                    "commands test expected",
+                    format!("unknown field '{}'", key),
+                    *key_node.span(),
+                )
+                .with_suggestion("Valid fields are: exit_code, stdout, stdout_regex"));
+            }
+        }
+    }
+
+    Ok(expected)
+}
+
 fn parse_commands_test_requirements(
     mapping: &marked_yaml::types::MarkedMappingNode,
 ) -> Result<CommandsTestRequirements, ParseError> {
@@ -354,17 +505,17 @@ fn parse_commands_test_requirements(
 fn parse_commands_test_files(
     mapping: &marked_yaml::types::MarkedMappingNode,
 ) -> Result<CommandsTestFiles, ParseError> {
-    let mut source = ConditionalList::default();
-    let mut recipe = ConditionalList::default();
+    let mut source = IncludeExclude::default();
+    let mut recipe = IncludeExclude::default();
 
     for (key_node, value_node) in mapping.iter() {
         let key = key_node.as_str();
         match key {
             "source" => {
-                source = parse_conditional_list(value_node)?;
+                source = parse_test_files_globs(value_node, "source")?;
             }
             "recipe" => {
-                recipe = parse_conditional_list(value_node)?;
+                recipe = parse_test_files_globs(value_node, "recipe")?;
             }
             _ => {
                 return Err(ParseError::invalid_value(
@@ -380,6 +531,41 @@ fn parse_commands_test_files(
     Ok(CommandsTestFiles { source, recipe })
 }
 
+/// Parse the `source` or `recipe` test files - can be a list or include/exclude mapping
+fn parse_test_files_globs(node: &Node, field: &str) -> Result<IncludeExclude, ParseError> {
+    if let Some(mapping) = node.as_mapping() {
+        let mut include = ConditionalList::default();
+        let mut exclude = ConditionalList::default();
+
+        for (key_node, value_node) in mapping.iter() {
+            match key_node.as_str() {
+                "include" => include = parse_conditional_list(value_node)?,
+                "exclude" => exclude = parse_conditional_list(value_node)?,
+                key => {
+                    return Err(ParseError::invalid_value(
+                        field,
+                        format!("unknown field '{}' in files.{} mapping", key, field),
+                        *key_node.span(),
+                    )
+                    .with_suggestion("Valid fields are: include, exclude"));
+                }
+            }
+        }
+
+        return Ok(IncludeExclude::Mapping { include, exclude });
+    }
+
+    if node.as_sequence().is_some() {
+        return Ok(IncludeExclude::List(parse_conditional_list(node)?));
+    }
+
+    Err(ParseError::expected_type(
+        "sequence or mapping with include/exclude",
+        "other",
+        get_span(node),
+    ))
+}
+
 fn parse_downstream_test(
     mapping: &marked_yaml::types::MarkedMappingNode,
 ) -> Result<DownstreamTest, ParseError> {
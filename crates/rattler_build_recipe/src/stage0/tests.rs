@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::stage0::{
     SerializableMatchSpec,
-    types::{ConditionalList, ConditionalListOrItem, Script, Value},
+    types::{ConditionalList, ConditionalListOrItem, IncludeExclude, Script, Value},
 };
 
 /// Python version specification for tests
@@ -15,6 +15,54 @@ pub enum PythonVersion {
     Multiple(ConditionalList<String>),
 }
 
+/// The timeout and number of retries of a test element
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestLimits {
+    /// The maximum time (in seconds) the test may run before it is killed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<Value<u64>>,
+
+    /// How often a failing (or timed out) test is retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<Value<u32>>,
+}
+
+impl TestLimits {
+    /// Collect all variables used in the limits
+    pub fn used_variables(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        if let Some(timeout) = &self.timeout {
+            vars.extend(timeout.used_variables());
+        }
+        if let Some(retries) = &self.retries {
+            vars.extend(retries.used_variables());
+        }
+        vars
+    }
+}
+
+/// The virtual package matrix of a test element: the test runs once for every entry, with the
+/// virtual packages (e.g. `__glibc: "2.17"`) of the test platform overridden
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VirtualPackageMatrix(pub Vec<indexmap::IndexMap<String, Value<String>>>);
+
+impl VirtualPackageMatrix {
+    /// Check if the matrix is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Collect all variables used in the matrix
+    pub fn used_variables(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .flat_map(|overrides| overrides.values())
+            .flat_map(|version| version.used_variables())
+            .collect()
+    }
+}
+
 /// A special Python test that checks if the imports are available and runs `pip check`
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PythonTest {
@@ -29,6 +77,14 @@ pub struct PythonTest {
     /// Python version(s) to test against. If not specified, the default python version is used.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub python_version: Option<PythonVersion>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A special Perl test that checks if the imports are available
@@ -37,6 +93,14 @@ pub struct PerlTest {
     /// List of perl `uses` to test
     #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
     pub uses: ConditionalList<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that checks if R libraries can be loaded
@@ -45,6 +109,14 @@ pub struct RTest {
     /// List of R libraries to test with library()
     #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
     pub libraries: ConditionalList<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that checks if Ruby gems/modules can be required
@@ -53,6 +125,14 @@ pub struct RubyTest {
     /// List of Ruby modules to test with require
     #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
     pub requires: ConditionalList<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// The extra requirements for the test
@@ -70,13 +150,44 @@ pub struct CommandsTestRequirements {
 /// The files that should be copied to the test directory
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandsTestFiles {
-    /// Files to be copied from the source directory to the test directory.
+    /// Files to be copied from the source directory to the test directory
+    /// (glob patterns or include/exclude mapping).
+    #[serde(default, skip_serializing_if = "IncludeExclude::is_empty")]
+    pub source: IncludeExclude,
+
+    /// Files to be copied from the recipe directory to the test directory
+    /// (glob patterns or include/exclude mapping).
+    #[serde(default, skip_serializing_if = "IncludeExclude::is_empty")]
+    pub recipe: IncludeExclude,
+}
+
+/// The expected result of a script test
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandsTestExpected {
+    /// The expected exit code of the script (defaults to 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<Value<i32>>,
+
+    /// Substrings that the standard output of the script has to contain
     #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
-    pub source: ConditionalList<String>,
+    pub stdout: ConditionalList<String>,
 
-    /// Files to be copied from the recipe directory to the test directory.
+    /// Regular expressions that have to match the standard output of the script
     #[serde(default, skip_serializing_if = "ConditionalList::is_empty")]
-    pub recipe: ConditionalList<String>,
+    pub stdout_regex: ConditionalList<String>,
+}
+
+impl CommandsTestExpected {
+    /// Collect all variables used in the expected result
+    pub fn used_variables(&self) -> Vec<String> {
+        let mut vars = Vec::new();
+        if let Some(exit_code) = &self.exit_code {
+            vars.extend(exit_code.used_variables());
+        }
+        vars.extend(self.stdout.used_variables());
+        vars.extend(self.stdout_regex.used_variables());
+        vars
+    }
 }
 
 /// A test that executes a script in a freshly created environment
@@ -93,6 +204,18 @@ pub struct CommandsTest {
     /// Extra files to include in the test
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub files: Option<CommandsTestFiles>,
+
+    /// The expected exit code and output of the script
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<CommandsTestExpected>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that runs the tests of a downstream package
@@ -200,15 +323,23 @@ impl TestType {
                         }
                     }
                 }
+                vars.extend(python.limits.used_variables());
+                vars.extend(python.virtual_packages.used_variables());
             }
             TestType::Perl { perl } => {
                 vars.extend(perl.uses.used_variables());
+                vars.extend(perl.limits.used_variables());
+                vars.extend(perl.virtual_packages.used_variables());
             }
             TestType::R { r } => {
                 vars.extend(r.libraries.used_variables());
+                vars.extend(r.limits.used_variables());
+                vars.extend(r.virtual_packages.used_variables());
             }
             TestType::Ruby { ruby } => {
                 vars.extend(ruby.requires.used_variables());
+                vars.extend(ruby.limits.used_variables());
+                vars.extend(ruby.virtual_packages.used_variables());
             }
             TestType::Commands(commands) => {
                 vars.extend(commands.script.used_variables());
@@ -220,6 +351,11 @@ impl TestType {
                     vars.extend(files.source.used_variables());
                     vars.extend(files.recipe.used_variables());
                 }
+                if let Some(expected) = &commands.expected {
+                    vars.extend(expected.used_variables());
+                }
+                vars.extend(commands.limits.used_variables());
+                vars.extend(commands.virtual_packages.used_variables());
             }
             TestType::Downstream(downstream) => {
                 vars.extend(downstream.downstream.used_variables());
@@ -40,6 +40,18 @@ impl<T> Default for IncludeExclude<T> {
     }
 }
 
+impl<T> IncludeExclude<T> {
+    /// Returns true if there are no include and no exclude patterns
+    pub fn is_empty(&self) -> bool {
+        match self {
+            IncludeExclude::List(list) => list.is_empty(),
+            IncludeExclude::Mapping { include, exclude } => {
+                include.is_empty() && exclude.is_empty()
+            }
+        }
+    }
+}
+
 impl<T: ToString + Debug> IncludeExclude<T> {
     /// Collect all variables used in this include/exclude pattern
     pub fn used_variables(&self) -> Vec<String> {
@@ -47,6 +47,17 @@ impl About {
         Self::default()
     }
 
+    /// The license family of the recipe, or the family of the license if the recipe does not
+    /// set one (e.g. `BSD` for `BSD-3-Clause`)
+    pub fn license_family_or_inferred(&self) -> Option<String> {
+        self.license_family.clone().or_else(|| {
+            self.license
+                .as_ref()
+                .and_then(License::family)
+                .map(str::to_string)
+        })
+    }
+
     /// Check if the About section is empty (all fields are None/empty)
     pub fn is_empty(&self) -> bool {
         self.homepage.is_none()
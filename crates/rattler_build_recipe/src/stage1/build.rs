@@ -1,4 +1,6 @@
 //! Stage 1 Build - evaluated build configuration with concrete values
+use std::path::Path;
+
 use rattler_build_jinja::Variable;
 use rattler_build_script::Script;
 use rattler_build_yaml_parser::ParseError;
@@ -199,6 +201,13 @@ pub struct PrefixDetection {
     /// Ignore binary files for prefix replacement (Unix only)
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub ignore_binary_files: bool,
+    /// Length of the padded host prefix that binaries are built against (Unix only).
+    /// Defaults to 255 characters when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placeholder_length: Option<u64>,
+    /// What to do when packaged files contain paths into the build prefix or work directory
+    #[serde(default, skip_serializing_if = "BuildPathLeaks::is_default")]
+    pub build_path_leaks: BuildPathLeaks,
 }
 
 impl Default for PrefixDetection {
@@ -207,6 +216,8 @@ impl Default for PrefixDetection {
             force_file_type: ForceFileType::default(),
             ignore: AllOrGlobVec::All(false),
             ignore_binary_files: false,
+            placeholder_length: None,
+            build_path_leaks: BuildPathLeaks::default(),
         }
     }
 }
@@ -214,7 +225,36 @@ impl Default for PrefixDetection {
 impl PrefixDetection {
     /// Check if this is the default configuration
     pub fn is_default(&self) -> bool {
-        self.force_file_type.is_default() && self.ignore.is_none() && !self.ignore_binary_files
+        self.force_file_type.is_default()
+            && self.ignore.is_none()
+            && !self.ignore_binary_files
+            && self.placeholder_length.is_none()
+            && self.build_path_leaks.is_default()
+    }
+}
+
+/// What to do when packaged files contain paths into the build prefix or the work directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildPathLeaks {
+    /// Do not scan the packaged files
+    Ignore,
+    /// Report the leaks as warnings (default)
+    #[default]
+    Warn,
+    /// Fail the build if there are leaks
+    Error,
+}
+
+impl BuildPathLeaks {
+    /// Check if this is Ignore
+    pub fn is_ignore(&self) -> bool {
+        matches!(self, BuildPathLeaks::Ignore)
+    }
+
+    /// Check if this is Warn (the default)
+    pub fn is_default(&self) -> bool {
+        matches!(self, BuildPathLeaks::Warn)
     }
 }
 
@@ -355,6 +395,141 @@ pub struct Build {
     /// Post-processing operations
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub post_process: Vec<PostProcess>,
+
+    /// A menuinst (v2) document that is rendered to `Menu/<package name>.json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu: Option<serde_value::Value>,
+
+    /// How symlinks are packaged
+    #[serde(default, skip_serializing_if = "Symlinks::is_default")]
+    pub symlinks: Symlinks,
+
+    /// Strip executables and shared libraries
+    #[serde(default, skip_serializing_if = "Strip::is_default")]
+    pub strip: Strip,
+
+    /// Cleanup of libtool archives and config scripts
+    #[serde(default, skip_serializing_if = "AutotoolsCleanup::is_default")]
+    pub autotools_cleanup: AutotoolsCleanup,
+}
+
+/// Cleanup of autotools leftovers in the package
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutotoolsCleanup {
+    /// Remove libtool archives (`*.la`)
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub remove_la_files: bool,
+    /// Rewrite pkg-config files and `*-config` scripts to use `${prefix}`
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub relative_config_files: bool,
+}
+
+impl Default for AutotoolsCleanup {
+    fn default() -> Self {
+        Self {
+            remove_la_files: true,
+            relative_config_files: true,
+        }
+    }
+}
+
+impl AutotoolsCleanup {
+    /// Check if this is the default configuration (clean up everything)
+    pub fn is_default(&self) -> bool {
+        self.remove_la_files && self.relative_config_files
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+/// Binary stripping configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Strip {
+    /// Whether to strip executables and shared libraries
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub enabled: bool,
+    /// Do not strip these files
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub exclude: GlobVec,
+}
+
+impl Strip {
+    /// Check if this is the default configuration (do not strip)
+    pub fn is_default(&self) -> bool {
+        !self.enabled && self.exclude.is_empty()
+    }
+
+    /// Whether the file at `path` (relative to the prefix) should be stripped
+    pub fn should_strip(&self, path: &Path) -> bool {
+        self.enabled && !self.exclude.is_match(path)
+    }
+}
+
+/// What to do with a symlink found while packaging
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkBehavior {
+    /// Package the symlink as a link (default)
+    #[default]
+    Preserve,
+    /// Package a copy of the file the symlink points to
+    Dereference,
+    /// Fail the build
+    Error,
+}
+
+impl SymlinkBehavior {
+    /// Check if this is Preserve (the default)
+    pub fn is_preserve(&self) -> bool {
+        matches!(self, SymlinkBehavior::Preserve)
+    }
+}
+
+/// Symlink packaging configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Symlinks {
+    /// Behavior for symlinks that match none of the globs
+    #[serde(default, skip_serializing_if = "SymlinkBehavior::is_preserve")]
+    pub default: SymlinkBehavior,
+    /// Keep these symlinks as links
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub preserve: GlobVec,
+    /// Replace these symlinks with a copy of the file they point to
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub dereference: GlobVec,
+    /// Fail the build if any of these symlinks is found
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub error: GlobVec,
+}
+
+impl Symlinks {
+    /// Check if this is the default configuration
+    pub fn is_default(&self) -> bool {
+        self.default.is_preserve()
+            && self.preserve.is_empty()
+            && self.dereference.is_empty()
+            && self.error.is_empty()
+    }
+
+    /// The behavior for the symlink at `path` (relative to the prefix). If a path matches
+    /// several globs, `error` wins over `dereference`, which wins over `preserve`.
+    pub fn behavior_for(&self, path: &Path) -> SymlinkBehavior {
+        if self.error.is_match(path) {
+            SymlinkBehavior::Error
+        } else if self.dereference.is_match(path) {
+            SymlinkBehavior::Dereference
+        } else if self.preserve.is_match(path) {
+            SymlinkBehavior::Preserve
+        } else {
+            self.default
+        }
+    }
 }
 
 /// Dynamic linking configuration
@@ -380,13 +555,29 @@ pub struct DynamicLinking {
     #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
     pub rpath_allowlist: GlobVec,
 
-    /// What to do when detecting overdepending (ignore or error)
-    #[serde(default, skip_serializing_if = "LinkingCheckBehavior::is_ignore")]
+    /// What to do when detecting overdepending (ignore, warn or error)
+    #[serde(default, skip_serializing_if = "LinkingCheckBehavior::is_default")]
     pub overdepending_behavior: LinkingCheckBehavior,
 
-    /// What to do when detecting overlinking (ignore or error)
-    #[serde(default, skip_serializing_if = "LinkingCheckBehavior::is_ignore")]
+    /// What to do when detecting overlinking (ignore, warn or error)
+    #[serde(default, skip_serializing_if = "LinkingCheckBehavior::is_default")]
     pub overlinking_behavior: LinkingCheckBehavior,
+
+    /// Per-library overrides of `overlinking_behavior`
+    #[serde(default, skip_serializing_if = "LinkingCheckOverrides::is_empty")]
+    pub overlinking_overrides: LinkingCheckOverrides,
+
+    /// Per-package overrides of `overdepending_behavior`
+    #[serde(default, skip_serializing_if = "LinkingCheckOverrides::is_empty")]
+    pub overdepending_overrides: LinkingCheckOverrides,
+
+    /// Whether to write `DT_RPATH` or `DT_RUNPATH` entries (Linux only)
+    #[serde(default, skip_serializing_if = "RpathType::is_default")]
+    pub rpath_type: RpathType,
+
+    /// Libraries from the host prefix to copy into the package (validated glob patterns)
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub vendor_libraries: GlobVec,
 }
 
 impl Default for DynamicLinking {
@@ -398,6 +589,10 @@ impl Default for DynamicLinking {
             rpath_allowlist: GlobVec::default(),
             overdepending_behavior: LinkingCheckBehavior::default(),
             overlinking_behavior: LinkingCheckBehavior::default(),
+            overlinking_overrides: LinkingCheckOverrides::default(),
+            overdepending_overrides: LinkingCheckOverrides::default(),
+            rpath_type: RpathType::default(),
+            vendor_libraries: GlobVec::default(),
         }
     }
 }
@@ -409,27 +604,91 @@ impl DynamicLinking {
             && self.binary_relocation.is_all()
             && self.missing_dso_allowlist.is_empty()
             && self.rpath_allowlist.is_empty()
-            && self.overdepending_behavior.is_ignore()
-            && self.overlinking_behavior.is_ignore()
+            && self.overdepending_behavior.is_default()
+            && self.overlinking_behavior.is_default()
+            && self.overlinking_overrides.is_empty()
+            && self.overdepending_overrides.is_empty()
+            && self.rpath_type.is_default()
+            && self.vendor_libraries.is_empty()
+    }
+}
+
+/// The dynamic section entry that holds the library search path of ELF binaries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RpathType {
+    /// `DT_RPATH`, searched before `LD_LIBRARY_PATH` (default)
+    #[default]
+    Rpath,
+    /// `DT_RUNPATH`, searched after `LD_LIBRARY_PATH`
+    Runpath,
+}
+
+impl RpathType {
+    /// Check if this is Rpath (the default)
+    pub fn is_default(&self) -> bool {
+        matches!(self, RpathType::Rpath)
     }
 }
 
 /// What to do during linking checks
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum LinkingCheckBehavior {
-    /// Ignore the issue (default)
-    #[default]
+    /// Do not report the issue
     Ignore,
+    /// Report the issue as a warning (default)
+    #[default]
+    Warn,
     /// Report the issue as an error
     Error,
 }
 
 impl LinkingCheckBehavior {
-    /// Check if this is Ignore (the default)
+    /// Check if this is Ignore
     pub fn is_ignore(&self) -> bool {
         matches!(self, LinkingCheckBehavior::Ignore)
     }
+
+    /// Check if this is Warn (the default)
+    pub fn is_default(&self) -> bool {
+        matches!(self, LinkingCheckBehavior::Warn)
+    }
+}
+
+/// Overrides of a linking check behavior for specific libraries or packages
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkingCheckOverrides {
+    /// Do not report these
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub ignore: GlobVec,
+    /// Report these as warnings
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub warn: GlobVec,
+    /// Fail the build for these
+    #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
+    pub error: GlobVec,
+}
+
+impl LinkingCheckOverrides {
+    /// Check if there are no overrides
+    pub fn is_empty(&self) -> bool {
+        self.ignore.is_empty() && self.warn.is_empty() && self.error.is_empty()
+    }
+
+    /// The behavior for `path`, falling back to `default` if no override matches. If a path
+    /// matches several globs, `error` wins over `warn`, which wins over `ignore`.
+    pub fn behavior_for(&self, path: &Path, default: LinkingCheckBehavior) -> LinkingCheckBehavior {
+        if self.error.is_match(path) {
+            LinkingCheckBehavior::Error
+        } else if self.warn.is_match(path) {
+            LinkingCheckBehavior::Warn
+        } else if self.ignore.is_match(path) {
+            LinkingCheckBehavior::Ignore
+        } else {
+            default
+        }
+    }
 }
 
 /// Python-specific build configuration
@@ -440,7 +699,7 @@ pub struct PythonBuild {
     pub entry_points: Vec<EntryPoint>,
 
     /// Skip pyc compilation for these files (validated glob patterns)
-    /// Only relevant for non-noarch Python packages
+    /// For noarch Python packages only used together with `noarch_pyc_versions`
     #[serde(default, skip_serializing_if = "GlobVec::is_empty")]
     pub skip_pyc_compilation: GlobVec,
 
@@ -457,6 +716,23 @@ pub struct PythonBuild {
     /// This setting only makes sense for the `python` package itself
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub site_packages_path: Option<String>,
+
+    /// GUI entry points (`gui_scripts`), launched without a console window on Windows
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gui_entry_points: Vec<EntryPoint>,
+
+    /// How entry points are materialized on Windows
+    #[serde(default, skip_serializing_if = "EntryPointStyle::is_launcher")]
+    pub entry_point_style: EntryPointStyle,
+
+    /// What to do when the declared entry points don't match the packaged metadata
+    #[serde(default, skip_serializing_if = "EntryPointCheck::is_warn")]
+    pub entry_point_check: EntryPointCheck,
+
+    /// The Python versions (e.g. `3.12`) to pre-compile `.pyc` files for.
+    /// Only relevant for noarch Python packages, if empty no `.pyc` files are packaged
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub noarch_pyc_versions: Vec<String>,
 }
 
 impl PythonBuild {
@@ -467,6 +743,48 @@ impl PythonBuild {
             && !self.use_python_app_entrypoint
             && !self.version_independent
             && self.site_packages_path.is_none()
+            && self.gui_entry_points.is_empty()
+            && self.entry_point_style.is_launcher()
+            && self.entry_point_check.is_warn()
+            && self.noarch_pyc_versions.is_empty()
+    }
+}
+
+/// How entry points are materialized on Windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryPointStyle {
+    /// Write a `<cmd>-script.py` next to a `<cmd>.exe` launcher (default)
+    #[default]
+    Launcher,
+    /// Write a `<cmd>-script.py` next to a `<cmd>.bat` wrapper, without an executable
+    Script,
+}
+
+impl EntryPointStyle {
+    /// Check if this is Launcher (the default)
+    pub fn is_launcher(&self) -> bool {
+        matches!(self, EntryPointStyle::Launcher)
+    }
+}
+
+/// What to do when declared entry points disagree with the packaged `entry_points.txt`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryPointCheck {
+    /// Don't check the entry points
+    Ignore,
+    /// Emit a warning for every mismatch (default)
+    #[default]
+    Warn,
+    /// Fail the build on any mismatch
+    Error,
+}
+
+impl EntryPointCheck {
+    /// Check if this is Warn (the default)
+    pub fn is_warn(&self) -> bool {
+        matches!(self, EntryPointCheck::Warn)
     }
 }
 
@@ -480,6 +798,10 @@ impl PartialEq for PythonBuild {
             && self.use_python_app_entrypoint == other.use_python_app_entrypoint
             && self.version_independent == other.version_independent
             && self.site_packages_path == other.site_packages_path
+            && self.gui_entry_points.len() == other.gui_entry_points.len()
+            && self.entry_point_style == other.entry_point_style
+            && self.entry_point_check == other.entry_point_check
+            && self.noarch_pyc_versions == other.noarch_pyc_versions
     }
 }
 
@@ -503,11 +825,7 @@ impl Build {
             && matches!(self.string, BuildString::Default)
             && self.script.is_default()
             && self.noarch.is_none()
-            && self.python.entry_points.is_empty()
-            && self.python.skip_pyc_compilation.is_empty()
-            && !self.python.use_python_app_entrypoint
-            && !self.python.version_independent
-            && self.python.site_packages_path.is_none()
+            && self.python.is_default()
             && !self.skip
             && self.always_copy_files.is_empty()
             && self.always_include_files.is_empty()
@@ -517,8 +835,12 @@ impl Build {
             && self.dynamic_linking.binary_relocation.is_all()
             && self.dynamic_linking.missing_dso_allowlist.is_empty()
             && self.dynamic_linking.rpath_allowlist.is_empty()
-            && self.dynamic_linking.overdepending_behavior == LinkingCheckBehavior::Ignore
-            && self.dynamic_linking.overlinking_behavior == LinkingCheckBehavior::Ignore
+            && self.dynamic_linking.overdepending_behavior.is_default()
+            && self.dynamic_linking.overlinking_behavior.is_default()
+            && self.dynamic_linking.overlinking_overrides.is_empty()
+            && self.dynamic_linking.overdepending_overrides.is_empty()
+            && self.dynamic_linking.rpath_type.is_default()
+            && self.dynamic_linking.vendor_libraries.is_empty()
             && self.variant.use_keys.is_empty()
             && self.variant.ignore_keys.is_empty()
             && self.variant.down_prioritize_variant.is_none()
@@ -526,7 +848,13 @@ impl Build {
             && self.prefix_detection.force_file_type.binary.is_empty()
             && self.prefix_detection.ignore.is_none()
             && !self.prefix_detection.ignore_binary_files
+            && self.prefix_detection.placeholder_length.is_none()
+            && self.prefix_detection.build_path_leaks.is_default()
             && self.post_process.is_empty()
+            && self.menu.is_none()
+            && self.symlinks.is_default()
+            && self.strip.is_default()
+            && self.autotools_cleanup.is_default()
     }
 }
 
@@ -566,4 +894,69 @@ mod tests {
         assert!(!build.is_default());
         assert!(!build.script.is_default());
     }
+
+    #[test]
+    fn test_linking_check_behavior_for() {
+        let overrides = LinkingCheckOverrides {
+            ignore: GlobVec::from_vec(vec!["libGL.so*"], None),
+            warn: GlobVec::from_vec(vec!["lib/libfoo*"], None),
+            error: GlobVec::from_vec(vec!["lib/libfoo_bad.so"], None),
+        };
+
+        assert!(!overrides.is_empty());
+        assert_eq!(
+            overrides.behavior_for(Path::new("libGL.so.1"), LinkingCheckBehavior::Error),
+            LinkingCheckBehavior::Ignore
+        );
+        assert_eq!(
+            overrides.behavior_for(Path::new("lib/libfoo.so"), LinkingCheckBehavior::Ignore),
+            LinkingCheckBehavior::Warn
+        );
+        // `error` takes precedence over `warn`
+        assert_eq!(
+            overrides.behavior_for(Path::new("lib/libfoo_bad.so"), LinkingCheckBehavior::Warn),
+            LinkingCheckBehavior::Error
+        );
+        assert_eq!(
+            overrides.behavior_for(Path::new("lib/libbar.so"), LinkingCheckBehavior::Error),
+            LinkingCheckBehavior::Error
+        );
+    }
+
+    #[test]
+    fn test_strip_should_strip() {
+        let strip = Strip {
+            enabled: true,
+            exclude: GlobVec::from_vec(vec!["lib/libkeep.so*"], None),
+        };
+        assert!(!strip.is_default());
+        assert!(strip.should_strip(Path::new("lib/libfoo.so")));
+        assert!(!strip.should_strip(Path::new("lib/libkeep.so.1")));
+        assert!(!Strip::default().should_strip(Path::new("bin/tool")));
+    }
+
+    #[test]
+    fn test_symlink_behavior_for() {
+        let symlinks = Symlinks {
+            default: SymlinkBehavior::Dereference,
+            preserve: GlobVec::from_vec(vec!["lib/*.so*"], None),
+            dereference: GlobVec::default(),
+            error: GlobVec::from_vec(vec!["lib/libbad.so"], None),
+        };
+
+        assert!(!symlinks.is_default());
+        assert_eq!(
+            symlinks.behavior_for(Path::new("lib/libfoo.so.1")),
+            SymlinkBehavior::Preserve
+        );
+        // `error` takes precedence over `preserve`
+        assert_eq!(
+            symlinks.behavior_for(Path::new("lib/libbad.so")),
+            SymlinkBehavior::Error
+        );
+        assert_eq!(
+            symlinks.behavior_for(Path::new("bin/tool")),
+            SymlinkBehavior::Dereference
+        );
+    }
 }
@@ -68,7 +68,7 @@ impl Formatter for PythonFormatter {
     }
 }
 
-use rattler_build_types::short_version;
+use rattler_build_types::{is_free_threaded_python, short_version};
 
 /// The hash info for a given variant
 #[derive(Debug, PartialEq, Clone, Eq, Hash, Serialize, Deserialize)]
@@ -139,10 +139,13 @@ impl HashInfo {
                 _ => 2,
             };
 
-            map.insert(
-                prefix.to_string(),
-                short_version(&version_spec.to_string(), version_length),
-            );
+            let version_spec = version_spec.to_string();
+            let mut version = short_version(&version_spec, version_length);
+            // free-threaded builds are marked like their ABI tag, e.g. `py313t`
+            if prefix == "py" && is_free_threaded_python(&version_spec) {
+                version.push('t');
+            }
+            map.insert(prefix.to_string(), version);
         }
 
         let order = vec!["np", "py", "pl", "lua", "r", "mro"];
@@ -429,4 +432,23 @@ mod tests {
         assert!(hash_info.prefix.contains("np120")); // numpy: 2 digits
         assert!(hash_info.prefix.contains("pl526")); // perl: 3 digits
     }
+
+    #[test]
+    fn test_hash_prefix_free_threaded() {
+        let mut variant = BTreeMap::new();
+        variant.insert(
+            NormalizedKey::from("python"),
+            Variable::from("3.13.* *_cp313t"),
+        );
+        let free_threaded = HashInfo::from_variant(&variant, &NoArchType::none());
+        assert_eq!(free_threaded.prefix, "py313t");
+
+        variant.insert(
+            NormalizedKey::from("python"),
+            Variable::from("3.13.* *_cp313"),
+        );
+        let default = HashInfo::from_variant(&variant, &NoArchType::none());
+        assert_eq!(default.prefix, "py313");
+        assert_ne!(free_threaded.hash, default.hash);
+    }
 }
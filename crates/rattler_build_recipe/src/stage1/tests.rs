@@ -25,6 +25,35 @@ impl PythonVersion {
     }
 }
 
+/// The timeout and number of retries of a test element (evaluated)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestLimits {
+    /// The maximum time (in seconds) the test may run before it is killed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+
+    /// How often a failing (or timed out) test is retried
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+/// The virtual package matrix of a test element (evaluated): the test runs once for every entry,
+/// with the virtual packages (name to version) of the test platform overridden
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VirtualPackageMatrix(pub Vec<indexmap::IndexMap<String, String>>);
+
+impl VirtualPackageMatrix {
+    /// Check if the matrix is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 /// A special Python test that checks if the imports are available and runs `pip check` (evaluated)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PythonTest {
@@ -38,6 +67,14 @@ pub struct PythonTest {
     /// Python version(s) to test against. If not specified, the default python version is used.
     #[serde(default, skip_serializing_if = "PythonVersion::is_none")]
     pub python_version: PythonVersion,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 fn default_pip_check() -> bool {
@@ -53,6 +90,14 @@ fn is_true(value: &bool) -> bool {
 pub struct PerlTest {
     /// List of perl `uses` to test
     pub uses: Vec<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that checks if R libraries can be loaded (evaluated)
@@ -60,6 +105,14 @@ pub struct PerlTest {
 pub struct RTest {
     /// List of R libraries to test with library()
     pub libraries: Vec<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that checks if Ruby gems/modules can be required (evaluated)
@@ -67,6 +120,14 @@ pub struct RTest {
 pub struct RubyTest {
     /// List of Ruby modules to test with require
     pub requires: Vec<String>,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// The extra requirements for the test (evaluated)
@@ -107,6 +168,29 @@ impl CommandsTestFiles {
     }
 }
 
+/// The expected result of a script test (evaluated)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandsTestExpected {
+    /// The expected exit code of the script (defaults to 0)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+
+    /// Substrings that the standard output of the script has to contain
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stdout: Vec<String>,
+
+    /// Regular expressions that have to match the standard output of the script
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stdout_regex: Vec<String>,
+}
+
+impl CommandsTestExpected {
+    /// Check if nothing is expected besides a successful exit
+    pub fn is_empty(&self) -> bool {
+        self.exit_code.is_none() && self.stdout.is_empty() && self.stdout_regex.is_empty()
+    }
+}
+
 /// A test that executes a script in a freshly created environment (evaluated)
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandsTest {
@@ -121,6 +205,18 @@ pub struct CommandsTest {
     /// Extra files to include in the test
     #[serde(default, skip_serializing_if = "CommandsTestFiles::is_empty")]
     pub files: CommandsTestFiles,
+
+    /// The expected exit code and output of the script
+    #[serde(default, skip_serializing_if = "CommandsTestExpected::is_empty")]
+    pub expected: CommandsTestExpected,
+
+    /// The timeout and number of retries of the test
+    #[serde(default, flatten)]
+    pub limits: TestLimits,
+
+    /// The virtual package overrides that the test is re-run with
+    #[serde(default, skip_serializing_if = "VirtualPackageMatrix::is_empty")]
+    pub virtual_packages: VirtualPackageMatrix,
 }
 
 /// A test that runs the tests of a downstream package (evaluated)
@@ -265,6 +361,20 @@ pub enum TestType {
     },
 }
 
+impl TestType {
+    /// The virtual package matrix of the test (if the test supports one)
+    pub fn virtual_packages(&self) -> Option<&VirtualPackageMatrix> {
+        match self {
+            TestType::Python { python } => Some(&python.virtual_packages),
+            TestType::Perl { perl } => Some(&perl.virtual_packages),
+            TestType::R { r } => Some(&r.virtual_packages),
+            TestType::Ruby { ruby } => Some(&ruby.virtual_packages),
+            TestType::Commands(commands) => Some(&commands.virtual_packages),
+            TestType::Downstream(_) | TestType::PackageContents { .. } => None,
+        }
+    }
+}
+
 #[allow(clippy::module_inception)]
 #[cfg(test)]
 mod tests {
@@ -280,6 +390,8 @@ mod tests {
                 imports: vec!["numpy".to_string()],
                 pip_check: true,
                 python_version: PythonVersion::Single("3.11".to_string()),
+                limits: TestLimits::default(),
+                virtual_packages: VirtualPackageMatrix::default(),
             },
         };
         insta::assert_snapshot!(serde_yaml::to_string(&python).unwrap(), @r###"
@@ -305,6 +417,9 @@ mod tests {
             },
             requirements: CommandsTestRequirements::default(),
             files: CommandsTestFiles::default(),
+            expected: CommandsTestExpected::default(),
+            limits: TestLimits::default(),
+            virtual_packages: VirtualPackageMatrix::default(),
         });
         insta::assert_snapshot!(serde_yaml::to_string(&commands).unwrap(), @r###"
         script:
@@ -340,6 +455,8 @@ mod tests {
                     imports: vec!["numpy".to_string()],
                     pip_check: false,
                     python_version: PythonVersion::Multiple(vec!["3.10".to_string()]),
+                    limits: TestLimits::default(),
+                    virtual_packages: VirtualPackageMatrix::default(),
                 },
             },
             TestType::Downstream(DownstreamTest {
@@ -355,6 +472,19 @@ mod tests {
                     build: vec![],
                 },
                 files: CommandsTestFiles::default(),
+                expected: CommandsTestExpected {
+                    exit_code: Some(2),
+                    stdout: vec!["test".to_string()],
+                    stdout_regex: vec![r"^te\w+$".to_string()],
+                },
+                limits: TestLimits {
+                    timeout: Some(60),
+                    retries: 2,
+                },
+                virtual_packages: VirtualPackageMatrix(vec![indexmap::IndexMap::from([(
+                    "__glibc".to_string(),
+                    "2.17".to_string(),
+                )])]),
             }),
         ];
 
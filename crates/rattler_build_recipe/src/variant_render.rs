@@ -18,12 +18,12 @@ use miette::Diagnostic;
 use petgraph::graph::{DiGraph, NodeIndex};
 use rattler_build_variant_config::VariantExpandError;
 use rattler_build_yaml_parser::ParseError;
-use rattler_conda_types::NoArchType;
+use rattler_conda_types::{MatchSpec, NoArchType, ParseStrictness};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use rattler_build_jinja::{JinjaConfig, Variable};
-use rattler_build_types::NormalizedKey;
+use rattler_build_types::{NormalizedKey, is_free_threaded_python};
 use rattler_build_variant_config::VariantConfig;
 
 use crate::stage0::evaluate::ALWAYS_INCLUDED_VARS;
@@ -1127,6 +1127,103 @@ fn render_multi_output_with_variants(
     render_with_variants(&stage0, variant_config, config)
 }
 
+/// The lowest Python version (`major.minor`) that a `python` variant value allows, e.g. `3.10`
+/// for `3.10.* *_cpython`
+fn python_lower_bound(value: &Variable) -> Option<rattler_conda_types::Version> {
+    let value = value.to_string();
+    let version = value
+        .split_whitespace()
+        .next()?
+        .trim_start_matches(['=', '>']);
+    let major_minor = version.split('.').take(2).collect::<Vec<_>>().join(".");
+    major_minor.parse().ok()
+}
+
+/// Build version independent (abi3) Python packages only once.
+///
+/// A package with `build.python.version_independent` works with every Python version from the
+/// one it was built against. Of the variants that only differ in `python`, only the one with
+/// the lowest Python version is kept, and a `python` run requirement without a version (or a
+/// missing one) becomes `python >=<major.minor>`.
+/// Free-threaded Python has no stable ABI, so these variants are built separately.
+fn collapse_version_independent_variants(results: Vec<RenderedVariant>) -> Vec<RenderedVariant> {
+    let python_key = NormalizedKey::from("python");
+    let is_collapsible = |result: &RenderedVariant| {
+        result.recipe.build.python.version_independent
+            && !result.recipe.build.skip
+            && result
+                .variant
+                .get(&python_key)
+                .is_some_and(|python| !is_free_threaded_python(&python.to_string()))
+    };
+    let group_key = |result: &RenderedVariant| {
+        let mut variant = result.variant.clone();
+        variant.remove(&python_key);
+        (result.recipe.package().name().clone(), variant)
+    };
+
+    // the lowest python version of every group of variants
+    let mut lowest: Vec<(_, rattler_conda_types::Version, usize)> = Vec::new();
+    for (idx, result) in results.iter().enumerate() {
+        if !is_collapsible(result) {
+            continue;
+        }
+        let Some(version) = python_lower_bound(&result.variant[&python_key]) else {
+            continue;
+        };
+        let key = group_key(result);
+        match lowest.iter_mut().find(|(group, _, _)| group == &key) {
+            Some(entry) if version < entry.1 => *entry = (key, version, idx),
+            Some(_) => {}
+            None => lowest.push((key, version, idx)),
+        }
+    }
+
+    results
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, mut result)| {
+            if !is_collapsible(&result) {
+                return Some(result);
+            }
+            let key = group_key(&result);
+            let Some((_, version, kept)) = lowest.iter().find(|(group, _, _)| group == &key) else {
+                return Some(result);
+            };
+            if *kept != idx {
+                return None;
+            }
+
+            // keep the run requirements as they are if the bound is not a valid match spec
+            let Ok(python_spec) =
+                MatchSpec::from_str(&format!("python >={version}"), ParseStrictness::Strict)
+            else {
+                return Some(result);
+            };
+            let run = &mut result.recipe.requirements.run;
+            let python = run.iter_mut().find_map(|dependency| match dependency {
+                Dependency::Spec(spec)
+                    if spec
+                        .name
+                        .as_ref()
+                        .is_some_and(|name| name.to_string() == "python") =>
+                {
+                    Some(spec)
+                }
+                _ => None,
+            });
+            match python {
+                Some(spec) if spec.version.is_none() && spec.build.is_none() => {
+                    **spec = python_spec;
+                }
+                Some(_) => {}
+                None => run.push(Dependency::Spec(Box::new(python_spec))),
+            }
+            Some(result)
+        })
+        .collect()
+}
+
 /// Internal unified function to render both single and multi-output recipes
 fn render_with_variants(
     stage0_recipe: &Stage0Recipe,
@@ -1195,6 +1292,9 @@ fn render_with_variants(
         }
     }
 
+    // Build version independent (abi3) packages only for the lowest python version
+    let results = collapse_version_independent_variants(results);
+
     // Sort variants topologically by pin_subpackage dependencies
     let mut results = topological_sort_variants(results)?;
 
@@ -1300,6 +1400,69 @@ python:
         assert_eq!(rendered.len(), 2);
     }
 
+    #[test]
+    fn test_render_version_independent() {
+        let recipe_yaml = r#"
+package:
+  name: test-abi3
+  version: "1.0.0"
+
+build:
+  python:
+    version_independent: true
+
+requirements:
+  build:
+    - ${{ compiler('c') }}
+  host:
+    - python
+    - python-abi3
+  run:
+    - python
+"#;
+
+        let variant_yaml = r#"
+python:
+  - "3.11.* *_cpython"
+  - "3.9.* *_cpython"
+  - "3.10.* *_cpython"
+  - "3.13.* *_cp313t"
+c_compiler:
+  - gcc
+  - clang
+"#;
+
+        let stage0_recipe = stage0::parse_recipe_or_multi_from_source(recipe_yaml).unwrap();
+        let variant_config = VariantConfig::from_yaml_str(variant_yaml).unwrap();
+
+        let rendered =
+            render_recipe_with_variant_config(&stage0_recipe, &variant_config, RenderConfig::new())
+                .unwrap();
+
+        // one package per compiler, built against the lowest python version, and the
+        // free-threaded packages that have no stable ABI
+        assert_eq!(rendered.len(), 4);
+        let (free_threaded, rendered): (Vec<_>, Vec<_>) = rendered.iter().partition(|variant| {
+            variant.variant.get(&"python".into()).unwrap().to_string() == "3.13.* *_cp313t"
+        });
+        assert_eq!(free_threaded.len(), 2);
+        assert_eq!(free_threaded[0].recipe.requirements.run.len(), 1);
+        assert!(
+            matches!(&free_threaded[0].recipe.requirements.run[0], Dependency::Spec(spec) if spec.to_string() == "python")
+        );
+        for variant in &rendered {
+            assert_eq!(
+                variant.variant.get(&"python".into()).unwrap().to_string(),
+                "3.9.* *_cpython"
+            );
+            let run = &variant.recipe.requirements.run;
+            assert_eq!(run.len(), 1);
+            assert!(
+                matches!(&run[0], Dependency::Spec(spec) if spec.to_string() == "python >=3.9")
+            );
+        }
+    }
+
     #[test]
     fn test_render_multi_output_simple() {
         let recipe_yaml = r#"
@@ -158,7 +158,7 @@ impl Script {
         jinja_renderer: Option<F>,
         sandbox_config: Option<&SandboxConfiguration>,
         debug: Debug,
-    ) -> Result<(), crate::InterpreterError>
+    ) -> Result<std::process::Output, crate::InterpreterError>
     where
         F: Fn(&str) -> Result<String, String>,
     {
@@ -229,9 +229,7 @@ impl Script {
             debug,
         };
 
-        crate::execution::run_script(exec_args, interpreter).await?;
-
-        Ok(())
+        crate::execution::run_script(exec_args, interpreter).await
     }
 
     fn find_file(&self, recipe_dir: &Path, extensions: &[&str], path: &Path) -> Option<PathBuf> {
@@ -409,29 +407,24 @@ use crate::interpreter::{
 };
 use rattler_shell::shell;
 
-/// Run a script with the given execution arguments and interpreter
+/// Run a script with the given execution arguments and interpreter, returning the captured
+/// output of the script
 pub async fn run_script(
     exec_args: ExecutionArgs,
     interpreter: &str,
-) -> Result<(), crate::InterpreterError> {
+) -> Result<std::process::Output, crate::InterpreterError> {
     match interpreter {
-        "nushell" | "nu" => NuShellInterpreter.run(exec_args).await?,
-        "bash" => BashInterpreter.run(exec_args).await?,
-        "cmd" => CmdExeInterpreter.run(exec_args).await?,
-        "python" => PythonInterpreter.run(exec_args).await?,
-        "perl" => PerlInterpreter.run(exec_args).await?,
-        "rscript" => RInterpreter.run(exec_args).await?,
-        "ruby" => RubyInterpreter.run(exec_args).await?,
-        "node" | "nodejs" => NodeJsInterpreter.run(exec_args).await?,
-        "powershell" => PowerShellInterpreter.run(exec_args).await?,
-        _ => {
-            return Err(
-                std::io::Error::other(format!("Unsupported interpreter: {}", interpreter)).into(),
-            );
-        }
-    };
-
-    Ok(())
+        "nushell" | "nu" => NuShellInterpreter.run(exec_args).await,
+        "bash" => BashInterpreter.run(exec_args).await,
+        "cmd" => CmdExeInterpreter.run(exec_args).await,
+        "python" => PythonInterpreter.run(exec_args).await,
+        "perl" => PerlInterpreter.run(exec_args).await,
+        "rscript" => RInterpreter.run(exec_args).await,
+        "ruby" => RubyInterpreter.run(exec_args).await,
+        "node" | "nodejs" => NodeJsInterpreter.run(exec_args).await,
+        "powershell" => PowerShellInterpreter.run(exec_args).await,
+        _ => Err(std::io::Error::other(format!("Unsupported interpreter: {}", interpreter)).into()),
+    }
 }
 
 /// Create build script files without executing them
@@ -482,6 +475,108 @@ fn find_rattler_sandbox() -> Option<PathBuf> {
     which::which("rattler-sandbox").ok()
 }
 
+/// A handler for the lines of output of a process, the second argument is `true` for stderr
+pub type OutputHandler = std::sync::Arc<dyn Fn(&str, bool) + Send + Sync>;
+
+tokio::task_local! {
+    static OUTPUT_HANDLER: OutputHandler;
+}
+
+/// Run `future` and pass every line of output of the processes that it runs with
+/// [`run_process_with_replacements`] to `handler` (after the replacements are applied).
+pub async fn with_output_handler<F: std::future::Future>(
+    handler: OutputHandler,
+    future: F,
+) -> F::Output {
+    OUTPUT_HANDLER.scope(handler, future).await
+}
+
+tokio::task_local! {
+    static KILL_PROCESS_TREE: ();
+}
+
+/// Run `future` and start the processes that it runs with [`run_process_with_replacements`] in
+/// their own process group (a job object on Windows). When such a process exits, or the future
+/// is dropped before that (e.g. on a timeout), the whole group is killed, including the
+/// processes that the script started in the background.
+pub async fn with_process_tree_cleanup<F: std::future::Future>(future: F) -> F::Output {
+    KILL_PROCESS_TREE.scope((), future).await
+}
+
+/// Kills the process group (or job object) of a process when it is dropped
+struct ProcessTreeGuard {
+    #[cfg(unix)]
+    process_group: Option<i32>,
+    // the `HANDLE` of the job object, as an integer so that the guard is `Send`
+    #[cfg(windows)]
+    job: Option<isize>,
+}
+
+impl ProcessTreeGuard {
+    /// A guard for `child`, which was started in its own process group on Unix
+    fn new(child: &tokio::process::Child) -> Self {
+        #[cfg(unix)]
+        {
+            Self {
+                process_group: child.id().map(|pid| pid as i32),
+            }
+        }
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::{
+                Foundation::CloseHandle,
+                System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW},
+            };
+            let Some(process) = child.raw_handle() else {
+                return Self { job: None };
+            };
+            // SAFETY: the job object is closed when the guard is dropped, the process handle is
+            // valid as long as `child` is alive
+            let job = unsafe {
+                let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+                if job.is_null() {
+                    None
+                } else if AssignProcessToJobObject(job, process as _) == 0 {
+                    CloseHandle(job);
+                    None
+                } else {
+                    Some(job as isize)
+                }
+            };
+            Self { job }
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            let _ = child;
+            Self {}
+        }
+    }
+}
+
+impl Drop for ProcessTreeGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(process_group) = self.process_group.take() {
+            // SAFETY: `killpg` has no memory safety requirements, a group that no longer exists
+            // is reported with `ESRCH`, which is ignored
+            unsafe {
+                libc::killpg(process_group, libc::SIGKILL);
+            }
+        }
+        #[cfg(windows)]
+        if let Some(job) = self.job.take() {
+            use windows_sys::Win32::{
+                Foundation::CloseHandle, System::JobObjects::TerminateJobObject,
+            };
+            // SAFETY: `job` is the job object that was created in `ProcessTreeGuard::new`
+            unsafe {
+                TerminateJobObject(job as _, 1);
+                CloseHandle(job as _);
+            }
+        }
+    }
+}
+
 /// Spawns a process and replaces the given strings in the output with the given replacements.
 /// This is used to replace the host prefix with $PREFIX and the build prefix with $BUILD_PREFIX
 pub async fn run_process_with_replacements(
@@ -533,9 +628,19 @@ pub async fn run_process_with_replacements(
         .args(&args[1..])
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+        .stderr(Stdio::piped())
+        // kill the process if the caller stops waiting for it (e.g. on a test timeout)
+        .kill_on_drop(true);
+
+    let kill_process_tree = KILL_PROCESS_TREE.try_with(|_| ()).is_ok();
+    #[cfg(unix)]
+    if kill_process_tree {
+        // the children of the process join its group, so that they are killed with it
+        command.process_group(0);
+    }
 
     let mut child = command.spawn()?;
+    let _guard = kill_process_tree.then(|| ProcessTreeGuard::new(&child));
 
     let stdout = child.stdout.take().expect("Failed to take stdout");
     let stderr = child.stderr.take().expect("Failed to take stderr");
@@ -579,6 +684,7 @@ pub async fn run_process_with_replacements(
                     tracing::warn!("Failed to write newline to build log: {:?}", e);
                 }
 
+                let _ = OUTPUT_HANDLER.try_with(|handler| handler(&filtered_line, is_stderr));
                 tracing::info!("{}", filtered_line);
             }
             Ok(None) if !is_stderr => closed.0 = true,
@@ -614,6 +720,41 @@ mod tests {
     use super::*;
     use tokio_util::bytes::BytesMut;
 
+    #[tokio::test]
+    #[cfg(target_os = "linux")]
+    async fn test_timeout_kills_background_children() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("sleep.pid");
+        let script = format!("sleep 60 & echo $! > {}; wait", pid_file.display());
+
+        let args = ["bash", "-c", script.as_str()];
+        let replacements = HashMap::new();
+        let run = run_process_with_replacements(&args, dir.path(), &replacements, None);
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            with_process_tree_cleanup(run),
+        )
+        .await;
+        assert!(result.is_err(), "the script should have timed out");
+
+        let pid = fs_err::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .to_string();
+        // the killed `sleep` is gone, or a zombie if nothing reaps orphans (e.g. in a container)
+        let is_running = || {
+            fs_err::read_to_string(format!("/proc/{pid}/stat"))
+                .is_ok_and(|stat| !stat.rsplit(')').next().unwrap().trim().starts_with('Z'))
+        };
+        for _ in 0..50 {
+            if !is_running() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        panic!("the background `sleep` ({pid}) is still running after the timeout");
+    }
+
     #[test]
     fn test_cmd_errorlevel_injected() {
         use crate::script::{Script, ScriptContent};
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 use rattler_conda_types::Platform;
 use rattler_shell::shell;
@@ -36,7 +36,7 @@ fn print_debug_info(args: &ExecutionArgs) -> String {
 }
 
 impl Interpreter for BashInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let script = self.get_script(&args, shell::Bash).unwrap();
 
         let build_env_path = args.work_dir.join("build_env.sh");
@@ -79,12 +79,10 @@ impl Interpreter for BashInterpreter {
             let status_code = output.status.code().unwrap_or(1);
             tracing::error!("Script failed with status {}", status_code);
             tracing::error!("{}", print_debug_info(&args));
-            return Err(InterpreterError::ExecutionFailed(std::io::Error::other(
-                "Script failed".to_string(),
-            )));
+            return Err(InterpreterError::NonZeroExitCode(output));
         }
 
-        Ok(())
+        Ok(output)
     }
 
     async fn find_interpreter(
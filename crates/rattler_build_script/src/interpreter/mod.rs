@@ -9,7 +9,7 @@ mod r;
 mod ruby;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 pub use bash::BashInterpreter;
 pub use cmd_exe::CmdExeInterpreter;
@@ -42,6 +42,11 @@ pub enum InterpreterError {
     /// interpreter is not found
     #[error("IO Error: {0}")]
     ExecutionFailed(#[from] std::io::Error),
+
+    /// This error is returned when the script exits with a non-zero exit code. It contains the
+    /// captured output of the script.
+    #[error("Script failed with exit code {}", .0.status.code().unwrap_or(1))]
+    NonZeroExitCode(Output),
 }
 
 pub const BASH_PREAMBLE: &str = r#"#!/usr/bin/env bash
@@ -130,7 +135,7 @@ pub trait Interpreter {
         Ok(shell_script.contents()?)
     }
 
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError>;
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError>;
 
     #[allow(dead_code)]
     async fn find_interpreter(
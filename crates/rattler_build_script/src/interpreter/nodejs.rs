@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 use rattler_conda_types::Platform;
 
@@ -10,7 +10,7 @@ pub struct NodeJsInterpreter;
 
 // NodeJS interpreter calls either bash or cmd.exe interpreter for activation and then runs Node script
 impl Interpreter for NodeJsInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let node_script = args.work_dir.join("conda_build_script.js");
         tokio::fs::write(&node_script, args.script.script()).await?;
 
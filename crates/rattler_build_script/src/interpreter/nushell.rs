@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, process::Output};
 
 use itertools::Itertools;
 use rattler_conda_types::Platform;
@@ -23,7 +23,7 @@ if not ("CONDA_BUILD" in $env) {
 "#;
 
 impl Interpreter for NuShellInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let host_shell_type = ShellEnum::default();
         let nushell = ShellEnum::NuShell(Default::default());
 
@@ -132,12 +132,10 @@ impl Interpreter for NuShellInterpreter {
             let status_code = output.status.code().unwrap_or(1);
             tracing::error!("Script failed with status {}", status_code);
             tracing::error!("Work directory: '{}'", args.work_dir.display());
-            return Err(InterpreterError::ExecutionFailed(std::io::Error::other(
-                "Script failed".to_string(),
-            )));
+            return Err(InterpreterError::NonZeroExitCode(output));
         }
 
-        Ok(())
+        Ok(output)
     }
 
     async fn find_interpreter(
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 use rattler_conda_types::Platform;
 
@@ -10,7 +10,7 @@ pub struct PerlInterpreter;
 
 // Perl interpreter calls either bash or cmd.exe interpreter for activation and then runs Perl script
 impl Interpreter for PerlInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let perl_script = args.work_dir.join("conda_build_script.pl");
         tokio::fs::write(&perl_script, args.script.script()).await?;
 
@@ -1,5 +1,7 @@
-use std::path::PathBuf;
-use std::process::Command;
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+};
 
 use rattler_conda_types::Platform;
 
@@ -50,7 +52,7 @@ fn detect_powershell() -> (&'static str, bool) {
 // PowerShell interpreter: writes a .ps1 script then delegates to cmd.exe (Windows) or bash (Unix)
 // to run it via the pwsh/powershell command.
 impl Interpreter for PowerShellInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let (shell_cmd, new_enough) = detect_powershell();
 
         if !new_enough {
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 use rattler_conda_types::Platform;
 
@@ -10,7 +10,7 @@ pub struct RInterpreter;
 
 // R interpreter calls either bash or cmd.exe interpreter for activation and then runs R script
 impl Interpreter for RInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let script = args.script.script();
         let r_script = args.work_dir.join("conda_build_script.R");
         tokio::fs::write(&r_script, script).await?;
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::Output};
 
 use rattler_conda_types::Platform;
 
@@ -10,7 +10,7 @@ pub struct RubyInterpreter;
 
 // Ruby interpreter calls either bash or cmd.exe interpreter for activation and then runs Ruby script
 impl Interpreter for RubyInterpreter {
-    async fn run(&self, args: ExecutionArgs) -> Result<(), InterpreterError> {
+    async fn run(&self, args: ExecutionArgs) -> Result<Output, InterpreterError> {
         let ruby_script = args.work_dir.join("conda_build_script.rb");
         tokio::fs::write(&ruby_script, args.script.script()).await?;
 
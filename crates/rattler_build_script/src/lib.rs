@@ -16,8 +16,8 @@ mod interpreter;
 
 #[cfg(feature = "execution")]
 pub use execution::{
-    Debug, ExecutionArgs, ResolvedScriptContents, create_build_script,
-    run_process_with_replacements, run_script,
+    Debug, ExecutionArgs, OutputHandler, ResolvedScriptContents, create_build_script,
+    run_process_with_replacements, run_script, with_output_handler, with_process_tree_cleanup,
 };
 #[cfg(feature = "execution")]
 pub use interpreter::InterpreterError;
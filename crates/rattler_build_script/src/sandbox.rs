@@ -34,6 +34,17 @@ pub struct SandboxArguments {
     /// Overwrite the default sandbox configuration
     #[clap(long, action, help_heading = "Sandbox arguments")]
     pub overwrite_default_sandbox_config: bool,
+
+    /// Run the build script hermetically: implies `--sandbox` without network access, only
+    /// allows reading the build directories, the caches and the system, and fails if the
+    /// script uses the home directory, `/usr/local` or a system package manager
+    #[clap(
+        long,
+        action,
+        conflicts_with = "allow_network",
+        help_heading = "Sandbox arguments"
+    )]
+    pub hermetic: bool,
 }
 
 /// Configuration for the sandbox
@@ -43,6 +54,8 @@ pub struct SandboxConfiguration {
     read: Vec<PathBuf>,
     read_execute: Vec<PathBuf>,
     read_write: Vec<PathBuf>,
+    #[serde(default)]
+    hermetic: bool,
 }
 
 impl Display for SandboxConfiguration {
@@ -57,6 +70,9 @@ impl Display for SandboxConfiguration {
                 console::Emoji("❌", " ")
             }
         )?;
+        if self.hermetic {
+            writeln!(f, "Hermetic: {}", console::Emoji("✅", " "))?;
+        }
 
         writeln!(f, "\n{} Read-only paths:", console::Emoji("📁 ", ""))?;
         for path in &self.read {
@@ -99,6 +115,7 @@ impl SandboxConfiguration {
             read: vec!["/".into()],
             read_execute,
             read_write,
+            hermetic: false,
         }
     }
 
@@ -140,9 +157,56 @@ impl SandboxConfiguration {
             read: vec!["/".into()],
             read_execute,
             read_write,
+            hermetic: false,
         }
     }
 
+    /// Restrict the configuration for a hermetic build: no network access, and instead of the
+    /// entire filesystem only the system directories can be read (the build directories and
+    /// caches are added with [`Self::with_read`]). In particular, the home directory,
+    /// `/usr/local` and the prefixes of system package managers (e.g. `/opt/homebrew`) cannot be
+    /// read.
+    pub fn hermetic(self) -> Self {
+        let system: &[&str] = if cfg!(target_os = "macos") {
+            &[
+                "/System",
+                "/Library/Developer",
+                "/Applications/Xcode.app",
+                "/usr/lib",
+                "/usr/share",
+                "/private/etc",
+                "/private/var/db",
+                "/dev",
+            ]
+        } else {
+            &["/etc", "/usr/share", "/dev", "/proc", "/sys"]
+        };
+        let mut read = self
+            .read
+            .into_iter()
+            .filter(|path| path != Path::new("/"))
+            .collect::<Vec<_>>();
+        read.extend(system.iter().map(PathBuf::from));
+
+        Self {
+            allow_network: false,
+            read,
+            hermetic: true,
+            ..self
+        }
+    }
+
+    /// Whether the build is hermetic (see [`Self::hermetic`])
+    pub fn is_hermetic(&self) -> bool {
+        self.hermetic
+    }
+
+    /// Allow read access to the given paths
+    pub fn with_read(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.read.extend(paths);
+        self
+    }
+
     /// Add the current working directory to the list of allowed paths
     /// Adds the parent directory of the current working directory to the list of allowed paths
     /// for read_execute and read_write
@@ -162,6 +226,7 @@ impl SandboxConfiguration {
             read: self.read.clone(),
             read_execute,
             read_write,
+            hermetic: self.hermetic,
         }
     }
 
@@ -194,7 +259,7 @@ impl SandboxConfiguration {
 
 impl From<SandboxArguments> for Option<SandboxConfiguration> {
     fn from(args: SandboxArguments) -> Self {
-        if !args.sandbox {
+        if !args.sandbox && !args.hermetic {
             return None;
         }
 
@@ -225,6 +290,10 @@ impl From<SandboxArguments> for Option<SandboxConfiguration> {
 
         result.allow_network = args.allow_network;
 
+        if args.hermetic {
+            result = result.hermetic();
+        }
+
         Some(result)
     }
 }
@@ -29,6 +29,15 @@ pub trait ProgressHandler: Send + Sync {
 
     /// Called when extraction completes
     fn on_extraction_complete(&self, path: &Path);
+
+    /// Called when the verification of the checksums of a file starts
+    fn on_hash_start(&self, _path: &Path, _total_size: u64) {}
+
+    /// Called periodically while the checksums of a file are verified
+    fn on_hash_progress(&self, _path: &Path, _hashed: u64, _total: u64) {}
+
+    /// Called when the checksums of a file are verified
+    fn on_hash_complete(&self, _path: &Path) {}
 }
 
 impl Default for SourceCacheBuilder {
@@ -73,7 +82,7 @@ impl SourceCacheBuilder {
         self
     }
 
-    /// Set a progress handler for download and extraction operations
+    /// Set a progress handler for download, checksum verification and extraction operations
     pub fn progress_handler<H: ProgressHandler + 'static>(mut self, handler: H) -> Self {
         self.progress_handler = Some(Box::new(handler));
         self
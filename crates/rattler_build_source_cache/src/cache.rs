@@ -1,7 +1,10 @@
 //! Main source cache implementation
 
 use flate2::read::GzDecoder;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
 use tokio::io::AsyncWriteExt;
 
 use crate::{
@@ -9,7 +12,7 @@ use crate::{
     error::CacheError,
     index::{CacheEntry, CacheIndex, SourceType},
     lock::LockManager,
-    source::{AttestationVerification, Checksum, GitSource, Source, UrlSource},
+    source::{AttestationVerification, Checksum, ChecksumMismatch, GitSource, Source, UrlSource},
 };
 use rattler_build_networking::BaseClient;
 use rattler_git::resolver::GitResolver;
@@ -21,6 +24,8 @@ pub struct SourceResult {
     pub path: PathBuf,
     /// For git sources, the resolved commit SHA
     pub git_commit: Option<String>,
+    /// Whether the source was found in the cache (instead of being downloaded or cloned)
+    pub cached: bool,
 }
 
 /// The main source cache that handles Git, URL, and Path sources
@@ -65,6 +70,7 @@ impl SourceCache {
                 Ok(SourceResult {
                     path: path.clone(),
                     git_commit: None,
+                    cached: false,
                 })
             }
         }
@@ -89,6 +95,7 @@ impl SourceCache {
                 return Ok(SourceResult {
                     path: cache_path,
                     git_commit: entry.git_commit.clone(),
+                    cached: true,
                 });
             }
         }
@@ -159,6 +166,7 @@ impl SourceCache {
         Ok(SourceResult {
             path: repo_path,
             git_commit: Some(commit_hash),
+            cached: false,
         })
     }
 
@@ -283,10 +291,11 @@ impl SourceCache {
                 )
                 .await
             {
-                Ok(path) => {
+                Ok((path, cached)) => {
                     return Ok(SourceResult {
                         path,
                         git_commit: None,
+                        cached,
                     });
                 }
                 Err(e) => {
@@ -299,14 +308,15 @@ impl SourceCache {
         Err(last_error.unwrap_or_else(|| CacheError::Other("No URLs provided".to_string())))
     }
 
-    /// Try to get a single URL from cache or download it
+    /// Try to get a single URL from cache or download it. Returns the path and whether it was
+    /// found in the cache.
     async fn try_url(
         &self,
         url: &url::Url,
         checksums: &[Checksum],
         file_name: Option<&str>,
         attestation: Option<&AttestationVerification>,
-    ) -> Result<PathBuf, CacheError> {
+    ) -> Result<(PathBuf, bool), CacheError> {
         let key = CacheIndex::generate_cache_key(url, checksums);
 
         // Acquire lock for this cache entry
@@ -334,17 +344,14 @@ impl SourceCache {
                     "Found extracted source in cache: {}",
                     extracted_path.display()
                 );
-                return Ok(extracted_path);
+                return Ok((extracted_path, true));
             }
 
             // Otherwise return the archive file
             if cache_path.exists() {
                 // Validate all checksums if provided
                 if !checksums.is_empty() {
-                    let mismatch = checksums
-                        .iter()
-                        .find_map(|cs| cs.validate(&cache_path).err());
-                    if mismatch.is_some() {
+                    if self.validate_checksums(checksums, &cache_path).is_err() {
                         tracing::warn!("Checksum validation failed, re-downloading");
                         fs_err::tokio::remove_file(&cache_path).await?;
                     } else {
@@ -358,7 +365,7 @@ impl SourceCache {
                         }
                         self.index.touch(&key).await?;
                         tracing::info!("Found source in cache: {}", cache_path.display());
-                        return Ok(cache_path);
+                        return Ok((cache_path, true));
                     }
                 } else {
                     // Re-verify attestation if configured but not yet verified for this entry
@@ -371,7 +378,7 @@ impl SourceCache {
                     }
                     self.index.touch(&key).await?;
                     tracing::info!("Found source in cache: {}", cache_path.display());
-                    return Ok(cache_path);
+                    return Ok((cache_path, true));
                 }
             }
         }
@@ -381,16 +388,14 @@ impl SourceCache {
         let (cache_path, actual_filename) = self.download_url(url, &key).await?;
 
         // Validate all checksums
-        for cs in checksums {
-            if let Err(mismatch) = cs.validate(&cache_path) {
-                fs_err::tokio::remove_file(&cache_path).await?;
-                return Err(CacheError::ValidationFailed {
-                    path: cache_path,
-                    expected: mismatch.expected,
-                    actual: mismatch.actual,
-                    kind: mismatch.kind.to_string(),
-                });
-            }
+        if let Err(mismatch) = self.validate_checksums(checksums, &cache_path) {
+            fs_err::tokio::remove_file(&cache_path).await?;
+            return Err(CacheError::ValidationFailed {
+                path: cache_path,
+                expected: mismatch.expected,
+                actual: mismatch.actual,
+                kind: mismatch.kind.to_string(),
+            });
         }
 
         // Perform attestation verification if configured
@@ -440,7 +445,7 @@ impl SourceCache {
 
         self.index.insert(key, entry).await?;
 
-        Ok(final_path.unwrap_or(cache_path))
+        Ok((final_path.unwrap_or(cache_path), false))
     }
 
     /// Download a URL to the cache
@@ -541,6 +546,27 @@ impl SourceCache {
         Ok((final_path, actual_filename))
     }
 
+    /// Validate a file against all checksums, reporting the progress to the progress handler
+    fn validate_checksums(
+        &self,
+        checksums: &[Checksum],
+        path: &Path,
+    ) -> Result<(), ChecksumMismatch> {
+        let Some(handler) = &self.progress_handler else {
+            return Checksum::validate_all(checksums, path, &|_| {});
+        };
+
+        let total = fs_err::metadata(path).map_or(0, |m| m.len());
+        let hashed = AtomicU64::new(0);
+        handler.on_hash_start(path, total);
+        let result = Checksum::validate_all(checksums, path, &|bytes| {
+            let hashed = hashed.fetch_add(bytes, Ordering::Relaxed) + bytes;
+            handler.on_hash_progress(path, hashed, total);
+        });
+        handler.on_hash_complete(path);
+        result
+    }
+
     /// Check if a file should be extracted based on its filename extension
     pub(crate) fn should_extract(&self, path: &Path) -> bool {
         let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
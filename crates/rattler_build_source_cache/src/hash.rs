@@ -0,0 +1,94 @@
+//! Hashing of (large) files.
+//!
+//! Files are memory-mapped and hashed in chunks, so that the progress can be reported. When the
+//! digests of several algorithms are needed (e.g. a sha256 and a md5 checksum), they are computed
+//! at the same time on separate threads, so that the file is only read once.
+
+use std::{io, path::Path};
+
+use md5::Md5;
+use sha2::{Digest, Sha256, digest::Output};
+
+use crate::source::ChecksumKind;
+
+/// The size of the chunks after which the progress is reported
+const CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// A memory-mapped file, or nothing for empty files (which cannot be mapped on all platforms)
+struct MappedFile(Option<memmap2::Mmap>);
+
+impl MappedFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = fs_err::File::open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Ok(Self(None));
+        }
+        // SAFETY: the mapping is only read. A file that is modified while it is hashed results in
+        // a wrong digest, just like when it is read with `read`.
+        let mmap = unsafe { memmap2::Mmap::map(file.file()) }?;
+        #[cfg(unix)]
+        let _ = mmap.advise(memmap2::Advice::Sequential);
+        Ok(Self(Some(mmap)))
+    }
+
+    fn data(&self) -> &[u8] {
+        self.0.as_deref().unwrap_or_default()
+    }
+}
+
+/// Hash `data` in chunks, calling `progress` with the number of bytes of every chunk
+fn digest_chunks<D: Digest>(data: &[u8], progress: &(dyn Fn(u64) + Sync)) -> Output<D> {
+    let mut hasher = D::new();
+    for chunk in data.chunks(CHUNK_SIZE) {
+        hasher.update(chunk);
+        progress(chunk.len() as u64);
+    }
+    hasher.finalize()
+}
+
+fn digest_kind(kind: ChecksumKind, data: &[u8], progress: &(dyn Fn(u64) + Sync)) -> Vec<u8> {
+    match kind {
+        ChecksumKind::Sha256 => digest_chunks::<Sha256>(data, progress).to_vec(),
+        ChecksumKind::Md5 => digest_chunks::<Md5>(data, progress).to_vec(),
+    }
+}
+
+/// Compute the digest of the file at `path`. `progress` is called with the number of bytes that
+/// were hashed since the last call.
+pub fn compute_file_digest<D: Digest>(
+    path: &Path,
+    progress: &(dyn Fn(u64) + Sync),
+) -> io::Result<Output<D>> {
+    let file = MappedFile::open(path)?;
+    Ok(digest_chunks::<D>(file.data(), progress))
+}
+
+/// Compute the digests of the file at `path` for all `kinds`, in the same order. The digests are
+/// computed in parallel and the file is only read once. `progress` is called with the number of
+/// bytes that were hashed since the last call.
+pub fn compute_file_digests(
+    path: &Path,
+    kinds: &[ChecksumKind],
+    progress: &(dyn Fn(u64) + Sync),
+) -> io::Result<Vec<Vec<u8>>> {
+    let file = MappedFile::open(path)?;
+    let data = file.data();
+    let Some((first, rest)) = kinds.split_first() else {
+        return Ok(Vec::new());
+    };
+
+    Ok(std::thread::scope(|scope| {
+        let others = rest
+            .iter()
+            .map(|kind| scope.spawn(move || digest_kind(*kind, data, &|_| {})))
+            .collect::<Vec<_>>();
+        // the progress is reported by the first digest only
+        let mut digests = vec![digest_kind(*first, data, progress)];
+        digests.extend(
+            others
+                .into_iter()
+                .map(|handle| handle.join().expect("hashing thread panicked")),
+        );
+        digests
+    }))
+}
@@ -6,6 +6,7 @@
 pub mod builder;
 pub mod cache;
 pub mod error;
+pub mod hash;
 pub mod index;
 pub mod lock;
 #[cfg(feature = "sigstore")]
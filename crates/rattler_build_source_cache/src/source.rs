@@ -23,46 +23,41 @@ impl Checksum {
     /// Validate a file against this checksum.
     /// Returns `Ok(())` if the checksum matches, or `Err(ChecksumMismatch)` with details.
     pub fn validate(&self, path: &std::path::Path) -> Result<(), ChecksumMismatch> {
-        use md5::{Digest, Md5};
-        use sha2::Sha256;
-        use std::io::Read;
-
-        let mut file = std::fs::File::open(path).map_err(|e| ChecksumMismatch {
-            expected: self.to_hex(),
-            actual: format!("<failed to open file: {e}>"),
-            kind: self.kind(),
-        })?;
+        Self::validate_all(std::slice::from_ref(self), path, &|_| {})
+    }
 
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)
-            .map_err(|e| ChecksumMismatch {
-                expected: self.to_hex(),
+    /// Validate a file against all the given checksums. The file is only read once and the
+    /// digests are computed in parallel. `progress` is called with the number of bytes that were
+    /// hashed since the last call. Returns the first checksum that does not match.
+    pub fn validate_all(
+        checksums: &[Checksum],
+        path: &std::path::Path,
+        progress: &(dyn Fn(u64) + Sync),
+    ) -> Result<(), ChecksumMismatch> {
+        if checksums.is_empty() {
+            return Ok(());
+        }
+        let kinds = checksums.iter().map(Checksum::kind).collect::<Vec<_>>();
+        let digests = crate::hash::compute_file_digests(path, &kinds, progress).map_err(|e| {
+            let first = &checksums[0];
+            ChecksumMismatch {
+                expected: first.to_hex(),
                 actual: format!("<failed to read file: {e}>"),
-                kind: self.kind(),
-            })?;
-
-        let actual_hex = match self {
-            Checksum::Sha256(_) => {
-                let mut hasher = Sha256::new();
-                hasher.update(&buffer);
-                hex::encode(hasher.finalize())
+                kind: first.kind(),
             }
-            Checksum::Md5(_) => {
-                let mut hasher = Md5::new();
-                hasher.update(&buffer);
-                hex::encode(hasher.finalize())
+        })?;
+
+        for (checksum, digest) in checksums.iter().zip(digests) {
+            let actual_hex = hex::encode(digest);
+            if actual_hex != checksum.to_hex() {
+                return Err(ChecksumMismatch {
+                    expected: checksum.to_hex(),
+                    actual: actual_hex,
+                    kind: checksum.kind(),
+                });
             }
-        };
-
-        if actual_hex == self.to_hex() {
-            Ok(())
-        } else {
-            Err(ChecksumMismatch {
-                expected: self.to_hex(),
-                actual: actual_hex,
-                kind: self.kind(),
-            })
         }
+        Ok(())
     }
 
     /// Returns the kind of this checksum.
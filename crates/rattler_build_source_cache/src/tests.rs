@@ -108,6 +108,41 @@ mod source_cache_tests {
         assert_ne!(err.expected, err.actual);
     }
 
+    #[test]
+    fn test_validate_all_checksums() {
+        use md5::Md5;
+        use sha2::{Digest, Sha256};
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let data = vec![7u8; 20 * 1024 * 1024];
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test_file");
+        fs_err::write(&file_path, &data).unwrap();
+
+        let sha256 = Checksum::Sha256(Sha256::digest(&data).to_vec());
+        let md5 = Checksum::Md5(Md5::digest(&data).to_vec());
+        let hashed = AtomicU64::new(0);
+        Checksum::validate_all(&[sha256.clone(), md5], &file_path, &|bytes| {
+            hashed.fetch_add(bytes, Ordering::Relaxed);
+        })
+        .unwrap();
+        assert_eq!(hashed.into_inner(), data.len() as u64);
+
+        // the mismatching checksum is reported
+        let wrong_md5 = Checksum::Md5(Md5::digest(b"other").to_vec());
+        let err = Checksum::validate_all(&[sha256, wrong_md5], &file_path, &|_| {}).unwrap_err();
+        assert!(matches!(err.kind, source::ChecksumKind::Md5));
+
+        // empty files cannot be memory-mapped on all platforms
+        let empty = temp_dir.path().join("empty");
+        fs_err::write(&empty, b"").unwrap();
+        assert!(
+            Checksum::Sha256(Sha256::digest(b"").to_vec())
+                .validate(&empty)
+                .is_ok()
+        );
+    }
+
     #[tokio::test]
     async fn test_path_source_passthrough() {
         let temp_dir = TempDir::new().unwrap();
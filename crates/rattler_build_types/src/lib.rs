@@ -19,3 +19,28 @@ pub fn short_version(input: &str, length: u32) -> String {
     }
     result
 }
+
+/// Whether a `python` variant or the build string of a `python` package selects the
+/// free-threaded ABI, e.g. `3.13.* *_cp313t` or `h4f43103_0_cp313t`.
+pub fn is_free_threaded_python(input: &str) -> bool {
+    input.match_indices("_cp").any(|(idx, _)| {
+        let rest = &input[idx + 3..];
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        let mut tail = rest[digits..].chars();
+        digits > 0 && tail.next() == Some('t') && !tail.next().is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_free_threaded_python() {
+        assert!(is_free_threaded_python("3.13.* *_cp313t"));
+        assert!(is_free_threaded_python("h4f43103_0_cp313t"));
+        assert!(!is_free_threaded_python("3.13.* *_cp313"));
+        assert!(!is_free_threaded_python("h4f43103_0_cpython"));
+        assert!(!is_free_threaded_python("3.12"));
+    }
+}
@@ -217,6 +217,9 @@ pub fn build_rendered_variant_py(
             ),
             store_recipe: !effective_no_include_recipe,
             force_colors: false, // Set to false for Python API
+            conda_build_env_vars: false,
+            zig_cc: false,
+            report_dependency_usage: false,
             sandbox_config: None,
             debug: ::rattler_build::metadata::Debug::new(debug),
             exclude_newer,
@@ -237,7 +240,7 @@ pub fn build_rendered_variant_py(
     let (build_result, log_buffer) =
         tracing_subscriber::with_log_capture(progress_callback, || {
             run_async_task(async {
-                run_build_from_args(vec![output.clone()], tool_config, None).await
+                run_build_from_args(vec![output.clone()], tool_config, None, None).await
             })
         });
 
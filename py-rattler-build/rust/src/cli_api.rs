@@ -7,14 +7,15 @@ use std::{collections::HashMap, path::PathBuf, str::FromStr};
 use ::rattler_build::{
     build_recipes,
     metadata::Debug,
-    opt::{BuildData, ChannelPriorityWrapper, CommonData, TestData},
+    opt::{BuildData, ChannelPriorityWrapper, CommonData, PackageSource, TestData, TestPackage},
     run_test,
     tool_configuration::{ContinueOnFailure, SkipExisting, TestStrategy},
 };
 use clap::ValueEnum;
 use pyo3::prelude::*;
 use rattler_conda_types::{NamedChannelOrUrl, Platform};
-use rattler_config::config::{ConfigBase, build::PackageFormatAndCompression};
+use rattler_build::config::Config;
+use rattler_config::config::build::PackageFormatAndCompression;
 
 use crate::error::RattlerBuildError;
 use crate::run_async_task;
@@ -62,7 +63,7 @@ pub fn build_recipes_py(
         .map(|c| ChannelPriorityWrapper::from_str(&c).map(|c| c.value))
         .transpose()
         .map_err(|e| RattlerBuildError::ChannelPriority(e.to_string()))?;
-    let config = ConfigBase::<()>::default();
+    let config = Config::default();
     let common = CommonData::new(
         output_dir,
         false,
@@ -121,16 +122,28 @@ pub fn build_recipes_py(
         ignore_recipe_variants,
         render_only,
         with_solve,
+        None, // dry_run
         keep_build,
+        false, // resume
         no_build_id,
+        None, // build_jobs
         package_format,
         compression_threads,
         io_concurrency_limit,
         no_include_recipe,
         test,
+        None,       // test_jobs
+        Vec::new(), // test_channels
+        Vec::new(), // test_extra_specs
+        Vec::new(), // test_python_versions
+        None,       // test_container
+        None,       // test_report_junit
+        None,       // test_report_json
         common,
         false, // TUI disabled
         skip_existing,
+        None,  // remote cache
+        false, // remote cache read-only
         noarch_build_platform,
         None, // extra meta
         None, // sandbox configuration
@@ -142,6 +155,10 @@ pub fn build_recipes_py(
         exclude_newer,
         build_num,
         None, // markdown_summary
+        None, // profile
+        Vec::new(), // hooks
+        None, // codesign_identity
+        None, // notarize_keychain_profile
     );
 
     run_async_task(async {
@@ -170,7 +187,7 @@ pub fn test_package_py(
         .map(|c| ChannelPriorityWrapper::from_str(&c).map(|c| c.value))
         .transpose()
         .map_err(|e| RattlerBuildError::ChannelPriority(e.to_string()))?;
-    let config = ConfigBase::<()>::default();
+    let config = Config::default();
     let common = CommonData::new(
         None,
         false,
@@ -196,11 +213,18 @@ pub fn test_package_py(
         ),
     };
     let test_data = TestData::new(
-        package_file,
+        TestPackage::Source(PackageSource::Path(package_file)),
         channel,
         compression_threads,
         Debug::new(debug),
         test_index,
+        Vec::new(), // test_python_versions
+        Vec::new(), // test_channels
+        Vec::new(), // test_extra_specs
+        None,       // exclude_newer
+        None,       // test_container
+        None,       // test_report_junit
+        None,       // test_report_json
         common,
     );
 
@@ -12,6 +12,7 @@ mod package_assembler;
 mod progress_callback;
 mod recipe_generation;
 mod render;
+mod session;
 mod stage0;
 mod stage1;
 mod tool_config;
@@ -57,6 +58,7 @@ fn rattler_build<'py>(_py: Python<'py>, m: Bound<'py, PyModule>) -> PyResult<()>
     )?;
     m.add_function(wrap_pyfunction!(cli_api::build_recipes_py, &m).unwrap())?;
     m.add_function(wrap_pyfunction!(build::build_rendered_variant_py, &m).unwrap())?;
+    m.add_function(wrap_pyfunction!(session::build_py, &m).unwrap())?;
     m.add_function(wrap_pyfunction!(cli_api::test_package_py, &m).unwrap())?;
     m.add_function(wrap_pyfunction!(upload::upload_package_to_quetz_py, &m).unwrap())?;
     m.add_function(wrap_pyfunction!(upload::upload_package_to_artifactory_py, &m).unwrap())?;
@@ -65,6 +67,10 @@ fn rattler_build<'py>(_py: Python<'py>, m: Bound<'py, PyModule>) -> PyResult<()>
     m.add_function(wrap_pyfunction!(upload::upload_packages_to_conda_forge_py, &m).unwrap())?;
     m.add_class::<PyJinjaConfig>()?;
     m.add_class::<BuildResultPy>()?;
+    m.add_class::<session::PyBuildOutputs>()?;
+    m.add_class::<session::PyBuiltPackage>()?;
+    m.add_class::<session::PyTestSuiteReport>()?;
+    m.add_class::<session::PyTestCaseReport>()?;
 
     // Register all submodules
     stage0::register_stage0_module(_py, &m)?;
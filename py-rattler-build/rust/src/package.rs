@@ -30,7 +30,7 @@ use ::rattler_build::{
     tool_configuration::TestStrategy,
 };
 use clap::ValueEnum;
-use rattler_config::config::ConfigBase;
+use rattler_build::config::Config;
 
 /// A loaded conda package for inspection and testing.
 #[pyclass(name = "Package")]
@@ -351,7 +351,7 @@ impl PyPackage {
             .unwrap_or_default();
 
         // Create common data
-        let config = ConfigBase::<()>::default();
+        let config = Config::default();
         let common = CommonData::new(
             output_dir,
             false,
@@ -496,17 +496,17 @@ impl PyPackage {
     ) -> PyResult<Vec<PyTestResult>> {
         use ::rattler_build::{
             metadata::Debug,
-            opt::{ChannelPriorityWrapper, CommonData, TestData},
+            opt::{ChannelPriorityWrapper, CommonData, TestData, TestPackage},
             run_test,
         };
-        use rattler_config::config::ConfigBase;
+        use rattler_build::config::Config;
 
         let channel_priority = channel_priority
             .map(|c| ChannelPriorityWrapper::from_str(&c).map(|c| c.value))
             .transpose()
             .map_err(|e| RattlerBuildError::ChannelPriority(e.to_string()))?;
 
-        let config = ConfigBase::<()>::default();
+        let config = Config::default();
         let common = CommonData::new(
             None,
             false,
@@ -549,11 +549,18 @@ impl PyPackage {
         }
 
         let test_data = TestData::new(
-            self.path.clone(),
+            TestPackage::Source(PackageSource::Path(self.path.clone())),
             channel,
             compression_threads,
             Debug::new(debug),
             test_index,
+            Vec::new(), // test_python_versions
+            Vec::new(), // test_channels
+            Vec::new(), // test_extra_specs
+            None,       // exclude_newer
+            None,       // test_container
+            None,       // test_report_junit
+            None,       // test_report_json
             common,
         );
 
@@ -0,0 +1,266 @@
+//! Bindings for [`rattler_build::session::BuildSession`], which renders, builds and tests
+//! recipes like `rattler-build build` and returns structured results.
+
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
+
+use ::rattler_build::{
+    package_test::{TestCaseReport, TestStatus, TestSuiteReport},
+    session::{BuildResult, BuildSession, BuiltPackage},
+    tool_configuration::{SkipExisting, TestStrategy},
+};
+use clap::ValueEnum;
+use pyo3::prelude::*;
+use rattler_conda_types::{NamedChannelOrUrl, Platform};
+
+use crate::error::RattlerBuildError;
+use crate::run_async_task;
+
+/// A package that was built
+#[pyclass(name = "BuiltPackage", from_py_object)]
+#[derive(Clone)]
+pub struct PyBuiltPackage {
+    /// Package name
+    #[pyo3(get)]
+    pub name: String,
+    /// Package version
+    #[pyo3(get)]
+    pub version: String,
+    /// Build string (hash and variant identifier)
+    #[pyo3(get)]
+    pub build_string: String,
+    /// Subdir of the package (e.g., "linux-64", "noarch")
+    #[pyo3(get)]
+    pub subdir: String,
+    /// Identifier of the package (`name-version-build`)
+    #[pyo3(get)]
+    pub identifier: String,
+    /// Dictionary of variant values used for this build
+    #[pyo3(get)]
+    pub variant: HashMap<String, String>,
+    /// Path of the package file
+    #[pyo3(get)]
+    pub path: PathBuf,
+    /// Log file of the build, if it was written
+    #[pyo3(get)]
+    pub log_file: Option<PathBuf>,
+    /// Warnings that were recorded during the build
+    #[pyo3(get)]
+    pub warnings: Vec<String>,
+    /// Build duration in seconds
+    #[pyo3(get)]
+    pub build_time: Option<f64>,
+}
+
+#[pymethods]
+impl PyBuiltPackage {
+    fn __repr__(&self) -> String {
+        format!(
+            "BuiltPackage({}, path={})",
+            self.identifier,
+            self.path.display()
+        )
+    }
+}
+
+impl From<BuiltPackage> for PyBuiltPackage {
+    fn from(package: BuiltPackage) -> Self {
+        Self {
+            name: package.name.as_normalized().to_string(),
+            version: package.version,
+            build_string: package.build_string,
+            subdir: package.subdir.to_string(),
+            identifier: package.identifier,
+            variant: package
+                .variant
+                .iter()
+                .map(|(k, v)| (k.0.clone(), v.to_string()))
+                .collect(),
+            path: package.path,
+            log_file: package.log_file,
+            warnings: package.warnings,
+            build_time: package.duration.map(|d| d.as_secs_f64()),
+        }
+    }
+}
+
+/// The result of a single test of a package
+#[pyclass(name = "TestCaseReport", from_py_object)]
+#[derive(Clone)]
+pub struct PyTestCaseReport {
+    /// Name of the test (e.g. `python #1`)
+    #[pyo3(get)]
+    pub name: String,
+    /// `passed`, `failed`, `skipped` or `error`
+    #[pyo3(get)]
+    pub status: String,
+    /// Duration of the test in seconds
+    #[pyo3(get)]
+    pub duration: f64,
+    /// Error message or reason for skipping
+    #[pyo3(get)]
+    pub message: Option<String>,
+}
+
+impl From<TestCaseReport> for PyTestCaseReport {
+    fn from(report: TestCaseReport) -> Self {
+        let status = match report.status {
+            TestStatus::Passed => "passed",
+            TestStatus::Failed => "failed",
+            TestStatus::Skipped => "skipped",
+            TestStatus::Error => "error",
+        };
+        Self {
+            name: report.name,
+            status: status.to_string(),
+            duration: report.duration,
+            message: report.message,
+        }
+    }
+}
+
+/// The results of the tests of a package
+#[pyclass(name = "TestSuiteReport", from_py_object)]
+#[derive(Clone)]
+pub struct PyTestSuiteReport {
+    /// Package that was tested (`name-version-build`)
+    #[pyo3(get)]
+    pub package: String,
+    /// Duration of all tests in seconds
+    #[pyo3(get)]
+    pub duration: f64,
+    /// Results of the individual tests
+    #[pyo3(get)]
+    pub tests: Vec<PyTestCaseReport>,
+}
+
+impl From<TestSuiteReport> for PyTestSuiteReport {
+    fn from(report: TestSuiteReport) -> Self {
+        Self {
+            package: report.package,
+            duration: report.duration,
+            tests: report.tests.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Result of building recipes with `build`
+#[pyclass(name = "BuildOutputs", from_py_object)]
+#[derive(Clone)]
+pub struct PyBuildOutputs {
+    /// Packages that were built
+    #[pyo3(get)]
+    pub packages: Vec<PyBuiltPackage>,
+    /// Test results of the built packages
+    #[pyo3(get)]
+    pub tests: Vec<PyTestSuiteReport>,
+    /// Captured log output, if `capture_logs` was set
+    #[pyo3(get)]
+    pub log: Option<String>,
+}
+
+#[pymethods]
+impl PyBuildOutputs {
+    fn __repr__(&self) -> String {
+        format!(
+            "BuildOutputs({} packages, {} tested)",
+            self.packages.len(),
+            self.tests.len()
+        )
+    }
+}
+
+impl From<BuildResult> for PyBuildOutputs {
+    fn from(result: BuildResult) -> Self {
+        Self {
+            packages: result.packages.into_iter().map(Into::into).collect(),
+            tests: result.tests.into_iter().map(Into::into).collect(),
+            log: result.log,
+        }
+    }
+}
+
+fn parse_platform(platform: Option<String>) -> PyResult<Option<Platform>> {
+    Ok(platform
+        .map(|p| Platform::from_str(&p))
+        .transpose()
+        .map_err(RattlerBuildError::from)?)
+}
+
+/// Render, build and test recipes like `rattler-build build`
+///
+/// The events of the build (rendered outputs, solved environments, script output, packages
+/// and test results) are passed as dictionaries to `on_event`.
+#[pyfunction]
+#[pyo3(signature = (recipes, target_platforms=None, build_platform=None, host_platform=None, channels=None, variant_config=None, variant_overrides=None, output_dir=None, test=None, skip_existing=None, capture_logs=false, on_event=None))]
+#[allow(clippy::too_many_arguments)]
+pub fn build_py(
+    py: Python<'_>,
+    recipes: Vec<PathBuf>,
+    target_platforms: Option<Vec<String>>,
+    build_platform: Option<String>,
+    host_platform: Option<String>,
+    channels: Option<Vec<String>>,
+    variant_config: Option<Vec<PathBuf>>,
+    variant_overrides: Option<HashMap<String, Vec<String>>>,
+    output_dir: Option<PathBuf>,
+    test: Option<String>,
+    skip_existing: Option<String>,
+    capture_logs: bool,
+    on_event: Option<Py<PyAny>>,
+) -> PyResult<PyBuildOutputs> {
+    let mut session = BuildSession::new().capture_logs(capture_logs);
+    for recipe in recipes {
+        session = session.recipe(recipe);
+    }
+    for platform in target_platforms.unwrap_or_default() {
+        let platform = Platform::from_str(&platform).map_err(RattlerBuildError::from)?;
+        session = session.target_platform(platform);
+    }
+    if let Some(platform) = parse_platform(build_platform)? {
+        session = session.build_platform(platform);
+    }
+    if let Some(platform) = parse_platform(host_platform)? {
+        session = session.host_platform(platform);
+    }
+    for channel in channels.unwrap_or_default() {
+        let channel = NamedChannelOrUrl::from_str(&channel)
+            .map_err(|e| RattlerBuildError::Channel(e.to_string()))?;
+        session = session.channel(channel);
+    }
+    for path in variant_config.unwrap_or_default() {
+        session = session.variant_config(path);
+    }
+    for (key, values) in variant_overrides.unwrap_or_default() {
+        session = session.variant(key, values);
+    }
+    if let Some(output_dir) = output_dir {
+        session = session.output_dir(output_dir);
+    }
+    if let Some(test) = test {
+        let test = TestStrategy::from_str(&test, false)
+            .map_err(|e| RattlerBuildError::Other(format!("Invalid test strategy: {}", e)))?;
+        session = session.test_strategy(test);
+    }
+    if let Some(skip_existing) = skip_existing {
+        let skip_existing = SkipExisting::from_str(&skip_existing, false)
+            .map_err(|e| RattlerBuildError::Other(format!("Invalid skip_existing: {}", e)))?;
+        session = session.skip_existing(skip_existing);
+    }
+    if let Some(callback) = on_event {
+        let callback = Arc::new(callback);
+        session = session.on_event(move |event| {
+            if let Err(e) = Python::attach(|py| {
+                let event = pythonize::pythonize(py, event)?;
+                callback.bind(py).call1((event,))?;
+                Ok::<(), PyErr>(())
+            }) {
+                // Log error but don't fail the build
+                eprintln!("Error in Python event callback: {}", e);
+            }
+        });
+    }
+
+    // release the GIL so that the event callback can be called from the build threads
+    py.detach(|| run_async_task(async { session.run().await }))
+        .map(PyBuildOutputs::from)
+}
@@ -42,6 +42,13 @@ from rattler_build.package import (
     RubyTest,
     TestResult,
 )
+from rattler_build.pipeline import (
+    BuildOutputs,
+    BuiltPackage,
+    TestCaseReport,
+    TestSuiteReport,
+    build,
+)
 from rattler_build.recipe_generation import (
     generate_cpan_recipe,
     generate_cran_recipe,
@@ -64,6 +71,7 @@ from rattler_build.variant_config import VariantConfig
 __all__ = [
     # Core API
     "rattler_build_version",
+    "build",
     "build_recipes",
     "test_package",
     # Package assembly (low-level)
@@ -85,6 +93,10 @@ __all__ = [
     "generate_luarocks_recipe",
     # Configuration
     "BuildResult",
+    "BuildOutputs",
+    "BuiltPackage",
+    "TestSuiteReport",
+    "TestCaseReport",
     "JinjaConfig",
     "VariantConfig",
     "ToolConfiguration",
@@ -0,0 +1,92 @@
+"""Build recipes end to end (render, build and test) with structured results."""
+
+from __future__ import annotations
+
+from collections.abc import Callable
+from pathlib import Path
+from typing import Any
+
+from rattler_build._rattler_build import (
+    BuildOutputs,
+    BuiltPackage,
+    TestCaseReport,
+    TestSuiteReport,
+    build_py,
+)
+
+__all__ = [
+    "build",
+    "BuildOutputs",
+    "BuiltPackage",
+    "TestCaseReport",
+    "TestSuiteReport",
+]
+
+
+def build(
+    recipes: list[str | Path] | str | Path,
+    *,
+    target_platforms: list[str] | None = None,
+    build_platform: str | None = None,
+    host_platform: str | None = None,
+    channels: list[str] | None = None,
+    variant_config: list[str | Path] | None = None,
+    variant_overrides: dict[str, list[str]] | None = None,
+    output_dir: str | Path | None = None,
+    test: str | None = None,
+    skip_existing: str | None = None,
+    capture_logs: bool = False,
+    on_event: Callable[[dict[str, Any]], None] | None = None,
+) -> BuildOutputs:
+    """
+    Render, build and test recipes like `rattler-build build`.
+
+    All variants of the recipes are built for every target platform, in the order of their
+    dependencies. Unlike `RenderedVariant.run_build()`, the packages are built with the same
+    orchestration as the command line (skipping existing packages, noarch handling, tests).
+
+    Args:
+        recipes: The recipe files or directories containing `recipe.yaml`.
+        target_platforms: The platforms to build for (defaults to the current platform).
+        build_platform: The build platform (defaults to the current platform).
+        host_platform: The host platform (defaults to the target platform).
+        channels: The channels to search for dependencies in (defaults to `conda-forge`).
+        variant_config: Variant configuration files.
+        variant_overrides: Variant keys and the values to build for, e.g. `{"python": ["3.12", "3.13"]}`.
+        output_dir: The directory to store the packages in (defaults to `./output`).
+        test: The test strategy: `skip`, `native` or `native-and-emulated` (default: `native-and-emulated`).
+        skip_existing: Skip packages that already exist: `none`, `local` or `all`.
+        capture_logs: Return the log output in `BuildOutputs.log` instead of emitting it.
+        on_event: Called with every event of the build as a dictionary with an `event` key
+            (`rendered`, `environment_solved`, `script_output`, `artifact`, `test_result`, ...).
+            The events are the same as with `--output-format json`.
+
+    Returns:
+        The built packages, the test results and the captured log.
+
+    Example:
+        ```python
+        from rattler_build import build
+
+        result = build("recipe/recipe.yaml", channels=["conda-forge"], test="skip")
+        for package in result.packages:
+            print(package.identifier, package.path)
+        ```
+    """
+    if isinstance(recipes, (str, Path)):
+        recipes = [recipes]
+
+    return build_py(
+        [Path(recipe) for recipe in recipes],
+        target_platforms,
+        build_platform,
+        host_platform,
+        channels,
+        [Path(path) for path in variant_config] if variant_config else None,
+        variant_overrides,
+        Path(output_dir) if output_dir else None,
+        test,
+        skip_existing,
+        capture_logs,
+        on_event,
+    )
@@ -0,0 +1,33 @@
+import shutil
+from pathlib import Path
+from typing import Any
+
+import rattler_build
+
+
+def test_build(tmp_path: Path, recipes_dir: Path) -> None:
+    recipe_path = tmp_path.joinpath("recipe.yaml")
+    shutil.copy(recipes_dir.joinpath("dummy", "recipe.yaml"), recipe_path)
+    output_dir = tmp_path.joinpath("output")
+
+    events: list[dict[str, Any]] = []
+    result = rattler_build.build(
+        recipe_path,
+        output_dir=output_dir,
+        test="skip",
+        capture_logs=True,
+        on_event=events.append,
+    )
+
+    assert len(result.packages) == 1
+    package = result.packages[0]
+    assert package.name == "dummy-x"
+    assert package.path.is_file()
+    assert package.path.parent == output_dir.joinpath(package.subdir)
+    assert package.identifier == f"{package.name}-{package.version}-{package.build_string}"
+    assert result.tests == []
+    assert result.log
+
+    event_types = [event["event"] for event in events]
+    assert "rendered" in event_types
+    assert "artifact" in event_types
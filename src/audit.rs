@@ -0,0 +1,581 @@
+//! Auditing packages for known vulnerabilities (`rattler-build audit`, `build --audit`).
+//!
+//! The package itself, its resolved host environment and its run environment are looked up in
+//! the [OSV](https://osv.dev) database, which aggregates the CVE and GitHub advisories. OSV has
+//! no ecosystem for conda packages, so a package is looked up in the ecosystem of its upstream
+//! project: Python packages (that depend on `python` or are `noarch: python`) on PyPI. The other
+//! packages are reported as not covered.
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use rattler_conda_types::RepoDataRecord;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+use crate::{metadata::Output, tool_configuration};
+
+/// The OSV API that is queried by default
+pub const DEFAULT_OSV_URL: &str = "https://api.osv.dev";
+
+/// The severity of a vulnerability
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The database has no severity for the vulnerability
+    Unknown,
+    /// Low severity (CVSS score below 4.0)
+    Low,
+    /// Moderate severity (CVSS score from 4.0)
+    Moderate,
+    /// High severity (CVSS score from 7.0)
+    High,
+    /// Critical severity (CVSS score from 9.0)
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Unknown => "unknown",
+            Self::Low => "low",
+            Self::Moderate => "moderate",
+            Self::High => "high",
+            Self::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl Severity {
+    /// The severity of a CVSS base score
+    fn from_score(score: f64) -> Self {
+        match score {
+            s if s >= 9.0 => Self::Critical,
+            s if s >= 7.0 => Self::High,
+            s if s >= 4.0 => Self::Moderate,
+            _ => Self::Low,
+        }
+    }
+
+    /// The severity of a GitHub advisory (`LOW`, `MODERATE`/`MEDIUM`, `HIGH`, `CRITICAL`)
+    fn from_label(label: &str) -> Option<Self> {
+        match label.to_ascii_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "moderate" | "medium" => Some(Self::Moderate),
+            "high" => Some(Self::High),
+            "critical" => Some(Self::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// The settings of the audit of a build (`--audit`)
+#[derive(Debug, Clone, Default)]
+pub struct AuditSettings {
+    /// Fail the build if a vulnerability has at least this severity
+    pub fail_on: Option<Severity>,
+    /// The OSV API to query
+    pub osv_url: Option<Url>,
+}
+
+/// An error of the audit
+#[derive(Debug, Error)]
+pub enum AuditError {
+    /// The OSV database could not be queried
+    #[error("Failed to query the OSV database: {0}")]
+    Request(#[from] reqwest_middleware::Error),
+
+    /// The OSV database returned an error
+    #[error("The OSV database returned an error: {0}")]
+    Response(#[from] reqwest::Error),
+
+    /// The URL of the OSV API is invalid
+    #[error("Invalid URL of the OSV API: {0}")]
+    Url(#[from] url::ParseError),
+
+    /// Vulnerabilities at or above the threshold were found
+    #[error(
+        "{count} known vulnerabilities with a severity of {threshold} or higher were found in {package}"
+    )]
+    Vulnerable {
+        /// The audited package
+        package: String,
+        /// The number of vulnerabilities at or above the threshold
+        count: usize,
+        /// The threshold
+        threshold: Severity,
+    },
+}
+
+/// A package that is looked up in the OSV database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditedPackage {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    /// Where the package comes from (`package`, `host` or `run`)
+    pub environment: String,
+    /// The OSV ecosystem that the package is looked up in
+    pub ecosystem: Option<&'static str>,
+}
+
+/// A known vulnerability of a package
+#[derive(Debug, Clone, Serialize)]
+pub struct Vulnerability {
+    /// The OSV identifier, e.g. `GHSA-...` or `PYSEC-...`
+    pub id: String,
+    /// Other identifiers of the vulnerability, e.g. the CVE
+    pub aliases: Vec<String>,
+    /// A short description
+    pub summary: Option<String>,
+    /// The severity
+    pub severity: Severity,
+    /// The affected package
+    pub package: String,
+    /// The affected version
+    pub version: String,
+    /// Where the package comes from (`package`, `host` or `run`)
+    pub environment: String,
+}
+
+/// The result of the audit of a package
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    /// The audited package (`name-version-build`)
+    pub package: String,
+    /// The known vulnerabilities, the most severe first
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// The packages without an ecosystem in the OSV database
+    pub not_covered: Vec<String>,
+}
+
+impl AuditReport {
+    /// Fail if a vulnerability has at least the severity `threshold`
+    pub fn check(&self, threshold: Severity) -> Result<(), AuditError> {
+        let count = self
+            .vulnerabilities
+            .iter()
+            .filter(|vulnerability| vulnerability.severity >= threshold)
+            .count();
+        if count == 0 {
+            return Ok(());
+        }
+        Err(AuditError::Vulnerable {
+            package: self.package.clone(),
+            count,
+            threshold,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct BatchQuery<'a> {
+    queries: Vec<Query<'a>>,
+}
+
+#[derive(Serialize)]
+struct Query<'a> {
+    package: QueryPackage<'a>,
+    version: &'a str,
+}
+
+#[derive(Serialize)]
+struct QueryPackage<'a> {
+    name: &'a str,
+    ecosystem: &'a str,
+}
+
+#[derive(Deserialize)]
+struct BatchResponse {
+    results: Vec<BatchResult>,
+}
+
+#[derive(Deserialize)]
+struct BatchResult {
+    #[serde(default)]
+    vulns: Vec<VulnerabilityId>,
+}
+
+#[derive(Deserialize)]
+struct VulnerabilityId {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct OsvVulnerability {
+    id: String,
+    summary: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    severity: Vec<OsvSeverity>,
+    database_specific: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct OsvSeverity {
+    #[serde(rename = "type")]
+    kind: String,
+    score: String,
+}
+
+impl OsvVulnerability {
+    /// The severity of the advisory, or else computed from the CVSS vector
+    fn severity(&self) -> Severity {
+        let label = self
+            .database_specific
+            .as_ref()
+            .and_then(|specific| specific.get("severity"))
+            .and_then(|severity| severity.as_str())
+            .and_then(Severity::from_label);
+        label
+            .or_else(|| {
+                self.severity
+                    .iter()
+                    .filter(|severity| severity.kind.starts_with("CVSS_V3"))
+                    .find_map(|severity| cvss3_base_score(&severity.score))
+                    .map(Severity::from_score)
+            })
+            .unwrap_or(Severity::Unknown)
+    }
+}
+
+/// The base score of a CVSS v3 vector, e.g. `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+fn cvss3_base_score(vector: &str) -> Option<f64> {
+    let metrics = vector
+        .split('/')
+        .filter_map(|metric| metric.split_once(':'))
+        .collect::<BTreeMap<_, _>>();
+    let scope_changed = *metrics.get("S")? == "C";
+    let attack_vector = match *metrics.get("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let attack_complexity = match *metrics.get("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let privileges = match (*metrics.get("PR")?, scope_changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let user_interaction = match *metrics.get("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact = |metric| match metrics.get(metric).copied() {
+        Some("H") => Some(0.56),
+        Some("L") => Some(0.22),
+        Some("N") => Some(0.0),
+        _ => None,
+    };
+    let iss = 1.0 - (1.0 - impact("C")?) * (1.0 - impact("I")?) * (1.0 - impact("A")?);
+    let impact = if scope_changed {
+        7.52 * (iss - 0.029) - 3.25 * (iss - 0.02f64).powi(15)
+    } else {
+        6.42 * iss
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges * user_interaction;
+    let score = if scope_changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    // round up to one decimal
+    Some((score.min(10.0) * 10.0).ceil() / 10.0)
+}
+
+/// The OSV ecosystem of a conda package with the given dependencies
+fn ecosystem(depends: &[String], noarch_python: bool) -> Option<&'static str> {
+    let depends_on_python = depends
+        .iter()
+        .any(|spec| spec.split([' ', '=', '<', '>']).next() == Some("python"));
+    (noarch_python || depends_on_python).then_some("PyPI")
+}
+
+fn audited_record(record: &RepoDataRecord, environment: &str) -> AuditedPackage {
+    let package = &record.package_record;
+    AuditedPackage {
+        name: package.name.as_normalized().to_string(),
+        version: package.version.to_string(),
+        environment: environment.to_string(),
+        ecosystem: ecosystem(&package.depends, package.noarch.is_python()),
+    }
+}
+
+/// The packages of the output to audit: the package itself, its host environment and its run
+/// environment
+pub(crate) async fn audited_packages(
+    output: &Output,
+    tool_configuration: &tool_configuration::Configuration,
+) -> Vec<AuditedPackage> {
+    let dependencies = output.finalized_dependencies.as_ref();
+    let run_depends = dependencies
+        .map(|dependencies| {
+            dependencies
+                .run
+                .depends
+                .iter()
+                .map(|dependency| dependency.spec().to_string())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let noarch_python = output
+        .recipe
+        .build()
+        .noarch
+        .is_some_and(|noarch| noarch.is_python());
+
+    let mut packages = vec![AuditedPackage {
+        name: output.name().as_normalized().to_string(),
+        version: output.version().to_string(),
+        environment: "package".to_string(),
+        ecosystem: ecosystem(&run_depends, noarch_python),
+    }];
+    if let Some(host) = dependencies.and_then(|dependencies| dependencies.host.as_ref()) {
+        packages.extend(
+            host.resolved
+                .iter()
+                .map(|record| audited_record(record, "host")),
+        );
+    }
+    let run = output.solve_run_environment(tool_configuration).await;
+    packages.extend(run.iter().map(|record| audited_record(record, "run")));
+    packages
+}
+
+/// Look up the packages in the OSV database
+pub async fn find_vulnerabilities(
+    client: &rattler_build_networking::BaseClient,
+    osv_url: &Url,
+    package: String,
+    packages: &[AuditedPackage],
+) -> Result<AuditReport, AuditError> {
+    let (covered, not_covered): (Vec<_>, Vec<_>) = packages
+        .iter()
+        .partition(|package| package.ecosystem.is_some());
+    let mut not_covered = not_covered
+        .into_iter()
+        .map(|package| package.name.clone())
+        .collect::<Vec<_>>();
+    not_covered.sort();
+    not_covered.dedup();
+
+    let mut report = AuditReport {
+        package,
+        vulnerabilities: Vec::new(),
+        not_covered,
+    };
+    if covered.is_empty() {
+        return Ok(report);
+    }
+
+    let query_url = osv_url.join("v1/querybatch")?;
+    let query = BatchQuery {
+        queries: covered
+            .iter()
+            .map(|package| Query {
+                package: QueryPackage {
+                    name: &package.name,
+                    ecosystem: package.ecosystem.unwrap_or_default(),
+                },
+                version: &package.version,
+            })
+            .collect(),
+    };
+    let response: BatchResponse = client
+        .for_host(&query_url)
+        .post(query_url.clone())
+        .json(&query)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    // the batch query only returns the identifiers, the details are fetched once per advisory
+    let mut details = BTreeMap::<String, OsvVulnerability>::new();
+    for (package, result) in covered.iter().zip(response.results) {
+        for id in result.vulns {
+            if !details.contains_key(&id.id) {
+                let url = osv_url.join(&format!("v1/vulns/{}", id.id))?;
+                let vulnerability: OsvVulnerability = client
+                    .for_host(&url)
+                    .get(url.clone())
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                details.insert(id.id.clone(), vulnerability);
+            }
+            let vulnerability = &details[&id.id];
+            report.vulnerabilities.push(Vulnerability {
+                id: vulnerability.id.clone(),
+                aliases: vulnerability.aliases.clone(),
+                summary: vulnerability.summary.clone(),
+                severity: vulnerability.severity(),
+                package: package.name.clone(),
+                version: package.version.clone(),
+                environment: package.environment.clone(),
+            });
+        }
+    }
+    report.vulnerabilities.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.package.cmp(&b.package))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(report)
+}
+
+/// Audit the output (the package, its host and its run environment)
+pub(crate) async fn audit_output(
+    output: &Output,
+    tool_configuration: &tool_configuration::Configuration,
+    osv_url: Option<&Url>,
+) -> Result<AuditReport, AuditError> {
+    let default_url = Url::parse(DEFAULT_OSV_URL)?;
+    let packages = audited_packages(output, tool_configuration).await;
+    find_vulnerabilities(
+        &tool_configuration.client,
+        osv_url.unwrap_or(&default_url),
+        output.identifier(),
+        &packages,
+    )
+    .await
+}
+
+/// Print the vulnerabilities of the report as a table
+pub(crate) fn print_report(report: &AuditReport) {
+    if report.vulnerabilities.is_empty() {
+        tracing::info!(
+            "{} No known vulnerabilities in {}",
+            console::style("✔").green(),
+            report.package
+        );
+    } else {
+        let mut table = comfy_table::Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header([
+                "Package",
+                "Version",
+                "Environment",
+                "Vulnerability",
+                "Severity",
+                "Summary",
+            ]);
+        for vulnerability in &report.vulnerabilities {
+            let id = std::iter::once(vulnerability.id.as_str())
+                .chain(vulnerability.aliases.iter().map(String::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+            table.add_row([
+                vulnerability.package.as_str(),
+                vulnerability.version.as_str(),
+                vulnerability.environment.as_str(),
+                id.as_str(),
+                &vulnerability.severity.to_string(),
+                vulnerability.summary.as_deref().unwrap_or("-"),
+            ]);
+        }
+        tracing::warn!(
+            "{} known vulnerabilities in {}",
+            report.vulnerabilities.len(),
+            report.package
+        );
+        tracing::info!("\n{table}");
+    }
+    if !report.not_covered.is_empty() {
+        tracing::info!(
+            "Not covered by the OSV database: {}",
+            report.not_covered.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cvss3_base_score() {
+        let score = |vector| cvss3_base_score(vector).unwrap();
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"), 9.8);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:R/S:C/C:L/I:L/A:N"), 6.1);
+        assert_eq!(score("CVSS:3.0/AV:L/AC:H/PR:L/UI:N/S:U/C:L/I:N/A:N"), 2.5);
+        assert_eq!(score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:N"), 0.0);
+        assert!(cvss3_base_score("CVSS:3.1/AV:X").is_none());
+    }
+
+    #[test]
+    fn test_severity() {
+        let vulnerability = |severity: serde_json::Value| -> OsvVulnerability {
+            serde_json::from_value(severity).unwrap()
+        };
+        let github = vulnerability(serde_json::json!({
+            "id": "GHSA-1234",
+            "database_specific": {"severity": "MODERATE"},
+            "severity": [{"type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"}]
+        }));
+        assert_eq!(github.severity(), Severity::Moderate);
+        let cvss = vulnerability(serde_json::json!({
+            "id": "PYSEC-1234",
+            "severity": [{"type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"}]
+        }));
+        assert_eq!(cvss.severity(), Severity::Critical);
+        let unknown = vulnerability(serde_json::json!({"id": "OSV-1234"}));
+        assert_eq!(unknown.severity(), Severity::Unknown);
+    }
+
+    #[test]
+    fn test_ecosystem() {
+        assert_eq!(
+            ecosystem(&["python >=3.9".to_string()], false),
+            Some("PyPI")
+        );
+        assert_eq!(ecosystem(&[], true), Some("PyPI"));
+        assert_eq!(ecosystem(&["python_abi 3.12.*".to_string()], false), None);
+        assert_eq!(ecosystem(&["libzlib >=1.3".to_string()], false), None);
+    }
+
+    #[test]
+    fn test_check() {
+        let vulnerability = |severity| Vulnerability {
+            id: "GHSA-1234".to_string(),
+            aliases: vec![],
+            summary: None,
+            severity,
+            package: "requests".to_string(),
+            version: "2.0.0".to_string(),
+            environment: "run".to_string(),
+        };
+        let report = AuditReport {
+            package: "foo-1.0-pyh0_0".to_string(),
+            vulnerabilities: vec![vulnerability(Severity::High), vulnerability(Severity::Low)],
+            not_covered: vec![],
+        };
+        assert!(report.check(Severity::Critical).is_ok());
+        assert!(matches!(
+            report.check(Severity::Low),
+            Err(AuditError::Vulnerable { count: 2, .. })
+        ));
+    }
+}
@@ -1,19 +1,28 @@
 //! The build module contains the code for running the build process for a given
 //! [`Output`]
-use std::{path::PathBuf, vec};
+use std::{path::PathBuf, time::Instant, vec};
 
 use miette::{Context, IntoDiagnostic};
 use rattler_build_recipe::stage1::TestType;
 use rattler_build_script::InterpreterError;
 use rattler_conda_types::{Channel, MatchSpec, Platform, package::PathsJson};
+use rattler_package_streaming::seek::read_package_file;
 
 use crate::{
-    apply_patch_custom,
+    apply_patch_custom, audit,
+    build_log::{self, BuildStatus},
+    build_state::{BuildState, CompletedPhase},
+    error_code::{ErrorCode, WithErrorCode},
+    events::{self, Event, Phase, SolvedPackage},
+    hermetic,
+    license_policy::{self, PolicyAction},
     metadata::{Output, build_reindexed_channels},
     package_test::PackageContentsTestExt as _,
     packaging::record_files,
+    remote_cache,
     render::{resolved_dependencies::RunExportsDownload, solver::load_repodatas},
-    tool_configuration,
+    slsa, tool_configuration,
+    types::BuildPhase,
 };
 
 /// Behavior for handling the working directory during the build process
@@ -109,10 +118,78 @@ pub async fn skip_existing(
 /// dependencies, and execute the build script. Returns the path to the
 /// resulting package.
 pub async fn run_build(
+    output: Output,
+    tool_configuration: &tool_configuration::Configuration,
+    working_directory_behavior: WorkingDirectoryBehavior,
+) -> miette::Result<(Output, PathBuf)> {
+    let package = output.identifier();
+    events::emit(|| Event::PhaseStart {
+        phase: Phase::Build,
+        package: Some(package.clone()),
+    });
+    let start = Instant::now();
+
+    let result = build_output(
+        output.clone(),
+        tool_configuration,
+        working_directory_behavior,
+    )
+    .await;
+
+    let (status, artifact) = match &result {
+        Ok((_, archive)) => (BuildStatus::Success, Some(archive.as_path())),
+        Err(_) => (BuildStatus::Failed, None),
+    };
+    if let Err(e) = build_log::record(&output, status, artifact) {
+        tracing::warn!("Failed to update the index of the build logs: {}", e);
+    }
+
+    events::emit(|| {
+        Event::phase_end(
+            Phase::Build,
+            Some(package.clone()),
+            start.elapsed(),
+            &result,
+        )
+    });
+    if let Ok((_, archive)) = &result {
+        events::emit(|| Event::artifact(package, archive));
+    }
+    result
+}
+
+async fn build_output(
     mut output: Output,
     tool_configuration: &tool_configuration::Configuration,
     working_directory_behavior: WorkingDirectoryBehavior,
 ) -> miette::Result<(Output, PathBuf)> {
+    let build_log = build_log::start(&output)
+        .inspect_err(|e| tracing::warn!("Failed to create the build log: {}", e))
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "Running build for",
+        recipe = output.identifier(),
+        span_color = output.identifier(),
+        build_log = build_log
+    );
+    let _enter = span.enter();
+
+    let resumed = if tool_configuration.resume {
+        let resumed = BuildState::find(&output);
+        match &resumed {
+            Some(state) => {
+                tracing::info!("Resuming the build in {}", state.describe());
+                state.restore(&mut output);
+            }
+            None => tracing::info!("No interrupted build to resume, starting a new build"),
+        }
+        resumed
+    } else {
+        None
+    };
+    let mut state = resumed.unwrap_or_else(|| BuildState::new(&output));
+
     let cleanup = matches!(
         working_directory_behavior,
         WorkingDirectoryBehavior::Cleanup
@@ -120,91 +197,174 @@ pub async fn run_build(
     output
         .build_configuration
         .directories
-        .create_build_dir(cleanup)
+        .create_build_dir(cleanup && state.completed.is_none())
         .into_diagnostic()?;
 
-    let span = tracing::info_span!(
-        "Running build for",
-        recipe = output.identifier(),
-        span_color = output.identifier()
-    );
-    let _enter = span.enter();
     output.record_build_start();
 
     let directories = output.build_configuration.directories.clone();
+    let mut remote_cache_key = None;
 
-    // Process staging caches if this output depends on any
-    // This will build or restore staging caches and return their dependencies/sources if inherited
-    let staging_result = output.process_staging_caches(tool_configuration).await?;
-
-    // If we inherit from a staging cache, store its dependencies and sources
-    if let Some((deps, sources)) = staging_result {
-        output.finalized_cache_dependencies = Some(deps);
-        output.finalized_cache_sources = Some(sources);
-    }
-
-    // Fetch sources for this output
-    let output = output
-        .fetch_sources(tool_configuration, apply_patch_custom)
-        .await
-        .into_diagnostic()?;
-
-    // Resolve dependencies for this output
-    // If we inherited from a staging cache, finalized_cache_dependencies will be merged
-    // into the final dependencies during the resolve_dependencies call
-    let output = output
-        .resolve_dependencies(tool_configuration, RunExportsDownload::DownloadMissing)
-        .await
-        .into_diagnostic()?;
+    let (output, pre_install_files) = if state.is_completed(CompletedPhase::SourcesFetched) {
+        (output, state.pre_install_files())
+    } else {
+        // Process staging caches if this output depends on any
+        // This will build or restore staging caches and return their dependencies/sources if inherited
+        let start = chrono::Utc::now();
+        let staging_result = output
+            .process_staging_caches(tool_configuration)
+            .await
+            .with_error_code(ErrorCode::STAGING)?;
+        if !output.recipe.staging_caches.is_empty() {
+            output.record_phase(BuildPhase::Staging, start);
+        }
 
-    // Snapshot the host prefix before dependency installation so we can
-    // detect files added by post-link scripts (which aren't recorded in
-    // conda-meta and would otherwise leak into the downstream package).
-    let pre_install_files = record_files(&output.build_configuration.directories.host_prefix).ok();
+        // If we inherit from a staging cache, store its dependencies and sources
+        if let Some((deps, sources)) = staging_result {
+            output.finalized_cache_dependencies = Some(deps);
+            output.finalized_cache_sources = Some(sources);
+        }
 
-    output
-        .install_environments(tool_configuration)
-        .await
-        .into_diagnostic()?;
+        // Fetch sources for this output
+        let start = chrono::Utc::now();
+        let output = output
+            .fetch_sources(tool_configuration, apply_patch_custom)
+            .await
+            .into_diagnostic()
+            .with_error_code(ErrorCode::FETCH)?;
+        output.record_phase(BuildPhase::Fetch, start);
+
+        // Snapshot the host prefix before dependency installation so we can
+        // detect files added by post-link scripts (which aren't recorded in
+        // conda-meta and would otherwise leak into the downstream package).
+        let pre_install_files =
+            record_files(&output.build_configuration.directories.host_prefix).ok();
+        state.sources_fetched(&output, pre_install_files.clone())?;
+        (output, pre_install_files)
+    };
 
-    // Compute the set of files added during install_environments. This
-    // includes both properly-recorded dependency files *and* untracked
-    // post-link artifacts. Passing this delta to create_package ensures
-    // the untracked artifacts are excluded without accidentally hiding
-    // files restored from a staging cache (which existed before install).
-    let install_added_files = record_files(&output.build_configuration.directories.host_prefix)
-        .ok()
-        .map(|post| {
-            if let Some(pre) = &pre_install_files {
-                post.difference(pre).cloned().collect()
-            } else {
-                post
+    let (output, install_added_files) = if state.is_completed(CompletedPhase::EnvironmentsCreated) {
+        (output, state.install_added_files())
+    } else {
+        // Resolve dependencies for this output
+        // If we inherited from a staging cache, finalized_cache_dependencies will be merged
+        // into the final dependencies during the resolve_dependencies call
+        let start = chrono::Utc::now();
+        let output = output
+            .resolve_dependencies(tool_configuration, RunExportsDownload::DownloadMissing)
+            .await
+            .into_diagnostic()
+            .with_error_code(ErrorCode::SOLVE)?;
+        emit_solved_environments(&output);
+
+        let (key, cached) = remote_cache::lookup(&output, tool_configuration).await;
+        remote_cache_key = key;
+        if let Some(archive) = cached {
+            let paths_json: PathsJson = read_package_file(&archive).into_diagnostic()?;
+            output.record_artifact(&archive, &paths_json);
+            output.record_phase(BuildPhase::Solve, start);
+            if !tool_configuration.no_clean {
+                directories.clean().into_diagnostic()?;
             }
-        });
+            return Ok((output, archive));
+        }
 
-    match output.run_build_script().await {
-        Ok(_) => {}
-        Err(InterpreterError::Debug(info)) => {
-            tracing::info!("{}", info);
-            return Err(miette::miette!(
-                "Script not executed because debug mode is enabled"
-            ));
+        output
+            .install_environments(tool_configuration)
+            .await
+            .into_diagnostic()
+            .with_error_code(ErrorCode::INSTALL)?;
+        output.record_phase(BuildPhase::Solve, start);
+
+        // Compute the set of files added during install_environments. This
+        // includes both properly-recorded dependency files *and* untracked
+        // post-link artifacts. Passing this delta to create_package ensures
+        // the untracked artifacts are excluded without accidentally hiding
+        // files restored from a staging cache (which existed before install).
+        let install_added_files = record_files(&output.build_configuration.directories.host_prefix)
+            .ok()
+            .map(|post| {
+                if let Some(pre) = &pre_install_files {
+                    post.difference(pre).cloned().collect()
+                } else {
+                    post
+                }
+            });
+        state.environments_created(&output, install_added_files.clone())?;
+        (output, install_added_files)
+    };
+
+    if !state.is_completed(CompletedPhase::ScriptSucceeded) {
+        let start = chrono::Utc::now();
+        let hermetic = hermetic::is_hermetic(&output);
+        if hermetic {
+            hermetic::check_build_script(&output).with_error_code(ErrorCode::SCRIPT_HERMETIC)?;
         }
-        Err(InterpreterError::ExecutionFailed(_)) => {
-            return Err(miette::miette!("Script failed to execute"));
+        match output.run_build_script().await {
+            Ok(_) => {}
+            Err(InterpreterError::Debug(info)) => {
+                tracing::info!("{}", info);
+                return Err(miette::miette!(
+                    "Script not executed because debug mode is enabled"
+                ))
+                .with_error_code(ErrorCode::SCRIPT_DEBUG);
+            }
+            Err(InterpreterError::ExecutionFailed(_)) => {
+                return Err(miette::miette!("Script failed to execute"))
+                    .with_error_code(ErrorCode::SCRIPT_EXECUTION);
+            }
+            Err(InterpreterError::NonZeroExitCode(_)) => {
+                if hermetic {
+                    hermetic::report_denied_accesses(&output);
+                }
+                return Err(miette::miette!("Script failed to execute"))
+                    .with_error_code(ErrorCode::SCRIPT_EXIT);
+            }
         }
+
+        output.record_phase(BuildPhase::Build, start);
+        state.script_succeeded()?;
+    }
+
+    // The package of the interrupted build was already created and tested
+    if let Some(artifact) = state
+        .artifact()
+        .filter(|_| state.is_completed(CompletedPhase::Packaged))
+    {
+        let paths_json: PathsJson = read_package_file(artifact).into_diagnostic()?;
+        output.record_artifact(artifact, &paths_json);
+        return Ok((output, artifact.to_path_buf()));
     }
 
     // Package all the new files
+    let start = chrono::Utc::now();
     let (result, paths_json) = output
         .create_package(tool_configuration, install_added_files.as_ref())
         .await
-        .into_diagnostic()?;
+        .into_diagnostic()
+        .with_error_code(ErrorCode::PACKAGE)?;
+
+    if let Some(policy) = &tool_configuration.license_policy {
+        tracing::info!("Checking the licenses against the license policy ...");
+        let violations = license_policy::check_output(&output, policy, tool_configuration).await;
+        if !violations.is_empty() {
+            license_policy::print_violations(&output, &violations);
+            if policy.on_violation.unwrap_or_default() == PolicyAction::Fail {
+                return Err(miette::miette!(
+                    help = "Change the licenses in `[license-policy]` of the configuration, or add the packages to its `ignore` list",
+                    "The licenses of {} violate the license policy",
+                    output.identifier()
+                ))
+                .with_error_code(ErrorCode::LICENSE_POLICY);
+            }
+        }
+    }
 
     // Check for binary prefix if configured
     if tool_configuration.error_prefix_in_binary {
         tracing::info!("Checking for embedded prefix in binary files...");
-        check_for_binary_prefix(&output, &paths_json)?;
+        check_for_binary_prefix(&output, &paths_json)
+            .with_error_code(ErrorCode::PREFIX_IN_BINARY)?;
     }
 
     // Check for symlinks on Windows if not allowed
@@ -216,7 +376,8 @@ pub async fn run_build(
         && !tool_configuration.allow_symlinks_on_windows
     {
         tracing::info!("Checking for symlinks ...");
-        check_for_symlinks_on_windows(&output, &paths_json)?;
+        check_for_symlinks_on_windows(&output, &paths_json)
+            .with_error_code(ErrorCode::SYMLINKS_ON_WINDOWS)?;
     }
 
     output.record_artifact(&result, &paths_json);
@@ -228,16 +389,47 @@ pub async fn run_build(
     for test in output.recipe.tests() {
         if let TestType::PackageContents { package_contents } = test {
             package_contents
-                .run_test(&paths_json, &output)
-                .into_diagnostic()?;
+                .run_test(
+                    &paths_json,
+                    output.target_platform(),
+                    output.is_python_version_independent(),
+                )
+                .into_diagnostic()
+                .with_error_code(ErrorCode::PACKAGE_CONTENTS)?;
+        }
+    }
+    state.packaged(&result)?;
+
+    if let Some(settings) = &tool_configuration.audit {
+        let report = audit::audit_output(&output, tool_configuration, settings.osv_url.as_ref())
+            .await
+            .into_diagnostic()
+            .with_error_code(ErrorCode::AUDIT)?;
+        audit::print_report(&report);
+        if let Some(threshold) = settings.fail_on {
+            report
+                .check(threshold)
+                .into_diagnostic()
+                .with_error_code(ErrorCode::AUDIT)?;
         }
     }
 
+    if let Some(mode) = tool_configuration.slsa_provenance {
+        slsa::write_provenance(&output, &result, mode)
+            .into_diagnostic()
+            .with_error_code(ErrorCode::PROVENANCE)?;
+    }
+
+    if let Some(key) = &remote_cache_key {
+        remote_cache::store(&output, key, &result, tool_configuration).await;
+    }
+
     if !tool_configuration.no_clean {
         directories.clean().into_diagnostic()?;
     }
 
     drop(enter);
+    output.record_phase(BuildPhase::Package, start);
 
     if !tool_configuration.no_clean {
         directories.clean().into_diagnostic()?;
@@ -246,6 +438,22 @@ pub async fn run_build(
     Ok((output, result))
 }
 
+/// Emit the solved build and host environments of `output`
+fn emit_solved_environments(output: &Output) {
+    let Some(dependencies) = &output.finalized_dependencies else {
+        return;
+    };
+    for (environment, resolved) in [("build", &dependencies.build), ("host", &dependencies.host)] {
+        if let Some(resolved) = resolved {
+            events::emit(|| Event::EnvironmentSolved {
+                package: output.identifier(),
+                environment: environment.to_string(),
+                packages: resolved.resolved.iter().map(SolvedPackage::from).collect(),
+            });
+        }
+    }
+}
+
 /// Check if any binary files contain the host prefix
 fn check_for_binary_prefix(output: &Output, paths_json: &PathsJson) -> Result<(), miette::Error> {
     use rattler_conda_types::package::FileMode;
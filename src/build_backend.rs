@@ -0,0 +1,443 @@
+//! rattler-build as a build backend of pixi (`rattler-build build-backend`).
+//!
+//! `pixi build` starts the backend and talks to it with JSON-RPC 2.0: every request is one line
+//! of JSON on stdin, and every response is one line of JSON on stdout (the logs are written to
+//! stderr). The backend is initialized with the pixi manifest, next to which the `recipe.yaml`
+//! is found, and then lists the packages of the recipe (`conda/outputs`) or builds them
+//! (`conda/build`). The configuration files and the cache directory are the same as the ones
+//! of the `rattler-build` command line, so that caches are shared with other builds.
+//!
+//! The protocol is described in `docs/build_backend.md`. Its version is [`PROTOCOL_VERSION`],
+//! which changes only with incompatible changes of the protocol.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use miette::IntoDiagnostic;
+use rattler_conda_types::{NamedChannelOrUrl, Platform};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    build_and_collect_packages, config, get_build_output, get_recipe_path, get_tool_config,
+    metadata::Output,
+    opt::{BuildData, BuildOpts, CommonOpts},
+    outputs::PackageOutput,
+    skip_noarch,
+};
+
+/// The version of the protocol
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The JSON-RPC error code of a request that is not valid JSON
+pub(crate) const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC error code of an unknown method
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code of invalid parameters
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+/// The error code of a request before `initialize`
+const NOT_INITIALIZED: i64 = -32002;
+/// The error code of a failed render or build, the error code of rattler-build (e.g.
+/// `RB-SCRIPT-001`) is in the `data` of the error
+const BUILD_FAILED: i64 = -32000;
+
+/// A JSON-RPC request
+#[derive(Debug, Clone, Deserialize)]
+pub struct Request {
+    /// The id of the request, `None` for notifications
+    #[serde(default)]
+    pub id: Option<Value>,
+    /// The method, e.g. `conda/build`
+    pub method: String,
+    /// The parameters of the method
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// A JSON-RPC response
+#[derive(Debug, Clone, Serialize)]
+pub struct Response {
+    jsonrpc: &'static str,
+    /// The id of the request
+    pub id: Value,
+    /// The result of a successful request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error of a failed request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ResponseError>,
+}
+
+/// The error of a failed request
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseError {
+    /// The JSON-RPC error code
+    pub code: i64,
+    /// The error message
+    pub message: String,
+    /// The error code of rattler-build and the causes of the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl ResponseError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn from_report(error: &miette::Report) -> Self {
+        Self {
+            code: BUILD_FAILED,
+            message: error.to_string(),
+            data: Some(serde_json::json!({
+                "code": error.code().map(|code| code.to_string()),
+                "causes": error.chain().skip(1).map(|e| e.to_string()).collect::<Vec<_>>(),
+            })),
+        }
+    }
+}
+
+impl Response {
+    pub(crate) fn new(id: Value, result: Result<Value, ResponseError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// The parameters of `initialize`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct InitializeParams {
+    /// The pixi manifest (or its directory), the recipe is the `recipe.yaml` next to it or in
+    /// the `recipe` directory next to it
+    pub manifest_path: PathBuf,
+    /// The cache directory of rattler-build (defaults to the one of the configuration)
+    #[serde(default)]
+    pub cache_directory: Option<PathBuf>,
+    /// A configuration file that is read after the configuration files that are found from the
+    /// directory of the manifest upwards
+    #[serde(default)]
+    pub config_file: Option<PathBuf>,
+}
+
+/// The platforms, channels and variants of `conda/outputs` and `conda/build`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TargetParams {
+    /// The platform that the packages are built for (defaults to the current platform)
+    #[serde(default)]
+    pub host_platform: Option<Platform>,
+    /// The platform that the packages are built on (defaults to the current platform)
+    #[serde(default)]
+    pub build_platform: Option<Platform>,
+    /// The channels of the dependencies (defaults to the channels of the configuration)
+    #[serde(default)]
+    pub channels: Option<Vec<NamedChannelOrUrl>>,
+    /// Variant keys and their values, they override the variant configuration files
+    #[serde(default)]
+    pub variant_configuration: BTreeMap<String, Vec<String>>,
+    /// Variant configuration files in addition to the `variants.yaml` next to the recipe
+    #[serde(default)]
+    pub variant_files: Vec<PathBuf>,
+}
+
+/// The parameters of `conda/build`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BuildParams {
+    /// The platforms, channels and variants
+    #[serde(flatten)]
+    pub target: TargetParams,
+    /// The names of the packages to build (defaults to all packages of the recipe)
+    #[serde(default)]
+    pub outputs: Option<Vec<String>>,
+    /// The directory that the packages are written to (defaults to the output directory of the
+    /// configuration)
+    #[serde(default)]
+    pub output_directory: Option<PathBuf>,
+}
+
+/// A package that was built
+#[derive(Debug, Clone, Serialize)]
+struct BuiltPackage {
+    #[serde(flatten)]
+    package: PackageOutput,
+    /// The path of the package file
+    path: PathBuf,
+}
+
+/// The state of the build backend between requests
+#[derive(Debug, Default)]
+pub struct BuildBackend {
+    recipe_path: Option<PathBuf>,
+    config: Option<config::Config>,
+    cache_directory: Option<PathBuf>,
+}
+
+/// The recipe of a pixi manifest: `recipe.yaml` or `recipe/recipe.yaml` next to it
+fn find_recipe(manifest_path: &Path) -> miette::Result<PathBuf> {
+    let dir = if manifest_path.is_dir() {
+        manifest_path
+    } else {
+        manifest_path.parent().unwrap_or(Path::new("."))
+    };
+    let nested = dir.join("recipe");
+    if !dir.join("recipe.yaml").is_file() && nested.join("recipe.yaml").is_file() {
+        return get_recipe_path(&nested);
+    }
+    get_recipe_path(dir)
+}
+
+pub(crate) fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ResponseError> {
+    // requests without parameters have the default parameters
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| ResponseError::new(INVALID_PARAMS, e.to_string()))
+}
+
+impl BuildBackend {
+    /// Create a backend that is not initialized yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle one request. Returns `None` for notifications (requests without an id).
+    pub async fn handle_request(&mut self, request: Request) -> Option<Response> {
+        let result = match request.method.as_str() {
+            "initialize" => match parse_params(request.params) {
+                Ok(params) => self.initialize(params),
+                Err(e) => Err(e),
+            },
+            "conda/outputs" => match parse_params(request.params) {
+                Ok(params) => self.outputs(params).await,
+                Err(e) => Err(e),
+            },
+            "conda/build" => match parse_params(request.params) {
+                Ok(params) => self.build(params).await,
+                Err(e) => Err(e),
+            },
+            "shutdown" => Ok(Value::Null),
+            method => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{method}`"),
+            )),
+        };
+        request.id.map(|id| Response::new(id, result))
+    }
+
+    fn initialize(&mut self, params: InitializeParams) -> Result<Value, ResponseError> {
+        let mut initialize = || -> miette::Result<Value> {
+            let recipe_path = find_recipe(&params.manifest_path)?;
+            let search_dir = recipe_path.parent().map(Path::to_path_buf);
+            self.config =
+                config::load_config(search_dir.as_deref(), params.config_file.as_deref())?;
+            self.cache_directory = params.cache_directory.clone();
+            self.recipe_path = Some(recipe_path.clone());
+            Ok(serde_json::json!({
+                "protocol_version": PROTOCOL_VERSION,
+                "rattler_build_version": crate::get_rattler_build_version(),
+                "recipe_path": recipe_path,
+            }))
+        };
+        initialize().map_err(|e| ResponseError::from_report(&e))
+    }
+
+    /// The build data of a request, like the options of `rattler-build build`
+    fn build_data(
+        &self,
+        target: TargetParams,
+        output_directory: Option<PathBuf>,
+    ) -> Result<(PathBuf, BuildData), ResponseError> {
+        let recipe_path = self
+            .recipe_path
+            .clone()
+            .ok_or_else(|| ResponseError::new(NOT_INITIALIZED, "the backend is not initialized"))?;
+        let opts = BuildOpts {
+            recipes: vec![recipe_path.clone()],
+            build_platform: target.build_platform,
+            host_platform: target.host_platform,
+            channels: target.channels,
+            variant_config: (!target.variant_files.is_empty()).then_some(target.variant_files),
+            variant_overrides: target.variant_configuration.into_iter().collect(),
+            common: CommonOpts {
+                output_dir: output_directory,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut build_data = BuildData::from_opts_and_config(opts, self.config.clone());
+        if let Some(cache_directory) = &self.cache_directory {
+            build_data.common.cache_dir = Some(cache_directory.clone());
+        }
+        Ok((recipe_path, build_data))
+    }
+
+    /// Render the outputs of the recipe, without the noarch outputs that are built on another
+    /// platform
+    async fn render(
+        recipe_path: &Path,
+        build_data: &BuildData,
+    ) -> miette::Result<(Vec<Output>, crate::tool_configuration::Configuration)> {
+        let tool_config = get_tool_config(build_data, &None)?;
+        let outputs = get_build_output(build_data, recipe_path, &tool_config).await?;
+        let outputs = skip_noarch(outputs, &tool_config).await?;
+        Ok((outputs, tool_config))
+    }
+
+    async fn outputs(&self, params: TargetParams) -> Result<Value, ResponseError> {
+        let (recipe_path, build_data) = self.build_data(params, None)?;
+        let outputs = async {
+            let (outputs, _) = Self::render(&recipe_path, &build_data).await?;
+            let packages = outputs.iter().map(PackageOutput::from).collect::<Vec<_>>();
+            serde_json::to_value(serde_json::json!({ "outputs": packages })).into_diagnostic()
+        };
+        outputs.await.map_err(|e| ResponseError::from_report(&e))
+    }
+
+    async fn build(&self, params: BuildParams) -> Result<Value, ResponseError> {
+        let (recipe_path, build_data) = self.build_data(params.target, params.output_directory)?;
+        let build = async {
+            let (mut outputs, tool_config) = Self::render(&recipe_path, &build_data).await?;
+            if let Some(names) = &params.outputs {
+                outputs.retain(|output| {
+                    names
+                        .iter()
+                        .any(|name| name == output.name().as_normalized())
+                });
+            }
+            let packages = outputs.iter().map(PackageOutput::from).collect::<Vec<_>>();
+            let paths = build_and_collect_packages(outputs, &tool_config, None).await?;
+            let built = paths
+                .into_iter()
+                .filter_map(|path| {
+                    let filename = path.file_name()?.to_string_lossy().to_string();
+                    let package = packages
+                        .iter()
+                        .find(|package| package.filename == filename)?;
+                    Some(BuiltPackage {
+                        package: package.clone(),
+                        path,
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_value(serde_json::json!({ "packages": built })).into_diagnostic()
+        };
+        build.await.map_err(|e| ResponseError::from_report(&e))
+    }
+}
+
+/// Serve the build backend: read one request per line from `reader` and write one response
+/// per line to `writer`, until `shutdown` or the end of the input
+pub async fn serve(
+    reader: impl AsyncBufRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+) -> miette::Result<()> {
+    let mut backend = BuildBackend::new();
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await.into_diagnostic()? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (response, shutdown) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let shutdown = request.method == "shutdown";
+                (backend.handle_request(request).await, shutdown)
+            }
+            Err(e) => (
+                Some(Response::new(
+                    Value::Null,
+                    Err(ResponseError::new(PARSE_ERROR, e.to_string())),
+                )),
+                false,
+            ),
+        };
+        if let Some(response) = response {
+            let mut line = serde_json::to_string(&response).into_diagnostic()?;
+            line.push('\n');
+            writer.write_all(line.as_bytes()).await.into_diagnostic()?;
+            writer.flush().await.into_diagnostic()?;
+        }
+        if shutdown {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(backend: &mut BuildBackend, method: &str, params: Value) -> Response {
+        backend
+            .handle_request(Request {
+                id: Some(Value::from(1)),
+                method: method.to_string(),
+                params,
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let mut backend = BuildBackend::new();
+        let response = request(&mut backend, "conda/outputs", Value::Null).await;
+        assert_eq!(response.error.unwrap().code, NOT_INITIALIZED);
+
+        let response = request(&mut backend, "conda/unknown", Value::Null).await;
+        assert_eq!(response.error.unwrap().code, METHOD_NOT_FOUND);
+
+        let response = request(&mut backend, "initialize", Value::Null).await;
+        assert_eq!(response.error.unwrap().code, INVALID_PARAMS);
+
+        // notifications have no response
+        let response = backend
+            .handle_request(Request {
+                id: None,
+                method: "shutdown".to_string(),
+                params: Value::Null,
+            })
+            .await;
+        assert!(response.is_none());
+    }
+
+    #[test]
+    fn test_find_recipe() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("pixi.toml");
+        fs_err::write(&manifest, "").unwrap();
+        assert!(find_recipe(&manifest).is_err());
+
+        fs_err::create_dir(dir.path().join("recipe")).unwrap();
+        fs_err::write(dir.path().join("recipe/recipe.yaml"), "").unwrap();
+        assert!(
+            find_recipe(&manifest)
+                .unwrap()
+                .ends_with("recipe/recipe.yaml")
+        );
+
+        // a recipe next to the manifest takes priority
+        fs_err::write(dir.path().join("recipe.yaml"), "").unwrap();
+        let recipe = find_recipe(dir.path()).unwrap();
+        assert_eq!(recipe.file_name().unwrap(), "recipe.yaml");
+        assert!(!recipe.parent().unwrap().ends_with("recipe"));
+    }
+}
@@ -0,0 +1,274 @@
+//! The log files of the builds in `<output_dir>/logs`.
+//!
+//! Every output that is built gets an uncolored log file with everything that is logged while it
+//! is built and tested (`logs/<subdir>/<name>-<version>-<build>.log`), in addition to the console
+//! output. `logs/index.json` maps the packages to their log files and the outcome of their latest
+//! build, so that CI jobs can upload the logs as artifacts and find the log of a failed package.
+//!
+//! The log is written by [`BuildLogLayer`] for all events inside a span with a `build_log` field
+//! that holds the path of the log file.
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use tracing::{Level, field};
+use tracing_core::{Event, Field, Subscriber, span::Id};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{console_utils::strip_ansi_codes, metadata::Output};
+
+/// The name of the directory of the logs in the output directory
+const LOGS_DIR: &str = "logs";
+
+/// The name of the index file in the logs directory
+const INDEX_FILE: &str = "index.json";
+
+/// The path of the log file of `output`
+pub(crate) fn log_path(output: &Output) -> PathBuf {
+    output
+        .build_configuration
+        .directories
+        .output_dir
+        .join(LOGS_DIR)
+        .join(output.target_platform().to_string())
+        .join(format!("{}.log", output.identifier()))
+}
+
+/// Start a new log file for the build of `output`, replacing the log of an earlier build
+pub(crate) fn start(output: &Output) -> std::io::Result<PathBuf> {
+    let path = log_path(output);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::File::create(&path)?;
+    Ok(path)
+}
+
+/// The outcome of the latest build of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BuildStatus {
+    Success,
+    Failed,
+}
+
+/// An entry of the index of the logs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct LogIndexEntry {
+    /// The log file, relative to the logs directory
+    pub log: PathBuf,
+    /// The outcome of the build
+    pub status: BuildStatus,
+    /// The package file, if the build succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<PathBuf>,
+    /// When the build finished
+    pub finished: DateTime<Utc>,
+}
+
+/// Serializes the updates of the index by builds that run concurrently
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// Record the outcome of the build of `output` in the index of the logs. The index maps the
+/// identifiers of the packages (`subdir/name-version-build`) to their entries.
+pub(crate) fn record(
+    output: &Output,
+    status: BuildStatus,
+    artifact: Option<&Path>,
+) -> std::io::Result<()> {
+    let _lock = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let logs_dir = output
+        .build_configuration
+        .directories
+        .output_dir
+        .join(LOGS_DIR);
+    let index_path = logs_dir.join(INDEX_FILE);
+    let mut index: BTreeMap<String, LogIndexEntry> = fs::read(&index_path)
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default();
+
+    let log = log_path(output);
+    index.insert(
+        format!("{}/{}", output.target_platform(), output.identifier()),
+        LogIndexEntry {
+            log: log.strip_prefix(&logs_dir).unwrap_or(&log).to_path_buf(),
+            status,
+            artifact: artifact.map(Path::to_path_buf),
+            finished: Utc::now(),
+        },
+    );
+
+    fs::create_dir_all(&logs_dir)?;
+    let temp_path = index_path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&index)?)?;
+    fs::rename(&temp_path, &index_path)
+}
+
+/// The log file of a span with a `build_log` field
+struct BuildLogFile {
+    file: Arc<Mutex<fs::File>>,
+    /// The depth of the span, the log is indented relative to it
+    depth: usize,
+}
+
+/// Finds the `build_log` field of a span
+#[derive(Default)]
+struct BuildLogVisitor(Option<PathBuf>);
+
+impl field::Visit for BuildLogVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "build_log" && !value.is_empty() {
+            self.0 = Some(PathBuf::from(value));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "build_log" {
+            self.record_str(field, &format!("{value:?}"));
+        }
+    }
+}
+
+/// Formats the message and the `recipe` and `package` fields, as in the console output
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{value}"));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.0.push_str(&format!("{value:?}")),
+            "recipe" | "package" => self.0.push_str(&format!(" {}: {value:?}", field.name())),
+            _ => {}
+        }
+    }
+}
+
+/// A layer that writes the events inside a span with a `build_log` field to the log file
+pub struct BuildLogLayer;
+
+impl BuildLogLayer {
+    fn write<S>(ctx: &Context<'_, S>, id: Option<&Id>, depth: usize, text: &str)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(span) = id.and_then(|id| ctx.span(id)) else {
+            return;
+        };
+        for span in span.scope() {
+            let extensions = span.extensions();
+            let Some(log) = extensions.get::<BuildLogFile>() else {
+                continue;
+            };
+            let indent = "  ".repeat(depth.saturating_sub(log.depth));
+            let mut file = log.file.lock().unwrap();
+            for line in strip_ansi_codes(text).lines() {
+                // a failing log file must never fail the build
+                let _ = writeln!(file, "{indent}{line}");
+            }
+            return;
+        }
+    }
+}
+
+impl<S> Layer<S> for BuildLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &Id,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let depth = span.scope().count();
+
+        let mut visitor = BuildLogVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(path) = visitor.0 {
+            let file = fs::OpenOptions::new().create(true).append(true).open(&path);
+            if let Ok(file) = file {
+                span.extensions_mut().insert(BuildLogFile {
+                    file: Arc::new(Mutex::new(file)),
+                    depth,
+                });
+            }
+        }
+
+        let mut message = MessageVisitor::default();
+        attrs.record(&mut message);
+        let header = format!("{}{}", span.name(), message.0);
+        Self::write(&ctx, Some(id), depth - 1, &format!("╭─ {header}"));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.event_span(event) else {
+            return;
+        };
+        let depth = span.scope().count();
+
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "error: ",
+            Level::WARN => "warning: ",
+            _ => "",
+        };
+        let text = message
+            .0
+            .lines()
+            .map(|line| format!("{prefix}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self::write(&ctx, Some(&span.id()), depth, &text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn test_build_log_layer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let log = tmp.path().join("foo.log");
+        let subscriber = tracing_subscriber::registry().with(BuildLogLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not part of a build");
+            let span = tracing::info_span!(
+                "Running build for",
+                recipe = "foo-1.0-h123_0",
+                build_log = %log.display()
+            );
+            let _enter = span.enter();
+            tracing::info!("{}", console::style("colored").green());
+            let inner = tracing::info_span!("Fetching source code");
+            let _inner = inner.enter();
+            tracing::warn!("first line\nsecond line");
+        });
+
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "╭─ Running build for recipe: foo-1.0-h123_0\n\
+             colored\n\
+             ╭─ Fetching source code\n\
+             \x20 warning: first line\n\
+             \x20 warning: second line\n"
+        );
+    }
+}
@@ -0,0 +1,438 @@
+//! Solve all environments of a build without building anything (`build --dry-run=solve`).
+//!
+//! The recipes are rendered, and the build, host and test environments of every output are
+//! solved in build order. No sources are fetched and no build scripts are run. Outputs that
+//! depend on other outputs of the same build are solved against a placeholder record of those
+//! outputs, so the plan matches the one of a real build.
+use std::collections::{BTreeMap, BTreeSet};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_build_recipe::stage1::{TestType, tests::PythonVersion};
+use rattler_conda_types::{
+    Channel, ChannelUrl, MatchSpec, PackageRecord, ParseStrictness, Platform, RepoDataRecord,
+};
+use rattler_repodata_gateway::{CacheClearMode, SubdirSelection};
+
+use crate::{
+    events::{self, Event, Phase, SolvedPackage},
+    metadata::{Output, PlatformWithVirtualPackages, build_reindexed_channels},
+    package_test::resolve_dependency,
+    render::{resolved_dependencies::RunExportsDownload, solver::solve_environment},
+    tool_configuration::{Configuration, TestStrategy},
+};
+
+/// A local channel with a placeholder record for every output that was solved, so that the
+/// outputs that depend on it (and the tests of the output itself) can be solved
+struct PlanChannel {
+    dir: tempfile::TempDir,
+    subdirs: BTreeSet<Platform>,
+    records: Vec<PackageRecord>,
+}
+
+impl PlanChannel {
+    fn new(outputs: &[Output]) -> miette::Result<Self> {
+        let mut subdirs = BTreeSet::from([Platform::NoArch]);
+        for output in outputs {
+            let config = &output.build_configuration;
+            subdirs.insert(config.target_platform);
+            subdirs.insert(config.host_platform.platform);
+            subdirs.insert(config.build_platform.platform);
+        }
+        let channel = Self {
+            dir: tempfile::tempdir().into_diagnostic()?,
+            subdirs,
+            records: Vec::new(),
+        };
+        channel.write_repodata()?;
+        Ok(channel)
+    }
+
+    fn url(&self) -> ChannelUrl {
+        Channel::from_directory(self.dir.path()).base_url
+    }
+
+    /// Add the placeholder record of a solved output
+    fn add(&mut self, output: &Output, tool_configuration: &Configuration) -> miette::Result<()> {
+        let mut record = PackageRecord::from_index_json(
+            output.index_json().into_diagnostic()?,
+            None,
+            None,
+            None,
+        )
+        .into_diagnostic()?;
+        // the run exports are read from the record instead of the (missing) package file
+        record.run_exports = output
+            .finalized_dependencies
+            .as_ref()
+            .map(|dependencies| dependencies.run.run_exports.clone());
+        self.records.push(record);
+        self.write_repodata()?;
+
+        tool_configuration
+            .repodata_gateway
+            .clear_repodata_cache(
+                &Channel::from_directory(self.dir.path()),
+                SubdirSelection::All,
+                CacheClearMode::InMemoryOnly,
+            )
+            .into_diagnostic()
+    }
+
+    fn write_repodata(&self) -> miette::Result<()> {
+        for subdir in &self.subdirs {
+            let packages = self
+                .records
+                .iter()
+                .filter(|record| record.subdir == subdir.as_str())
+                .map(|record| {
+                    let file_name = format!(
+                        "{}-{}-{}.conda",
+                        record.name.as_normalized(),
+                        record.version,
+                        record.build
+                    );
+                    (file_name, record)
+                })
+                .collect::<BTreeMap<_, _>>();
+            let repodata = serde_json::json!({
+                "info": { "subdir": subdir.as_str() },
+                "packages": {},
+                "packages.conda": packages,
+            });
+            let dir = self.dir.path().join(subdir.as_str());
+            fs::create_dir_all(&dir).into_diagnostic()?;
+            fs::write(
+                dir.join("repodata.json"),
+                serde_json::to_string(&repodata).into_diagnostic()?,
+            )
+            .into_diagnostic()?;
+        }
+        Ok(())
+    }
+}
+
+/// An environment of the plan that has to be solved
+struct PlannedEnvironment {
+    name: String,
+    platform: PlatformWithVirtualPackages,
+    specs: Vec<MatchSpec>,
+}
+
+/// The test environments of `output`. The test environments contain the package itself, which
+/// is available from the plan channel.
+fn test_environments(
+    output: &Output,
+    tool_configuration: &Configuration,
+) -> miette::Result<Vec<PlannedEnvironment>> {
+    let config = &output.build_configuration;
+    let package = MatchSpec::from_str(
+        &format!(
+            "{}={}={}",
+            output.name().as_normalized(),
+            output.version(),
+            output.build_string()
+        ),
+        ParseStrictness::Lenient,
+    )
+    .into_diagnostic()?;
+    let run_environment = |name: String, mut specs: Vec<MatchSpec>| {
+        specs.push(package.clone());
+        specs.extend(tool_configuration.test_extra_specs.iter().cloned());
+        PlannedEnvironment {
+            name,
+            platform: config.host_platform.clone(),
+            specs,
+        }
+    };
+    let spec = |spec: &str| MatchSpec::from_str(spec, ParseStrictness::Lenient).into_diagnostic();
+
+    let mut environments = Vec::new();
+    for (index, test) in output.recipe.tests().iter().enumerate() {
+        let name = |kind: &str| format!("test ({kind} #{})", index + 1);
+        match test {
+            TestType::Commands(commands) => {
+                let resolve =
+                    |deps: &[rattler_build_recipe::stage1::requirements::Dependency]| {
+                        deps.iter()
+                            .map(|dep| resolve_dependency(dep, output).as_match_spec().clone())
+                            .collect::<Vec<_>>()
+                    };
+                if !commands.requirements.build.is_empty() {
+                    environments.push(PlannedEnvironment {
+                        name: format!("{} build", name("commands")),
+                        platform: config.build_platform.clone(),
+                        specs: resolve(&commands.requirements.build),
+                    });
+                }
+                environments.push(run_environment(
+                    name("commands"),
+                    resolve(&commands.requirements.run),
+                ));
+            }
+            TestType::Python { python } => {
+                let versions = match &python.python_version {
+                    PythonVersion::Single(version) => vec![Some(version.clone())],
+                    PythonVersion::Multiple(versions) => {
+                        versions.iter().cloned().map(Some).collect()
+                    }
+                    PythonVersion::None => vec![None],
+                };
+                for version in versions {
+                    let mut specs = Vec::new();
+                    let mut name = name("python");
+                    if let Some(version) = version {
+                        specs.push(spec(&format!("python={version}"))?);
+                        name = format!("{name} python {version}");
+                    }
+                    if python.pip_check {
+                        specs.push(spec("pip")?);
+                    }
+                    environments.push(run_environment(name, specs));
+                }
+            }
+            TestType::Perl { .. } => {
+                environments.push(run_environment(name("perl"), vec![spec("perl")?]))
+            }
+            TestType::R { .. } => {
+                environments.push(run_environment(name("r"), vec![spec("r-base")?]))
+            }
+            TestType::Ruby { .. } => {
+                environments.push(run_environment(name("ruby"), vec![spec("ruby")?]))
+            }
+            // downstream packages are not built, the package contents are checked after the build
+            TestType::Downstream(_) | TestType::PackageContents { .. } => {}
+        }
+    }
+    Ok(environments)
+}
+
+/// The result of solving an environment of the plan
+struct SolvedEnvironment {
+    package: String,
+    environment: String,
+    platform: Platform,
+    result: Result<Vec<RepoDataRecord>, String>,
+}
+
+/// Solve the build, host and test environments of `outputs` (in build order) and report the
+/// plan. Fails if any environment could not be solved.
+pub(crate) async fn solve_plan(
+    outputs: Vec<Output>,
+    tool_configuration: &Configuration,
+) -> miette::Result<()> {
+    let mut plan_channel = PlanChannel::new(&outputs)?;
+    let mut solved = Vec::new();
+
+    for mut output in outputs {
+        let package = output.identifier();
+        let span = tracing::info_span!(
+            "Solving environments for",
+            recipe = package,
+            span_color = package
+        );
+        let _enter = span.enter();
+        events::emit(|| Event::PhaseStart {
+            phase: Phase::Solve,
+            package: Some(package.clone()),
+        });
+        let start = std::time::Instant::now();
+
+        // the outputs that were solved before are available like in a real build
+        output
+            .build_configuration
+            .channels
+            .insert(0, plan_channel.url());
+        let config = output.build_configuration.clone();
+        // the packages in the output directory are used like in a real build, the build
+        // directories are not created
+        fs::create_dir_all(&config.directories.output_dir).into_diagnostic()?;
+
+        let result = output
+            .resolve_dependencies(tool_configuration, RunExportsDownload::DownloadMissing)
+            .await
+            .into_diagnostic();
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                tracing::error!("Failed to solve the environments of {}: {}", package, e);
+                solved.push(SolvedEnvironment {
+                    package: package.clone(),
+                    environment: "build / host".to_string(),
+                    platform: config.host_platform.platform,
+                    result: Err(e.to_string()),
+                });
+                events::emit(|| {
+                    Event::phase_end(
+                        Phase::Solve,
+                        Some(package),
+                        start.elapsed(),
+                        &Err::<(), _>(e),
+                    )
+                });
+                continue;
+            }
+        };
+
+        let dependencies = output
+            .finalized_dependencies
+            .as_ref()
+            .expect("dependencies are resolved");
+        for (environment, resolved, platform) in [
+            ("build", &dependencies.build, config.build_platform.platform),
+            ("host", &dependencies.host, config.host_platform.platform),
+        ] {
+            if let Some(resolved) = resolved {
+                events::emit(|| Event::EnvironmentSolved {
+                    package: package.clone(),
+                    environment: environment.to_string(),
+                    packages: resolved.resolved.iter().map(SolvedPackage::from).collect(),
+                });
+                solved.push(SolvedEnvironment {
+                    package: package.clone(),
+                    environment: environment.to_string(),
+                    platform,
+                    result: Ok(resolved.resolved.clone()),
+                });
+            }
+        }
+
+        plan_channel.add(&output, tool_configuration)?;
+
+        let mut failed = None;
+        if !matches!(tool_configuration.test_strategy, TestStrategy::Skip) {
+            let mut channels = build_reindexed_channels(&config, tool_configuration)
+                .await
+                .into_diagnostic()?;
+            channels.splice(1..1, tool_configuration.test_channels.iter().cloned());
+            for environment in test_environments(&output, tool_configuration)? {
+                let result =
+                    solve_test_environment(&environment, &channels, &output, tool_configuration)
+                        .await;
+                match &result {
+                    Ok(records) => events::emit(|| Event::EnvironmentSolved {
+                        package: package.clone(),
+                        environment: environment.name.clone(),
+                        packages: records.iter().map(SolvedPackage::from).collect(),
+                    }),
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to solve the {} environment of {}: {}",
+                            environment.name,
+                            package,
+                            e
+                        );
+                        failed = Some(e.clone());
+                    }
+                }
+                solved.push(SolvedEnvironment {
+                    package: package.clone(),
+                    environment: environment.name,
+                    platform: environment.platform.platform,
+                    result,
+                });
+            }
+        }
+
+        let result = failed.map_or(Ok(()), Err);
+        events::emit(|| Event::phase_end(Phase::Solve, Some(package), start.elapsed(), &result));
+    }
+
+    if solved.is_empty() {
+        tracing::info!(
+            "Dry run, nothing was built: none of the outputs have environments to solve"
+        );
+    } else {
+        tracing::info!("Dry run, nothing was built:\n{}", plan_table(&solved));
+    }
+
+    let failed = solved.iter().filter(|env| env.result.is_err()).count();
+    if failed > 0 {
+        return Err(miette::miette!(
+            "{} of {} environments could not be solved",
+            failed,
+            solved.len()
+        ));
+    }
+    Ok(())
+}
+
+async fn solve_test_environment(
+    environment: &PlannedEnvironment,
+    channels: &[ChannelUrl],
+    output: &Output,
+    tool_configuration: &Configuration,
+) -> Result<Vec<RepoDataRecord>, String> {
+    solve_environment(
+        &environment.name,
+        &environment.specs,
+        &environment.platform,
+        channels,
+        tool_configuration,
+        output.build_configuration.channel_priority,
+        output.build_configuration.solve_strategy,
+        output.build_configuration.exclude_newer,
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Render the summary table of the plan
+fn plan_table(solved: &[SolvedEnvironment]) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(["Package", "Environment", "Platform", "Result"]);
+    for env in solved {
+        let result = match &env.result {
+            Ok(records) => format!("{} packages", records.len()),
+            Err(_) => "failed to solve".to_string(),
+        };
+        table.add_row([
+            env.package.clone(),
+            env.environment.clone(),
+            env.platform.to_string(),
+            result,
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use rattler_conda_types::{PackageName, RepoData};
+
+    use super::*;
+
+    #[test]
+    fn test_plan_channel_repodata() {
+        let mut channel = PlanChannel {
+            dir: tempfile::tempdir().unwrap(),
+            subdirs: BTreeSet::from([Platform::NoArch, Platform::Linux64]),
+            records: Vec::new(),
+        };
+        let mut record = PackageRecord::new(
+            PackageName::new_unchecked("foo"),
+            "1.0".parse::<rattler_conda_types::Version>().unwrap(),
+            "h123_0".to_string(),
+        );
+        record.subdir = "linux-64".to_string();
+        channel.records.push(record);
+        channel.write_repodata().unwrap();
+
+        let read = |subdir: &str| {
+            RepoData::from_path(channel.dir.path().join(subdir).join("repodata.json")).unwrap()
+        };
+        assert!(read("noarch").conda_packages.is_empty());
+        let linux = read("linux-64");
+        assert_eq!(
+            linux
+                .conda_packages
+                .keys()
+                .map(|file_name| file_name.to_string())
+                .collect::<Vec<_>>(),
+            ["foo-1.0-h123_0.conda"]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Building independent outputs concurrently (`build --build-jobs`).
+//!
+//! An output is started as soon as all the outputs it depends on are built, as long as fewer
+//! than `build_jobs` builds are running. Outputs that share a build directory (the outputs that
+//! inherit from the same staging cache) are never built at the same time. Every build runs on its
+//! own thread and its log is captured and printed as one block once the build has finished.
+use std::path::PathBuf;
+
+use futures::{StreamExt, stream::FuturesUnordered};
+use miette::IntoDiagnostic;
+use tracing::instrument::WithSubscriber;
+
+use crate::{
+    build::{WorkingDirectoryBehavior, run_build},
+    build_log, console_utils,
+    graph::DependencyGraph,
+    metadata::Output,
+    timings::format_duration,
+    tool_configuration::{Configuration, ContinueOnFailure},
+};
+
+/// The state of an output in the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Pending,
+    Running,
+    Built,
+    /// The build failed, or it was not started because a dependency failed
+    Failed,
+}
+
+/// The outputs that are waiting to be built, in the order in which they would be built one by
+/// one
+#[derive(Debug)]
+pub(crate) struct BuildQueue {
+    /// The indices of the outputs that each output depends on
+    dependencies: Vec<Vec<usize>>,
+    build_dirs: Vec<PathBuf>,
+    states: Vec<State>,
+}
+
+impl BuildQueue {
+    /// The queue of `outputs`
+    pub fn new(outputs: &[Output]) -> Self {
+        let graph = DependencyGraph::from_outputs(outputs);
+        let index = |id: &str| graph.nodes.iter().position(|node| node.id == id);
+        let mut dependencies = vec![Vec::new(); outputs.len()];
+        for edge in &graph.edges {
+            if let (Some(from), Some(to)) = (index(&edge.from), index(&edge.to)) {
+                dependencies[to].push(from);
+            }
+        }
+        let build_dirs = outputs
+            .iter()
+            .map(|output| output.build_configuration.directories.build_dir.clone())
+            .collect();
+        Self::from_parts(dependencies, build_dirs)
+    }
+
+    fn from_parts(dependencies: Vec<Vec<usize>>, build_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            states: vec![State::Pending; dependencies.len()],
+            dependencies,
+            build_dirs,
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.states.contains(&State::Running)
+    }
+
+    fn build_dir_in_use(&self, index: usize) -> bool {
+        self.states.iter().enumerate().any(|(other, state)| {
+            *state == State::Running && self.build_dirs[other] == self.build_dirs[index]
+        })
+    }
+
+    /// The next output that can be built, which is then marked as running
+    pub fn next(&mut self) -> Option<usize> {
+        let pending = (0..self.states.len())
+            .filter(|&index| self.states[index] == State::Pending && !self.build_dir_in_use(index))
+            .collect::<Vec<_>>();
+        let ready = pending.iter().copied().find(|&index| {
+            self.dependencies[index]
+                .iter()
+                .all(|&dependency| self.states[dependency] == State::Built)
+        });
+        // Outputs that depend on each other (e.g. through run dependencies) are built in the
+        // order of the queue, once nothing else is running
+        let next = ready.or_else(|| {
+            (!self.is_running())
+                .then(|| pending.first().copied())
+                .flatten()
+        })?;
+        self.states[next] = State::Running;
+        Some(next)
+    }
+
+    /// Record the outcome of the build of `index`. Returns the outputs that can no longer be
+    /// built, because they (indirectly) depend on a failed output.
+    pub fn finish(&mut self, index: usize, success: bool) -> Vec<usize> {
+        if success {
+            self.states[index] = State::Built;
+            return Vec::new();
+        }
+        self.states[index] = State::Failed;
+        let mut skipped = Vec::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for other in 0..self.states.len() {
+                if self.states[other] == State::Pending
+                    && self.dependencies[other]
+                        .iter()
+                        .any(|&dependency| self.states[dependency] == State::Failed)
+                {
+                    self.states[other] = State::Failed;
+                    skipped.push(other);
+                    changed = true;
+                }
+            }
+        }
+        skipped
+    }
+}
+
+/// Build `outputs` with up to `build_jobs` concurrent builds. Returns the result of every build
+/// that was started, in the order of `outputs`. After a failed build no new builds are started,
+/// unless the build continues on failure.
+pub(crate) async fn build_concurrently(
+    outputs: &[Output],
+    tool_configuration: &Configuration,
+) -> miette::Result<Vec<(usize, miette::Result<(Output, PathBuf)>)>> {
+    tracing::info!(
+        "Building {} outputs with up to {} concurrent jobs",
+        outputs.len(),
+        tool_configuration.build_jobs
+    );
+    let mut queue = BuildQueue::new(outputs);
+    let mut running = FuturesUnordered::new();
+    let mut results = Vec::new();
+    let mut stopped = false;
+    loop {
+        while !stopped && running.len() < tool_configuration.build_jobs {
+            let Some(index) = queue.next() else {
+                break;
+            };
+            tracing::info!("Starting the build of {}", outputs[index].identifier());
+            let output = outputs[index].clone();
+            let tool_configuration = tool_configuration.clone();
+            let handle = tokio::runtime::Handle::current();
+            // The spans of a build are entered across `.await` points, so every build needs
+            // its own thread and subscriber to not leak into the other builds
+            running.push(tokio::task::spawn_blocking(move || {
+                let capture = console_utils::LogCapture::default();
+                let start = std::time::Instant::now();
+                let result = handle.block_on(
+                    run_build(
+                        output,
+                        &tool_configuration,
+                        WorkingDirectoryBehavior::Cleanup,
+                    )
+                    .with_subscriber(capture.subscriber()),
+                );
+                (index, result, capture, start.elapsed())
+            }));
+        }
+
+        let Some(finished) = running.next().await else {
+            break;
+        };
+        let (index, result, capture, duration) = finished.into_diagnostic()?;
+        let output = &outputs[index];
+        {
+            let span = tracing::info_span!(
+                "Build log of",
+                recipe = %output.identifier(),
+                span_color = output.identifier(),
+                build_log = %build_log::log_path(output).display()
+            );
+            let _enter = span.enter();
+            tracing::info!("{}", capture.output());
+        }
+        match &result {
+            Ok(_) => tracing::info!(
+                "Finished the build of {} in {}",
+                output.identifier(),
+                format_duration(duration)
+            ),
+            Err(e) => {
+                tracing::error!("The build of {} failed: {}", output.identifier(), e);
+                if tool_configuration.continue_on_failure == ContinueOnFailure::No {
+                    stopped = true;
+                }
+            }
+        }
+
+        for skipped in queue.finish(index, result.is_ok()) {
+            let skipped = &outputs[skipped];
+            tracing::warn!(
+                "Not building {} because {} failed to build",
+                skipped.identifier(),
+                output.identifier()
+            );
+            skipped.record_warning(&format!(
+                "Not built because {} failed to build",
+                output.identifier()
+            ));
+        }
+        results.push((index, result));
+    }
+
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(dependencies: Vec<Vec<usize>>, build_dirs: &[&str]) -> BuildQueue {
+        BuildQueue::from_parts(dependencies, build_dirs.iter().map(PathBuf::from).collect())
+    }
+
+    #[test]
+    fn test_build_queue() {
+        // 2 depends on 0, 3 depends on 2, 1 is independent
+        let mut queue = queue(
+            vec![vec![], vec![], vec![0], vec![2]],
+            &["a", "b", "c", "d"],
+        );
+        assert_eq!(queue.next(), Some(0));
+        assert_eq!(queue.next(), Some(1));
+        assert_eq!(queue.next(), None, "2 waits for 0");
+        assert!(queue.finish(0, true).is_empty());
+        assert_eq!(queue.next(), Some(2));
+        assert_eq!(queue.finish(2, false), vec![3]);
+        assert_eq!(queue.next(), None);
+        assert!(queue.finish(1, true).is_empty());
+        assert_eq!(queue.next(), None);
+    }
+
+    #[test]
+    fn test_build_queue_shared_build_dir() {
+        let mut queue = queue(vec![vec![], vec![], vec![]], &["a", "a", "b"]);
+        assert_eq!(queue.next(), Some(0));
+        assert_eq!(queue.next(), Some(2), "1 shares the build directory of 0");
+        assert_eq!(queue.next(), None);
+        queue.finish(0, true);
+        assert_eq!(queue.next(), Some(1));
+    }
+
+    #[test]
+    fn test_build_queue_cycle() {
+        let mut queue = queue(vec![vec![1], vec![0]], &["a", "b"]);
+        assert_eq!(queue.next(), Some(0));
+        assert_eq!(queue.next(), None);
+        queue.finish(0, true);
+        assert_eq!(queue.next(), Some(1));
+    }
+}
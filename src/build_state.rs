@@ -0,0 +1,290 @@
+//! The state of a build that is persisted in its build directory, so that an interrupted or
+//! failed build can be resumed from the last phase that completed (`build --resume`).
+//!
+//! After every phase the state is written to `<build_dir>/.build_state.json`. It records the
+//! directories of the build (the host prefix is padded to a fixed length and can not be moved)
+//! and everything that later phases need from the earlier ones: the finalized sources and
+//! dependencies, the files of the host prefix before the dependencies were installed and the
+//! package that was created.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_build_recipe::stage1::Source;
+use rattler_digest::compute_bytes_digest;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    metadata::Output, render::resolved_dependencies::FinalizedDependencies, types::Directories,
+};
+
+/// The name of the file in the build directory that stores the state
+const STATE_FILE: &str = ".build_state.json";
+
+/// The phases of a build after which the state is stored, in the order in which they complete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CompletedPhase {
+    /// The staging caches were restored and the sources were fetched into the work directory
+    SourcesFetched,
+    /// The dependencies were resolved and installed into the build and host prefixes
+    EnvironmentsCreated,
+    /// The build script ran successfully
+    ScriptSucceeded,
+    /// The package was created and its contents were tested
+    Packaged,
+}
+
+impl CompletedPhase {
+    fn description(self) -> &'static str {
+        match self {
+            Self::SourcesFetched => "sources fetched",
+            Self::EnvironmentsCreated => "environments created",
+            Self::ScriptSucceeded => "build script succeeded",
+            Self::Packaged => "packaged",
+        }
+    }
+}
+
+/// The state of a build of a single output
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct BuildState {
+    /// The identifier of the output (`name-version-build`)
+    identifier: String,
+    /// A hash of the rendered recipe and the variant, a changed recipe is never resumed
+    fingerprint: String,
+    /// The build, work and prefix directories of the build
+    directories: Directories,
+    /// The last phase that completed
+    pub completed: Option<CompletedPhase>,
+    finalized_sources: Option<Vec<Source>>,
+    finalized_cache_sources: Option<Vec<Source>>,
+    finalized_cache_dependencies: Option<FinalizedDependencies>,
+    finalized_dependencies: Option<FinalizedDependencies>,
+    /// The files of the host prefix before the dependencies were installed
+    pre_install_files: Option<HashSet<PathBuf>>,
+    /// The files that were added to the host prefix by installing the dependencies
+    install_added_files: Option<HashSet<PathBuf>>,
+    /// The package that was created
+    artifact: Option<PathBuf>,
+}
+
+/// A hash of everything that determines the contents of the package of an output
+fn fingerprint(output: &Output) -> String {
+    let rendered = serde_json::to_vec(&(&output.recipe, &output.build_configuration.variant))
+        .unwrap_or_default();
+    format!("{:x}", compute_bytes_digest::<sha2::Sha256>(&rendered))
+}
+
+impl BuildState {
+    /// The state of a build of `output` that has not completed any phase yet
+    pub fn new(output: &Output) -> Self {
+        Self {
+            identifier: output.identifier(),
+            fingerprint: fingerprint(output),
+            directories: output.build_configuration.directories.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Find the most recent interrupted build of `output` in `<output_dir>/bld`
+    pub fn find(output: &Output) -> Option<Self> {
+        let bld = output
+            .build_configuration
+            .directories
+            .output_dir
+            .join("bld");
+        latest_state(&bld, &output.identifier(), &fingerprint(output))
+    }
+
+    /// A description of the build that is resumed
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({})",
+            self.directories.build_dir.display(),
+            self.completed
+                .map(CompletedPhase::description)
+                .unwrap_or("no phase completed")
+        )
+    }
+
+    /// Whether `phase` was completed by the build
+    pub fn is_completed(&self, phase: CompletedPhase) -> bool {
+        self.completed >= Some(phase)
+    }
+
+    /// Continue the build in the directories of the interrupted build with the results of its
+    /// completed phases
+    pub fn restore(&self, output: &mut Output) {
+        let directories = &mut output.build_configuration.directories;
+        directories.build_dir = self.directories.build_dir.clone();
+        directories.work_dir = self.directories.work_dir.clone();
+        directories.host_prefix = self.directories.host_prefix.clone();
+        directories.build_prefix = self.directories.build_prefix.clone();
+
+        if self.is_completed(CompletedPhase::SourcesFetched) {
+            output.finalized_sources = self.finalized_sources.clone();
+            output.finalized_cache_sources = self.finalized_cache_sources.clone();
+            output.finalized_cache_dependencies = self.finalized_cache_dependencies.clone();
+        }
+        if self.is_completed(CompletedPhase::EnvironmentsCreated) {
+            output.finalized_dependencies = self.finalized_dependencies.clone();
+        }
+    }
+
+    /// The files of the host prefix before the dependencies were installed
+    pub fn pre_install_files(&self) -> Option<HashSet<PathBuf>> {
+        self.pre_install_files.clone()
+    }
+
+    /// The files that were added to the host prefix by installing the dependencies
+    pub fn install_added_files(&self) -> Option<HashSet<PathBuf>> {
+        self.install_added_files.clone()
+    }
+
+    /// The package that was created
+    pub fn artifact(&self) -> Option<&Path> {
+        self.artifact.as_deref()
+    }
+
+    /// The sources were fetched into the work directory
+    pub fn sources_fetched(
+        &mut self,
+        output: &Output,
+        pre_install_files: Option<HashSet<PathBuf>>,
+    ) -> miette::Result<()> {
+        self.finalized_sources = output.finalized_sources.clone();
+        self.finalized_cache_sources = output.finalized_cache_sources.clone();
+        self.finalized_cache_dependencies = output.finalized_cache_dependencies.clone();
+        self.pre_install_files = pre_install_files;
+        self.complete(CompletedPhase::SourcesFetched)
+    }
+
+    /// The dependencies were installed into the build and host prefixes
+    pub fn environments_created(
+        &mut self,
+        output: &Output,
+        install_added_files: Option<HashSet<PathBuf>>,
+    ) -> miette::Result<()> {
+        self.finalized_dependencies = output.finalized_dependencies.clone();
+        self.install_added_files = install_added_files;
+        self.complete(CompletedPhase::EnvironmentsCreated)
+    }
+
+    /// The build script ran successfully
+    pub fn script_succeeded(&mut self) -> miette::Result<()> {
+        self.complete(CompletedPhase::ScriptSucceeded)
+    }
+
+    /// The package was created at `artifact`
+    pub fn packaged(&mut self, artifact: &Path) -> miette::Result<()> {
+        self.artifact = Some(artifact.to_path_buf());
+        self.complete(CompletedPhase::Packaged)
+    }
+
+    fn complete(&mut self, phase: CompletedPhase) -> miette::Result<()> {
+        self.completed = Some(phase);
+        self.save()
+    }
+
+    /// Write the state to the build directory. The state is written to a temporary file first,
+    /// so that an interruption never leaves a truncated state behind.
+    fn save(&self) -> miette::Result<()> {
+        let path = self.directories.build_dir.join(STATE_FILE);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec(self).into_diagnostic()?).into_diagnostic()?;
+        fs::rename(&temp_path, &path).into_diagnostic()
+    }
+}
+
+/// The most recently written state in the build directories in `bld` that belongs to the output
+/// with `identifier` and `fingerprint` and that can be resumed
+fn latest_state(bld: &Path, identifier: &str, fingerprint: &str) -> Option<BuildState> {
+    fs::read_dir(bld)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path().join(STATE_FILE))
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            let state: BuildState = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+            Some((modified, state))
+        })
+        .filter(|(_, state)| state.identifier == identifier && state.fingerprint == fingerprint)
+        // the build directory is removed after a successful build, except for the package
+        .filter(|(_, state)| {
+            state.completed != Some(CompletedPhase::Packaged)
+                || state
+                    .artifact
+                    .as_ref()
+                    .is_some_and(|artifact| artifact.exists())
+        })
+        .max_by_key(|(modified, _): &(SystemTime, _)| *modified)
+        .map(|(_, state)| state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_state(bld: &Path, dirname: &str, identifier: &str, completed: CompletedPhase) {
+        let build_dir = bld.join(dirname);
+        fs::create_dir_all(&build_dir).unwrap();
+        let mut state = BuildState {
+            identifier: identifier.to_string(),
+            fingerprint: "abc".to_string(),
+            directories: Directories {
+                build_dir,
+                ..Directories::default()
+            },
+            ..BuildState::default()
+        };
+        state.complete(completed).unwrap();
+    }
+
+    #[test]
+    fn test_latest_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bld = tmp.path();
+        assert!(latest_state(bld, "foo-1.0-h123_0", "abc").is_none());
+
+        write_state(
+            bld,
+            "rattler-build_foo_1",
+            "foo-1.0-h123_0",
+            CompletedPhase::SourcesFetched,
+        );
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_state(
+            bld,
+            "rattler-build_foo_2",
+            "foo-1.0-h123_0",
+            CompletedPhase::ScriptSucceeded,
+        );
+        write_state(
+            bld,
+            "rattler-build_bar_3",
+            "bar-1.0-h123_0",
+            CompletedPhase::SourcesFetched,
+        );
+        // the package of this build does not exist anymore
+        write_state(
+            bld,
+            "rattler-build_foo_4",
+            "foo-1.0-h123_0",
+            CompletedPhase::Packaged,
+        );
+
+        let state = latest_state(bld, "foo-1.0-h123_0", "abc").unwrap();
+        assert_eq!(state.completed, Some(CompletedPhase::ScriptSucceeded));
+        assert_eq!(state.directories.build_dir, bld.join("rattler-build_foo_2"));
+        assert!(state.is_completed(CompletedPhase::EnvironmentsCreated));
+        assert!(!state.is_completed(CompletedPhase::Packaged));
+
+        // a changed recipe is never resumed
+        assert!(latest_state(bld, "foo-1.0-h123_0", "def").is_none());
+    }
+}
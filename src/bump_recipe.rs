@@ -4,18 +4,20 @@
 //! - Bump a recipe to a specific version
 //! - Auto-detect new versions from various providers (GitHub, PyPI, etc.)
 //! - Update SHA256 checksums automatically
+//! - Check recipes for newer upstream versions without modifying them
 
 use fs_err as fs;
 use indexmap::IndexMap;
 use minijinja::Value;
-use rattler_conda_types::Platform;
+use rattler_conda_types::{Platform, Version};
 use rattler_digest::Sha256Hash;
 use regex::Regex;
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_yaml::Value as YamlValue;
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use thiserror::Error;
 
 use rattler_build_jinja::{Jinja, JinjaConfig};
@@ -132,6 +134,8 @@ struct CrateInfo {
 /// Recipe context containing version and other variables
 #[derive(Debug, Default)]
 pub struct RecipeContext {
+    /// The package name (or the recipe name of multi-output recipes), may contain Jinja
+    pub name: Option<String>,
     /// The version from the context section (the raw, literal value)
     pub version: Option<String>,
     /// The build number (from context or build.number)
@@ -221,6 +225,12 @@ impl RecipeContext {
             }
         }
 
+        // Extract the name of the package, or of the recipe for multi-output recipes
+        ctx.name = ["package", "recipe"]
+            .iter()
+            .filter_map(|section| yaml.get(section).and_then(|v| v.get("name")))
+            .find_map(|name| name.as_str().map(str::to_string));
+
         // Extract build.number if not found in context
         if ctx.build_number.is_none()
             && let Some(build) = yaml.get("build").and_then(|v| v.as_mapping())
@@ -559,6 +569,73 @@ fn reset_build_number(content: &str, build_num: &BuildNumber) -> String {
         .unwrap_or_else(|_| content.to_string())
 }
 
+/// Replace the literal version in the `version` entry of the context section
+///
+/// Only the value of the entry is replaced, so that other occurrences of the version string
+/// (e.g. in dependency constraints) are left untouched.
+fn replace_context_version(content: &str, old_version: &str, new_version: &str) -> String {
+    let pattern = format!(
+        r#"(?m)^(\s+version:\s*["']?){}(["']?[ \t]*(?:#.*)?)$"#,
+        regex::escape(old_version)
+    );
+    Regex::new(&pattern)
+        .map(|re| {
+            re.replacen(content, 1, |caps: &regex::Captures| {
+                format!("{}{}{}", &caps[1], new_version, &caps[2])
+            })
+            .to_string()
+        })
+        .unwrap_or_else(|_| content.to_string())
+}
+
+/// Update a source URL that is written out literally (without Jinja expressions) to the new
+/// version. URL templates are returned unchanged, they pick up the new version when rendered.
+fn update_literal_url(url_template: &str, old_version: &str, new_version: &str) -> String {
+    if url_template.contains("{{") {
+        url_template.to_string()
+    } else {
+        url_template.replace(old_version, new_version)
+    }
+}
+
+/// Apply a version bump to the content of a recipe, preserving formatting and comments
+fn update_recipe_content(
+    content: &str,
+    ctx: &RecipeContext,
+    new_version: &str,
+    new_url_templates: &[String],
+    new_sha256s: &[String],
+    keep_build_number: bool,
+) -> String {
+    let mut content = match &ctx.version {
+        Some(old_version) => replace_context_version(content, old_version, new_version),
+        None => content.to_string(),
+    };
+
+    // Replace literal URLs in order
+    for (old_url, new_url) in ctx.source_urls.iter().zip(new_url_templates) {
+        if old_url != new_url {
+            content = content.replacen(old_url.as_str(), new_url, 1);
+        }
+    }
+
+    // Replace SHA256 checksums in order
+    for (old_sha, new_sha) in ctx.sha256_checksums.iter().zip(new_sha256s) {
+        content = content.replacen(old_sha.as_str(), new_sha, 1);
+    }
+
+    // Reset build number to 0 (unless --keep-build-number is set)
+    if !keep_build_number
+        && let Some(build_num) = &ctx.build_number
+        && build_num.value != 0
+    {
+        content = reset_build_number(&content, build_num);
+        tracing::debug!("Reset build number from {} to 0", build_num.value);
+    }
+
+    content
+}
+
 /// Build a URL with a specific version using proper Jinja rendering
 ///
 /// This function uses the real Jinja rendering engine with the full context
@@ -675,10 +752,17 @@ pub async fn bump_recipe(
 
     tracing::info!("Bumping {} -> {}", old_version, new_version);
 
+    // Literal URLs (without Jinja expressions) contain the version verbatim
+    let new_url_templates = ctx
+        .source_urls
+        .iter()
+        .map(|url| update_literal_url(url, &old_version, &new_version))
+        .collect::<Vec<_>>();
+
     // Build URLs with new version and fetch SHA256
     let mut new_sha256s = Vec::new();
 
-    for url_template in &ctx.source_urls {
+    for url_template in &new_url_templates {
         let new_url = build_url_with_version(url_template, &new_version, &ctx.raw_context)?;
         tracing::debug!("Resolved URL: {}", new_url);
 
@@ -691,26 +775,15 @@ pub async fn bump_recipe(
     }
 
     if !dry_run {
-        // Read the recipe file content
-        let mut content = fs::read_to_string(recipe_path)?;
-
-        // Simple string replacement: replace old version with new version
-        // This preserves all formatting, comments, etc.
-        content = content.replace(&old_version, &new_version);
-
-        // Replace SHA256 checksums in order
-        for (old_sha, new_sha) in ctx.sha256_checksums.iter().zip(new_sha256s.iter()) {
-            content = content.replacen(old_sha, new_sha, 1);
-        }
-
-        // Reset build number to 0 (unless --keep-build-number is set)
-        if !keep_build_number
-            && let Some(build_num) = &ctx.build_number
-            && build_num.value != 0
-        {
-            content = reset_build_number(&content, build_num);
-            tracing::debug!("Reset build number from {} to 0", build_num.value);
-        }
+        let content = fs::read_to_string(recipe_path)?;
+        let content = update_recipe_content(
+            &content,
+            &ctx,
+            &new_version,
+            &new_url_templates,
+            &new_sha256s,
+            keep_build_number,
+        );
 
         // Write the updated content back
         fs::write(recipe_path, &content)?;
@@ -767,6 +840,155 @@ pub async fn check_for_updates(
     }
 }
 
+/// The result of an upstream update check of a recipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStatus {
+    /// A newer version is available upstream
+    UpdateAvailable,
+    /// The recipe is at the latest upstream version
+    UpToDate,
+    /// The source URL does not belong to a supported provider
+    Unsupported,
+    /// The check failed
+    Error,
+}
+
+impl std::fmt::Display for UpdateStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateStatus::UpdateAvailable => write!(f, "update available"),
+            UpdateStatus::UpToDate => write!(f, "up to date"),
+            UpdateStatus::Unsupported => write!(f, "unsupported"),
+            UpdateStatus::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// An upstream update check of a recipe
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateCheck {
+    /// The path of the recipe
+    pub recipe: PathBuf,
+    /// The name of the package
+    pub name: Option<String>,
+    /// The version of the recipe
+    pub current_version: Option<String>,
+    /// The latest upstream version
+    pub latest_version: Option<String>,
+    /// The provider that was queried
+    pub provider: Option<String>,
+    /// The result of the check
+    pub status: UpdateStatus,
+    /// Why the check failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl UpdateCheck {
+    fn new(recipe_path: &Path) -> Self {
+        Self {
+            recipe: recipe_path.to_path_buf(),
+            name: None,
+            current_version: None,
+            latest_version: None,
+            provider: None,
+            status: UpdateStatus::Error,
+            error: None,
+        }
+    }
+
+    async fn run(
+        &mut self,
+        client: &Client,
+        include_prerelease: bool,
+    ) -> Result<(), BumpRecipeError> {
+        let ctx = RecipeContext::from_recipe_file(&self.recipe)?;
+        let current_version = ctx
+            .version
+            .clone()
+            .ok_or(BumpRecipeError::VersionNotFound)?;
+        self.name = ctx.name.as_ref().map(|name| {
+            build_url_with_version(name, &current_version, &ctx.raw_context)
+                .unwrap_or_else(|_| name.clone())
+        });
+        self.current_version = Some(current_version.clone());
+
+        let source_url_template = ctx
+            .source_urls
+            .first()
+            .ok_or(BumpRecipeError::NoSourceUrl)?;
+        let rendered_url =
+            build_url_with_version(source_url_template, &current_version, &ctx.raw_context)?;
+        let provider = detect_provider(&rendered_url)?;
+        if let VersionProvider::Generic { .. } = provider {
+            self.status = UpdateStatus::Unsupported;
+            self.error = Some(format!("no version provider for {rendered_url}"));
+            return Ok(());
+        }
+        self.provider = Some(provider.to_string());
+
+        let latest_version = fetch_latest_version(client, &provider, include_prerelease).await?;
+        self.status = if is_newer_version(&latest_version, &current_version) {
+            UpdateStatus::UpdateAvailable
+        } else {
+            UpdateStatus::UpToDate
+        };
+        self.latest_version = Some(latest_version);
+        Ok(())
+    }
+}
+
+/// Whether `latest` is newer than `current`, falling back to string inequality for versions
+/// that cannot be parsed
+fn is_newer_version(latest: &str, current: &str) -> bool {
+    match (Version::from_str(latest), Version::from_str(current)) {
+        (Ok(latest), Ok(current)) => latest > current,
+        _ => latest != current,
+    }
+}
+
+/// Check a recipe for a newer upstream version. Failures are reported in the result, so that
+/// checking many recipes does not stop at the first recipe that cannot be checked.
+pub async fn check_recipe_for_updates(
+    recipe_path: &Path,
+    client: &Client,
+    include_prerelease: bool,
+) -> UpdateCheck {
+    let mut check = UpdateCheck::new(recipe_path);
+    if let Err(e) = check.run(client, include_prerelease).await {
+        check.status = UpdateStatus::Error;
+        check.error = Some(e.to_string());
+    }
+    check
+}
+
+/// Print the results of update checks as a table
+pub fn print_update_checks(checks: &[UpdateCheck]) {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(vec!["Package", "Current", "Latest", "Provider", "Status"]);
+    for check in checks {
+        let status = match &check.error {
+            Some(error) => format!("{}: {}", check.status, error),
+            None => check.status.to_string(),
+        };
+        table.add_row(vec![
+            check
+                .name
+                .clone()
+                .unwrap_or_else(|| check.recipe.display().to_string()),
+            check.current_version.clone().unwrap_or_default(),
+            check.latest_version.clone().unwrap_or_default(),
+            check.provider.clone().unwrap_or_default(),
+            status,
+        ]);
+    }
+    tracing::info!("\n{}", table);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1027,4 +1249,94 @@ build:
         // The ${{ build_number }} reference should be preserved
         assert!(result.contains("${{ build_number }}"));
     }
+
+    #[test]
+    fn test_update_recipe_content() {
+        let content = r#"
+context:
+  version: "1.2.3" # the upstream version
+  number: 4
+
+package:
+  name: test-package
+  version: ${{ version }}
+
+source:
+  - url: https://github.com/owner/repo/archive/v${{ version }}.tar.gz
+    sha256: cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc
+  - url: https://example.com/data-1.2.3.tar.gz
+    sha256: dddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd
+
+build:
+  number: ${{ number }}
+
+requirements:
+  run:
+    - other-package >=1.2.3
+"#;
+
+        let ctx = RecipeContext::from_yaml_content(content).unwrap();
+        let new_url_templates = ctx
+            .source_urls
+            .iter()
+            .map(|url| update_literal_url(url, "1.2.3", "1.3.0"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            new_url_templates,
+            [
+                "https://github.com/owner/repo/archive/v${{ version }}.tar.gz",
+                "https://example.com/data-1.3.0.tar.gz"
+            ]
+        );
+
+        let new_sha256s = ["a".repeat(64), "b".repeat(64)];
+        let result = update_recipe_content(
+            content,
+            &ctx,
+            "1.3.0",
+            &new_url_templates,
+            &new_sha256s,
+            false,
+        );
+        assert!(result.contains(r#"version: "1.3.0" # the upstream version"#));
+        assert!(result.contains("url: https://example.com/data-1.3.0.tar.gz"));
+        assert!(result.contains(&format!("sha256: {}", "a".repeat(64))));
+        assert!(result.contains(&format!("sha256: {}", "b".repeat(64))));
+        assert!(result.contains("  number: 0\n"));
+        // other occurrences of the version are left untouched
+        assert!(result.contains("other-package >=1.2.3"));
+
+        let result = update_recipe_content(
+            content,
+            &ctx,
+            "1.3.0",
+            &new_url_templates,
+            &new_sha256s,
+            true,
+        );
+        assert!(result.contains("  number: 4\n"));
+    }
+
+    #[test]
+    fn test_is_newer_version() {
+        assert!(is_newer_version("1.10.0", "1.9.2"));
+        assert!(!is_newer_version("1.9.2", "1.9.2"));
+        assert!(!is_newer_version("1.9.0", "1.10.0rc1"));
+        assert!(is_newer_version("2024.1", "2023.12"));
+    }
+
+    #[test]
+    fn test_parse_recipe_name() {
+        let ctx = RecipeContext::from_yaml_content(
+            "context:\n  version: \"1.0\"\npackage:\n  name: foo\n  version: ${{ version }}\n",
+        )
+        .unwrap();
+        assert_eq!(ctx.name.as_deref(), Some("foo"));
+
+        let ctx = RecipeContext::from_yaml_content(
+            "context:\n  version: \"1.0\"\nrecipe:\n  name: bar\noutputs: []\n",
+        )
+        .unwrap();
+        assert_eq!(ctx.name.as_deref(), Some("bar"));
+    }
 }
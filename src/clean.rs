@@ -0,0 +1,267 @@
+//! Remove the build directories, caches and intermediate files of old builds
+//! (`rattler-build clean`).
+//!
+//! Only the entries inside the known directories are removed (e.g. the build directory of a
+//! single build in `<output-dir>/bld`), never the packages in the output directory.
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use fs_err as fs;
+use indicatif::HumanBytes;
+use miette::IntoDiagnostic;
+
+use crate::opt::CleanOpts;
+
+/// A kind of files that can be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    BuildDirs,
+    Sources,
+    Intermediates,
+    Packages,
+}
+
+impl Category {
+    fn description(self) -> &'static str {
+        match self {
+            Self::BuildDirs => "build directories",
+            Self::Sources => "source cache",
+            Self::Intermediates => "intermediate files",
+            Self::Packages => "package cache",
+        }
+    }
+
+    /// The directories that contain the entries of the category
+    fn directories(self, output_dir: &Path, cache_dir: &Path) -> Vec<PathBuf> {
+        match self {
+            Self::BuildDirs => vec![output_dir.join("bld")],
+            Self::Sources => vec![output_dir.join("src_cache")],
+            Self::Intermediates => vec![output_dir.join("build_cache"), output_dir.join("test")],
+            Self::Packages => vec![cache_dir.join(rattler_cache::PACKAGE_CACHE_DIR)],
+        }
+    }
+}
+
+/// The categories that are selected by the options
+fn selected_categories(opts: &CleanOpts) -> Vec<Category> {
+    let selected = [
+        (opts.build_dirs, Category::BuildDirs),
+        (opts.sources, Category::Sources),
+        (opts.intermediates, Category::Intermediates),
+        (opts.packages, Category::Packages),
+    ]
+    .into_iter()
+    .filter(|(selected, _)| *selected || opts.all)
+    .map(|(_, category)| category)
+    .collect::<Vec<_>>();
+
+    if selected.is_empty() {
+        vec![
+            Category::BuildDirs,
+            Category::Sources,
+            Category::Intermediates,
+        ]
+    } else {
+        selected
+    }
+}
+
+/// The entries of a category that are removed and kept
+#[derive(Debug, Default)]
+struct Selection {
+    removed: Vec<(PathBuf, u64)>,
+    kept: Vec<(PathBuf, u64)>,
+}
+
+impl Selection {
+    fn removed_size(&self) -> u64 {
+        self.removed.iter().map(|(_, size)| size).sum()
+    }
+
+    fn kept_size(&self) -> u64 {
+        self.kept.iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// The size of a file, or of all files in a directory
+fn disk_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Select the entries of `directories` that were last modified before `now - older_than`
+fn select_entries(
+    directories: &[PathBuf],
+    older_than: Option<Duration>,
+    now: SystemTime,
+) -> miette::Result<Selection> {
+    let mut selection = Selection::default();
+    for directory in directories {
+        if !directory.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(directory).into_diagnostic()? {
+            let path = entry.into_diagnostic()?.path();
+            let age = fs::symlink_metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            let size = disk_size(&path);
+            if older_than.is_none_or(|older_than| age > older_than) {
+                selection.removed.push((path, size));
+            } else {
+                selection.kept.push((path, size));
+            }
+        }
+    }
+    selection.removed.sort();
+    Ok(selection)
+}
+
+fn remove_entry(path: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Remove the selected build directories, caches and intermediate files
+pub(crate) fn clean(
+    opts: CleanOpts,
+    output_dir: PathBuf,
+    cache_dir: PathBuf,
+) -> miette::Result<()> {
+    let now = SystemTime::now();
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header([
+            "Category",
+            "Location",
+            if opts.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            },
+            "Kept",
+        ]);
+
+    let (mut removed_size, mut failed) = (0, 0);
+    for category in selected_categories(&opts) {
+        let directories = category.directories(&output_dir, &cache_dir);
+        let selection = select_entries(&directories, opts.older_than, now)?;
+
+        for (path, size) in &selection.removed {
+            if opts.dry_run {
+                tracing::info!("Would remove {} ({})", path.display(), HumanBytes(*size));
+                continue;
+            }
+            tracing::debug!("Removing {}", path.display());
+            match remove_entry(path) {
+                Ok(()) => removed_size += size,
+                Err(e) => {
+                    tracing::warn!("Failed to remove {}: {}", path.display(), e);
+                    failed += 1;
+                }
+            }
+        }
+        if opts.dry_run {
+            removed_size += selection.removed_size();
+        }
+
+        let location = directories
+            .iter()
+            .map(|directory| directory.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary =
+            |entries: usize, size: u64| format!("{} entries ({})", entries, HumanBytes(size));
+        table.add_row([
+            category.description().to_string(),
+            location,
+            summary(selection.removed.len(), selection.removed_size()),
+            summary(selection.kept.len(), selection.kept_size()),
+        ]);
+    }
+
+    tracing::info!("\n{}", table);
+    if opts.dry_run {
+        tracing::info!("Dry run, {} would be freed", HumanBytes(removed_size));
+    } else {
+        tracing::info!("{} freed", HumanBytes(removed_size));
+    }
+
+    if failed > 0 {
+        return Err(miette::miette!("{} entries could not be removed", failed));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bld = tmp.path().join("bld");
+        fs::create_dir_all(bld.join("rattler-build_foo_1").join("work")).unwrap();
+        fs::write(
+            bld.join("rattler-build_foo_1").join("work").join("a.txt"),
+            "abc",
+        )
+        .unwrap();
+        fs::write(bld.join("log.txt"), "12345").unwrap();
+        let directories = [bld, tmp.path().join("missing")];
+
+        let now = SystemTime::now() + Duration::from_secs(60);
+        let selection = select_entries(&directories, None, now).unwrap();
+        assert_eq!(selection.removed.len(), 2);
+        assert_eq!(selection.removed_size(), 8);
+
+        let selection = select_entries(&directories, Some(Duration::from_secs(3600)), now).unwrap();
+        assert!(selection.removed.is_empty());
+        assert_eq!(selection.kept.len(), 2);
+        assert_eq!(selection.kept_size(), 8);
+    }
+
+    #[test]
+    fn test_selected_categories() {
+        let opts = CleanOpts {
+            output_dir: None,
+            build_dirs: false,
+            sources: true,
+            intermediates: false,
+            packages: false,
+            all: false,
+            older_than: None,
+            dry_run: false,
+        };
+        assert_eq!(selected_categories(&opts), [Category::Sources]);
+        assert_eq!(
+            selected_categories(&CleanOpts {
+                sources: false,
+                ..opts.clone()
+            }),
+            [
+                Category::BuildDirs,
+                Category::Sources,
+                Category::Intermediates
+            ]
+        );
+        assert_eq!(
+            selected_categories(&CleanOpts { all: true, ..opts }).len(),
+            4
+        );
+    }
+}
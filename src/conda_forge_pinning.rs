@@ -0,0 +1,210 @@
+//! The global pinning of conda-forge as variant configuration (`--pinning conda-forge[@ref]`).
+//!
+//! The `conda_build_config.yaml` of the
+//! [conda-forge-pinning feedstock](https://github.com/conda-forge/conda-forge-pinning-feedstock)
+//! is downloaded into the cache directory and used as the first variant configuration, so that
+//! the variant files of the recipe and `--variant-config` take priority over it. A file of a
+//! pinned git ref is downloaded once, the file of `main` is downloaded again once a day.
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use fs_err as fs;
+use miette::{Context, IntoDiagnostic};
+use url::Url;
+
+/// The raw files of the conda-forge-pinning feedstock
+pub const DEFAULT_PINNING_URL: &str =
+    "https://raw.githubusercontent.com/conda-forge/conda-forge-pinning-feedstock/";
+
+/// The branch that is used if no git ref is given
+const DEFAULT_REF: &str = "main";
+
+/// How long the downloaded file of `main` is used before it is downloaded again
+const MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The global pinning of conda-forge, optionally at a git ref (a tag or a commit of the
+/// conda-forge-pinning feedstock)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pinning {
+    /// The git ref, e.g. `2025.01.06.10.08.42` (defaults to `main`)
+    pub git_ref: Option<String>,
+    /// The URL that the raw files of the feedstock are downloaded from
+    pub url: Option<Url>,
+}
+
+impl FromStr for Pinning {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, git_ref) = match s.split_once('@') {
+            Some((name, git_ref)) => (name, Some(git_ref)),
+            None => (s, None),
+        };
+        if name != "conda-forge" {
+            return Err(format!(
+                "unknown pinning `{name}`, only `conda-forge` is supported"
+            ));
+        }
+        let git_ref = match git_ref {
+            Some("") => return Err("the git ref after `@` is empty".to_string()),
+            Some(git_ref) => Some(git_ref.to_string()),
+            None => None,
+        };
+        Ok(Self { git_ref, url: None })
+    }
+}
+
+impl fmt::Display for Pinning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "conda-forge")?;
+        if let Some(git_ref) = &self.git_ref {
+            write!(f, "@{git_ref}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Pinning {
+    /// Set the URL that the raw files of the feedstock are downloaded from
+    pub fn with_url(self, url: Option<Url>) -> Self {
+        Self { url, ..self }
+    }
+
+    fn git_ref(&self) -> &str {
+        self.git_ref.as_deref().unwrap_or(DEFAULT_REF)
+    }
+
+    /// The URL of the `conda_build_config.yaml` at the git ref
+    pub fn file_url(&self) -> Result<Url, url::ParseError> {
+        let base = match &self.url {
+            Some(url) => url.clone(),
+            None => Url::parse(DEFAULT_PINNING_URL)?,
+        };
+        // make sure that the last segment of the base URL is kept
+        let base = if base.path().ends_with('/') {
+            base
+        } else {
+            Url::parse(&format!("{base}/"))?
+        };
+        base.join(&format!(
+            "{}/recipe/conda_build_config.yaml",
+            self.git_ref()
+        ))
+    }
+
+    /// The path of the downloaded file in the cache directory. The file keeps its name, so that
+    /// it is read with the `# [selector]` syntax of conda-build.
+    pub fn cache_path(&self, cache_dir: &Path) -> PathBuf {
+        let git_ref = self.git_ref().replace(['/', '\\', ':'], "_");
+        cache_dir
+            .join("conda-forge-pinning")
+            .join(git_ref)
+            .join("conda_build_config.yaml")
+    }
+
+    /// Whether the cached file can be used without downloading it again
+    fn is_fresh(&self, path: &Path) -> bool {
+        if self.git_ref.is_some() {
+            return path.is_file();
+        }
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < MAX_AGE)
+    }
+
+    /// Download the `conda_build_config.yaml` into the cache directory (unless it is cached) and
+    /// return its path. If the download fails, a previously downloaded file is used.
+    pub async fn fetch(
+        &self,
+        client: &rattler_build_networking::BaseClient,
+        cache_dir: &Path,
+    ) -> miette::Result<PathBuf> {
+        let path = self.cache_path(cache_dir);
+        if self.is_fresh(&path) {
+            tracing::debug!("Using the cached pinning {} at {}", self, path.display());
+            return Ok(path);
+        }
+
+        let url = self.file_url().into_diagnostic()?;
+        tracing::info!("Downloading the pinning {self} from {url}");
+        let download = async {
+            client
+                .for_host(&url)
+                .get(url.clone())
+                .send()
+                .await
+                .into_diagnostic()?
+                .error_for_status()
+                .into_diagnostic()?
+                .text()
+                .await
+                .into_diagnostic()
+        };
+        match download.await {
+            Ok(content) => {
+                fs::create_dir_all(path.parent().expect("the cache path has a parent"))
+                    .into_diagnostic()?;
+                fs::write(&path, content).into_diagnostic()?;
+                Ok(path)
+            }
+            Err(e) if path.is_file() => {
+                tracing::warn!(
+                    "Failed to download the pinning {self} ({e}), using the file downloaded before"
+                );
+                Ok(path)
+            }
+            Err(e) => Err(e).wrap_err(format!("Failed to download the pinning {self} from {url}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let pinning: Pinning = "conda-forge".parse().unwrap();
+        assert_eq!(pinning.git_ref, None);
+        assert_eq!(
+            pinning.file_url().unwrap().as_str(),
+            "https://raw.githubusercontent.com/conda-forge/conda-forge-pinning-feedstock/main/recipe/conda_build_config.yaml"
+        );
+
+        let pinning: Pinning = "conda-forge@2025.01.06.10.08.42".parse().unwrap();
+        assert_eq!(pinning.to_string(), "conda-forge@2025.01.06.10.08.42");
+        assert_eq!(
+            pinning
+                .with_url(Some(Url::parse("http://localhost:8000/pinning").unwrap()))
+                .file_url()
+                .unwrap()
+                .as_str(),
+            "http://localhost:8000/pinning/2025.01.06.10.08.42/recipe/conda_build_config.yaml"
+        );
+
+        assert!("bioconda".parse::<Pinning>().is_err());
+        assert!("conda-forge@".parse::<Pinning>().is_err());
+    }
+
+    #[test]
+    fn test_cache_path() {
+        let pinning: Pinning = "conda-forge@refs/tags/v1".parse().unwrap();
+        assert_eq!(
+            pinning.cache_path(Path::new("/cache")),
+            Path::new("/cache/conda-forge-pinning/refs_tags_v1/conda_build_config.yaml")
+        );
+        // a file of a pinned ref is never downloaded again
+        let dir = tempfile::tempdir().unwrap();
+        let path = pinning.cache_path(dir.path());
+        assert!(!pinning.is_fresh(&path));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "python:\n  - 3.12\n").unwrap();
+        assert!(pinning.is_fresh(&path));
+    }
+}
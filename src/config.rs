@@ -0,0 +1,500 @@
+//! The configuration files of rattler-build.
+//!
+//! The configuration files have the format of pixi's global configuration (channels, mirrors,
+//! S3 options and the package format), with additional settings of rattler-build. The files
+//! are read in the following order, later files taking priority over earlier ones:
+//!
+//! 1. the user configuration (e.g. `~/.config/rattler-build/config.toml` on Linux)
+//! 2. the `rattler-build.toml` files in the directory of the recipe and its parent
+//!    directories, the nearest one taking priority
+//! 3. the file that is passed with `--config-file`
+//!
+//! Options on the command line take priority over all configuration files.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_build_script::SandboxArguments;
+use rattler_conda_types::{NamedChannelOrUrl, Platform};
+use rattler_config::config::{Config as _, ConfigBase, MergeError, ValidationError};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    hooks::Hook, license_policy::LicensePolicy, post_process::plugin::PostProcessor,
+    upload::plugin::UploaderPlugin,
+};
+
+/// The name of the project configuration file
+pub const PROJECT_CONFIG_FILE: &str = "rattler-build.toml";
+
+/// The configuration of rattler-build
+pub type Config = ConfigBase<RattlerBuildConfig>;
+
+/// The settings of rattler-build in addition to the ones of pixi's configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RattlerBuildConfig {
+    /// The platform that is built on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_platform: Option<Platform>,
+
+    /// The platform that is built for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_platform: Option<Platform>,
+
+    /// The platform of the host environment (defaults to the target platform)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_platform: Option<Platform>,
+
+    /// The directory that the packages are written to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+
+    /// The directory of the package and repodata caches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_dir: Option<PathBuf>,
+
+    /// The settings of `rattler-build publish`
+    #[serde(default, skip_serializing_if = "PublishConfig::is_default")]
+    pub publish: PublishConfig,
+
+    /// The settings of the build sandbox
+    #[serde(default, skip_serializing_if = "SandboxConfig::is_default")]
+    pub sandbox: SandboxConfig,
+
+    /// The hooks that are fired when a build finishes (`[[hooks]]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+
+    /// The external post-processors that are run on the files of every package
+    /// (`[[post-processors]]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessor>,
+
+    /// The uploader plugins of `rattler-build upload plugin`, by name (`[uploaders.<name>]`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub uploaders: BTreeMap<String, UploaderPlugin>,
+
+    /// The remote build cache
+    #[serde(default, skip_serializing_if = "RemoteCacheConfig::is_default")]
+    pub remote_cache: RemoteCacheConfig,
+
+    /// The allowed and denied licenses of the packages and their run dependencies
+    #[serde(default, skip_serializing_if = "LicensePolicy::is_default")]
+    pub license_policy: LicensePolicy,
+
+    /// Whether builds fail unless all sources and dependencies are pinned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_pinned: Option<bool>,
+
+    /// Whether the build scripts get the environment variables of conda-build that are not set
+    /// by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conda_build_env_vars: Option<bool>,
+
+    /// Whether `zig cc` is used instead of the compiler packages for Linux targets that are
+    /// cross-compiled or have an older glibc baseline than the build machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zig_cc: Option<bool>,
+
+    /// Whether rendered recipes are cached in the cache directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub render_cache: Option<bool>,
+}
+
+/// The settings of `rattler-build publish`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PublishConfig {
+    /// The channel that packages are published to (the default of `--to`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<NamedChannelOrUrl>,
+}
+
+impl PublishConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// The settings of the remote build cache
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RemoteCacheConfig {
+    /// The URL of the cache (the default of `--remote-cache`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<Url>,
+
+    /// Whether packages are only looked up in the cache, but never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+}
+
+impl RemoteCacheConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// The settings of the build sandbox
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SandboxConfig {
+    /// Whether the build script is run in the sandbox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Whether the build script has network access in the sandbox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_network: Option<bool>,
+
+    /// Whether the build script is run hermetically
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hermetic: Option<bool>,
+
+    /// Paths that can be read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_read: Vec<PathBuf>,
+
+    /// Paths that can be read and executed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_read_execute: Vec<PathBuf>,
+
+    /// Paths that can be read and written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_read_write: Vec<PathBuf>,
+}
+
+impl SandboxConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Fill the sandbox arguments that were not given on the command line from the
+    /// configuration
+    pub fn apply(&self, mut args: SandboxArguments) -> SandboxArguments {
+        args.sandbox |= self.enabled.unwrap_or(false);
+        args.allow_network |= self.allow_network.unwrap_or(false);
+        args.hermetic |= self.hermetic.unwrap_or(false);
+        for (arg, configured) in [
+            (&mut args.allow_read, &self.allow_read),
+            (&mut args.allow_read_execute, &self.allow_read_execute),
+            (&mut args.allow_read_write, &self.allow_read_write),
+        ] {
+            if arg.is_empty() {
+                arg.clone_from(configured);
+            }
+        }
+        args
+    }
+}
+
+impl RattlerBuildConfig {
+    /// Make the relative paths of a configuration file relative to the directory of the file
+    fn resolve_paths(&mut self, base: &Path) {
+        let paths = [&mut self.output_dir, &mut self.cache_dir]
+            .into_iter()
+            .flatten()
+            .chain(self.sandbox.allow_read.iter_mut())
+            .chain(self.sandbox.allow_read_execute.iter_mut())
+            .chain(self.sandbox.allow_read_write.iter_mut());
+        for path in paths {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+}
+
+impl rattler_config::config::Config for RattlerBuildConfig {
+    fn get_extension_name(&self) -> String {
+        "rattler-build".to_string()
+    }
+
+    fn merge_config(self, other: &Self) -> Result<Self, MergeError> {
+        let sandbox = &other.sandbox;
+        let or_self = |other: &Vec<PathBuf>, current: Vec<PathBuf>| {
+            if other.is_empty() {
+                current
+            } else {
+                other.clone()
+            }
+        };
+        Ok(Self {
+            build_platform: other.build_platform.or(self.build_platform),
+            target_platform: other.target_platform.or(self.target_platform),
+            host_platform: other.host_platform.or(self.host_platform),
+            output_dir: other.output_dir.clone().or(self.output_dir),
+            cache_dir: other.cache_dir.clone().or(self.cache_dir),
+            publish: PublishConfig {
+                to: other.publish.to.clone().or(self.publish.to),
+            },
+            sandbox: SandboxConfig {
+                enabled: sandbox.enabled.or(self.sandbox.enabled),
+                allow_network: sandbox.allow_network.or(self.sandbox.allow_network),
+                hermetic: sandbox.hermetic.or(self.sandbox.hermetic),
+                allow_read: or_self(&sandbox.allow_read, self.sandbox.allow_read),
+                allow_read_execute: or_self(
+                    &sandbox.allow_read_execute,
+                    self.sandbox.allow_read_execute,
+                ),
+                allow_read_write: or_self(&sandbox.allow_read_write, self.sandbox.allow_read_write),
+            },
+            hooks: if other.hooks.is_empty() {
+                self.hooks
+            } else {
+                other.hooks.clone()
+            },
+            post_processors: if other.post_processors.is_empty() {
+                self.post_processors
+            } else {
+                other.post_processors.clone()
+            },
+            uploaders: {
+                // the uploaders of a file are added to the ones of the files it takes priority over
+                let mut uploaders = self.uploaders;
+                uploaders.extend(other.uploaders.clone());
+                uploaders
+            },
+            remote_cache: RemoteCacheConfig {
+                url: other.remote_cache.url.clone().or(self.remote_cache.url),
+                read_only: other.remote_cache.read_only.or(self.remote_cache.read_only),
+            },
+            license_policy: if other.license_policy.is_default() {
+                self.license_policy
+            } else {
+                other.license_policy.clone()
+            },
+            require_pinned: other.require_pinned.or(self.require_pinned),
+            conda_build_env_vars: other.conda_build_env_vars.or(self.conda_build_env_vars),
+            zig_cc: other.zig_cc.or(self.zig_cc),
+            render_cache: other.render_cache.or(self.render_cache),
+        })
+    }
+
+    fn validate(&self) -> Result<(), ValidationError> {
+        for hook in &self.hooks {
+            hook.validate()
+                .map_err(|e| ValidationError::InvalidValue("hooks".to_string(), e))?;
+        }
+        self.license_policy
+            .validate()
+            .map_err(|e| ValidationError::InvalidValue("license-policy".to_string(), e))?;
+        Ok(())
+    }
+
+    fn keys(&self) -> Vec<String> {
+        [
+            "build-platform",
+            "target-platform",
+            "host-platform",
+            "output-dir",
+            "cache-dir",
+            "publish",
+            "publish.to",
+            "sandbox",
+            "sandbox.enabled",
+            "sandbox.allow-network",
+            "sandbox.hermetic",
+            "sandbox.allow-read",
+            "sandbox.allow-read-execute",
+            "sandbox.allow-read-write",
+            "hooks",
+            "post-processors",
+            "uploaders",
+            "remote-cache",
+            "remote-cache.url",
+            "remote-cache.read-only",
+            "license-policy",
+            "license-policy.allow",
+            "license-policy.deny",
+            "license-policy.on-violation",
+            "license-policy.ignore",
+            "require-pinned",
+            "conda-build-env-vars",
+            "zig-cc",
+            "render-cache",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect()
+    }
+}
+
+/// The path of the user configuration file
+pub fn user_config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rattler-build").join("config.toml"))
+}
+
+/// The `rattler-build.toml` files in `dir` and its parent directories, the farthest one first
+pub fn project_config_files(dir: &Path) -> Vec<PathBuf> {
+    let dir = dunce::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let dir = if dir.is_file() {
+        dir.parent().map(Path::to_path_buf).unwrap_or(dir)
+    } else {
+        dir
+    };
+    let mut files = dir
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .filter(|file| file.is_file())
+        .collect::<Vec<_>>();
+    files.reverse();
+    files
+}
+
+/// Load and merge the configuration files, later files taking priority. Relative paths in a
+/// file are relative to the directory of the file.
+pub fn load_config_files(paths: &[PathBuf]) -> miette::Result<Config> {
+    let mut config = Config::default();
+    for path in paths {
+        let content = fs::read_to_string(path).into_diagnostic()?;
+        let mut file_config: Config = toml::from_str(&content).map_err(|e| {
+            miette::miette!(
+                "Failed to parse the configuration file {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        if let Some(base) = path.parent() {
+            file_config.extensions.resolve_paths(base);
+        }
+        file_config.loaded_from = vec![path.clone()];
+        config = config.merge_config(&file_config).into_diagnostic()?;
+    }
+    config.validate().into_diagnostic()?;
+    Ok(config)
+}
+
+/// Load the configuration of a command: the user configuration, the project configuration
+/// files found from `search_dir` upwards and the `--config-file`, if any. Returns `None` if
+/// there is no configuration file.
+pub fn load_config(
+    search_dir: Option<&Path>,
+    config_file: Option<&Path>,
+) -> miette::Result<Option<Config>> {
+    let mut paths = Vec::new();
+    paths.extend(user_config_file().filter(|file| file.is_file()));
+    if let Some(dir) = search_dir {
+        paths.extend(project_config_files(dir));
+    }
+    paths.extend(config_file.map(Path::to_path_buf));
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    tracing::debug!("Loading the configuration from {:?}", paths);
+    load_config_files(&paths).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license_policy::PolicyAction;
+
+    #[test]
+    fn test_load_project_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("project");
+        let recipe = project.join("recipes").join("foo");
+        fs::create_dir_all(&recipe).unwrap();
+        fs::write(
+            project.join(PROJECT_CONFIG_FILE),
+            r#"
+default-channels = ["conda-forge"]
+target-platform = "linux-aarch64"
+output-dir = "output"
+
+[build]
+package-format = "tar-bz2:9"
+
+[publish]
+to = "https://prefix.dev/my-channel"
+
+[sandbox]
+enabled = true
+allow-read = ["/opt"]
+
+[[hooks]]
+command = "notify-send rattler-build"
+
+[[post-processors]]
+name = "sign"
+command = "sign-package"
+
+[uploaders.internal-store]
+command = "upload-to-store"
+
+[remote-cache]
+url = "https://cache.example.com/builds"
+
+[license-policy]
+deny = ["AGPL-3.0-only"]
+on-violation = "warn"
+"#,
+        )
+        .unwrap();
+        fs::write(
+            recipe.join(PROJECT_CONFIG_FILE),
+            r#"
+target-platform = "osx-arm64"
+cache-dir = "../cache"
+require-pinned = true
+conda-build-env-vars = true
+zig-cc = true
+render-cache = true
+"#,
+        )
+        .unwrap();
+
+        let files = project_config_files(&recipe);
+        assert_eq!(files.len(), 2);
+        assert!(files[0].starts_with(dunce::canonicalize(&project).unwrap()));
+
+        let config = load_config_files(&files).unwrap();
+        let project = dunce::canonicalize(&project).unwrap();
+        let ext = &config.extensions;
+        // the nearest file takes priority
+        assert_eq!(ext.target_platform, Some(Platform::OsxArm64));
+        assert_eq!(ext.output_dir, Some(project.join("output")));
+        assert_eq!(
+            ext.cache_dir,
+            Some(project.join("recipes").join("foo").join("../cache"))
+        );
+        assert_eq!(
+            ext.publish.to.as_ref().map(ToString::to_string).as_deref(),
+            Some("https://prefix.dev/my-channel")
+        );
+        assert_eq!(
+            config.default_channels.as_ref().map(Vec::len),
+            Some(1),
+            "the settings of pixi's configuration are read as well"
+        );
+        assert!(config.build.package_format.is_some());
+        assert_eq!(ext.hooks.len(), 1);
+        assert_eq!(ext.post_processors[0].display_name(), "sign");
+        assert_eq!(ext.uploaders["internal-store"].command, "upload-to-store");
+        assert_eq!(ext.license_policy.deny, vec!["AGPL-3.0-only".to_string()]);
+        assert_eq!(ext.license_policy.on_violation, Some(PolicyAction::Warn));
+        assert_eq!(ext.require_pinned, Some(true));
+        assert_eq!(ext.conda_build_env_vars, Some(true));
+        assert_eq!(ext.zig_cc, Some(true));
+        assert_eq!(ext.render_cache, Some(true));
+        assert_eq!(
+            ext.remote_cache.url.as_ref().map(Url::as_str),
+            Some("https://cache.example.com/builds")
+        );
+
+        let args = ext.sandbox.apply(SandboxArguments {
+            allow_read: vec![PathBuf::from("/usr")],
+            ..Default::default()
+        });
+        assert!(args.sandbox);
+        assert!(!args.allow_network);
+        // the command line takes priority
+        assert_eq!(args.allow_read, vec![PathBuf::from("/usr")]);
+    }
+}
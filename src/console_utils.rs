@@ -648,10 +648,130 @@ pub enum LogStyle {
     Simple,
 }
 
+/// The subsystems whose verbosity can be set with `--log-filter`, and the tracing targets
+/// that belong to them
+const LOG_SUBSYSTEMS: &[(&str, &[&str])] = &[
+    (
+        "render",
+        &[
+            "rattler_build::render",
+            "rattler_build_recipe",
+            "rattler_build_variant_config",
+            "rattler_build_jinja",
+        ],
+    ),
+    (
+        "source",
+        &[
+            "rattler_build::source",
+            "rattler_build_source_cache",
+            "rattler_git",
+        ],
+    ),
+    (
+        "solver",
+        &[
+            "rattler_build::render::solver",
+            "rattler_solve",
+            "resolvo",
+            "rattler_repodata_gateway",
+        ],
+    ),
+    (
+        "install",
+        &["rattler::install", "rattler_cache", "rattler_shell"],
+    ),
+    ("script", &["rattler_build::script", "rattler_build_script"]),
+    (
+        "packaging",
+        &[
+            "rattler_build::packaging",
+            "rattler_build::post_process",
+            "rattler_build::linux",
+            "rattler_build::macos",
+            "rattler_build::windows",
+            "rattler_build_package",
+        ],
+    ),
+    ("test", &["rattler_build::package_test"]),
+    (
+        "upload",
+        &[
+            "rattler_build::upload",
+            "rattler_build::publish",
+            "rattler_upload",
+            "rattler_index",
+        ],
+    ),
+    (
+        "network",
+        &["rattler_networking", "rattler_build_networking", "reqwest"],
+    ),
+];
+
+/// The verbosity of a subsystem (e.g. `solver=debug`), or of a tracing target
+/// (e.g. `rattler_build::source::git_source=trace`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    /// The subsystem or tracing target
+    pub target: String,
+    /// The verbosity
+    pub level: tracing_core::LevelFilter,
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (target, level) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected `<subsystem>=<level>`, got `{s}`"))?;
+        let level = tracing_core::LevelFilter::from_str(level.trim()).map_err(|_| {
+            format!("invalid level `{level}` (expected off, error, warn, info, debug or trace)")
+        })?;
+        let target = target.trim();
+        if target.is_empty() {
+            return Err(format!(
+                "missing subsystem in `{s}` (one of {})",
+                LOG_SUBSYSTEMS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        Ok(Self {
+            target: target.to_string(),
+            level,
+        })
+    }
+}
+
+impl LogFilter {
+    /// The directives of the filter, one for every tracing target of the subsystem. Anything
+    /// that is not a subsystem is used as a tracing target.
+    fn directives(&self) -> Vec<String> {
+        match LOG_SUBSYSTEMS.iter().find(|(name, _)| *name == self.target) {
+            Some((_, targets)) => targets
+                .iter()
+                .map(|target| format!("{target}={}", self.level))
+                .collect(),
+            None => vec![format!("{}={}", self.target, self.level)],
+        }
+    }
+}
+
+/// The verbosity and the log filters that the global subscriber was initialized with, so that
+/// the subscribers of [`LogCapture`] use the same filter
+static LOG_SETTINGS: std::sync::OnceLock<(clap_verbosity_flag::log::LevelFilter, Vec<LogFilter>)> =
+    std::sync::OnceLock::new();
+
 /// Constructs a default [`EnvFilter`] that is used when the user did not
-/// specify a custom RUST_LOG.
+/// specify a custom RUST_LOG. The `log_filters` take priority over the
+/// verbosity.
 pub fn get_default_env_filter(
     verbose: clap_verbosity_flag::log::LevelFilter,
+    log_filters: &[LogFilter],
 ) -> Result<EnvFilter, ParseError> {
     let mut result = EnvFilter::new(format!("rattler_build={verbose}"));
 
@@ -670,11 +790,15 @@ pub fn get_default_env_filter(
         )?);
     }
 
+    for directive in log_filters.iter().flat_map(LogFilter::directives) {
+        result = result.add_directive(Directive::from_str(&directive)?);
+    }
+
     Ok(result)
 }
 
 /// Strip ANSI escape codes from a string.
-fn strip_ansi_codes(s: &str) -> String {
+pub(crate) fn strip_ansi_codes(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
     let mut chars = s.chars().peekable();
     while let Some(c) = chars.next() {
@@ -751,6 +875,84 @@ impl<S: Subscriber> Layer<S> for GitHubActionsLayer {
     }
 }
 
+/// Captures the log output of a task that runs concurrently with other tasks (e.g. the tests of
+/// several packages), so that it can be printed as one block instead of being interleaved.
+#[derive(Clone, Default)]
+pub struct LogCapture {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl LogCapture {
+    /// A subscriber that records the log output into this capture. Attach it to the future of
+    /// the task with [`tracing::instrument::WithSubscriber::with_subscriber`].
+    pub fn subscriber(&self) -> impl Subscriber + Send + Sync + 'static {
+        // use the same filter as the global subscriber
+        let (verbose, log_filters) = LOG_SETTINGS.get().cloned().unwrap_or_else(|| {
+            let verbose = match tracing_core::LevelFilter::current() {
+                tracing_core::LevelFilter::OFF => clap_verbosity_flag::log::LevelFilter::Info,
+                current => clap_verbosity_flag::log::LevelFilter::from_str(&current.to_string())
+                    .unwrap_or(clap_verbosity_flag::log::LevelFilter::Info),
+            };
+            (verbose, Vec::new())
+        });
+        let filter = get_default_env_filter(verbose, &log_filters)
+            .unwrap_or_else(|_| EnvFilter::new("rattler_build=info"));
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(self.clone())
+    }
+
+    /// The captured log output
+    pub fn output(&self) -> String {
+        self.lines.lock().unwrap().join("\n")
+    }
+
+    fn push(&self, depth: usize, line: &str) {
+        self.lines
+            .lock()
+            .unwrap()
+            .push(format!("{}{}", "  ".repeat(depth), line));
+    }
+}
+
+impl<S> Layer<S> for LogCapture
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &Id,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Vec::new();
+        attrs.record(&mut CustomVisitor::new(&mut fields));
+        let depth = span.scope().count() - 1;
+        self.push(
+            depth,
+            &format!("{}{}", span.name(), String::from_utf8_lossy(&fields)),
+        );
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let depth = ctx.event_scope(event).map_or(0, |scope| scope.count());
+        let mut message = String::new();
+        event.record(&mut PlainMessageVisitor::new(&mut message));
+
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => "error: ",
+            Level::WARN => "warning: ",
+            _ => "",
+        };
+        for line in message.lines() {
+            self.push(depth, &format!("{prefix}{line}"));
+        }
+    }
+}
+
 /// Whether to use colors in the output.
 #[derive(clap::ValueEnum, Clone, Eq, PartialEq, Debug, Copy, Default)]
 pub enum Color {
@@ -767,6 +969,7 @@ pub enum Color {
 pub fn init_logging(
     log_style: &LogStyle,
     verbosity: &Verbosity<InfoLevel>,
+    log_filters: &[LogFilter],
     color: &Color,
     wrap_lines: Option<bool>,
     #[cfg(feature = "tui")] tui_log_sender: Option<
@@ -798,8 +1001,11 @@ pub fn init_logging(
     }
 
     // Setup tracing subscriber
-    let registry =
-        tracing_subscriber::registry().with(get_default_env_filter(verbosity.log_level_filter())?);
+    let registry = tracing_subscriber::registry().with(get_default_env_filter(
+        verbosity.log_level_filter(),
+        log_filters,
+    )?);
+    let _ = LOG_SETTINGS.set((verbosity.log_level_filter(), log_filters.to_vec()));
 
     let log_style = if verbosity.log_level_filter() >= clap_verbosity_flag::log::LevelFilter::Debug
     {
@@ -808,23 +1014,18 @@ pub fn init_logging(
         *log_style
     };
 
-    let registry = registry.with(GitHubActionsLayer(github_integration_enabled()));
+    let registry = registry
+        .with(GitHubActionsLayer(github_integration_enabled()))
+        .with(crate::build_log::BuildLogLayer);
 
     #[cfg(feature = "tui")]
     {
         if let Some(tui_log_sender) = tui_log_sender {
             log_handler.set_progress_bars_hidden(true);
-            let writer = crate::tui::logger::TuiOutputHandler {
-                log_sender: tui_log_sender,
-            };
             registry
-                .with(
-                    fmt::layer()
-                        .with_writer(writer)
-                        .without_time()
-                        .with_level(false)
-                        .with_target(false),
-                )
+                .with(crate::tui::logger::TuiLayer {
+                    log_sender: tui_log_sender,
+                })
                 .init();
             return Ok(log_handler);
         }
@@ -871,3 +1072,58 @@ pub fn github_integration_enabled() -> bool {
 pub fn github_action_runner() -> bool {
     std::env::var(consts::GITHUB_ACTIONS) == Ok("true".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use tracing::instrument::WithSubscriber;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_log_capture() {
+        let capture = LogCapture::default();
+        async {
+            let span = tracing::info_span!("Running tests for", package = "foo");
+            let _enter = span.enter();
+            tracing::info!("first line\nsecond line");
+            tracing::warn!("something is off");
+        }
+        .with_subscriber(capture.subscriber())
+        .await;
+
+        assert_eq!(
+            capture.output(),
+            "Running tests for package: foo\n  first line\n  second line\n  warning: something is off"
+        );
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let filter = LogFilter::from_str("solver=debug").unwrap();
+        assert_eq!(filter.level, tracing_core::LevelFilter::DEBUG);
+        assert_eq!(
+            filter.directives(),
+            vec![
+                "rattler_build::render::solver=debug",
+                "rattler_solve=debug",
+                "resolvo=debug",
+                "rattler_repodata_gateway=debug",
+            ]
+        );
+        // anything else is a tracing target
+        assert_eq!(
+            LogFilter::from_str("rattler_build::source=trace")
+                .unwrap()
+                .directives(),
+            vec!["rattler_build::source=trace"]
+        );
+        assert!(LogFilter::from_str("solver").is_err());
+        assert!(LogFilter::from_str("solver=loud").is_err());
+        assert!(LogFilter::from_str("=debug").is_err());
+
+        let filters = [LogFilter::from_str("upload=off").unwrap()];
+        assert!(
+            get_default_env_filter(clap_verbosity_flag::log::LevelFilter::Info, &filters).is_ok()
+        );
+    }
+}
@@ -13,7 +13,6 @@ use rattler_build::{
     source::create_patch,
 };
 use rattler_conda_types::MatchSpec;
-use rattler_config::config::ConfigBase;
 
 /// Parse the directories JSON from environment, rattler-build-log.txt, or work directory.
 ///
@@ -381,7 +380,7 @@ pub fn debug_create_patch(opts: CreatePatchOpts) -> miette::Result<()> {
 pub async fn debug_env_add(
     env_name: &str,
     opts: DebugEnvAddOpts,
-    _config: Option<ConfigBase<()>>,
+    _config: Option<rattler_build::config::Config>,
     log_handler: &Option<LoggingOutputHandler>,
 ) -> miette::Result<()> {
     let (_work_dir, directories_json) =
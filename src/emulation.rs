@@ -0,0 +1,196 @@
+//! Building for foreign architectures with emulation.
+//!
+//! With `--emulate`, the build platform is the target platform, so that the build environment is
+//! installed for the foreign architecture. Its build scripts and tests are then run with
+//! `qemu-user`: the kernel runs every foreign executable with the emulator that is registered for
+//! its architecture with `binfmt_misc`, so this works transparently for every process that is
+//! started during the build.
+use std::path::Path;
+
+use rattler_conda_types::Platform;
+
+/// The directory of the `binfmt_misc` file system
+const BINFMT_MISC: &str = "/proc/sys/fs/binfmt_misc";
+
+/// An error if the build scripts of a platform cannot be run
+#[derive(Debug, thiserror::Error)]
+pub enum EmulationError {
+    /// There is no emulator for the architecture
+    #[error(
+        "cannot run the build scripts for {platform} on {current}: emulation is only supported for Linux platforms on Linux, use cross-compilation instead"
+    )]
+    Unsupported {
+        /// The platform of the build scripts
+        platform: Platform,
+        /// The platform rattler-build runs on
+        current: Platform,
+    },
+
+    /// The emulator is not registered with `binfmt_misc`
+    #[error(
+        "cannot run the build scripts for {platform} on {current}: `qemu-{arch}` is not registered with binfmt_misc (install `qemu-user-static` or run `docker run --privileged --rm tonistiigi/binfmt --install {arch}`)"
+    )]
+    NotRegistered {
+        /// The platform of the build scripts
+        platform: Platform,
+        /// The platform rattler-build runs on
+        current: Platform,
+        /// The architecture name of qemu
+        arch: &'static str,
+    },
+}
+
+/// The name of the architecture of a Linux platform in qemu (`qemu-<arch>`)
+pub fn qemu_arch(platform: Platform) -> Option<&'static str> {
+    Some(match platform {
+        Platform::Linux32 => "i386",
+        Platform::Linux64 => "x86_64",
+        Platform::LinuxAarch64 => "aarch64",
+        Platform::LinuxArmV6l | Platform::LinuxArmV7l => "arm",
+        Platform::LinuxLoongArch64 => "loongarch64",
+        Platform::LinuxPpc64le => "ppc64le",
+        Platform::LinuxPpc64 => "ppc64",
+        Platform::LinuxPpc => "ppc",
+        Platform::LinuxS390X => "s390x",
+        Platform::LinuxRiscv32 => "riscv32",
+        Platform::LinuxRiscv64 => "riscv64",
+        _ => return None,
+    })
+}
+
+/// Whether the executables of `platform` have to be emulated on `current`
+pub fn needs_emulation(platform: Platform, current: Platform) -> bool {
+    platform.is_linux()
+        && platform != current
+        // 32-bit x86 executables run natively on x86_64
+        && !(platform == Platform::Linux32 && current == Platform::Linux64)
+}
+
+/// Whether `binfmt_misc` has an enabled entry for `qemu-<arch>`
+fn is_registered(binfmt_misc: &Path, arch: &str) -> bool {
+    let enabled = |path: &Path| {
+        fs_err::read_to_string(path).is_ok_and(|content| content.lines().next() == Some("enabled"))
+    };
+    enabled(&binfmt_misc.join("status")) && enabled(&binfmt_misc.join(format!("qemu-{arch}")))
+}
+
+/// Whether the executables of `platform` can be run on the current platform, either natively or
+/// with an emulator that is registered with `binfmt_misc`
+pub fn can_run(platform: Platform) -> bool {
+    can_run_on(platform, Platform::current(), Path::new(BINFMT_MISC))
+}
+
+fn can_run_on(platform: Platform, current: Platform, binfmt_misc: &Path) -> bool {
+    if platform == Platform::NoArch || platform == current {
+        return true;
+    }
+    if platform.is_linux() {
+        return !needs_emulation(platform, current)
+            || (current.is_linux()
+                && qemu_arch(platform).is_some_and(|arch| is_registered(binfmt_misc, arch)));
+    }
+    matches!(
+        (platform, current),
+        // Rosetta 2 and the x86 emulation of Windows on ARM
+        (Platform::Osx64, Platform::OsxArm64)
+            | (Platform::Win32, Platform::Win64)
+            | (Platform::Win64 | Platform::Win32, Platform::WinArm64)
+    )
+}
+
+/// Check that the build scripts for `platform` can be run on the current platform, i.e. that they
+/// either run natively, or that an emulator is registered for them.
+pub fn check_build_platform(platform: Platform) -> Result<(), EmulationError> {
+    check_build_platform_on(platform, Platform::current(), Path::new(BINFMT_MISC))
+}
+
+fn check_build_platform_on(
+    platform: Platform,
+    current: Platform,
+    binfmt_misc: &Path,
+) -> Result<(), EmulationError> {
+    if !needs_emulation(platform, current) {
+        return Ok(());
+    }
+
+    let arch = match qemu_arch(platform) {
+        Some(arch) if current.is_linux() => arch,
+        _ => return Err(EmulationError::Unsupported { platform, current }),
+    };
+
+    if !is_registered(binfmt_misc, arch) {
+        return Err(EmulationError::NotRegistered {
+            platform,
+            current,
+            arch,
+        });
+    }
+
+    tracing::info!("Running the build scripts for {platform} with emulation (qemu-{arch})");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_build_platform() {
+        let binfmt_misc = tempfile::tempdir().unwrap();
+        let path = binfmt_misc.path();
+
+        // native builds never need an emulator
+        assert!(check_build_platform_on(Platform::Linux64, Platform::Linux64, path).is_ok());
+        assert!(check_build_platform_on(Platform::Linux32, Platform::Linux64, path).is_ok());
+        assert!(check_build_platform_on(Platform::OsxArm64, Platform::Osx64, path).is_ok());
+
+        assert!(matches!(
+            check_build_platform_on(Platform::LinuxAarch64, Platform::Osx64, path),
+            Err(EmulationError::Unsupported { .. })
+        ));
+        assert!(matches!(
+            check_build_platform_on(Platform::LinuxAarch64, Platform::Linux64, path),
+            Err(EmulationError::NotRegistered {
+                arch: "aarch64",
+                ..
+            })
+        ));
+
+        fs_err::write(path.join("status"), "enabled\n").unwrap();
+        fs_err::write(
+            path.join("qemu-aarch64"),
+            "enabled\ninterpreter /usr/bin/qemu-aarch64-static\n",
+        )
+        .unwrap();
+        fs_err::write(
+            path.join("qemu-ppc64le"),
+            "disabled\ninterpreter /usr/bin/qemu-ppc64le-static\n",
+        )
+        .unwrap();
+        assert!(check_build_platform_on(Platform::LinuxAarch64, Platform::Linux64, path).is_ok());
+        assert!(check_build_platform_on(Platform::LinuxPpc64le, Platform::Linux64, path).is_err());
+    }
+
+    #[test]
+    fn test_can_run() {
+        let binfmt_misc = tempfile::tempdir().unwrap();
+        let path = binfmt_misc.path();
+
+        assert!(can_run_on(Platform::NoArch, Platform::Linux64, path));
+        assert!(can_run_on(Platform::Linux32, Platform::Linux64, path));
+        assert!(can_run_on(Platform::Osx64, Platform::OsxArm64, path));
+        assert!(!can_run_on(Platform::OsxArm64, Platform::Osx64, path));
+        assert!(!can_run_on(Platform::OsxArm64, Platform::Linux64, path));
+        assert!(!can_run_on(
+            Platform::EmscriptenWasm32,
+            Platform::Linux64,
+            path
+        ));
+        assert!(!can_run_on(Platform::LinuxAarch64, Platform::Linux64, path));
+
+        fs_err::write(path.join("status"), "enabled\n").unwrap();
+        fs_err::write(path.join("qemu-aarch64"), "enabled\n").unwrap();
+        assert!(can_run_on(Platform::LinuxAarch64, Platform::Linux64, path));
+        assert!(!can_run_on(Platform::LinuxAarch64, Platform::Osx64, path));
+    }
+}
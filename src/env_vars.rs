@@ -1,12 +1,18 @@
 //! Functions to collect environment variables that are used during the build process.
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::{collections::HashMap, env};
 
-use rattler_conda_types::Platform;
+use rattler_build_jinja::default_compiler;
+use rattler_build_recipe::stage1::requirements::Dependency;
+use rattler_build_types::is_free_threaded_python;
+use rattler_conda_types::{PackageNameMatcher, Platform};
 
 use crate::linux;
 use crate::macos;
 use crate::metadata::Output;
+use crate::system_tools::Tool;
+use crate::unix;
 use crate::windows;
 
 macro_rules! insert {
@@ -15,6 +21,8 @@ macro_rules! insert {
     };
 }
 
+/// The standard library directory of Python, `py_ver` is e.g. `3.12` or `3.13t` for the
+/// free-threaded build
 fn get_stdlib_dir(prefix: &Path, platform: Platform, py_ver: &str) -> PathBuf {
     if platform.is_windows() {
         prefix.join("Lib")
@@ -24,8 +32,18 @@ fn get_stdlib_dir(prefix: &Path, platform: Platform, py_ver: &str) -> PathBuf {
     }
 }
 
-fn get_sitepackages_dir(prefix: &Path, platform: Platform, py_ver: &str) -> PathBuf {
-    get_stdlib_dir(prefix, platform, py_ver).join("site-packages")
+/// The site-packages directory of Python, either the `python_site_packages_path` of the
+/// `python` package or the default one in the standard library directory
+fn get_sitepackages_dir(
+    prefix: &Path,
+    platform: Platform,
+    py_ver: &str,
+    site_packages_path: Option<&str>,
+) -> PathBuf {
+    match site_packages_path {
+        Some(path) => prefix.join(path),
+        None => get_stdlib_dir(prefix, platform, py_ver).join("site-packages"),
+    }
 }
 
 /// Returns a map of environment variables for Python that are used in the build process.
@@ -64,15 +82,21 @@ pub fn python_vars(output: &Output) -> HashMap<String, Option<String>> {
     if let Some(py_ver) = python_version {
         let py_ver: Vec<_> = py_ver.split('.').take(2).collect();
         let py_ver_str = py_ver.join(".");
-        let stdlib_dir = get_stdlib_dir(
-            output.prefix(),
-            output.host_platform().platform,
-            &py_ver_str,
-        );
+        // the free-threaded build has its own directories, e.g. `lib/python3.13t`
+        let abi_ver = if output.is_free_threaded_python() {
+            format!("{py_ver_str}t")
+        } else {
+            py_ver_str.clone()
+        };
+        let site_packages_path = output
+            .find_resolved_package("python")
+            .and_then(|(record, _)| record.package_record.python_site_packages_path.clone());
+        let stdlib_dir = get_stdlib_dir(output.prefix(), output.host_platform().platform, &abi_ver);
         let site_packages_dir = get_sitepackages_dir(
             output.prefix(),
             output.host_platform().platform,
-            &py_ver_str,
+            &abi_ver,
+            site_packages_path.as_deref(),
         );
         let py3k = if py_ver[0] == "3" { "1" } else { "0" };
         insert!(result, "PY3K", py3k);
@@ -135,7 +159,7 @@ pub fn language_vars(output: &Output) -> HashMap<String, Option<String>> {
 ///
 /// Variables:
 /// - CPU_COUNT: Number of CPUs
-/// - SHLIB_EXT: Shared library extension for platform (e.g. Linux -> .so, Windows -> .dll, macOS -> .dylib)
+/// - SHLIB_EXT: Shared library extension for platform (e.g. Linux -> .so, Windows -> .dll, macOS -> .dylib, emscripten -> .so)
 ///
 /// Forwards the following environment variables:
 /// - PATH: Path where executables are found
@@ -164,7 +188,8 @@ pub fn os_vars(prefix: &Path, platform: &Platform) -> HashMap<String, Option<Str
         ".dll"
     } else if platform.is_osx() {
         ".dylib"
-    } else if platform.is_linux() {
+    } else if platform.is_linux() || *platform == Platform::EmscriptenWasm32 {
+        // emscripten side modules (e.g. Python extensions for pyodide) use `.so` as well
         ".so"
     } else {
         ".not_implemented"
@@ -179,11 +204,322 @@ pub fn os_vars(prefix: &Path, platform: &Platform) -> HashMap<String, Option<Str
         vars.extend(macos::env::default_env_vars(prefix, platform));
     } else if platform.is_linux() {
         vars.extend(linux::env::default_env_vars(prefix, platform));
+    } else if platform.is_unix() {
+        // e.g. emscripten-wasm32, whose build scripts run on a Unix build machine
+        vars.extend(unix::env::default_env_vars(prefix));
     }
 
     vars
 }
 
+/// The major and minor version of a version, e.g. `3.12` for `3.12.1`
+fn major_minor(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+/// The version of a package in the variant, or else of the package in the host environment
+fn language_version(output: &Output, name: &str) -> Option<String> {
+    output
+        .variant()
+        .get(&name.into())
+        .map(|version| version.to_string())
+        .or_else(|| {
+            output
+                .find_resolved_package(name)
+                .map(|(record, _)| record.package_record.version.to_string())
+        })
+}
+
+/// The `GIT_*` variables of conda-build, parsed from the output of
+/// `git describe --tags --long` (e.g. `v1.2-3-gabc1234`) and the full hash of the commit
+fn git_describe_vars(describe: &str, full_hash: &str) -> HashMap<String, Option<String>> {
+    let mut vars = HashMap::new();
+    let mut parts = describe.rsplitn(3, '-');
+    if let (Some(hash), Some(number), Some(tag)) = (parts.next(), parts.next(), parts.next()) {
+        insert!(vars, "GIT_DESCRIBE_TAG", tag);
+        insert!(vars, "GIT_DESCRIBE_NUMBER", number);
+        insert!(vars, "GIT_DESCRIBE_HASH", hash);
+        insert!(vars, "GIT_BUILD_STR", format!("{number}_{hash}"));
+    }
+    insert!(vars, "GIT_FULL_HASH", full_hash);
+    vars
+}
+
+/// The `GIT_*` variables of conda-build, if the work directory is a git checkout
+fn git_vars(output: &Output) -> HashMap<String, Option<String>> {
+    let work_dir = &output.build_configuration.directories.work_dir;
+    if !work_dir.join(".git").exists() {
+        return HashMap::new();
+    }
+    let Ok(git) = output.system_tools.find_tool(Tool::Git) else {
+        return HashMap::new();
+    };
+    let run = |args: &[&str]| {
+        Command::new(&git)
+            .arg("-C")
+            .arg(work_dir)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    match run(&["rev-parse", "HEAD"]) {
+        Some(full_hash) => git_describe_vars(
+            &run(&["describe", "--tags", "--long", "HEAD"]).unwrap_or_default(),
+            &full_hash,
+        ),
+        None => HashMap::new(),
+    }
+}
+
+/// Returns the variables of conda-build that rattler-build does not set by default, for build
+/// scripts that were written for conda-build (`--conda-build-env-vars`).
+///
+/// Variables:
+/// - `CONDA_PY`, `CONDA_NPY`, `CONDA_R`, `CONDA_PERL`, `CONDA_LUA`: the versions without dots,
+///   e.g. `312` for Python 3.12
+/// - `PERL`, `PERL_VER`, `LUA`, `LUA_VER`, `LUA_INCLUDE_DIR`: the Perl and Lua of the host
+///   environment
+/// - `PY3K`, `PY_VER`, `STDLIB_DIR`, `SP_DIR` and `PYTHON` of the build environment, if Python
+///   is only a build dependency (conda-build always sets them)
+/// - `SYS_PREFIX`, `SYS_PYTHON` and `ROOT`: the build environment (the base environment of
+///   conda-build)
+/// - `DIRTY`: always empty
+/// - `GIT_DESCRIBE_TAG`, `GIT_DESCRIBE_NUMBER`, `GIT_DESCRIBE_HASH`, `GIT_BUILD_STR` and
+///   `GIT_FULL_HASH`, if the work directory is a git checkout
+///
+/// Forwards the following environment variables:
+/// - `HTTP_PROXY`, `HTTPS_PROXY` and `REQUESTS_CA_BUNDLE`
+pub fn conda_build_vars(output: &Output) -> HashMap<String, Option<String>> {
+    let mut vars = HashMap::new();
+    let windows = output.host_platform().platform.is_windows();
+    let prefix = output.prefix();
+    let build_prefix = &output.build_configuration.directories.build_prefix;
+    let python_bin = |prefix: &Path| {
+        if windows {
+            prefix.join("python.exe")
+        } else {
+            prefix.join("bin/python")
+        }
+    };
+
+    let python_version = language_version(output, "python");
+    if let Some(py_ver) = &python_version {
+        insert!(vars, "CONDA_PY", major_minor(py_ver).replace('.', ""));
+    } else if let Some(record) = output
+        .finalized_dependencies
+        .as_ref()
+        .and_then(|deps| deps.build.as_ref())
+        .and_then(|build| {
+            build
+                .resolved
+                .iter()
+                .find(|record| record.package_record.name.as_normalized() == "python")
+        })
+    {
+        let package = &record.package_record;
+        let py_ver = major_minor(&package.version.to_string());
+        let abi_ver = if is_free_threaded_python(&package.build) {
+            format!("{py_ver}t")
+        } else {
+            py_ver.clone()
+        };
+        let platform = output.build_configuration.build_platform.platform;
+        let py3k = if py_ver.starts_with("3.") { "1" } else { "0" };
+        insert!(vars, "PY3K", py3k);
+        insert!(vars, "CONDA_PY", py_ver.replace('.', ""));
+        insert!(
+            vars,
+            "STDLIB_DIR",
+            get_stdlib_dir(build_prefix, platform, &abi_ver).to_string_lossy()
+        );
+        insert!(
+            vars,
+            "SP_DIR",
+            get_sitepackages_dir(
+                build_prefix,
+                platform,
+                &abi_ver,
+                package.python_site_packages_path.as_deref()
+            )
+            .to_string_lossy()
+        );
+        insert!(vars, "PY_VER", py_ver);
+        insert!(vars, "PYTHON", python_bin(build_prefix).to_string_lossy());
+    }
+
+    if let Some(npy_ver) = output.variant().get(&"numpy".into()) {
+        insert!(
+            vars,
+            "CONDA_NPY",
+            major_minor(&npy_ver.to_string()).replace('.', "")
+        );
+    }
+    if let Some(r_ver) = output.variant().get(&"r-base".into()) {
+        insert!(vars, "CONDA_R", r_ver);
+    }
+    if let Some(perl_ver) = language_version(output, "perl") {
+        let perl = if windows {
+            prefix.join("Library/bin/perl.exe")
+        } else {
+            prefix.join("bin/perl")
+        };
+        insert!(vars, "PERL", perl.to_string_lossy());
+        insert!(vars, "CONDA_PERL", perl_ver);
+        insert!(vars, "PERL_VER", perl_ver);
+    }
+    if let Some(lua_ver) = language_version(output, "lua") {
+        let lua = if windows {
+            prefix.join("Library/bin/lua.exe")
+        } else {
+            prefix.join("bin/lua")
+        };
+        let lua_ver = major_minor(&lua_ver);
+        insert!(vars, "LUA", lua.to_string_lossy());
+        insert!(
+            vars,
+            "LUA_INCLUDE_DIR",
+            prefix.join("include").to_string_lossy()
+        );
+        insert!(vars, "CONDA_LUA", lua_ver);
+        insert!(vars, "LUA_VER", lua_ver);
+    }
+
+    insert!(vars, "SYS_PREFIX", build_prefix.to_string_lossy());
+    insert!(
+        vars,
+        "SYS_PYTHON",
+        python_bin(build_prefix).to_string_lossy()
+    );
+    insert!(vars, "ROOT", build_prefix.to_string_lossy());
+    insert!(vars, "DIRTY", "");
+    for name in ["HTTP_PROXY", "HTTPS_PROXY", "REQUESTS_CA_BUNDLE"] {
+        vars.insert(name.to_string(), env::var(name).ok());
+    }
+
+    vars.extend(git_vars(output));
+    vars
+}
+
+/// The Rust target triple of a platform, e.g. `aarch64-unknown-linux-gnu`
+fn rust_target(platform: Platform) -> Option<&'static str> {
+    Some(match platform {
+        Platform::Linux32 => "i686-unknown-linux-gnu",
+        Platform::Linux64 => "x86_64-unknown-linux-gnu",
+        Platform::LinuxAarch64 => "aarch64-unknown-linux-gnu",
+        Platform::LinuxArmV6l => "arm-unknown-linux-gnueabihf",
+        Platform::LinuxArmV7l => "armv7-unknown-linux-gnueabihf",
+        Platform::LinuxPpc64le => "powerpc64le-unknown-linux-gnu",
+        Platform::LinuxPpc64 => "powerpc64-unknown-linux-gnu",
+        Platform::LinuxS390X => "s390x-unknown-linux-gnu",
+        Platform::LinuxRiscv64 => "riscv64gc-unknown-linux-gnu",
+        Platform::LinuxLoongArch64 => "loongarch64-unknown-linux-gnu",
+        Platform::FreeBsd64 => "x86_64-unknown-freebsd",
+        Platform::Osx64 => "x86_64-apple-darwin",
+        Platform::OsxArm64 => "aarch64-apple-darwin",
+        Platform::Win32 => "i686-pc-windows-msvc",
+        Platform::Win64 => "x86_64-pc-windows-msvc",
+        Platform::WinArm64 => "aarch64-pc-windows-msvc",
+        Platform::EmscriptenWasm32 => "wasm32-unknown-emscripten",
+        Platform::WasiWasm32 => "wasm32-wasip1",
+        _ => return None,
+    })
+}
+
+/// The `GOOS` and `GOARCH` of a platform, e.g. `linux` and `arm64`
+fn go_target(platform: Platform) -> Option<(&'static str, &'static str)> {
+    Some(match platform {
+        Platform::Linux32 => ("linux", "386"),
+        Platform::Linux64 => ("linux", "amd64"),
+        Platform::LinuxAarch64 => ("linux", "arm64"),
+        Platform::LinuxArmV6l | Platform::LinuxArmV7l => ("linux", "arm"),
+        Platform::LinuxPpc64le => ("linux", "ppc64le"),
+        Platform::LinuxPpc64 => ("linux", "ppc64"),
+        Platform::LinuxS390X => ("linux", "s390x"),
+        Platform::LinuxRiscv64 => ("linux", "riscv64"),
+        Platform::LinuxLoongArch64 => ("linux", "loong64"),
+        Platform::FreeBsd64 => ("freebsd", "amd64"),
+        Platform::Osx64 => ("darwin", "amd64"),
+        Platform::OsxArm64 => ("darwin", "arm64"),
+        Platform::Win32 => ("windows", "386"),
+        Platform::Win64 => ("windows", "amd64"),
+        Platform::WinArm64 => ("windows", "arm64"),
+        Platform::WasiWasm32 => ("wasip1", "wasm"),
+        _ => return None,
+    })
+}
+
+/// The variables that point the Rust and Go compilers to the host platform
+fn compiler_target_vars(
+    platform: Platform,
+    rust: bool,
+    go: Option<bool>,
+) -> HashMap<String, Option<String>> {
+    let mut vars = HashMap::new();
+    if rust && let Some(target) = rust_target(platform) {
+        insert!(vars, "CARGO_BUILD_TARGET", target);
+    }
+    if let Some(cgo) = go
+        && let Some((os, arch)) = go_target(platform)
+    {
+        insert!(vars, "GOOS", os);
+        insert!(vars, "GOARCH", arch);
+        match platform {
+            Platform::LinuxArmV6l => {
+                insert!(vars, "GOARM", "6");
+            }
+            Platform::LinuxArmV7l => {
+                insert!(vars, "GOARM", "7");
+            }
+            _ => {}
+        }
+        insert!(vars, "CGO_ENABLED", if cgo { "1" } else { "0" });
+    }
+    vars
+}
+
+/// Returns the target variables of the Rust and Go compilers in the build requirements
+/// (`${{ compiler('rust') }}`, `${{ compiler('go') }}` and `${{ compiler('go-cgo') }}`).
+///
+/// Variables:
+/// - CARGO_BUILD_TARGET: The Rust target triple of the host platform, e.g. x86_64-unknown-linux-gnu
+/// - GOOS, GOARCH (and GOARM): The Go platform of the host platform, e.g. linux and amd64
+/// - CGO_ENABLED: 1 for the `go-cgo` compiler, 0 for the `go` compiler
+pub fn compiler_vars(output: &Output) -> HashMap<String, Option<String>> {
+    let target_platform = *output.target_platform();
+    let build_names = output
+        .recipe
+        .requirements()
+        .build
+        .iter()
+        .filter_map(|dependency| match dependency {
+            Dependency::Spec(spec) => match &spec.name {
+                Some(PackageNameMatcher::Exact(name)) => Some(name.as_normalized()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let has_compiler = |language: &str| {
+        output
+            .variant()
+            .get(&format!("{language}_compiler").into())
+            .cloned()
+            .or_else(|| default_compiler(target_platform, language))
+            .is_some_and(|name| build_names.contains(&format!("{name}_{target_platform}").as_str()))
+    };
+
+    let go = if has_compiler("go-cgo") {
+        Some(true)
+    } else if has_compiler("go") {
+        Some(false)
+    } else {
+        None
+    };
+    compiler_target_vars(output.host_platform().platform, has_compiler("rust"), go)
+}
+
 /// Set environment variables that help to force color output.
 fn force_color_vars() -> HashMap<String, Option<String>> {
     let mut vars = HashMap::new();
@@ -304,7 +640,18 @@ pub fn vars(output: &Output, build_state: &str) -> HashMap<String, Option<String
     );
     insert!(vars, "CONDA_BUILD_STATE", build_state);
 
+    // the sysroot when cross-compiling to Linux
+    vars.extend(linux::sysroot::env_vars(output));
+    // the zig wrappers when building for Linux with `zig cc`
+    vars.extend(linux::zig::env_vars(output));
+
     vars.extend(language_vars(output));
+    vars.extend(compiler_vars(output));
+
+    // the variables of conda-build that are not set by default
+    if output.build_configuration.conda_build_env_vars {
+        vars.extend(conda_build_vars(output));
+    }
 
     // for reproducibility purposes, set the SOURCE_DATE_EPOCH to the configured timestamp
     // this value will be taken from the previous package for rebuild purposes
@@ -313,3 +660,95 @@ pub fn vars(output: &Output, build_state: &str) -> HashMap<String, Option<String
 
     vars
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_os_vars_emscripten() {
+        let prefix = Path::new("/prefix");
+        let vars = os_vars(prefix, &Platform::EmscriptenWasm32);
+        assert_eq!(vars.get("SHLIB_EXT"), Some(&Some(".so".to_string())));
+        assert_eq!(
+            vars.get("PKG_CONFIG_PATH"),
+            Some(&Some(
+                prefix.join("lib/pkgconfig").to_string_lossy().to_string()
+            ))
+        );
+        assert_eq!(
+            vars.get("CMAKE_GENERATOR"),
+            Some(&Some("Unix Makefiles".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_sitepackages_dir() {
+        let prefix = Path::new("/prefix");
+        assert_eq!(
+            get_sitepackages_dir(prefix, Platform::Linux64, "3.13t", None),
+            Path::new("/prefix/lib/python3.13t/site-packages")
+        );
+        assert_eq!(
+            get_sitepackages_dir(
+                prefix,
+                Platform::Linux64,
+                "3.13t",
+                Some("lib/python3.13t/site-packages")
+            ),
+            Path::new("/prefix/lib/python3.13t/site-packages")
+        );
+        assert_eq!(
+            get_sitepackages_dir(prefix, Platform::Win64, "3.13t", None),
+            Path::new("/prefix/Lib/site-packages")
+        );
+    }
+
+    #[test]
+    fn test_git_describe_vars() {
+        let vars = git_describe_vars("v1.2-rc1-3-gabc1234", "abc1234def");
+        let get = |key: &str| vars.get(key).cloned().flatten();
+        assert_eq!(get("GIT_DESCRIBE_TAG").as_deref(), Some("v1.2-rc1"));
+        assert_eq!(get("GIT_DESCRIBE_NUMBER").as_deref(), Some("3"));
+        assert_eq!(get("GIT_DESCRIBE_HASH").as_deref(), Some("gabc1234"));
+        assert_eq!(get("GIT_BUILD_STR").as_deref(), Some("3_gabc1234"));
+        assert_eq!(get("GIT_FULL_HASH").as_deref(), Some("abc1234def"));
+
+        // without a tag, only the full hash is set
+        let vars = git_describe_vars("", "abc1234def");
+        assert_eq!(vars.len(), 1);
+    }
+
+    #[test]
+    fn test_compiler_target_vars() {
+        let vars = compiler_target_vars(Platform::LinuxAarch64, true, None);
+        assert_eq!(
+            vars,
+            HashMap::from([(
+                "CARGO_BUILD_TARGET".to_string(),
+                Some("aarch64-unknown-linux-gnu".to_string())
+            )])
+        );
+
+        let vars = compiler_target_vars(Platform::LinuxArmV7l, false, Some(true));
+        let get = |key: &str| vars.get(key).cloned().flatten();
+        assert_eq!(get("GOOS").as_deref(), Some("linux"));
+        assert_eq!(get("GOARCH").as_deref(), Some("arm"));
+        assert_eq!(get("GOARM").as_deref(), Some("7"));
+        assert_eq!(get("CGO_ENABLED").as_deref(), Some("1"));
+        assert_eq!(get("CARGO_BUILD_TARGET"), None);
+
+        let vars = compiler_target_vars(Platform::OsxArm64, true, Some(false));
+        let get = |key: &str| vars.get(key).cloned().flatten();
+        assert_eq!(
+            get("CARGO_BUILD_TARGET").as_deref(),
+            Some("aarch64-apple-darwin")
+        );
+        assert_eq!(get("GOOS").as_deref(), Some("darwin"));
+        assert_eq!(get("GOARCH").as_deref(), Some("arm64"));
+        assert_eq!(get("CGO_ENABLED").as_deref(), Some("0"));
+
+        // noarch packages have no target
+        assert!(compiler_target_vars(Platform::NoArch, true, Some(true)).is_empty());
+    }
+}
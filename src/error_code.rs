@@ -0,0 +1,335 @@
+//! Stable error codes and process exit codes for the major classes of failures, so that CI
+//! pipelines can tell a failing solve from a failing build script without parsing the log.
+//!
+//! An error is tagged with its code where it occurs (see [`WithErrorCode`]). The code is shown
+//! with the error, reported in the JSON `error` event and determines the exit code of
+//! rattler-build. Errors without a code exit with 1.
+use std::fmt;
+
+use miette::{Diagnostic, LabeledSpan, Severity, SourceCode};
+
+/// A class of failures with its own process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// The recipe could not be rendered
+    Render,
+    /// The environments could not be solved or installed
+    Solve,
+    /// The sources could not be fetched
+    Fetch,
+    /// The build script failed
+    Script,
+    /// The package could not be created or failed a check
+    Package,
+    /// The tests of the package failed
+    Test,
+    /// The packages could not be uploaded
+    Upload,
+}
+
+impl FailureClass {
+    /// The exit code of rattler-build for a failure of this class
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Render => 10,
+            Self::Solve => 11,
+            Self::Fetch => 12,
+            Self::Script => 13,
+            Self::Package => 14,
+            Self::Test => 15,
+            Self::Upload => 16,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Render => "RENDER",
+            Self::Solve => "SOLVE",
+            Self::Fetch => "FETCH",
+            Self::Script => "SCRIPT",
+            Self::Package => "PACKAGE",
+            Self::Test => "TEST",
+            Self::Upload => "UPLOAD",
+        }
+    }
+}
+
+/// A stable error code, e.g. `RB-SOLVE-001`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorCode {
+    /// The class of the failure
+    pub class: FailureClass,
+    number: u16,
+    /// What the failure means
+    pub description: &'static str,
+}
+
+impl ErrorCode {
+    const fn new(class: FailureClass, number: u16, description: &'static str) -> Self {
+        Self {
+            class,
+            number,
+            description,
+        }
+    }
+
+    /// The recipe could not be parsed or rendered
+    pub const RENDER: Self = Self::new(
+        FailureClass::Render,
+        1,
+        "The recipe could not be parsed or rendered",
+    );
+    /// The inputs of the build are not pinned
+    pub const UNPINNED: Self = Self::new(
+        FailureClass::Render,
+        2,
+        "The sources or dependencies are not pinned (`--require-pinned`)",
+    );
+    /// The dependencies could not be resolved
+    pub const SOLVE: Self = Self::new(
+        FailureClass::Solve,
+        1,
+        "The build, host or test environment could not be solved",
+    );
+    /// The solved environments could not be installed
+    pub const INSTALL: Self = Self::new(
+        FailureClass::Solve,
+        2,
+        "The build or host environment could not be installed",
+    );
+    /// The sources could not be fetched
+    pub const FETCH: Self = Self::new(
+        FailureClass::Fetch,
+        1,
+        "The sources could not be fetched, extracted or patched",
+    );
+    /// A staging cache could not be built or restored
+    pub const STAGING: Self = Self::new(
+        FailureClass::Fetch,
+        2,
+        "A staging cache could not be built or restored",
+    );
+    /// The build script exited with a non-zero exit code
+    pub const SCRIPT_EXIT: Self = Self::new(
+        FailureClass::Script,
+        1,
+        "The build script exited with a non-zero exit code",
+    );
+    /// The build script could not be executed
+    pub const SCRIPT_EXECUTION: Self = Self::new(
+        FailureClass::Script,
+        2,
+        "The build script could not be executed",
+    );
+    /// The build script was not run because of `--debug`
+    pub const SCRIPT_DEBUG: Self = Self::new(
+        FailureClass::Script,
+        3,
+        "The build script was not executed because debug mode is enabled",
+    );
+    /// The build scripts for a foreign platform cannot be run because no emulator is set up
+    pub const SCRIPT_EMULATION: Self = Self::new(
+        FailureClass::Script,
+        4,
+        "The build scripts for a foreign platform cannot be run without an emulator",
+    );
+    /// The build script uses the machine outside of the build (`--hermetic`)
+    pub const SCRIPT_HERMETIC: Self = Self::new(
+        FailureClass::Script,
+        5,
+        "The build script is not hermetic (--hermetic)",
+    );
+    /// The package could not be created
+    pub const PACKAGE: Self =
+        Self::new(FailureClass::Package, 1, "The package could not be created");
+    /// The host prefix was found in a binary file (`--error-prefix-in-binary`)
+    pub const PREFIX_IN_BINARY: Self = Self::new(
+        FailureClass::Package,
+        2,
+        "The host prefix was found in a binary file (--error-prefix-in-binary)",
+    );
+    /// The package contains symlinks, but is installed on Windows
+    pub const SYMLINKS_ON_WINDOWS: Self = Self::new(
+        FailureClass::Package,
+        3,
+        "The package contains symlinks but can be installed on Windows",
+    );
+    /// A `package_contents` test failed
+    pub const PACKAGE_CONTENTS: Self =
+        Self::new(FailureClass::Package, 4, "A package contents test failed");
+    /// The SLSA provenance of the package could not be written or signed
+    pub const PROVENANCE: Self = Self::new(
+        FailureClass::Package,
+        5,
+        "The SLSA provenance of the package could not be written or signed",
+    );
+    /// The audit found a vulnerability at or above the threshold (`--audit-fail-on`)
+    pub const AUDIT: Self = Self::new(
+        FailureClass::Package,
+        6,
+        "The audit found known vulnerabilities at or above the threshold",
+    );
+    /// The license of the package or of a run dependency violates the license policy
+    pub const LICENSE_POLICY: Self = Self::new(
+        FailureClass::Package,
+        7,
+        "The license of the package or of a run dependency violates the license policy",
+    );
+    /// The tests of the package failed
+    pub const TEST: Self = Self::new(FailureClass::Test, 1, "The tests of the package failed");
+    /// The packages could not be uploaded
+    pub const UPLOAD: Self = Self::new(
+        FailureClass::Upload,
+        1,
+        "The packages could not be uploaded or the channel could not be indexed",
+    );
+
+    /// All error codes
+    pub const ALL: &[Self] = &[
+        Self::RENDER,
+        Self::UNPINNED,
+        Self::SOLVE,
+        Self::INSTALL,
+        Self::FETCH,
+        Self::STAGING,
+        Self::SCRIPT_EXIT,
+        Self::SCRIPT_EXECUTION,
+        Self::SCRIPT_DEBUG,
+        Self::SCRIPT_EMULATION,
+        Self::SCRIPT_HERMETIC,
+        Self::PACKAGE,
+        Self::PREFIX_IN_BINARY,
+        Self::SYMLINKS_ON_WINDOWS,
+        Self::PACKAGE_CONTENTS,
+        Self::PROVENANCE,
+        Self::AUDIT,
+        Self::LICENSE_POLICY,
+        Self::TEST,
+        Self::UPLOAD,
+    ];
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RB-{}-{:03}", self.class.name(), self.number)
+    }
+}
+
+/// An error tagged with an error code. It is displayed exactly like the error, except for the
+/// code of the diagnostic.
+#[derive(Debug)]
+struct CodedError {
+    code: ErrorCode,
+    error: miette::Report,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for CodedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl Diagnostic for CodedError {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        Some(Box::new(self.code))
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.error.severity()
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.help()
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.error.url()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        self.error.source_code()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        self.error.labels()
+    }
+
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        self.error.related()
+    }
+
+    fn diagnostic_source(&self) -> Option<&dyn Diagnostic> {
+        self.error.diagnostic_source()
+    }
+}
+
+/// The error code of `error`, if it (or one of its causes) was tagged with one
+pub fn error_code(error: &miette::Report) -> Option<ErrorCode> {
+    error
+        .chain()
+        .find_map(|error| error.downcast_ref::<CodedError>())
+        .map(|error| error.code)
+}
+
+/// The exit code of rattler-build for `error`
+pub fn exit_code(error: &miette::Report) -> i32 {
+    error_code(error).map_or(1, |code| code.class.exit_code())
+}
+
+/// Tag the error of a result with an error code
+pub trait WithErrorCode<T> {
+    /// Tag the error with `code`, unless it already has an error code (the code of the
+    /// innermost failure is the most specific one)
+    fn with_error_code(self, code: ErrorCode) -> miette::Result<T>;
+}
+
+impl<T> WithErrorCode<T> for miette::Result<T> {
+    fn with_error_code(self, code: ErrorCode) -> miette::Result<T> {
+        self.map_err(|error| {
+            if error_code(&error).is_some() {
+                error
+            } else {
+                CodedError { code, error }.into()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use miette::WrapErr;
+
+    use super::*;
+
+    #[test]
+    fn test_error_code() {
+        assert_eq!(ErrorCode::SCRIPT_DEBUG.to_string(), "RB-SCRIPT-003");
+        let codes = ErrorCode::ALL
+            .iter()
+            .map(ToString::to_string)
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(codes.len(), ErrorCode::ALL.len(), "the codes are unique");
+
+        let error = Err::<(), _>(miette::miette!("no candidates for foo"))
+            .with_error_code(ErrorCode::SOLVE)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "no candidates for foo");
+        assert_eq!(error.code().unwrap().to_string(), "RB-SOLVE-001");
+        assert_eq!(exit_code(&error), 11);
+
+        // the code of the innermost failure is kept, also through context
+        let error = Err::<(), _>(error)
+            .wrap_err("failed to build bar")
+            .with_error_code(ErrorCode::STAGING)
+            .unwrap_err();
+        assert_eq!(error_code(&error), Some(ErrorCode::SOLVE));
+
+        assert_eq!(exit_code(&miette::miette!("something else")), 1);
+    }
+}
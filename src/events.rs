@@ -0,0 +1,368 @@
+//! Structured output of the results of a command (`--output-format json`).
+//!
+//! With the JSON output format, every command writes one JSON object per line (NDJSON) to
+//! stdout for the steps of a build, render, test or upload, so that CI systems and wrappers can
+//! consume the results without parsing the logs. The logs are written to stderr in both
+//! output formats. Every event has an `event` field with the type of the event.
+//!
+//! Tools that use rattler-build as a library can receive the same events with [`subscribe`],
+//! independent of the output format.
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, OnceLock, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use rattler_conda_types::RepoDataRecord;
+use serde::Serialize;
+
+use crate::package_test::TestSuiteReport;
+
+/// The format of the results that a command writes to stdout
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable results (in the logs).
+    #[default]
+    Human,
+    /// Newline delimited JSON events on stdout.
+    Json,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Set the output format of the process. Only the first call has an effect.
+pub fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+/// Whether events are written to stdout
+pub fn enabled() -> bool {
+    OUTPUT_FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+/// A step of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Rendering the recipes
+    Render,
+    /// Solving the environments of a package (`build --dry-run=solve`)
+    Solve,
+    /// Building a package
+    Build,
+    /// Testing a package
+    Test,
+    /// Uploading packages
+    Upload,
+}
+
+/// A package of a solved environment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SolvedPackage {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    /// The build string of the package
+    pub build: String,
+    /// The channel that the package comes from
+    pub channel: Option<String>,
+}
+
+impl From<&RepoDataRecord> for SolvedPackage {
+    fn from(record: &RepoDataRecord) -> Self {
+        Self {
+            name: record.package_record.name.as_normalized().to_string(),
+            version: record.package_record.version.to_string(),
+            build: record.package_record.build.clone(),
+            channel: record.channel.clone(),
+        }
+    }
+}
+
+/// An event that is written to stdout with `--output-format json`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A phase has started
+    PhaseStart {
+        /// The phase that has started
+        phase: Phase,
+        /// The package (`name-version-build`) that the phase is for, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        package: Option<String>,
+    },
+    /// A phase has finished
+    PhaseEnd {
+        /// The phase that has finished
+        phase: Phase,
+        /// The package (`name-version-build`) that the phase is for, if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        package: Option<String>,
+        /// Whether the phase was successful
+        success: bool,
+        /// The duration of the phase in seconds
+        duration: f64,
+        /// The error message if the phase failed
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// A recipe output was rendered
+    Rendered {
+        /// The package (`name-version-build`) of the output
+        package: String,
+        /// The rendered output, as printed by `build --render-only`
+        output: serde_json::Value,
+    },
+    /// The build, host or test environment of a package was solved
+    EnvironmentSolved {
+        /// The package (`name-version-build`) that is built
+        package: String,
+        /// The environment (`build`, `host` or the name of a test)
+        environment: String,
+        /// The packages of the environment
+        packages: Vec<SolvedPackage>,
+    },
+    /// A package was created
+    Artifact {
+        /// The package (`name-version-build`)
+        package: String,
+        /// The path to the package file
+        path: PathBuf,
+        /// The SHA256 hash of the package file
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// The tests of a package have finished
+    TestResult {
+        /// Whether all tests passed
+        success: bool,
+        /// The results of the tests
+        #[serde(flatten)]
+        suite: TestSuiteReport,
+    },
+    /// A line of output of the build script. These events are only passed to the subscribers,
+    /// the output is already part of the logs.
+    ScriptOutput {
+        /// The package (`name-version-build`) that is built
+        package: String,
+        /// The line of output, with the prefixes replaced by `$PREFIX` and `$BUILD_PREFIX`
+        line: String,
+        /// Whether the line was written to stderr
+        stderr: bool,
+    },
+    /// A package was uploaded (or checked with `--dry-run`)
+    Upload {
+        /// The file name of the package
+        package: String,
+        /// `uploaded`, `skipped`, `would upload` or `failed`
+        status: String,
+        /// The destination, the reason for skipping or the error
+        details: String,
+    },
+    /// The command failed
+    Error {
+        /// The code of the diagnostic, or `rattler_build::error` for errors without a code
+        code: String,
+        /// The error message
+        message: String,
+        /// The messages of the underlying errors
+        causes: Vec<String>,
+    },
+}
+
+impl Event {
+    /// The event for the end of `phase`
+    pub fn phase_end<T, E: std::fmt::Display>(
+        phase: Phase,
+        package: Option<String>,
+        duration: Duration,
+        result: &Result<T, E>,
+    ) -> Self {
+        Self::PhaseEnd {
+            phase,
+            package,
+            success: result.is_ok(),
+            duration: duration.as_secs_f64(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+
+    /// The event for a created package file
+    pub fn artifact(package: String, path: &Path) -> Self {
+        let sha256 = rattler_digest::compute_file_digest::<rattler_digest::Sha256>(path)
+            .ok()
+            .map(|digest| format!("{digest:x}"));
+        Self::Artifact {
+            package,
+            path: path.to_path_buf(),
+            sha256,
+        }
+    }
+
+    /// The event for a failed command
+    pub fn error(error: &miette::Report) -> Self {
+        Self::Error {
+            code: error
+                .code()
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "rattler_build::error".to_string()),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+/// A function that receives the events of the process
+pub type Subscriber = Arc<dyn Fn(&Event) + Send + Sync>;
+
+static SUBSCRIBERS: RwLock<Vec<(usize, Subscriber)>> = RwLock::new(Vec::new());
+static NEXT_SUBSCRIBER: AtomicUsize = AtomicUsize::new(0);
+
+/// A registered subscriber, it is removed when the subscription is dropped
+#[must_use = "the subscriber is removed when the subscription is dropped"]
+#[derive(Debug)]
+pub struct Subscription {
+    id: usize,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Ok(mut subscribers) = SUBSCRIBERS.write() {
+            subscribers.retain(|(id, _)| *id != self.id);
+        }
+    }
+}
+
+/// Pass all events of the process to `subscriber` until the returned [`Subscription`] is
+/// dropped. The subscriber is called on the thread that emits the event, so it should return
+/// quickly (e.g. by sending the event to a channel).
+pub fn subscribe(subscriber: Subscriber) -> Subscription {
+    let id = NEXT_SUBSCRIBER.fetch_add(1, Ordering::Relaxed);
+    SUBSCRIBERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((id, subscriber));
+    Subscription { id }
+}
+
+fn subscribers() -> Vec<Subscriber> {
+    SUBSCRIBERS
+        .read()
+        .map(|subscribers| subscribers.iter().map(|(_, s)| s.clone()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether events are passed to a subscriber or written to stdout
+pub fn active() -> bool {
+    enabled() || SUBSCRIBERS.read().is_ok_and(|s| !s.is_empty())
+}
+
+/// Pass `event` to the subscribers and write it as one line of JSON to stdout, if the JSON
+/// output format is enabled. The event is only created if it is used.
+pub fn emit(event: impl FnOnce() -> Event) {
+    let subscribers = subscribers();
+    if !enabled() && subscribers.is_empty() {
+        return;
+    }
+    let event = event();
+    for subscriber in &subscribers {
+        subscriber(&event);
+    }
+    if !enabled() || matches!(event, Event::ScriptOutput { .. }) {
+        return;
+    }
+    match serde_json::to_string(&event) {
+        Ok(line) => {
+            let mut stdout = std::io::stdout().lock();
+            let _ = writeln!(stdout, "{line}").and_then(|_| stdout.flush());
+        }
+        Err(e) => tracing::warn!("Failed to serialize event: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_events() {
+        let event = Event::phase_end::<(), _>(
+            Phase::Build,
+            Some("foo-1.0-h123_0".to_string()),
+            Duration::from_millis(1500),
+            &Err("Script failed to execute"),
+        );
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "phase_end",
+                "phase": "build",
+                "package": "foo-1.0-h123_0",
+                "success": false,
+                "duration": 1.5,
+                "error": "Script failed to execute",
+            })
+        );
+
+        let event = Event::error(&miette::miette!(code = "rattler_build::test", "failed"));
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "error",
+                "code": "rattler_build::test",
+                "message": "failed",
+                "causes": [],
+            })
+        );
+
+        let mut suite = TestSuiteReport::new(Path::new("foo-1.0-h123_0.conda"));
+        suite.record::<String>("commands #1", Duration::from_secs(2), Ok(()));
+        let event = Event::TestResult {
+            success: true,
+            suite,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "test_result",
+                "success": true,
+                "package": "foo-1.0-h123_0",
+                "duration": 0.0,
+                "tests": [{ "name": "commands #1", "status": "passed", "duration": 2.0 }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let upload = |package: &str| Event::Upload {
+            package: package.to_string(),
+            status: "skipped".to_string(),
+            details: String::new(),
+        };
+
+        let subscription = subscribe(Arc::new({
+            let received = received.clone();
+            move |event| {
+                // other tests emit events at the same time
+                if let Event::Upload { package, .. } = event
+                    && package.starts_with("test-subscribe")
+                {
+                    received.lock().unwrap().push(package.clone());
+                }
+            }
+        }));
+        assert!(active());
+        emit(|| upload("test-subscribe-1"));
+        drop(subscription);
+        emit(|| upload("test-subscribe-2"));
+
+        assert_eq!(*received.lock().unwrap(), vec!["test-subscribe-1"]);
+    }
+}
@@ -0,0 +1,277 @@
+//! Explain why a recipe produces a package with a different build string than a previous build
+//! (`rattler-build explain-rebuild`).
+//!
+//! The rendered recipe of the previous build is read from the package (`info/recipe`) and
+//! compared against the freshly rendered recipe: the package version and build number, the
+//! variant (which determines the hash of the build string), the requirements and the rendered
+//! recipe itself.
+use std::collections::{BTreeMap, BTreeSet};
+
+use fs_err as fs;
+use miette::{Context, IntoDiagnostic};
+use serde::Serialize;
+
+use crate::{metadata::Output, opt::PackageSource, rebuild};
+
+/// An input that differs between the previous and the new build
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Change {
+    /// The name of the input
+    pub name: String,
+    /// The value in the previous build (`None` if it did not exist)
+    pub previous: Option<String>,
+    /// The value in the new build (`None` if it does not exist anymore)
+    pub new: Option<String>,
+}
+
+/// The differences between a previous build and the new build of a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct RebuildExplanation {
+    /// The identifier of the previous build (`name-version-build`)
+    pub previous: String,
+    /// The identifier of the new build
+    pub new: String,
+    /// Changes of the version, the build number and the build string
+    pub package: Vec<Change>,
+    /// Changes of the variant, these change the hash of the build string
+    pub variant: Vec<Change>,
+    /// Changes of the requirements, keyed by `<section>: <package>`
+    pub dependencies: Vec<Change>,
+    /// A unified diff of the rendered recipes, if they differ
+    pub recipe_diff: Option<String>,
+}
+
+impl RebuildExplanation {
+    /// Whether the new build produces the same package as the previous build
+    pub fn is_unchanged(&self) -> bool {
+        self.package.is_empty()
+            && self.variant.is_empty()
+            && self.dependencies.is_empty()
+            && self.recipe_diff.is_none()
+    }
+}
+
+/// Compare two maps, returning a change for every key with a different value
+fn diff_maps(previous: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<Change> {
+    previous
+        .keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| previous.get(*key) != new.get(*key))
+        .map(|key| Change {
+            name: key.clone(),
+            previous: previous.get(key).cloned(),
+            new: new.get(key).cloned(),
+        })
+        .collect()
+}
+
+/// The variant of an output as strings
+fn variant(output: &Output) -> BTreeMap<String, String> {
+    output
+        .variant()
+        .iter()
+        .map(|(key, value)| (key.normalize(), value.to_string()))
+        .collect()
+}
+
+/// The requirements of an output, keyed by `<section>: <package>`
+fn dependencies(output: &Output) -> BTreeMap<String, String> {
+    let requirements = output.recipe.requirements();
+    let mut dependencies = BTreeMap::<String, Vec<String>>::new();
+    for (section, specs) in [
+        ("build", &requirements.build),
+        ("host", &requirements.host),
+        ("run", &requirements.run),
+        ("run_constraints", &requirements.run_constraints),
+    ] {
+        for spec in specs {
+            let spec = spec.to_string();
+            let name = spec
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            dependencies
+                .entry(format!("{section}: {name}"))
+                .or_default()
+                .push(spec);
+        }
+    }
+    dependencies
+        .into_iter()
+        .map(|(key, specs)| (key, specs.join(", ")))
+        .collect()
+}
+
+/// Explain the differences between the previous and the new build of a package
+pub(crate) fn explain(previous: &Output, new: &Output) -> miette::Result<RebuildExplanation> {
+    let package = |output: &Output| {
+        BTreeMap::from([
+            ("version".to_string(), output.version().to_string()),
+            (
+                "build number".to_string(),
+                output.recipe.build().number.unwrap_or(0).to_string(),
+            ),
+            (
+                "build string".to_string(),
+                output.build_string().to_string(),
+            ),
+        ])
+    };
+
+    let previous_recipe = serde_yaml::to_string(&previous.recipe).into_diagnostic()?;
+    let new_recipe = serde_yaml::to_string(&new.recipe).into_diagnostic()?;
+    let recipe_diff = (previous_recipe != new_recipe).then(|| {
+        let patch = flickzeug::DiffOptions::default()
+            .set_original_filename("a/rendered_recipe.yaml")
+            .set_modified_filename("b/rendered_recipe.yaml")
+            .create_patch(&previous_recipe, &new_recipe);
+        flickzeug::PatchFormatter::new()
+            .fmt_patch(&patch)
+            .to_string()
+    });
+
+    Ok(RebuildExplanation {
+        previous: previous.identifier(),
+        new: new.identifier(),
+        package: diff_maps(&package(previous), &package(new)),
+        variant: diff_maps(&variant(previous), &variant(new)),
+        dependencies: diff_maps(&dependencies(previous), &dependencies(new)),
+        recipe_diff,
+    })
+}
+
+/// Find the output of the recipe that corresponds to the previous build: an output with the same
+/// name and build string, or else the one whose variant differs the least
+pub(crate) fn matching_output<'a>(previous: &Output, outputs: &'a [Output]) -> Option<&'a Output> {
+    let previous_variant = variant(previous);
+    outputs
+        .iter()
+        .filter(|output| output.name() == previous.name())
+        .min_by_key(|output| {
+            (
+                output.build_string() != previous.build_string(),
+                diff_maps(&previous_variant, &variant(output)).len(),
+            )
+        })
+}
+
+/// Read the rendered recipe of a package (`info/recipe/rendered_recipe.yaml`)
+pub(crate) async fn read_rendered_output(
+    package: &PackageSource,
+    client: &reqwest_middleware::ClientWithMiddleware,
+) -> miette::Result<Output> {
+    let temp_dir = tempfile::tempdir().into_diagnostic()?;
+    let recipe_dir = match package {
+        PackageSource::Path(path) => {
+            rebuild::extract_recipe(path, temp_dir.path())
+                .into_diagnostic()
+                .with_context(|| format!("Failed to extract the recipe of {}", path.display()))?;
+            temp_dir.path().to_path_buf()
+        }
+        PackageSource::Url(url) => {
+            tracing::info!("Downloading {}", url);
+            rattler_package_streaming::reqwest::tokio::extract(
+                client.clone(),
+                url.clone(),
+                temp_dir.path(),
+                None,
+                None,
+            )
+            .await
+            .into_diagnostic()
+            .with_context(|| format!("Failed to extract package from URL: {url}"))?;
+            temp_dir.path().join("info").join("recipe")
+        }
+    };
+
+    let rendered_recipe =
+        fs::read_to_string(recipe_dir.join("rendered_recipe.yaml")).into_diagnostic()?;
+    serde_yaml::from_str(&rendered_recipe)
+        .into_diagnostic()
+        .context("Failed to read the rendered recipe of the previous build")
+}
+
+/// Print the explanation as tables and a diff of the rendered recipes
+pub(crate) fn print_explanation(explanation: &RebuildExplanation) {
+    let table = |changes: &[Change]| {
+        let mut table = comfy_table::Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header(["Input", "Previous", "New"]);
+        for change in changes {
+            table.add_row([
+                change.name.as_str(),
+                change.previous.as_deref().unwrap_or("-"),
+                change.new.as_deref().unwrap_or("-"),
+            ]);
+        }
+        table
+    };
+
+    tracing::info!(
+        "Comparing {} (previous) with {} (new)",
+        explanation.previous,
+        explanation.new
+    );
+    if explanation.is_unchanged() {
+        tracing::info!("{} No inputs changed", console::style("✔").green());
+        return;
+    }
+
+    for (title, changes) in [
+        ("Package", &explanation.package),
+        ("Variant (changes the hash)", &explanation.variant),
+        ("Dependencies", &explanation.dependencies),
+    ] {
+        if !changes.is_empty() {
+            tracing::info!("\n{}:\n{}", title, table(changes));
+        }
+    }
+    if let Some(diff) = &explanation.recipe_diff {
+        tracing::info!("\nRendered recipe:\n{}", diff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_maps() {
+        let previous = BTreeMap::from([
+            ("python".to_string(), "3.11".to_string()),
+            ("numpy".to_string(), "1.26".to_string()),
+            ("target_platform".to_string(), "linux-64".to_string()),
+        ]);
+        let new = BTreeMap::from([
+            ("python".to_string(), "3.12".to_string()),
+            ("openssl".to_string(), "3".to_string()),
+            ("target_platform".to_string(), "linux-64".to_string()),
+        ]);
+        assert_eq!(
+            diff_maps(&previous, &new),
+            vec![
+                Change {
+                    name: "numpy".to_string(),
+                    previous: Some("1.26".to_string()),
+                    new: None,
+                },
+                Change {
+                    name: "openssl".to_string(),
+                    previous: None,
+                    new: Some("3".to_string()),
+                },
+                Change {
+                    name: "python".to_string(),
+                    previous: Some("3.11".to_string()),
+                    new: Some("3.12".to_string()),
+                },
+            ]
+        );
+        assert!(diff_maps(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,926 @@
+//! Generate the configuration of a CI pipeline that builds recipes (`generate-ci`).
+//!
+//! The recipes are rendered for every platform, and the packages of a recipe are split into
+//! jobs ("shards") per platform and variant. A recipe is only split by the variant keys that
+//! every package uses, so that every package is built by exactly one job. Noarch packages are
+//! built by the jobs of the first platform.
+//!
+//! Every job downloads the release of rattler-build that generated the pipeline, restores the
+//! package cache, builds its packages and (with `--upload-to`) publishes them on pushes to the
+//! branch.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::{Path, PathBuf},
+};
+
+use rattler_build_jinja::Variable;
+use rattler_build_types::NormalizedKey;
+use rattler_build_variant_config::VariantConfig;
+use rattler_conda_types::{NamedChannelOrUrl, Platform};
+use serde_yaml::Value;
+
+use crate::{
+    get_build_output, load_variant_config, opt::BuildData, opt::CiProvider,
+    tool_configuration::Configuration,
+};
+
+/// The variant keys that are set by the platform of the job instead of `--variant`
+const PLATFORM_KEYS: [&str; 2] = ["target_platform", "build_platform"];
+
+/// The cache directory of rattler-build in the jobs, relative to the checkout
+const CACHE_DIR: &str = ".rattler-cache";
+
+impl CiProvider {
+    /// The file that the provider reads the pipeline from
+    pub fn default_path(self) -> &'static Path {
+        Path::new(match self {
+            Self::GitHub => ".github/workflows/rattler-build.yml",
+            Self::GitLab => ".gitlab-ci.yml",
+            Self::Azure => "azure-pipelines.yml",
+        })
+    }
+
+    /// The runner of the provider that builds the packages for the target platform, and the
+    /// platform of the runner. Packages of platforms without a runner are cross-compiled.
+    pub fn runner(self, target_platform: Platform) -> (&'static str, Platform) {
+        match self {
+            Self::GitHub => match target_platform {
+                Platform::LinuxAarch64 => ("ubuntu-24.04-arm", Platform::LinuxAarch64),
+                Platform::Osx64 => ("macos-13", Platform::Osx64),
+                p if p.is_osx() => ("macos-latest", Platform::OsxArm64),
+                p if p.is_windows() => ("windows-latest", Platform::Win64),
+                _ => ("ubuntu-latest", Platform::Linux64),
+            },
+            Self::GitLab => match target_platform {
+                Platform::LinuxAarch64 => ("saas-linux-small-arm64", Platform::LinuxAarch64),
+                p if p.is_osx() => ("saas-macos-medium-m1", Platform::OsxArm64),
+                p if p.is_windows() => ("saas-windows-medium-amd64", Platform::Win64),
+                _ => ("saas-linux-small-amd64", Platform::Linux64),
+            },
+            Self::Azure => match target_platform {
+                p if p.is_osx() => ("macOS-latest", Platform::Osx64),
+                p if p.is_windows() => ("windows-latest", Platform::Win64),
+                _ => ("ubuntu-latest", Platform::Linux64),
+            },
+        }
+    }
+}
+
+/// The URL of the release binary of this version of rattler-build for the platform of a runner
+fn rattler_build_url(platform: Platform) -> String {
+    let target = match platform {
+        Platform::Linux64 => "x86_64-unknown-linux-musl",
+        Platform::LinuxAarch64 => "aarch64-unknown-linux-musl",
+        Platform::Osx64 => "x86_64-apple-darwin",
+        Platform::OsxArm64 => "aarch64-apple-darwin",
+        Platform::Win64 => "x86_64-pc-windows-msvc.exe",
+        _ => unreachable!("there is no runner for {platform}"),
+    };
+    format!(
+        "https://github.com/prefix-dev/rattler-build/releases/download/v{}/rattler-build-{target}",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// The environment variable with the API key of the channel, which the pipeline reads from the
+/// secrets of the provider
+fn upload_secret(channel: &NamedChannelOrUrl) -> Option<&'static str> {
+    let NamedChannelOrUrl::Url(url) = channel else {
+        return None;
+    };
+    match url.host_str()? {
+        "prefix.dev" | "repo.prefix.dev" => Some("PREFIX_API_KEY"),
+        "anaconda.org" => Some("ANACONDA_API_KEY"),
+        _ => None,
+    }
+}
+
+/// Quote an argument for the shell of a job (bash, or PowerShell on Windows runners of GitLab)
+fn quote(arg: &str, powershell: bool) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.,/:=+@".contains(c))
+    {
+        arg.to_string()
+    } else if powershell {
+        format!("'{}'", arg.replace('\'', "''"))
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// A job of the pipeline that builds (a shard of) the packages of a recipe for a platform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CiJob {
+    /// The name of the job, e.g. `numpy-linux-64-python3.12`
+    pub name: String,
+    /// The path of the recipe, relative to the root of the repository
+    pub recipe: String,
+    /// The platform that the packages are built for
+    pub target_platform: Platform,
+    /// The runner that builds the packages
+    pub runner: &'static str,
+    /// The platform of the runner
+    pub build_platform: Platform,
+    /// The value of `--noarch-build-platform`, if the recipe has noarch packages
+    pub noarch_build_platform: Option<Platform>,
+    /// The variant of the shard, passed with `--variant`
+    pub variant: BTreeMap<String, String>,
+}
+
+impl CiJob {
+    /// The arguments of `rattler-build build` that select the packages of the job
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--recipe".to_string(),
+            self.recipe.clone(),
+            "--target-platform".to_string(),
+            self.target_platform.to_string(),
+        ];
+        if let Some(noarch_build_platform) = self.noarch_build_platform {
+            args.push("--noarch-build-platform".to_string());
+            args.push(noarch_build_platform.to_string());
+        }
+        for (key, value) in &self.variant {
+            args.push("--variant".to_string());
+            args.push(format!("{key}={value}"));
+        }
+        args
+    }
+}
+
+/// The name of a job from the package name, the platform and the variant of the shard
+fn job_name(name: &str, platform: Platform, variant: &BTreeMap<String, String>) -> String {
+    let mut job_name = format!("{name}-{platform}");
+    if !variant.is_empty() {
+        job_name.push('-');
+        for (key, value) in variant {
+            job_name.push_str(key);
+            job_name.push_str(value);
+        }
+    }
+    job_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Split the variants of the packages of a recipe into shards, the `--variant` values of the
+/// jobs. A recipe is split by the keys that have different values in the variants, if every
+/// package uses them. The values of the keys that are zipped with them are added, so that the
+/// zip keys still have the same length. An empty shard builds all packages.
+pub fn shard_variants(
+    variants: &[BTreeMap<NormalizedKey, Variable>],
+    variant_config: &VariantConfig,
+) -> Vec<BTreeMap<String, String>> {
+    let unsharded = vec![BTreeMap::new()];
+
+    let mut values = BTreeMap::<&NormalizedKey, BTreeSet<String>>::new();
+    for (key, value) in variants.iter().flatten() {
+        if !PLATFORM_KEYS.contains(&key.normalize().as_str()) {
+            values.entry(key).or_default().insert(value.to_string());
+        }
+    }
+    let shard_keys = values
+        .into_iter()
+        .filter(|(_, values)| values.len() > 1)
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    if shard_keys.is_empty()
+        || variants
+            .iter()
+            .any(|variant| shard_keys.iter().any(|key| !variant.contains_key(*key)))
+    {
+        return unsharded;
+    }
+
+    let zip_keys = variant_config.zip_keys.clone().unwrap_or_default();
+    let mut shards = BTreeSet::new();
+    for variant in variants {
+        let mut shard = shard_keys
+            .iter()
+            .map(|&key| (key.clone(), variant[key].to_string()))
+            .collect::<BTreeMap<_, _>>();
+
+        for zip in zip_keys
+            .iter()
+            .filter(|zip| zip.iter().any(|key| shard_keys.contains(&key)))
+        {
+            // the positions in the zip that match the values of the shard
+            let len = zip
+                .iter()
+                .filter_map(|key| variant_config.variants.get(key))
+                .map(Vec::len)
+                .max()
+                .unwrap_or(0);
+            let positions = (0..len)
+                .filter(|&i| {
+                    zip.iter().all(|key| match shard.get(key) {
+                        Some(value) => variant_config
+                            .variants
+                            .get(key)
+                            .and_then(|values| values.get(i))
+                            .is_some_and(|v| v.to_string() == *value),
+                        None => true,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for key in zip.iter().filter(|key| !shard_keys.contains(key)) {
+                let Some(zip_values) = variant_config.variants.get(key) else {
+                    continue;
+                };
+                let zip_values = positions
+                    .iter()
+                    .filter_map(|&i| zip_values.get(i).map(|v| v.to_string()))
+                    .collect::<BTreeSet<_>>();
+                // the zipped key cannot be passed with a single value
+                if zip_values.len() != 1 {
+                    return unsharded;
+                }
+                shard.insert(key.clone(), zip_values.into_iter().next().unwrap());
+            }
+        }
+        shards.insert(shard);
+    }
+
+    // values with a comma cannot be passed with `--variant`
+    if shards
+        .iter()
+        .flatten()
+        .any(|(_, value)| value.contains(','))
+    {
+        return unsharded;
+    }
+    shards
+        .into_iter()
+        .map(|shard| {
+            shard
+                .into_iter()
+                .map(|(key, value)| (key.normalize(), value))
+                .collect()
+        })
+        .collect()
+}
+
+/// The path of the recipe relative to the current directory, with `/` as separator
+fn relative_recipe_path(recipe_path: &Path) -> String {
+    let current_dir = std::env::current_dir()
+        .and_then(dunce::canonicalize)
+        .unwrap_or_default();
+    let path = dunce::canonicalize(recipe_path).unwrap_or_else(|_| recipe_path.to_path_buf());
+    path.strip_prefix(&current_dir)
+        .map(Path::to_path_buf)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Render the recipe for every target platform of the build data, and split its packages into
+/// the jobs of the pipeline
+pub(crate) async fn recipe_jobs(
+    recipe_path: &Path,
+    provider: CiProvider,
+    build_data: &BuildData,
+    tool_config: &Configuration,
+) -> miette::Result<Vec<CiJob>> {
+    let recipe = relative_recipe_path(recipe_path);
+
+    // noarch packages are built by the jobs of the first platform with a native runner
+    let platforms = build_data
+        .for_each_target_platform()
+        .into_iter()
+        .map(|build_data| {
+            let (runner, build_platform) = provider.runner(build_data.target_platform);
+            (
+                runner,
+                BuildData {
+                    build_platform,
+                    ..build_data
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    let noarch_platform = platforms
+        .iter()
+        .find(|(_, data)| data.build_platform == data.target_platform)
+        .or(platforms.first())
+        .map(|(_, data)| data.target_platform);
+
+    let mut noarch = HashSet::new();
+    let mut has_noarch = false;
+    let mut jobs = Vec::new();
+    for (runner, build_data) in &platforms {
+        let mut outputs = get_build_output(build_data, recipe_path, tool_config).await?;
+        outputs.retain(|output| {
+            *output.target_platform() != Platform::NoArch
+                || (Some(build_data.target_platform) == noarch_platform
+                    && noarch.insert(output.identifier()))
+        });
+        has_noarch |= !noarch.is_empty();
+        let Some(first) = outputs.first() else {
+            continue;
+        };
+        let name = first.name().as_normalized().to_string();
+
+        let variant_config = load_variant_config(build_data, recipe_path, tool_config).await?;
+        let variants = outputs
+            .iter()
+            .map(|output| output.build_configuration.variant.clone())
+            .collect::<Vec<_>>();
+        for variant in shard_variants(&variants, &variant_config) {
+            jobs.push(CiJob {
+                name: job_name(&name, build_data.target_platform, &variant),
+                recipe: recipe.clone(),
+                target_platform: build_data.target_platform,
+                runner,
+                build_platform: build_data.build_platform,
+                noarch_build_platform: None,
+                variant,
+            });
+        }
+    }
+
+    // the jobs of the other platforms skip the noarch packages with a build platform that is
+    // not their own
+    if has_noarch && let Some(noarch_platform) = noarch_platform {
+        let noarch_build_platform = provider.runner(noarch_platform).1;
+        for job in &mut jobs {
+            job.noarch_build_platform = Some(if job.target_platform == noarch_platform {
+                noarch_build_platform
+            } else if job.build_platform == noarch_build_platform {
+                job.target_platform
+            } else {
+                noarch_build_platform
+            });
+        }
+    }
+    Ok(jobs)
+}
+
+/// A YAML mapping with the keys in the order of the entries
+fn mapping<const N: usize>(entries: [(&str, Value); N]) -> Value {
+    Value::Mapping(
+        entries
+            .into_iter()
+            .map(|(key, value)| (Value::from(key), value))
+            .collect(),
+    )
+}
+
+/// A YAML sequence of strings
+fn sequence<S: ToString>(items: impl IntoIterator<Item = S>) -> Value {
+    Value::Sequence(
+        items
+            .into_iter()
+            .map(|item| Value::from(item.to_string()))
+            .collect(),
+    )
+}
+
+/// A CI pipeline that builds the jobs and publishes the packages
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    /// The provider of the pipeline
+    pub provider: CiProvider,
+    /// The jobs of the pipeline
+    pub jobs: Vec<CiJob>,
+    /// The arguments of `rattler-build build` that every job passes (channels and variant
+    /// configuration)
+    pub build_args: Vec<String>,
+    /// The channel to publish the packages to
+    pub upload_to: Option<NamedChannelOrUrl>,
+    /// The branch whose pushes publish the packages
+    pub branch: String,
+}
+
+impl Pipeline {
+    /// The command line that builds the packages of a job (arguments of the job are appended)
+    fn build_command(&self, executable: &str, job_args: &str, powershell: bool) -> String {
+        let mut command = format!("{executable} build {job_args}");
+        for arg in &self.build_args {
+            command.push(' ');
+            command.push_str(&quote(arg, powershell));
+        }
+        command
+    }
+
+    /// The command line that publishes the built packages
+    fn publish_command(&self, executable: &str, powershell: bool) -> Option<String> {
+        let channel = self.upload_to.as_ref()?;
+        let packages = if powershell {
+            r"(Get-ChildItem output\*\*.conda).FullName"
+        } else {
+            "output/*/*.conda"
+        };
+        Some(format!(
+            "{executable} publish {packages} --to {}",
+            quote(&channel.to_string(), powershell)
+        ))
+    }
+
+    /// The job arguments of a job as one string for the shell
+    fn job_args(job: &CiJob, powershell: bool) -> String {
+        job.args()
+            .iter()
+            .map(|arg| quote(arg, powershell))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The pipeline as YAML
+    pub fn to_yaml(&self) -> miette::Result<String> {
+        let pipeline = match self.provider {
+            CiProvider::GitHub => self.github(),
+            CiProvider::GitLab => self.gitlab(),
+            CiProvider::Azure => self.azure(),
+        };
+        let yaml = serde_yaml::to_string(&pipeline)
+            .map_err(|e| miette::miette!("Failed to serialize the pipeline: {e}"))?;
+        Ok(format!(
+            "# Generated by `rattler-build generate-ci` {}\n{yaml}",
+            env!("CARGO_PKG_VERSION")
+        ))
+    }
+
+    fn github(&self) -> Value {
+        let include = self
+            .jobs
+            .iter()
+            .map(|job| {
+                mapping([
+                    ("name", Value::from(job.name.clone())),
+                    ("os", Value::from(job.runner)),
+                    (
+                        "rattler_build_url",
+                        rattler_build_url(job.build_platform).into(),
+                    ),
+                    ("args", Self::job_args(job, false).into()),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let mut steps = vec![
+            mapping([("uses", "actions/checkout@v4".into())]),
+            mapping([
+                ("uses", "actions/cache@v4".into()),
+                (
+                    "with",
+                    mapping([
+                        ("path", CACHE_DIR.into()),
+                        ("key", "rattler-${{ matrix.name }}-${{ github.sha }}".into()),
+                        ("restore-keys", "rattler-${{ matrix.name }}-".into()),
+                    ]),
+                ),
+            ]),
+            mapping([
+                ("name", "Install rattler-build".into()),
+                ("shell", "bash".into()),
+                (
+                    "run",
+                    concat!(
+                        "mkdir -p \"$RUNNER_TEMP/bin\"\n",
+                        "curl -fsSL -o \"$RUNNER_TEMP/bin/rattler-build${{ runner.os == 'Windows' && '.exe' || '' }}\" ${{ matrix.rattler_build_url }}\n",
+                        "chmod +x \"$RUNNER_TEMP/bin/\"*\n",
+                        "echo \"$RUNNER_TEMP/bin\" >> \"$GITHUB_PATH\"\n",
+                    )
+                    .into(),
+                ),
+            ]),
+            mapping([
+                ("name", "Build".into()),
+                ("shell", "bash".into()),
+                (
+                    "run",
+                    self.build_command("rattler-build", "${{ matrix.args }}", false)
+                        .into(),
+                ),
+            ]),
+        ];
+        let mut permissions = vec![("contents", Value::from("read"))];
+        if let Some(publish) = self.publish_command("rattler-build", false) {
+            let secret = self.upload_to.as_ref().and_then(upload_secret);
+            let mut step = mapping([
+                ("name", "Publish".into()),
+                (
+                    "if",
+                    format!(
+                        "github.event_name == 'push' && github.ref == 'refs/heads/{}'",
+                        self.branch
+                    )
+                    .into(),
+                ),
+                ("shell", "bash".into()),
+                ("run", publish.into()),
+            ]);
+            if let Some(secret) = secret {
+                step.as_mapping_mut().unwrap().insert(
+                    "env".into(),
+                    mapping([(secret, format!("${{{{ secrets.{secret} }}}}").into())]),
+                );
+            }
+            steps.push(step);
+            // trusted publishing to prefix.dev
+            if secret == Some("PREFIX_API_KEY") {
+                permissions.push(("id-token", "write".into()));
+            }
+        }
+
+        mapping([
+            ("name", "rattler-build".into()),
+            (
+                "on",
+                mapping([
+                    ("push", mapping([("branches", sequence([&self.branch]))])),
+                    ("pull_request", Value::Null),
+                ]),
+            ),
+            (
+                "permissions",
+                Value::Mapping(
+                    permissions
+                        .into_iter()
+                        .map(|(key, value)| (Value::from(key), value))
+                        .collect(),
+                ),
+            ),
+            (
+                "jobs",
+                mapping([(
+                    "build",
+                    mapping([
+                        ("name", "${{ matrix.name }}".into()),
+                        ("runs-on", "${{ matrix.os }}".into()),
+                        (
+                            "strategy",
+                            mapping([
+                                ("fail-fast", false.into()),
+                                ("matrix", mapping([("include", Value::Sequence(include))])),
+                            ]),
+                        ),
+                        (
+                            "env",
+                            mapping([(
+                                "RATTLER_CACHE_DIR",
+                                format!("${{{{ github.workspace }}}}/{CACHE_DIR}").into(),
+                            )]),
+                        ),
+                        ("steps", Value::Sequence(steps)),
+                    ]),
+                )]),
+            ),
+        ])
+    }
+
+    fn gitlab(&self) -> Value {
+        let mut pipeline = serde_yaml::Mapping::new();
+        pipeline.insert("stages".into(), sequence(["build"]));
+        pipeline.insert(
+            "variables".into(),
+            mapping([(
+                "RATTLER_CACHE_DIR",
+                format!("$CI_PROJECT_DIR/{CACHE_DIR}").into(),
+            )]),
+        );
+        pipeline.insert(
+            ".rattler-build".into(),
+            mapping([
+                ("stage", "build".into()),
+                (
+                    "cache",
+                    mapping([
+                        ("key", "$CI_JOB_NAME".into()),
+                        ("paths", sequence([CACHE_DIR])),
+                    ]),
+                ),
+            ]),
+        );
+
+        for job in &self.jobs {
+            let url = rattler_build_url(job.build_platform);
+            let powershell = job.build_platform.is_windows();
+            let mut script = Vec::new();
+            if powershell {
+                script.push(format!(
+                    "Invoke-WebRequest -Uri {url} -OutFile rattler-build.exe"
+                ));
+                script.push(self.build_command(
+                    r".\rattler-build.exe",
+                    &Self::job_args(job, true),
+                    true,
+                ));
+                if let Some(publish) = self.publish_command(r".\rattler-build.exe", true) {
+                    script.push(format!(
+                        "if ($env:CI_COMMIT_BRANCH -eq \"{}\") {{ {publish} }}",
+                        self.branch
+                    ));
+                }
+            } else {
+                script.push(format!(
+                    "curl -fsSL -o rattler-build {url} && chmod +x rattler-build"
+                ));
+                script.push(self.build_command(
+                    "./rattler-build",
+                    &Self::job_args(job, false),
+                    false,
+                ));
+                if let Some(publish) = self.publish_command("./rattler-build", false) {
+                    script.push(format!(
+                        "if [ \"$CI_COMMIT_BRANCH\" = \"{}\" ]; then {publish}; fi",
+                        self.branch
+                    ));
+                }
+            }
+
+            let mut entry = serde_yaml::Mapping::new();
+            entry.insert("extends".into(), ".rattler-build".into());
+            entry.insert("tags".into(), sequence([job.runner]));
+            if job.build_platform.is_linux() {
+                entry.insert("image".into(), "buildpack-deps:noble-curl".into());
+            } else if job.build_platform.is_osx() {
+                entry.insert("image".into(), "macos-15-xcode-16".into());
+            }
+            entry.insert("script".into(), sequence(script));
+            pipeline.insert(job.name.clone().into(), Value::Mapping(entry));
+        }
+        Value::Mapping(pipeline)
+    }
+
+    fn azure(&self) -> Value {
+        let matrix = self
+            .jobs
+            .iter()
+            .map(|job| {
+                let executable = if job.build_platform.is_windows() {
+                    "rattler-build.exe"
+                } else {
+                    "rattler-build"
+                };
+                (
+                    Value::from(job.name.replace(['-', '.'], "_")),
+                    mapping([
+                        ("vmImage", job.runner.into()),
+                        (
+                            "rattlerBuildUrl",
+                            rattler_build_url(job.build_platform).into(),
+                        ),
+                        ("rattlerBuildExecutable", executable.into()),
+                        ("args", Self::job_args(job, false).into()),
+                    ]),
+                )
+            })
+            .collect::<serde_yaml::Mapping>();
+
+        let mut steps = vec![
+            mapping([("checkout", "self".into())]),
+            mapping([
+                ("task", "Cache@2".into()),
+                (
+                    "inputs",
+                    mapping([
+                        (
+                            "key",
+                            "rattler | \"$(Agent.JobName)\" | \"$(Build.SourceVersion)\"".into(),
+                        ),
+                        ("restoreKeys", "rattler | \"$(Agent.JobName)\"".into()),
+                        ("path", "$(RATTLER_CACHE_DIR)".into()),
+                    ]),
+                ),
+            ]),
+            mapping([
+                (
+                    "bash",
+                    concat!(
+                        "mkdir -p \"$(Agent.TempDirectory)/bin\"\n",
+                        "curl -fsSL -o \"$(Agent.TempDirectory)/bin/$(rattlerBuildExecutable)\" $(rattlerBuildUrl)\n",
+                        "chmod +x \"$(Agent.TempDirectory)/bin/\"*\n",
+                        "echo \"##vso[task.prependpath]$(Agent.TempDirectory)/bin\"\n",
+                    )
+                    .into(),
+                ),
+                ("displayName", "Install rattler-build".into()),
+            ]),
+            mapping([
+                (
+                    "bash",
+                    self.build_command("rattler-build", "$(args)", false).into(),
+                ),
+                ("displayName", "Build".into()),
+            ]),
+        ];
+        if let Some(publish) = self.publish_command("rattler-build", false) {
+            let mut step = mapping([
+                ("bash", publish.into()),
+                ("displayName", "Publish".into()),
+                (
+                    "condition",
+                    format!(
+                        "and(succeeded(), ne(variables['Build.Reason'], 'PullRequest'), eq(variables['Build.SourceBranch'], 'refs/heads/{}'))",
+                        self.branch
+                    )
+                    .into(),
+                ),
+            ]);
+            if let Some(secret) = self.upload_to.as_ref().and_then(upload_secret) {
+                step.as_mapping_mut().unwrap().insert(
+                    "env".into(),
+                    mapping([(secret, format!("$({secret})").into())]),
+                );
+            }
+            steps.push(step);
+        }
+
+        mapping([
+            (
+                "trigger",
+                mapping([("branches", mapping([("include", sequence([&self.branch]))]))]),
+            ),
+            (
+                "variables",
+                mapping([(
+                    "RATTLER_CACHE_DIR",
+                    format!("$(Pipeline.Workspace)/{CACHE_DIR}").into(),
+                )]),
+            ),
+            (
+                "jobs",
+                Value::Sequence(vec![mapping([
+                    ("job", "build".into()),
+                    ("strategy", mapping([("matrix", Value::Mapping(matrix))])),
+                    ("pool", mapping([("vmImage", "$(vmImage)".into())])),
+                    ("steps", Value::Sequence(steps)),
+                ])]),
+            ),
+        ])
+    }
+}
+
+/// Write the pipeline to the file (or stdout for `-`), and return the path of the file
+pub(crate) fn write_pipeline(
+    pipeline: &Pipeline,
+    output: Option<PathBuf>,
+) -> miette::Result<Option<PathBuf>> {
+    use miette::IntoDiagnostic;
+
+    let yaml = pipeline.to_yaml()?;
+    let path = output.unwrap_or_else(|| pipeline.provider.default_path().to_path_buf());
+    if path == Path::new("-") {
+        print!("{yaml}");
+        return Ok(None);
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs_err::create_dir_all(parent).into_diagnostic()?;
+    }
+    fs_err::write(&path, yaml).into_diagnostic()?;
+    Ok(Some(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(entries: &[(&str, &str)]) -> BTreeMap<NormalizedKey, Variable> {
+        entries
+            .iter()
+            .map(|(key, value)| (NormalizedKey::from(*key), Variable::from(*value)))
+            .collect()
+    }
+
+    fn shard(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_variants() {
+        let variant_config: VariantConfig = serde_yaml::from_str(
+            "zip_keys:\n  - [python, numpy]\npython: ['3.12', '3.13']\nnumpy: ['1.26', '2.0']\n",
+        )
+        .unwrap();
+
+        // split by python, and the zipped numpy is added
+        let variants = [
+            variant(&[("python", "3.12"), ("target_platform", "linux-64")]),
+            variant(&[("python", "3.13"), ("target_platform", "linux-64")]),
+        ];
+        assert_eq!(
+            shard_variants(&variants, &variant_config),
+            vec![
+                shard(&[("numpy", "1.26"), ("python", "3.12")]),
+                shard(&[("numpy", "2.0"), ("python", "3.13")]),
+            ]
+        );
+
+        // a package without python would be built by every shard
+        let variants = [
+            variant(&[("python", "3.12")]),
+            variant(&[("python", "3.13")]),
+            variant(&[("target_platform", "linux-64")]),
+        ];
+        assert_eq!(shard_variants(&variants, &variant_config), vec![shard(&[])]);
+
+        // a single variant is not split
+        let variants = [variant(&[("python", "3.12")])];
+        assert_eq!(shard_variants(&variants, &variant_config), vec![shard(&[])]);
+    }
+
+    #[test]
+    fn test_runner() {
+        assert_eq!(
+            CiProvider::GitHub.runner(Platform::LinuxAarch64),
+            ("ubuntu-24.04-arm", Platform::LinuxAarch64)
+        );
+        // cross-compiled on a runner of another platform
+        assert_eq!(
+            CiProvider::GitHub.runner(Platform::LinuxPpc64le),
+            ("ubuntu-latest", Platform::Linux64)
+        );
+        assert_eq!(
+            CiProvider::Azure.runner(Platform::OsxArm64),
+            ("macOS-latest", Platform::Osx64)
+        );
+        assert_eq!(
+            CiProvider::GitLab.runner(Platform::Osx64),
+            ("saas-macos-medium-m1", Platform::OsxArm64)
+        );
+    }
+
+    #[test]
+    fn test_job_args() {
+        let job = CiJob {
+            name: job_name(
+                "numpy",
+                Platform::Linux64,
+                &shard(&[("python", "3.12.* *_cpython")]),
+            ),
+            recipe: "recipes/numpy/recipe.yaml".to_string(),
+            target_platform: Platform::Linux64,
+            runner: "ubuntu-latest",
+            build_platform: Platform::Linux64,
+            noarch_build_platform: Some(Platform::Linux64),
+            variant: shard(&[("python", "3.12.* *_cpython")]),
+        };
+        assert_eq!(job.name, "numpy-linux-64-python3.12.____cpython");
+        assert_eq!(
+            Pipeline::job_args(&job, false),
+            "--recipe recipes/numpy/recipe.yaml --target-platform linux-64 --noarch-build-platform linux-64 --variant 'python=3.12.* *_cpython'"
+        );
+        assert_eq!(quote("it's", false), r"'it'\''s'");
+        assert_eq!(quote("it's", true), "'it''s'");
+    }
+
+    #[test]
+    fn test_pipeline_yaml() {
+        let job = CiJob {
+            name: "foo-win-64".to_string(),
+            recipe: "recipe.yaml".to_string(),
+            target_platform: Platform::Win64,
+            runner: "windows-latest",
+            build_platform: Platform::Win64,
+            noarch_build_platform: None,
+            variant: BTreeMap::new(),
+        };
+        for provider in [CiProvider::GitHub, CiProvider::GitLab, CiProvider::Azure] {
+            let pipeline = Pipeline {
+                provider,
+                jobs: vec![CiJob {
+                    runner: provider.runner(Platform::Win64).0,
+                    ..job.clone()
+                }],
+                build_args: vec!["-c".to_string(), "conda-forge".to_string()],
+                upload_to: Some("https://prefix.dev/my-channel".parse().unwrap()),
+                branch: "main".to_string(),
+            };
+            let yaml = pipeline.to_yaml().unwrap();
+            // the pipeline is valid YAML
+            serde_yaml::from_str::<Value>(&yaml).unwrap();
+            assert!(yaml.contains("--to https://prefix.dev/my-channel"));
+            assert!(yaml.contains("-c conda-forge"));
+            match provider {
+                CiProvider::GitHub => {
+                    assert!(yaml.contains("id-token: write"));
+                    assert!(yaml.contains("PREFIX_API_KEY: ${{ secrets.PREFIX_API_KEY }}"));
+                }
+                CiProvider::GitLab => {
+                    assert!(yaml.contains(r".\rattler-build.exe build --recipe recipe.yaml"));
+                }
+                CiProvider::Azure => {
+                    assert!(yaml.contains("foo_win_64:"));
+                    assert!(yaml.contains("rattlerBuildExecutable: rattler-build.exe"));
+                }
+            }
+        }
+    }
+}
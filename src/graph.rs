@@ -0,0 +1,387 @@
+//! The dependency graph among the outputs of one or more recipes (`rattler-build graph`).
+//!
+//! Every rendered output (variant) is a node. An edge points from an output to the outputs that
+//! depend on it in their build, host or run requirements, i.e. in the order in which they have to
+//! be built. A dependency on a package with several variants is connected to the variants that
+//! agree with the variant of the dependent output.
+use std::collections::BTreeMap;
+
+use rattler_build_recipe::stage1::requirements::Dependency;
+use serde::Serialize;
+
+use crate::metadata::Output;
+
+/// An output of a recipe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Node {
+    /// The identifier of the output (`name-version-build`)
+    pub id: String,
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    /// The build string of the package
+    pub build_string: String,
+    /// The recipe that the output belongs to
+    pub recipe: String,
+    /// The variant of the output
+    pub variant: BTreeMap<String, String>,
+}
+
+/// A dependency of an output on another output
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub(crate) struct Edge {
+    /// The output that is depended on
+    pub from: String,
+    /// The output that depends on it
+    pub to: String,
+    /// The requirement sections that contain the dependency (`build`, `host`, `run`)
+    pub sections: Vec<String>,
+    /// Whether the dependency is a `pin_subpackage`
+    pub pin_subpackage: bool,
+}
+
+/// The dependency graph among outputs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct DependencyGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A requirement of an output: the section, the package name and whether it is a
+/// `pin_subpackage`
+type Requirement = (&'static str, String, bool);
+
+/// The variants of `candidate` and `variant` agree on all the keys they have in common (noarch
+/// outputs can be used by outputs of any target platform)
+fn is_compatible(candidate: &Node, variant: &BTreeMap<String, String>) -> bool {
+    candidate.variant.iter().all(|(key, value)| {
+        variant.get(key).is_none_or(|other| {
+            other == value || (key == "target_platform" && (value == "noarch" || other == "noarch"))
+        })
+    })
+}
+
+impl DependencyGraph {
+    /// The dependency graph among the rendered `outputs`
+    pub fn from_outputs(outputs: &[Output]) -> Self {
+        let nodes = outputs
+            .iter()
+            .map(|output| {
+                let node = Node {
+                    id: output.identifier(),
+                    name: output.name().as_normalized().to_string(),
+                    version: output.version().to_string(),
+                    build_string: output.build_string().to_string(),
+                    recipe: output
+                        .build_configuration
+                        .directories
+                        .recipe_path
+                        .display()
+                        .to_string(),
+                    variant: output
+                        .variant()
+                        .iter()
+                        .map(|(key, value)| (key.normalize(), value.to_string()))
+                        .collect(),
+                };
+                let requirements = output.recipe.requirements();
+                let requirements = [
+                    ("build", &requirements.build),
+                    ("host", &requirements.host),
+                    ("run", &requirements.run),
+                ]
+                .into_iter()
+                .flat_map(|(section, dependencies)| {
+                    dependencies.iter().filter_map(move |dependency| {
+                        let name = dependency.name()?.as_normalized().to_string();
+                        let pin = matches!(dependency, Dependency::PinSubpackage(_));
+                        Some((section, name, pin))
+                    })
+                })
+                .collect();
+                (node, requirements)
+            })
+            .collect::<Vec<_>>();
+        Self::new(nodes)
+    }
+
+    /// The graph of `nodes` with their requirements
+    fn new(nodes: Vec<(Node, Vec<Requirement>)>) -> Self {
+        let mut edges = BTreeMap::<(String, String), Edge>::new();
+        for (node, requirements) in &nodes {
+            for (section, name, pin_subpackage) in requirements {
+                let candidates = nodes
+                    .iter()
+                    .map(|(candidate, _)| candidate)
+                    .filter(|candidate| &candidate.name == name && candidate.id != node.id)
+                    .collect::<Vec<_>>();
+                let compatible = candidates
+                    .iter()
+                    .copied()
+                    .filter(|candidate| is_compatible(candidate, &node.variant))
+                    .collect::<Vec<_>>();
+                let dependencies = if compatible.is_empty() {
+                    candidates
+                } else {
+                    compatible
+                };
+
+                for dependency in dependencies {
+                    let edge = edges
+                        .entry((dependency.id.clone(), node.id.clone()))
+                        .or_insert_with(|| Edge {
+                            from: dependency.id.clone(),
+                            to: node.id.clone(),
+                            sections: Vec::new(),
+                            pin_subpackage: false,
+                        });
+                    if !edge.sections.iter().any(|s| s == section) {
+                        edge.sections.push(section.to_string());
+                    }
+                    edge.pin_subpackage |= pin_subpackage;
+                }
+            }
+        }
+
+        Self {
+            nodes: nodes.into_iter().map(|(node, _)| node).collect(),
+            edges: edges.into_values().collect(),
+        }
+    }
+
+    /// The nodes grouped by recipe, in the order in which the recipes appear
+    fn nodes_by_recipe(&self) -> Vec<(&str, Vec<(usize, &Node)>)> {
+        let mut recipes = Vec::<(&str, Vec<(usize, &Node)>)>::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            match recipes
+                .iter_mut()
+                .find(|(recipe, _)| *recipe == node.recipe)
+            {
+                Some((_, nodes)) => nodes.push((index, node)),
+                None => recipes.push((&node.recipe, vec![(index, node)])),
+            }
+        }
+        recipes
+    }
+
+    fn index(&self, id: &str) -> usize {
+        self.nodes
+            .iter()
+            .position(|node| node.id == id)
+            .expect("edges only connect nodes of the graph")
+    }
+
+    fn label(edge: &Edge) -> String {
+        let mut label = edge.sections.join(", ");
+        if edge.pin_subpackage {
+            label.push_str(" (pin_subpackage)");
+        }
+        label
+    }
+
+    /// The graph in the DOT language of Graphviz. The outputs of every recipe are grouped in a
+    /// cluster if there are several recipes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph outputs {\n    node [shape=box];\n");
+        let recipes = self.nodes_by_recipe();
+        for (cluster, (recipe, nodes)) in recipes.iter().enumerate() {
+            let indent = if recipes.len() > 1 {
+                dot.push_str(&format!(
+                    "    subgraph cluster_{cluster} {{\n        label={recipe:?};\n"
+                ));
+                "        "
+            } else {
+                "    "
+            };
+            for (index, node) in nodes {
+                dot.push_str(&format!(
+                    "{indent}n{index} [label={:?}];\n",
+                    format!("{}\n{}\n{}", node.name, node.version, node.build_string)
+                ));
+            }
+            if recipes.len() > 1 {
+                dot.push_str("    }\n");
+            }
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    n{} -> n{} [label={:?}{}];\n",
+                self.index(&edge.from),
+                self.index(&edge.to),
+                Self::label(edge),
+                if edge.pin_subpackage {
+                    ", style=bold"
+                } else {
+                    ""
+                }
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph as a Mermaid flowchart. `pin_subpackage` dependencies are drawn as thick
+    /// arrows.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart LR\n");
+        let recipes = self.nodes_by_recipe();
+        for (cluster, (recipe, nodes)) in recipes.iter().enumerate() {
+            let indent = if recipes.len() > 1 {
+                mermaid.push_str(&format!("    subgraph r{cluster} [\"{recipe}\"]\n"));
+                "        "
+            } else {
+                "    "
+            };
+            for (index, node) in nodes {
+                mermaid.push_str(&format!(
+                    "{indent}n{index}[\"{} {}<br/>{}\"]\n",
+                    node.name, node.version, node.build_string
+                ));
+            }
+            if recipes.len() > 1 {
+                mermaid.push_str("    end\n");
+            }
+        }
+        for edge in &self.edges {
+            mermaid.push_str(&format!(
+                "    n{} {}|{}| n{}\n",
+                self.index(&edge.from),
+                if edge.pin_subpackage { "==>" } else { "-->" },
+                edge.sections.join(", "),
+                self.index(&edge.to)
+            ));
+        }
+        mermaid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, python: Option<&str>, recipe: &str) -> Node {
+        let build_string = python.map_or("h0_0".to_string(), |python| format!("py{python}_0"));
+        Node {
+            id: format!("{name}-1.0-{build_string}"),
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            build_string,
+            recipe: recipe.to_string(),
+            variant: python
+                .map(|python| BTreeMap::from([("python".to_string(), python.to_string())]))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn graph() -> DependencyGraph {
+        DependencyGraph::new(vec![
+            (node("libfoo", None, "foo/recipe.yaml"), vec![]),
+            (
+                node("py-foo", Some("312"), "foo/recipe.yaml"),
+                vec![
+                    ("host", "libfoo".to_string(), true),
+                    ("run", "libfoo".to_string(), true),
+                    ("host", "python".to_string(), false),
+                ],
+            ),
+            (
+                node("py-foo", Some("313"), "foo/recipe.yaml"),
+                vec![("host", "libfoo".to_string(), true)],
+            ),
+            (
+                node("app", Some("313"), "app/recipe.yaml"),
+                vec![("run", "py-foo".to_string(), false)],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_edges() {
+        let edges = graph()
+            .edges
+            .into_iter()
+            .map(|edge| (edge.from, edge.to, edge.sections, edge.pin_subpackage))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                (
+                    "libfoo-1.0-h0_0".to_string(),
+                    "py-foo-1.0-py312_0".to_string(),
+                    vec!["host".to_string(), "run".to_string()],
+                    true
+                ),
+                (
+                    "libfoo-1.0-h0_0".to_string(),
+                    "py-foo-1.0-py313_0".to_string(),
+                    vec!["host".to_string()],
+                    true
+                ),
+                // only the variant with the same python version
+                (
+                    "py-foo-1.0-py313_0".to_string(),
+                    "app-1.0-py313_0".to_string(),
+                    vec!["run".to_string()],
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edges_per_target_platform() {
+        let platform_node = |name: &str, platform: &str| Node {
+            id: format!("{name}-1.0-{platform}"),
+            variant: BTreeMap::from([("target_platform".to_string(), platform.to_string())]),
+            ..node(name, None, "recipe.yaml")
+        };
+        let graph = DependencyGraph::new(vec![
+            (platform_node("data", "noarch"), vec![]),
+            (platform_node("lib", "osx-64"), vec![]),
+            (platform_node("lib", "osx-arm64"), vec![]),
+            (
+                platform_node("app", "osx-64"),
+                vec![
+                    ("host", "lib".to_string(), true),
+                    ("run", "data".to_string(), false),
+                ],
+            ),
+            (
+                platform_node("app", "osx-arm64"),
+                vec![
+                    ("host", "lib".to_string(), true),
+                    ("run", "data".to_string(), false),
+                ],
+            ),
+        ]);
+
+        let edges = graph
+            .edges
+            .into_iter()
+            .map(|edge| (edge.from, edge.to))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                ("data-1.0-noarch".to_string(), "app-1.0-osx-64".to_string()),
+                (
+                    "data-1.0-noarch".to_string(),
+                    "app-1.0-osx-arm64".to_string()
+                ),
+                ("lib-1.0-osx-64".to_string(), "app-1.0-osx-64".to_string()),
+                (
+                    "lib-1.0-osx-arm64".to_string(),
+                    "app-1.0-osx-arm64".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_formats() {
+        let graph = graph();
+        insta::assert_snapshot!(graph.to_dot());
+        insta::assert_snapshot!(graph.to_mermaid());
+    }
+}
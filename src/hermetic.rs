@@ -0,0 +1,275 @@
+//! Checks of hermetic builds (`--hermetic`).
+//!
+//! A hermetic build script only uses the work directory, the prefixes and the caches of the
+//! build, so that the package does not depend on the machine it was built on. Before the script
+//! runs, it is checked for uses of the home directory, of `/usr/local` (and the prefixes of other
+//! system package managers) and of system package managers. Any other read outside of the build
+//! is denied by the sandbox, and the denials are reported if the script fails.
+use std::fmt;
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+
+use crate::metadata::Output;
+
+/// The kind of a use of the machine outside of the build
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The home directory of the user
+    HomeDirectory,
+    /// The prefix of a system package manager, e.g. `/usr/local`
+    SystemPrefix,
+    /// A system package manager, e.g. `apt-get` or `brew`
+    SystemPackageManager,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HomeDirectory => write!(f, "uses the home directory"),
+            Self::SystemPrefix => write!(f, "uses a system prefix"),
+            Self::SystemPackageManager => write!(f, "runs a system package manager"),
+        }
+    }
+}
+
+/// A use of the machine outside of the build in the build script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// The line of the script (starting at 1)
+    pub line: usize,
+    /// What is used
+    pub kind: ViolationKind,
+    /// The offending text, e.g. `$HOME` or `apt-get`
+    pub text: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} (`{}`)", self.line, self.kind, self.text)
+    }
+}
+
+/// References to the home directory in shell, cmd and PowerShell scripts
+const HOME_DIRECTORY: &[&str] = &[
+    "$HOME",
+    "${HOME}",
+    "~/",
+    "%USERPROFILE%",
+    "%HOMEPATH%",
+    "$env:USERPROFILE",
+    "$env:HOME",
+];
+
+/// The prefixes of system package managers
+const SYSTEM_PREFIXES: &[&str] = &[
+    "/usr/local",
+    "/opt/homebrew",
+    "/opt/local",
+    "/home/linuxbrew",
+];
+
+/// System package managers
+const SYSTEM_PACKAGE_MANAGERS: &[&str] = &[
+    "apt", "apt-get", "aptitude", "dpkg", "yum", "dnf", "rpm", "zypper", "pacman", "apk", "brew",
+    "port", "choco", "winget", "scoop",
+];
+
+/// Words that can precede the command of a shell command
+const COMMAND_PREFIXES: &[&str] = &["sudo", "env", "command", "exec", "time", "nohup"];
+
+/// Whether `pattern` occurs in `line` as a word of its own (and not e.g. as a part of
+/// `$PREFIX/usr/local` or `$HOMEBREW_PREFIX`)
+fn contains_word(line: &str, pattern: &str) -> bool {
+    line.match_indices(pattern).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + pattern.len()..].chars().next();
+        before.is_none_or(|c| c.is_whitespace() || "\"'=:(`;".contains(c))
+            && after.is_none_or(|c| !c.is_alphanumeric() && c != '_')
+    })
+}
+
+/// The commands of a line of a script, split at `;`, `&&`, `||`, `|`, `(` and backticks
+fn commands(line: &str) -> impl Iterator<Item = &str> {
+    line.split([';', '&', '|', '(', '`'])
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+}
+
+/// The program that a shell command runs
+fn program(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|word| !COMMAND_PREFIXES.contains(word) && !word.contains('='))
+        .map(|word| word.rsplit(['/', '\\']).next().unwrap_or(word))
+        .map(|word| word.trim_end_matches(".exe"))
+}
+
+/// Find the uses of the machine outside of the build in a build script
+pub fn find_violations(script: &str) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for (index, line) in script.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let lowercase = trimmed.to_lowercase();
+        if trimmed.starts_with('#') || trimmed.starts_with("::") || lowercase.starts_with("rem ") {
+            continue;
+        }
+        let mut add = |kind, text: &str| {
+            violations.push(Violation {
+                line: index + 1,
+                kind,
+                text: text.to_string(),
+            })
+        };
+
+        for (kind, patterns) in [
+            (ViolationKind::HomeDirectory, HOME_DIRECTORY),
+            (ViolationKind::SystemPrefix, SYSTEM_PREFIXES),
+        ] {
+            for pattern in patterns {
+                if contains_word(line, pattern) {
+                    add(kind, pattern);
+                }
+            }
+        }
+
+        for program in commands(line).filter_map(program) {
+            if SYSTEM_PACKAGE_MANAGERS.contains(&program) {
+                add(ViolationKind::SystemPackageManager, program);
+            }
+        }
+    }
+    violations
+}
+
+/// The lines of the build log in which the sandbox denied an access
+pub fn denied_accesses(log: &str) -> Vec<&str> {
+    log.lines()
+        .filter(|line| {
+            line.contains("Permission denied") || line.contains("Operation not permitted")
+        })
+        .collect()
+}
+
+/// Whether the build of the output is hermetic
+pub(crate) fn is_hermetic(output: &Output) -> bool {
+    output
+        .build_configuration
+        .sandbox_config
+        .as_ref()
+        .is_some_and(|config| config.is_hermetic())
+}
+
+/// Check that the build script of the output does not use the machine outside of the build.
+/// All the violations are reported before the build fails.
+pub(crate) fn check_build_script(output: &Output) -> miette::Result<()> {
+    let directories = &output.build_configuration.directories;
+    let script = output
+        .recipe
+        .build()
+        .script
+        .resolve_content(
+            &directories.recipe_dir,
+            Some(output.jinja_renderer()),
+            if cfg!(windows) { &["bat"] } else { &["sh"] },
+        )
+        .into_diagnostic()?;
+
+    let violations = find_violations(script.script());
+    if violations.is_empty() {
+        return Ok(());
+    }
+    for violation in &violations {
+        tracing::error!("The build script {violation}");
+    }
+    Err(miette::miette!(
+        help = "Use the build and host environments instead of the tools and libraries of the machine, or build without `--hermetic`",
+        "The build script is not hermetic ({} violation(s))",
+        violations.len()
+    ))
+}
+
+/// Report the accesses that the sandbox denied, if the build script of a hermetic build failed
+pub(crate) fn report_denied_accesses(output: &Output) {
+    let log = output
+        .build_configuration
+        .directories
+        .work_dir
+        .join("conda_build.log");
+    let Ok(log) = fs::read_to_string(log) else {
+        return;
+    };
+    let denied = denied_accesses(&log);
+    if denied.is_empty() {
+        return;
+    }
+    tracing::warn!(
+        "The sandbox of the hermetic build denied {} access(es) outside of the build:",
+        denied.len()
+    );
+    for line in denied {
+        tracing::warn!("  {}", line.trim());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_violations() {
+        let script = r#"
+# the cache in $HOME is not used
+export PKG_CONFIG_PATH=/usr/local/lib/pkgconfig
+cp -r ~/.cargo $SRC_DIR
+echo ${HOME} $HOMEBREW_PREFIX
+sudo apt-get install -y libfoo && make
+brew install bar
+make PREFIX=$PREFIX/usr/local install
+./configure --port 8080
+"#;
+        let violations = find_violations(script)
+            .into_iter()
+            .map(|violation| (violation.line, violation.kind, violation.text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            vec![
+                (3, ViolationKind::SystemPrefix, "/usr/local".to_string()),
+                (4, ViolationKind::HomeDirectory, "~/".to_string()),
+                (5, ViolationKind::HomeDirectory, "${HOME}".to_string()),
+                (
+                    6,
+                    ViolationKind::SystemPackageManager,
+                    "apt-get".to_string()
+                ),
+                (7, ViolationKind::SystemPackageManager, "brew".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_violations_windows() {
+        let script = "REM %USERPROFILE% is not used\r\ncopy %USERPROFILE%\\foo.txt %PREFIX%\r\nchoco.exe install cmake\r\n";
+        let violations = find_violations(script)
+            .into_iter()
+            .map(|violation| (violation.line, violation.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            vec![
+                (2, ViolationKind::HomeDirectory),
+                (3, ViolationKind::SystemPackageManager),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_denied_accesses() {
+        let log = "checking for gcc... yes\ncat: /home/user/.netrc: Permission denied\nok\n";
+        assert_eq!(
+            denied_accesses(log),
+            vec!["cat: /home/user/.netrc: Permission denied"]
+        );
+    }
+}
@@ -0,0 +1,371 @@
+//! Notification hooks that are fired when a build finishes: an HTTP webhook that receives a JSON
+//! report of the build, or a command that receives the report on stdin.
+//!
+//! The report has a `text` field with a one-line summary, so that it can be posted to Slack or
+//! Matrix incoming webhooks as is. A failing hook is reported as a warning and never fails the
+//! build.
+use std::{io::Write, path::PathBuf, process::Stdio};
+
+use chrono::{DateTime, Utc};
+use rattler_digest::compute_file_digest;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{get_rattler_build_version, metadata::Output};
+
+/// When a hook is fired
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookEvent {
+    /// All packages were built (and tested) successfully
+    Success,
+    /// The build of a package or its tests failed
+    Failure,
+}
+
+impl HookEvent {
+    fn all() -> Vec<Self> {
+        vec![Self::Success, Self::Failure]
+    }
+}
+
+/// A hook that is fired when a build finishes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hook {
+    /// The URL that the JSON report is posted to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<Url>,
+
+    /// The command that is run (with `sh -c`, or `cmd /C` on Windows) with the JSON report on
+    /// stdin and the status in `RATTLER_BUILD_STATUS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    /// The events that fire the hook (by default success and failure)
+    #[serde(default = "HookEvent::all")]
+    pub on: Vec<HookEvent>,
+}
+
+impl Hook {
+    /// A webhook that is fired on success and failure
+    pub fn webhook(url: Url) -> Self {
+        Self {
+            webhook: Some(url),
+            command: None,
+            on: HookEvent::all(),
+        }
+    }
+
+    /// A command that is fired on success and failure
+    pub fn command(command: String) -> Self {
+        Self {
+            webhook: None,
+            command: Some(command),
+            on: HookEvent::all(),
+        }
+    }
+
+    /// Check that the hook has either a webhook or a command
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.webhook, &self.command) {
+            (Some(_), None) | (None, Some(_)) => Ok(()),
+            _ => Err("a hook needs either a `webhook` or a `command`".to_string()),
+        }
+    }
+}
+
+/// The status of a package at the end of a build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PackageStatus {
+    /// The package was built
+    Built,
+    /// The build of the package failed
+    Failed,
+    /// The package was not built (e.g. because it already exists or an earlier build failed)
+    NotBuilt,
+}
+
+/// A package in the report of a build
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PackageReport {
+    pub name: String,
+    pub version: String,
+    pub build_string: String,
+    pub subdir: String,
+    pub status: PackageStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+}
+
+impl From<&Output> for PackageReport {
+    fn from(output: &Output) -> Self {
+        let summary = output.build_summary.lock().unwrap();
+        let artifact = summary
+            .artifact
+            .clone()
+            .filter(|artifact| artifact.exists());
+        let status = if summary.failed {
+            PackageStatus::Failed
+        } else if artifact.is_some() {
+            PackageStatus::Built
+        } else {
+            PackageStatus::NotBuilt
+        };
+        let duration_seconds = summary
+            .build_start
+            .zip(summary.build_end)
+            .map(|(start, end)| (end - start).as_seconds_f64());
+        Self {
+            name: output.name().as_normalized().to_string(),
+            version: output.version().to_string(),
+            build_string: output.build_string().to_string(),
+            subdir: output.target_platform().to_string(),
+            status,
+            size: artifact
+                .as_ref()
+                .and_then(|artifact| fs_err::metadata(artifact).ok())
+                .map(|metadata| metadata.len()),
+            sha256: artifact.as_ref().and_then(|artifact| {
+                compute_file_digest::<sha2::Sha256>(artifact)
+                    .ok()
+                    .map(|digest| format!("{digest:x}"))
+            }),
+            artifact,
+            duration_seconds,
+        }
+    }
+}
+
+/// The JSON report of a build that is sent to the hooks
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BuildReport {
+    /// A one-line summary of the build (the message of Slack and Matrix webhooks)
+    pub text: String,
+    pub status: HookEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub rattler_build_version: &'static str,
+    pub started: DateTime<Utc>,
+    pub finished: DateTime<Utc>,
+    pub packages: Vec<PackageReport>,
+}
+
+impl BuildReport {
+    /// The report of the build of `outputs` that started at `started` and ended with `result`
+    pub fn new(outputs: &[Output], result: &miette::Result<()>, started: DateTime<Utc>) -> Self {
+        let packages = outputs.iter().map(PackageReport::from).collect::<Vec<_>>();
+        let failed = packages
+            .iter()
+            .any(|package| package.status == PackageStatus::Failed);
+        let status = if result.is_err() || failed {
+            HookEvent::Failure
+        } else {
+            HookEvent::Success
+        };
+        Self {
+            text: summary_text(status, &packages),
+            status,
+            error: result.as_ref().err().map(|e| e.to_string()),
+            rattler_build_version: get_rattler_build_version(),
+            started,
+            finished: Utc::now(),
+            packages,
+        }
+    }
+}
+
+/// A one-line summary of the build, e.g.
+/// `rattler-build: build failed: built foo-1.0-h123_0; failed bar-1.0-h123_0`
+fn summary_text(status: HookEvent, packages: &[PackageReport]) -> String {
+    let list = |wanted: PackageStatus| {
+        packages
+            .iter()
+            .filter(|package| package.status == wanted)
+            .map(|package| {
+                format!(
+                    "{}-{}-{}",
+                    package.name, package.version, package.build_string
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut parts = Vec::new();
+    for (verb, status) in [
+        ("built", PackageStatus::Built),
+        ("failed", PackageStatus::Failed),
+    ] {
+        let packages = list(status);
+        if !packages.is_empty() {
+            parts.push(format!("{verb} {}", packages.join(", ")));
+        }
+    }
+    let outcome = match status {
+        HookEvent::Success => "build succeeded",
+        HookEvent::Failure => "build failed",
+    };
+    if parts.is_empty() {
+        format!("rattler-build: {outcome}")
+    } else {
+        format!("rattler-build: {outcome}: {}", parts.join("; "))
+    }
+}
+
+/// Run a command hook with the report on stdin
+fn run_command(command: &str, report: &BuildReport, payload: &[u8]) -> std::io::Result<()> {
+    let mut child = if cfg!(windows) {
+        std::process::Command::new("cmd")
+            .args(["/C", command])
+            .stdin(Stdio::piped())
+            .env("RATTLER_BUILD_STATUS", status_name(report.status))
+            .spawn()?
+    } else {
+        std::process::Command::new("sh")
+            .args(["-c", command])
+            .stdin(Stdio::piped())
+            .env("RATTLER_BUILD_STATUS", status_name(report.status))
+            .spawn()?
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command does not have to read the report
+        let _ = stdin.write_all(payload);
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("exited with {status}")));
+    }
+    Ok(())
+}
+
+fn status_name(status: HookEvent) -> &'static str {
+    match status {
+        HookEvent::Success => "success",
+        HookEvent::Failure => "failure",
+    }
+}
+
+/// Fire the hooks that are configured for the status of the build of `outputs`
+pub(crate) async fn fire(
+    hooks: &[Hook],
+    outputs: &[Output],
+    result: &miette::Result<()>,
+    started: DateTime<Utc>,
+    client: &rattler_build_networking::BaseClient,
+) {
+    if hooks.is_empty() {
+        return;
+    }
+    let report = BuildReport::new(outputs, result, started);
+    let payload = match serde_json::to_vec(&report) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::warn!("Failed to serialize the build report for the hooks: {}", e);
+            return;
+        }
+    };
+
+    for hook in hooks.iter().filter(|hook| hook.on.contains(&report.status)) {
+        if let Some(url) = &hook.webhook {
+            tracing::info!(
+                "Sending the build report to {}",
+                url.host_str().unwrap_or("")
+            );
+            let response = client
+                .for_host(url)
+                .post(url.clone())
+                .json(&report)
+                .send()
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|response| {
+                    response
+                        .error_for_status()
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(e) = response {
+                tracing::warn!("The webhook failed: {}", e);
+            }
+        }
+        if let Some(command) = &hook.command {
+            tracing::info!("Running the hook `{}`", command);
+            if let Err(e) = run_command(command, &report, &payload) {
+                tracing::warn!("The hook `{}` failed: {}", command, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, status: PackageStatus) -> PackageReport {
+        PackageReport {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+            build_string: "h123_0".to_string(),
+            subdir: "linux-64".to_string(),
+            status,
+            artifact: None,
+            size: None,
+            sha256: None,
+            duration_seconds: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_text() {
+        let packages = [
+            package("foo", PackageStatus::Built),
+            package("bar", PackageStatus::Failed),
+            package("baz", PackageStatus::NotBuilt),
+        ];
+        assert_eq!(
+            summary_text(HookEvent::Failure, &packages),
+            "rattler-build: build failed: built foo-1.0-h123_0; failed bar-1.0-h123_0"
+        );
+        assert_eq!(
+            summary_text(HookEvent::Success, &[]),
+            "rattler-build: build succeeded"
+        );
+    }
+
+    #[test]
+    fn test_hook_from_toml() {
+        #[derive(Deserialize)]
+        struct Hooks {
+            hooks: Vec<Hook>,
+        }
+        let hooks: Hooks = toml::from_str(
+            r#"
+[[hooks]]
+webhook = "https://hooks.slack.com/services/T000/B000/XXX"
+on = ["failure"]
+
+[[hooks]]
+command = "notify-send rattler-build \"$RATTLER_BUILD_STATUS\""
+"#,
+        )
+        .unwrap();
+        assert_eq!(hooks.hooks[0].on, vec![HookEvent::Failure]);
+        assert_eq!(hooks.hooks[1].on, HookEvent::all());
+        assert!(hooks.hooks.iter().all(|hook| hook.validate().is_ok()));
+        assert!(
+            Hook {
+                webhook: None,
+                command: None,
+                on: HookEvent::all()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Index a local or S3 channel (`rattler-build index`) so that the packages in it can be
+//! installed, e.g. after copying packages into the channel with another tool.
+use std::path::{Path, PathBuf};
+
+use miette::IntoDiagnostic;
+use rattler_conda_types::Platform;
+use rattler_index::{IndexFsConfig, ensure_channel_initialized_fs, index_fs};
+use url::Url;
+
+/// A channel that can be indexed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum IndexTarget {
+    /// A channel in a local directory
+    Local(PathBuf),
+    /// A channel in an S3 bucket (`s3://bucket/channel`)
+    S3(Url),
+}
+
+impl IndexTarget {
+    /// Parse a local path, a `file://` URL or an `s3://` URL
+    pub fn parse(channel: &str) -> miette::Result<Self> {
+        match Url::parse(channel) {
+            Ok(url) if url.scheme() == "s3" => Ok(Self::S3(url)),
+            Ok(url) if url.scheme() == "file" => url
+                .to_file_path()
+                .map(Self::Local)
+                .map_err(|_| miette::miette!("Invalid file URL: {}", url)),
+            // a single letter is a Windows drive (e.g. `C:\channel`)
+            Ok(url) if url.scheme().len() > 1 => Err(miette::miette!(
+                "Only local channels and S3 channels (s3://bucket/channel) can be indexed, not `{}`",
+                channel
+            )),
+            _ => Ok(Self::Local(PathBuf::from(channel))),
+        }
+    }
+}
+
+/// The platforms of the subdirs that `package_paths` belong to
+pub(crate) fn package_platforms(package_paths: &[PathBuf]) -> miette::Result<Vec<Platform>> {
+    let mut platforms = Vec::new();
+    for package_path in package_paths {
+        let subdir = crate::publish::determine_package_subdir(package_path)?;
+        let platform = subdir
+            .parse::<Platform>()
+            .map_err(|e| miette::miette!("Invalid platform subdir '{}': {}", subdir, e))?;
+        if !platforms.contains(&platform) {
+            platforms.push(platform);
+        }
+    }
+    Ok(platforms)
+}
+
+/// The platforms of the subdirs in the local channel at `channel`
+fn local_platforms(channel: &Path) -> miette::Result<Vec<Platform>> {
+    let mut platforms = Vec::new();
+    for entry in fs_err::read_dir(channel).into_diagnostic()? {
+        let entry = entry.into_diagnostic()?;
+        if entry.file_type().into_diagnostic()?.is_dir()
+            && let Some(platform) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse::<Platform>().ok())
+        {
+            platforms.push(platform);
+        }
+    }
+    platforms.sort();
+    Ok(platforms)
+}
+
+/// Index the `platforms` of the local channel at `channel` (all subdirs of the channel if
+/// `platforms` is empty), and write the `run_exports.json` of each subdir.
+pub(crate) async fn index_local_channel(
+    channel: &Path,
+    platforms: &[Platform],
+    force: bool,
+) -> miette::Result<()> {
+    tracing::info!("Indexing local channel at {}", channel.display());
+
+    // Ensure channel is initialized with noarch/repodata.json
+    ensure_channel_initialized_fs(channel)
+        .await
+        .map_err(|e| miette::miette!("Failed to initialize local channel: {}", e))?;
+
+    let platforms = if platforms.is_empty() {
+        local_platforms(channel)?
+    } else {
+        platforms.to_vec()
+    };
+
+    for target_platform in platforms {
+        let index_config = IndexFsConfig {
+            channel: channel.to_path_buf(),
+            target_platform: Some(target_platform),
+            repodata_patch: None,
+            write_zst: true,
+            write_shards: true,
+            force,
+            max_parallel: num_cpus::get_physical(),
+            multi_progress: None,
+        };
+
+        index_fs(index_config)
+            .await
+            .map_err(|e| miette::miette!("Failed to index channel: {}", e))?;
+
+        crate::types::write_run_exports_index(channel, target_platform)
+            .map_err(|e| miette::miette!("Failed to write run_exports.json: {}", e))?;
+    }
+
+    tracing::info!("Successfully indexed local channel");
+    Ok(())
+}
+
+/// Index the `platforms` of the S3 channel at `channel` (all subdirs of the channel if
+/// `platforms` is empty).
+#[cfg(feature = "s3")]
+pub(crate) async fn index_s3_channel(
+    channel: &Url,
+    credentials: rattler_s3::ResolvedS3Credentials,
+    platforms: &[Platform],
+    force: bool,
+) -> miette::Result<()> {
+    use rattler_index::{IndexS3Config, ensure_channel_initialized_s3, index_s3};
+
+    // Ensure channel is initialized with noarch/repodata.json
+    ensure_channel_initialized_s3(channel, &credentials)
+        .await
+        .map_err(|e| miette::miette!("Failed to initialize S3 channel: {}", e))?;
+
+    let platforms = if platforms.is_empty() {
+        vec![None]
+    } else {
+        platforms.iter().copied().map(Some).collect()
+    };
+
+    for target_platform in platforms {
+        match target_platform {
+            Some(platform) => tracing::info!("Indexing S3 channel at {} / {}", channel, platform),
+            None => tracing::info!("Indexing S3 channel at {}", channel),
+        }
+
+        let index_config = IndexS3Config {
+            channel: channel.clone(),
+            credentials: credentials.clone(),
+            target_platform,
+            repodata_patch: None,
+            write_zst: true,
+            write_shards: true,
+            force,
+            max_parallel: num_cpus::get_physical(),
+            multi_progress: None,
+            precondition_checks: rattler_index::PreconditionChecks::Enabled,
+        };
+
+        index_s3(index_config)
+            .await
+            .map_err(|e| miette::miette!("Failed to index S3 channel: {}", e))?;
+    }
+
+    tracing::info!("Successfully indexed S3 channel");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_index_target() {
+        assert_eq!(
+            IndexTarget::parse("./output").unwrap(),
+            IndexTarget::Local(PathBuf::from("./output"))
+        );
+        assert_eq!(
+            IndexTarget::parse("s3://bucket/channel").unwrap(),
+            IndexTarget::S3(Url::parse("s3://bucket/channel").unwrap())
+        );
+        assert!(IndexTarget::parse("https://prefix.dev/channel").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_index_local_channel() {
+        let channel = tempfile::tempdir().unwrap();
+        fs_err::create_dir_all(channel.path().join("linux-64")).unwrap();
+        fs_err::create_dir_all(channel.path().join("not-a-platform")).unwrap();
+
+        index_local_channel(channel.path(), &[], false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            local_platforms(channel.path()).unwrap(),
+            vec![Platform::Linux64, Platform::NoArch]
+        );
+        assert!(channel.path().join("linux-64/repodata.json").exists());
+        assert!(channel.path().join("noarch/repodata.json").exists());
+    }
+}
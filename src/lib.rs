@@ -2,11 +2,16 @@
 
 //! rattler-build library.
 
+pub mod audit;
 pub mod build;
+pub mod build_backend;
 pub mod bump_recipe;
+pub mod conda_forge_pinning;
 // pub mod cache;
 // pub mod conda_build_config;
+pub mod config;
 pub mod console_utils;
+pub mod events;
 pub mod metadata;
 pub mod migrate_recipe;
 pub mod opt;
@@ -14,6 +19,7 @@ pub mod package_test;
 pub mod packaging;
 pub mod render;
 pub mod script;
+pub mod session;
 pub mod source;
 pub mod staging;
 pub mod system_tools;
@@ -27,21 +33,44 @@ pub mod tui;
 pub mod types;
 pub mod utils;
 
+mod build_log;
+mod build_plan;
+mod build_queue;
+mod build_state;
+mod clean;
 mod consts;
+pub mod emulation;
 pub mod env_vars;
+pub mod error_code;
+mod explain_rebuild;
+mod generate_ci;
+mod graph;
+mod hermetic;
+pub mod hooks;
+mod index;
+pub mod license_policy;
 mod linux;
+pub mod lsp;
 mod macos;
+mod outputs;
 mod package_info;
+mod pinning;
 mod post_process;
 pub mod publish;
 pub mod rebuild;
+pub mod remote_cache;
+mod render_cache;
+pub mod slsa;
+mod timings;
 mod unix;
+mod upload;
+mod watch;
 mod windows;
 
 mod package_cache_reporter;
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Path, PathBuf},
     process::Command,
     str::FromStr,
@@ -52,16 +81,17 @@ use build::{WorkingDirectoryBehavior, run_build, skip_existing};
 use console_utils::LoggingOutputHandler;
 use dunce::canonicalize;
 use fs_err as fs;
-use futures::FutureExt;
+use futures::{FutureExt, StreamExt};
 use miette::{Context, IntoDiagnostic};
 use opt::*;
-use package_test::TestConfiguration;
+use package_test::{TestConfiguration, TestReport};
 use rattler_build_recipe::{
     stage0,
     stage1::{Recipe, TestType},
     variant_render::RenderConfig,
 };
 use rattler_build_variant_config::VariantConfig;
+use tracing::{Instrument, instrument::WithSubscriber};
 
 // Re-export types needed by Python bindings and external consumers
 pub use rattler_build_jinja::Variable;
@@ -69,8 +99,10 @@ pub use rattler_build_recipe::stage1::build::BuildString;
 pub use rattler_build_recipe::stage1::{HashInfo, HashInput};
 pub use rattler_build_types::NormalizedKey;
 use rattler_conda_types::{
-    MatchSpec, NamedChannelOrUrl, PackageName, Platform, compression_level::CompressionLevel,
-    package::CondaArchiveType,
+    Arch, Channel, ChannelConfig, ChannelUrl, MatchSpec, Matches, NamedChannelOrUrl, PackageName,
+    Platform,
+    compression_level::CompressionLevel,
+    package::{CondaArchiveType, IndexJson},
 };
 use rattler_config::config::build::PackageFormatAndCompression;
 use rattler_index::ensure_channel_initialized_fs;
@@ -78,16 +110,19 @@ use rattler_index::ensure_channel_initialized_fs;
 use rattler_index::ensure_channel_initialized_s3;
 use rattler_solve::SolveStrategy;
 use rattler_virtual_packages::VirtualPackageOverrides;
+use remote_cache::RemoteCache;
 use render::resolved_dependencies::RunExportsDownload;
+use render_cache::RenderCache;
 use source::patch::apply_patch_custom;
 use system_tools::SystemTools;
 use tool_configuration::{Configuration, ContinueOnFailure, SkipExisting, TestStrategy};
 use types::Directories;
 use types::{
-    BuildConfiguration, BuildSummary, PackageIdentifier, PackagingSettings,
+    BuildConfiguration, BuildPhase, BuildSummary, PackageIdentifier, PackagingSettings,
     build_reindexed_channels,
 };
 
+use crate::error_code::{ErrorCode, WithErrorCode};
 use crate::metadata::{Debug, Output, PlatformWithVirtualPackages};
 use crate::publish::{
     BuildNumberOverride, apply_build_number_override, fetch_highest_build_numbers,
@@ -98,7 +133,7 @@ use rattler_conda_types::NoArchType;
 
 /// A discovered output from variant expansion
 #[allow(missing_docs)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DiscoveredOutput {
     pub name: String,
     pub version: String,
@@ -286,12 +321,50 @@ pub fn get_tool_config(
     )
     .into_diagnostic()?;
 
+    let channel_config = ChannelConfig::default_with_root_dir(
+        std::env::current_dir().unwrap_or_else(|_err| PathBuf::from("/")),
+    );
+    let test_channels = build_data
+        .test_channels
+        .iter()
+        .map(|c| c.clone().into_base_url(&channel_config))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+
     let configuration_builder = Configuration::builder()
+        .with_opt_cache_dir(build_data.common.cache_dir.clone())
         .with_keep_build(build_data.keep_build)
+        .with_resume(build_data.resume)
         .with_compression_threads(build_data.compression_threads)
         .with_reqwest_client(client)
         .with_test_strategy(build_data.test)
+        .with_build_jobs(build_data.build_jobs)
+        .with_test_jobs(build_data.test_jobs)
+        .with_test_channels(test_channels)
+        .with_test_extra_specs(build_data.test_extra_specs.clone())
+        .with_test_python_versions(build_data.test_python_versions.clone())
+        .with_test_container(build_data.test_container.clone())
+        .with_auth_file(build_data.common.auth_file.clone())
+        .with_test_report(TestReport::from_paths(
+            build_data.test_report_junit.clone(),
+            build_data.test_report_json.clone(),
+        ))
         .with_skip_existing(build_data.skip_existing)
+        .with_remote_cache(build_data.remote_cache.clone().map(|url| {
+            RemoteCache::new(
+                url,
+                build_data.remote_cache_read_only,
+                #[cfg(feature = "s3")]
+                build_data.common.auth_file.clone(),
+                #[cfg(feature = "s3")]
+                build_data.common.s3_config.clone(),
+            )
+        }))
+        .with_slsa_provenance(build_data.slsa_provenance)
+        .with_audit(build_data.audit.clone())
+        .with_license_policy(build_data.license_policy.clone())
+        .with_post_processors(build_data.post_processors.clone())
+        .with_require_pinned(build_data.require_pinned)
         .with_continue_on_failure(build_data.continue_on_failure)
         .with_noarch_build_platform(build_data.noarch_build_platform)
         .with_channel_priority(build_data.common.channel_priority)
@@ -299,6 +372,8 @@ pub fn get_tool_config(
         .with_error_prefix_in_binary(build_data.error_prefix_in_binary)
         .with_allow_symlinks_on_windows(build_data.allow_symlinks_on_windows)
         .with_allow_absolute_license_paths(build_data.allow_absolute_license_paths)
+        .with_codesign_identity(build_data.codesign_identity.clone())
+        .with_notarize_keychain_profile(build_data.notarize_keychain_profile.clone())
         .with_io_concurrency_limit(Some(build_data.io_concurrency_limit))
         .with_zstd_repodata_enabled(build_data.common.use_zstd)
         .with_bz2_repodata_enabled(build_data.common.use_bz2)
@@ -313,50 +388,32 @@ pub fn get_tool_config(
     Ok(configuration_builder.finish())
 }
 
-/// Returns the output for the build.
-pub async fn get_build_output(
+/// Load the variant configuration of a recipe: the global pinning, the variant file next to
+/// the recipe, the `--variant-config` files and the `--variant` overrides
+pub(crate) async fn load_variant_config(
     build_data: &BuildData,
     recipe_path: &Path,
     tool_config: &Configuration,
-) -> miette::Result<Vec<Output>> {
-    let mut output_dir = build_data.common.output_dir.clone();
-    if output_dir.exists() {
-        output_dir = canonicalize(&output_dir).into_diagnostic()?;
-    }
-
-    if build_data.target_platform == Platform::NoArch
-        || build_data.build_platform == Platform::NoArch
-    {
-        return Err(miette::miette!(
-            "target-platform / build-platform cannot be `noarch` - that should be defined in the recipe"
-        ));
-    }
-
-    tracing::debug!(
-        "Platforms: build: {}, host: {}, target: {}",
-        build_data.build_platform,
-        build_data.host_platform,
-        build_data.target_platform
-    );
-
-    let span = tracing::info_span!("Finding outputs from recipe");
-    let enter = span.enter();
-
-    // Read the recipe content
-    let recipe_content = fs::read_to_string(recipe_path).into_diagnostic()?;
+) -> miette::Result<VariantConfig> {
+    let variant_configs = variant_config_files(build_data, recipe_path, tool_config).await?;
+    parse_variant_config(build_data, &variant_configs)
+}
 
-    // Detect deprecated `cache:` key and give a helpful error
-    if migrate_recipe::has_cache_key(&recipe_content) {
-        return Err(miette::miette!(
-            "this recipe uses the deprecated top-level 'cache:' key. \
-             The 'cache' format has been replaced by 'staging' outputs. \
-             To automatically migrate your recipe, run:\n\n\
-             rattler-build migrate-recipe --recipe {}\n\n\
-             For more information, see: https://rattler-build.prefix.dev/latest/multiple_output_cache/",
-            recipe_path.display()
-        ));
+/// The cache directory of the build (`cache-dir` in the configuration)
+fn cache_dir(build_data: &BuildData) -> miette::Result<PathBuf> {
+    match &build_data.common.cache_dir {
+        Some(cache_dir) => Ok(cache_dir.clone()),
+        None => rattler_cache::default_cache_dir()
+            .map_err(|e| miette::miette!("Failed to determine the cache directory: {e}")),
     }
+}
 
+/// The variant configuration files of a recipe, the one with the lowest priority first
+async fn variant_config_files(
+    build_data: &BuildData,
+    recipe_path: &Path,
+    tool_config: &Configuration,
+) -> miette::Result<Vec<PathBuf>> {
     // Check if there is a `variants.yaml` or `conda_build_config.yaml` file next to
     // the recipe that we should potentially use.
     let mut detected_variant_config = None;
@@ -388,8 +445,22 @@ pub async fn get_build_output(
     let mut variant_configs = detected_variant_config.unwrap_or_default();
     variant_configs.extend(build_data.variant_config.clone());
 
-    let mut variant_config =
-        VariantConfig::from_files(&variant_configs, build_data.target_platform).map_err(|e| {
+    // The global pinning comes first, so that all other variant configs take priority over it
+    if let Some(pinning) = &build_data.pinning {
+        let cache_dir = cache_dir(build_data)?;
+        variant_configs.insert(0, pinning.fetch(&tool_config.client, &cache_dir).await?);
+    }
+
+    Ok(variant_configs)
+}
+
+/// Parse the variant configuration files and apply the platforms and the `--variant` overrides
+fn parse_variant_config(
+    build_data: &BuildData,
+    variant_configs: &[PathBuf],
+) -> miette::Result<VariantConfig> {
+    let mut variant_config = VariantConfig::from_files(variant_configs, build_data.target_platform)
+        .map_err(|e| {
             // Check if this is a ParseError with a file path
             if let rattler_build_variant_config::VariantConfigError::ParseError { path, source } =
                 &e
@@ -443,18 +514,92 @@ pub async fn get_build_output(
         variant_config.variants.insert(normalized_key, variables);
     }
 
+    Ok(variant_config)
+}
+
+/// Returns the output for the build.
+pub async fn get_build_output(
+    build_data: &BuildData,
+    recipe_path: &Path,
+    tool_config: &Configuration,
+) -> miette::Result<Vec<Output>> {
+    let mut output_dir = build_data.common.output_dir.clone();
+    if output_dir.exists() {
+        output_dir = canonicalize(&output_dir).into_diagnostic()?;
+    }
+
+    if build_data.target_platform == Platform::NoArch
+        || build_data.build_platform == Platform::NoArch
+    {
+        return Err(miette::miette!(
+            "target-platform / build-platform cannot be `noarch` - that should be defined in the recipe"
+        ));
+    }
+
+    tracing::debug!(
+        "Platforms: build: {}, host: {}, target: {}",
+        build_data.build_platform,
+        build_data.host_platform,
+        build_data.target_platform
+    );
+
+    let span = tracing::info_span!("Finding outputs from recipe");
+    let enter = span.enter();
+
+    // Read the recipe content
+    let recipe_content = fs::read_to_string(recipe_path).into_diagnostic()?;
+
+    // Detect deprecated `cache:` key and give a helpful error
+    if migrate_recipe::has_cache_key(&recipe_content) {
+        return Err(miette::miette!(
+            "this recipe uses the deprecated top-level 'cache:' key. \
+             The 'cache' format has been replaced by 'staging' outputs. \
+             To automatically migrate your recipe, run:\n\n\
+             rattler-build migrate-recipe --recipe {}\n\n\
+             For more information, see: https://rattler-build.prefix.dev/latest/multiple_output_cache/",
+            recipe_path.display()
+        ));
+    }
+
+    let variant_configs = variant_config_files(build_data, recipe_path, tool_config).await?;
+
+    let render_cache = if build_data.render_cache
+        && let Some(key) =
+            render_cache::cache_key(build_data, recipe_path, &recipe_content, &variant_configs)?
+    {
+        Some((RenderCache::new(&cache_dir(build_data)?), key))
+    } else {
+        None
+    };
+    let cached = render_cache
+        .as_ref()
+        .and_then(|(cache, key)| cache.load(key));
+
     let FoundVariants {
         outputs: outputs_and_variants,
         recipe_name,
-    } = find_variants(
-        &variant_config,
-        recipe_path,
-        &recipe_content,
-        build_data.target_platform,
-        build_data.build_platform,
-        build_data.host_platform,
-        build_data.common.experimental,
-    )?;
+    } = match cached {
+        Some(found) => {
+            tracing::info!("Using the cached rendered recipe");
+            found
+        }
+        None => {
+            let variant_config = parse_variant_config(build_data, &variant_configs)?;
+            let found = find_variants(
+                &variant_config,
+                recipe_path,
+                &recipe_content,
+                build_data.target_platform,
+                build_data.build_platform,
+                build_data.host_platform,
+                build_data.common.experimental,
+            )?;
+            if let Some((cache, key)) = &render_cache {
+                cache.store(key, &found);
+            }
+            found
+        }
+    };
 
     tracing::info!("Found {} variants\n", outputs_and_variants.len());
     for discovered_output in &outputs_and_variants {
@@ -521,8 +666,15 @@ pub async fn get_build_output(
         // Use the global build name for outputs that inherit from staging caches
         // This ensures staging caches and their dependent packages share the same build directory
         // Otherwise, use the output's own name for the build directory
+        // Concurrent builds of the variants of a package need separate build directories
         let build_name = if recipe.inherits_from.is_some() {
             global_build_name.clone()
+        } else if build_data.build_jobs > 1 {
+            format!(
+                "{}_{}",
+                recipe.package().name().as_normalized(),
+                discovered_output.hash.hash
+            )
         } else {
             recipe.package().name().as_normalized().to_string()
         };
@@ -566,13 +718,24 @@ pub async fn get_build_output(
             .collect::<Result<Vec<_>, _>>()
             .into_diagnostic()?;
 
+        // The executables of a WebAssembly host environment (e.g. the python interpreter) cannot
+        // run on the build machine, so noarch outputs use a host environment for the build
+        // platform instead
+        let host_platform = if discovered_output.target_platform == Platform::NoArch
+            && build_data.host_platform.arch() == Some(Arch::Wasm32)
+        {
+            build_data.build_platform
+        } else {
+            build_data.host_platform
+        };
+
         let virtual_package_override = VirtualPackageOverrides::from_env();
         let output = Output {
             recipe: discovered_output.recipe.clone(),
             build_configuration: BuildConfiguration {
                 target_platform: discovered_output.target_platform,
                 host_platform: PlatformWithVirtualPackages::detect_for_platform(
-                    build_data.host_platform,
+                    host_platform,
                     &virtual_package_override,
                 )
                 .into_diagnostic()?,
@@ -591,6 +754,13 @@ pub async fn get_build_output(
                 )
                 .no_build_id(build_data.no_build_id)
                 .merge_build_and_host(recipe.build().merge_build_and_host_envs)
+                .placeholder_length(
+                    recipe
+                        .build()
+                        .prefix_detection
+                        .placeholder_length
+                        .map(|length| length as usize),
+                )
                 .skip_directory_creation(build_data.render_only)
                 .build()
                 .into_diagnostic()?,
@@ -605,6 +775,9 @@ pub async fn get_build_output(
                 ),
                 store_recipe: !build_data.no_include_recipe,
                 force_colors: build_data.color_build_log && console::colors_enabled(),
+                conda_build_env_vars: build_data.conda_build_env_vars,
+                zig_cc: build_data.zig_cc,
+                report_dependency_usage: build_data.report_dependency_usage,
                 sandbox_config: build_data.sandbox_configuration.clone(),
                 debug: build_data.debug,
                 exclude_newer: build_data.exclude_newer,
@@ -732,168 +905,404 @@ fn can_test(output: &Output, all_output_names: &[&PackageName], done_outputs: &[
     true
 }
 
-/// Runs build.
-pub async fn run_build_from_args(
-    build_output: Vec<Output>,
-    tool_configuration: Configuration,
-    markdown_summary: Option<&Path>,
+/// The test configuration for an output that was just built
+fn output_test_configuration(
+    output: &Output,
+    tool_configuration: &Configuration,
+    channels: Vec<ChannelUrl>,
+) -> TestConfiguration {
+    TestConfiguration {
+        test_prefix: output
+            .build_configuration
+            .directories
+            .output_dir
+            .join("test"),
+        target_platform: Some(output.build_configuration.target_platform),
+        host_platform: Some(output.build_configuration.host_platform.clone()),
+        current_platform: output.build_configuration.build_platform.clone(),
+        keep_test_prefix: tool_configuration.no_clean,
+        channels,
+        channel_priority: tool_configuration.channel_priority,
+        solve_strategy: SolveStrategy::Highest,
+        tool_configuration: tool_configuration.clone(),
+        test_index: None,
+        output_dir: output.build_configuration.directories.output_dir.clone(),
+        debug: output.build_configuration.debug,
+        exclude_newer: output.build_configuration.exclude_newer,
+    }
+}
+
+/// Move the package of a failed test to the `broken` directory, and either record the failure
+/// (with `--continue-on-failure`) or return it as error.
+async fn handle_test_failure(
+    output: &Output,
+    archive: &Path,
+    error: package_test::TestError,
+    tool_configuration: &Configuration,
 ) -> miette::Result<()> {
-    let mut outputs = Vec::new();
-    let mut test_queue = Vec::new();
-    let outputs_to_build = skip_existing(build_output, &tool_configuration).await?;
+    // move the package file to the failed directory
+    let failed_dir = output
+        .build_configuration
+        .directories
+        .output_dir
+        .join("broken");
+    fs::create_dir_all(&failed_dir).into_diagnostic()?;
+    fs::rename(archive, failed_dir.join(archive.file_name().unwrap())).into_diagnostic()?;
+
+    // Reindex the output directory so that the broken package is no longer
+    // listed in the repodata. This is important for --skip-existing to work
+    // correctly on subsequent builds.
+    if let Err(e) = build_reindexed_channels(&output.build_configuration, tool_configuration).await
+    {
+        tracing::warn!(
+            "Failed to reindex output directory after moving package to broken folder: {}",
+            e
+        );
+    }
 
-    let all_output_names = outputs_to_build
-        .iter()
-        .map(|o| o.name())
-        .collect::<Vec<_>>();
-    tracing::info!("Starting build of {} outputs", outputs_to_build.len());
-    for (index, output) in outputs_to_build.iter().enumerate() {
-        let (output, archive) = match run_build(
-            output.clone(),
-            &tool_configuration,
-            WorkingDirectoryBehavior::Cleanup,
-        )
-        .boxed_local()
-        .await
+    if let Err(e) = build_log::record(output, build_log::BuildStatus::Failed, None) {
+        tracing::warn!("Failed to update the index of the build logs: {}", e);
+    }
+
+    if tool_configuration.continue_on_failure == ContinueOnFailure::Yes {
+        tracing::error!("Test failed for {}: {}", output.identifier(), error);
+        output.record_warning(&format!("Test failed: {}", error));
+        Ok(())
+    } else {
+        Err(miette::miette!("Test failed: {}", error)).with_error_code(ErrorCode::TEST)
+    }
+}
+
+/// Test the given packages. With `test_jobs > 1`, up to `test_jobs` tests run at the same time
+/// and the log of each test is captured and printed as one block when the test has finished.
+async fn run_tests(
+    to_test: Vec<(Output, PathBuf)>,
+    tool_configuration: &Configuration,
+) -> miette::Result<()> {
+    // Reindex the output channels up front, the tests only read from them
+    let mut tests = Vec::with_capacity(to_test.len());
+    for (output, archive) in to_test {
+        let mut channels =
+            build_reindexed_channels(&output.build_configuration, tool_configuration)
+                .await
+                .into_diagnostic()
+                .context("failed to reindex output channel")?;
+        // the test channels come right after the local output channel
+        channels.splice(1..1, tool_configuration.test_channels.iter().cloned());
+        let test_configuration = output_test_configuration(&output, tool_configuration, channels);
+        tests.push((output, archive, test_configuration));
+    }
+
+    if tool_configuration.test_jobs <= 1 {
+        for (output, archive, test_configuration) in tests {
+            let span = tracing::info_span!(
+                "Running tests for",
+                package = %output.identifier(),
+                span_color = output.identifier(),
+                build_log = %build_log::log_path(&output).display()
+            );
+            let start = chrono::Utc::now();
+            let result = package_test::run_test(&archive, &test_configuration, None)
+                .instrument(span)
+                .await;
+            output.record_phase(BuildPhase::Test, start);
+            if let Err(e) = result {
+                handle_test_failure(&output, &archive, e, tool_configuration).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Testing {} packages with up to {} concurrent jobs",
+        tests.len(),
+        tool_configuration.test_jobs
+    );
+    let results = futures::stream::iter(tests)
+        .map(|(output, archive, test_configuration)| async move {
+            let capture = console_utils::LogCapture::default();
+            let start = chrono::Utc::now();
+            let result = package_test::run_test(&archive, &test_configuration, None)
+                .with_subscriber(capture.subscriber())
+                .await;
+            output.record_phase(BuildPhase::Test, start);
+
+            let span = tracing::info_span!(
+                "Test log of",
+                package = %output.identifier(),
+                span_color = output.identifier(),
+                build_log = %build_log::log_path(&output).display()
+            );
+            let _enter = span.enter();
+            tracing::info!("{}", capture.output());
+            (output, archive, result)
+        })
+        .buffer_unordered(tool_configuration.test_jobs)
+        .collect::<Vec<_>>()
+        .await;
+
+    // Handle the failures only after all tests have finished, moving packages while other tests
+    // solve against the output channel would be racy
+    for (output, archive, result) in results {
+        if let Err(e) = result {
+            handle_test_failure(&output, &archive, e, tool_configuration).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Why the tests of an output are not run
+enum SkippedTests {
+    /// The tests are disabled (`--test=skip`)
+    Disabled,
+    /// The package is cross-compiled and cannot (or, with `--test=native`, should not) run on
+    /// the build machine. The tests are deferred to `rattler-build test --deferred`.
+    CrossCompiled(String),
+}
+
+impl std::fmt::Display for SkippedTests {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkippedTests::Disabled => write!(f, "the argument --test=skip was set"),
+            SkippedTests::CrossCompiled(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+/// Why the tests of `output` are skipped, if they are
+fn skip_test_reason(output: &Output, tool_configuration: &Configuration) -> Option<SkippedTests> {
+    let configuration = &output.build_configuration;
+    let host_platform = configuration.host_platform.platform;
+    let build_platform = configuration.build_platform.platform;
+    match tool_configuration.test_strategy {
+        TestStrategy::Skip => Some(SkippedTests::Disabled),
+        // Skip if `host_platform != build_platform` and `target_platform != noarch`
+        TestStrategy::Native
+            if configuration.target_platform != Platform::NoArch
+                && host_platform != build_platform =>
+        {
+            Some(SkippedTests::CrossCompiled(format!(
+                "the argument --test=native was set and the build is a cross-compilation (target_platform={}, build_platform={}, host_platform={})",
+                configuration.target_platform, build_platform, host_platform
+            )))
+        }
+        // Skip if the packages cannot be run here, not even with an emulator
+        TestStrategy::NativeAndEmulated
+            if configuration.target_platform != Platform::NoArch
+                && !emulation::can_run(host_platform) =>
         {
+            Some(SkippedTests::CrossCompiled(format!(
+                "the build is a cross-compilation and the packages for {} cannot be run on {}",
+                host_platform,
+                Platform::current()
+            )))
+        }
+        TestStrategy::Native | TestStrategy::NativeAndEmulated => None,
+    }
+}
+
+/// Record the tests of a package that are not run in the test report, and add cross-compiled
+/// packages to the deferred tests of the output directory
+fn record_skipped_tests(
+    output: &Output,
+    archive: &Path,
+    skipped: &SkippedTests,
+    tool_configuration: &Configuration,
+) {
+    let SkippedTests::CrossCompiled(reason) = skipped else {
+        return;
+    };
+    let tests = output.recipe.tests();
+    if tests.is_empty() {
+        return;
+    }
+
+    if let Some(report) = &tool_configuration.test_report {
+        let mut suite = package_test::TestSuiteReport::new(archive);
+        for (index, test) in tests.iter().enumerate() {
+            suite.skip(
+                package_test::test_name(test, index),
+                format!("skipped: cross-compiled ({reason})"),
+            );
+        }
+        report.add_suite(suite);
+    }
+
+    let output_dir = &output.build_configuration.directories.output_dir;
+    if let Err(e) = package_test::defer_tests(output_dir, archive, reason) {
+        tracing::warn!("Failed to record the deferred tests: {}", e);
+    }
+}
+
+/// Build the outputs concurrently (`--build-jobs`) and queue the tests of the built packages,
+/// which run after all builds have finished
+async fn build_outputs_concurrently(
+    outputs_to_build: &[Output],
+    tool_configuration: &Configuration,
+    outputs: &mut Vec<Output>,
+    test_queue: &mut Vec<(Output, PathBuf)>,
+) -> miette::Result<()> {
+    let mut first_error = None;
+    for (index, result) in
+        build_queue::build_concurrently(outputs_to_build, tool_configuration).await?
+    {
+        let (output, archive) = match result {
             Ok((output, archive)) => {
                 output.record_build_end();
                 (output, archive)
             }
             Err(e) => {
+                let output = &outputs_to_build[index];
+                output.record_failure();
                 if tool_configuration.continue_on_failure == ContinueOnFailure::Yes {
-                    tracing::error!("Build failed for {}: {}", output.identifier(), e);
                     output.record_warning(&format!("Build failed: {}", e));
-                    continue;
+                } else {
+                    first_error.get_or_insert(e);
                 }
-                return Err(e);
+                continue;
             }
         };
 
         outputs.push(output.clone());
-
-        // We can now run the tests for the output. However, we need to check if
-        // all dependencies that are needed for the test are already built.
-
-        // Decide whether the tests should be skipped or not
-        let (skip_test, skip_test_reason) = match tool_configuration.test_strategy {
-            TestStrategy::Skip => (true, "the argument --test=skip was set".to_string()),
-            TestStrategy::Native => {
-                // Skip if `host_platform != build_platform` and `target_platform != noarch`
-                if output.build_configuration.target_platform != Platform::NoArch
-                    && output.build_configuration.host_platform.platform
-                        != output.build_configuration.build_platform.platform
-                {
-                    let reason = format!(
-                        "the argument --test=native was set and the build is a cross-compilation (target_platform={}, build_platform={}, host_platform={})",
-                        output.build_configuration.target_platform,
-                        output.build_configuration.build_platform.platform,
-                        output.build_configuration.host_platform.platform
-                    );
-
-                    (true, reason)
-                } else {
-                    (false, "".to_string())
-                }
-            }
-            TestStrategy::NativeAndEmulated => (false, "".to_string()),
-        };
-        if skip_test {
-            tracing::info!("Skipping tests because {}", skip_test_reason);
-            build_reindexed_channels(&output.build_configuration, &tool_configuration)
+        if let Some(skip_test_reason) = skip_test_reason(&output, tool_configuration) {
+            tracing::info!(
+                "Skipping tests of {} because {}",
+                output.identifier(),
+                skip_test_reason
+            );
+            record_skipped_tests(&output, &archive, &skip_test_reason, tool_configuration);
+            build_reindexed_channels(&output.build_configuration, tool_configuration)
                 .await
                 .into_diagnostic()
                 .context("failed to reindex output channel")?;
         } else {
             test_queue.push((output, archive));
+        }
+    }
 
-            let is_last_iteration = index == outputs_to_build.len() - 1;
-            let to_test = if is_last_iteration {
-                // On last iteration, test everything in the queue
-                std::mem::take(&mut test_queue)
-            } else {
-                // Update the test queue with the tests that we can't run yet
-                let (to_test, new_test_queue) = test_queue
-                    .into_iter()
-                    .partition(|(output, _)| can_test(output, &all_output_names, &outputs));
-                test_queue = new_test_queue;
-                to_test
-            };
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
 
-            for (output, archive) in &to_test {
-                match package_test::run_test(
-                    archive,
-                    &TestConfiguration {
-                        test_prefix: output
-                            .build_configuration
-                            .directories
-                            .output_dir
-                            .join("test"),
-                        target_platform: Some(output.build_configuration.target_platform),
-                        host_platform: Some(output.build_configuration.host_platform.clone()),
-                        current_platform: output.build_configuration.build_platform.clone(),
-                        keep_test_prefix: tool_configuration.no_clean,
-                        channels: build_reindexed_channels(
-                            &output.build_configuration,
-                            &tool_configuration,
-                        )
-                        .await
-                        .into_diagnostic()
-                        .context("failed to reindex output channel")?,
-                        channel_priority: tool_configuration.channel_priority,
-                        solve_strategy: SolveStrategy::Highest,
-                        tool_configuration: tool_configuration.clone(),
-                        test_index: None,
-                        output_dir: output.build_configuration.directories.output_dir.clone(),
-                        debug: output.build_configuration.debug,
-                        exclude_newer: output.build_configuration.exclude_newer,
-                    },
-                    None,
-                )
-                .await
-                {
-                    Ok(_) => {}
-                    Err(e) => {
-                        // move the package file to the failed directory
-                        let failed_dir = output
-                            .build_configuration
-                            .directories
-                            .output_dir
-                            .join("broken");
-                        fs::create_dir_all(&failed_dir).into_diagnostic()?;
-                        fs::rename(archive, failed_dir.join(archive.file_name().unwrap()))
-                            .into_diagnostic()?;
-
-                        // Reindex the output directory so that the broken package is no longer
-                        // listed in the repodata. This is important for --skip-existing to work
-                        // correctly on subsequent builds.
-                        if let Err(e) = build_reindexed_channels(
-                            &output.build_configuration,
-                            &tool_configuration,
-                        )
-                        .await
-                        {
-                            tracing::warn!(
-                                "Failed to reindex output directory after moving package to broken folder: {}",
-                                e
-                            );
-                        }
+/// Runs build. Returns the outputs that were built, the package of a built output is recorded
+/// in its build summary.
+pub async fn run_build_from_args(
+    build_output: Vec<Output>,
+    tool_configuration: Configuration,
+    markdown_summary: Option<&Path>,
+    profile: Option<&Path>,
+) -> miette::Result<Vec<Output>> {
+    let mut outputs = Vec::new();
+    let mut test_queue = Vec::new();
+    let outputs_to_build = skip_existing(build_output, &tool_configuration).await?;
 
-                        if tool_configuration.continue_on_failure == ContinueOnFailure::Yes {
-                            tracing::error!("Test failed for {}: {}", output.identifier(), e);
-                            output.record_warning(&format!("Test failed: {}", e));
-                        } else {
-                            return Err(miette::miette!("Test failed: {}", e));
-                        }
+    // fail early if the build scripts of a foreign platform cannot be run
+    for build_platform in outputs_to_build
+        .iter()
+        .map(|output| output.build_configuration.build_platform.platform)
+        .collect::<HashSet<_>>()
+    {
+        emulation::check_build_platform(build_platform)
+            .into_diagnostic()
+            .with_error_code(ErrorCode::SCRIPT_EMULATION)?;
+    }
+
+    // refuse to build anything if an input of an output is not pinned
+    if tool_configuration.require_pinned {
+        pinning::check_outputs(&outputs_to_build).with_error_code(ErrorCode::UNPINNED)?;
+    }
+
+    let all_output_names = outputs_to_build
+        .iter()
+        .map(|o| o.name())
+        .collect::<Vec<_>>();
+    tracing::info!("Starting build of {} outputs", outputs_to_build.len());
+    if tool_configuration.build_jobs > 1 {
+        build_outputs_concurrently(
+            &outputs_to_build,
+            &tool_configuration,
+            &mut outputs,
+            &mut test_queue,
+        )
+        .await?;
+    } else {
+        for (index, output) in outputs_to_build.iter().enumerate() {
+            let (output, archive) = match run_build(
+                output.clone(),
+                &tool_configuration,
+                WorkingDirectoryBehavior::Cleanup,
+            )
+            .boxed_local()
+            .await
+            {
+                Ok((output, archive)) => {
+                    output.record_build_end();
+                    (output, archive)
+                }
+                Err(e) => {
+                    output.record_failure();
+                    if tool_configuration.continue_on_failure == ContinueOnFailure::Yes {
+                        tracing::error!("Build failed for {}: {}", output.identifier(), e);
+                        output.record_warning(&format!("Build failed: {}", e));
+                        continue;
                     }
+                    return Err(e);
                 }
+            };
+
+            outputs.push(output.clone());
+
+            // We can now run the tests for the output. However, we need to check if
+            // all dependencies that are needed for the test are already built.
+            if let Some(skip_test_reason) = skip_test_reason(&output, &tool_configuration) {
+                tracing::info!("Skipping tests because {}", skip_test_reason);
+                record_skipped_tests(&output, &archive, &skip_test_reason, &tool_configuration);
+                build_reindexed_channels(&output.build_configuration, &tool_configuration)
+                    .await
+                    .into_diagnostic()
+                    .context("failed to reindex output channel")?;
+            } else {
+                test_queue.push((output, archive));
+
+                // With concurrent tests, everything is tested after all builds have finished
+                if tool_configuration.test_jobs > 1 {
+                    continue;
+                }
+
+                let is_last_iteration = index == outputs_to_build.len() - 1;
+                let to_test = if is_last_iteration {
+                    // On last iteration, test everything in the queue
+                    std::mem::take(&mut test_queue)
+                } else {
+                    // Update the test queue with the tests that we can't run yet
+                    let (to_test, new_test_queue) = test_queue
+                        .into_iter()
+                        .partition(|(output, _)| can_test(output, &all_output_names, &outputs));
+                    test_queue = new_test_queue;
+                    to_test
+                };
+
+                run_tests(to_test, &tool_configuration).await?;
             }
         }
     }
 
+    // Run the remaining tests (all of them when testing concurrently)
+    if !test_queue.is_empty() {
+        run_tests(test_queue, &tool_configuration).await?;
+    }
+
     let span = tracing::info_span!("Build summary");
     let _enter = span.enter();
-    for output in outputs {
+    if !outputs.is_empty() {
+        tracing::info!("\n{}", timings::summary_table(&outputs));
+    }
+    if let Some(profile) = profile {
+        timings::write_chrome_trace(&outputs, profile)?;
+    }
+    for output in &outputs {
         // print summaries for each output
         let _ = output.log_build_summary().map_err(|e| {
             tracing::error!("Error writing build summary: {}", e);
@@ -909,7 +1318,7 @@ pub async fn run_build_from_args(
         }
     }
 
-    Ok(())
+    Ok(outputs)
 }
 
 /// Check if the noarch builds should be skipped because the noarch platform has
@@ -942,14 +1351,84 @@ pub async fn skip_noarch(
     Ok(outputs)
 }
 
+/// Find the best matching package for `spec` in the channels (or the channel of the spec)
+async fn find_test_package(
+    spec: &MatchSpec,
+    channels: &[ChannelUrl],
+    platform: Platform,
+    tool_config: &Configuration,
+) -> miette::Result<rattler_conda_types::RepoDataRecord> {
+    let channels = match &spec.channel {
+        Some(channel) => vec![channel.as_ref().clone()],
+        None => channels.iter().cloned().map(Channel::from_url).collect(),
+    };
+    let platforms = match &spec.subdir {
+        Some(subdir) => vec![subdir.parse::<Platform>().into_diagnostic()?],
+        None => vec![platform, Platform::NoArch],
+    };
+
+    let repodata = tool_config
+        .repodata_gateway
+        .query(channels, platforms, [spec.clone()])
+        .recursive(false)
+        .await
+        .into_diagnostic()
+        .context("Failed to fetch the repodata")?;
+
+    repodata
+        .iter()
+        .flat_map(|repo| repo.iter())
+        .filter(|record| spec.matches(*record))
+        .max_by(|a, b| {
+            (
+                a.package_record.version.version(),
+                a.package_record.build_number,
+                a.package_record.timestamp,
+            )
+                .cmp(&(
+                    b.package_record.version.version(),
+                    b.package_record.build_number,
+                    b.package_record.timestamp,
+                ))
+        })
+        .cloned()
+        .ok_or_else(|| miette::miette!("No package matching `{}` found in the channel(s)", spec))
+}
+
+/// Download a package into `dest_dir` and verify that it is a valid conda package (and that it
+/// matches the SHA256 hash from the repodata, if known)
+async fn download_test_package(
+    url: &url::Url,
+    sha256: Option<&rattler_digest::Sha256Hash>,
+    dest_dir: &Path,
+    tool_config: &Configuration,
+) -> miette::Result<PathBuf> {
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| name.ends_with(".conda") || name.ends_with(".tar.bz2"))
+        .ok_or_else(|| miette::miette!("The URL does not point to a conda package: {}", url))?;
+    let package_file = dest_dir.join(file_name);
+
+    tracing::info!("Downloading package from {}", url);
+    package_test::download_package(url, sha256, &package_file, tool_config)
+        .await
+        .into_diagnostic()?;
+
+    rattler_package_streaming::seek::read_package_file::<IndexJson>(&package_file)
+        .into_diagnostic()
+        .with_context(|| format!("The downloaded file is not a valid conda package: {}", url))?;
+
+    Ok(package_file)
+}
+
 /// Runs test.
 pub async fn run_test(
     test_data: TestData,
     fancy_log_handler: Option<LoggingOutputHandler>,
 ) -> miette::Result<()> {
-    let package_file = canonicalize(test_data.package_file).into_diagnostic()?;
-
-    let mut tool_config_builder = Configuration::builder();
+    let mut tool_config_builder =
+        Configuration::builder().with_opt_cache_dir(test_data.common.cache_dir.clone());
 
     // Determine virtual packages of the system. These packages define the
     // capabilities of the system. Some packages depend on these virtual
@@ -968,12 +1447,22 @@ pub async fn run_test(
             .into_diagnostic()?
     };
 
+    let channel_config = ChannelConfig::default_with_root_dir(
+        std::env::current_dir().unwrap_or_else(|_err| PathBuf::from("/")),
+    );
+    let test_channels = test_data
+        .test_channels
+        .into_iter()
+        .map(|c| c.into_base_url(&channel_config))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+
     let tool_config = tool_config_builder
         .with_keep_build(true)
         .with_compression_threads(test_data.compression_threads)
         .with_reqwest_client(
             tool_configuration::reqwest_client_from_auth_storage(
-                test_data.common.auth_file,
+                test_data.common.auth_file.clone(),
                 #[cfg(feature = "s3")]
                 test_data.common.s3_config,
                 test_data.common.mirror_config,
@@ -982,21 +1471,36 @@ pub async fn run_test(
             .into_diagnostic()?,
         )
         .with_channel_priority(test_data.common.channel_priority)
+        .with_test_python_versions(test_data.test_python_versions)
+        .with_test_channels(test_channels)
+        .with_test_extra_specs(test_data.test_extra_specs)
+        .with_test_container(test_data.test_container)
+        .with_auth_file(test_data.common.auth_file)
+        .with_test_report(TestReport::from_paths(
+            test_data.test_report_junit,
+            test_data.test_report_json,
+        ))
         .finish();
 
     let channels = test_data
         .channels
         .unwrap_or(vec![NamedChannelOrUrl::Name("conda-forge".to_string())]);
-    let channels = channels
-        .into_iter()
-        .map(|c| c.into_base_url(&tool_config.channel_config))
+    // the test channels take precedence over the channels
+    let channels = tool_config
+        .test_channels
+        .iter()
+        .cloned()
+        .map(Ok)
+        .chain(
+            channels
+                .into_iter()
+                .map(|c| c.into_base_url(&tool_config.channel_config)),
+        )
         .collect::<Result<Vec<_>, _>>()
         .into_diagnostic()?;
 
-    let tempdir = tempfile::tempdir().into_diagnostic()?;
-
     let test_options = TestConfiguration {
-        test_prefix: tempdir.path().to_path_buf(),
+        test_prefix: PathBuf::new(),
         target_platform: None,
         host_platform: None,
         current_platform,
@@ -1008,7 +1512,50 @@ pub async fn run_test(
         tool_configuration: tool_config,
         output_dir: test_data.common.output_dir,
         debug: test_data.debug,
-        exclude_newer: None,
+        exclude_newer: test_data.exclude_newer,
+    };
+
+    // remote packages are downloaded into a temporary directory
+    let download_dir = tempfile::tempdir().into_diagnostic()?;
+    let package_file = match test_data.package {
+        TestPackage::Source(PackageSource::Path(path)) => canonicalize(path).into_diagnostic()?,
+        TestPackage::Source(PackageSource::Url(url)) => {
+            download_test_package(
+                &url,
+                None,
+                download_dir.path(),
+                &test_options.tool_configuration,
+            )
+            .await?
+        }
+        TestPackage::Spec(spec) => {
+            let record = find_test_package(
+                &spec,
+                &test_options.channels,
+                test_options.current_platform.platform,
+                &test_options.tool_configuration,
+            )
+            .await?;
+            download_test_package(
+                &record.url,
+                record.package_record.sha256.as_ref(),
+                download_dir.path(),
+                &test_options.tool_configuration,
+            )
+            .await?
+        }
+        TestPackage::Deferred => return run_deferred_tests(test_options).await,
+    };
+
+    test_package(&package_file, &test_options).await
+}
+
+/// Run the tests of a package in a temporary test prefix
+async fn test_package(package_file: &Path, test_options: &TestConfiguration) -> miette::Result<()> {
+    let tempdir = tempfile::tempdir().into_diagnostic()?;
+    let test_options = TestConfiguration {
+        test_prefix: tempdir.path().to_path_buf(),
+        ..test_options.clone()
     };
 
     let package_name = package_file
@@ -1019,10 +1566,72 @@ pub async fn run_test(
 
     let span = tracing::info_span!("Running tests for", package = %package_name, span_color = package_name);
     let _enter = span.enter();
-    package_test::run_test(&package_file, &test_options, None)
+    package_test::run_test(package_file, &test_options, None)
         .await
-        .into_diagnostic()?;
+        .into_diagnostic()
+        .with_error_code(ErrorCode::TEST)?;
+
+    Ok(())
+}
+
+/// Run the deferred tests of the output directory (`rattler-build test --deferred`). The
+/// packages of the output directory are available to the tests. Packages that pass their tests
+/// are removed from the deferred tests, the others remain.
+async fn run_deferred_tests(mut test_options: TestConfiguration) -> miette::Result<()> {
+    let output_dir = canonicalize(&test_options.output_dir).into_diagnostic()?;
+    let deferred = package_test::read_deferred_tests(&output_dir).into_diagnostic()?;
+    if deferred.is_empty() {
+        tracing::info!("No deferred tests in {}", output_dir.display());
+        return Ok(());
+    }
+    test_options
+        .channels
+        .insert(0, Channel::from_directory(&output_dir).base_url);
+
+    let mut remaining = Vec::new();
+    let mut failed = Vec::new();
+    for test in deferred {
+        let package_file = output_dir.join(&test.package);
+        let platform = test
+            .package
+            .parent()
+            .and_then(|subdir| subdir.to_str())
+            .and_then(|subdir| subdir.parse::<Platform>().ok());
+        if !package_file.exists() {
+            tracing::warn!(
+                "Skipping the deferred tests of {} because the package does not exist",
+                test.package.display()
+            );
+            continue;
+        }
+        if let Some(platform) = platform
+            && !emulation::can_run(platform)
+        {
+            tracing::warn!(
+                "Skipping the deferred tests of {} because the packages for {} cannot be run on {}",
+                test.package.display(),
+                platform,
+                Platform::current()
+            );
+            remaining.push(test);
+            continue;
+        }
+
+        if let Err(e) = test_package(&package_file, &test_options).await {
+            tracing::error!("{:?}", e);
+            failed.push(test.package.display().to_string());
+            remaining.push(test);
+        }
+    }
 
+    package_test::write_deferred_tests(&output_dir, remaining).into_diagnostic()?;
+    if !failed.is_empty() {
+        return Err(miette::miette!(
+            "the deferred tests of {} failed",
+            failed.join(", ")
+        ))
+        .with_error_code(ErrorCode::TEST);
+    }
     Ok(())
 }
 
@@ -1140,6 +1749,7 @@ pub async fn rebuild_package_core(
     output.build_configuration.directories.output_dir = temp_output_path.clone();
 
     let tool_config = Configuration::builder()
+        .with_opt_cache_dir(rebuild_data.common.cache_dir.clone())
         .with_logging_output_handler(fancy_log_handler)
         .with_keep_build(true)
         .with_compression_threads(rebuild_data.compression_threads)
@@ -1215,8 +1825,14 @@ pub async fn rebuild(
     rebuild_data: RebuildData,
     fancy_log_handler: LoggingOutputHandler,
 ) -> miette::Result<()> {
+    let verify = rebuild_data.verify;
+    let verify_report = rebuild_data.verify_report.clone();
     let result = rebuild_package_core(rebuild_data, fancy_log_handler).await?;
 
+    if verify {
+        return verify_rebuild(&result, verify_report.as_deref());
+    }
+
     // Compare the SHA hashes
     if result.is_identical() {
         tracing::info!(
@@ -1269,6 +1885,83 @@ pub async fn rebuild(
     Ok(())
 }
 
+/// Compare the rebuilt package with the original file by file (`rebuild --verify`), print the
+/// differences and fail if the packages are not identical
+fn verify_rebuild(result: &RebuildOutput, report_path: Option<&Path>) -> miette::Result<()> {
+    use rebuild::FileComparison;
+
+    let report = rebuild::verify_rebuild(&result.original_path, &result.rebuilt_path)
+        .into_diagnostic()
+        .context("failed to compare the rebuilt package with the original")?;
+
+    if let Some(path) = report_path {
+        let json = serde_json::to_string_pretty(&report).into_diagnostic()?;
+        fs::write(path, json).into_diagnostic()?;
+    }
+
+    for file in &report.files {
+        match &file.comparison {
+            FileComparison::Identical => {}
+            FileComparison::Different {
+                original_size,
+                rebuilt_size,
+                offsets,
+            } => {
+                let offsets = offsets
+                    .iter()
+                    .map(|range| format!("{:#x}..{:#x}", range.start, range.end))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                tracing::info!(
+                    "  differs: {} ({} -> {} bytes{}{})",
+                    file.path.display(),
+                    original_size,
+                    rebuilt_size,
+                    if offsets.is_empty() { "" } else { ", at " },
+                    offsets
+                );
+            }
+            FileComparison::MissingInRebuild => {
+                tracing::info!("  missing in the rebuilt package: {}", file.path.display());
+            }
+            FileComparison::MissingInOriginal => {
+                tracing::info!("  missing in the original package: {}", file.path.display());
+            }
+        }
+    }
+
+    let identical = report.count(|c| *c == FileComparison::Identical);
+    let different = report.count(|c| matches!(c, FileComparison::Different { .. }));
+    let missing = report.files.len() - identical - different;
+    tracing::info!(
+        "Compared {} files: {} identical, {} different, {} missing",
+        report.files.len(),
+        identical,
+        different,
+        missing
+    );
+
+    if !result.is_identical() {
+        tracing::info!("  Original SHA256: {}", result.original_sha256);
+        tracing::info!("  Rebuilt SHA256:  {}", result.rebuilt_sha256);
+    }
+    if !report.identical {
+        miette::bail!(
+            "the rebuilt package {} is not identical to the original",
+            result.rebuilt_path.display()
+        );
+    } else if !result.is_identical() {
+        // the files are identical, but the archives are not (e.g. the compression)
+        miette::bail!(
+            "the files of the rebuilt package {} are identical, but the archive differs from the original",
+            result.rebuilt_path.display()
+        );
+    }
+
+    tracing::info!("✅ The rebuilt package is bit-for-bit identical to the original");
+    Ok(())
+}
+
 // /// Sort the build outputs (recipes) topologically based on their dependencies.
 // pub fn sort_build_outputs_topologically(
 //     outputs: &mut Vec<Output>,
@@ -1382,14 +2075,88 @@ pub async fn build_recipes(
     log_handler: &Option<console_utils::LoggingOutputHandler>,
 ) -> Result<(), miette::Error> {
     let tool_config = get_tool_config(&build_data, log_handler)?;
+    let outputs = render_recipes(&recipe_paths, &build_data, &tool_config).await?;
+    build_rendered_outputs(outputs, &build_data, tool_config).await
+}
+
+/// Build rattler-build recipes, and rebuild them whenever the recipes, their scripts, the variant
+/// configuration or local path sources change
+pub async fn watch_recipes(
+    recipe_paths: Vec<std::path::PathBuf>,
+    build_data: BuildData,
+    log_handler: &Option<console_utils::LoggingOutputHandler>,
+) -> Result<(), miette::Error> {
+    watch::watch(recipe_paths, build_data, log_handler).await
+}
+
+/// Render the outputs of all recipes, for every target platform
+pub(crate) async fn render_recipes(
+    recipe_paths: &[PathBuf],
+    build_data: &BuildData,
+    tool_config: &Configuration,
+) -> miette::Result<Vec<Output>> {
     let mut outputs = Vec::new();
-    for recipe_path in &recipe_paths {
-        tracing::info!(
-            "Processing recipe at path: {}",
-            recipe_path.canonicalize().unwrap().display()
-        );
-        let output = get_build_output(&build_data, recipe_path, &tool_config).await?;
-        outputs.extend(output);
+    let all_build_data = build_data.for_each_target_platform();
+    for build_data in &all_build_data {
+        if all_build_data.len() > 1 {
+            tracing::info!(
+                "Rendering for target platform {}",
+                build_data.target_platform
+            );
+        }
+        for recipe_path in recipe_paths {
+            tracing::info!(
+                "Processing recipe at path: {}",
+                recipe_path.canonicalize().unwrap().display()
+            );
+            let output = get_build_output(build_data, recipe_path, tool_config)
+                .await
+                .with_error_code(ErrorCode::RENDER)?;
+            outputs.extend(output);
+        }
+    }
+
+    // noarch outputs are the same for every target platform, they are only built once
+    let mut noarch = HashSet::new();
+    outputs.retain(|output| {
+        *output.target_platform() != Platform::NoArch || noarch.insert(output.identifier())
+    });
+    if let Some(channel) = &build_data.auto_build_number {
+        publish::apply_auto_build_number(channel, &mut outputs, tool_config).await?;
+    }
+    // with `--render-only` the outputs are emitted after the (optional) solve
+    if !build_data.render_only {
+        emit_rendered(&outputs)?;
+    }
+    Ok(outputs)
+}
+
+/// Emit a `rendered` event for every output
+fn emit_rendered(outputs: &[Output]) -> miette::Result<()> {
+    if !events::active() {
+        return Ok(());
+    }
+    for output in outputs {
+        let rendered = serde_json::to_value(output).into_diagnostic()?;
+        events::emit(|| events::Event::Rendered {
+            package: output.identifier(),
+            output: rendered,
+        });
+    }
+    Ok(())
+}
+
+/// Build (or only print, solve, ... depending on `build_data`) the rendered outputs of recipes
+async fn build_rendered_outputs(
+    mut outputs: Vec<Output>,
+    build_data: &BuildData,
+    tool_config: Configuration,
+) -> miette::Result<()> {
+    if build_data.dry_run == Some(DryRun::Solve) {
+        let outputs = skip_noarch(outputs, &tool_config).await?;
+        return build_plan::solve_plan(outputs, &tool_config)
+            .await
+            .with_error_code(ErrorCode::SOLVE);
     }
 
     if build_data.render_only {
@@ -1397,6 +2164,11 @@ pub async fn build_recipes(
         // TODO(refactor): figure out if this is still needed
         // sort_build_outputs_topologically(&mut outputs, build_data.up_to.as_deref())?;
 
+        events::emit(|| events::Event::PhaseStart {
+            phase: events::Phase::Render,
+            package: None,
+        });
+        let start = std::time::Instant::now();
         let outputs = if build_data.with_solve {
             let mut updated_outputs = Vec::new();
             for output in outputs {
@@ -1412,10 +2184,21 @@ pub async fn build_recipes(
             outputs
         };
 
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&outputs).into_diagnostic()?
-        );
+        emit_rendered(&outputs)?;
+        events::emit(|| {
+            events::Event::phase_end::<_, miette::Report>(
+                events::Phase::Render,
+                None,
+                start.elapsed(),
+                &Ok(()),
+            )
+        });
+        if !events::enabled() {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&outputs).into_diagnostic()?
+            );
+        }
         return Ok(());
     }
 
@@ -1423,21 +2206,33 @@ pub async fn build_recipes(
     outputs = skip_noarch(outputs, &tool_config).await?;
 
     // sort_build_outputs_topologically(&mut outputs, build_data.up_to.as_deref())?;
-    run_build_from_args(outputs, tool_config, build_data.markdown_summary.as_deref()).await?;
+    let started = chrono::Utc::now();
+    let client = tool_config.client.clone();
+    let result = run_build_from_args(
+        outputs.clone(),
+        tool_config,
+        build_data.markdown_summary.as_deref(),
+        build_data.profile.as_deref(),
+    )
+    .await
+    .map(|_| ());
 
-    Ok(())
+    hooks::fire(&build_data.hooks, &outputs, &result, started, &client).await;
+    result
 }
 
 /// Build all outputs and collect the package paths
 async fn build_and_collect_packages(
     build_output: Vec<Output>,
     tool_configuration: &Configuration,
+    profile: Option<&Path>,
 ) -> miette::Result<Vec<PathBuf>> {
     let mut package_paths = Vec::new();
+    let mut outputs = Vec::new();
     let outputs_to_build = skip_existing(build_output, tool_configuration).await?;
 
     for output in outputs_to_build.iter() {
-        let (_output, archive) = match run_build(
+        let (output, archive) = match run_build(
             output.clone(),
             tool_configuration,
             WorkingDirectoryBehavior::Cleanup,
@@ -1450,6 +2245,7 @@ async fn build_and_collect_packages(
                 (output, archive)
             }
             Err(e) => {
+                output.record_failure();
                 if tool_configuration.continue_on_failure == ContinueOnFailure::Yes {
                     tracing::error!("Build failed for {}: {}", output.identifier(), e);
                     continue;
@@ -1459,9 +2255,17 @@ async fn build_and_collect_packages(
             }
         };
 
+        outputs.push(output);
         package_paths.push(archive);
     }
 
+    if !outputs.is_empty() {
+        tracing::info!("\n{}", timings::summary_table(&outputs));
+    }
+    if let Some(profile) = profile {
+        timings::write_chrome_trace(&outputs, profile)?;
+    }
+
     Ok(package_paths)
 }
 
@@ -1581,10 +2385,9 @@ pub async fn publish_packages(
         // Sort to ensure deterministic ordering across platforms/filesystems
         expanded_recipe_paths.sort();
 
-        for recipe_path in &expanded_recipe_paths {
-            let output = get_build_output(&publish_data.build, recipe_path, &tool_config).await?;
-            outputs.extend(output);
-        }
+        outputs.extend(
+            render_recipes(&expanded_recipe_paths, &publish_data.build, &tool_config).await?,
+        );
 
         // Apply build number override if specified
         if let Some(ref build_number_arg) = publish_data.build_number {
@@ -1614,6 +2417,13 @@ pub async fn publish_packages(
             );
         }
 
+        if publish_data.build.dry_run == Some(DryRun::Solve) {
+            let outputs = skip_noarch(outputs, &tool_config).await?;
+            return build_plan::solve_plan(outputs, &tool_config)
+                .await
+                .with_error_code(ErrorCode::SOLVE);
+        }
+
         if publish_data.build.render_only {
             let outputs = if publish_data.build.with_solve {
                 let mut updated_outputs = Vec::new();
@@ -1643,7 +2453,25 @@ pub async fn publish_packages(
         // sort_build_outputs_topologically(&mut outputs, publish_data.build.up_to.as_deref())?;
 
         // Build all packages and collect the paths
-        let built_packages = build_and_collect_packages(outputs, &tool_config).await?;
+        let started = chrono::Utc::now();
+        let result = build_and_collect_packages(
+            outputs.clone(),
+            &tool_config,
+            publish_data.build.profile.as_deref(),
+        )
+        .await;
+        hooks::fire(
+            &publish_data.build.hooks,
+            &outputs,
+            &result
+                .as_ref()
+                .map(|_| ())
+                .map_err(|e| miette::miette!("{e}")),
+            started,
+            &tool_config.client,
+        )
+        .await;
+        let built_packages = result?;
 
         if built_packages.is_empty() {
             tracing::info!("No packages were built");
@@ -1659,7 +2487,8 @@ pub async fn publish_packages(
         &publish_data,
         &tool_config.repodata_gateway,
     )
-    .await?;
+    .await
+    .with_error_code(ErrorCode::UPLOAD)?;
 
     Ok(())
 }
@@ -1678,6 +2507,7 @@ pub async fn debug_recipe(
         channels: debug_data.channels,
         common: debug_data.common,
         keep_build: true,
+        resume: false,
         debug: Debug::new(true),
         test: TestStrategy::Skip,
         up_to: None,
@@ -1686,7 +2516,9 @@ pub async fn debug_recipe(
         ignore_recipe_variants: false,
         render_only: false,
         with_solve: true,
+        dry_run: None,
         no_build_id: false,
+        build_jobs: 1,
         package_format: PackageFormatAndCompression {
             archive_type: CondaArchiveType::Conda,
             compression_level: CompressionLevel::Default,
@@ -1694,9 +2526,18 @@ pub async fn debug_recipe(
         compression_threads: None,
         io_concurrency_limit: num_cpus::get(),
         no_include_recipe: false,
+        test_jobs: 1,
+        test_channels: Vec::new(),
+        test_extra_specs: Vec::new(),
+        test_python_versions: Vec::new(),
+        test_container: None,
+        test_report_junit: None,
+        test_report_json: None,
         color_build_log: true,
         tui: false,
         skip_existing: SkipExisting::None,
+        remote_cache: None,
+        remote_cache_read_only: false,
         noarch_build_platform: None,
         extra_meta: None,
         sandbox_configuration: None,
@@ -1707,6 +2548,23 @@ pub async fn debug_recipe(
         exclude_newer: None,
         build_num_override: None,
         markdown_summary: None,
+        profile: None,
+        hooks: Vec::new(),
+        codesign_identity: None,
+        notarize_keychain_profile: None,
+        extra_target_platforms: Vec::new(),
+        emulate: false,
+        slsa_provenance: None,
+        audit: None,
+        license_policy: None,
+        require_pinned: false,
+        pinning: None,
+        conda_build_env_vars: false,
+        zig_cc: false,
+        report_dependency_usage: false,
+        render_cache: false,
+        post_processors: Vec::new(),
+        auto_build_number: None,
     };
 
     let tool_config = get_tool_config(&build_data, log_handler)?;
@@ -1779,23 +2637,32 @@ pub async fn debug_recipe(
             }
         }
 
-        tracing::info!("\nTo run the actual build, use:");
+        let work_dir = &output.build_configuration.directories.work_dir;
         tracing::info!(
-            "rattler-build build --recipe {}",
-            output.build_configuration.directories.recipe_path.display()
+            "\nThe sources, build script and activation script are in {}",
+            work_dir.display()
+        );
+        tracing::info!("To run the build script in the build environment, use:");
+        tracing::info!(
+            "  rattler-build debug run --work-dir {}",
+            work_dir.display()
         );
-        tracing::info!("Or run the build script directly with:");
+        tracing::info!("To open a shell with the build environment activated, use:");
+        tracing::info!(
+            "  rattler-build debug shell --work-dir {}",
+            work_dir.display()
+        );
+        tracing::info!("Or run the build script manually with:");
         if cfg!(windows) {
-            tracing::info!(
-                "cd {} && ./conda_build.bat",
-                output.build_configuration.directories.work_dir.display()
-            );
+            tracing::info!("  cd {} && conda_build.bat", work_dir.display());
         } else {
-            tracing::info!(
-                "cd {} && ./conda_build.sh",
-                output.build_configuration.directories.work_dir.display()
-            );
+            tracing::info!("  cd {} && bash conda_build.sh", work_dir.display());
         }
+        tracing::info!("To run the full build, use:");
+        tracing::info!(
+            "  rattler-build build --recipe {}",
+            output.build_configuration.directories.recipe_path.display()
+        );
     }
 
     Ok(())
@@ -1810,3 +2677,279 @@ pub fn show_package_info(args: InspectOpts) -> miette::Result<()> {
 pub async fn extract_package(args: opt::ExtractOpts) -> miette::Result<()> {
     package_info::extract_package(args).await
 }
+
+/// Compare the exported symbols of a package against a previous build
+pub async fn abi_diff_package(args: opt::AbiDiffOpts) -> miette::Result<()> {
+    package_info::abi_diff(args).await
+}
+
+/// Index a local or S3 channel
+pub async fn index_channel(
+    args: opt::IndexOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    match index::IndexTarget::parse(&args.channel)? {
+        index::IndexTarget::Local(channel) => {
+            index::index_local_channel(&channel, &args.target_platforms, args.force).await
+        }
+        #[cfg(feature = "s3")]
+        index::IndexTarget::S3(channel) => {
+            let s3_config = rattler_networking::s3_middleware::compute_s3_config(
+                &config.unwrap_or_default().s3_options.0,
+            );
+            let credentials =
+                tool_configuration::resolve_s3_credentials(&s3_config, args.auth_file, &channel)
+                    .await
+                    .into_diagnostic()?;
+            index::index_s3_channel(&channel, credentials, &args.target_platforms, args.force).await
+        }
+        #[cfg(not(feature = "s3"))]
+        index::IndexTarget::S3(_) => {
+            let _ = (config, args.auth_file);
+            Err(miette::miette!(
+                "rattler-build was built without S3 support, S3 channels cannot be indexed"
+            ))
+        }
+    }
+}
+
+/// List the packages that a recipe produces, without solving or building
+pub async fn list_outputs(
+    args: opt::OutputsOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    let recipe_path = get_recipe_path(&args.recipe)?;
+    let json = args.json;
+    let build_data = BuildData::from_opts_and_config(args.into(), config);
+    let tool_config = get_tool_config(&build_data, &None)?;
+
+    let outputs = get_build_output(&build_data, &recipe_path, &tool_config).await?;
+    let outputs = skip_noarch(outputs, &tool_config).await?;
+    let packages = outputs
+        .iter()
+        .map(outputs::PackageOutput::from)
+        .collect::<Vec<_>>();
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&packages).into_diagnostic()?
+        );
+    } else {
+        println!("{}", outputs::outputs_table(&packages));
+    }
+    Ok(())
+}
+
+/// Print the dependency graph among the outputs of the recipes
+pub async fn dependency_graph(
+    recipe_paths: Vec<PathBuf>,
+    args: opt::GraphOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    let format = args.format;
+    let build_data = BuildData::from_opts_and_config(args.into(), config);
+    let tool_config = get_tool_config(&build_data, &None)?;
+    let outputs = render_recipes(&recipe_paths, &build_data, &tool_config).await?;
+    let graph = graph::DependencyGraph::from_outputs(&outputs);
+
+    match format {
+        opt::GraphFormat::Dot => print!("{}", graph.to_dot()),
+        opt::GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
+        opt::GraphFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&graph).into_diagnostic()?
+        ),
+    }
+    Ok(())
+}
+
+/// Generate the configuration of a CI pipeline that builds the recipes
+pub async fn generate_ci(
+    recipe_paths: Vec<PathBuf>,
+    args: opt::GenerateCiOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    let provider = args.provider;
+    let output = args.output.clone();
+    let upload_to = args.upload_to.clone();
+    let branch = args.branch.clone();
+
+    // the options of the jobs that are the same for every job
+    let mut build_args = Vec::new();
+    for channel in args.channels.iter().flatten() {
+        build_args.extend(["-c".to_string(), channel.to_string()]);
+    }
+    for variant_config in args.variant_config.iter().flatten() {
+        build_args.extend([
+            "-m".to_string(),
+            variant_config.to_string_lossy().replace('\\', "/"),
+        ]);
+    }
+    if args.ignore_recipe_variants {
+        build_args.push("--ignore-recipe-variants".to_string());
+    }
+    if let Some(pinning) = &args.pinning {
+        build_args.extend(["--pinning".to_string(), pinning.to_string()]);
+    }
+
+    let build_data = BuildData::from_opts_and_config(args.into(), config);
+    let tool_config = get_tool_config(&build_data, &None)?;
+    let mut jobs = Vec::new();
+    for recipe_path in &recipe_paths {
+        jobs.extend(
+            generate_ci::recipe_jobs(recipe_path, provider, &build_data, &tool_config)
+                .await
+                .with_error_code(ErrorCode::RENDER)?,
+        );
+    }
+    if jobs.is_empty() {
+        return Err(miette::miette!(
+            "The recipes do not produce any package for the platforms"
+        ));
+    }
+
+    let pipeline = generate_ci::Pipeline {
+        provider,
+        jobs,
+        build_args,
+        upload_to,
+        branch,
+    };
+    if let Some(path) = generate_ci::write_pipeline(&pipeline, output)? {
+        tracing::info!(
+            "Wrote the pipeline with {} job(s) to {}",
+            pipeline.jobs.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Remove the build directories, caches and intermediate files of old builds
+pub fn clean_builds(args: opt::CleanOpts, config: Option<config::Config>) -> miette::Result<()> {
+    let settings = config.map(|config| config.extensions).unwrap_or_default();
+    let output_dir = args
+        .output_dir
+        .clone()
+        .or(settings.output_dir)
+        .unwrap_or_else(|| PathBuf::from("./output"));
+    let cache_dir = match settings.cache_dir {
+        Some(cache_dir) => cache_dir,
+        None => rattler_cache::default_cache_dir()
+            .map_err(|e| miette::miette!("Failed to determine the cache directory: {}", e))?,
+    };
+    clean::clean(args, output_dir, cache_dir)
+}
+
+/// Explain which inputs changed between a previous build of a package and the current recipe
+pub async fn explain_rebuild(
+    mut args: opt::ExplainRebuildOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    let recipe_path = get_recipe_path(&args.recipe)?;
+    let client = package_info::create_authenticated_client()?;
+    let previous = explain_rebuild::read_rendered_output(&args.previous, &client).await?;
+
+    // render the recipe for the platform of the previous build
+    if args.target_platform.is_none() && *previous.target_platform() != Platform::NoArch {
+        args.target_platform = Some(*previous.target_platform());
+    }
+
+    let json = args.json;
+    let build_data = BuildData::from_opts_and_config(args.into(), config);
+    let tool_config = get_tool_config(&build_data, &None)?;
+    let outputs = get_build_output(&build_data, &recipe_path, &tool_config).await?;
+
+    let new = explain_rebuild::matching_output(&previous, &outputs).ok_or_else(|| {
+        miette::miette!(
+            "The recipe has no output named {}",
+            previous.name().as_normalized()
+        )
+    })?;
+    let explanation = explain_rebuild::explain(&previous, new)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&explanation).into_diagnostic()?
+        );
+    } else {
+        explain_rebuild::print_explanation(&explanation);
+    }
+    Ok(())
+}
+
+/// Audit packages for known vulnerabilities in the OSV database
+pub async fn audit_packages(
+    args: opt::AuditOpts,
+    config: Option<config::Config>,
+    fancy_log_handler: LoggingOutputHandler,
+) -> miette::Result<()> {
+    let common = CommonData::from_opts_and_config(args.common, config.unwrap_or_default());
+    let client = tool_configuration::reqwest_client_from_auth_storage(
+        common.auth_file.clone(),
+        #[cfg(feature = "s3")]
+        common.s3_config.clone(),
+        common.mirror_config.clone(),
+        common.allow_insecure_host.clone(),
+    )
+    .into_diagnostic()?;
+    let tool_config = Configuration::builder()
+        .with_opt_cache_dir(common.cache_dir.clone())
+        .with_logging_output_handler(fancy_log_handler)
+        .with_reqwest_client(client)
+        .finish();
+    let channels = args
+        .channels
+        .map(|channels| {
+            channels
+                .into_iter()
+                .map(|channel| channel.into_base_url(&tool_config.channel_config))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .into_diagnostic()?;
+
+    let mut reports = Vec::new();
+    for package in &args.packages {
+        let mut output =
+            explain_rebuild::read_rendered_output(package, tool_config.client.get_client()).await?;
+        if let Some(channels) = &channels {
+            output.build_configuration.channels = channels.clone();
+        }
+        let report = audit::audit_output(&output, &tool_config, args.osv_url.as_ref())
+            .await
+            .into_diagnostic()?;
+        if !args.json {
+            audit::print_report(&report);
+        }
+        reports.push(report);
+    }
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&reports).into_diagnostic()?
+        );
+    }
+    if let Some(threshold) = args.fail_on {
+        for report in &reports {
+            report
+                .check(threshold)
+                .into_diagnostic()
+                .with_error_code(ErrorCode::AUDIT)?;
+        }
+    }
+    Ok(())
+}
+
+/// Upload packages to a channel, an OCI registry, a GitHub release or with an uploader plugin
+pub async fn upload_packages(
+    args: UploadOpts,
+    config: Option<config::Config>,
+) -> miette::Result<()> {
+    upload::upload_packages(args, config)
+        .await
+        .with_error_code(ErrorCode::UPLOAD)
+}
@@ -0,0 +1,310 @@
+//! The license policy of the packages (`[license-policy]` in the configuration).
+//!
+//! When a package is created, its license and the licenses of the packages in its run
+//! environment are checked against the SPDX licenses that the policy allows and denies. The
+//! violations are reported in a table and either fail the build or are only shown as warnings.
+use std::fmt;
+
+use rattler_conda_types::RepoDataRecord;
+use serde::{Deserialize, Serialize};
+use spdx::{Expression, LicenseReq, Licensee, ParseMode};
+
+use crate::{metadata::Output, tool_configuration};
+
+/// What happens if a license violates the policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyAction {
+    /// The build fails
+    #[default]
+    Fail,
+    /// A warning is shown
+    Warn,
+}
+
+/// The allowed and denied licenses of the packages and their run dependencies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LicensePolicy {
+    /// The allowed SPDX licenses, e.g. `MIT` or `Apache-2.0 WITH LLVM-exception`. If empty, all
+    /// licenses that are not denied are allowed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+
+    /// The denied SPDX licenses, e.g. `AGPL-3.0-only`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+
+    /// Whether a violation fails the build or is only a warning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_violation: Option<PolicyAction>,
+
+    /// The packages that are not checked, e.g. because their license is not an SPDX expression
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore: Vec<String>,
+}
+
+/// Why a license violates the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationReason {
+    /// The package has no license
+    Missing,
+    /// The license is not an SPDX expression
+    Invalid,
+    /// The license requires a denied license
+    Denied(Vec<String>),
+    /// The license requires a license that is not allowed
+    NotAllowed(Vec<String>),
+}
+
+impl fmt::Display for ViolationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "no license"),
+            Self::Invalid => write!(f, "not an SPDX license expression"),
+            Self::Denied(licenses) => write!(f, "denied: {}", licenses.join(", ")),
+            Self::NotAllowed(licenses) => write!(f, "not allowed: {}", licenses.join(", ")),
+        }
+    }
+}
+
+/// A package whose license violates the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LicenseViolation {
+    /// The name of the package
+    pub package: String,
+    /// The version of the package
+    pub version: String,
+    /// The license of the package
+    pub license: Option<String>,
+    /// Why the license violates the policy
+    pub reason: ViolationReason,
+}
+
+/// The parsed licenses of a list of the policy (invalid entries are rejected when the
+/// configuration is validated)
+fn licensees(licenses: &[String]) -> Vec<Licensee> {
+    licenses
+        .iter()
+        .filter_map(|license| Licensee::parse(license).ok())
+        .collect()
+}
+
+impl LicensePolicy {
+    pub(crate) fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether the policy allows or denies any license
+    pub fn is_active(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    /// Check that the licenses of the policy are SPDX licenses
+    pub fn validate(&self) -> Result<(), String> {
+        for license in self.allow.iter().chain(&self.deny) {
+            Licensee::parse(license)
+                .map_err(|error| format!("`{license}` is not an SPDX license: {error}"))?;
+        }
+        Ok(())
+    }
+
+    /// Check a license expression against the policy. The license is accepted if it can be
+    /// satisfied with licenses that are allowed and not denied (i.e. one of the alternatives
+    /// of an `OR` is enough).
+    pub fn check_license(&self, license: Option<&str>) -> Result<(), ViolationReason> {
+        let license = license
+            .map(str::trim)
+            .filter(|license| !license.is_empty())
+            .ok_or(ViolationReason::Missing)?;
+        let expression = Expression::parse_mode(license, ParseMode::LAX)
+            .map_err(|_| ViolationReason::Invalid)?;
+
+        let allow = licensees(&self.allow);
+        let deny = licensees(&self.deny);
+        let allowed = |req: &LicenseReq| allow.is_empty() || allow.iter().any(|l| l.satisfies(req));
+        let denied = |req: &LicenseReq| deny.iter().any(|l| l.satisfies(req));
+        if expression.evaluate(|req| allowed(req) && !denied(req)) {
+            return Ok(());
+        }
+
+        let requirements = |matches: &dyn Fn(&LicenseReq) -> bool| {
+            let mut licenses = expression
+                .requirements()
+                .filter(|requirement| matches(&requirement.req))
+                .map(|requirement| requirement.req.to_string())
+                .collect::<Vec<_>>();
+            licenses.dedup();
+            licenses
+        };
+        let denied_licenses = requirements(&denied);
+        if !denied_licenses.is_empty() {
+            return Err(ViolationReason::Denied(denied_licenses));
+        }
+        Err(ViolationReason::NotAllowed(requirements(&|req| {
+            !allowed(req)
+        })))
+    }
+
+    /// Check the packages (name, version and license) against the policy
+    pub fn check<'a>(
+        &self,
+        packages: impl IntoIterator<Item = (&'a str, String, Option<&'a str>)>,
+    ) -> Vec<LicenseViolation> {
+        let mut violations = Vec::new();
+        for (name, version, license) in packages {
+            if self.ignore.iter().any(|ignored| ignored == name)
+                || violations
+                    .iter()
+                    .any(|violation: &LicenseViolation| violation.package == name)
+            {
+                continue;
+            }
+            if let Err(reason) = self.check_license(license) {
+                violations.push(LicenseViolation {
+                    package: name.to_string(),
+                    version,
+                    license: license.map(str::to_string),
+                    reason,
+                });
+            }
+        }
+        violations
+    }
+}
+
+fn record_license(record: &RepoDataRecord) -> (&str, String, Option<&str>) {
+    let package = &record.package_record;
+    (
+        package.name.as_normalized(),
+        package.version.to_string(),
+        package.license.as_deref(),
+    )
+}
+
+/// Check the license of the output and of the packages in its run environment against the
+/// policy
+pub(crate) async fn check_output(
+    output: &Output,
+    policy: &LicensePolicy,
+    tool_configuration: &tool_configuration::Configuration,
+) -> Vec<LicenseViolation> {
+    let license = output
+        .recipe
+        .about()
+        .license
+        .as_ref()
+        .map(|license| license.to_string());
+    let run = output.solve_run_environment(tool_configuration).await;
+    policy.check(
+        std::iter::once((
+            output.name().as_normalized(),
+            output.version().to_string(),
+            license.as_deref(),
+        ))
+        .chain(run.iter().map(record_license)),
+    )
+}
+
+/// Report the violations of the license policy as a table
+pub(crate) fn print_violations(output: &Output, violations: &[LicenseViolation]) {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(["Package", "Version", "License", "Violation"]);
+    for violation in violations {
+        table.add_row([
+            violation.package.as_str(),
+            violation.version.as_str(),
+            violation.license.as_deref().unwrap_or("-"),
+            &violation.reason.to_string(),
+        ]);
+    }
+    tracing::warn!(
+        "{} license(s) of {} and its run dependencies violate the license policy",
+        violations.len(),
+        output.identifier()
+    );
+    tracing::info!("\n{table}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allow: &[&str], deny: &[&str]) -> LicensePolicy {
+        LicensePolicy {
+            allow: allow.iter().map(|l| l.to_string()).collect(),
+            deny: deny.iter().map(|l| l.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_license() {
+        let policy = policy(
+            &["MIT", "BSD-3-Clause", "Apache-2.0", "GPL-3.0-only"],
+            &["AGPL-3.0-only", "GPL-3.0-only"],
+        );
+        assert_eq!(policy.check_license(Some("MIT")), Ok(()));
+        // one of the alternatives is enough
+        assert_eq!(policy.check_license(Some("MIT OR AGPL-3.0-only")), Ok(()));
+        assert_eq!(
+            policy.check_license(Some("MIT AND AGPL-3.0-only")),
+            Err(ViolationReason::Denied(vec!["AGPL-3.0-only".to_string()]))
+        );
+        // deny takes priority over allow
+        assert_eq!(
+            policy.check_license(Some("GPL-3.0-only")),
+            Err(ViolationReason::Denied(vec!["GPL-3.0-only".to_string()]))
+        );
+        assert_eq!(
+            policy.check_license(Some("BSD-3-Clause AND Zlib")),
+            Err(ViolationReason::NotAllowed(vec!["Zlib".to_string()]))
+        );
+        // imprecise names of conda packages are accepted
+        assert_eq!(policy.check_license(Some("Apache 2.0")), Ok(()));
+        assert_eq!(
+            policy.check_license(Some("Proprietary license")),
+            Err(ViolationReason::Invalid)
+        );
+        assert_eq!(policy.check_license(None), Err(ViolationReason::Missing));
+    }
+
+    #[test]
+    fn test_deny_only() {
+        let policy = policy(&[], &["GPL-3.0-or-later"]);
+        assert_eq!(policy.check_license(Some("LGPL-2.1-or-later")), Ok(()));
+        assert!(policy.check_license(Some("GPL-3.0-or-later")).is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let policy = LicensePolicy {
+            ignore: vec!["libfoo".to_string()],
+            ..policy(&["MIT"], &[])
+        };
+        let violations = policy.check([
+            ("app", "1.0".to_string(), Some("MIT")),
+            ("libfoo", "2.0".to_string(), Some("Proprietary")),
+            ("libbar", "3.0".to_string(), None),
+            ("libbar", "3.0".to_string(), None),
+        ]);
+        assert_eq!(
+            violations,
+            vec![LicenseViolation {
+                package: "libbar".to_string(),
+                version: "3.0".to_string(),
+                license: None,
+                reason: ViolationReason::Missing,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(policy(&["MIT"], &["GPL-3.0-only"]).validate().is_ok());
+        assert!(policy(&["MIT"], &["Not a license"]).validate().is_err());
+    }
+}
@@ -5,7 +5,7 @@ use goblin::elf::{Dyn, Elf};
 use goblin::strtab::Strtab;
 use itertools::Itertools;
 use memmap2::MmapMut;
-use rattler_build_recipe::stage1::GlobVec;
+use rattler_build_recipe::stage1::{GlobVec, build::RpathType};
 use scroll::Pwrite;
 use scroll::ctx::SizeWith;
 use std::collections::{HashMap, HashSet};
@@ -13,7 +13,7 @@ use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
 
-use crate::post_process::relink::{RelinkError, Relinker};
+use crate::post_process::relink::{CustomRpath, RelinkError, Relinker};
 use crate::system_tools::{SystemTools, Tool};
 use crate::unix::permission_guard::{PermissionGuard, READ_WRITE};
 use crate::utils::to_lexical_absolute;
@@ -33,6 +33,18 @@ pub struct SharedObject {
     has_dynamic: bool,
 }
 
+impl SharedObject {
+    /// The `DT_RPATH` entries of the shared object
+    pub fn rpaths(&self) -> &[String] {
+        &self.rpaths
+    }
+
+    /// The `DT_RUNPATH` entries of the shared object
+    pub fn runpaths(&self) -> &[String] {
+        &self.runpaths
+    }
+}
+
 impl Relinker for SharedObject {
     /// Check if the file is an ELF file by reading the first 4 bytes
     fn test_file(path: &Path) -> Result<bool, RelinkError> {
@@ -169,13 +181,15 @@ impl Relinker for SharedObject {
 
     /// Find all RPATH and RUNPATH entries and replace them with the encoded prefix.
     ///
-    /// If the rpath is outside of the prefix, it is removed.
+    /// If the rpath is outside of the prefix, it is removed. Entries of `custom_rpaths` that are
+    /// relative to `$ORIGIN` or absolute are kept as they are.
     fn relink(
         &self,
         prefix: &Path,
         encoded_prefix: &Path,
         custom_rpaths: &[String],
         rpath_allowlist: &GlobVec,
+        rpath_type: RpathType,
         system_tools: &SystemTools,
     ) -> Result<(), RelinkError> {
         if !self.has_dynamic {
@@ -190,12 +204,18 @@ impl Relinker for SharedObject {
             .filter(|r| !r.is_empty())
             .map(PathBuf::from)
             .collect::<Vec<_>>();
-        rpaths.extend(
-            custom_rpaths
-                .iter()
-                .map(|v| encoded_prefix.join(v))
-                .collect::<Vec<PathBuf>>(),
-        );
+        let custom_rpaths = custom_rpaths
+            .iter()
+            .map(|v| CustomRpath::new(v, encoded_prefix, "$ORIGIN"))
+            .collect::<Vec<_>>();
+        rpaths.extend(custom_rpaths.iter().map(|r| r.path().to_path_buf()));
+        let keep = custom_rpaths
+            .iter()
+            .filter_map(|r| match r {
+                CustomRpath::Keep(path) => Some(path),
+                CustomRpath::Prefix(_) => None,
+            })
+            .collect::<HashSet<_>>();
 
         let runpaths = self
             .runpaths
@@ -208,7 +228,10 @@ impl Relinker for SharedObject {
         let mut final_rpaths = Vec::new();
 
         for rpath in rpaths.iter().chain(runpaths.iter()) {
-            if rpath.starts_with("$ORIGIN") || rpath.starts_with("${ORIGIN}") {
+            if keep.contains(rpath) {
+                tracing::info!("Keeping rpath {} from the recipe", rpath.display());
+                final_rpaths.push(rpath.clone());
+            } else if rpath.starts_with("$ORIGIN") || rpath.starts_with("${ORIGIN}") {
                 let resolved = self.resolve_rpath(rpath, prefix, encoded_prefix);
                 if resolved.starts_with(encoded_prefix) {
                     final_rpaths.push(rpath.clone());
@@ -259,11 +282,39 @@ impl Relinker for SharedObject {
         // keep only first unique item
         final_rpaths = final_rpaths.into_iter().unique().collect();
 
+        // report every modification of the dynamic section in the build log
+        let (new_tag, old_tag, replaced) = match rpath_type {
+            RpathType::Rpath => ("RPATH", "RUNPATH", &self.runpaths),
+            RpathType::Runpath => ("RUNPATH", "RPATH", &self.rpaths),
+        };
+        let old_entries = self
+            .rpaths
+            .iter()
+            .map(|r| format!("RPATH={r}"))
+            .chain(self.runpaths.iter().map(|r| format!("RUNPATH={r}")))
+            .join(", ");
+        tracing::info!(
+            "Setting {} of {:?} to {:?} (previously {}){}",
+            new_tag,
+            self.path.file_name().unwrap_or_default(),
+            final_rpaths.iter().map(|p| p.to_string_lossy()).join(":"),
+            if old_entries.is_empty() {
+                "unset"
+            } else {
+                &old_entries
+            },
+            if replaced.is_empty() {
+                String::new()
+            } else {
+                format!(", removing {old_tag}")
+            }
+        );
+
         let _permission_guard = PermissionGuard::new(&self.path, READ_WRITE)?;
 
         // run builtin relink. if it fails, try patchelf
-        if builtin_relink(&self.path, &final_rpaths).is_err() {
-            call_patchelf(&self.path, &final_rpaths, system_tools)?;
+        if builtin_relink(&self.path, &final_rpaths, rpath_type).is_err() {
+            call_patchelf(&self.path, &final_rpaths, rpath_type, system_tools)?;
         }
 
         Ok(())
@@ -274,6 +325,7 @@ impl Relinker for SharedObject {
 fn call_patchelf(
     elf_path: &Path,
     new_rpath: &[PathBuf],
+    rpath_type: RpathType,
     system_tools: &SystemTools,
 ) -> Result<(), RelinkError> {
     let new_rpath = new_rpath.iter().map(|p| p.to_string_lossy()).join(":");
@@ -291,7 +343,10 @@ fn call_patchelf(
     // `LD_LIBRARY_PATH`. This ensures that the libraries from the environment
     // are found first, providing better isolation and preventing potential
     // conflicts with system libraries.
-    cmd.arg("--force-rpath");
+    // Without `--force-rpath`, patchelf writes (and converts to) a RUNPATH.
+    if rpath_type == RpathType::Rpath {
+        cmd.arg("--force-rpath");
+    }
 
     // set the new rpath
     cmd.arg("--set-rpath").arg(new_rpath).arg(elf_path);
@@ -325,12 +380,17 @@ fn get_context(object: &Elf) -> goblin::container::Ctx {
     goblin::container::Ctx { container, le }
 }
 
-/// To relink binaries we do the following operations:
+/// To relink binaries we do the following operations (with `RpathType::Runpath`, the roles of
+/// RPATH and RUNPATH are swapped):
 ///
 /// - if the binary has both, a RUNPATH and a RPATH, we delete the RUNPATH
 /// - if the binary has only a RUNPATH, we turn the RUNPATH into an RPATH
 /// - if the binary has only a RPATH, we just rewrite the RPATH
-fn builtin_relink(elf_path: &Path, new_rpath: &[PathBuf]) -> Result<(), RelinkError> {
+fn builtin_relink(
+    elf_path: &Path,
+    new_rpath: &[PathBuf],
+    rpath_type: RpathType,
+) -> Result<(), RelinkError> {
     let new_rpath = new_rpath.iter().map(|p| p.to_string_lossy()).join(":");
 
     let file = std::fs::OpenOptions::new()
@@ -359,18 +419,23 @@ fn builtin_relink(elf_path: &Path, new_rpath: &[PathBuf]) -> Result<(), RelinkEr
     // reopen to please the borrow checker
     let data = unsafe { memmap2::Mmap::map(&file) }?;
 
-    let has_rpath = dynamic
-        .dyns
-        .iter()
-        .any(|entry| entry.d_tag == goblin::elf::dynamic::DT_RPATH);
+    let (target_tag, other_tag) = match rpath_type {
+        RpathType::Rpath => (
+            goblin::elf::dynamic::DT_RPATH,
+            goblin::elf::dynamic::DT_RUNPATH,
+        ),
+        RpathType::Runpath => (
+            goblin::elf::dynamic::DT_RUNPATH,
+            goblin::elf::dynamic::DT_RPATH,
+        ),
+    };
+
+    let has_target = dynamic.dyns.iter().any(|entry| entry.d_tag == target_tag);
 
-    let has_runpath = dynamic
-        .dyns
-        .iter()
-        .any(|entry| entry.d_tag == goblin::elf::dynamic::DT_RUNPATH);
+    let has_other = dynamic.dyns.iter().any(|entry| entry.d_tag == other_tag);
 
     // fallback to patchelf if there is no rpath found
-    if !has_rpath && !has_runpath {
+    if !has_target && !has_other {
         return Err(RelinkError::RpathNotFound);
     }
 
@@ -401,21 +466,21 @@ fn builtin_relink(elf_path: &Path, new_rpath: &[PathBuf]) -> Result<(), RelinkEr
     let mut needs_rewrite = false;
 
     for entry in dynamic.dyns.iter() {
-        if entry.d_tag == goblin::elf::dynamic::DT_RPATH {
+        if entry.d_tag == target_tag {
             overwrite_strtab(&mut data_mut, entry.d_val as usize, &new_rpath)?;
             new_dynamic.push(entry.clone());
-        } else if entry.d_tag == goblin::elf::dynamic::DT_RUNPATH {
+        } else if entry.d_tag == other_tag {
             needs_rewrite = true;
-            if has_rpath {
+            if has_target {
                 // todo: clear value from strtab to avoid any mentions of placeholders in the binary
                 overwrite_strtab(&mut data_mut, entry.d_val as usize, "")?;
                 push_to_end.push(Dyn {
-                    d_tag: goblin::elf::dynamic::DT_RPATH,
+                    d_tag: target_tag,
                     d_val: entry.d_val,
                 });
             } else {
                 let mut new_entry = entry.clone();
-                new_entry.d_tag = goblin::elf::dynamic::DT_RPATH;
+                new_entry.d_tag = target_tag;
                 overwrite_strtab(&mut data_mut, entry.d_val as usize, &new_rpath)?;
                 new_dynamic.push(new_entry);
             }
@@ -510,6 +575,7 @@ mod test {
             encoded_prefix,
             &[],
             &globvec,
+            RpathType::default(),
             &SystemTools::default(),
         )?;
         let object = SharedObject::new(&binary_path)?;
@@ -556,6 +622,7 @@ mod test {
             encoded_prefix,
             &[String::from("lib/")],
             &GlobVec::default(),
+            RpathType::default(),
             &SystemTools::default(),
         )?;
         let object = SharedObject::new(&binary_path)?;
@@ -593,6 +660,7 @@ mod test {
                 PathBuf::from("$ORIGIN/../lib"),
                 PathBuf::from("/usr/lib/custom_lib"),
             ],
+            RpathType::Rpath,
         )?;
 
         let object = SharedObject::new(&binary_path)?;
@@ -626,6 +694,7 @@ mod test {
                 PathBuf::from("$ORIGIN/../lib"),
                 PathBuf::from("/usr/lib/custom_lib"),
             ],
+            RpathType::Rpath,
         )?;
 
         let object = SharedObject::new(&binary_path)?;
@@ -640,4 +709,42 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn relink_custom_rpaths_to_runpath() -> Result<(), RelinkError> {
+        // copy binary to a temporary directory
+        let prefix = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/binary_files");
+        let tmp_dir = tempdir_in(&prefix)?;
+        let binary_path = tmp_dir.path().join("zlink");
+        fs::copy(prefix.join("zlink"), &binary_path)?;
+
+        let encoded_prefix = Path::new("/rattler-build_zlink/host_env_placehold");
+        let object = SharedObject::new(&binary_path)?;
+        object.relink(
+            &prefix,
+            encoded_prefix,
+            &[
+                String::from("lib/"),
+                String::from("@loader_path/../plugins"),
+                String::from("/opt/vendor/lib"),
+            ],
+            &GlobVec::default(),
+            RpathType::Runpath,
+            &SystemTools::default(),
+        )?;
+
+        // the rpath is turned into a runpath, origin-relative and absolute entries are kept
+        let object = SharedObject::new(&binary_path)?;
+        assert!(object.rpaths.is_empty());
+        assert_eq!(
+            vec!["$ORIGIN/../lib", "$ORIGIN/../plugins", "/opt/vendor/lib"],
+            object
+                .runpaths
+                .iter()
+                .flat_map(|r| r.split(':'))
+                .collect::<Vec<&str>>()
+        );
+
+        Ok(())
+    }
 }
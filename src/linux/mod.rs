@@ -1,2 +1,4 @@
 pub mod env;
 pub mod link;
+pub mod sysroot;
+pub mod zig;
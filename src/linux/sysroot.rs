@@ -0,0 +1,333 @@
+//! The sysroot (the glibc headers and libraries) for cross-compiling to Linux.
+//!
+//! When cross-compiling with a compiler for a Linux target, the matching `sysroot_<target>`
+//! package is added to the build environment, pinned to the glibc baseline of the variant
+//! (`c_stdlib_version`). Once installed, the sysroot is exposed to the build script with
+//! `CONDA_BUILD_SYSROOT` and a CMake toolchain file.
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rattler_build_jinja::Variable;
+use rattler_build_types::NormalizedKey;
+use rattler_conda_types::{
+    MatchSpec, NamelessMatchSpec, PackageName, ParseStrictness, Platform, PrefixRecord,
+    RepoDataRecord, Version, VersionSpec,
+};
+
+use crate::{
+    metadata::Output,
+    render::resolved_dependencies::{
+        DependencyInfo, ResolveError, SourceDependency, VariantDependency,
+    },
+};
+
+/// The name of the sysroot package for `platform`
+fn sysroot_name(platform: Platform) -> String {
+    format!("sysroot_{platform}")
+}
+
+/// The glibc baseline of the variant, i.e. `c_stdlib_version` if the C standard library is the
+/// sysroot
+pub(crate) fn glibc_baseline(variant: &BTreeMap<NormalizedKey, Variable>) -> Option<String> {
+    let stdlib = variant.get(&NormalizedKey::from("c_stdlib"));
+    if stdlib.is_some_and(|stdlib| stdlib.to_string() != "sysroot") {
+        return None;
+    }
+    variant
+        .get(&NormalizedKey::from("c_stdlib_version"))
+        .map(|version| version.to_string())
+}
+
+/// The sysroot that has to be added to the build environment, if any.
+///
+/// A sysroot is added when cross-compiling to Linux, the build environment contains a compiler
+/// for the host platform (a package named `<compiler>_<host_platform>`, e.g.
+/// `gcc_linux-aarch64`), and it does not already contain a sysroot.
+pub fn sysroot_dependency(
+    build_platform: Platform,
+    host_platform: Platform,
+    variant: &BTreeMap<NormalizedKey, Variable>,
+    build_specs: &[DependencyInfo],
+) -> Result<Option<DependencyInfo>, ResolveError> {
+    if !host_platform.is_linux() || build_platform == host_platform {
+        return Ok(None);
+    }
+
+    let names = build_specs
+        .iter()
+        .filter_map(|dep| dep.spec().name.as_ref())
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    let suffix = format!("_{host_platform}");
+    let has_compiler = names.iter().any(|name| name.ends_with(&suffix));
+    let has_sysroot = names.iter().any(|name| name.starts_with("sysroot_"));
+    if !has_compiler || has_sysroot {
+        return Ok(None);
+    }
+
+    let name = PackageName::from_str(&sysroot_name(host_platform))
+        .expect("the sysroot name is a valid package name");
+    let dependency: DependencyInfo = match glibc_baseline(variant) {
+        Some(baseline) => {
+            let spec =
+                NamelessMatchSpec::from_str(&format!("={baseline}"), ParseStrictness::Lenient)
+                    .map_err(|e| {
+                        ResolveError::VariantSpecParseError("c_stdlib_version".into(), e)
+                    })?;
+            VariantDependency {
+                spec: MatchSpec::from_nameless(spec, Some(name.into())),
+                variant: "c_stdlib_version".to_string(),
+            }
+            .into()
+        }
+        None => SourceDependency {
+            spec: MatchSpec::from_nameless(NamelessMatchSpec::default(), Some(name.into())),
+        }
+        .into(),
+    };
+
+    tracing::info!(
+        "Adding {} to the build environment for cross-compiling to {host_platform}",
+        dependency.spec()
+    );
+    Ok(Some(dependency))
+}
+
+/// Whether the version of a sysroot is the glibc baseline (e.g. `2.17.0` for `2.17`)
+fn matches_baseline(version: &Version, baseline: &str) -> bool {
+    VersionSpec::from_str(&format!("={baseline}"), ParseStrictness::Lenient)
+        .is_ok_and(|spec| spec.matches(version))
+}
+
+/// Check that the resolved sysroot for `host_platform` matches the glibc baseline of the variant
+pub fn validate_sysroot(
+    host_platform: Platform,
+    variant: &BTreeMap<NormalizedKey, Variable>,
+    records: &[RepoDataRecord],
+) -> Result<(), ResolveError> {
+    let name = sysroot_name(host_platform);
+    let Some(record) = records
+        .iter()
+        .find(|record| record.package_record.name.as_normalized() == name)
+    else {
+        return Ok(());
+    };
+    let Some(baseline) = glibc_baseline(variant) else {
+        return Ok(());
+    };
+
+    let version = &record.package_record.version;
+    if !matches_baseline(version, &baseline) {
+        return Err(ResolveError::SysrootMismatch {
+            package: name,
+            version: version.to_string(),
+            baseline,
+        });
+    }
+    Ok(())
+}
+
+/// The directory of the sysroot for `platform` that is installed in `prefix`
+pub fn find_sysroot(prefix: &Path, platform: Platform) -> Option<PathBuf> {
+    let record_prefix = format!("{}-", sysroot_name(platform));
+    let record = fs_err::read_dir(prefix.join("conda-meta"))
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&record_prefix) && name.ends_with(".json"))
+        })?;
+    let record = PrefixRecord::from_path(record).ok()?;
+
+    // the files of the sysroot are installed in `<triplet>/sysroot`
+    record.files.iter().find_map(|file| {
+        let mut sysroot = prefix.to_path_buf();
+        for component in file.components() {
+            sysroot.push(component);
+            if component.as_os_str() == "sysroot" {
+                return Some(sysroot);
+            }
+        }
+        None
+    })
+}
+
+/// A CMake toolchain file for cross-compiling to `platform` with the sysroot
+fn cmake_toolchain(platform: Platform, sysroot: &Path, host_prefix: &Path) -> String {
+    let processor = platform
+        .arch()
+        .map(|arch| arch.to_string())
+        .unwrap_or_default();
+    format!(
+        r#"# Generated by rattler-build for cross-compiling to {platform}
+set(CMAKE_SYSTEM_NAME Linux)
+set(CMAKE_SYSTEM_PROCESSOR {processor})
+set(CMAKE_SYSROOT "{sysroot}")
+set(CMAKE_FIND_ROOT_PATH "{host_prefix};{sysroot}")
+set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)
+set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)
+set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE ONLY)
+set(CMAKE_FIND_ROOT_PATH_MODE_PACKAGE ONLY)
+"#,
+        sysroot = sysroot.display(),
+        host_prefix = host_prefix.display(),
+    )
+}
+
+/// The environment variables for the sysroot when cross-compiling to Linux:
+///
+/// - `CONDA_BUILD_SYSROOT`: the directory of the sysroot
+/// - `CMAKE_TOOLCHAIN_FILE`: a toolchain file (written to the build directory) that points CMake
+///   to the sysroot and the host prefix
+pub fn env_vars(output: &Output) -> HashMap<String, Option<String>> {
+    let mut vars = HashMap::new();
+    let configuration = &output.build_configuration;
+    let host_platform = configuration.host_platform.platform;
+    if !host_platform.is_linux() || !configuration.cross_compilation() {
+        return vars;
+    }
+
+    let directories = &configuration.directories;
+    // with merged build and host environments, the sysroot is installed in the host prefix
+    let Some(sysroot) = find_sysroot(&directories.build_prefix, host_platform)
+        .or_else(|| find_sysroot(&directories.host_prefix, host_platform))
+    else {
+        return vars;
+    };
+    vars.insert(
+        "CONDA_BUILD_SYSROOT".to_string(),
+        Some(sysroot.to_string_lossy().to_string()),
+    );
+
+    let toolchain = directories.build_dir.join("conda_toolchain.cmake");
+    match fs_err::write(
+        &toolchain,
+        cmake_toolchain(host_platform, &sysroot, &directories.host_prefix),
+    ) {
+        Ok(()) => {
+            vars.insert(
+                "CMAKE_TOOLCHAIN_FILE".to_string(),
+                Some(toolchain.to_string_lossy().to_string()),
+            );
+        }
+        Err(e) => tracing::warn!("Could not write the CMake toolchain file: {e}"),
+    }
+
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(spec: &str) -> DependencyInfo {
+        SourceDependency {
+            spec: MatchSpec::from_str(spec, ParseStrictness::Strict).unwrap(),
+        }
+        .into()
+    }
+
+    fn variant(entries: &[(&str, &str)]) -> BTreeMap<NormalizedKey, Variable> {
+        entries
+            .iter()
+            .map(|(k, v)| (NormalizedKey::from(*k), Variable::from_string(v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_sysroot_dependency() {
+        let baseline = variant(&[("c_stdlib", "sysroot"), ("c_stdlib_version", "2.17")]);
+        let specs = [source("gcc_linux-aarch64"), source("cmake")];
+
+        let dependency =
+            sysroot_dependency(Platform::Linux64, Platform::LinuxAarch64, &baseline, &specs)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            dependency.spec().to_string(),
+            "sysroot_linux-aarch64 2.17.*"
+        );
+        assert_eq!(dependency.as_variant().unwrap().variant, "c_stdlib_version");
+
+        // without a baseline, any sysroot is selected
+        let dependency = sysroot_dependency(
+            Platform::Linux64,
+            Platform::LinuxAarch64,
+            &variant(&[]),
+            &specs,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(dependency.spec().to_string(), "sysroot_linux-aarch64");
+
+        // native builds, builds without compiler and builds with a sysroot are left alone
+        for (build, host, specs) in [
+            (Platform::LinuxAarch64, Platform::LinuxAarch64, &specs[..]),
+            (Platform::Linux64, Platform::LinuxAarch64, &specs[1..]),
+            (
+                Platform::Osx64,
+                Platform::OsxArm64,
+                &[source("clang_osx-arm64")][..],
+            ),
+            (
+                Platform::Linux64,
+                Platform::LinuxAarch64,
+                &[
+                    source("gcc_linux-aarch64"),
+                    source("sysroot_linux-aarch64 2.28.*"),
+                ][..],
+            ),
+        ] {
+            assert!(
+                sysroot_dependency(build, host, &baseline, specs)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+    }
+
+    #[test]
+    fn test_matches_baseline() {
+        let version = |v: &str| Version::from_str(v).unwrap();
+        assert!(matches_baseline(&version("2.17"), "2.17"));
+        assert!(matches_baseline(&version("2.17.1"), "2.17"));
+        assert!(!matches_baseline(&version("2.28"), "2.17"));
+        assert!(!matches_baseline(&version("2.170"), "2.17"));
+    }
+
+    #[test]
+    fn test_find_sysroot() {
+        let prefix = tempfile::tempdir().unwrap();
+        let conda_meta = prefix.path().join("conda-meta");
+        fs_err::create_dir_all(&conda_meta).unwrap();
+        fs_err::write(
+            conda_meta.join("sysroot_linux-aarch64-2.17-h5b4a56d_18.json"),
+            serde_json::json!({
+                "name": "sysroot_linux-aarch64",
+                "version": "2.17",
+                "build": "h5b4a56d_18",
+                "build_number": 18,
+                "depends": [],
+                "subdir": "noarch",
+                "fn": "sysroot_linux-aarch64-2.17-h5b4a56d_18.conda",
+                "url": "https://conda.anaconda.org/conda-forge/noarch/sysroot_linux-aarch64-2.17-h5b4a56d_18.conda",
+                "channel": "https://conda.anaconda.org/conda-forge/",
+                "files": ["aarch64-conda-linux-gnu/sysroot/lib64/libc.so.6"],
+                "paths_data": {"paths_version": 1, "paths": []},
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_sysroot(prefix.path(), Platform::LinuxAarch64),
+            Some(prefix.path().join("aarch64-conda-linux-gnu/sysroot"))
+        );
+        assert_eq!(find_sysroot(prefix.path(), Platform::LinuxPpc64le), None);
+    }
+}
@@ -0,0 +1,328 @@
+//! `zig cc` as the C/C++ compiler for Linux targets.
+//!
+//! With `--zig-cc`, builds for a Linux host platform that is cross-compiled, or that has a glibc
+//! baseline (`c_stdlib_version`) older than the glibc of the build machine, use the `zig` package
+//! instead of the `gcc`/`gxx` compiler packages and the sysroot. The build script gets `CC`,
+//! `CXX`, `AR` and `RANLIB` wrappers that call `zig` with the target triple of the host platform
+//! and the glibc baseline (e.g. `aarch64-linux-gnu.2.17`), and a CMake toolchain file.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use rattler_build_jinja::default_compiler;
+use rattler_conda_types::{MatchSpec, NamelessMatchSpec, PackageName, Platform, Version};
+
+use crate::{
+    linux::sysroot::glibc_baseline,
+    metadata::{BuildConfiguration, Output},
+    render::resolved_dependencies::{DependencyInfo, SourceDependency},
+};
+
+/// The zig target triple of a Linux platform, with the glibc baseline if known
+pub fn zig_target(platform: Platform, glibc: Option<&str>) -> Option<String> {
+    let (arch, abi) = match platform {
+        Platform::Linux32 => ("x86", "gnu"),
+        Platform::Linux64 => ("x86_64", "gnu"),
+        Platform::LinuxAarch64 => ("aarch64", "gnu"),
+        Platform::LinuxArmV6l | Platform::LinuxArmV7l => ("arm", "gnueabihf"),
+        Platform::LinuxPpc64le => ("powerpc64le", "gnu"),
+        Platform::LinuxPpc64 => ("powerpc64", "gnu"),
+        Platform::LinuxS390X => ("s390x", "gnu"),
+        Platform::LinuxRiscv64 => ("riscv64", "gnu"),
+        Platform::LinuxLoongArch64 => ("loongarch64", "gnu"),
+        _ => return None,
+    };
+    Some(match glibc {
+        Some(glibc) => format!("{arch}-linux-{abi}.{glibc}"),
+        None => format!("{arch}-linux-{abi}"),
+    })
+}
+
+/// Whether the glibc baseline is older than the glibc of the build machine
+fn older_than_build_machine(configuration: &BuildConfiguration, baseline: &str) -> bool {
+    let Ok(baseline) = Version::from_str(baseline) else {
+        return false;
+    };
+    configuration
+        .build_platform
+        .virtual_packages
+        .iter()
+        .find(|package| package.name.as_normalized() == "__glibc")
+        .is_some_and(|glibc| baseline < glibc.version)
+}
+
+/// Whether the build uses `zig cc` instead of the compiler packages
+pub fn uses_zig(configuration: &BuildConfiguration) -> bool {
+    let host_platform = configuration.host_platform.platform;
+    if !configuration.zig_cc || zig_target(host_platform, None).is_none() {
+        return false;
+    }
+    host_platform != configuration.build_platform.platform
+        || glibc_baseline(&configuration.variant)
+            .is_some_and(|baseline| older_than_build_machine(configuration, &baseline))
+}
+
+/// The names of the packages that `zig` replaces: the C and C++ compilers and the C standard
+/// library (`${{ compiler('c') }}`, `${{ compiler('cxx') }}` and `${{ stdlib('c') }}`)
+fn replaced_packages(configuration: &BuildConfiguration) -> Vec<String> {
+    let host_platform = configuration.host_platform.platform;
+    let variant = |key: &str| configuration.variant.get(&key.into()).cloned();
+    let mut names = ["c", "cxx"]
+        .into_iter()
+        .filter_map(|language| {
+            variant(&format!("{language}_compiler"))
+                .or_else(|| default_compiler(host_platform, language))
+        })
+        .map(|name| format!("{name}_{host_platform}"))
+        .collect::<Vec<_>>();
+    names.push(format!(
+        "{}_{host_platform}",
+        variant("c_stdlib").map_or_else(|| "sysroot".to_string(), |name| name.to_string())
+    ));
+    names
+}
+
+/// Replace the C/C++ compilers and the C standard library of the build environment with `zig`
+pub fn replace_compilers(configuration: &BuildConfiguration, specs: &mut Vec<DependencyInfo>) {
+    if !uses_zig(configuration) {
+        return;
+    }
+
+    let replaced = replaced_packages(configuration);
+    let count = specs.len();
+    specs.retain(|dep| {
+        !dep.spec()
+            .name
+            .as_ref()
+            .is_some_and(|name| replaced.contains(&name.to_string()))
+    });
+    if specs.len() == count {
+        return;
+    }
+
+    let name = PackageName::from_str("zig").expect("zig is a valid package name");
+    specs.push(
+        SourceDependency {
+            spec: MatchSpec::from_nameless(NamelessMatchSpec::default(), Some(name.into())),
+        }
+        .into(),
+    );
+    tracing::info!(
+        "Using zig instead of the compiler packages to build for {}",
+        configuration.host_platform.platform
+    );
+}
+
+/// Whether the `zig` package is installed in `prefix`
+fn has_zig(prefix: &Path) -> bool {
+    let Ok(entries) = fs_err::read_dir(prefix.join("conda-meta")) else {
+        return false;
+    };
+    entries.filter_map(Result::ok).any(|entry| {
+        entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("zig-"))
+            .and_then(|rest| rest.strip_suffix(".json"))
+            // the rest of the name is `<version>-<build>`
+            .is_some_and(|rest| rest.matches('-').count() == 1)
+    })
+}
+
+/// Write the wrapper scripts that call `zig <tool> -target <target>` to `dir`
+fn write_wrappers(
+    dir: &Path,
+    target: &str,
+    windows: bool,
+) -> std::io::Result<HashMap<&'static str, PathBuf>> {
+    fs_err::create_dir_all(dir)?;
+    let mut wrappers = HashMap::new();
+    for (var, name, command) in [
+        ("CC", "cc", format!("zig cc -target {target}")),
+        ("CXX", "c++", format!("zig c++ -target {target}")),
+        ("AR", "ar", "zig ar".to_string()),
+        ("RANLIB", "ranlib", "zig ranlib".to_string()),
+    ] {
+        let path = if windows {
+            let path = dir.join(format!("{name}.bat"));
+            fs_err::write(&path, format!("@{command} %*\r\n"))?;
+            path
+        } else {
+            let path = dir.join(name);
+            fs_err::write(&path, format!("#!/bin/sh\nexec {command} \"$@\"\n"))?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs_err::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            path
+        };
+        wrappers.insert(var, path);
+    }
+    Ok(wrappers)
+}
+
+/// A CMake toolchain file that uses the zig wrappers
+fn cmake_toolchain(
+    platform: Platform,
+    cross_compiling: bool,
+    wrappers: &HashMap<&'static str, PathBuf>,
+    host_prefix: &Path,
+) -> String {
+    let mut toolchain =
+        format!("# Generated by rattler-build for building for {platform} with zig\n");
+    if cross_compiling {
+        let processor = platform
+            .arch()
+            .map(|arch| arch.to_string())
+            .unwrap_or_default();
+        toolchain.push_str(&format!(
+            "set(CMAKE_SYSTEM_NAME Linux)\nset(CMAKE_SYSTEM_PROCESSOR {processor})\n"
+        ));
+    }
+    for (var, cmake_var) in [
+        ("CC", "CMAKE_C_COMPILER"),
+        ("CXX", "CMAKE_CXX_COMPILER"),
+        ("AR", "CMAKE_AR"),
+        ("RANLIB", "CMAKE_RANLIB"),
+    ] {
+        toolchain.push_str(&format!(
+            "set({cmake_var} \"{}\")\n",
+            wrappers[var].display()
+        ));
+    }
+    toolchain.push_str(&format!(
+        r#"set(CMAKE_FIND_ROOT_PATH "{}")
+set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)
+set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY ONLY)
+set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE ONLY)
+set(CMAKE_FIND_ROOT_PATH_MODE_PACKAGE ONLY)
+"#,
+        host_prefix.display()
+    ));
+    toolchain
+}
+
+/// The environment variables for building with zig:
+///
+/// - `CC`, `CXX`, `AR` and `RANLIB`: wrappers (written to the build directory) that call zig
+///   with the target triple of the host platform
+/// - `ZIG_TARGET`: the target triple, e.g. `aarch64-linux-gnu.2.17`
+/// - `ZIG_GLOBAL_CACHE_DIR`: a cache directory in the build directory
+/// - `CMAKE_TOOLCHAIN_FILE`: a toolchain file that points CMake to the wrappers
+pub fn env_vars(output: &Output) -> HashMap<String, Option<String>> {
+    let mut vars = HashMap::new();
+    let configuration = &output.build_configuration;
+    if !uses_zig(configuration) {
+        return vars;
+    }
+
+    let directories = &configuration.directories;
+    // with merged build and host environments, zig is installed in the host prefix
+    if !has_zig(&directories.build_prefix) && !has_zig(&directories.host_prefix) {
+        return vars;
+    }
+
+    let host_platform = configuration.host_platform.platform;
+    let baseline = glibc_baseline(&configuration.variant);
+    let Some(target) = zig_target(host_platform, baseline.as_deref()) else {
+        return vars;
+    };
+    let wrappers = match write_wrappers(
+        &directories.build_dir.join("zig"),
+        &target,
+        configuration.build_platform.platform.is_windows(),
+    ) {
+        Ok(wrappers) => wrappers,
+        Err(e) => {
+            tracing::warn!("Could not write the zig wrappers: {e}");
+            return vars;
+        }
+    };
+
+    for (var, path) in &wrappers {
+        vars.insert(var.to_string(), Some(path.to_string_lossy().to_string()));
+    }
+    vars.insert("ZIG_TARGET".to_string(), Some(target));
+    vars.insert(
+        "ZIG_GLOBAL_CACHE_DIR".to_string(),
+        Some(
+            directories
+                .build_dir
+                .join("zig-cache")
+                .to_string_lossy()
+                .to_string(),
+        ),
+    );
+
+    let toolchain = directories.build_dir.join("conda_toolchain.cmake");
+    let cross_compiling = host_platform != configuration.build_platform.platform;
+    match fs_err::write(
+        &toolchain,
+        cmake_toolchain(
+            host_platform,
+            cross_compiling,
+            &wrappers,
+            &directories.host_prefix,
+        ),
+    ) {
+        Ok(()) => {
+            vars.insert(
+                "CMAKE_TOOLCHAIN_FILE".to_string(),
+                Some(toolchain.to_string_lossy().to_string()),
+            );
+        }
+        Err(e) => tracing::warn!("Could not write the CMake toolchain file: {e}"),
+    }
+
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zig_target() {
+        assert_eq!(
+            zig_target(Platform::LinuxAarch64, Some("2.17")).as_deref(),
+            Some("aarch64-linux-gnu.2.17")
+        );
+        assert_eq!(
+            zig_target(Platform::LinuxArmV7l, None).as_deref(),
+            Some("arm-linux-gnueabihf")
+        );
+        assert_eq!(zig_target(Platform::OsxArm64, None), None);
+    }
+
+    #[test]
+    fn test_has_zig() {
+        let prefix = tempfile::tempdir().unwrap();
+        let conda_meta = prefix.path().join("conda-meta");
+        fs_err::create_dir_all(&conda_meta).unwrap();
+        fs_err::write(conda_meta.join("zig-cc-1.0-h123_0.json"), "{}").unwrap();
+        assert!(!has_zig(prefix.path()));
+        fs_err::write(conda_meta.join("zig-0.13.0-h2f55bd6_1.json"), "{}").unwrap();
+        assert!(has_zig(prefix.path()));
+    }
+
+    #[test]
+    fn test_write_wrappers() {
+        let dir = tempfile::tempdir().unwrap();
+        let wrappers = write_wrappers(dir.path(), "aarch64-linux-gnu.2.17", false).unwrap();
+        let cc = fs_err::read_to_string(&wrappers["CC"]).unwrap();
+        assert_eq!(
+            cc,
+            "#!/bin/sh\nexec zig cc -target aarch64-linux-gnu.2.17 \"$@\"\n"
+        );
+        let toolchain =
+            cmake_toolchain(Platform::LinuxAarch64, true, &wrappers, Path::new("/host"));
+        assert!(toolchain.contains("set(CMAKE_SYSTEM_PROCESSOR aarch64)"));
+        assert!(toolchain.contains(&format!(
+            "set(CMAKE_CXX_COMPILER \"{}\")",
+            dir.path().join("c++").display()
+        )));
+    }
+}
@@ -0,0 +1,785 @@
+//! The analysis of a recipe document: diagnostics, hover, completion and go to definition.
+//!
+//! The diagnostics come from parsing the document like `rattler-build build` does, and a few
+//! lints on the parsed YAML. The other features work on the lines of the document, so that they
+//! also work while the document is edited and not valid YAML.
+use std::path::{Path, PathBuf};
+
+use marked_yaml::{LoadError, LoaderOptions, Marker, Node, Span, types::MarkedMappingNode};
+use rattler_build_recipe::{ParseError, stage0};
+use serde::{Deserialize, Serialize};
+
+use super::docs;
+
+/// A position in a document, the line and character are zero-based
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    /// The line of the position
+    pub line: u32,
+    /// The character of the position in the line
+    pub character: u32,
+}
+
+/// A range in a document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    /// The start of the range
+    pub start: Position,
+    /// The end of the range (exclusive)
+    pub end: Position,
+}
+
+impl Range {
+    fn in_line(line: usize, start: usize, end: usize) -> Self {
+        Self {
+            start: Position {
+                line: line as u32,
+                character: start as u32,
+            },
+            end: Position {
+                line: line as u32,
+                character: end as u32,
+            },
+        }
+    }
+}
+
+/// The severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The recipe cannot be built
+    Error = 1,
+    /// The recipe can be built, but should be fixed
+    Warning = 2,
+    /// A suggestion
+    Hint = 4,
+}
+
+impl Serialize for Severity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// A problem of a recipe
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    /// The range of the problem
+    pub range: Range,
+    /// The severity of the problem
+    pub severity: Severity,
+    /// The tool that found the problem
+    pub source: &'static str,
+    /// The description of the problem
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(range: Range, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            severity,
+            source: "rattler-build",
+            message: message.into(),
+        }
+    }
+}
+
+/// The kind of a completion item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// A Jinja function
+    Function = 3,
+    /// A variable of the context or the variant configuration
+    Variable = 6,
+    /// A package name
+    Value = 12,
+    /// A key of the recipe
+    Property = 10,
+}
+
+impl Serialize for CompletionKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// A completion of the text at a position
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionItem {
+    /// The text of the completion
+    pub label: String,
+    /// The kind of the completion
+    pub kind: CompletionKind,
+    /// The documentation of the completion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<String>,
+}
+
+impl CompletionItem {
+    fn new(label: impl Into<String>, kind: CompletionKind, documentation: Option<&str>) -> Self {
+        Self {
+            label: label.into(),
+            kind,
+            documentation: documentation.map(str::to_string),
+        }
+    }
+}
+
+/// The requirement lists whose items are package names
+const REQUIREMENT_KEYS: &[&str] = &[
+    "requirements.build",
+    "requirements.host",
+    "requirements.run",
+    "requirements.run_constraints",
+    "tests.requirements.build",
+    "tests.requirements.run",
+];
+
+/// The range of a span of the YAML parser. Spans without an end cover the token at the start.
+fn span_range(text: &str, span: &Span) -> Range {
+    let Some(start) = span.start() else {
+        return Range::default();
+    };
+    let line = start.line().saturating_sub(1);
+    let character = start.column().saturating_sub(1);
+    match span.end() {
+        Some(end) if (end.line(), end.column()) > (start.line(), start.column()) => Range {
+            start: Position {
+                line: line as u32,
+                character: character as u32,
+            },
+            end: Position {
+                line: end.line().saturating_sub(1) as u32,
+                character: end.column().saturating_sub(1) as u32,
+            },
+        },
+        _ => {
+            let rest = text
+                .lines()
+                .nth(line)
+                .map(|line| line.chars().skip(character).collect::<String>())
+                .unwrap_or_default();
+            let length = rest
+                .find(|c: char| c.is_whitespace() || c == ':' || c == ',')
+                .unwrap_or(rest.len())
+                .max(1);
+            Range::in_line(line, character, character + length)
+        }
+    }
+}
+
+fn marker_range(text: &str, marker: &Marker) -> Range {
+    span_range(text, &Span::new_start(*marker))
+}
+
+fn load_error_range(text: &str, error: &LoadError) -> Range {
+    match error {
+        LoadError::TopLevelMustBeMapping(marker)
+        | LoadError::TopLevelMustBeSequence(marker)
+        | LoadError::UnexpectedAnchor(marker)
+        | LoadError::MappingKeyMustBeScalar(marker)
+        | LoadError::UnexpectedTag(marker)
+        | LoadError::ScanError(marker, _) => marker_range(text, marker),
+        LoadError::DuplicateKey(inner) => span_range(text, inner.key.span()),
+    }
+}
+
+fn parse_error(text: &str, error: &ParseError) -> Diagnostic {
+    let range = match error {
+        ParseError::IoError { .. } => Range::default(),
+        _ => span_range(text, error.span()),
+    };
+    let mut message = error.to_string();
+    if let Some(help) = miette::Diagnostic::help(error) {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+    Diagnostic::new(range, Severity::Error, message)
+}
+
+/// The diagnostics of a recipe: the YAML and recipe errors, or the lints if the recipe is valid
+pub fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let options = LoaderOptions::default()
+        .error_on_duplicate_keys(true)
+        .prevent_coercion(true);
+    let root = match marked_yaml::parse_yaml_with_options(0, text, options) {
+        Ok(root) => root,
+        Err(e) => {
+            return vec![Diagnostic::new(
+                load_error_range(text, &e),
+                Severity::Error,
+                format!("Failed to parse YAML: {e}"),
+            )];
+        }
+    };
+    if let Err(e) = stage0::parse_recipe_or_multi_from_source(text) {
+        return vec![parse_error(text, &e)];
+    }
+    root.as_mapping()
+        .map(|root| lint(text, root))
+        .unwrap_or_default()
+}
+
+fn key_span<'a>(mapping: &'a MarkedMappingNode, key: &str) -> Option<&'a Span> {
+    mapping.keys().find(|k| k.as_str() == key).map(|k| k.span())
+}
+
+fn has_license(mapping: &MarkedMappingNode) -> bool {
+    mapping
+        .get_mapping("about")
+        .is_some_and(|about| about.get_node("license").is_some())
+}
+
+/// Collect the mappings of the sources, also in conditionals and lists
+fn sources<'a>(node: &'a Node, sources: &mut Vec<&'a MarkedMappingNode>) {
+    match node {
+        Node::Mapping(mapping) => {
+            if mapping.get_node("if").is_some() {
+                for branch in ["then", "else"] {
+                    if let Some(node) = mapping.get_node(branch) {
+                        self::sources(node, sources);
+                    }
+                }
+            } else {
+                sources.push(mapping);
+            }
+        }
+        Node::Sequence(sequence) => sequence
+            .iter()
+            .for_each(|node| self::sources(node, sources)),
+        Node::Scalar(_) => {}
+    }
+}
+
+/// Lints of a recipe that parses, but should be fixed
+fn lint(text: &str, root: &MarkedMappingNode) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut outputs = vec![root];
+    if let Some(sequence) = root.get_sequence("outputs") {
+        outputs.extend(sequence.iter().filter_map(Node::as_mapping));
+    }
+    let mut source_nodes = Vec::new();
+    for output in &outputs {
+        if let Some(source) = output.get_node("source") {
+            sources(source, &mut source_nodes);
+        }
+    }
+    for source in source_nodes {
+        if let Some(url) = key_span(source, "url")
+            && source.get_node("sha256").is_none()
+            && source.get_node("md5").is_none()
+        {
+            diagnostics.push(Diagnostic::new(
+                span_range(text, url),
+                Severity::Warning,
+                "The source has no checksum, add a `sha256` to verify the download",
+            ));
+        }
+    }
+
+    // the outputs inherit the `about` section of a multi-output recipe
+    if !has_license(root) {
+        for output in &outputs {
+            if let Some(package) = key_span(output, "package")
+                && !has_license(output)
+            {
+                diagnostics.push(Diagnostic::new(
+                    span_range(text, package),
+                    Severity::Warning,
+                    "The package has no license, add `about.license`",
+                ));
+            }
+        }
+    }
+
+    if root.get_node("outputs").is_none()
+        && root.get_node("tests").is_none()
+        && let Some(package) = key_span(root, "package")
+    {
+        diagnostics.push(Diagnostic::new(
+            span_range(text, package),
+            Severity::Hint,
+            "The package has no tests",
+        ));
+    }
+    diagnostics
+}
+
+/// A line of a YAML document
+#[derive(Debug, Default)]
+struct Line<'a> {
+    /// The indentation of the content
+    indent: usize,
+    /// The column of the dash of a list item
+    dash: Option<usize>,
+    /// The column and name of the key of the line
+    key: Option<(usize, &'a str)>,
+    /// The value after the dash or the key
+    value: &'a str,
+    /// Whether the line is empty or a comment
+    blank: bool,
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+    if content.is_empty() || content.starts_with('#') {
+        return Line {
+            indent,
+            blank: true,
+            ..Default::default()
+        };
+    }
+    let (dash, start) = match content.strip_prefix('-') {
+        Some(rest) if rest.is_empty() || rest.starts_with(' ') => {
+            (Some(indent), line.len() - rest.trim_start().len())
+        }
+        _ => (None, indent),
+    };
+    let rest = &line[start..];
+    let key = rest.find(':').filter(|&end| {
+        let name = &rest[..end];
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-.".contains(c))
+            && rest[end + 1..].chars().next().is_none_or(|c| c == ' ')
+    });
+    let value = match key {
+        Some(end) => rest[end + 1..].trim(),
+        None => rest.trim(),
+    };
+    Line {
+        indent,
+        dash,
+        key: key.map(|end| (start, &rest[..end])),
+        value,
+        blank: false,
+    }
+}
+
+/// The keys of the mappings around an element of the document, from the outermost. Elements
+/// that are list items are inside mappings whose keys have at most the indentation of the dash,
+/// other elements are inside mappings whose keys have less indentation.
+fn parents(lines: &[&str], line: usize, mut indent: usize, mut list_item: bool) -> Vec<String> {
+    let mut parents = Vec::new();
+    for text in lines[..line].iter().rev() {
+        let parsed = parse_line(text);
+        let Some((column, key)) = parsed.key else {
+            continue;
+        };
+        let is_parent = if list_item {
+            column <= indent
+        } else {
+            column < indent
+        };
+        if !is_parent {
+            continue;
+        }
+        // the branches of conditionals are transparent
+        if !matches!(key, "then" | "else" | "if") {
+            parents.push(key.to_string());
+        }
+        (indent, list_item) = match parsed.dash {
+            Some(dash) => (dash, true),
+            None => (column, false),
+        };
+        if indent == 0 && !list_item {
+            break;
+        }
+    }
+    parents.reverse();
+    parents
+}
+
+/// The path of keys for the documentation: the keys of the outputs of a multi-output recipe
+/// have the same documentation as the keys of a single-output recipe
+fn doc_path(keys: &[String]) -> String {
+    let keys = match keys.first().map(String::as_str) {
+        Some("outputs" | "staging") if keys.len() > 1 => &keys[1..],
+        _ => keys,
+    };
+    keys.join(".")
+}
+
+/// The word (identifier) at a character of a line, and where it starts
+fn word_at(line: &str, character: usize) -> Option<(usize, &str)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let character = character.min(line.len());
+    let start = line[..character]
+        .rfind(|c: char| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = line[character..]
+        .find(|c: char| !is_word(c))
+        .map_or(line.len(), |i| character + i);
+    (start < end).then(|| (start, &line[start..end]))
+}
+
+/// Whether a character of a line is inside a Jinja expression `${{ ... }}`
+fn in_jinja(line: &str, character: usize) -> bool {
+    let before = &line[..character.min(line.len())];
+    before
+        .rfind("${{")
+        .is_some_and(|open| !before[open..].contains("}}"))
+}
+
+fn line_at<'a>(lines: &[&'a str], position: Position) -> Option<(&'a str, usize)> {
+    let line = lines.get(position.line as usize)?;
+    // positions past the end of the line are at the end of the line
+    let character = (position.character as usize).min(line.len());
+    line.is_char_boundary(character)
+        .then_some((line, character))
+}
+
+/// The documentation of the key or the Jinja function at a position, as markdown
+pub fn hover(text: &str, position: Position) -> Option<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let (line, character) = line_at(&lines, position)?;
+
+    if in_jinja(line, character) {
+        let (_, word) = word_at(line, character)?;
+        return docs::jinja_function(word).map(|doc| format!("**{word}**\n\n{doc}"));
+    }
+
+    let parsed = parse_line(line);
+    let (column, key) = parsed.key?;
+    if !(column..=column + key.len()).contains(&character) {
+        return None;
+    }
+    let mut keys = parents(
+        &lines,
+        position.line as usize,
+        parsed.dash.unwrap_or(column),
+        parsed.dash.is_some(),
+    );
+    keys.push(key.to_string());
+    let path = doc_path(&keys);
+    docs::key(&path).map(|doc| format!("**{path}**\n\n{doc}"))
+}
+
+/// The keys of the `context` of a recipe
+fn context_keys<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let mut keys = Vec::new();
+    let mut in_context = false;
+    for line in lines {
+        let parsed = parse_line(line);
+        if parsed.blank {
+            continue;
+        }
+        match parsed.key {
+            Some((0, key)) => in_context = key == "context",
+            Some((_, key)) if in_context => keys.push(key),
+            _ if parsed.indent == 0 => in_context = false,
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// What is completed at a position
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompletionContext {
+    /// A Jinja function or variable
+    Jinja,
+    /// A package name of a requirement list
+    Package(String),
+    /// A key of the mapping with the given path
+    Key(String),
+    /// Nothing is completed
+    None,
+}
+
+/// What is completed at a position of a document
+pub fn completion_context(text: &str, position: Position) -> CompletionContext {
+    let lines = text.lines().collect::<Vec<_>>();
+    // completions at the end of the document are after the last line
+    let line = lines.get(position.line as usize).copied().unwrap_or("");
+    let character = (position.character as usize).min(line.len());
+    if !line.is_char_boundary(character) {
+        return CompletionContext::None;
+    }
+    let before = &line[..character];
+    if in_jinja(line, character) {
+        return CompletionContext::Jinja;
+    }
+
+    let parsed = parse_line(before);
+    let line_index = (position.line as usize).min(lines.len());
+    if parsed.key.is_some() {
+        return CompletionContext::None;
+    }
+    if let Some(dash) = parsed.dash {
+        let path = doc_path(&parents(&lines, line_index, dash, true));
+        return if REQUIREMENT_KEYS.contains(&path.as_str()) {
+            CompletionContext::Package(parsed.value.to_string())
+        } else {
+            CompletionContext::None
+        };
+    }
+    if parsed.value.contains(' ') {
+        return CompletionContext::None;
+    }
+    let indent = if parsed.blank {
+        character
+    } else {
+        parsed.indent
+    };
+    CompletionContext::Key(doc_path(&parents(&lines, line_index, indent, false)))
+}
+
+/// The completions at a position of a document. `variables` are the keys of the variant
+/// configuration and `packages` the names of the packages of the channels.
+pub fn completions(
+    text: &str,
+    position: Position,
+    variables: &[String],
+    packages: &[String],
+) -> Vec<CompletionItem> {
+    match completion_context(text, position) {
+        CompletionContext::Jinja => {
+            let lines = text.lines().collect::<Vec<_>>();
+            let mut items = docs::JINJA_FUNCTIONS
+                .iter()
+                .map(|(name, doc)| {
+                    let kind = if doc.starts_with('`') {
+                        CompletionKind::Function
+                    } else {
+                        CompletionKind::Variable
+                    };
+                    CompletionItem::new(*name, kind, Some(doc))
+                })
+                .collect::<Vec<_>>();
+            for key in context_keys(&lines) {
+                items.push(CompletionItem::new(
+                    key,
+                    CompletionKind::Variable,
+                    Some("A variable of the `context`"),
+                ));
+            }
+            for key in variables {
+                items.push(CompletionItem::new(
+                    key,
+                    CompletionKind::Variable,
+                    Some("A key of the variant configuration"),
+                ));
+            }
+            items
+        }
+        CompletionContext::Package(prefix) => packages
+            .iter()
+            .filter(|name| name.starts_with(&prefix))
+            .take(MAX_PACKAGE_COMPLETIONS)
+            .map(|name| CompletionItem::new(name, CompletionKind::Value, None))
+            .collect(),
+        CompletionContext::Key(parent) => docs::children(&parent)
+            .map(|(name, doc)| CompletionItem::new(name, CompletionKind::Property, Some(doc)))
+            .collect(),
+        CompletionContext::None => Vec::new(),
+    }
+}
+
+/// The maximum number of package names that are completed at once
+pub const MAX_PACKAGE_COMPLETIONS: usize = 200;
+
+/// The file of a script (`script: build.sh`, `file: test.py` or an item of a script list) at a
+/// position of a document, relative to the directory of the recipe
+pub fn definition(text: &str, position: Position, recipe_dir: &Path) -> Option<PathBuf> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let (line, _) = line_at(&lines, position)?;
+    let parsed = parse_line(line);
+    let value = parsed.value.trim_matches(|c| c == '"' || c == '\'');
+    if value.is_empty() || value.contains("${{") {
+        return None;
+    }
+
+    let mut keys = match parsed.key {
+        Some((column, _)) => parents(
+            &lines,
+            position.line as usize,
+            parsed.dash.unwrap_or(column),
+            parsed.dash.is_some(),
+        ),
+        None => parents(
+            &lines,
+            position.line as usize,
+            parsed.dash.unwrap_or(parsed.indent),
+            parsed.dash.is_some(),
+        ),
+    };
+    if let Some((_, key)) = parsed.key {
+        keys.push(key.to_string());
+    }
+    if !keys.iter().any(|key| key == "script" || key == "file") {
+        return None;
+    }
+
+    let path = recipe_dir.join(value);
+    if path.is_file() {
+        return Some(path);
+    }
+    // `script: build` runs `build.sh` or `build.bat`
+    if path.extension().is_none() {
+        return ["sh", "bat"]
+            .iter()
+            .map(|extension| path.with_extension(extension))
+            .find(|path| path.is_file());
+    }
+    None
+}
+
+/// The keys of the variant configuration files next to a recipe
+pub fn variant_keys(recipe_dir: &Path) -> Vec<String> {
+    let mut keys = Vec::new();
+    for file in ["variants.yaml", "conda_build_config.yaml"] {
+        let Ok(content) = fs_err::read_to_string(recipe_dir.join(file)) else {
+            continue;
+        };
+        let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(&content) else {
+            continue;
+        };
+        for key in mapping.keys().filter_map(|key| key.as_str()) {
+            if !matches!(key, "zip_keys" | "pin_run_as_build") && !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPE: &str = r#"context:
+  version: "1.0"
+  name: foo
+
+package:
+  name: ${{ name }}
+  version: ${{ version }}
+
+source:
+  url: https://example.com/foo-${{ version }}.tar.gz
+
+build:
+  script: build.sh
+
+requirements:
+  build:
+    - ${{ compiler('c') }}
+  host:
+  - python
+"#;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = diagnostics(RECIPE);
+        let messages = diagnostics
+            .iter()
+            .map(|d| (d.range.start.line, d.severity, d.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    9,
+                    Severity::Warning,
+                    "The source has no checksum, add a `sha256` to verify the download"
+                ),
+                (
+                    4,
+                    Severity::Warning,
+                    "The package has no license, add `about.license`"
+                ),
+                (4, Severity::Hint, "The package has no tests"),
+            ]
+        );
+        assert_eq!(diagnostics[0].range, Range::in_line(9, 2, 5));
+
+        let diagnostics = super::diagnostics("package:\n  name: foo\n  name: bar\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].range.start.line, 2);
+
+        let diagnostics = super::diagnostics("package:\n  name: foo\n  versoin: 1.0\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_hover() {
+        let hover = hover(RECIPE, position(12, 3)).unwrap();
+        assert!(hover.starts_with("**build.script**"));
+        let hover = super::hover(RECIPE, position(16, 12)).unwrap();
+        assert!(hover.starts_with("**compiler**"));
+        assert_eq!(super::hover(RECIPE, position(18, 5)), None);
+
+        let multi = "outputs:\n  - package:\n      name: foo\n    requirements:\n      host:\n";
+        let hover = super::hover(multi, position(4, 7)).unwrap();
+        assert!(hover.starts_with("**requirements.host**"));
+    }
+
+    #[test]
+    fn test_completions() {
+        assert_eq!(
+            completion_context(RECIPE, position(5, 11)),
+            CompletionContext::Jinja
+        );
+        assert_eq!(
+            completion_context(RECIPE, position(18, 4)),
+            CompletionContext::Package(String::new())
+        );
+        assert_eq!(
+            completion_context(RECIPE, position(18, 6)),
+            CompletionContext::Package("py".to_string())
+        );
+        assert_eq!(
+            completion_context(RECIPE, position(19, 0)),
+            CompletionContext::Key(String::new())
+        );
+        assert_eq!(
+            completion_context("build:\n  nu\n", position(1, 4)),
+            CompletionContext::Key("build".to_string())
+        );
+
+        let items = completions(RECIPE, position(5, 11), &["cuda".to_string()], &[]);
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        for label in ["compiler", "version", "name", "cuda"] {
+            assert!(labels.contains(&label), "{label} is missing");
+        }
+
+        let packages = ["python", "pytest", "numpy"].map(String::from);
+        let items = completions(RECIPE, position(18, 6), &[], &packages);
+        let labels = items.iter().map(|i| i.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, ["python", "pytest"]);
+    }
+
+    #[test]
+    fn test_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(definition(RECIPE, position(12, 12), dir.path()), None);
+        fs_err::write(dir.path().join("build.sh"), "").unwrap();
+        assert_eq!(
+            definition(RECIPE, position(12, 12), dir.path()),
+            Some(dir.path().join("build.sh"))
+        );
+        // not a script
+        assert_eq!(definition(RECIPE, position(5, 12), dir.path()), None);
+    }
+
+    #[test]
+    fn test_variant_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(
+            dir.path().join("variants.yaml"),
+            "python: ['3.12']\nzip_keys: [[python, numpy]]\nnumpy: ['2']\n",
+        )
+        .unwrap();
+        assert_eq!(variant_keys(dir.path()), ["python", "numpy"]);
+    }
+}
@@ -0,0 +1,335 @@
+//! The documentation of the recipe keys and the Jinja functions that is shown on hover and in
+//! completions.
+
+/// The keys of a recipe by their path (e.g. `build.script`) and their documentation. The keys of
+/// an output of a multi-output recipe have the same paths as the keys of a single-output recipe.
+pub const KEYS: &[(&str, &str)] = &[
+    (
+        "schema_version",
+        "The version of the recipe format, currently `1`.",
+    ),
+    (
+        "context",
+        "Variables that can be used in the Jinja expressions of the recipe, e.g. `${{ version }}`.",
+    ),
+    ("package", "The name and version of the package."),
+    ("package.name", "The name of the package."),
+    ("package.version", "The version of the package."),
+    (
+        "recipe",
+        "The name and version of a multi-output recipe, the outputs inherit the version.",
+    ),
+    ("recipe.name", "The name of the recipe."),
+    (
+        "recipe.version",
+        "The version of the recipe, inherited by the outputs.",
+    ),
+    (
+        "source",
+        "The sources of the package: archives (`url`), git repositories (`git`) or local paths (`path`).",
+    ),
+    (
+        "source.url",
+        "The URL (or a list of mirrors) of a source archive or file.",
+    ),
+    (
+        "source.sha256",
+        "The SHA256 checksum of the source archive.",
+    ),
+    ("source.md5", "The MD5 checksum of the source archive."),
+    (
+        "source.file_name",
+        "The file name of a downloaded file that is not extracted.",
+    ),
+    ("source.git", "The URL of a git repository."),
+    ("source.rev", "The git revision (commit) to check out."),
+    ("source.tag", "The git tag to check out."),
+    ("source.branch", "The git branch to check out."),
+    ("source.depth", "The depth of the git clone."),
+    ("source.lfs", "Whether to fetch the git LFS files."),
+    (
+        "source.expected_commit",
+        "The commit that the git tag or branch is expected to point to.",
+    ),
+    (
+        "source.path",
+        "A local path, relative to the recipe, to use as source.",
+    ),
+    (
+        "source.use_gitignore",
+        "Whether to skip the files of a local path that are ignored by `.gitignore` (default `true`).",
+    ),
+    (
+        "source.patches",
+        "Patches to apply to the source, relative to the recipe.",
+    ),
+    (
+        "source.target_directory",
+        "The directory in the work directory that the source is placed in.",
+    ),
+    (
+        "source.filter",
+        "Glob patterns to include or exclude files of the source.",
+    ),
+    ("build", "How the package is built."),
+    (
+        "build.number",
+        "The build number of the package (default `0`).",
+    ),
+    (
+        "build.string",
+        "The build string of the package (defaults to the variant hash and the build number).",
+    ),
+    (
+        "build.script",
+        "The build script: a list of commands, a file (e.g. `build.sh`) or a mapping with `interpreter`, `env`, `secrets`, `content` or `file`.",
+    ),
+    (
+        "build.noarch",
+        "Build an architecture independent package: `python` or `generic`.",
+    ),
+    ("build.skip", "Conditions under which the build is skipped."),
+    (
+        "build.python",
+        "Python specific options: `entry_points`, `use_python_app_entrypoint`, `version_independent`, `site_packages_path`.",
+    ),
+    (
+        "build.python.entry_points",
+        "Entry points of the package, e.g. `mycli = mypackage.cli:main`.",
+    ),
+    (
+        "build.python.version_independent",
+        "Build the package once for all Python versions (abi3).",
+    ),
+    (
+        "build.files",
+        "Glob patterns of the files that are included in the package.",
+    ),
+    (
+        "build.always_copy_files",
+        "Glob patterns of the files that are always copied instead of hard linked.",
+    ),
+    (
+        "build.always_include_files",
+        "Glob patterns of the files that are included even if they are already in the host environment.",
+    ),
+    (
+        "build.merge_build_and_host_envs",
+        "Use a single environment for the build and host requirements.",
+    ),
+    (
+        "build.dynamic_linking",
+        "Options of the dynamic linking checks: `rpaths`, `binary_relocation`, `missing_dso_allowlist`, `overdepending_behavior`, ...",
+    ),
+    (
+        "build.variant",
+        "Options of the variant: `use_keys`, `ignore_keys` and `down_prioritize_variant`.",
+    ),
+    (
+        "build.variant.use_keys",
+        "Variant keys that are used for the package even if they are not dependencies.",
+    ),
+    (
+        "build.variant.ignore_keys",
+        "Variant keys that are ignored for the hash of the package.",
+    ),
+    (
+        "build.variant.down_prioritize_variant",
+        "Make the solver prefer other variants of the package.",
+    ),
+    (
+        "build.prefix_detection",
+        "Options of the detection and replacement of the build prefix in the files of the package.",
+    ),
+    (
+        "build.post_process",
+        "Regular expressions to replace in the files of the package.",
+    ),
+    ("requirements", "The dependencies of the package."),
+    (
+        "requirements.build",
+        "Dependencies that run on the build platform, e.g. compilers and build tools.",
+    ),
+    (
+        "requirements.host",
+        "Dependencies of the target platform that are needed to build the package, e.g. libraries to link against.",
+    ),
+    (
+        "requirements.run",
+        "Dependencies that are installed with the package.",
+    ),
+    (
+        "requirements.run_constraints",
+        "Constraints on packages that are installed together with the package, without depending on them.",
+    ),
+    (
+        "requirements.run_exports",
+        "Dependencies that are added to the packages that depend on this package.",
+    ),
+    (
+        "requirements.ignore_run_exports",
+        "Run exports to ignore, `by_name` or `from_package`.",
+    ),
+    ("tests", "The tests of the package."),
+    (
+        "tests.script",
+        "Commands or a script file that test the package.",
+    ),
+    (
+        "tests.requirements",
+        "The `run` and `build` requirements of a script test.",
+    ),
+    (
+        "tests.files",
+        "The `source` and `recipe` files that are copied for a script test.",
+    ),
+    (
+        "tests.python",
+        "Test that Python modules can be imported (`imports`, `pip_check`).",
+    ),
+    (
+        "tests.python.imports",
+        "The Python modules that are imported.",
+    ),
+    (
+        "tests.python.pip_check",
+        "Whether to run `pip check` (default `true`).",
+    ),
+    (
+        "tests.package_contents",
+        "Check that the package contains (or does not contain) files: `files`, `site_packages`, `bin`, `lib`, `include`.",
+    ),
+    (
+        "tests.downstream",
+        "A package that depends on this package and whose tests are run.",
+    ),
+    ("tests.perl", "Test that Perl modules can be used (`uses`)."),
+    (
+        "tests.r",
+        "Test that R libraries can be loaded (`libraries`).",
+    ),
+    ("about", "Information about the package."),
+    ("about.homepage", "The homepage of the project."),
+    (
+        "about.repository",
+        "The source code repository of the project.",
+    ),
+    ("about.documentation", "The documentation of the project."),
+    (
+        "about.license",
+        "The SPDX license expression of the package, e.g. `BSD-3-Clause`.",
+    ),
+    (
+        "about.license_file",
+        "The license files that are included in the package, relative to the source or the recipe.",
+    ),
+    ("about.license_family", "The license family (deprecated)."),
+    ("about.summary", "A short summary of the package."),
+    ("about.description", "A longer description of the package."),
+    ("extra", "Extra metadata, e.g. the `recipe-maintainers`."),
+    (
+        "outputs",
+        "The outputs of a multi-output recipe, each with its own `package`, `build`, `requirements`, `tests` and `about`.",
+    ),
+    (
+        "staging",
+        "A staging output that is built once and whose files are inherited by other outputs.",
+    ),
+    (
+        "inherit",
+        "The staging output that an output inherits its files from.",
+    ),
+];
+
+/// The Jinja functions and variables of recipes with their documentation
+pub const JINJA_FUNCTIONS: &[(&str, &str)] = &[
+    (
+        "compiler",
+        "`compiler('c')`: the compiler package of a language for the target platform, from the `<lang>_compiler` and `<lang>_compiler_version` variant keys.",
+    ),
+    (
+        "stdlib",
+        "`stdlib('c')`: the standard library package for the target platform, from the `<lang>_stdlib` and `<lang>_stdlib_version` variant keys.",
+    ),
+    (
+        "pin_subpackage",
+        "`pin_subpackage('name', upper_bound='x.x', exact=False)`: a dependency on another output of the recipe.",
+    ),
+    (
+        "pin_compatible",
+        "`pin_compatible('name', upper_bound='x.x')`: a run dependency on the version of a package in the host environment.",
+    ),
+    (
+        "cdt",
+        "`cdt('name')`: the name of a Core Dependency Tree package for the target platform.",
+    ),
+    (
+        "match",
+        "`match(python, '>=3.10')`: whether a variant value matches a version spec.",
+    ),
+    (
+        "load_from_file",
+        "`load_from_file('pyproject.toml')`: load a JSON, YAML or TOML file relative to the recipe (experimental).",
+    ),
+    (
+        "is_linux",
+        "`is_linux(platform)`: whether the platform is a Linux platform.",
+    ),
+    (
+        "is_osx",
+        "`is_osx(platform)`: whether the platform is a macOS platform.",
+    ),
+    (
+        "is_windows",
+        "`is_windows(platform)`: whether the platform is a Windows platform.",
+    ),
+    (
+        "is_unix",
+        "`is_unix(platform)`: whether the platform is a Unix platform.",
+    ),
+    (
+        "env",
+        "`env.get('NAME', default='')` and `env.exists('NAME')`: read environment variables.",
+    ),
+    (
+        "target_platform",
+        "The platform that the package is built for, e.g. `linux-64`.",
+    ),
+    (
+        "build_platform",
+        "The platform that the package is built on.",
+    ),
+    ("host_platform", "The platform of the host environment."),
+    ("hash", "The hash of the variant of the package."),
+    ("linux", "Whether the target platform is a Linux platform."),
+    ("osx", "Whether the target platform is a macOS platform."),
+    ("win", "Whether the target platform is a Windows platform."),
+    ("unix", "Whether the target platform is a Unix platform."),
+];
+
+/// The documentation of a key
+pub fn key(path: &str) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(key, _)| *key == path)
+        .map(|(_, doc)| *doc)
+}
+
+/// The documentation of a Jinja function or variable
+pub fn jinja_function(name: &str) -> Option<&'static str> {
+    JINJA_FUNCTIONS
+        .iter()
+        .find(|(function, _)| *function == name)
+        .map(|(_, doc)| *doc)
+}
+
+/// The child keys of a key (the top-level keys for `""`)
+pub fn children(parent: &str) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+    KEYS.iter().filter_map(move |(key, doc)| {
+        let name = if parent.is_empty() {
+            key
+        } else {
+            key.strip_prefix(parent)?.strip_prefix('.')?
+        };
+        (!name.contains('.')).then_some((name, *doc))
+    })
+}
@@ -0,0 +1,444 @@
+//! A language server for recipes (`rattler-build lsp`).
+//!
+//! Editors start the server and talk to it with the Language Server Protocol: JSON-RPC 2.0
+//! messages with a `Content-Length` header on stdin and stdout (the logs are written to
+//! stderr). The server publishes the diagnostics of the open recipes (parse errors and lints),
+//! shows the documentation of the keys and Jinja functions on hover, completes keys, Jinja
+//! functions, variant variables and the package names of the configured channels, and goes to
+//! the script files of the recipe.
+//!
+//! The features work on the text of the recipe, see [`analysis`].
+pub mod analysis;
+pub mod docs;
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use miette::IntoDiagnostic;
+use rattler_conda_types::{Channel, NamedChannelOrUrl, Platform};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    build_backend::{
+        INVALID_PARAMS, METHOD_NOT_FOUND, PARSE_ERROR, Request, Response, ResponseError,
+        parse_params,
+    },
+    config, get_tool_config,
+    opt::{BuildData, BuildOpts},
+};
+use analysis::{CompletionContext, Position};
+
+/// The parameters of `initialize`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeParams {
+    #[serde(default)]
+    root_uri: Option<String>,
+    #[serde(default)]
+    initialization_options: Option<InitializationOptions>,
+}
+
+/// The options of the client, e.g. `{"channels": ["conda-forge", "bioconda"]}`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializationOptions {
+    /// The channels whose package names are completed (default `conda-forge`)
+    #[serde(default)]
+    channels: Option<Vec<NamedChannelOrUrl>>,
+    /// The configuration file of rattler-build
+    #[serde(default)]
+    config_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentPositionParams {
+    text_document: TextDocumentIdentifier,
+    position: Position,
+}
+
+#[derive(Debug, Deserialize)]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+
+/// The package names of the channels, which are loaded in the background on the first
+/// completion of a requirement
+#[derive(Debug, Default)]
+enum PackageNames {
+    #[default]
+    NotLoaded,
+    Loading,
+    Loaded(Vec<String>),
+}
+
+/// The state of the language server between messages
+#[derive(Debug, Default)]
+pub struct LanguageServer {
+    documents: HashMap<String, String>,
+    build_data: Option<BuildData>,
+    package_names: Arc<Mutex<PackageNames>>,
+    exit: bool,
+}
+
+/// The path of a `file://` URI
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    url::Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// The package names of the channels of a build
+async fn load_package_names(build_data: &BuildData) -> miette::Result<Vec<String>> {
+    let tool_config = get_tool_config(build_data, &None)?;
+    let channels = build_data
+        .channels
+        .clone()
+        .unwrap_or(vec![NamedChannelOrUrl::Name("conda-forge".to_string())])
+        .into_iter()
+        .map(|c| {
+            c.into_base_url(&tool_config.channel_config)
+                .map(Channel::from_url)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+    let names = tool_config
+        .repodata_gateway
+        .names(channels, [build_data.target_platform, Platform::NoArch])
+        .await
+        .into_diagnostic()?;
+    let mut names = names
+        .into_iter()
+        .map(|name| name.as_normalized().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+impl LanguageServer {
+    /// Create a server that is not initialized yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle one message. Returns the messages to send: the response of a request and the
+    /// notifications (e.g. the diagnostics of a changed document).
+    pub fn handle_message(&mut self, request: Request) -> Vec<Value> {
+        let mut messages = Vec::new();
+        let result = match request.method.as_str() {
+            "initialize" => parse_params(request.params).map(|params| self.initialize(params)),
+            "shutdown" => Ok(Value::Null),
+            "exit" => {
+                self.exit = true;
+                Ok(Value::Null)
+            }
+            "textDocument/didOpen" => {
+                let document = &request.params["textDocument"];
+                if let (Some(uri), Some(text)) =
+                    (document["uri"].as_str(), document["text"].as_str())
+                {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    messages.push(self.diagnostics(uri));
+                }
+                Ok(Value::Null)
+            }
+            "textDocument/didChange" => {
+                // the server asks for the full text of the document on every change
+                let uri = request.params["textDocument"]["uri"].as_str();
+                let text = request.params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    messages.push(self.diagnostics(uri));
+                }
+                Ok(Value::Null)
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = request.params["textDocument"]["uri"].as_str() {
+                    self.documents.remove(uri);
+                    messages.push(notification(
+                        "textDocument/publishDiagnostics",
+                        json!({ "uri": uri, "diagnostics": [] }),
+                    ));
+                }
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => parse_params(request.params).map(|params| self.hover(params)),
+            "textDocument/completion" => {
+                parse_params(request.params).map(|params| self.completion(params))
+            }
+            "textDocument/definition" => {
+                parse_params(request.params).map(|params| self.definition(params))
+            }
+            method => Err(ResponseError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{method}`"),
+            )),
+        };
+        // notifications have no response
+        if let Some(id) = request.id {
+            let response = Response::new(id, result);
+            messages.insert(0, serde_json::to_value(response).unwrap_or_default());
+        }
+        messages
+    }
+
+    fn initialize(&mut self, params: InitializeParams) -> Value {
+        let options = params.initialization_options.unwrap_or_default();
+        let root = params.root_uri.as_deref().and_then(uri_to_path);
+        let config = match config::load_config(root.as_deref(), options.config_file.as_deref()) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("Failed to load the configuration: {e}");
+                None
+            }
+        };
+        let opts = BuildOpts {
+            channels: options.channels,
+            ..Default::default()
+        };
+        self.build_data = Some(BuildData::from_opts_and_config(opts, config));
+        json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "completionProvider": { "triggerCharacters": ["{", " ", "-"] },
+                "definitionProvider": true,
+            },
+            "serverInfo": {
+                "name": "rattler-build",
+                "version": crate::get_rattler_build_version(),
+            },
+        })
+    }
+
+    fn diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map(String::as_str).unwrap_or("");
+        notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": analysis::diagnostics(text) }),
+        )
+    }
+
+    fn document(&self, uri: &str) -> Result<&str, ResponseError> {
+        self.documents
+            .get(uri)
+            .map(String::as_str)
+            .ok_or_else(|| ResponseError::new(INVALID_PARAMS, format!("unknown document `{uri}`")))
+    }
+
+    fn hover(&self, params: TextDocumentPositionParams) -> Value {
+        let Ok(text) = self.document(&params.text_document.uri) else {
+            return Value::Null;
+        };
+        match analysis::hover(text, params.position) {
+            Some(value) => json!({ "contents": { "kind": "markdown", "value": value } }),
+            None => Value::Null,
+        }
+    }
+
+    /// The package names of the channels, or `None` while they are loaded
+    fn package_names(&self) -> Option<Vec<String>> {
+        let mut names = self.package_names.lock().unwrap();
+        match &*names {
+            PackageNames::Loaded(names) => return Some(names.clone()),
+            PackageNames::Loading => return None,
+            PackageNames::NotLoaded => *names = PackageNames::Loading,
+        }
+        let Some(build_data) = self.build_data.clone() else {
+            *names = PackageNames::Loaded(Vec::new());
+            return Some(Vec::new());
+        };
+        let package_names = self.package_names.clone();
+        tokio::spawn(async move {
+            let names = load_package_names(&build_data).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to load the package names of the channels: {e}");
+                Vec::new()
+            });
+            *package_names.lock().unwrap() = PackageNames::Loaded(names);
+        });
+        None
+    }
+
+    fn completion(&self, params: TextDocumentPositionParams) -> Value {
+        let uri = &params.text_document.uri;
+        let Ok(text) = self.document(uri) else {
+            return Value::Null;
+        };
+        let recipe_dir = uri_to_path(uri).and_then(|path| path.parent().map(Path::to_path_buf));
+        let (variables, packages, incomplete) =
+            match analysis::completion_context(text, params.position) {
+                CompletionContext::Jinja => (
+                    recipe_dir
+                        .as_deref()
+                        .map(analysis::variant_keys)
+                        .unwrap_or_default(),
+                    Vec::new(),
+                    false,
+                ),
+                // the client asks again while the names are loaded or the list is truncated
+                CompletionContext::Package(_) => match self.package_names() {
+                    Some(names) => (Vec::new(), names, true),
+                    None => (Vec::new(), Vec::new(), true),
+                },
+                _ => (Vec::new(), Vec::new(), false),
+            };
+        let items = analysis::completions(text, params.position, &variables, &packages);
+        json!({ "isIncomplete": incomplete, "items": items })
+    }
+
+    fn definition(&self, params: TextDocumentPositionParams) -> Value {
+        let uri = &params.text_document.uri;
+        let (Ok(text), Some(path)) = (self.document(uri), uri_to_path(uri)) else {
+            return Value::Null;
+        };
+        let recipe_dir = path.parent().unwrap_or(Path::new("."));
+        analysis::definition(text, params.position, recipe_dir)
+            .and_then(|path| url::Url::from_file_path(path).ok())
+            .map(|target| {
+                json!({
+                    "uri": target,
+                    "range": analysis::Range::default(),
+                })
+            })
+            .unwrap_or(Value::Null)
+    }
+}
+
+/// Read one message with a `Content-Length` header, `None` at the end of the input
+async fn read_message(reader: &mut (impl AsyncBufRead + Unpin)) -> miette::Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await.into_diagnostic()? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = Some(value.trim().parse::<usize>().into_diagnostic()?);
+        }
+    }
+    let length =
+        content_length.ok_or_else(|| miette::miette!("the message has no Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await.into_diagnostic()?;
+    String::from_utf8(body).into_diagnostic().map(Some)
+}
+
+async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &Value,
+) -> miette::Result<()> {
+    let body = serde_json::to_string(message).into_diagnostic()?;
+    let message = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    writer
+        .write_all(message.as_bytes())
+        .await
+        .into_diagnostic()?;
+    writer.flush().await.into_diagnostic()
+}
+
+/// Serve the language server: read the messages from `reader` and write the responses and
+/// notifications to `writer`, until `exit` or the end of the input
+pub async fn serve(
+    mut reader: impl AsyncBufRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+) -> miette::Result<()> {
+    let mut server = LanguageServer::new();
+    while let Some(body) = read_message(&mut reader).await? {
+        let messages = match serde_json::from_str::<Request>(&body) {
+            Ok(request) => server.handle_message(request),
+            Err(e) => {
+                let response = Response::new(
+                    Value::Null,
+                    Err(ResponseError::new(PARSE_ERROR, e.to_string())),
+                );
+                vec![serde_json::to_value(response).into_diagnostic()?]
+            }
+        };
+        for message in &messages {
+            write_message(&mut writer, message).await?;
+        }
+        if server.exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(method: &str, id: Option<i64>, params: Value) -> String {
+        let mut message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        if let Some(id) = id {
+            message["id"] = Value::from(id);
+        }
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{body}", body.len())
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let uri = "file:///recipe/recipe.yaml";
+        let input = [
+            message("initialize", Some(1), json!({ "capabilities": {} })),
+            message("initialized", None, json!({})),
+            message(
+                "textDocument/didOpen",
+                None,
+                json!({ "textDocument": { "uri": uri, "text": "package:\n  nme: foo\n" } }),
+            ),
+            message(
+                "textDocument/hover",
+                Some(2),
+                json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 2 } }),
+            ),
+            message(
+                "textDocument/completion",
+                Some(3),
+                json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 2 } }),
+            ),
+            message("shutdown", Some(4), Value::Null),
+            message("exit", None, Value::Null),
+        ]
+        .concat();
+
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).await.unwrap();
+
+        let mut reader = output.as_slice();
+        let mut messages = Vec::new();
+        while let Some(body) = read_message(&mut reader).await.unwrap() {
+            messages.push(serde_json::from_str::<Value>(&body).unwrap());
+        }
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(messages[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(messages[1]["params"]["diagnostics"][0]["severity"], 1);
+        assert!(
+            messages[2]["result"]["contents"]["value"]
+                .as_str()
+                .unwrap()
+                .starts_with("**package**")
+        );
+        let items = messages[3]["result"]["items"].as_array().unwrap();
+        assert!(items.iter().any(|item| item["label"] == "version"));
+        assert_eq!(messages[4]["id"], 4);
+    }
+}
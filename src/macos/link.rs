@@ -6,14 +6,14 @@ use goblin::mach::header::{
 };
 use itertools::Itertools;
 use memmap2::MmapMut;
-use rattler_build_recipe::stage1::GlobVec;
+use rattler_build_recipe::stage1::{GlobVec, build::RpathType};
 use scroll::Pread;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
 
-use crate::post_process::relink::{RelinkError, Relinker};
+use crate::post_process::relink::{CustomRpath, RelinkError, Relinker};
 use crate::system_tools::{SystemTools, Tool};
 use crate::unix::permission_guard::{PermissionGuard, READ_WRITE};
 use crate::utils::to_lexical_absolute;
@@ -31,6 +31,13 @@ pub struct Dylib {
     id: Option<PathBuf>,
 }
 
+impl Dylib {
+    /// The `LC_RPATH` entries of the binary
+    pub fn rpaths(&self) -> &[PathBuf] {
+        &self.rpaths
+    }
+}
+
 impl Relinker for Dylib {
     /// Parse the magic number of a file and check if it
     /// is a Mach-O file that should be relinked.
@@ -179,12 +186,15 @@ impl Relinker for Dylib {
     /// * `dylib_path` - Path to the dylib to modify
     /// * `prefix` - The prefix of the file (usually a temporary directory)
     /// * `encoded_prefix` - The prefix of the file as encoded in the dylib at build time (e.g. the host prefix)
+    /// * `custom_rpaths` - Rpaths from the recipe. Entries relative to `@loader_path` (or `$ORIGIN`) and
+    ///   absolute entries are kept as they are, all others are relative to the prefix
     fn relink(
         &self,
         prefix: &Path,
         encoded_prefix: &Path,
         custom_rpaths: &[String],
         rpath_allowlist: &GlobVec,
+        _rpath_type: RpathType,
         system_tools: &SystemTools,
     ) -> Result<(), RelinkError> {
         let mut changes = DylibChanges::default();
@@ -196,10 +206,15 @@ impl Relinker for Dylib {
             .map(|rpath| self.resolve_rpath(rpath, prefix, encoded_prefix))
             .collect::<Vec<_>>();
         let mut new_rpaths = self.rpaths.clone();
+        let mut keep = HashSet::new();
 
         for rpath in custom_rpaths.iter().rev() {
-            let rpath = encoded_prefix.join(rpath);
-            if !resolved_rpaths.contains(&rpath) {
+            let rpath = CustomRpath::new(rpath, encoded_prefix, "@loader_path");
+            if let CustomRpath::Keep(path) = &rpath {
+                keep.insert(path.clone());
+            }
+            let rpath = rpath.path().to_path_buf();
+            if !resolved_rpaths.contains(&rpath) && !new_rpaths.contains(&rpath) {
                 tracing::debug!("Adding rpath: {:?}", rpath);
                 new_rpaths.insert(0, rpath);
             }
@@ -208,7 +223,10 @@ impl Relinker for Dylib {
         let mut final_rpaths = Vec::new();
 
         for rpath in &new_rpaths {
-            if rpath.starts_with("@loader_path") {
+            if keep.contains(rpath) {
+                tracing::info!("Keeping rpath {} from the recipe", rpath.display());
+                final_rpaths.push(rpath.clone());
+            } else if rpath.starts_with("@loader_path") {
                 let resolved = self.resolve_rpath(rpath, prefix, encoded_prefix);
                 if resolved.starts_with(encoded_prefix) {
                     final_rpaths.push(rpath.clone());
@@ -273,9 +291,9 @@ impl Relinker for Dylib {
 
         // find the first rpath that looks like `lib/` and extends the prefix
         // by default, the first element of custom_rpaths is `lib/`
-        let base_rpath = custom_rpaths
-            .iter()
-            .find(|r| !r.contains("@") && !r.starts_with('/') && !r.starts_with('.'));
+        let base_rpath = custom_rpaths.iter().find(|r| {
+            !r.contains("@") && !r.starts_with('$') && !r.starts_with('/') && !r.starts_with('.')
+        });
 
         let exchange_dylib = |path: &Path| {
             // treat 'libfoo.dylib' the same as $PREFIX/lib/libfoo.dylib
@@ -865,6 +883,7 @@ mod tests {
                 &encoded_prefix,
                 &[],
                 &GlobVec::default(),
+                RpathType::default(),
                 &SystemTools::default(),
             )
             .unwrap();
@@ -958,6 +977,7 @@ mod tests {
                 tmp_prefix,
                 &["lib/".to_string()],
                 &GlobVec::default(),
+                RpathType::default(),
                 &SystemTools::default(),
             )
             .unwrap();
@@ -14,26 +14,28 @@ use std::{
 };
 
 use clap::{CommandFactory, Parser};
+use futures::StreamExt;
 use miette::IntoDiagnostic;
 use rattler_build::{
-    build_recipes, bump_recipe,
+    abi_diff_package, audit_packages, build_recipes, bump_recipe, clean_builds,
     console_utils::init_logging,
-    debug_recipe, extract_package, get_recipe_path, migrate_recipe,
+    debug_recipe, dependency_graph, error_code, events, explain_rebuild, extract_package,
+    generate_ci, get_recipe_path, index_channel, list_outputs, migrate_recipe,
     opt::{
-        App, BuildData, BumpRecipeOpts, DebugData, DebugSubCommands, MigrateRecipeOpts,
-        PackageCommands, PublishData, RebuildData, ShellCompletion, SubCommands, TestData,
+        App, BuildData, BumpRecipeOpts, CheckUpdatesOpts, DebugData, DebugSubCommands,
+        MigrateRecipeOpts, PackageCommands, PublishData, RebuildData, SchemaFormat, SchemaOpts,
+        ShellCompletion, SubCommands, TestData,
     },
     publish_packages, rebuild, run_test, show_package_info,
     tool_configuration::APP_USER_AGENT,
+    upload_packages, watch_recipes,
 };
-use rattler_config::config::ConfigBase;
-use rattler_upload::upload_from_args;
 use tempfile::{TempDir, tempdir};
 
 /// Run the bump-recipe command
 async fn run_bump_recipe(opts: BumpRecipeOpts) -> miette::Result<()> {
     // Resolve recipe path
-    let recipe_path = get_recipe_path(&opts.recipe)?;
+    let recipe_path = get_recipe_path(opts.recipe_path.as_ref().unwrap_or(&opts.recipe))?;
 
     // Create a simple HTTP client
     let client = reqwest::Client::builder()
@@ -87,6 +89,35 @@ async fn run_bump_recipe(opts: BumpRecipeOpts) -> miette::Result<()> {
     Ok(())
 }
 
+/// Run the check-updates command
+async fn run_check_updates(opts: CheckUpdatesOpts) -> miette::Result<()> {
+    let (recipe_paths, _temp_dir) = recipe_paths(opts.recipes, opts.recipe_dir.as_ref())?;
+
+    let client = reqwest::Client::builder()
+        .user_agent(APP_USER_AGENT)
+        .referer(false)
+        .build()
+        .into_diagnostic()?;
+
+    let checks = futures::stream::iter(recipe_paths.iter())
+        .map(|recipe_path| {
+            bump_recipe::check_recipe_for_updates(recipe_path, &client, opts.include_prerelease)
+        })
+        .buffered(8)
+        .collect::<Vec<_>>()
+        .await;
+
+    if opts.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&checks).into_diagnostic()?
+        );
+    } else {
+        bump_recipe::print_update_checks(&checks);
+    }
+    Ok(())
+}
+
 /// Run the migrate-recipe command
 fn run_migrate_recipe(opts: MigrateRecipeOpts) -> miette::Result<()> {
     let recipe_path = get_recipe_path(&opts.recipe)?;
@@ -110,6 +141,22 @@ fn run_migrate_recipe(opts: MigrateRecipeOpts) -> miette::Result<()> {
     Ok(())
 }
 
+/// Run the schema command
+fn run_schema(opts: SchemaOpts) -> miette::Result<()> {
+    let schema = match opts.format {
+        SchemaFormat::JsonSchema => rattler_build_recipe::schema::recipe_schema(),
+    };
+    let mut schema = serde_json::to_string_pretty(&schema).into_diagnostic()?;
+    schema.push('\n');
+    match opts.output {
+        Some(path) => fs_err::write(path, schema).into_diagnostic(),
+        None => {
+            print!("{schema}");
+            Ok(())
+        }
+    }
+}
+
 fn main() -> miette::Result<()> {
     // Stack size varies significantly across platforms:
     // - Windows: only 1MB by default
@@ -132,22 +179,39 @@ fn main() -> miette::Result<()> {
                 .enable_all()
                 .build()
                 .unwrap()
-                .block_on(async { async_main().await })
+                .block_on(async {
+                    // the error is also reported as event for wrappers that only read stdout
+                    async_main()
+                        .await
+                        .inspect_err(|e| events::emit(|| events::Event::error(e)))
+                })
         })
         .map_err(|e| miette::miette!("Failed to spawn thread: {}", e))?;
 
-    thread_handle
+    let result = thread_handle
         .join()
-        .map_err(|_| miette::miette!("Thread panicked"))?
+        .map_err(|_| miette::miette!("Thread panicked"))?;
+
+    // failures with an error code have their own exit code
+    if let Err(e) = &result {
+        let exit_code = error_code::exit_code(e);
+        if exit_code != 1 {
+            eprintln!("Error: {e:?}");
+            std::process::exit(exit_code);
+        }
+    }
+    result
 }
 
 async fn async_main() -> miette::Result<()> {
     let app = App::parse();
+    events::set_output_format(app.output_format);
     let log_handler = if !app.is_tui() {
         Some(
             init_logging(
                 &app.log_style,
                 &app.verbose,
+                &app.log_filter,
                 &app.color,
                 app.wrap_log_lines,
                 #[cfg(feature = "tui")]
@@ -162,11 +226,10 @@ async fn async_main() -> miette::Result<()> {
         None
     };
 
-    let config = if let Some(config_path) = app.config_file {
-        Some(ConfigBase::<()>::load_from_files(&[config_path]).into_diagnostic()?)
-    } else {
-        None
-    };
+    let config = rattler_build::config::load_config(
+        Some(&app.config_search_path()),
+        app.config_file.as_deref(),
+    )?;
 
     match app.subcommand {
         Some(SubCommands::Completion(ShellCompletion { shell })) => {
@@ -189,6 +252,7 @@ async fn async_main() -> miette::Result<()> {
         Some(SubCommands::Build(build_args)) => {
             let recipes = build_args.recipes.clone();
             let recipe_dir = build_args.recipe_dir.clone();
+            let watch = build_args.watch;
             let build_data = BuildData::from_opts_and_config(build_args, config);
 
             // Get all recipe paths and keep tempdir alive until end of the function
@@ -209,6 +273,7 @@ async fn async_main() -> miette::Result<()> {
                     let log_handler = init_logging(
                         &app.log_style,
                         &app.verbose,
+                        &app.log_filter,
                         &app.color,
                         Some(true),
                         Some(tui.event_handler.sender.clone()),
@@ -219,11 +284,27 @@ async fn async_main() -> miette::Result<()> {
                 return Ok(());
             }
 
+            if watch {
+                return watch_recipes(recipe_paths, build_data, &log_handler).await;
+            }
+
             build_recipes(recipe_paths, build_data, &log_handler).await
         }
 
+        Some(SubCommands::Graph(graph_args)) => {
+            let (recipe_paths, _temp_dir) =
+                recipe_paths(graph_args.recipes.clone(), graph_args.recipe_dir.as_ref())?;
+            dependency_graph(recipe_paths, graph_args, config).await
+        }
+
+        Some(SubCommands::GenerateCi(args)) => {
+            let (recipe_paths, _temp_dir) =
+                recipe_paths(args.recipes.clone(), args.recipe_dir.as_ref())?;
+            generate_ci(recipe_paths, args, config).await
+        }
+
         Some(SubCommands::Publish(publish_args)) => {
-            let publish_data = PublishData::from_opts_and_config(publish_args, config);
+            let publish_data = PublishData::from_opts_and_config(publish_args, config)?;
             publish_packages(publish_data, &log_handler).await
         }
 
@@ -241,13 +322,28 @@ async fn async_main() -> miette::Result<()> {
             )
             .await
         }
-        Some(SubCommands::Upload(upload_args)) => upload_from_args(upload_args).await,
+        Some(SubCommands::Upload(upload_args)) => upload_packages(upload_args, config).await,
+        Some(SubCommands::Index(index_args)) => index_channel(index_args, config).await,
+        Some(SubCommands::Outputs(outputs_args)) => list_outputs(outputs_args, config).await,
+        Some(SubCommands::Clean(clean_args)) => clean_builds(clean_args, config),
+        Some(SubCommands::ExplainRebuild(args)) => explain_rebuild(args, config).await,
+        Some(SubCommands::Audit(args)) => {
+            audit_packages(
+                args,
+                config,
+                log_handler.expect("logger is not initialized"),
+            )
+            .await
+        }
         #[cfg(feature = "recipe-generation")]
         Some(SubCommands::GenerateRecipe(args)) => {
             rattler_build::recipe_generator::generate_recipe(args).await
         }
         Some(SubCommands::Auth(args)) => rattler::cli::auth::execute(args).await.into_diagnostic(),
-        Some(SubCommands::Debug(args)) => match args.subcommand {
+        Some(SubCommands::Debug(args)) => match args
+            .subcommand
+            .unwrap_or(DebugSubCommands::Setup(args.setup))
+        {
             DebugSubCommands::Setup(opts) => {
                 let debug_data = DebugData::from_setup_opts_and_config(opts, config);
                 debug_recipe(debug_data, &log_handler).await
@@ -272,9 +368,20 @@ async fn async_main() -> miette::Result<()> {
         Some(SubCommands::Package(cmd)) => match cmd {
             PackageCommands::Inspect(opts) => show_package_info(opts),
             PackageCommands::Extract(opts) => extract_package(opts).await,
+            PackageCommands::AbiDiff(opts) => abi_diff_package(opts).await,
         },
         Some(SubCommands::BumpRecipe(opts)) => run_bump_recipe(opts).await,
+        Some(SubCommands::CheckUpdates(opts)) => run_check_updates(opts).await,
         Some(SubCommands::MigrateRecipe(opts)) => run_migrate_recipe(opts),
+        Some(SubCommands::Schema(opts)) => run_schema(opts),
+        Some(SubCommands::BuildBackend) => {
+            let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+            rattler_build::build_backend::serve(stdin, tokio::io::stdout()).await
+        }
+        Some(SubCommands::Lsp) => {
+            let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+            rattler_build::lsp::serve(stdin, tokio::io::stdout()).await
+        }
         None => {
             _ = App::command().print_long_help();
             Ok(())
@@ -7,23 +7,36 @@ use clap::{Parser, ValueEnum, builder::ArgPredicate, crate_version};
 use clap_complete::{Generator, shells};
 use clap_complete_nushell::Nushell;
 use clap_verbosity_flag::{InfoLevel, Verbosity};
+use itertools::Itertools;
 use rattler_build_script::{SandboxArguments, SandboxConfiguration};
 use rattler_conda_types::{
-    NamedChannelOrUrl, Platform, compression_level::CompressionLevel, package::CondaArchiveType,
+    MatchSpec, NamedChannelOrUrl, ParseStrictness, Platform, VersionSpec,
+    compression_level::CompressionLevel, package::CondaArchiveType,
 };
-use rattler_config::config::ConfigBase;
 use rattler_config::config::build::PackageFormatAndCompression;
 use rattler_networking::mirror_middleware;
 #[cfg(feature = "s3")]
 use rattler_networking::s3_middleware;
 use rattler_solve::ChannelPriority;
-use rattler_upload::upload::opt::UploadOpts;
+#[cfg(feature = "s3")]
+use rattler_upload::upload::opt::S3Opts;
+use rattler_upload::upload::opt::{
+    AnacondaOpts, CommonOpts as UploadCommonOpts, CondaForgeOpts, PrefixOpts, QuetzOpts,
+};
 use serde_json::{Value, json};
 use url::Url;
 
 use crate::{
-    console_utils::{Color, LogStyle},
+    audit::{AuditSettings, Severity},
+    conda_forge_pinning::Pinning,
+    config::Config,
+    console_utils::{Color, LogFilter, LogStyle},
+    events::OutputFormat,
+    hooks::Hook,
+    license_policy::LicensePolicy,
     metadata::Debug,
+    post_process::plugin::PostProcessor,
+    slsa::SlsaProvenance,
     tool_configuration::{ContinueOnFailure, SkipExisting, TestStrategy},
 };
 #[cfg(feature = "recipe-generation")]
@@ -63,6 +76,55 @@ pub enum SubCommands {
     /// Upload a package
     Upload(UploadOpts),
 
+    /// Index a local or S3 channel
+    ///
+    /// This updates the `repodata.json` of the channel after packages were copied or
+    /// uploaded to it, so that they can be installed.
+    Index(IndexOpts),
+
+    /// List the packages that a recipe produces, without solving or building
+    ///
+    /// This prints the name, version, build string, subdir and file name of the package of
+    /// every variant, e.g. to shard builds on CI or to check which packages already exist.
+    Outputs(OutputsOpts),
+
+    /// Print the dependency graph among the outputs of one or more recipes
+    ///
+    /// Every output (variant) is a node, and an edge points from an output to the outputs
+    /// that depend on it in their build, host or run requirements (`pin_subpackage`
+    /// dependencies are highlighted). The graph can be printed as DOT (Graphviz), Mermaid
+    /// or JSON.
+    Graph(GraphOpts),
+
+    /// Generate the configuration of a CI pipeline that builds the recipes
+    ///
+    /// The recipes are rendered for every platform, and the packages are split into jobs per
+    /// recipe, platform and variant. Every job installs rattler-build, caches the package
+    /// cache, builds the packages and (with `--upload-to`) publishes them on pushes to the
+    /// branch. Supported providers are GitHub Actions, GitLab CI/CD and Azure Pipelines.
+    GenerateCi(GenerateCiOpts),
+
+    /// Remove build directories, caches and intermediate files of old builds
+    ///
+    /// Without a selector, the build directories, the source cache and the intermediate files
+    /// in the output directory are removed. The package cache is only removed with
+    /// `--packages` or `--all`, as it is shared with other tools.
+    Clean(CleanOpts),
+
+    /// Explain why a recipe produces a different build string than a previous build
+    ///
+    /// This compares the rendered recipe of a previous build of the package against the
+    /// current recipe and prints which inputs changed: the version, the build number, the
+    /// variant (which determines the hash), the requirements and the rendered recipe.
+    ExplainRebuild(ExplainRebuildOpts),
+
+    /// Audit packages for known vulnerabilities
+    ///
+    /// The package itself, its resolved host environment and its run environment are looked
+    /// up in the OSV database (CVE and GitHub advisories). Python packages are looked up on
+    /// PyPI, the other packages are reported as not covered.
+    Audit(AuditOpts),
+
     /// Generate shell completion script
     Completion(ShellCompletion),
 
@@ -85,24 +147,56 @@ pub enum SubCommands {
     /// This command updates the version and SHA256 checksum(s) in a recipe file.
     /// It can either use a specified version or auto-detect the latest version
     /// from supported providers (GitHub, PyPI, crates.io).
+    #[command(visible_alias = "bump")]
     BumpRecipe(BumpRecipeOpts),
 
+    /// Check recipes for newer upstream versions
+    ///
+    /// The latest version is looked up with the provider of the source URL (GitHub releases
+    /// and tags, PyPI, crates.io) and compared against the version of the recipe. The recipes
+    /// are not modified, use `bump-recipe` to update them.
+    CheckUpdates(CheckUpdatesOpts),
+
     /// Migrate a recipe from the deprecated `cache:` format to `staging:` outputs
     MigrateRecipe(MigrateRecipeOpts),
+
+    /// Run as a build backend of pixi (`pixi build`)
+    ///
+    /// The backend reads JSON-RPC requests (one per line) from stdin and writes the responses
+    /// to stdout, the logs are written to stderr. It lists and builds the packages of the
+    /// recipe next to the pixi manifest with the configuration and caches of rattler-build.
+    BuildBackend,
+
+    /// Print the schema of the recipe format
+    ///
+    /// The schema is generated from the recipe parser of this version of rattler-build, so
+    /// that editors and validators accept the same recipes as the build.
+    Schema(SchemaOpts),
+
+    /// Run a language server for recipes
+    ///
+    /// The server reads Language Server Protocol messages from stdin and writes to stdout, the
+    /// logs are written to stderr. It provides diagnostics, hover documentation, completions
+    /// (keys, Jinja functions, variant variables and package names) and go to script files.
+    Lsp,
 }
 
 /// Arguments for the `debug` command.
 ///
-/// A subcommand is always required. Use `debug setup` to prepare a debug
-/// environment from a recipe, `debug shell` to enter an existing one
-/// interactively, and `debug run` to execute the build script
-/// non-interactively.
+/// `debug <recipe>` is a shorthand for `debug setup <recipe>`: it prepares a
+/// debug environment from a recipe without running the build. Use `debug
+/// shell` to enter an existing one interactively, and `debug run` to execute
+/// the build script non-interactively.
 #[derive(Parser)]
-#[command(subcommand_required = true, arg_required_else_help = true)]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
 pub struct DebugArgs {
     /// The debug subcommand to run.
     #[command(subcommand)]
-    pub subcommand: DebugSubCommands,
+    pub subcommand: Option<DebugSubCommands>,
+
+    /// The options of `debug setup` when no subcommand is given
+    #[clap(flatten)]
+    pub setup: DebugSetupOpts,
 }
 
 /// Debug subcommands
@@ -164,6 +258,10 @@ pub struct DebugSetupOpts {
     #[arg(short, long, default_value = ".")]
     pub recipe: PathBuf,
 
+    /// Recipe file or directory to debug (same as `--recipe`)
+    #[arg(value_name = "RECIPE", conflicts_with = "recipe")]
+    pub recipe_path: Option<PathBuf>,
+
     /// The target platform to build for
     #[arg(long)]
     pub target_platform: Option<Platform>,
@@ -264,6 +362,8 @@ pub enum PackageCommands {
     Inspect(InspectOpts),
     /// Extract a conda package to a directory
     Extract(ExtractOpts),
+    /// Compare the exported symbols of the shared libraries against a previous build
+    AbiDiff(AbiDiffOpts),
 }
 
 /// Shell completion options.
@@ -274,6 +374,13 @@ pub struct ShellCompletion {
     pub shell: Shell,
 }
 
+/// What is done in a dry run of a build
+#[derive(ValueEnum, Clone, Debug, Copy, Eq, Hash, PartialEq)]
+pub enum DryRun {
+    /// Solve the build, host and test environments of all outputs
+    Solve,
+}
+
 /// Defines the shells for which we can provide completions
 #[allow(clippy::enum_variant_names)]
 #[derive(ValueEnum, Clone, Debug, Copy, Eq, Hash, PartialEq)]
@@ -328,6 +435,19 @@ pub struct App {
     #[command(flatten)]
     pub verbose: Verbosity<InfoLevel>,
 
+    /// Set the log verbosity of single subsystems, e.g. `solver=debug,script=info,upload=warn`.
+    /// Takes priority over `-v` and `-q`. The subsystems are render, source, solver, install,
+    /// script, packaging, test, upload and network; any other name is used as a tracing target
+    /// (e.g. `rattler_build::source::git_source=trace`).
+    #[clap(
+        long,
+        env = "RATTLER_BUILD_LOG_FILTER",
+        value_name = "SUBSYSTEM=LEVEL",
+        value_delimiter = ',',
+        global = true
+    )]
+    pub log_filter: Vec<LogFilter>,
+
     /// Logging style
     #[clap(
         long,
@@ -337,6 +457,17 @@ pub struct App {
     )]
     pub log_style: LogStyle,
 
+    /// The format of the results on stdout. With `json`, the build, render, test and upload
+    /// commands write newline delimited JSON events (phases, solved environments, packages,
+    /// test results and errors) to stdout.
+    #[clap(
+        long,
+        env = "RATTLER_BUILD_OUTPUT_FORMAT",
+        default_value = "human",
+        global = true
+    )]
+    pub output_format: OutputFormat,
+
     /// Wrap log lines at the terminal width.
     /// This is automatically disabled on CI (by detecting the `CI` environment variable).
     #[clap(
@@ -348,7 +479,8 @@ pub struct App {
     )]
     pub wrap_log_lines: Option<bool>,
 
-    /// The rattler-build configuration file to use
+    /// The rattler-build configuration file to use. It takes priority over the user
+    /// configuration and the `rattler-build.toml` files of the project.
     #[arg(long, global = true)]
     pub config_file: Option<PathBuf>,
 
@@ -371,6 +503,42 @@ impl App {
             _ => false,
         }
     }
+
+    /// The path that the `rattler-build.toml` files of the project are searched from: the recipe
+    /// of the command, or the current directory.
+    pub fn config_search_path(&self) -> PathBuf {
+        let recipe = match &self.subcommand {
+            Some(SubCommands::Build(args)) => {
+                args.recipe_dir.as_ref().or_else(|| args.recipes.first())
+            }
+            Some(SubCommands::Publish(args)) => args
+                .build
+                .recipe_dir
+                .as_ref()
+                .or_else(|| args.package_or_recipe.first()),
+            Some(SubCommands::Outputs(args)) => Some(&args.recipe),
+            Some(SubCommands::Graph(args)) => {
+                args.recipe_dir.as_ref().or_else(|| args.recipes.first())
+            }
+            Some(SubCommands::GenerateCi(args)) => {
+                args.recipe_dir.as_ref().or_else(|| args.recipes.first())
+            }
+            Some(SubCommands::ExplainRebuild(args)) => Some(&args.recipe),
+            Some(SubCommands::Debug(DebugArgs {
+                subcommand: Some(DebugSubCommands::Setup(args)),
+                ..
+            }))
+            | Some(SubCommands::Debug(DebugArgs {
+                subcommand: None,
+                setup: args,
+            })) => Some(args.recipe_path.as_ref().unwrap_or(&args.recipe)),
+            _ => None,
+        };
+        recipe
+            .filter(|path| path.exists())
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
 }
 
 /// Common opts that are shared between [`Rebuild`] and [`Build`]` subcommands
@@ -428,6 +596,7 @@ pub struct CommonData {
     pub use_zstd: bool,
     pub use_bz2: bool,
     pub use_sharded: bool,
+    pub cache_dir: Option<PathBuf>,
 }
 
 impl CommonData {
@@ -437,7 +606,7 @@ impl CommonData {
         output_dir: Option<PathBuf>,
         experimental: bool,
         auth_file: Option<PathBuf>,
-        config: ConfigBase<()>,
+        config: Config,
         channel_priority: Option<ChannelPriority>,
         allow_insecure_host: Option<Vec<String>>,
         use_zstd: bool,
@@ -477,7 +646,9 @@ impl CommonData {
         let s3_config = rattler_networking::s3_middleware::compute_s3_config(&config.s3_options.0);
 
         Self {
-            output_dir: output_dir.unwrap_or_else(|| PathBuf::from("./output")),
+            output_dir: output_dir
+                .or_else(|| config.extensions.output_dir.clone())
+                .unwrap_or_else(|| PathBuf::from("./output")),
             experimental,
             auth_file,
             #[cfg(feature = "s3")]
@@ -488,11 +659,12 @@ impl CommonData {
             use_zstd,
             use_bz2,
             use_sharded,
+            cache_dir: config.extensions.cache_dir.clone(),
         }
     }
 
     /// Create from CLI options and config file
-    pub fn from_opts_and_config(value: CommonOpts, config: ConfigBase<()>) -> Self {
+    pub fn from_opts_and_config(value: CommonOpts, config: Config) -> Self {
         Self::new(
             value.output_dir,
             value.experimental,
@@ -557,9 +729,24 @@ pub struct BuildOpts {
     #[arg(long)]
     pub build_platform: Option<Platform>,
 
-    /// The target platform for the build.
-    #[arg(long)]
-    pub target_platform: Option<Platform>,
+    /// Build for a foreign Linux architecture with emulation instead of
+    /// cross-compilation: the build environment is installed for the target
+    /// platform and the build scripts run with qemu-user, which has to be
+    /// registered with binfmt_misc.
+    #[arg(long, conflicts_with = "build_platform")]
+    pub emulate: bool,
+
+    /// Use `zig cc` as the C/C++ compiler instead of the compiler packages and
+    /// the sysroot when cross-compiling to Linux or building for a glibc
+    /// baseline (`c_stdlib_version`) older than the one of the build machine.
+    #[arg(long, conflicts_with = "emulate")]
+    pub zig_cc: bool,
+
+    /// The target platform for the build. Can be repeated (or separated by
+    /// commas) to build for several platforms in one invocation, e.g.
+    /// `--target-platform osx-64,osx-arm64`.
+    #[arg(long, value_delimiter = ',')]
+    pub target_platform: Vec<Platform>,
 
     /// The host platform for the build. If set, it will be used to determine
     /// also the target_platform (as long as it is not noarch).
@@ -583,6 +770,22 @@ pub struct BuildOpts {
     #[arg(long)]
     pub ignore_recipe_variants: bool,
 
+    /// Use the global pinning of conda-forge as variant configuration, optionally at a git ref
+    /// of the conda-forge-pinning feedstock (e.g. `conda-forge@2025.01.06.10.08.42`). The
+    /// variant files of the recipe and `--variant-config` take priority over it.
+    #[arg(long, value_name = "conda-forge[@REF]")]
+    pub pinning: Option<Pinning>,
+
+    /// The URL that the files of the conda-forge-pinning feedstock are downloaded from
+    #[arg(long, env = "RATTLER_BUILD_PINNING_URL", hide = true)]
+    pub pinning_url: Option<Url>,
+
+    /// Cache the rendered recipes and their variants in the cache directory, keyed by the
+    /// recipe, the variant configuration and the platforms, so that rendering the same recipe
+    /// again skips evaluating it.
+    #[arg(long, env = "RATTLER_BUILD_RENDER_CACHE")]
+    pub render_cache: bool,
+
     /// Render the recipe files without executing the build.
     #[arg(long)]
     pub render_only: bool,
@@ -591,10 +794,34 @@ pub struct BuildOpts {
     #[arg(long, requires("render_only"))]
     pub with_solve: bool,
 
+    /// Solve the build, host and test environments of all outputs and report the package
+    /// plan, without fetching the sources or building anything.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "solve",
+        conflicts_with = "render_only"
+    )]
+    pub dry_run: Option<DryRun>,
+
     /// Keep intermediate build artifacts after the build.
     #[arg(long)]
     pub keep_build: bool,
 
+    /// Resume an interrupted or failed build from the last phase that completed
+    /// (sources fetched, environments created, build script succeeded or packaged)
+    /// instead of starting over.
+    #[arg(long)]
+    pub resume: bool,
+
+    /// The number of outputs to build concurrently. Outputs are only built at the same time
+    /// when they do not depend on each other; with more than one job, the log of each build is
+    /// printed as one block once it completes, and the tests run after all builds.
+    #[arg(long)]
+    pub build_jobs: Option<usize>,
+
     /// Don't use build id(timestamp) when creating build directory name.
     #[arg(long)]
     pub no_build_id: bool,
@@ -629,10 +856,59 @@ pub struct BuildOpts {
     #[arg(long, help_heading = "Modifying result")]
     pub test: Option<TestStrategy>,
 
+    /// The number of packages to test concurrently. With more than one job, the tests of all
+    /// outputs run after the builds have finished, and the log of each test is printed as one
+    /// block once it completes.
+    #[arg(long, help_heading = "Modifying result")]
+    pub test_jobs: Option<usize>,
+
+    /// Add a channel to search for test dependencies in (e.g. a channel with release
+    /// candidates). Test channels take precedence over the build channels.
+    #[arg(long = "test-channel", help_heading = "Modifying result")]
+    pub test_channels: Vec<NamedChannelOrUrl>,
+
+    /// Install an additional package into every test environment (e.g. a pytest plugin)
+    #[arg(long = "test-extra-spec", value_parser = parse_match_spec, help_heading = "Modifying result")]
+    pub test_extra_specs: Vec<MatchSpec>,
+
+    /// Test noarch python packages with this python version (e.g. `3.10`). Every test runs
+    /// in a separate environment for each version.
+    #[arg(long = "test-python-version", value_parser = parse_python_version, help_heading = "Modifying result")]
+    pub test_python_versions: Vec<String>,
+
+    /// Run the tests inside of a container of this image (with `docker` or `podman`). Only the
+    /// package and the local channels are mounted into the container. Linux only.
+    #[arg(
+        long = "test-in-container",
+        value_name = "IMAGE",
+        help_heading = "Modifying result"
+    )]
+    pub test_container: Option<String>,
+
+    /// Write a JUnit XML report of all tests to this file
+    #[arg(long, help_heading = "Modifying result")]
+    pub test_report_junit: Option<PathBuf>,
+
+    /// Write a JSON report of all tests to this file
+    #[arg(long, help_heading = "Modifying result")]
+    pub test_report_json: Option<PathBuf>,
+
     /// Don't force colors in the output of the build script
     #[arg(long, default_value = "true", help_heading = "Modifying result")]
     pub color_build_log: bool,
 
+    /// Set the environment variables of conda-build that rattler-build does not set by
+    /// default (e.g. `CONDA_PY`, `SYS_PREFIX` and `GIT_DESCRIBE_TAG`) for the build scripts,
+    /// to ease the migration of recipes from conda-build
+    #[arg(long, help_heading = "Modifying result")]
+    pub conda_build_env_vars: bool,
+
+    /// Report which build and host requirements are used by the package (linked
+    /// libraries, Python imports, executables in the build script, ...) and warn
+    /// about the ones that are likely unused
+    #[arg(long, help_heading = "Modifying result")]
+    pub report_dependency_usage: bool,
+
     #[allow(missing_docs)]
     #[clap(flatten)]
     pub common: CommonOpts,
@@ -641,6 +917,11 @@ pub struct BuildOpts {
     #[arg(long, hide = !cfg!(feature = "tui"))]
     pub tui: bool,
 
+    /// Watch the recipe, its scripts, the variant configuration and local path
+    /// sources, and rebuild automatically when they change.
+    #[arg(long, conflicts_with = "tui")]
+    pub watch: bool,
+
     /// Whether to skip packages that already exist in any channel
     /// If set to `none`, do not skip any packages, default when not specified.
     /// If set to `local`, only skip packages that already exist locally,
@@ -650,6 +931,56 @@ pub struct BuildOpts {
     )]
     pub skip_existing: Option<SkipExisting>,
 
+    /// Look up the packages in this remote build cache before building them, and store the
+    /// packages that were built in it. The cache is an HTTP server (`GET` and `PUT`), an S3
+    /// bucket (`s3://`) or a directory, and its packages are keyed by a hash of all the inputs
+    /// of the build.
+    #[arg(
+        long,
+        value_name = "URL",
+        env = "RATTLER_BUILD_REMOTE_CACHE",
+        value_parser = parse_remote_cache_url,
+        help_heading = "Remote cache"
+    )]
+    pub remote_cache: Option<Url>,
+
+    /// Only look up packages in the remote cache, but never store them (e.g. for the builds of
+    /// pull requests)
+    #[arg(long, help_heading = "Remote cache")]
+    pub remote_cache_read_only: bool,
+
+    /// Write an in-toto SLSA provenance statement next to every package
+    /// (`<package>.intoto.json`). With `signed`, the statement is also signed with `cosign`
+    /// (`<package>.sigstore.json`).
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_missing_value = "unsigned",
+        num_args = 0..=1,
+        help_heading = "Modifying result"
+    )]
+    pub slsa_provenance: Option<SlsaProvenance>,
+
+    /// Audit the packages, their host and their run environments for known vulnerabilities
+    /// in the OSV database after building
+    #[arg(long, help_heading = "Modifying result")]
+    pub audit: bool,
+
+    /// Fail the build if the audit finds a vulnerability with at least this severity
+    /// (implies `--audit`)
+    #[arg(long, value_name = "SEVERITY", help_heading = "Modifying result")]
+    pub audit_fail_on: Option<Severity>,
+
+    /// The OSV API that the audit queries (defaults to https://api.osv.dev)
+    #[arg(long, env = "RATTLER_BUILD_OSV_URL", hide = true)]
+    pub osv_url: Option<Url>,
+
+    /// Refuse to build unless every source has a SHA256 hash, every git source is pinned to a
+    /// commit and every build and host dependency without a version is pinned by the variant
+    /// configuration
+    #[arg(long)]
+    pub require_pinned: bool,
+
     /// Define a "noarch platform" for which the noarch packages will be built
     /// for. The noarch builds will be skipped on the other platforms.
     #[arg(long, help_heading = "Modifying result")]
@@ -672,6 +1003,21 @@ pub struct BuildOpts {
     #[arg(long, help_heading = "Modifying result")]
     pub markdown_summary: Option<PathBuf>,
 
+    /// Write the timings of the build phases to the specified file in the Chrome trace
+    /// event format, which can be opened in Perfetto (https://ui.perfetto.dev).
+    #[arg(long, value_name = "FILE", help_heading = "Modifying result")]
+    pub profile: Option<PathBuf>,
+
+    /// Post a JSON report of the build (status, packages, artifacts and their sha256) to this
+    /// URL when the build finishes, e.g. a Slack or Matrix incoming webhook. Can be repeated.
+    #[arg(long, value_name = "URL", help_heading = "Notifications")]
+    pub webhook: Vec<Url>,
+
+    /// Run this command when the build finishes, with the JSON report of the build on stdin and
+    /// the status (`success` or `failure`) in `RATTLER_BUILD_STATUS`. Can be repeated.
+    #[arg(long, value_name = "COMMAND", help_heading = "Notifications")]
+    pub on_complete: Vec<String>,
+
     /// Continue building even if (one) of the packages fails to build.
     /// This is useful when building many packages with `--recipe-dir`.`
     #[clap(long)]
@@ -696,6 +1042,41 @@ pub struct BuildOpts {
     /// Override the build number for all outputs (defaults to the build number in the recipe)
     #[arg(long, help_heading = "Modifying result")]
     pub build_num: Option<u64>,
+
+    /// Look up the packages with the same name, version and variant in this channel and use one
+    /// more than their highest build number, so that rebuilds do not overwrite existing packages
+    #[arg(
+        long,
+        value_name = "CHANNEL",
+        help_heading = "Modifying result",
+        conflicts_with = "build_num"
+    )]
+    pub auto_build_number: Option<NamedChannelOrUrl>,
+
+    /// Sign all Mach-O binaries of macOS packages with this identity instead of ad-hoc signing
+    /// the ones without a valid signature
+    #[arg(
+        long,
+        env = "RATTLER_BUILD_CODESIGN_IDENTITY",
+        help_heading = "Modifying result"
+    )]
+    pub codesign_identity: Option<String>,
+
+    /// Submit the signed Mach-O binaries for notarization with `xcrun notarytool`, using the
+    /// credentials stored in this keychain profile (requires `--codesign-identity`)
+    #[arg(
+        long,
+        env = "RATTLER_BUILD_NOTARIZE_KEYCHAIN_PROFILE",
+        requires = "codesign_identity",
+        help_heading = "Modifying result"
+    )]
+    pub notarize_keychain_profile: Option<String>,
+
+    /// Run this command on the files of every package after the built-in post-processing, with
+    /// a JSON request (the package directory, its files and metadata) on stdin. The command
+    /// modifies the files in place. Can be repeated.
+    #[arg(long, value_name = "COMMAND", help_heading = "Modifying result")]
+    pub post_processor: Vec<String>,
 }
 
 /// Publish options for the `publish` command.
@@ -723,8 +1104,10 @@ pub struct PublishOpts {
     /// - Artifactory: artifactory://server.company.com/channel
     ///
     /// Note: This channel is also used as the highest priority channel when solving dependencies.
+    ///
+    /// Defaults to `publish.to` of the configuration.
     #[arg(long = "to", help_heading = "Publishing")]
-    pub to: NamedChannelOrUrl,
+    pub to: Option<NamedChannelOrUrl>,
 
     /// Override the build number for all outputs.
     /// Use an absolute value (e.g., `--build-number=12`) or a relative bump (e.g., `--build-number=+1`).
@@ -761,7 +1144,22 @@ pub struct PublishData {
 
 impl PublishData {
     /// Generate a new PublishData struct from PublishOpts and an optional config.
-    pub fn from_opts_and_config(opts: PublishOpts, config: Option<ConfigBase<()>>) -> Self {
+    /// Fails if no channel to publish to was given and none is configured.
+    pub fn from_opts_and_config(opts: PublishOpts, config: Option<Config>) -> miette::Result<Self> {
+        let to = opts
+            .to
+            .clone()
+            .or_else(|| {
+                config
+                    .as_ref()
+                    .and_then(|config| config.extensions.publish.to.clone())
+            })
+            .ok_or_else(|| {
+                miette::miette!(
+                    "No channel to publish to, use `--to` or set `publish.to` in the configuration"
+                )
+            })?;
+
         // Separate package files from recipe paths based on file extension
         let mut package_files = Vec::new();
         let mut recipe_paths = Vec::new();
@@ -819,7 +1217,7 @@ impl PublishData {
 
         // Prepend the --to channel to the list of channels for dependency resolution
         let mut build_opts = opts.build;
-        let to_channel = opts.to.clone();
+        let to_channel = to.clone();
 
         // Add the to channel as the first channel (highest priority)
         let channels = if let Some(mut channels) = build_opts.channels.take() {
@@ -831,15 +1229,15 @@ impl PublishData {
 
         build_opts.channels = channels;
 
-        Self {
-            to: opts.to,
+        Ok(Self {
+            to,
             build_number: opts.build_number,
             force: opts.force,
             generate_attestation: opts.generate_attestation,
             package_files,
             recipe_paths,
             build: BuildData::from_opts_and_config(build_opts, config),
-        }
+        })
     }
 }
 
@@ -856,17 +1254,29 @@ pub struct BuildData {
     pub ignore_recipe_variants: bool,
     pub render_only: bool,
     pub with_solve: bool,
+    pub dry_run: Option<DryRun>,
     pub keep_build: bool,
+    pub resume: bool,
     pub no_build_id: bool,
+    pub build_jobs: usize,
     pub package_format: PackageFormatAndCompression,
     pub compression_threads: Option<u32>,
     pub io_concurrency_limit: usize,
     pub no_include_recipe: bool,
     pub test: TestStrategy,
+    pub test_jobs: usize,
+    pub test_channels: Vec<NamedChannelOrUrl>,
+    pub test_extra_specs: Vec<MatchSpec>,
+    pub test_python_versions: Vec<String>,
+    pub test_container: Option<String>,
+    pub test_report_junit: Option<PathBuf>,
+    pub test_report_json: Option<PathBuf>,
     pub color_build_log: bool,
     pub common: CommonData,
     pub tui: bool,
     pub skip_existing: SkipExisting,
+    pub remote_cache: Option<Url>,
+    pub remote_cache_read_only: bool,
     pub noarch_build_platform: Option<Platform>,
     pub extra_meta: Option<Vec<(String, Value)>>,
     pub sandbox_configuration: Option<SandboxConfiguration>,
@@ -878,6 +1288,23 @@ pub struct BuildData {
     pub exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
     pub build_num_override: Option<u64>,
     pub markdown_summary: Option<PathBuf>,
+    pub profile: Option<PathBuf>,
+    pub hooks: Vec<Hook>,
+    pub codesign_identity: Option<String>,
+    pub notarize_keychain_profile: Option<String>,
+    pub extra_target_platforms: Vec<Platform>,
+    pub emulate: bool,
+    pub slsa_provenance: Option<SlsaProvenance>,
+    pub audit: Option<AuditSettings>,
+    pub license_policy: Option<LicensePolicy>,
+    pub require_pinned: bool,
+    pub pinning: Option<Pinning>,
+    pub conda_build_env_vars: bool,
+    pub zig_cc: bool,
+    pub report_dependency_usage: bool,
+    pub render_cache: bool,
+    pub post_processors: Vec<PostProcessor>,
+    pub auto_build_number: Option<NamedChannelOrUrl>,
 }
 
 impl BuildData {
@@ -894,16 +1321,28 @@ impl BuildData {
         ignore_recipe_variants: bool,
         render_only: bool,
         with_solve: bool,
+        dry_run: Option<DryRun>,
         keep_build: bool,
+        resume: bool,
         no_build_id: bool,
+        build_jobs: Option<usize>,
         package_format: Option<PackageFormatAndCompression>,
         compression_threads: Option<u32>,
         io_concurrency_limit: Option<usize>,
         no_include_recipe: bool,
         test: Option<TestStrategy>,
+        test_jobs: Option<usize>,
+        test_channels: Vec<NamedChannelOrUrl>,
+        test_extra_specs: Vec<MatchSpec>,
+        test_python_versions: Vec<String>,
+        test_container: Option<String>,
+        test_report_junit: Option<PathBuf>,
+        test_report_json: Option<PathBuf>,
         common: CommonData,
         tui: bool,
         skip_existing: Option<SkipExisting>,
+        remote_cache: Option<Url>,
+        remote_cache_read_only: bool,
         noarch_build_platform: Option<Platform>,
         extra_meta: Option<Vec<(String, Value)>>,
         sandbox_configuration: Option<SandboxConfiguration>,
@@ -915,6 +1354,10 @@ impl BuildData {
         exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
         build_num_override: Option<u64>,
         markdown_summary: Option<PathBuf>,
+        profile: Option<PathBuf>,
+        hooks: Vec<Hook>,
+        codesign_identity: Option<String>,
+        notarize_keychain_profile: Option<String>,
     ) -> Self {
         Self {
             up_to,
@@ -929,10 +1372,14 @@ impl BuildData {
             variant_config: variant_config.unwrap_or_default(),
             variant_overrides,
             ignore_recipe_variants,
-            render_only,
+            // a dry run does not create the build directories either
+            render_only: render_only || dry_run.is_some(),
             with_solve,
+            dry_run,
             keep_build,
+            resume,
             no_build_id,
+            build_jobs: build_jobs.unwrap_or(1).max(1),
             package_format: package_format.unwrap_or(PackageFormatAndCompression {
                 archive_type: CondaArchiveType::Conda,
                 compression_level: CompressionLevel::Default,
@@ -941,10 +1388,19 @@ impl BuildData {
             io_concurrency_limit: io_concurrency_limit.unwrap_or(num_cpus::get() * 8),
             no_include_recipe,
             test: test.unwrap_or_default(),
+            test_jobs: test_jobs.unwrap_or(1).max(1),
+            test_channels,
+            test_extra_specs,
+            test_python_versions,
+            test_container,
+            test_report_junit,
+            test_report_json,
             color_build_log: true,
             common,
             tui,
             skip_existing: skip_existing.unwrap_or(SkipExisting::None),
+            remote_cache,
+            remote_cache_read_only,
             noarch_build_platform,
             extra_meta,
             sandbox_configuration,
@@ -956,36 +1412,84 @@ impl BuildData {
             exclude_newer,
             build_num_override,
             markdown_summary,
+            profile,
+            hooks,
+            codesign_identity,
+            notarize_keychain_profile,
+            extra_target_platforms: Vec::new(),
+            emulate: false,
+            slsa_provenance: None,
+            audit: None,
+            license_policy: None,
+            require_pinned: false,
+            pinning: None,
+            conda_build_env_vars: false,
+            zig_cc: false,
+            report_dependency_usage: false,
+            render_cache: false,
+            post_processors: Vec::new(),
+            auto_build_number: None,
         }
     }
+
+    /// The build data for every target platform: this one, followed by one for each of the
+    /// additional target platforms (with the host platform, and with emulation also the build
+    /// platform, set to the target platform).
+    pub fn for_each_target_platform(&self) -> Vec<BuildData> {
+        let mut all = vec![self.clone()];
+        all.extend(
+            self.extra_target_platforms
+                .iter()
+                .map(|&platform| BuildData {
+                    build_platform: if self.emulate {
+                        platform
+                    } else {
+                        self.build_platform
+                    },
+                    target_platform: platform,
+                    host_platform: platform,
+                    extra_target_platforms: Vec::new(),
+                    ..self.clone()
+                }),
+        );
+        all
+    }
 }
 
 impl BuildData {
     /// Generate a new BuildData struct from BuildOpts and an optional pixi config.
     /// BuildOpts have higher priority than the pixi config.
-    pub fn from_opts_and_config(opts: BuildOpts, config: Option<ConfigBase<()>>) -> Self {
-        Self::new(
+    pub fn from_opts_and_config(opts: BuildOpts, config: Option<Config>) -> Self {
+        let config = config.unwrap_or_default();
+        let settings = &config.extensions;
+        let mut target_platforms = opts.target_platform.into_iter().unique();
+        let target_platform = target_platforms.next().or(settings.target_platform);
+        let extra_target_platforms = target_platforms.collect();
+        let host_platform = opts.host_platform.or(settings.host_platform);
+        // with emulation, the build environment is installed for the host platform
+        let build_platform = if opts.emulate {
+            host_platform.or(target_platform)
+        } else {
+            opts.build_platform.or(settings.build_platform)
+        };
+        let build_data = Self::new(
             opts.up_to,
-            opts.build_platform,
-            opts.target_platform, // todo: read this from config as well
-            opts.host_platform,
-            opts.channels.or_else(|| {
-                config
-                    .as_ref()
-                    .and_then(|config| config.default_channels.clone())
-            }),
+            build_platform,
+            target_platform,
+            host_platform,
+            opts.channels.or_else(|| config.default_channels.clone()),
             opts.variant_config,
             opts.variant_overrides.into_iter().collect(),
             opts.ignore_recipe_variants,
             opts.render_only,
             opts.with_solve,
+            opts.dry_run,
             opts.keep_build,
+            opts.resume,
             opts.no_build_id,
-            opts.package_format.or_else(|| {
-                config
-                    .as_ref()
-                    .and_then(|config| config.build.package_format.clone())
-            }),
+            opts.build_jobs,
+            opts.package_format
+                .or_else(|| config.build.package_format.clone()),
             opts.compression_threads,
             opts.io_concurrency_limit,
             opts.no_include_recipe,
@@ -994,12 +1498,22 @@ impl BuildData {
             } else {
                 None
             }),
-            CommonData::from_opts_and_config(opts.common, config.unwrap_or_default()),
+            opts.test_jobs,
+            opts.test_channels,
+            opts.test_extra_specs,
+            opts.test_python_versions,
+            opts.test_container,
+            opts.test_report_junit,
+            opts.test_report_json,
+            CommonData::from_opts_and_config(opts.common, config.clone()),
             opts.tui,
             opts.skip_existing,
+            opts.remote_cache
+                .or_else(|| settings.remote_cache.url.clone()),
+            opts.remote_cache_read_only || settings.remote_cache.read_only.unwrap_or(false),
             opts.noarch_build_platform,
             opts.extra_meta,
-            opts.sandbox_arguments.into(),
+            settings.sandbox.apply(opts.sandbox_arguments).into(),
             Debug::new(opts.debug),
             opts.continue_on_failure.into(),
             opts.error_prefix_in_binary,
@@ -1008,7 +1522,58 @@ impl BuildData {
             opts.exclude_newer,
             opts.build_num,
             opts.markdown_summary,
-        )
+            opts.profile,
+            // the hooks of the command line are fired in addition to the configured ones
+            opts.webhook
+                .into_iter()
+                .map(Hook::webhook)
+                .chain(opts.on_complete.into_iter().map(Hook::command))
+                .chain(settings.hooks.iter().cloned())
+                .collect(),
+            opts.codesign_identity,
+            opts.notarize_keychain_profile,
+        );
+        Self {
+            extra_target_platforms,
+            emulate: opts.emulate,
+            slsa_provenance: opts.slsa_provenance,
+            audit: (opts.audit || opts.audit_fail_on.is_some()).then_some(AuditSettings {
+                fail_on: opts.audit_fail_on,
+                osv_url: opts.osv_url,
+            }),
+            license_policy: Some(settings.license_policy.clone()).filter(LicensePolicy::is_active),
+            require_pinned: opts.require_pinned || settings.require_pinned.unwrap_or(false),
+            pinning: opts
+                .pinning
+                .map(|pinning| pinning.with_url(opts.pinning_url)),
+            conda_build_env_vars: opts.conda_build_env_vars
+                || settings.conda_build_env_vars.unwrap_or(false),
+            zig_cc: opts.zig_cc || settings.zig_cc.unwrap_or(false),
+            report_dependency_usage: opts.report_dependency_usage,
+            render_cache: opts.render_cache || settings.render_cache.unwrap_or(false),
+            // the post-processors of the command line run after the configured ones
+            post_processors: settings
+                .post_processors
+                .iter()
+                .cloned()
+                .chain(opts.post_processor.into_iter().map(PostProcessor::command))
+                .collect(),
+            auto_build_number: opts.auto_build_number,
+            ..build_data
+        }
+    }
+}
+
+/// Parse the URL of the remote cache, a directory is turned into a `file://` URL
+fn parse_remote_cache_url(url: &str) -> Result<Url, String> {
+    match Url::parse(url) {
+        // `C:\cache` parses as a URL with the scheme `c`
+        Ok(url) if url.scheme().len() > 1 => Ok(url),
+        _ => {
+            let path = std::path::absolute(url).map_err(|e| e.to_string())?;
+            Url::from_directory_path(&path)
+                .map_err(|_| format!("invalid remote cache directory: {}", path.display()))
+        }
     }
 }
 
@@ -1031,6 +1596,14 @@ fn parse_key_val(s: &str) -> Result<(String, Value), Box<dyn Error + Send + Sync
     Ok((key.to_string(), json!(value)))
 }
 
+/// Parse a property of an uploaded package (e.g., "team=data")
+fn parse_property(s: &str) -> Result<(String, String), Box<dyn Error + Send + Sync + 'static>> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{}`", s))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
 /// Parse variant override (e.g., "python=3.12" or "python=3.12,3.11")
 fn parse_variant_override(
     s: &str,
@@ -1043,6 +1616,30 @@ fn parse_variant_override(
     Ok((key.to_string(), values))
 }
 
+/// Parse an age like `30s`, `30m`, `12h`, `7d` or `2w`
+fn parse_age(s: &str) -> Result<std::time::Duration, String> {
+    let unit_start = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in `{s}`, e.g. `7d`"))?;
+    let (value, unit) = s.split_at(unit_start);
+    let value = value
+        .parse::<u64>()
+        .map_err(|_| format!("invalid age `{s}`, e.g. `7d`"))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown unit `{unit}` in `{s}`, use `s`, `m`, `h`, `d` or `w`"
+            ));
+        }
+    };
+    Ok(std::time::Duration::from_secs(value * seconds))
+}
+
 /// Parse a datetime string in RFC3339 format
 fn parse_datetime(s: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
     chrono::DateTime::parse_from_rfc3339(s)
@@ -1055,6 +1652,16 @@ fn parse_datetime(s: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
         })
 }
 
+fn parse_match_spec(s: &str) -> Result<MatchSpec, String> {
+    MatchSpec::from_str(s, ParseStrictness::Lenient).map_err(|e| e.to_string())
+}
+
+fn parse_python_version(s: &str) -> Result<String, String> {
+    VersionSpec::from_str(s, ParseStrictness::Lenient)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
 /// Test options.
 #[derive(Parser)]
 pub struct TestOpts {
@@ -1062,9 +1669,19 @@ pub struct TestOpts {
     #[arg(short = 'c', long = "channel")]
     pub channels: Option<Vec<NamedChannelOrUrl>>,
 
-    /// The package file or extracted package directory to test
-    #[arg(short, long)]
-    pub package_file: PathBuf,
+    /// The package file, extracted package directory or package URL to test
+    #[arg(short, long, required_unless_present_any = ["package_spec", "deferred"])]
+    pub package_file: Option<PackageSource>,
+
+    /// Download the best matching package from the channels (`--channel`) and test it, e.g.
+    /// `numpy 2.0.*` or `conda-forge/linux-64::numpy 2.0.0 *_1`
+    #[arg(long, value_parser = parse_match_spec, conflicts_with = "package_file")]
+    pub package_spec: Option<MatchSpec>,
+
+    /// Run the tests that were skipped when cross-compiling (the packages in
+    /// `deferred_tests.json` of the output directory), e.g. on native hardware
+    #[arg(long, conflicts_with_all = ["package_file", "package_spec"])]
+    pub deferred: bool,
 
     /// The number of threads to use for compression.
     #[clap(long, env = "RATTLER_COMPRESSION_THREADS")]
@@ -1078,6 +1695,37 @@ pub struct TestOpts {
     #[arg(long)]
     pub debug: bool,
 
+    /// Test noarch python packages with this python version (e.g. `3.10`). Every test runs
+    /// in a separate environment for each version.
+    #[arg(long = "test-python-version", value_parser = parse_python_version)]
+    pub test_python_versions: Vec<String>,
+
+    /// Add a channel to search for test dependencies in (e.g. a channel with release
+    /// candidates). Test channels take precedence over the channels (`--channel`).
+    #[arg(long = "test-channel")]
+    pub test_channels: Vec<NamedChannelOrUrl>,
+
+    /// Install an additional package into every test environment (e.g. a pytest plugin)
+    #[arg(long = "test-extra-spec", value_parser = parse_match_spec)]
+    pub test_extra_specs: Vec<MatchSpec>,
+
+    /// Exclude packages newer than this date from the test environments, in RFC3339 format (e.g. 2024-03-15T12:00:00Z)
+    #[arg(long, value_parser = parse_datetime)]
+    pub exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Run the tests inside of a container of this image (with `docker` or `podman`). Only the
+    /// package and the local channels are mounted into the container. Linux only.
+    #[arg(long = "test-in-container", value_name = "IMAGE")]
+    pub test_container: Option<String>,
+
+    /// Write a JUnit XML report of the tests to this file
+    #[arg(long)]
+    pub test_report_junit: Option<PathBuf>,
+
+    /// Write a JSON report of the tests to this file
+    #[arg(long)]
+    pub test_report_json: Option<PathBuf>,
+
     /// Common options.
     #[clap(flatten)]
     pub common: CommonOpts,
@@ -1087,47 +1735,95 @@ pub struct TestOpts {
 #[allow(missing_docs)]
 pub struct TestData {
     pub channels: Option<Vec<NamedChannelOrUrl>>,
-    pub package_file: PathBuf,
+    pub package: TestPackage,
     pub compression_threads: Option<u32>,
     pub common: CommonData,
     pub test_index: Option<usize>,
     pub debug: Debug,
+    pub test_python_versions: Vec<String>,
+    pub test_channels: Vec<NamedChannelOrUrl>,
+    pub test_extra_specs: Vec<MatchSpec>,
+    pub exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
+    pub test_container: Option<String>,
+    pub test_report_junit: Option<PathBuf>,
+    pub test_report_json: Option<PathBuf>,
 }
 
 impl TestData {
     /// Generate a new TestData struct from TestOpts and an optional pixi config.
     /// TestOpts have higher priority than the pixi config.
-    pub fn from_opts_and_config(value: TestOpts, config: Option<ConfigBase<()>>) -> Self {
+    pub fn from_opts_and_config(value: TestOpts, config: Option<Config>) -> Self {
+        let package = match (value.package_file, value.package_spec) {
+            _ if value.deferred => TestPackage::Deferred,
+            (_, Some(spec)) => TestPackage::Spec(Box::new(spec)),
+            (Some(source), None) => TestPackage::Source(source),
+            (None, None) => {
+                unreachable!("clap requires either a package file, a package spec or --deferred")
+            }
+        };
         Self::new(
-            value.package_file,
+            package,
             value.channels,
             value.compression_threads,
             Debug::new(value.debug),
             value.test_index,
+            value.test_python_versions,
+            value.test_channels,
+            value.test_extra_specs,
+            value.exclude_newer,
+            value.test_container,
+            value.test_report_junit,
+            value.test_report_json,
             CommonData::from_opts_and_config(value.common, config.unwrap_or_default()),
         )
     }
 
     /// Create a new instance of `TestData`
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        package_file: PathBuf,
+        package: TestPackage,
         channels: Option<Vec<NamedChannelOrUrl>>,
         compression_threads: Option<u32>,
         debug: Debug,
         test_index: Option<usize>,
+        test_python_versions: Vec<String>,
+        test_channels: Vec<NamedChannelOrUrl>,
+        test_extra_specs: Vec<MatchSpec>,
+        exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
+        test_container: Option<String>,
+        test_report_junit: Option<PathBuf>,
+        test_report_json: Option<PathBuf>,
         common: CommonData,
     ) -> Self {
         Self {
-            package_file,
+            package,
             channels,
             compression_threads,
             test_index,
             debug,
+            test_python_versions,
+            test_channels,
+            test_extra_specs,
+            exclude_newer,
+            test_container,
+            test_report_junit,
+            test_report_json,
             common,
         }
     }
 }
 
+/// The package that is tested by `rattler-build test`
+#[derive(Debug, Clone)]
+pub enum TestPackage {
+    /// A local package file, an extracted package directory or the URL of a package
+    Source(PackageSource),
+    /// The best matching package from the test channels
+    Spec(Box<MatchSpec>),
+    /// The packages with deferred tests in the output directory
+    Deferred,
+}
+
 /// Represents a package source that can be either a local path or a URL
 #[derive(Debug, Clone)]
 pub enum PackageSource {
@@ -1177,6 +1873,14 @@ pub struct RebuildOpts {
     #[clap(long, env = "RATTLER_IO_CONCURRENCY_LIMIT")]
     pub io_concurrency_limit: Option<usize>,
 
+    /// Compare the rebuilt package with the original file by file and fail if they differ
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Write the file-by-file comparison of `--verify` as JSON to this file
+    #[arg(long, requires = "verify")]
+    pub verify_report: Option<PathBuf>,
+
     /// Common options.
     #[clap(flatten)]
     pub common: CommonOpts,
@@ -1188,23 +1892,29 @@ pub struct RebuildData {
     pub package_file: PackageSource,
     pub test: TestStrategy,
     pub compression_threads: Option<u32>,
+    pub verify: bool,
+    pub verify_report: Option<PathBuf>,
     pub common: CommonData,
 }
 
 impl RebuildData {
     /// Generate a new RebuildData struct from RebuildOpts and an optional pixi config.
     /// RebuildOpts have higher priority than the pixi config.
-    pub fn from_opts_and_config(value: RebuildOpts, config: Option<ConfigBase<()>>) -> Self {
-        Self::new(
-            value.package_file,
-            value.test.unwrap_or(if value.no_test {
-                TestStrategy::Skip
-            } else {
-                TestStrategy::default()
-            }),
-            value.compression_threads,
-            CommonData::from_opts_and_config(value.common, config.unwrap_or_default()),
-        )
+    pub fn from_opts_and_config(value: RebuildOpts, config: Option<Config>) -> Self {
+        Self {
+            verify: value.verify,
+            verify_report: value.verify_report,
+            ..Self::new(
+                value.package_file,
+                value.test.unwrap_or(if value.no_test {
+                    TestStrategy::Skip
+                } else {
+                    TestStrategy::default()
+                }),
+                value.compression_threads,
+                CommonData::from_opts_and_config(value.common, config.unwrap_or_default()),
+            )
+        }
     }
 
     /// Create a new instance of `RebuildData`
@@ -1218,88 +1928,323 @@ impl RebuildData {
             package_file,
             test,
             compression_threads,
+            verify: false,
+            verify_report: None,
             common,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-/// Data structure containing the configuration for debugging a recipe
-pub struct DebugData {
-    /// Path to the recipe file to debug
-    pub recipe_path: PathBuf,
-    /// Directory where build artifacts will be stored
-    pub output_dir: PathBuf,
-    /// Platform where the build is being executed
-    pub build_platform: Platform,
-    /// Target platform for the build
-    pub target_platform: Platform,
-    /// Host platform for runtime dependencies
-    pub host_platform: Platform,
-    /// List of channels to search for dependencies
-    pub channels: Option<Vec<NamedChannelOrUrl>>,
-    /// Common configuration options
-    pub common: CommonData,
-    /// Name of the specific output to debug (if recipe has multiple outputs)
-    pub output_name: Option<String>,
-}
+/// Upload options.
+#[derive(Parser, Debug)]
+pub struct UploadOpts {
+    /// The package file to upload
+    #[arg(global = true, required = false)]
+    pub package_files: Vec<PathBuf>,
 
-impl DebugData {
-    /// Generate a new DebugData struct from DebugSetupOpts and an optional
-    /// config.
-    pub fn from_setup_opts_and_config(
-        opts: DebugSetupOpts,
-        config: Option<ConfigBase<()>>,
-    ) -> Self {
-        Self {
-            recipe_path: opts.recipe,
-            output_dir: opts
-                .common
-                .output_dir
-                .clone()
-                .unwrap_or_else(|| PathBuf::from("./output")),
-            build_platform: opts.build_platform.unwrap_or(Platform::current()),
-            target_platform: opts.target_platform.unwrap_or(Platform::current()),
-            host_platform: opts
-                .host_platform
-                .unwrap_or_else(|| opts.target_platform.unwrap_or(Platform::current())),
-            channels: opts.channels,
-            common: CommonData::from_opts_and_config(opts.common, config.unwrap_or_default()),
-            output_name: opts.output_name,
-        }
-    }
-}
+    /// The server type
+    #[clap(subcommand)]
+    pub server_type: UploadServerType,
 
-/// Options for the `create-patch` command.
-#[derive(Parser, Debug, Clone)]
-pub struct CreatePatchOpts {
-    /// Directory where we want to create the patch.
-    /// Defaults to current directory if not specified.
-    #[arg(short, long)]
-    pub directory: Option<PathBuf>,
+    /// The number of packages to upload concurrently
+    #[arg(long, global = true, default_value_t = 4)]
+    pub jobs: usize,
 
-    /// The name for the patch file to create.
-    #[arg(long, default_value = "changes")]
-    pub name: String,
+    /// Only run the pre-flight checks: verify the integrity of the packages, validate the
+    /// credentials, check for conflicts with existing packages and print where the packages
+    /// would be uploaded, without uploading anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
 
-    /// Whether to overwrite the patch file if it already exists.
-    #[arg(long, default_value = "false")]
-    pub overwrite: bool,
+    /// Common options.
+    #[clap(flatten)]
+    pub common: UploadCommonOpts,
+}
 
-    /// Optional directory where the patch file should be written. Defaults to the recipe directory determined from `.source_info.json` if not provided.
-    #[arg(long, value_name = "DIR")]
-    pub patch_dir: Option<PathBuf>,
+/// The server type to upload to
+#[derive(Debug, clap::Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub enum UploadServerType {
+    /// Upload to an OCI registry (e.g. ghcr.io)
+    Oci(OciUploadOpts),
 
-    /// Comma-separated list of file names (or glob patterns) that should be excluded from the diff.
-    #[arg(long, value_delimiter = ',')]
-    pub exclude: Option<Vec<String>>,
+    /// Attach the packages to a GitHub release
+    GithubRelease(GithubReleaseUploadOpts),
 
-    /// Include new files matching these glob patterns (e.g., "*.txt", "src/**/*.rs")
-    #[arg(long, value_delimiter = ',')]
-    pub add: Option<Vec<String>>,
+    /// Move packages on anaconda.org from one label to another (e.g. from `rc` to `main`
+    /// after they have been validated)
+    AnacondaPromote(AnacondaPromoteOpts),
 
-    /// Only include modified files matching these glob patterns (e.g., "*.c", "src/**/*.rs")
-    /// If not specified, all modified files are included (subject to --exclude)
+    #[allow(missing_docs)]
+    Quetz(QuetzUploadOpts),
+
+    #[allow(missing_docs)]
+    Artifactory(ArtifactoryUploadOpts),
+
+    #[allow(missing_docs)]
+    Prefix(PrefixOpts),
+
+    #[allow(missing_docs)]
+    Anaconda(AnacondaOpts),
+
+    #[allow(missing_docs)]
+    #[cfg(feature = "s3")]
+    S3(S3UploadOpts),
+
+    #[allow(missing_docs)]
+    #[command(hide = true)]
+    CondaForge(CondaForgeOpts),
+
+    /// Upload with an uploader plugin, a command that is configured in the
+    /// `[uploaders.<NAME>]` section of the configuration
+    Plugin(PluginUploadOpts),
+}
+
+/// Upload to a Quetz server.
+/// Authentication is used from the keychain / auth-file.
+#[derive(Parser, Clone, Debug)]
+pub struct QuetzUploadOpts {
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub quetz: QuetzOpts,
+
+    /// The endpoint that exchanges the OIDC token of the CI runner for an API key of the
+    /// Quetz server (trusted publishing). Quetz does not provide such an endpoint itself, it
+    /// has to be provided by a plugin or a service in front of the server.
+    #[arg(long, env = "QUETZ_TRUSTED_PUBLISHING_URL")]
+    pub trusted_publishing_url: Option<Url>,
+}
+
+/// Upload to an S3 bucket that is laid out as a conda channel.
+/// Authentication is used from the arguments, the keychain / auth-file or the standard AWS
+/// credential chain.
+#[cfg(feature = "s3")]
+#[derive(Parser, Clone, Debug)]
+pub struct S3UploadOpts {
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub s3: S3Opts,
+
+    /// Update the `repodata.json` of every subdir that received a package after the upload
+    /// (the channel is initialized if needed)
+    #[arg(long)]
+    pub index: bool,
+}
+
+/// Options for uploading with an uploader plugin
+#[derive(Parser, Clone, Debug)]
+pub struct PluginUploadOpts {
+    /// The name of the uploader plugin in the configuration
+    #[arg(short, long)]
+    pub name: String,
+}
+
+/// Options for uploading to an Artifactory channel.
+/// Authentication is used from the arguments, or from the keychain / auth-file.
+///
+/// Packages are deployed by checksum first, so the package is only transferred if Artifactory
+/// does not have a file with the same checksum yet.
+#[derive(Parser, Clone, Debug)]
+pub struct ArtifactoryUploadOpts {
+    /// The URL to your Artifactory server
+    #[arg(short, long, env = "ARTIFACTORY_SERVER_URL")]
+    pub url: Url,
+
+    /// The URL to your channel
+    #[arg(short, long = "channel", env = "ARTIFACTORY_CHANNEL")]
+    pub channels: String,
+
+    /// Your Artifactory username (for basic authentication with the password)
+    #[arg(long, env = "ARTIFACTORY_USERNAME", hide = true)]
+    pub username: Option<String>,
+
+    /// Your Artifactory password (for basic authentication with the username)
+    #[arg(long, env = "ARTIFACTORY_PASSWORD", hide = true)]
+    pub password: Option<String>,
+
+    /// Your Artifactory access token
+    #[arg(short, long, env = "ARTIFACTORY_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Your Artifactory API key (deprecated by JFrog in favor of access tokens)
+    #[arg(long, env = "ARTIFACTORY_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// A property to set on the uploaded packages (e.g. `team=data`). Can be given multiple
+    /// times. On GitHub Actions and GitLab CI, `build.number` and `vcs.revision` are set
+    /// automatically.
+    #[arg(long = "property", value_name = "KEY=VALUE", value_parser = parse_property)]
+    pub properties: Vec<(String, String)>,
+}
+
+/// Upload to an OCI registry, following the layout that is used by `oci://` channels.
+/// Authentication is read from the arguments, or from the docker config
+/// (`$DOCKER_CONFIG/config.json` or `~/.docker/config.json`).
+#[derive(Parser, Clone, Debug)]
+pub struct OciUploadOpts {
+    /// The URL of the channel in the registry, e.g. `oci://ghcr.io/my-org/my-channel`
+    #[arg(short, long, env = "OCI_CHANNEL_URL")]
+    pub url: Url,
+
+    /// The username for the registry
+    #[arg(long, env = "OCI_USERNAME", requires = "password")]
+    pub username: Option<String>,
+
+    /// The password or token for the registry
+    #[arg(
+        long,
+        env = "OCI_PASSWORD",
+        requires = "username",
+        hide_env_values = true
+    )]
+    pub password: Option<String>,
+
+    /// Skip packages whose version and build string already exist in the registry
+    #[arg(long)]
+    pub skip_existing: bool,
+}
+
+/// Attach packages to a GitHub release. The release is created if it does not exist, and the
+/// SHA256 checksums of the packages are added to the release notes.
+#[derive(Parser, Clone, Debug)]
+pub struct GithubReleaseUploadOpts {
+    /// The repository, e.g. `my-org/my-repo`
+    #[arg(short, long, env = "GITHUB_REPOSITORY")]
+    pub repository: String,
+
+    /// The tag of the release
+    #[arg(short, long)]
+    pub tag: String,
+
+    /// The GitHub token, needs write access to the contents of the repository
+    #[arg(long, env = "GITHUB_TOKEN", hide_env_values = true)]
+    pub token: String,
+
+    /// The URL of the GitHub API (for GitHub Enterprise Server)
+    #[arg(long, env = "GITHUB_API_URL", default_value = "https://api.github.com")]
+    pub api_url: Url,
+
+    /// Skip packages that are already attached to the release
+    #[arg(long)]
+    pub skip_existing: bool,
+}
+
+/// Move packages that were uploaded to anaconda.org from one label to another.
+/// Authentication is used from the keychain / auth-file if no API key is given.
+#[derive(Parser, Clone, Debug)]
+pub struct AnacondaPromoteOpts {
+    /// The owner of the distribution (e.g. conda-forge or your username)
+    #[arg(short, long, env = "ANACONDA_OWNER")]
+    pub owner: String,
+
+    /// The label that the packages are currently in (e.g. `rc`)
+    #[arg(long)]
+    pub from_label: String,
+
+    /// The label to add the packages to
+    #[arg(long, default_value = "main")]
+    pub to_label: String,
+
+    /// Keep the packages in the label they are promoted from
+    #[arg(long)]
+    pub keep_from_label: bool,
+
+    /// The Anaconda API key, if none is provided, the token is read from the
+    /// keychain / auth-file
+    #[arg(short, long, env = "ANACONDA_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// The URL of the Anaconda API
+    #[arg(
+        short,
+        long,
+        env = "ANACONDA_SERVER_URL",
+        default_value = "https://api.anaconda.org"
+    )]
+    pub url: Url,
+}
+
+#[derive(Debug, Clone)]
+/// Data structure containing the configuration for debugging a recipe
+pub struct DebugData {
+    /// Path to the recipe file to debug
+    pub recipe_path: PathBuf,
+    /// Directory where build artifacts will be stored
+    pub output_dir: PathBuf,
+    /// Platform where the build is being executed
+    pub build_platform: Platform,
+    /// Target platform for the build
+    pub target_platform: Platform,
+    /// Host platform for runtime dependencies
+    pub host_platform: Platform,
+    /// List of channels to search for dependencies
+    pub channels: Option<Vec<NamedChannelOrUrl>>,
+    /// Common configuration options
+    pub common: CommonData,
+    /// Name of the specific output to debug (if recipe has multiple outputs)
+    pub output_name: Option<String>,
+}
+
+impl DebugData {
+    /// Generate a new DebugData struct from DebugSetupOpts and an optional
+    /// config.
+    pub fn from_setup_opts_and_config(opts: DebugSetupOpts, config: Option<Config>) -> Self {
+        let config = config.unwrap_or_default();
+        let settings = &config.extensions;
+        let target_platform = opts
+            .target_platform
+            .or(settings.target_platform)
+            .unwrap_or(Platform::current());
+        let common = CommonData::from_opts_and_config(opts.common, config.clone());
+        Self {
+            recipe_path: opts.recipe_path.unwrap_or(opts.recipe),
+            output_dir: common.output_dir.clone(),
+            build_platform: opts
+                .build_platform
+                .or(settings.build_platform)
+                .unwrap_or(Platform::current()),
+            target_platform,
+            host_platform: opts
+                .host_platform
+                .or(settings.host_platform)
+                .unwrap_or(target_platform),
+            channels: opts.channels.or_else(|| config.default_channels.clone()),
+            common,
+            output_name: opts.output_name,
+        }
+    }
+}
+
+/// Options for the `create-patch` command.
+#[derive(Parser, Debug, Clone)]
+pub struct CreatePatchOpts {
+    /// Directory where we want to create the patch.
+    /// Defaults to current directory if not specified.
+    #[arg(short, long)]
+    pub directory: Option<PathBuf>,
+
+    /// The name for the patch file to create.
+    #[arg(long, default_value = "changes")]
+    pub name: String,
+
+    /// Whether to overwrite the patch file if it already exists.
+    #[arg(long, default_value = "false")]
+    pub overwrite: bool,
+
+    /// Optional directory where the patch file should be written. Defaults to the recipe directory determined from `.source_info.json` if not provided.
+    #[arg(long, value_name = "DIR")]
+    pub patch_dir: Option<PathBuf>,
+
+    /// Comma-separated list of file names (or glob patterns) that should be excluded from the diff.
+    #[arg(long, value_delimiter = ',')]
+    pub exclude: Option<Vec<String>>,
+
+    /// Include new files matching these glob patterns (e.g., "*.txt", "src/**/*.rs")
+    #[arg(long, value_delimiter = ',')]
+    pub add: Option<Vec<String>>,
+
+    /// Only include modified files matching these glob patterns (e.g., "*.c", "src/**/*.rs")
+    /// If not specified, all modified files are included (subject to --exclude)
     #[arg(long, value_delimiter = ',')]
     pub include: Option<Vec<String>>,
 
@@ -1326,6 +2271,15 @@ pub struct InspectOpts {
     #[arg(long)]
     pub run_exports: bool,
 
+    /// Show where each file came from and which post-processing steps modified it
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Unpack the package and report prefix placeholders, rpaths, shebangs and paths that look
+    /// specific to the build machine
+    #[arg(long)]
+    pub relocatability: bool,
+
     /// Show all available information
     #[arg(long)]
     pub all: bool,
@@ -1350,6 +2304,16 @@ impl InspectOpts {
     pub fn show_run_exports(&self) -> bool {
         self.run_exports || self.all
     }
+
+    /// Check if file provenance should be shown (either explicitly or via --all)
+    pub fn show_provenance(&self) -> bool {
+        self.provenance || self.all
+    }
+
+    /// Check if the relocatability report should be shown (either explicitly or via --all)
+    pub fn show_relocatability(&self) -> bool {
+        self.relocatability || self.all
+    }
 }
 
 /// Options for the `package extract` command.
@@ -1363,9 +2327,32 @@ pub struct ExtractOpts {
     pub dest: Option<PathBuf>,
 }
 
+/// Options for the `package abi-diff` command.
+#[derive(Parser, Debug, Clone)]
+pub struct AbiDiffOpts {
+    /// Path to the newly built package file (.conda, .tar.bz2)
+    pub package_file: PathBuf,
+
+    /// The previous build to compare against (a local path or a URL)
+    #[arg(long, required_unless_present = "channel", conflicts_with = "channel")]
+    pub previous: Option<PackageSource>,
+
+    /// Look up the previous version of the package in these channels
+    #[arg(short = 'c', long)]
+    pub channel: Vec<NamedChannelOrUrl>,
+
+    /// Output the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
 /// Options for the `bump-recipe` command.
 #[derive(Parser, Debug, Clone)]
 pub struct BumpRecipeOpts {
+    /// Path to the recipe file (recipe.yaml), as an alternative to `--recipe`.
+    #[arg(value_name = "RECIPE", conflicts_with = "recipe")]
+    pub recipe_path: Option<PathBuf>,
+
     /// Path to the recipe file (recipe.yaml). Defaults to current directory.
     #[arg(short, long, default_value = ".")]
     pub recipe: PathBuf,
@@ -1392,6 +2379,468 @@ pub struct BumpRecipeOpts {
     pub keep_build_number: bool,
 }
 
+/// Options for the `check-updates` command.
+#[derive(Parser, Debug, Clone)]
+pub struct CheckUpdatesOpts {
+    /// The recipe file or directory containing `recipe.yaml`. Defaults to the
+    /// current directory.
+    #[arg(
+        short,
+        long = "recipe",
+        default_value = ".",
+        default_value_if("recipe_dir", ArgPredicate::IsPresent, None),
+        conflicts_with = "recipe_dir"
+    )]
+    pub recipes: Vec<PathBuf>,
+
+    /// The directory that contains recipes, to check all of them.
+    #[arg(long, value_parser = is_dir, conflicts_with = "recipes")]
+    pub recipe_dir: Option<PathBuf>,
+
+    /// Include pre-release versions (e.g., alpha, beta, rc).
+    #[arg(long, default_value = "false")]
+    pub include_prerelease: bool,
+
+    /// Output the results as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Options for the `index` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct IndexOpts {
+    /// The channel to index: a local directory or an S3 URL (e.g. `s3://my-bucket/my-channel`)
+    pub channel: String,
+
+    /// The platforms to index (e.g. `linux-64`). Defaults to all platforms of the channel
+    #[arg(short, long = "target-platform")]
+    pub target_platforms: Vec<Platform>,
+
+    /// Re-index all packages instead of only the packages that were added or removed
+    #[arg(long)]
+    pub force: bool,
+
+    /// Path to an auth-file to read authentication information from
+    #[clap(long, env = "RATTLER_AUTH_FILE", hide = true)]
+    pub auth_file: Option<PathBuf>,
+}
+
+/// Options for the `outputs` subcommand.
+#[derive(Parser, Clone)]
+pub struct OutputsOpts {
+    /// The recipe file or directory containing `recipe.yaml`. Defaults to the current
+    /// directory.
+    #[arg(default_value = ".")]
+    pub recipe: PathBuf,
+
+    /// The build platform to render the recipe for.
+    #[arg(long)]
+    pub build_platform: Option<Platform>,
+
+    /// The target platform to render the recipe for.
+    #[arg(long)]
+    pub target_platform: Option<Platform>,
+
+    /// The host platform to render the recipe for. If set, it will be used to determine
+    /// also the target_platform (as long as it is not noarch).
+    #[arg(long)]
+    pub host_platform: Option<Platform>,
+
+    /// Variant configuration files for the build.
+    #[arg(short = 'm', long)]
+    pub variant_config: Option<Vec<PathBuf>>,
+
+    /// Override specific variant values (e.g. --variant python=3.12 or --variant python=3.12,3.11).
+    #[arg(long = "variant", value_parser = parse_variant_override, action = clap::ArgAction::Append)]
+    pub variant_overrides: Vec<(String, Vec<String>)>,
+
+    /// Do not read the `variants.yaml` file next to a recipe.
+    #[arg(long)]
+    pub ignore_recipe_variants: bool,
+
+    /// Cache the rendered recipes and their variants in the cache directory (as with
+    /// `build --render-cache`).
+    #[arg(long, env = "RATTLER_BUILD_RENDER_CACHE")]
+    pub render_cache: bool,
+
+    /// The package format of the packages (`tar-bz2` or `conda`), this determines the file
+    /// names.
+    #[arg(long)]
+    pub package_format: Option<PackageFormatAndCompression>,
+
+    /// Override the build number for all outputs (defaults to the build number in the recipe)
+    #[arg(long)]
+    pub build_num: Option<u64>,
+
+    /// Only list noarch packages if the build platform is this platform (as with
+    /// `build --noarch-build-platform`).
+    #[arg(long)]
+    pub noarch_build_platform: Option<Platform>,
+
+    /// Print the outputs as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub common: CommonOpts,
+}
+
+impl From<OutputsOpts> for BuildOpts {
+    fn from(opts: OutputsOpts) -> Self {
+        Self {
+            recipes: vec![opts.recipe],
+            build_platform: opts.build_platform,
+            target_platform: opts.target_platform.into_iter().collect(),
+            host_platform: opts.host_platform,
+            variant_config: opts.variant_config,
+            variant_overrides: opts.variant_overrides,
+            ignore_recipe_variants: opts.ignore_recipe_variants,
+            render_cache: opts.render_cache,
+            render_only: true,
+            package_format: opts.package_format,
+            build_num: opts.build_num,
+            noarch_build_platform: opts.noarch_build_platform,
+            common: opts.common,
+            ..Default::default()
+        }
+    }
+}
+
+/// The format of the dependency graph
+#[derive(ValueEnum, Clone, Debug, Copy, Default, Eq, Hash, PartialEq)]
+pub enum GraphFormat {
+    /// The DOT language of Graphviz
+    #[default]
+    Dot,
+    /// A Mermaid flowchart
+    Mermaid,
+    /// JSON with the nodes and edges of the graph
+    Json,
+}
+
+/// Options for the `graph` subcommand.
+#[derive(Parser, Clone)]
+pub struct GraphOpts {
+    /// The recipe file or directory containing `recipe.yaml`. Defaults to the
+    /// current directory.
+    #[arg(
+        short,
+        long = "recipe",
+        default_value = ".",
+        default_value_if("recipe_dir", ArgPredicate::IsPresent, None),
+        conflicts_with = "recipe_dir"
+    )]
+    pub recipes: Vec<PathBuf>,
+
+    /// The directory that contains recipes, to print the graph among all of them.
+    #[arg(long, value_parser = is_dir, conflicts_with = "recipes")]
+    pub recipe_dir: Option<PathBuf>,
+
+    /// The format of the graph.
+    #[arg(long, default_value = "dot")]
+    pub format: GraphFormat,
+
+    /// The build platform to render the recipes for.
+    #[arg(long)]
+    pub build_platform: Option<Platform>,
+
+    /// The target platform to render the recipes for.
+    #[arg(long)]
+    pub target_platform: Option<Platform>,
+
+    /// The host platform to render the recipes for. If set, it will be used to determine
+    /// also the target_platform (as long as it is not noarch).
+    #[arg(long)]
+    pub host_platform: Option<Platform>,
+
+    /// Variant configuration files for the build.
+    #[arg(short = 'm', long)]
+    pub variant_config: Option<Vec<PathBuf>>,
+
+    /// Override specific variant values (e.g. --variant python=3.12 or --variant python=3.12,3.11).
+    #[arg(long = "variant", value_parser = parse_variant_override, action = clap::ArgAction::Append)]
+    pub variant_overrides: Vec<(String, Vec<String>)>,
+
+    /// Do not read the `variants.yaml` file next to a recipe.
+    #[arg(long)]
+    pub ignore_recipe_variants: bool,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub common: CommonOpts,
+}
+
+impl From<GraphOpts> for BuildOpts {
+    fn from(opts: GraphOpts) -> Self {
+        Self {
+            recipes: opts.recipes,
+            recipe_dir: opts.recipe_dir,
+            build_platform: opts.build_platform,
+            target_platform: opts.target_platform.into_iter().collect(),
+            host_platform: opts.host_platform,
+            variant_config: opts.variant_config,
+            variant_overrides: opts.variant_overrides,
+            ignore_recipe_variants: opts.ignore_recipe_variants,
+            render_only: true,
+            common: opts.common,
+            ..Default::default()
+        }
+    }
+}
+
+/// The CI provider to generate the pipeline for
+#[derive(ValueEnum, Clone, Debug, Copy, Eq, Hash, PartialEq)]
+pub enum CiProvider {
+    /// GitHub Actions (`.github/workflows/rattler-build.yml`)
+    #[value(name = "github")]
+    GitHub,
+    /// GitLab CI/CD (`.gitlab-ci.yml`)
+    #[value(name = "gitlab")]
+    GitLab,
+    /// Azure Pipelines (`azure-pipelines.yml`)
+    Azure,
+}
+
+/// Options for the `generate-ci` subcommand.
+#[derive(Parser, Clone)]
+pub struct GenerateCiOpts {
+    /// The CI provider to generate the pipeline for.
+    #[arg(long)]
+    pub provider: CiProvider,
+
+    /// The recipe file or directory containing `recipe.yaml`. Defaults to the
+    /// current directory.
+    #[arg(
+        short,
+        long = "recipe",
+        default_value = ".",
+        default_value_if("recipe_dir", ArgPredicate::IsPresent, None),
+        conflicts_with = "recipe_dir"
+    )]
+    pub recipes: Vec<PathBuf>,
+
+    /// The directory that contains recipes, to build all of them in the pipeline.
+    #[arg(long, value_parser = is_dir, conflicts_with = "recipes")]
+    pub recipe_dir: Option<PathBuf>,
+
+    /// The platforms to build the packages for. Platforms without a runner of the provider are
+    /// cross-compiled.
+    #[arg(
+        long = "platform",
+        default_values = ["linux-64", "linux-aarch64", "osx-64", "osx-arm64", "win-64"]
+    )]
+    pub platforms: Vec<Platform>,
+
+    /// Channels to use when building.
+    #[arg(short = 'c', long = "channel")]
+    pub channels: Option<Vec<NamedChannelOrUrl>>,
+
+    /// Variant configuration files for the build.
+    #[arg(short = 'm', long)]
+    pub variant_config: Option<Vec<PathBuf>>,
+
+    /// Do not read the `variants.yaml` file next to a recipe.
+    #[arg(long)]
+    pub ignore_recipe_variants: bool,
+
+    /// Use the global pinning of conda-forge as variant configuration (`conda-forge`, or
+    /// `conda-forge@<git ref>`).
+    #[arg(long)]
+    pub pinning: Option<Pinning>,
+
+    /// The channel to publish the packages to, on pushes to the branch. Without it, the
+    /// pipeline only builds the packages.
+    #[arg(long)]
+    pub upload_to: Option<NamedChannelOrUrl>,
+
+    /// The branch whose pushes publish the packages.
+    #[arg(long, default_value = "main")]
+    pub branch: String,
+
+    /// The file to write the pipeline to (`-` for stdout). Defaults to the file that the
+    /// provider reads, e.g. `.github/workflows/rattler-build.yml`.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub common: CommonOpts,
+}
+
+impl From<GenerateCiOpts> for BuildOpts {
+    fn from(opts: GenerateCiOpts) -> Self {
+        Self {
+            recipes: opts.recipes,
+            recipe_dir: opts.recipe_dir,
+            target_platform: opts.platforms,
+            channels: opts.channels,
+            variant_config: opts.variant_config,
+            ignore_recipe_variants: opts.ignore_recipe_variants,
+            pinning: opts.pinning,
+            render_only: true,
+            common: opts.common,
+            ..Default::default()
+        }
+    }
+}
+
+/// Options for the `clean` subcommand.
+#[derive(Parser, Clone, Debug)]
+pub struct CleanOpts {
+    /// The output directory of the builds
+    #[arg(long, env = "CONDA_BLD_PATH")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Remove the build directories (`<output-dir>/bld`) with the work directories and the
+    /// build and host environments.
+    #[arg(long, help_heading = "Selectors")]
+    pub build_dirs: bool,
+
+    /// Remove the source cache (`<output-dir>/src_cache`).
+    #[arg(long, help_heading = "Selectors")]
+    pub sources: bool,
+
+    /// Remove the intermediate files of builds: the staging caches (`<output-dir>/build_cache`)
+    /// and the test environments (`<output-dir>/test`).
+    #[arg(long, help_heading = "Selectors")]
+    pub intermediates: bool,
+
+    /// Remove the package cache (`pkgs` in the cache directory), which is shared with other
+    /// tools that use the same cache directory.
+    #[arg(long, help_heading = "Selectors")]
+    pub packages: bool,
+
+    /// Remove everything, including the package cache.
+    #[arg(long, help_heading = "Selectors")]
+    pub all: bool,
+
+    /// Only remove entries that were last modified longer ago than this (e.g. `30m`, `12h`,
+    /// `7d` or `2w`).
+    #[arg(long, value_name = "AGE", value_parser = parse_age)]
+    pub older_than: Option<std::time::Duration>,
+
+    /// Only report what would be removed, without removing anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Options for the `audit` subcommand.
+#[derive(Parser, Clone)]
+pub struct AuditOpts {
+    /// The packages to audit (local paths or URLs)
+    #[arg(required = true)]
+    pub packages: Vec<PackageSource>,
+
+    /// Fail if a vulnerability has at least this severity
+    #[arg(long, value_name = "SEVERITY")]
+    pub fail_on: Option<Severity>,
+
+    /// The channels to solve the run environment with. Defaults to the channels of the build
+    /// of the package.
+    #[arg(short = 'c', long = "channel")]
+    pub channels: Option<Vec<NamedChannelOrUrl>>,
+
+    /// The OSV API to query (defaults to https://api.osv.dev)
+    #[arg(long, env = "RATTLER_BUILD_OSV_URL")]
+    pub osv_url: Option<Url>,
+
+    /// Output the reports as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Common options.
+    #[clap(flatten)]
+    pub common: CommonOpts,
+}
+
+/// Options for the `explain-rebuild` subcommand.
+#[derive(Parser, Clone)]
+pub struct ExplainRebuildOpts {
+    /// The previous build of the package (a local path or a URL)
+    pub previous: PackageSource,
+
+    /// The recipe file or directory containing `recipe.yaml`. Defaults to the current
+    /// directory.
+    #[arg(short, long, default_value = ".")]
+    pub recipe: PathBuf,
+
+    /// The build platform to render the recipe for.
+    #[arg(long)]
+    pub build_platform: Option<Platform>,
+
+    /// The target platform to render the recipe for. Defaults to the target platform of the
+    /// previous build.
+    #[arg(long)]
+    pub target_platform: Option<Platform>,
+
+    /// The host platform to render the recipe for.
+    #[arg(long)]
+    pub host_platform: Option<Platform>,
+
+    /// Variant configuration files for the build.
+    #[arg(short = 'm', long)]
+    pub variant_config: Option<Vec<PathBuf>>,
+
+    /// Override specific variant values (e.g. --variant python=3.12 or --variant python=3.12,3.11).
+    #[arg(long = "variant", value_parser = parse_variant_override, action = clap::ArgAction::Append)]
+    pub variant_overrides: Vec<(String, Vec<String>)>,
+
+    /// Do not read the `variants.yaml` file next to a recipe.
+    #[arg(long)]
+    pub ignore_recipe_variants: bool,
+
+    /// Override the build number for all outputs (defaults to the build number in the recipe)
+    #[arg(long)]
+    pub build_num: Option<u64>,
+
+    /// Output the explanation as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    #[allow(missing_docs)]
+    #[clap(flatten)]
+    pub common: CommonOpts,
+}
+
+impl From<ExplainRebuildOpts> for BuildOpts {
+    fn from(opts: ExplainRebuildOpts) -> Self {
+        Self {
+            recipes: vec![opts.recipe],
+            build_platform: opts.build_platform,
+            target_platform: opts.target_platform.into_iter().collect(),
+            host_platform: opts.host_platform,
+            variant_config: opts.variant_config,
+            variant_overrides: opts.variant_overrides,
+            ignore_recipe_variants: opts.ignore_recipe_variants,
+            render_only: true,
+            build_num: opts.build_num,
+            common: opts.common,
+            ..Default::default()
+        }
+    }
+}
+
+/// The format of the recipe schema
+#[derive(ValueEnum, Clone, Debug, Copy, Default, Eq, Hash, PartialEq)]
+pub enum SchemaFormat {
+    /// JSON Schema (draft 2020-12)
+    #[default]
+    JsonSchema,
+}
+
+/// Options for the `schema` subcommand.
+#[derive(Parser, Clone)]
+pub struct SchemaOpts {
+    /// The format of the schema.
+    #[arg(long, default_value = "json-schema")]
+    pub format: SchemaFormat,
+
+    /// Write the schema to this file instead of stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
 /// Options for the `migrate-recipe` subcommand.
 #[derive(Parser)]
 pub struct MigrateRecipeOpts {
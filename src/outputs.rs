@@ -0,0 +1,65 @@
+//! List the packages that a recipe produces (`rattler-build outputs`), without solving the
+//! dependencies or building anything.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::metadata::Output;
+
+/// A package that a variant of a recipe produces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct PackageOutput {
+    /// The name of the package
+    pub name: String,
+    /// The version of the package
+    pub version: String,
+    /// The build string of the package
+    pub build_string: String,
+    /// The subdir that the package is uploaded to (e.g. `linux-64` or `noarch`)
+    pub subdir: String,
+    /// The file name of the package
+    pub filename: String,
+    /// The variant of the package (the variables that were used to render it)
+    pub variant: BTreeMap<String, String>,
+}
+
+impl From<&Output> for PackageOutput {
+    fn from(output: &Output) -> Self {
+        let settings = &output.build_configuration.packaging_settings;
+        Self {
+            name: output.name().as_normalized().to_string(),
+            version: output.version().to_string(),
+            build_string: output.build_string().to_string(),
+            subdir: output.target_platform().to_string(),
+            filename: format!(
+                "{}{}",
+                output.identifier(),
+                settings.archive_type.extension()
+            ),
+            variant: output
+                .variant()
+                .iter()
+                .map(|(key, value)| (key.normalize(), value.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// Render the table of `packages`
+pub(crate) fn outputs_table(packages: &[PackageOutput]) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(["Name", "Version", "Build", "Subdir", "Filename"]);
+    for package in packages {
+        table.add_row([
+            package.name.as_str(),
+            package.version.as_str(),
+            package.build_string.as_str(),
+            package.subdir.as_str(),
+            package.filename.as_str(),
+        ]);
+    }
+    table
+}
@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use fs_err as fs;
 use indicatif::HumanBytes;
+use itertools::Itertools;
 use miette::{Context, IntoDiagnostic};
 use rattler_conda_types::package::{
     AboutJson, CondaArchiveType, IndexJson, PathType, PathsJson, RunExportsJson,
@@ -15,16 +16,26 @@ use reqwest::Client;
 use url::Url;
 
 use crate::opt::{ExtractOpts, InspectOpts, PackageSource};
+use crate::packaging::PathsProvenance;
 
 #[cfg(feature = "s3")]
 use rattler_networking::s3_middleware;
 
+mod abi_diff;
+mod relocatability;
+
+pub use abi_diff::abi_diff;
+use relocatability::RelocatabilityReport;
+
 /// Package metadata read from the archive
 struct PackageMetadata {
     index: IndexJson,
     about: Option<AboutJson>,
     paths: Option<PathsJson>,
     run_exports: Option<RunExportsJson>,
+    provenance: Option<PathsProvenance>,
+    /// Only collected when requested, because the package has to be unpacked
+    relocatability: Option<RelocatabilityReport>,
 }
 
 /// Read and display information about a package
@@ -47,29 +58,24 @@ pub fn package_info(args: InspectOpts) -> miette::Result<()> {
     }
 
     // Read metadata directly from the package file
-    let metadata = read_package_metadata(package_path)?;
+    let mut metadata = read_package_metadata(package_path)?;
+
+    // The relocatability report needs the contents of the package
+    if args.show_relocatability() {
+        metadata.relocatability = Some(relocatability::relocatability_report(
+            package_path,
+            metadata.paths.as_ref(),
+        )?);
+    }
 
     // Output as JSON if requested
     if args.json {
-        output_json(
-            &metadata.index,
-            &metadata.about,
-            &metadata.paths,
-            &metadata.run_exports,
-            &args,
-        )?;
+        output_json(&metadata, &args)?;
         return Ok(());
     }
 
     // Output human-readable format
-    output_human_readable(
-        &metadata.index,
-        &metadata.about,
-        &metadata.paths,
-        &metadata.run_exports,
-        &args,
-        package_path,
-    )?;
+    output_human_readable(&metadata, &args, package_path)?;
 
     Ok(())
 }
@@ -90,22 +96,29 @@ fn read_package_metadata(package_path: &Path) -> miette::Result<PackageMetadata>
     // Read run_exports.json (optional)
     let run_exports_json: Option<RunExportsJson> = read_package_file(package_path).ok();
 
+    // Read paths_provenance.json (optional, only written by newer versions of rattler-build)
+    let provenance_json: Option<PathsProvenance> = read_package_file(package_path).ok();
+
     Ok(PackageMetadata {
         index: index_json,
         about: about_json,
         paths: paths_json,
         run_exports: run_exports_json,
+        provenance: provenance_json,
+        relocatability: None,
     })
 }
 
 /// Output package information in JSON format
-fn output_json(
-    index_json: &IndexJson,
-    about_json: &Option<AboutJson>,
-    paths_json: &Option<PathsJson>,
-    run_exports_json: &Option<RunExportsJson>,
-    args: &InspectOpts,
-) -> miette::Result<()> {
+fn output_json(metadata: &PackageMetadata, args: &InspectOpts) -> miette::Result<()> {
+    let PackageMetadata {
+        index: index_json,
+        about: about_json,
+        paths: paths_json,
+        run_exports: run_exports_json,
+        provenance: provenance_json,
+        relocatability,
+    } = metadata;
     let mut output = serde_json::Map::new();
 
     // Always include index info
@@ -144,6 +157,24 @@ fn output_json(
         );
     }
 
+    // Include file provenance if requested
+    if args.show_provenance()
+        && let Some(provenance) = provenance_json
+    {
+        output.insert(
+            "provenance".to_string(),
+            serde_json::to_value(provenance).into_diagnostic()?,
+        );
+    }
+
+    // Include the relocatability report if requested
+    if let Some(relocatability) = relocatability {
+        output.insert(
+            "relocatability".to_string(),
+            serde_json::to_value(relocatability).into_diagnostic()?,
+        );
+    }
+
     println!(
         "{}",
         serde_json::to_string_pretty(&output).into_diagnostic()?
@@ -154,13 +185,18 @@ fn output_json(
 
 /// Output package information in human-readable format
 fn output_human_readable(
-    index_json: &IndexJson,
-    about_json: &Option<AboutJson>,
-    paths_json: &Option<PathsJson>,
-    run_exports_json: &Option<RunExportsJson>,
+    metadata: &PackageMetadata,
     args: &InspectOpts,
     package_path: &Path,
 ) -> miette::Result<()> {
+    let PackageMetadata {
+        index: index_json,
+        about: about_json,
+        paths: paths_json,
+        run_exports: run_exports_json,
+        provenance: provenance_json,
+        relocatability,
+    } = metadata;
     // Package file info
     if package_path.is_file() {
         let size = fs::metadata(package_path).map(|m| m.len()).unwrap_or(0);
@@ -327,6 +363,46 @@ fn output_human_readable(
         tracing::info!("{}", run_exports_str);
     }
 
+    // File provenance (only with --provenance flag)
+    if args.show_provenance() {
+        match provenance_json {
+            Some(provenance) => {
+                tracing::info!("\nFile provenance:");
+
+                let mut provenance_table = comfy_table::Table::new();
+                provenance_table
+                    .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+                    .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+                    .set_header(vec!["Path", "Origin", "Modified by"]);
+
+                for entry in &provenance.paths {
+                    let origin = entry
+                        .origin
+                        .map(|o| o.to_string())
+                        .unwrap_or_else(|| "-".to_string());
+                    let modified_by = if entry.modified_by.is_empty() {
+                        "-".to_string()
+                    } else {
+                        entry.modified_by.iter().map(|m| m.to_string()).join(", ")
+                    };
+
+                    let path = entry.relative_path.to_string_lossy();
+                    provenance_table.add_row(vec![&*path, &origin, &modified_by]);
+                }
+
+                tracing::info!("{}", provenance_table);
+            }
+            None => {
+                tracing::info!("\nThe package does not contain file provenance information");
+            }
+        }
+    }
+
+    // Relocatability report (only with --relocatability flag)
+    if let Some(relocatability) = relocatability {
+        relocatability::print_report(relocatability);
+    }
+
     Ok(())
 }
 
@@ -339,7 +415,8 @@ fn strip_package_extension(filename: &str) -> &str {
 
 /// Creates an HTTP client with authentication middleware
 /// TODO: Refactor to use rattler-build-networking when its available.
-fn create_authenticated_client() -> miette::Result<reqwest_middleware::ClientWithMiddleware> {
+pub(crate) fn create_authenticated_client()
+-> miette::Result<reqwest_middleware::ClientWithMiddleware> {
     let download_client = Client::builder()
         .no_gzip()
         .build()
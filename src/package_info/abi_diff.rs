@@ -0,0 +1,693 @@
+//! Compare the exported symbols of the shared libraries in a package against a previous build.
+//!
+//! Both packages are unpacked into temporary directories, the exported symbols of every shared
+//! library (ELF, Mach-O and PE) are collected and the libraries are matched by their SONAME /
+//! install name (or the file name). Removed or changed symbols break binaries that were linked
+//! against the previous build, which means the `run_exports` pin has to be tightened or a
+//! migration is needed.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use goblin::elf::sym::{
+    STB_GLOBAL, STB_GNU_UNIQUE, STB_WEAK, STT_FUNC, STT_GNU_IFUNC, STT_OBJECT, STT_TLS, STV_HIDDEN,
+    STV_INTERNAL,
+};
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::{
+    ChannelConfig, MatchSpec, PackageName, Platform, RepoDataRecord, package::IndexJson,
+};
+use rattler_package_streaming::seek::read_package_file;
+use rattler_repodata_gateway::Gateway;
+use serde::Serialize;
+
+use crate::opt::{AbiDiffOpts, PackageSource};
+
+/// The kind of an exported symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    /// A function (including indirect functions)
+    Function,
+    /// A data object
+    Object,
+    /// A thread local variable
+    Tls,
+    /// Anything else (or unknown, e.g. for Mach-O and PE exports)
+    Other,
+}
+
+/// An exported symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Symbol {
+    /// The kind of the symbol
+    pub kind: SymbolKind,
+    /// The size of the symbol (only known for ELF)
+    pub size: u64,
+}
+
+/// The exported symbols of a shared library
+#[derive(Debug, Clone, Default)]
+struct Library {
+    /// The path of the library in the package
+    path: PathBuf,
+    /// The exported symbols by name
+    symbols: BTreeMap<String, Symbol>,
+}
+
+/// A symbol that changed between the two builds
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SymbolChange {
+    /// The name of the symbol
+    pub name: String,
+    /// The symbol in the previous build
+    pub old: Symbol,
+    /// The symbol in the new build
+    pub new: Symbol,
+}
+
+/// The differences of a library that exists in both builds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LibraryDiff {
+    /// The SONAME / install name (or file name) of the library
+    pub library: String,
+    /// The path of the library in the new package
+    pub path: PathBuf,
+    /// Symbols that are no longer exported
+    pub removed: Vec<String>,
+    /// Symbols that are newly exported
+    pub added: Vec<String>,
+    /// Symbols whose kind or size changed
+    pub changed: Vec<SymbolChange>,
+}
+
+/// The ABI differences between two builds of a package
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AbiDiffReport {
+    /// The package that was compared against
+    pub previous: String,
+    /// Libraries that only exist in the previous build
+    pub removed_libraries: Vec<String>,
+    /// Libraries that only exist in the new build
+    pub added_libraries: Vec<String>,
+    /// Libraries that exist in both builds and have different exports
+    pub libraries: Vec<LibraryDiff>,
+}
+
+impl AbiDiffReport {
+    /// Whether binaries linked against the previous build still work with the new build
+    pub fn is_backwards_compatible(&self) -> bool {
+        self.removed_libraries.is_empty()
+            && self
+                .libraries
+                .iter()
+                .all(|lib| lib.removed.is_empty() && lib.changed.is_empty())
+    }
+
+    /// Whether there are any differences at all
+    pub fn has_changes(&self) -> bool {
+        !self.removed_libraries.is_empty()
+            || !self.added_libraries.is_empty()
+            || !self.libraries.is_empty()
+    }
+}
+
+/// Compare the symbols of a library in two builds
+fn diff_symbols(
+    old: &BTreeMap<String, Symbol>,
+    new: &BTreeMap<String, Symbol>,
+) -> (Vec<String>, Vec<String>, Vec<SymbolChange>) {
+    let removed = old
+        .keys()
+        .filter(|name| !new.contains_key(*name))
+        .cloned()
+        .collect();
+    let added = new
+        .keys()
+        .filter(|name| !old.contains_key(*name))
+        .cloned()
+        .collect();
+    let changed = old
+        .iter()
+        .filter_map(|(name, old)| {
+            let new = new.get(name)?;
+            // the size of a function does not matter for the ABI, the size of an object does
+            let size_changed = old.kind == SymbolKind::Object && old.size != new.size;
+            (old.kind != new.kind || size_changed).then(|| SymbolChange {
+                name: name.clone(),
+                old: *old,
+                new: *new,
+            })
+        })
+        .collect();
+    (removed, added, changed)
+}
+
+/// Compare the libraries of two builds
+fn diff_libraries(
+    previous: String,
+    old: &BTreeMap<String, Library>,
+    new: &BTreeMap<String, Library>,
+) -> AbiDiffReport {
+    let mut report = AbiDiffReport {
+        previous,
+        ..Default::default()
+    };
+    for (name, old_lib) in old {
+        let Some(new_lib) = new.get(name) else {
+            report.removed_libraries.push(name.clone());
+            continue;
+        };
+        let (removed, added, changed) = diff_symbols(&old_lib.symbols, &new_lib.symbols);
+        if !removed.is_empty() || !added.is_empty() || !changed.is_empty() {
+            report.libraries.push(LibraryDiff {
+                library: name.clone(),
+                path: new_lib.path.clone(),
+                removed,
+                added,
+                changed,
+            });
+        }
+    }
+    report.added_libraries = new
+        .keys()
+        .filter(|name| !old.contains_key(*name))
+        .cloned()
+        .collect();
+    report
+}
+
+/// The exported symbols of an ELF shared library, keyed by its SONAME
+fn elf_exports(elf: &goblin::elf::Elf, file_name: &str) -> Option<(String, Library)> {
+    if elf.header.e_type != goblin::elf::header::ET_DYN
+        || (elf.soname.is_none() && !file_name.contains(".so"))
+    {
+        return None;
+    }
+
+    let mut symbols = BTreeMap::new();
+    for sym in elf.dynsyms.iter() {
+        // undefined symbols are imports
+        if sym.st_shndx == 0
+            || !matches!(sym.st_bind(), STB_GLOBAL | STB_WEAK | STB_GNU_UNIQUE)
+            || matches!(sym.st_visibility(), STV_HIDDEN | STV_INTERNAL)
+        {
+            continue;
+        }
+        let Some(name) = elf.dynstrtab.get_at(sym.st_name).filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        let kind = match sym.st_type() {
+            STT_FUNC | STT_GNU_IFUNC => SymbolKind::Function,
+            STT_OBJECT => SymbolKind::Object,
+            STT_TLS => SymbolKind::Tls,
+            _ => SymbolKind::Other,
+        };
+        symbols.insert(
+            name.to_string(),
+            Symbol {
+                kind,
+                size: sym.st_size,
+            },
+        );
+    }
+
+    let key = elf.soname.unwrap_or(file_name).to_string();
+    Some((
+        key,
+        Library {
+            symbols,
+            ..Default::default()
+        },
+    ))
+}
+
+/// The exported symbols of a Mach-O dylib, keyed by the file name of its install name
+fn macho_exports(macho: &goblin::mach::MachO, file_name: &str) -> Option<(String, Library)> {
+    if macho.header.filetype != goblin::mach::header::MH_DYLIB {
+        return None;
+    }
+
+    let symbols = macho
+        .exports()
+        .ok()?
+        .into_iter()
+        .map(|export| {
+            (
+                export.name,
+                Symbol {
+                    kind: SymbolKind::Other,
+                    size: 0,
+                },
+            )
+        })
+        .collect();
+
+    let key = macho
+        .name
+        .and_then(|name| Path::new(name).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_name.to_string());
+    Some((
+        key,
+        Library {
+            symbols,
+            ..Default::default()
+        },
+    ))
+}
+
+/// The exported symbols of a DLL, keyed by its name
+fn pe_exports(pe: &goblin::pe::PE, file_name: &str) -> Option<(String, Library)> {
+    if !pe.is_lib {
+        return None;
+    }
+
+    let symbols = pe
+        .exports
+        .iter()
+        .filter_map(|export| export.name)
+        .map(|name| {
+            (
+                name.to_string(),
+                Symbol {
+                    kind: SymbolKind::Other,
+                    size: 0,
+                },
+            )
+        })
+        .collect();
+
+    let key = pe.name.unwrap_or(file_name).to_string();
+    Some((
+        key,
+        Library {
+            symbols,
+            ..Default::default()
+        },
+    ))
+}
+
+/// The exported symbols of a shared library, or `None` if the file is not a shared library
+fn library_exports(contents: &[u8], file_name: &str) -> Option<(String, Library)> {
+    match goblin::Object::parse(contents).ok()? {
+        goblin::Object::Elf(elf) => elf_exports(&elf, file_name),
+        goblin::Object::Mach(goblin::mach::Mach::Binary(macho)) => macho_exports(&macho, file_name),
+        goblin::Object::Mach(goblin::mach::Mach::Fat(fat)) => match fat.get(0).ok()? {
+            goblin::mach::SingleArch::MachO(macho) => macho_exports(&macho, file_name),
+            goblin::mach::SingleArch::Archive(_) => None,
+        },
+        goblin::Object::PE(pe) => pe_exports(&pe, file_name),
+        _ => None,
+    }
+}
+
+/// Collect the exported symbols of all shared libraries in an extracted package
+fn collect_libraries(dir: &Path) -> miette::Result<BTreeMap<String, Library>> {
+    let mut files = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut libraries = BTreeMap::new();
+    for path in files {
+        let rel_path = path.strip_prefix(dir).unwrap_or(&path);
+        if rel_path.starts_with("info") {
+            continue;
+        }
+        let file_name = rel_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let contents = fs::read(&path).into_diagnostic()?;
+        let Some((key, mut library)) = library_exports(&contents, &file_name) else {
+            continue;
+        };
+        if libraries.contains_key(&key) {
+            tracing::warn!(
+                "Found multiple libraries named {}, only comparing the first one",
+                key
+            );
+            continue;
+        }
+        library.path = rel_path.to_path_buf();
+        libraries.insert(key, library);
+    }
+    Ok(libraries)
+}
+
+/// The part of a build string that identifies the variant: the hash (e.g. `h1234abc` of
+/// `py312h1234abc_0`), or the build string without the build number if it has no hash
+fn variant_hash(build: &str) -> &str {
+    let build = match build.rsplit_once('_') {
+        Some((variant, number))
+            if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            variant
+        }
+        _ => build,
+    };
+    match build
+        .len()
+        .checked_sub(8)
+        .map(|start| build.split_at(start))
+    {
+        Some((_, hash))
+            if hash.starts_with('h') && hash[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            hash
+        }
+        _ => build,
+    }
+}
+
+/// Find the latest package in the channel that is older than the new package, built for the same
+/// subdir and variant
+async fn find_previous(
+    args: &AbiDiffOpts,
+    index: &IndexJson,
+    client: reqwest_middleware::ClientWithMiddleware,
+) -> miette::Result<RepoDataRecord> {
+    let channel_config = ChannelConfig::default_with_root_dir(
+        std::env::current_dir().unwrap_or_else(|_err| PathBuf::from("/")),
+    );
+    let channels = args
+        .channel
+        .iter()
+        .map(|channel| channel.clone().into_channel(&channel_config))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+
+    let subdir = index
+        .subdir
+        .as_deref()
+        .map(|subdir| subdir.parse::<Platform>())
+        .transpose()
+        .into_diagnostic()?
+        .unwrap_or(Platform::NoArch);
+
+    let cache_dir = rattler_cache::default_cache_dir()
+        .map_err(|e| miette::miette!("Failed to determine the cache directory: {e}"))?;
+    let gateway = Gateway::builder()
+        .with_cache_dir(cache_dir.join(rattler_cache::REPODATA_CACHE_DIR))
+        .with_client(client)
+        .finish();
+
+    let name: PackageName = index.name.clone();
+    let repodata = gateway
+        .query(channels, [subdir], [MatchSpec::from(name.clone())])
+        .recursive(false)
+        .await
+        .into_diagnostic()
+        .context("Failed to fetch the repodata")?;
+
+    let new_version = index.version.version();
+    let new_variant = variant_hash(&index.build);
+    let previous = repodata
+        .iter()
+        .flat_map(|repo| repo.iter())
+        .filter(|record| {
+            record.package_record.subdir == subdir.as_str()
+                && variant_hash(&record.package_record.build) == new_variant
+        })
+        .filter(|record| {
+            let version = record.package_record.version.version();
+            version < new_version
+                || (version == new_version
+                    && record.package_record.build_number < index.build_number)
+        })
+        .max_by(|a, b| {
+            (
+                a.package_record.version.version(),
+                a.package_record.build_number,
+                a.url.as_str(),
+            )
+                .cmp(&(
+                    b.package_record.version.version(),
+                    b.package_record.build_number,
+                    b.url.as_str(),
+                ))
+        })
+        .cloned()
+        .ok_or_else(|| {
+            miette::miette!(
+                help = "pass the package to compare against with `--previous`",
+                "Could not find a previous version of {} for {} with the same variant ({}) in the channel(s)",
+                name.as_normalized(),
+                subdir,
+                new_variant
+            )
+        })?;
+
+    Ok(previous)
+}
+
+/// Unpack the previous package into `dest` and return its name for the report
+async fn extract_previous(
+    args: &AbiDiffOpts,
+    index: &IndexJson,
+    dest: &Path,
+) -> miette::Result<String> {
+    let client = super::create_authenticated_client()?;
+
+    let url = match &args.previous {
+        Some(PackageSource::Path(path)) => {
+            rattler_package_streaming::fs::extract(path, dest)
+                .into_diagnostic()
+                .with_context(|| format!("Failed to extract package: {}", path.display()))?;
+            return Ok(path.display().to_string());
+        }
+        Some(PackageSource::Url(url)) => url.clone(),
+        None => {
+            let record = find_previous(args, index, client.clone()).await?;
+            tracing::info!("Comparing against {}", record.url);
+            record.url
+        }
+    };
+
+    if let Ok(path) = url.to_file_path() {
+        rattler_package_streaming::fs::extract(&path, dest)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to extract package: {}", path.display()))?;
+    } else {
+        rattler_package_streaming::reqwest::tokio::extract(client, url.clone(), dest, None, None)
+            .await
+            .into_diagnostic()
+            .with_context(|| format!("Failed to extract package from URL: {url}"))?;
+    }
+    Ok(url.to_string())
+}
+
+/// Print the report as tables
+fn print_report(report: &AbiDiffReport) {
+    let new_table = |header: Vec<&str>| {
+        let mut table = comfy_table::Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header(header);
+        table
+    };
+
+    tracing::info!("Compared against {}", report.previous);
+
+    if !report.removed_libraries.is_empty() || !report.added_libraries.is_empty() {
+        let mut table = new_table(vec!["Library", "Status"]);
+        for library in &report.removed_libraries {
+            table.add_row(vec![library.as_str(), "removed"]);
+        }
+        for library in &report.added_libraries {
+            table.add_row(vec![library.as_str(), "added"]);
+        }
+        tracing::info!("\nLibraries:\n{}", table);
+    }
+
+    if !report.libraries.is_empty() {
+        let mut table = new_table(vec!["Library", "Removed", "Added", "Changed"]);
+        for lib in &report.libraries {
+            table.add_row(vec![
+                lib.library.clone(),
+                lib.removed.len().to_string(),
+                lib.added.len().to_string(),
+                lib.changed.len().to_string(),
+            ]);
+        }
+        tracing::info!("\nSymbols:\n{}", table);
+    }
+
+    for lib in &report.libraries {
+        if lib.removed.is_empty() && lib.changed.is_empty() {
+            continue;
+        }
+        let mut table = new_table(vec!["Symbol", "Status", "Details"]);
+        for name in &lib.removed {
+            table.add_row(vec![name.as_str(), "removed", ""]);
+        }
+        for change in &lib.changed {
+            table.add_row(vec![
+                change.name.clone(),
+                "changed".to_string(),
+                format!(
+                    "{:?} ({} bytes) -> {:?} ({} bytes)",
+                    change.old.kind, change.old.size, change.new.kind, change.new.size
+                ),
+            ]);
+        }
+        tracing::info!("\n{} ({}):\n{}", lib.library, lib.path.display(), table);
+    }
+
+    if !report.is_backwards_compatible() {
+        tracing::warn!(
+            "The ABI is not backwards compatible: tighten the `run_exports` pin or start a migration"
+        );
+    } else if report.has_changes() {
+        tracing::info!(
+            "{} Only additions, the ABI is backwards compatible",
+            console::style("✔").green()
+        );
+    } else {
+        tracing::info!("{} No ABI changes", console::style("✔").green());
+    }
+}
+
+/// Compare the exported symbols of a package against a previous build
+pub async fn abi_diff(args: AbiDiffOpts) -> miette::Result<()> {
+    let index: IndexJson = read_package_file(&args.package_file)
+        .into_diagnostic()
+        .with_context(|| {
+            format!(
+                "Failed to read index.json from {}",
+                args.package_file.display()
+            )
+        })?;
+
+    let new_dir = tempfile::tempdir().into_diagnostic()?;
+    rattler_package_streaming::fs::extract(&args.package_file, new_dir.path())
+        .into_diagnostic()
+        .with_context(|| format!("Failed to extract package: {}", args.package_file.display()))?;
+
+    let old_dir = tempfile::tempdir().into_diagnostic()?;
+    let previous = extract_previous(&args, &index, old_dir.path()).await?;
+
+    let old = collect_libraries(old_dir.path())?;
+    let new = collect_libraries(new_dir.path())?;
+    let report = diff_libraries(previous, &old, &new);
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).into_diagnostic()?
+        );
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_hash() {
+        assert_eq!(variant_hash("py312h1234abc_0"), "h1234abc");
+        assert_eq!(variant_hash("h1234abc_12"), "h1234abc");
+        assert_eq!(variant_hash("pyhd8ed1ab_0"), "hd8ed1ab");
+        // custom build strings without a hash
+        assert_eq!(variant_hash("cuda_12"), "cuda");
+        assert_eq!(variant_hash("custom_build"), "custom_build");
+        assert_eq!(variant_hash("hzzzzzzz_0"), "hzzzzzzz");
+    }
+
+    fn symbols(entries: &[(&str, SymbolKind, u64)]) -> BTreeMap<String, Symbol> {
+        entries
+            .iter()
+            .map(|(name, kind, size)| {
+                (
+                    name.to_string(),
+                    Symbol {
+                        kind: *kind,
+                        size: *size,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_libraries() {
+        let old = BTreeMap::from([
+            (
+                "libfoo.so.1".to_string(),
+                Library {
+                    path: PathBuf::from("lib/libfoo.so.1"),
+                    symbols: symbols(&[
+                        ("foo", SymbolKind::Function, 10),
+                        ("bar", SymbolKind::Function, 10),
+                        ("table", SymbolKind::Object, 16),
+                    ]),
+                },
+            ),
+            ("libgone.so".to_string(), Library::default()),
+        ]);
+        let new = BTreeMap::from([(
+            "libfoo.so.1".to_string(),
+            Library {
+                path: PathBuf::from("lib/libfoo.so.1"),
+                symbols: symbols(&[
+                    ("foo", SymbolKind::Function, 20),
+                    ("baz", SymbolKind::Function, 10),
+                    ("table", SymbolKind::Object, 32),
+                ]),
+            },
+        )]);
+
+        let report = diff_libraries("old".to_string(), &old, &new);
+        assert_eq!(report.removed_libraries, vec!["libgone.so".to_string()]);
+        assert!(report.added_libraries.is_empty());
+        assert_eq!(
+            report.libraries,
+            vec![LibraryDiff {
+                library: "libfoo.so.1".to_string(),
+                path: PathBuf::from("lib/libfoo.so.1"),
+                removed: vec!["bar".to_string()],
+                added: vec!["baz".to_string()],
+                changed: vec![SymbolChange {
+                    name: "table".to_string(),
+                    old: Symbol {
+                        kind: SymbolKind::Object,
+                        size: 16
+                    },
+                    new: Symbol {
+                        kind: SymbolKind::Object,
+                        size: 32
+                    },
+                }],
+            }]
+        );
+        assert!(!report.is_backwards_compatible());
+
+        // only additions are backwards compatible
+        let report = diff_libraries("old".to_string(), &new, &new);
+        assert!(!report.has_changes());
+        let mut newer = new.clone();
+        newer.insert("libbar.so".to_string(), Library::default());
+        let report = diff_libraries("old".to_string(), &new, &newer);
+        assert!(report.has_changes());
+        assert!(report.is_backwards_compatible());
+    }
+
+    #[test]
+    fn test_elf_exports() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/binary_files/simple.so");
+        let contents = fs::read(path).unwrap();
+        let (_, library) = library_exports(&contents, "simple.so").unwrap();
+        assert_eq!(library.symbols["add"].kind, SymbolKind::Function);
+        assert_eq!(library.symbols["multiply"].kind, SymbolKind::Function);
+        assert!(library.symbols.keys().all(|name| !name.is_empty()));
+    }
+}
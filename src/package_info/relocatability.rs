@@ -0,0 +1,337 @@
+//! Audit a built package for everything that depends on where it was built or is installed.
+//!
+//! The package is unpacked into a temporary directory and every file is checked for prefix
+//! placeholders (from `info/paths.json`), rpath entries, shebangs and paths that look specific
+//! to the machine the package was built on (home directories, build directories, ...).
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use memchr::memmem;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::package::{FileMode, PathsJson};
+use serde::Serialize;
+
+use crate::{linux::link::SharedObject, macos::link::Dylib, post_process::relink::Relinker};
+
+/// Substrings that point to a location on the build machine
+const MACHINE_SPECIFIC_PATTERNS: &[(&str, &str)] = &[
+    ("/home/", "home directory"),
+    ("/Users/", "home directory"),
+    ("C:\\Users\\", "home directory"),
+    ("/private/var/folders/", "temporary directory"),
+    ("conda-bld", "conda-build directory"),
+    ("rattler-build_", "rattler-build directory"),
+];
+
+/// A file that contains the prefix placeholder and is rewritten at installation time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PrefixPlaceholderEntry {
+    /// The path of the file in the package
+    pub path: PathBuf,
+    /// Whether the prefix is replaced in text or binary mode
+    pub file_mode: FileMode,
+    /// The placeholder that is replaced
+    pub placeholder: String,
+}
+
+/// An rpath entry of a binary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RpathEntry {
+    /// The path of the binary in the package
+    pub path: PathBuf,
+    /// The kind of entry (`RPATH`, `RUNPATH` or `LC_RPATH`)
+    pub kind: &'static str,
+    /// The value of the entry
+    pub value: String,
+}
+
+/// The shebang of a script
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShebangEntry {
+    /// The path of the script in the package
+    pub path: PathBuf,
+    /// The shebang line
+    pub shebang: String,
+}
+
+/// A file that contains something that looks specific to the build machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MachineSpecificEntry {
+    /// The path of the file in the package
+    pub path: PathBuf,
+    /// The substring that was found
+    pub pattern: &'static str,
+    /// What the substring usually indicates
+    pub description: &'static str,
+    /// The offset of the first occurrence
+    pub offset: usize,
+    /// The number of occurrences
+    pub count: usize,
+}
+
+/// Everything in a package that depends on the location it was built in or is installed to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RelocatabilityReport {
+    /// Files that contain the prefix placeholder
+    pub prefix_placeholders: Vec<PrefixPlaceholderEntry>,
+    /// rpath entries of all binaries
+    pub rpaths: Vec<RpathEntry>,
+    /// Shebangs of all scripts
+    pub shebangs: Vec<ShebangEntry>,
+    /// Paths that look specific to the build machine
+    pub machine_specific: Vec<MachineSpecificEntry>,
+}
+
+/// Find the machine specific patterns in `contents`, ignoring matches inside of the prefix
+/// placeholder (which is replaced at installation time)
+fn find_machine_specific(
+    path: &Path,
+    contents: &[u8],
+    placeholder: Option<&str>,
+) -> Vec<MachineSpecificEntry> {
+    let placeholder_ranges = placeholder
+        .map(|placeholder| {
+            memmem::find_iter(contents, placeholder.as_bytes())
+                .map(|start| start..start + placeholder.len())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut result = Vec::new();
+    for (pattern, description) in MACHINE_SPECIFIC_PATTERNS {
+        let mut offsets = memmem::find_iter(contents, pattern.as_bytes())
+            .filter(|offset| !placeholder_ranges.iter().any(|r| r.contains(offset)));
+        if let Some(offset) = offsets.next() {
+            result.push(MachineSpecificEntry {
+                path: path.to_path_buf(),
+                pattern,
+                description,
+                offset,
+                count: 1 + offsets.count(),
+            });
+        }
+    }
+    result
+}
+
+/// The shebang line of a script, if the file starts with `#!`
+fn shebang(contents: &[u8]) -> Option<String> {
+    if !contents.starts_with(b"#!") {
+        return None;
+    }
+    let end = memchr::memchr(b'\n', contents).unwrap_or(contents.len());
+    Some(
+        String::from_utf8_lossy(&contents[..end])
+            .trim_end()
+            .to_string(),
+    )
+}
+
+/// The rpath entries of an ELF or Mach-O binary
+fn rpaths(path: &Path, rel_path: &Path) -> Vec<RpathEntry> {
+    let entry = |kind, value: String| RpathEntry {
+        path: rel_path.to_path_buf(),
+        kind,
+        value,
+    };
+
+    if SharedObject::test_file(path).unwrap_or(false) {
+        match SharedObject::new(path) {
+            Ok(object) => {
+                return object
+                    .rpaths()
+                    .iter()
+                    .map(|r| entry("RPATH", r.clone()))
+                    .chain(
+                        object
+                            .runpaths()
+                            .iter()
+                            .map(|r| entry("RUNPATH", r.clone())),
+                    )
+                    .collect();
+            }
+            Err(e) => tracing::debug!("Could not parse {}: {}", rel_path.display(), e),
+        }
+    } else if Dylib::test_file(path).unwrap_or(false) {
+        match Dylib::new(path) {
+            Ok(dylib) => {
+                return dylib
+                    .rpaths()
+                    .iter()
+                    .map(|r| entry("LC_RPATH", r.to_string_lossy().to_string()))
+                    .collect();
+            }
+            Err(e) => tracing::debug!("Could not parse {}: {}", rel_path.display(), e),
+        }
+    }
+    Vec::new()
+}
+
+/// Unpack the package and collect the relocatability report
+pub fn relocatability_report(
+    package_path: &Path,
+    paths_json: Option<&PathsJson>,
+) -> miette::Result<RelocatabilityReport> {
+    let tmp_dir = tempfile::tempdir().into_diagnostic()?;
+    rattler_package_streaming::fs::extract(package_path, tmp_dir.path())
+        .into_diagnostic()
+        .with_context(|| format!("Failed to extract package: {}", package_path.display()))?;
+
+    let mut report = RelocatabilityReport::default();
+    let mut placeholders = std::collections::HashMap::new();
+    if let Some(paths_json) = paths_json {
+        for entry in &paths_json.paths {
+            if let Some(prefix_placeholder) = &entry.prefix_placeholder {
+                report.prefix_placeholders.push(PrefixPlaceholderEntry {
+                    path: entry.relative_path.clone(),
+                    file_mode: prefix_placeholder.file_mode,
+                    placeholder: prefix_placeholder.placeholder.clone(),
+                });
+                placeholders.insert(
+                    entry.relative_path.clone(),
+                    prefix_placeholder.placeholder.clone(),
+                );
+            }
+        }
+    }
+
+    let mut files = walkdir::WalkDir::new(tmp_dir.path())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    for path in files {
+        let rel_path = path.strip_prefix(tmp_dir.path()).unwrap_or(&path);
+        // the metadata (e.g. the rendered recipe) always contains paths of the build machine
+        if rel_path.starts_with("info") {
+            continue;
+        }
+
+        let contents = fs::read(&path).into_diagnostic()?;
+        if let Some(shebang) = shebang(&contents) {
+            report.shebangs.push(ShebangEntry {
+                path: rel_path.to_path_buf(),
+                shebang,
+            });
+        }
+        report.rpaths.extend(rpaths(&path, rel_path));
+        report.machine_specific.extend(find_machine_specific(
+            rel_path,
+            &contents,
+            placeholders.get(rel_path).map(String::as_str),
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Print the report as tables
+pub fn print_report(report: &RelocatabilityReport) {
+    let new_table = |header: Vec<&str>| {
+        let mut table = comfy_table::Table::new();
+        table
+            .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header(header);
+        table
+    };
+
+    tracing::info!(
+        "\nFiles with prefix placeholders ({}):",
+        report.prefix_placeholders.len()
+    );
+    if !report.prefix_placeholders.is_empty() {
+        let mut table = new_table(vec!["Path", "Mode"]);
+        for entry in &report.prefix_placeholders {
+            let mode = match entry.file_mode {
+                FileMode::Binary => "binary",
+                FileMode::Text => "text",
+            };
+            table.add_row(vec![&*entry.path.to_string_lossy(), mode]);
+        }
+        tracing::info!("{}", table);
+    }
+
+    tracing::info!("\nRpath entries ({}):", report.rpaths.len());
+    if !report.rpaths.is_empty() {
+        let mut table = new_table(vec!["Path", "Type", "Entry"]);
+        for entry in &report.rpaths {
+            table.add_row(vec![
+                &*entry.path.to_string_lossy(),
+                entry.kind,
+                &entry.value,
+            ]);
+        }
+        tracing::info!("{}", table);
+    }
+
+    tracing::info!("\nShebangs ({}):", report.shebangs.len());
+    if !report.shebangs.is_empty() {
+        let mut table = new_table(vec!["Path", "Shebang"]);
+        for entry in &report.shebangs {
+            table.add_row(vec![&*entry.path.to_string_lossy(), &entry.shebang]);
+        }
+        tracing::info!("{}", table);
+    }
+
+    tracing::info!(
+        "\nMachine specific paths ({}):",
+        report.machine_specific.len()
+    );
+    if !report.machine_specific.is_empty() {
+        let mut table = new_table(vec!["Path", "Found", "Offset", "Count"]);
+        for entry in &report.machine_specific {
+            table.add_row(vec![
+                entry.path.to_string_lossy().to_string(),
+                format!("{} ({})", entry.pattern, entry.description),
+                format!("{:#x}", entry.offset),
+                entry.count.to_string(),
+            ]);
+        }
+        tracing::info!("{}", table);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shebang() {
+        assert_eq!(
+            shebang(b"#!/opt/env/bin/python\nimport sys\n"),
+            Some("#!/opt/env/bin/python".to_string())
+        );
+        assert_eq!(shebang(b"#!/bin/sh\r\n"), Some("#!/bin/sh".to_string()));
+        assert_eq!(shebang(b"import sys\n"), None);
+    }
+
+    #[test]
+    fn test_find_machine_specific() {
+        let placeholder = "/home/user/bld/rattler-build_foo/host_env_placehold";
+        let contents = format!("prefix={placeholder}\nsrc=/home/user/src/foo.c\n");
+        let found = find_machine_specific(
+            Path::new("lib/pkgconfig/foo.pc"),
+            contents.as_bytes(),
+            Some(placeholder),
+        );
+        assert_eq!(
+            found,
+            vec![MachineSpecificEntry {
+                path: PathBuf::from("lib/pkgconfig/foo.pc"),
+                pattern: "/home/",
+                description: "home directory",
+                offset: contents.find("/home/user/src").unwrap(),
+                count: 1,
+            }]
+        );
+
+        // without the placeholder, the prefix is reported as well
+        let found = find_machine_specific(Path::new("foo.pc"), contents.as_bytes(), None);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].count, 2);
+    }
+}
@@ -0,0 +1,338 @@
+//! Run the tests of a package inside of a container (`--test-in-container`).
+//!
+//! Only the package, the local channels, the credentials of the remote channels and the
+//! `rattler-build` executable are mounted into the container, and the tests are run with
+//! `rattler-build test`. Undeclared dependencies on tools or libraries of the host system make
+//! the tests fail, instead of silently passing on the machine of the developer.
+//!
+//! The mounted executable still needs its C library: a `rattler-build` that is linked against
+//! glibc does not start in an image without glibc (e.g. Alpine). Statically linked (musl)
+//! executables run in any image.
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use fs_err as fs;
+use rattler_networking::authentication_storage::{StorageBackend, backends::file::FileStorage};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+use super::{TestConfiguration, TestError, TestSuiteReport, report::read_json_report};
+
+/// The directory in the container that everything is mounted to
+const CONTAINER_ROOT: &str = "/rattler-build";
+
+/// The container engines that are supported, in order of preference
+const CONTAINER_ENGINES: &[&str] = &["docker", "podman"];
+
+/// Log the lines of the output of the container
+async fn log_lines(reader: impl AsyncBufRead + Unpin) {
+    let mut lines = reader.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        tracing::info!("{}", line);
+    }
+}
+
+/// Write the credentials of the remote channels to `credentials.json` in `dir`, so that they can
+/// be mounted into the container. Returns `None` if none of the channels needs credentials.
+fn write_credentials(config: &TestConfiguration, dir: &Path) -> Result<Option<PathBuf>, TestError> {
+    let storage =
+        crate::tool_configuration::get_auth_store(config.tool_configuration.auth_file.clone())
+            .map_err(|e| TestError::TestFailed(format!("failed to read the credentials: {e}")))?;
+
+    let path = dir.join("credentials.json");
+    let file_storage = FileStorage::from_path(path.clone())
+        .map_err(|e| TestError::TestFailed(format!("failed to write the credentials: {e}")))?;
+    let mut found = false;
+    for channel in &config.channels {
+        if channel.url().scheme() == "file" {
+            continue;
+        }
+        if let Ok((_, Some((host, authentication)))) =
+            storage.get_by_url_with_host(channel.url().as_str())
+        {
+            file_storage.store(&host, &authentication).map_err(|e| {
+                TestError::TestFailed(format!("failed to write the credentials: {e}"))
+            })?;
+            found = true;
+        }
+    }
+
+    Ok(found.then_some(path))
+}
+
+/// Returns the ELF interpreter (dynamic loader) of `executable`, if it is dynamically linked
+fn elf_interpreter(executable: &Path) -> Option<String> {
+    let bytes = fs::read(executable).ok()?;
+    let elf = goblin::elf::Elf::parse(&bytes).ok()?;
+    elf.interpreter.map(str::to_string)
+}
+
+/// Build the arguments of `<engine> run` for testing `package_file` in `image`
+fn container_arguments(
+    image: &str,
+    package_file: &Path,
+    executable: &Path,
+    report_dir: &Path,
+    credentials: Option<&Path>,
+    config: &TestConfiguration,
+) -> Result<Vec<String>, TestError> {
+    let package_dir = package_file
+        .parent()
+        .ok_or(TestError::MissingPackageFileName)?;
+    let package_name = package_file
+        .file_name()
+        .ok_or(TestError::MissingPackageFileName)?
+        .to_string_lossy();
+    let volume = |source: &Path, target: String, read_only: bool| {
+        let mode = if read_only { ":ro" } else { "" };
+        [
+            "--volume".to_string(),
+            format!("{}:{target}{mode}", source.display()),
+        ]
+    };
+
+    let mut args = vec!["run".to_string(), "--rm".to_string()];
+    args.extend(volume(
+        package_dir,
+        format!("{CONTAINER_ROOT}/package"),
+        true,
+    ));
+    args.extend(volume(
+        executable,
+        format!("{CONTAINER_ROOT}/bin/rattler-build"),
+        true,
+    ));
+    args.extend(volume(
+        report_dir,
+        format!("{CONTAINER_ROOT}/report"),
+        false,
+    ));
+
+    if let Some(credentials) = credentials {
+        let target = format!("{CONTAINER_ROOT}/credentials.json");
+        args.extend(volume(credentials, target.clone(), true));
+        args.extend(["--env".to_string(), format!("RATTLER_AUTH_FILE={target}")]);
+    }
+
+    // local channels (e.g. the output directory) are mounted, remote channels are used as-is
+    let mut channels = Vec::new();
+    for (index, channel) in config.channels.iter().enumerate() {
+        match channel.url().to_file_path() {
+            Ok(path) => {
+                let target = format!("{CONTAINER_ROOT}/channels/{index}");
+                args.extend(volume(&path, target.clone(), true));
+                channels.push(target);
+            }
+            Err(()) => channels.push(channel.url().to_string()),
+        }
+    }
+
+    args.push(image.to_string());
+    args.extend([
+        format!("{CONTAINER_ROOT}/bin/rattler-build"),
+        "test".to_string(),
+        "--log-style".to_string(),
+        "simple".to_string(),
+        "--color".to_string(),
+        if console::colors_enabled() {
+            "always"
+        } else {
+            "never"
+        }
+        .to_string(),
+        "--package-file".to_string(),
+        format!("{CONTAINER_ROOT}/package/{package_name}"),
+        "--test-report-json".to_string(),
+        format!("{CONTAINER_ROOT}/report/report.json"),
+    ]);
+    for channel in channels {
+        args.extend(["--channel".to_string(), channel]);
+    }
+    if let Some(test_index) = config.test_index {
+        args.extend(["--test-index".to_string(), test_index.to_string()]);
+    }
+    for version in &config.tool_configuration.test_python_versions {
+        args.extend(["--test-python-version".to_string(), version.clone()]);
+    }
+    for spec in &config.tool_configuration.test_extra_specs {
+        args.extend(["--test-extra-spec".to_string(), spec.to_string()]);
+    }
+    if let Some(exclude_newer) = config.exclude_newer {
+        args.extend([
+            "--exclude-newer".to_string(),
+            exclude_newer.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        ]);
+    }
+
+    Ok(args)
+}
+
+/// Run the tests of `package_file` with `rattler-build test` inside of a container of `image`.
+/// The results of the individual tests are recorded in `suite`.
+pub(crate) async fn run_test_in_container(
+    image: &str,
+    package_file: &Path,
+    config: &TestConfiguration,
+    suite: &mut TestSuiteReport,
+) -> Result<(), TestError> {
+    if !cfg!(target_os = "linux") {
+        return Err(TestError::TestFailed(
+            "running tests in a container is only supported on Linux".to_string(),
+        ));
+    }
+
+    let engine = CONTAINER_ENGINES
+        .iter()
+        .find_map(|engine| which::which(engine).ok())
+        .ok_or_else(|| {
+            TestError::TestFailed(format!(
+                "no container engine found, install one of: {}",
+                CONTAINER_ENGINES.join(", ")
+            ))
+        })?;
+
+    let package_file = fs::canonicalize(package_file)?;
+    let executable = std::env::current_exe()?;
+    let report_dir = tempfile::tempdir()?;
+    let credentials_dir = tempfile::tempdir()?;
+    let credentials = write_credentials(config, credentials_dir.path())?;
+    let args = container_arguments(
+        image,
+        &package_file,
+        &executable,
+        report_dir.path(),
+        credentials.as_deref(),
+        config,
+    )?;
+
+    tracing::info!("Running the tests in a container of {}", image);
+    tracing::debug!("{} {}", engine.display(), args.join(" "));
+    let mut child = tokio::process::Command::new(&engine)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+    tokio::join!(log_lines(stdout), log_lines(stderr));
+    let status = child.wait().await?;
+
+    // take over the results of the individual tests from the report of the container
+    let report = report_dir.path().join("report.json");
+    if report.exists() {
+        for inner in read_json_report(&report)? {
+            suite.tests.extend(inner.tests);
+        }
+    }
+
+    if !status.success() {
+        // without a report, `rattler-build` most likely did not start in the container
+        let hint = match elf_interpreter(&executable) {
+            Some(interpreter) if !report.exists() => format!(
+                ". The rattler-build executable is dynamically linked and needs {interpreter} \
+                 in the image; use an image with the same C library (e.g. glibc) or a \
+                 statically linked (musl) rattler-build"
+            ),
+            _ => String::new(),
+        };
+        return Err(TestError::TestFailed(format!(
+            "the tests in the container of {image} failed ({status}){hint}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_configuration(dir: &Path) -> TestConfiguration {
+        TestConfiguration {
+            test_prefix: dir.to_path_buf(),
+            target_platform: None,
+            host_platform: None,
+            current_platform: crate::metadata::PlatformWithVirtualPackages {
+                platform: rattler_conda_types::Platform::Linux64,
+                virtual_packages: Vec::new(),
+            },
+            keep_test_prefix: false,
+            test_index: Some(1),
+            channels: vec![
+                rattler_conda_types::Channel::from_directory(Path::new("/home/user/output"))
+                    .base_url,
+                rattler_conda_types::Channel::from_url(
+                    "https://conda.anaconda.org/conda-forge"
+                        .parse::<url::Url>()
+                        .unwrap(),
+                )
+                .base_url,
+            ],
+            channel_priority: Default::default(),
+            solve_strategy: Default::default(),
+            tool_configuration: crate::tool_configuration::Configuration::builder().finish(),
+            output_dir: dir.to_path_buf(),
+            debug: Default::default(),
+            exclude_newer: None,
+        }
+    }
+
+    #[test]
+    fn test_container_arguments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut config = test_configuration(tmp.path());
+        config.exclude_newer = Some("2024-03-15T12:00:00Z".parse().unwrap());
+        config.tool_configuration.test_extra_specs = vec![
+            rattler_conda_types::MatchSpec::from_str(
+                "pytest-xdist",
+                rattler_conda_types::ParseStrictness::Strict,
+            )
+            .unwrap(),
+        ];
+
+        let args = container_arguments(
+            "ubuntu:24.04",
+            Path::new("/home/user/output/linux-64/foo-1.0-h123_0.conda"),
+            Path::new("/usr/bin/rattler-build"),
+            Path::new("/tmp/report"),
+            Some(Path::new("/tmp/credentials/credentials.json")),
+            &config,
+        )
+        .unwrap();
+        let args = args.join(" ").replace(" --color always", " --color never");
+        insta::assert_snapshot!(args, @"run --rm --volume /home/user/output/linux-64:/rattler-build/package:ro --volume /usr/bin/rattler-build:/rattler-build/bin/rattler-build:ro --volume /tmp/report:/rattler-build/report --volume /tmp/credentials/credentials.json:/rattler-build/credentials.json:ro --env RATTLER_AUTH_FILE=/rattler-build/credentials.json --volume /home/user/output/:/rattler-build/channels/0:ro ubuntu:24.04 /rattler-build/bin/rattler-build test --log-style simple --color never --package-file /rattler-build/package/foo-1.0-h123_0.conda --test-report-json /rattler-build/report/report.json --channel /rattler-build/channels/0 --channel https://conda.anaconda.org/conda-forge/ --test-index 1 --test-extra-spec pytest-xdist --exclude-newer 2024-03-15T12:00:00Z");
+    }
+
+    #[test]
+    fn test_write_credentials() {
+        let tmp = tempfile::tempdir().unwrap();
+        let auth_file = tmp.path().join("auth.json");
+        fs::write(
+            &auth_file,
+            r#"{"conda.anaconda.org": {"BearerToken": "secret"}, "example.com": {"BearerToken": "other"}}"#,
+        )
+        .unwrap();
+        let mut config = test_configuration(tmp.path());
+        config.tool_configuration.auth_file = Some(auth_file);
+
+        let credentials = write_credentials(&config, tmp.path()).unwrap().unwrap();
+        // only the credentials of the remote channels are passed into the container
+        let content: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(credentials).unwrap()).unwrap();
+        assert_eq!(
+            content,
+            serde_json::json!({"conda.anaconda.org": {"BearerToken": "secret"}})
+        );
+
+        config
+            .channels
+            .retain(|channel| channel.url().scheme() == "file");
+        assert!(
+            write_credentials(&config, &tmp.path().join("none"))
+                .unwrap()
+                .is_none()
+        );
+    }
+}
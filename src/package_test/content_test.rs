@@ -5,7 +5,7 @@ use rattler_build_recipe::stage1::tests::PackageContentsTest;
 use rattler_build_types::GlobWithSource;
 use rattler_conda_types::{Platform, package::PathsJson};
 
-use crate::{package_test::TestError, types::Output};
+use crate::package_test::TestError;
 
 fn build_glob(glob: String) -> Result<Glob, globset::Error> {
     tracing::debug!("Building glob: {}", glob);
@@ -110,7 +110,12 @@ pub trait PackageContentsTestExt {
     ) -> Result<Vec<(String, GlobSet)>, globset::Error>;
 
     /// Run the package content test
-    fn run_test(&self, paths: &PathsJson, output: &Output) -> Result<(), TestError>;
+    fn run_test(
+        &self,
+        paths: &PathsJson,
+        target_platform: &Platform,
+        version_independent: bool,
+    ) -> Result<(), TestError>;
 }
 
 impl PackageContentsTestExt for PackageContentsTest {
@@ -406,16 +411,20 @@ impl PackageContentsTestExt for PackageContentsTest {
         self.get_globs_for_section(Section::Files, false, target_platform, false)
     }
 
-    /// Run the package content test
-    fn run_test(&self, paths: &PathsJson, output: &Output) -> Result<(), TestError> {
+    /// Run the package content test against the `paths.json` of the package. Python files of
+    /// version independent packages are expected in `site-packages` at the root of the package.
+    fn run_test(
+        &self,
+        paths: &PathsJson,
+        target_platform: &Platform,
+        version_independent: bool,
+    ) -> Result<(), TestError> {
         let span = tracing::info_span!("Package content test");
         let _enter = span.enter();
-        let target_platform = output.target_platform();
         let paths: Vec<&PathBuf> = paths.paths.iter().map(|p| &p.relative_path).collect();
 
         let mut collected_issues = Vec::new();
         let mut matched_paths = HashSet::<&PathBuf>::new();
-        let version_independent = output.is_python_version_independent();
 
         // Check all sections for both exists and not_exists
         let sections = [
@@ -522,7 +531,7 @@ mod tests {
     use globset::GlobSet;
     use rattler_build_recipe::stage1::tests::{PackageContentsCheckFiles, PackageContentsTest};
     use rattler_build_types::GlobVec;
-    use rattler_conda_types::Platform;
+    use rattler_conda_types::{Platform, package::PathsJson};
     use serde::Deserialize;
 
     #[derive(Debug)]
@@ -755,4 +764,50 @@ mod tests {
         };
         assert!(!non_strict_contents.strict);
     }
+
+    #[test]
+    fn test_run_test_against_paths_json() {
+        let paths = [
+            "bin/foo",
+            "lib/libfoo.so.1.2",
+            "site-packages/foo/__init__.py",
+            "site-packages/foo/sub/module.py",
+        ];
+        let paths_json: PathsJson = serde_json::from_value(serde_json::json!({
+            "paths_version": 1,
+            "paths": paths
+                .iter()
+                .map(|p| serde_json::json!({ "_path": p, "path_type": "hardlink" }))
+                .collect::<Vec<_>>(),
+        }))
+        .unwrap();
+
+        let contents = PackageContentsTest {
+            files: make_check_files(
+                vec!["site-packages/foo/**/*.py", "lib/libfoo.so.*"],
+                Some(vec!["site-packages/foo/tests/**"]),
+            ),
+            bin: make_check_files(vec!["foo"], None),
+            site_packages: make_check_files(vec!["foo.sub.module"], None),
+            ..Default::default()
+        };
+        contents
+            .run_test(&paths_json, &Platform::Linux64, true)
+            .unwrap();
+        // python files are expected in `lib/python*/site-packages` for version dependent packages
+        assert!(
+            contents
+                .run_test(&paths_json, &Platform::Linux64, false)
+                .is_err()
+        );
+
+        let contents = PackageContentsTest {
+            files: make_check_files(vec!["bin/*"], Some(vec!["lib/*.so.*"])),
+            ..Default::default()
+        };
+        let err = contents
+            .run_test(&paths_json, &Platform::Linux64, true)
+            .unwrap_err();
+        assert!(err.to_string().contains("lib/*.so.*"), "{err}");
+    }
 }
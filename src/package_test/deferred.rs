@@ -0,0 +1,103 @@
+//! Tests that could not run on the build machine (`deferred_tests.json`).
+//!
+//! The tests of a cross-compiled package that cannot be executed on the build machine are
+//! recorded as skipped, and the package is added to `deferred_tests.json` in the output
+//! directory. `rattler-build test --deferred` runs these tests later, e.g. on native hardware.
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+/// The name of the file with the deferred tests in the output directory
+pub const DEFERRED_TESTS_FILE: &str = "deferred_tests.json";
+
+/// A package whose tests have not been run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeferredTest {
+    /// The package file, relative to the output directory (e.g. `linux-aarch64/foo-1.0-h0_0.conda`)
+    pub package: PathBuf,
+    /// Why the tests were not run
+    pub reason: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DeferredTests {
+    packages: Vec<DeferredTest>,
+}
+
+/// Read the deferred tests of the output directory
+pub fn read_deferred_tests(output_dir: &Path) -> io::Result<Vec<DeferredTest>> {
+    let path = output_dir.join(DEFERRED_TESTS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let tests: DeferredTests = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(tests.packages)
+}
+
+/// Write the deferred tests of the output directory. The file is removed if there are none.
+pub fn write_deferred_tests(output_dir: &Path, packages: Vec<DeferredTest>) -> io::Result<()> {
+    let path = output_dir.join(DEFERRED_TESTS_FILE);
+    if packages.is_empty() {
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    let json = serde_json::to_string_pretty(&DeferredTests { packages })?;
+    fs::write(path, json)
+}
+
+/// Add the tests of `package_file` to the deferred tests of the output directory (replacing an
+/// earlier entry for the same package)
+pub fn defer_tests(output_dir: &Path, package_file: &Path, reason: &str) -> io::Result<()> {
+    let package = package_file
+        .strip_prefix(output_dir)
+        .unwrap_or(package_file)
+        .to_path_buf();
+    let mut packages = read_deferred_tests(output_dir)?;
+    packages.retain(|test| test.package != package);
+    packages.push(DeferredTest {
+        package,
+        reason: reason.to_string(),
+    });
+    write_deferred_tests(output_dir, packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defer_tests() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_dir = output_dir.path();
+        let package = |name: &str| output_dir.join("linux-aarch64").join(name);
+
+        defer_tests(output_dir, &package("a-1.0-h0_0.conda"), "cross-compiled").unwrap();
+        defer_tests(output_dir, &package("b-1.0-h0_0.conda"), "cross-compiled").unwrap();
+        defer_tests(output_dir, &package("a-1.0-h0_0.conda"), "again").unwrap();
+
+        let tests = read_deferred_tests(output_dir).unwrap();
+        assert_eq!(
+            tests,
+            vec![
+                DeferredTest {
+                    package: PathBuf::from("linux-aarch64/b-1.0-h0_0.conda"),
+                    reason: "cross-compiled".to_string(),
+                },
+                DeferredTest {
+                    package: PathBuf::from("linux-aarch64/a-1.0-h0_0.conda"),
+                    reason: "again".to_string(),
+                },
+            ]
+        );
+
+        write_deferred_tests(output_dir, Vec::new()).unwrap();
+        assert!(!output_dir.join(DEFERRED_TESTS_FILE).exists());
+        assert!(read_deferred_tests(output_dir).unwrap().is_empty());
+    }
+}
@@ -1,8 +1,17 @@
 //! Tests that are run as part of the package build process.
+mod container;
 mod content_test;
+mod deferred;
+mod report;
 mod run_test;
 mod serialize_test;
 
 pub use content_test::PackageContentsTestExt;
-pub use run_test::{TestConfiguration, TestError, run_test};
+pub use deferred::{
+    DEFERRED_TESTS_FILE, DeferredTest, defer_tests, read_deferred_tests, write_deferred_tests,
+};
+pub use report::{TestCaseReport, TestReport, TestStatus, TestSuiteReport};
+pub(crate) use run_test::test_name;
+pub use run_test::{TestConfiguration, TestError, download_package, run_test};
+pub(crate) use serialize_test::resolve_dependency;
 pub(crate) use serialize_test::write_test_files;
@@ -0,0 +1,330 @@
+//! Machine readable reports of the test results (`--test-report-junit` and `--test-report-json`).
+//!
+//! Every tested package is a test suite, and every test element of the package is a test case.
+//! The report files are rewritten after each tested package, so that they are complete up to the
+//! point where a failing test stops the build.
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use fs_err as fs;
+use rattler_conda_types::package::CondaArchiveIdentifier;
+use serde::{Deserialize, Serialize};
+
+/// The outcome of a single test
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    /// The test passed
+    Passed,
+    /// The test failed
+    Failed,
+    /// The test was not run
+    Skipped,
+    /// The tests could not be run (e.g. the package could not be extracted)
+    Error,
+}
+
+/// The result of a single test element of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestCaseReport {
+    /// The name of the test, e.g. `python #1`
+    pub name: String,
+    /// The outcome of the test
+    pub status: TestStatus,
+    /// The duration of the test in seconds
+    pub duration: f64,
+    /// The failure message or the reason why the test was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// The results of all tests of a package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestSuiteReport {
+    /// The package that was tested (`name-version-build`)
+    pub package: String,
+    /// The duration of all tests of the package in seconds
+    pub duration: f64,
+    /// The results of the test elements
+    pub tests: Vec<TestCaseReport>,
+}
+
+impl TestSuiteReport {
+    /// Create an empty test suite for the given package file
+    pub fn new(package_file: &Path) -> Self {
+        let package = CondaArchiveIdentifier::try_from_path(package_file)
+            .map(|pkg| pkg.identifier.to_string())
+            .unwrap_or_else(|| {
+                package_file
+                    .file_name()
+                    .unwrap_or(package_file.as_os_str())
+                    .to_string_lossy()
+                    .to_string()
+            });
+        Self {
+            package,
+            duration: 0.0,
+            tests: Vec::new(),
+        }
+    }
+
+    /// Record the result of a test element
+    pub fn record<E: std::fmt::Display>(
+        &mut self,
+        name: impl Into<String>,
+        duration: Duration,
+        result: Result<(), &E>,
+    ) {
+        let (status, message) = match result {
+            Ok(()) => (TestStatus::Passed, None),
+            Err(e) => (TestStatus::Failed, Some(e.to_string())),
+        };
+        self.tests.push(TestCaseReport {
+            name: name.into(),
+            status,
+            duration: duration.as_secs_f64(),
+            message,
+        });
+    }
+
+    /// Record a test element that was not run
+    pub fn skip(&mut self, name: impl Into<String>, reason: impl Into<String>) {
+        self.tests.push(TestCaseReport {
+            name: name.into(),
+            status: TestStatus::Skipped,
+            duration: 0.0,
+            message: Some(reason.into()),
+        });
+    }
+
+    /// Finish the test suite. An error that does not belong to any test element is recorded
+    /// as a separate `setup` test case.
+    pub fn finish<E: std::fmt::Display>(&mut self, duration: Duration, result: Result<(), &E>) {
+        self.duration = duration.as_secs_f64();
+        if let Err(e) = result
+            && !self.tests.iter().any(|t| t.status == TestStatus::Failed)
+        {
+            self.tests.push(TestCaseReport {
+                name: "setup".to_string(),
+                status: TestStatus::Error,
+                duration: 0.0,
+                message: Some(e.to_string()),
+            });
+        }
+    }
+
+    fn count(&self, status: TestStatus) -> usize {
+        self.tests.iter().filter(|t| t.status == status).count()
+    }
+}
+
+#[derive(Default)]
+struct TestReportState {
+    junit: Option<PathBuf>,
+    json: Option<PathBuf>,
+    suites: Vec<TestSuiteReport>,
+}
+
+/// Collects the test results of all tested packages and writes them to the report files
+#[derive(Clone, Default)]
+pub struct TestReport {
+    state: Arc<Mutex<TestReportState>>,
+}
+
+impl TestReport {
+    /// Create a test report that is written to the given files. Returns `None` if no report
+    /// file is requested.
+    pub fn from_paths(junit: Option<PathBuf>, json: Option<PathBuf>) -> Option<Self> {
+        if junit.is_none() && json.is_none() {
+            return None;
+        }
+        Some(Self {
+            state: Arc::new(Mutex::new(TestReportState {
+                junit,
+                json,
+                suites: Vec::new(),
+            })),
+        })
+    }
+
+    /// The results of the packages that were tested so far
+    pub fn suites(&self) -> Vec<TestSuiteReport> {
+        self.state.lock().unwrap().suites.clone()
+    }
+
+    /// Add the results of a tested package and rewrite the report files
+    pub fn add_suite(&self, suite: TestSuiteReport) {
+        let mut state = self.state.lock().unwrap();
+        state.suites.push(suite);
+
+        if let Some(path) = &state.junit
+            && let Err(e) = fs::write(path, to_junit(&state.suites))
+        {
+            tracing::warn!("Failed to write JUnit test report: {}", e);
+        }
+        if let Some(path) = &state.json {
+            let report = JsonReport {
+                packages: &state.suites,
+            };
+            let result = serde_json::to_string_pretty(&report)
+                .map_err(std::io::Error::from)
+                .and_then(|json| fs::write(path, json));
+            if let Err(e) = result {
+                tracing::warn!("Failed to write JSON test report: {}", e);
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    packages: &'a [TestSuiteReport],
+}
+
+/// Read the test suites from a JSON report (e.g. the report of the tests in a container)
+pub(crate) fn read_json_report(path: &Path) -> Result<Vec<TestSuiteReport>, std::io::Error> {
+    #[derive(Deserialize)]
+    struct OwnedJsonReport {
+        packages: Vec<TestSuiteReport>,
+    }
+
+    let report: OwnedJsonReport = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(report.packages)
+}
+
+/// Escape a string for use in XML attributes and text
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // control characters (e.g. ANSI escape codes) are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Render the test suites as JUnit XML
+fn to_junit(suites: &[TestSuiteReport]) -> String {
+    let total = |status| suites.iter().map(|s| s.count(status)).sum::<usize>();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        r#"<testsuites name="rattler-build" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+        suites.iter().map(|s| s.tests.len()).sum::<usize>(),
+        total(TestStatus::Failed),
+        total(TestStatus::Error),
+        total(TestStatus::Skipped),
+        suites.iter().map(|s| s.duration).sum::<f64>(),
+    );
+
+    for suite in suites {
+        let package = escape_xml(&suite.package);
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{package}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.3}">"#,
+            suite.tests.len(),
+            suite.count(TestStatus::Failed),
+            suite.count(TestStatus::Error),
+            suite.count(TestStatus::Skipped),
+            suite.duration,
+        );
+        for test in &suite.tests {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{package}" time="{:.3}""#,
+                escape_xml(&test.name),
+                test.duration,
+            );
+            let message = test.message.as_deref().unwrap_or_default();
+            let first_line = escape_xml(message.lines().next().unwrap_or_default());
+            match test.status {
+                TestStatus::Passed => xml.push_str("/>\n"),
+                TestStatus::Skipped => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <skipped message=\"{first_line}\"/>\n    </testcase>"
+                    );
+                }
+                TestStatus::Failed | TestStatus::Error => {
+                    let tag = if test.status == TestStatus::Failed {
+                        "failure"
+                    } else {
+                        "error"
+                    };
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <{tag} message=\"{first_line}\">{}</{tag}>\n    </testcase>",
+                        escape_xml(message)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_report() {
+        let mut suite = TestSuiteReport::new(Path::new("/tmp/foo-1.0-h123_0.conda"));
+        assert_eq!(suite.package, "foo-1.0-h123_0");
+        suite.record::<String>("python #0", Duration::from_millis(1500), Ok(()));
+        suite.record(
+            "script #1",
+            Duration::from_secs(2),
+            Err(&"expected stdout to contain \"<foo>\"\n--- stdout ---\nbar"),
+        );
+        suite.skip("downstream #2", "already testing a downstream package");
+        suite.finish::<String>(Duration::from_secs(4), Ok(()));
+
+        insta::assert_snapshot!(to_junit(&[suite]), @r#"
+        <?xml version="1.0" encoding="UTF-8"?>
+        <testsuites name="rattler-build" tests="3" failures="1" errors="0" skipped="1" time="4.000">
+          <testsuite name="foo-1.0-h123_0" tests="3" failures="1" errors="0" skipped="1" time="4.000">
+            <testcase name="python #0" classname="foo-1.0-h123_0" time="1.500"/>
+            <testcase name="script #1" classname="foo-1.0-h123_0" time="2.000">
+              <failure message="expected stdout to contain &quot;&lt;foo&gt;&quot;">expected stdout to contain &quot;&lt;foo&gt;&quot;
+        --- stdout ---
+        bar</failure>
+            </testcase>
+            <testcase name="downstream #2" classname="foo-1.0-h123_0" time="0.000">
+              <skipped message="already testing a downstream package"/>
+            </testcase>
+          </testsuite>
+        </testsuites>
+        "#);
+    }
+
+    #[test]
+    fn test_setup_error() {
+        let mut suite = TestSuiteReport::new(Path::new("foo-1.0-h123_0.tar.bz2"));
+        suite.finish(Duration::from_secs(1), Err(&"failed to extract package"));
+        assert_eq!(suite.tests.len(), 1);
+        assert_eq!(suite.tests[0].status, TestStatus::Error);
+
+        // errors of a failed test element are not recorded twice
+        let mut suite = TestSuiteReport::new(Path::new("foo-1.0-h123_0.conda"));
+        suite.record("python #0", Duration::ZERO, Err(&"import failed"));
+        suite.finish(Duration::from_secs(1), Err(&"import failed"));
+        assert_eq!(suite.tests.len(), 1);
+
+        let json = serde_json::to_value(JsonReport { packages: &[suite] }).unwrap();
+        assert_eq!(json["packages"][0]["tests"][0]["status"], "failed");
+    }
+}
@@ -7,16 +7,22 @@
 //! * `imports` - import a list of modules and check if they can be imported
 //! * `files` - check if a list of files exist
 use fs_err as fs;
+use itertools::Itertools;
 use rattler_build_recipe::stage1::{
     TestType,
-    tests::{CommandsTest, DownstreamTest, PerlTest, PythonTest, PythonVersion, RTest, RubyTest},
+    tests::{
+        CommandsTest, CommandsTestExpected, DownstreamTest, PerlTest, PythonTest, PythonVersion,
+        RTest, RubyTest, TestLimits,
+    },
 };
-use rattler_build_script::{Debug as ScriptDebug, Script, ScriptContent};
+use rattler_build_script::{Debug as ScriptDebug, InterpreterError, Script, ScriptContent};
 use rattler_conda_types::{
-    Channel, ChannelUrl, MatchSpec, ParseStrictness, Platform,
+    Channel, ChannelUrl, GenericVirtualPackage, MatchSpec, PackageName, ParseStrictness, Platform,
+    Version,
     compression_level::CompressionLevel,
-    package::{CondaArchiveIdentifier, IndexJson, PackageFile},
+    package::{CondaArchiveIdentifier, IndexJson, PackageFile, PathsJson},
 };
+use rattler_digest::Sha256Hash;
 use rattler_index::{IndexFsConfig, index_fs};
 use rattler_package_streaming::write::write_conda_package;
 use rattler_shell::{
@@ -26,10 +32,12 @@ use rattler_shell::{
 use rattler_solve::{ChannelPriority, SolveStrategy};
 use std::fmt::Write;
 use std::{
+    borrow::Cow,
     collections::HashMap,
     io::Write as _,
     path::{Path, PathBuf},
     str::FromStr,
+    time::{Duration, Instant},
 };
 use tempfile::TempDir;
 use walkdir::WalkDir;
@@ -39,7 +47,11 @@ use rattler_cache::validation::ValidationMode;
 
 use crate::{
     env_vars,
+    events::{self, Event, Phase},
     metadata::{Debug, PlatformWithVirtualPackages},
+    package_test::{
+        PackageContentsTestExt as _, TestSuiteReport, container::run_test_in_container,
+    },
     render::solver::create_environment,
     source::copy_dir::CopyDir,
     tool_configuration,
@@ -90,6 +102,9 @@ pub enum TestError {
     #[error("missing package file name")]
     MissingPackageFileName,
 
+    #[error("failed to download package {0}")]
+    PackageDownload(String),
+
     #[error("archive type not supported")]
     ArchiveTypeNotSupported,
 
@@ -152,6 +167,15 @@ enum Tests {
 }
 
 impl Tests {
+    /// The name of the test in the test report
+    fn name(&self) -> String {
+        let (Tests::Commands(path) | Tests::Python(path)) = self;
+        format!(
+            "legacy {}",
+            path.file_name().unwrap_or_default().to_string_lossy()
+        )
+    }
+
     async fn run(
         &self,
         environment: &Path,
@@ -291,6 +315,68 @@ pub struct TestConfiguration {
     pub exclude_newer: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+impl TestConfiguration {
+    /// The dependencies of a test environment together with the extra specs from the command
+    /// line (`--test-extra-spec`)
+    fn with_extra_specs(&self, dependencies: &[MatchSpec]) -> Vec<MatchSpec> {
+        dependencies
+            .iter()
+            .chain(&self.tool_configuration.test_extra_specs)
+            .cloned()
+            .collect()
+    }
+
+    /// A copy of the configuration with the given virtual packages of the host platform replaced
+    /// (or added)
+    fn with_virtual_packages(
+        &self,
+        overrides: &indexmap::IndexMap<String, String>,
+    ) -> Result<Self, TestError> {
+        let mut host_platform = self
+            .host_platform
+            .clone()
+            .unwrap_or_else(|| self.current_platform.clone());
+        for (name, version) in overrides {
+            let name = PackageName::from_str(name)
+                .map_err(|e| TestError::TestFailed(format!("invalid virtual package: {e}")))?;
+            let version = Version::from_str(version).map_err(|e| {
+                TestError::TestFailed(format!("invalid version of {}: {e}", name.as_source()))
+            })?;
+            match host_platform
+                .virtual_packages
+                .iter_mut()
+                .find(|package| package.name == name)
+            {
+                Some(package) => package.version = version,
+                None => host_platform.virtual_packages.push(GenericVirtualPackage {
+                    name,
+                    version,
+                    build_string: "0".to_string(),
+                }),
+            }
+        }
+
+        Ok(Self {
+            host_platform: Some(host_platform),
+            ..self.clone()
+        })
+    }
+
+    /// A copy of the configuration that installs the given python version into the test
+    /// environments (`--test-python-version`)
+    fn with_python_version(&self, version: &str) -> Result<Self, TestError> {
+        let mut config = self.clone();
+        config
+            .tool_configuration
+            .test_extra_specs
+            .push(MatchSpec::from_str(
+                &format!("python={version}"),
+                ParseStrictness::Lenient,
+            )?);
+        Ok(config)
+    }
+}
+
 fn env_vars_from_package(index_json: &IndexJson) -> HashMap<String, String> {
     let mut res = HashMap::new();
 
@@ -336,11 +422,59 @@ fn env_vars_from_package(index_json: &IndexJson) -> HashMap<String, String> {
 ///
 /// * `Ok(())` if the test was successful
 /// * `Err(TestError::TestFailed)` if the test failed
+///
+/// The results are added to the test report of the tool configuration (if any).
 #[async_recursion::async_recursion]
 pub async fn run_test(
     package_file: &Path,
     config: &TestConfiguration,
     downstream_package: Option<PathBuf>,
+) -> Result<(), TestError> {
+    let start = Instant::now();
+    let mut suite = TestSuiteReport::new(package_file);
+    let is_downstream = downstream_package.is_some();
+    if !is_downstream {
+        events::emit(|| Event::PhaseStart {
+            phase: Phase::Test,
+            package: Some(suite.package.clone()),
+        });
+    }
+    let result = match &config.tool_configuration.test_container {
+        Some(image) if !is_downstream => {
+            run_test_in_container(image, package_file, config, &mut suite).await
+        }
+        _ => run_package_tests(package_file, config, downstream_package, &mut suite).await,
+    };
+
+    // the tests of a downstream package are recorded as one test of the upstream package
+    if is_downstream {
+        return result;
+    }
+    suite.finish(start.elapsed(), result.as_ref().map(|_| ()));
+    events::emit(|| Event::TestResult {
+        success: result.is_ok(),
+        suite: suite.clone(),
+    });
+    events::emit(|| {
+        Event::phase_end(
+            Phase::Test,
+            Some(suite.package.clone()),
+            start.elapsed(),
+            &result,
+        )
+    });
+    if let Some(report) = &config.tool_configuration.test_report {
+        report.add_suite(suite);
+    }
+    result
+}
+
+/// Run the tests of a package and record the result of every test in `suite`.
+async fn run_package_tests(
+    package_file: &Path,
+    config: &TestConfiguration,
+    downstream_package: Option<PathBuf>,
+    suite: &mut TestSuiteReport,
 ) -> Result<(), TestError> {
     let tmp_repo = tempfile::tempdir()?;
 
@@ -510,7 +644,7 @@ pub async fn run_test(
 
         create_environment(
             "test",
-            &dependencies,
+            &config.with_extra_specs(&dependencies),
             &host_platform,
             &prefix,
             &config.channels,
@@ -526,7 +660,10 @@ pub async fn run_test(
         let (test_folder, tests) = legacy_tests_from_folder(&package_folder).await?;
 
         for test in tests {
-            test.run(&prefix, &test_folder, &env).await?;
+            let start = Instant::now();
+            let result = test.run(&prefix, &test_folder, &env).await;
+            suite.record(test.name(), start.elapsed(), result.as_ref().map(|_| ()));
+            result?;
         }
 
         tracing::info!(
@@ -553,50 +690,128 @@ pub async fn run_test(
             )));
         }
 
+        let first_index = config.test_index.unwrap_or(0);
         let tests = if let Some(test_index) = config.test_index {
             vec![tests[test_index].clone()]
         } else {
             tests
         };
 
-        for test in tests {
-            let test_prefix = TempDir::with_prefix_in(
-                format!("test_{}", pkg.identifier.name),
-                &config.test_prefix,
-            )?
-            .keep();
-            match test {
-                TestType::Commands(c) => {
-                    run_commands_test(&c, &pkg, &package_folder, &test_prefix, &config, &env)
-                        .await?
-                }
-                TestType::Python { python } => {
-                    run_python_test(&python, &pkg, &package_folder, &test_prefix, &config).await?
-                }
-                TestType::Perl { perl } => {
-                    run_perl_test(&perl, &pkg, &package_folder, &test_prefix, &config).await?
-                }
-                TestType::R { r } => {
-                    run_r_test(&r, &pkg, &package_folder, &test_prefix, &config).await?
-                }
-                TestType::Ruby { ruby } => {
-                    run_ruby_test(&ruby, &pkg, &package_folder, &test_prefix, &config).await?
-                }
-                TestType::Downstream(downstream) if downstream_package.is_none() => {
-                    run_downstream_test(&downstream, &pkg, package_file, &test_prefix, &config)
-                        .await?
+        for (index, test) in tests.into_iter().enumerate() {
+            let name = test_name(&test, first_index + index);
+
+            // package content tests only look at the paths of the package and don't need an
+            // environment
+            if let TestType::PackageContents { package_contents } = &test {
+                let start = Instant::now();
+                let paths_json = PathsJson::from_package_directory(&package_folder)?;
+                let version_independent = index_json.noarch.is_python();
+                let result =
+                    package_contents.run_test(&paths_json, &target_platform, version_independent);
+                suite.record(name, start.elapsed(), result.as_ref().map(|_| ()));
+                result?;
+                continue;
+            }
+
+            // noarch python packages are tested with every python version of
+            // `--test-python-version`, each in its own environment
+            let python_versions = &config.tool_configuration.test_python_versions;
+            let runs = if index_json.noarch.is_python()
+                && !python_versions.is_empty()
+                && !matches!(test, TestType::Downstream(_))
+            {
+                python_versions
+                    .iter()
+                    .map(|version| {
+                        let mut test = test.clone();
+                        if let TestType::Python { python } = &mut test {
+                            python.python_version = PythonVersion::None;
+                        }
+                        Ok((
+                            format!("{name} (python {version})"),
+                            Cow::Owned(config.with_python_version(version)?),
+                            Cow::Owned(test),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, TestError>>()?
+            } else {
+                vec![(name, Cow::Borrowed(&config), Cow::Borrowed(&test))]
+            };
+
+            // re-run the test for every entry of its virtual package matrix
+            let virtual_packages = test
+                .virtual_packages()
+                .map(|matrix| matrix.0.as_slice())
+                .unwrap_or_default();
+            let runs = if virtual_packages.is_empty() {
+                runs
+            } else {
+                let mut matrix = Vec::with_capacity(runs.len() * virtual_packages.len());
+                for (name, config, test) in runs {
+                    for overrides in virtual_packages {
+                        let description = overrides
+                            .iter()
+                            .map(|(name, version)| format!("{name}={version}"))
+                            .join(", ");
+                        matrix.push((
+                            format!("{name} ({description})"),
+                            Cow::Owned(config.with_virtual_packages(overrides)?),
+                            test.clone(),
+                        ));
+                    }
                 }
-                TestType::Downstream(_) => {
-                    tracing::info!(
-                        "Skipping downstream test as we are already testing a downstream package"
-                    )
+                matrix
+            };
+
+            let is_matrix = runs.len() > 1;
+            for (name, config, test) in runs {
+                if is_matrix {
+                    tracing::info!("Running {}", name);
                 }
-                // This test already runs during the build process and we don't need to run it again
-                TestType::PackageContents { .. } => {}
-            }
+                let start = Instant::now();
+                let test_prefix = TempDir::with_prefix_in(
+                    format!("test_{}", pkg.identifier.name),
+                    &config.test_prefix,
+                )?
+                .keep();
+                let result = match &*test {
+                    TestType::Commands(c) => {
+                        run_commands_test(c, &pkg, &package_folder, &test_prefix, &config, &env)
+                            .await
+                    }
+                    TestType::Python { python } => {
+                        run_python_test(python, &pkg, &package_folder, &test_prefix, &config).await
+                    }
+                    TestType::Perl { perl } => {
+                        run_perl_test(perl, &pkg, &package_folder, &test_prefix, &config).await
+                    }
+                    TestType::R { r } => {
+                        run_r_test(r, &pkg, &package_folder, &test_prefix, &config).await
+                    }
+                    TestType::Ruby { ruby } => {
+                        run_ruby_test(ruby, &pkg, &package_folder, &test_prefix, &config).await
+                    }
+                    TestType::Downstream(downstream) if downstream_package.is_none() => {
+                        run_downstream_test(downstream, &pkg, package_file, &test_prefix, &config)
+                            .await
+                    }
+                    TestType::Downstream(_) => {
+                        tracing::info!(
+                            "Skipping downstream test as we are already testing a downstream package"
+                        );
+                        suite.skip(name, "already testing a downstream package");
+                        fs::remove_dir_all(test_prefix)?;
+                        continue;
+                    }
+                    // Already checked above, without a test prefix
+                    TestType::PackageContents { .. } => Ok(()),
+                };
+                suite.record(name, start.elapsed(), result.as_ref().map(|_| ()));
+                result?;
 
-            if !config.keep_test_prefix {
-                fs::remove_dir_all(test_prefix)?;
+                if !config.keep_test_prefix {
+                    fs::remove_dir_all(test_prefix)?;
+                }
             }
         }
 
@@ -609,6 +824,108 @@ pub async fn run_test(
     Ok(())
 }
 
+/// The name of a test element in the test report, e.g. `python #1`
+pub(crate) fn test_name(test: &TestType, index: usize) -> String {
+    let kind = match test {
+        TestType::Commands(_) => "script",
+        TestType::Python { .. } => "python",
+        TestType::Perl { .. } => "perl",
+        TestType::R { .. } => "r",
+        TestType::Ruby { .. } => "ruby",
+        TestType::Downstream(_) => "downstream",
+        TestType::PackageContents { .. } => "package_contents",
+    };
+    format!("{kind} #{index}")
+}
+
+/// Run a test script with the timeout and retries of the test. The script process and all
+/// processes that it started are killed by the test runner when the timeout is exceeded.
+async fn run_with_limits<F, Fut, T, E>(limits: &TestLimits, mut run: F) -> Result<(), TestError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+    E: std::fmt::Display,
+{
+    let mut attempt = 0;
+    loop {
+        // the processes that the test starts are killed with it, so that none of them keeps
+        // running (and using the test prefix) after a timeout
+        let run = rattler_build_script::with_process_tree_cleanup(run());
+        let result = match limits.timeout {
+            Some(timeout) => match tokio::time::timeout(Duration::from_secs(timeout), run).await {
+                Ok(result) => result.map_err(|e| e.to_string()),
+                Err(_) => Err(format!("test timed out after {timeout} seconds")),
+            },
+            None => run.await.map_err(|e| e.to_string()),
+        };
+
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < limits.retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Test failed ({}), retrying (attempt {} of {})",
+                    e,
+                    attempt,
+                    limits.retries
+                );
+            }
+            Err(e) => return Err(TestError::TestFailed(e)),
+        }
+    }
+}
+
+/// Check the exit code and the output of a script test against the `expected` section of the
+/// test. All failed assertions are reported together.
+fn check_expected_output(
+    expected: &CommandsTestExpected,
+    result: Result<std::process::Output, InterpreterError>,
+) -> Result<(), String> {
+    let output = match result {
+        Ok(output) => output,
+        Err(InterpreterError::NonZeroExitCode(output)) if expected.exit_code.is_some() => output,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut failures = Vec::new();
+    let expected_exit_code = expected.exit_code.unwrap_or(0);
+    match output.status.code() {
+        Some(code) if code == expected_exit_code => {}
+        Some(code) => failures.push(format!(
+            "expected exit code {expected_exit_code}, but the script exited with {code}"
+        )),
+        None => failures.push(format!(
+            "expected exit code {expected_exit_code}, but the script was terminated by a signal"
+        )),
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for substring in &expected.stdout {
+        if !stdout.contains(substring.as_str()) {
+            failures.push(format!("expected stdout to contain {substring:?}"));
+        }
+    }
+    for pattern in &expected.stdout_regex {
+        let regex = regex::RegexBuilder::new(pattern)
+            .multi_line(true)
+            .build()
+            .map_err(|e| format!("invalid regular expression {pattern:?}: {e}"))?;
+        if !regex.is_match(&stdout) {
+            failures.push(format!("expected stdout to match {pattern:?}"));
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}\n--- stdout ---\n{}",
+            failures.join("\n"),
+            stdout.trim_end()
+        ))
+    }
+}
+
 /// Execute the Python test
 async fn run_python_test(
     python_test: &PythonTest,
@@ -707,7 +1024,7 @@ async fn run_python_test_inner(
     let test_prefix = prefix.join("test_env");
     create_environment(
         "test",
-        &dependencies,
+        &config.with_extra_specs(&dependencies),
         config
             .host_platform
             .as_ref()
@@ -735,8 +1052,8 @@ async fn run_python_test_inner(
 
     let test_dir = prefix.join("test");
     fs::create_dir_all(&test_dir)?;
-    script
-        .run_script(
+    run_with_limits(&python_test.limits, || {
+        script.run_script(
             Default::default(),
             &test_dir,
             path,
@@ -746,8 +1063,8 @@ async fn run_python_test_inner(
             None,
             ScriptDebug::new(config.debug.is_enabled()),
         )
-        .await
-        .map_err(|e| TestError::TestFailed(e.to_string()))?;
+    })
+    .await?;
 
     tracing::info!(
         "{} python imports test passed!",
@@ -759,8 +1076,8 @@ async fn run_python_test_inner(
             content: ScriptContent::Command("pip check".into()),
             ..Script::default()
         };
-        script
-            .run_script(
+        run_with_limits(&python_test.limits, || {
+            script.run_script(
                 Default::default(),
                 path,
                 path,
@@ -770,8 +1087,8 @@ async fn run_python_test_inner(
                 None,
                 ScriptDebug::new(config.debug.is_enabled()),
             )
-            .await
-            .map_err(|e| TestError::TestFailed(e.to_string()))?;
+        })
+        .await?;
 
         tracing::info!(
             "{} pip check passed!",
@@ -810,7 +1127,7 @@ async fn run_perl_test(
     let test_prefix = prefix.join("test_env");
     create_environment(
         "test",
-        &dependencies,
+        &config.with_extra_specs(&dependencies),
         config
             .host_platform
             .as_ref()
@@ -842,8 +1159,8 @@ async fn run_perl_test(
 
     let test_folder = prefix.join("test_files");
     fs::create_dir_all(&test_folder)?;
-    script
-        .run_script(
+    run_with_limits(&perl_test.limits, || {
+        script.run_script(
             Default::default(),
             &test_folder,
             path,
@@ -853,8 +1170,13 @@ async fn run_perl_test(
             None,
             ScriptDebug::new(config.debug.is_enabled()),
         )
-        .await
-        .map_err(|e| TestError::TestFailed(e.to_string()))?;
+    })
+    .await?;
+
+    tracing::info!(
+        "{} perl imports test passed!",
+        console::style(console::Emoji("✔", "")).green()
+    );
 
     Ok(())
 }
@@ -923,7 +1245,7 @@ async fn run_commands_test(
     let run_prefix = test_directory.join("test_run_env");
     create_environment(
         "test",
-        &dependencies,
+        &config.with_extra_specs(&dependencies),
         platform,
         &run_prefix,
         &config.channels,
@@ -955,21 +1277,75 @@ async fn run_commands_test(
     })?;
 
     tracing::info!("Testing commands:");
-    commands_test
-        .script
-        .run_script(
-            env_vars,
-            &test_dir,
-            path,
-            &run_prefix,
-            build_prefix.as_ref(),
-            None::<fn(&str) -> Result<String, String>>,
-            None,
-            ScriptDebug::new(config.debug.is_enabled()),
-        )
-        .await
-        .map_err(|e| TestError::TestFailed(e.to_string()))?;
+    let (script, test_dir, run_prefix) = (&commands_test.script, &test_dir, &run_prefix);
+    let build_prefix = build_prefix.as_ref();
+    run_with_limits(&commands_test.limits, || {
+        let env_vars = env_vars.clone();
+        async move {
+            let result = script
+                .run_script(
+                    env_vars,
+                    test_dir,
+                    path,
+                    run_prefix,
+                    build_prefix,
+                    None::<fn(&str) -> Result<String, String>>,
+                    None,
+                    ScriptDebug::new(config.debug.is_enabled()),
+                )
+                .await;
+            check_expected_output(&commands_test.expected, result)
+        }
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Download the package at `url` to `dest` and verify its SHA256 hash (if known). The
+/// authenticated client is used, so that packages from private channels can be tested as well.
+pub async fn download_package(
+    url: &url::Url,
+    sha256: Option<&Sha256Hash>,
+    dest: &Path,
+    tool_configuration: &tool_configuration::Configuration,
+) -> Result<(), TestError> {
+    let download_error =
+        |e: &dyn std::fmt::Display| TestError::PackageDownload(format!("{url}: {e}"));
+    let bytes = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| download_error(&"invalid file URL"))?;
+        fs::read(path)?
+    } else {
+        let response = tool_configuration
+            .client
+            .for_host(url)
+            .get(url.clone())
+            .send()
+            .await
+            .map_err(|e| download_error(&e))?
+            .error_for_status()
+            .map_err(|e| download_error(&e))?;
+        response
+            .bytes()
+            .await
+            .map_err(|e| download_error(&e))?
+            .to_vec()
+    };
 
+    if let Some(expected) = sha256 {
+        let actual = rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(&bytes);
+        if actual != *expected {
+            return Err(download_error(&format!(
+                "SHA256 mismatch (expected {expected:x}, got {actual:x})"
+            )));
+        }
+    }
+
+    // write out the package to a temporary directory
+    let mut file = fs::File::create(dest)?;
+    file.write_all(&bytes)?;
     Ok(())
 }
 
@@ -1048,18 +1424,13 @@ async fn run_downstream_test(
                 .path()
                 .join(downstream_package.identifier.to_file_name());
 
-            if downstream_package.url.scheme() == "file" {
-                fs::copy(
-                    downstream_package.url.to_file_path().unwrap(),
-                    &package_file,
-                )?;
-            } else {
-                let package_dl = reqwest::get(downstream_package.url.clone()).await.unwrap();
-                // write out the package to a temporary directory
-                let mut file = fs::File::create(&package_file)?;
-                let bytes = package_dl.bytes().await.unwrap();
-                file.write_all(&bytes)?;
-            }
+            download_package(
+                &downstream_package.url,
+                downstream_package.package_record.sha256.as_ref(),
+                &package_file,
+                &config.tool_configuration,
+            )
+            .await?;
 
             // run the test with the downstream package
             tracing::info!("Running downstream test with {:?}", &package_file);
@@ -1109,7 +1480,7 @@ async fn run_r_test(
     let test_prefix = prefix.join("test_env");
     create_environment(
         "test",
-        &dependencies,
+        &config.with_extra_specs(&dependencies),
         config
             .host_platform
             .as_ref()
@@ -1141,8 +1512,8 @@ async fn run_r_test(
 
     let test_folder = prefix.join("test_files");
     fs::create_dir_all(&test_folder)?;
-    script
-        .run_script(
+    run_with_limits(&r_test.limits, || {
+        script.run_script(
             Default::default(),
             &test_folder,
             path,
@@ -1152,8 +1523,13 @@ async fn run_r_test(
             None,
             ScriptDebug::new(config.debug.is_enabled()),
         )
-        .await
-        .map_err(|e| TestError::TestFailed(e.to_string()))?;
+    })
+    .await?;
+
+    tracing::info!(
+        "{} R libraries test passed!",
+        console::style(console::Emoji("✔", "")).green()
+    );
 
     Ok(())
 }
@@ -1187,7 +1563,7 @@ async fn run_ruby_test(
     let test_prefix = prefix.join("test_env");
     create_environment(
         "test",
-        &dependencies,
+        &config.with_extra_specs(&dependencies),
         config
             .host_platform
             .as_ref()
@@ -1219,8 +1595,8 @@ async fn run_ruby_test(
 
     let test_folder = prefix.join("test_files");
     fs::create_dir_all(&test_folder)?;
-    script
-        .run_script(
+    run_with_limits(&ruby_test.limits, || {
+        script.run_script(
             Default::default(),
             &test_folder,
             path,
@@ -1230,8 +1606,8 @@ async fn run_ruby_test(
             None,
             ScriptDebug::new(config.debug.is_enabled()),
         )
-        .await
-        .map_err(|e| TestError::TestFailed(e.to_string()))?;
+    })
+    .await?;
 
     Ok(())
 }
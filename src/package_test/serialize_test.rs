@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use fs_err as fs;
+use indicatif::HumanBytes;
 use rattler_build_recipe::stage1::{TestType, requirements::Dependency, tests::CommandsTest};
 use rattler_build_script::{
     ResolvedScriptContents, ScriptContent, determine_interpreter_from_path,
@@ -10,7 +11,7 @@ use rattler_conda_types::{MatchSpec, PackageNameMatcher};
 use crate::{metadata::Output, packaging::PackagingError};
 
 /// Resolve a dependency, converting PinSubpackage/PinCompatible to concrete MatchSpecs
-fn resolve_dependency(dep: &Dependency, output: &Output) -> Dependency {
+pub(crate) fn resolve_dependency(dep: &Dependency, output: &Output) -> Dependency {
     match dep {
         Dependency::Spec(_) => dep.clone(),
         Dependency::PinSubpackage(pin) => {
@@ -87,6 +88,7 @@ pub fn write_command_test_files(
         .use_gitignore(false)
         .run()?;
 
+        warn_on_large_test_files("recipe", folder, copy_dir.copied_paths());
         test_files.extend(copy_dir.copied_paths().iter().cloned());
     }
 
@@ -100,12 +102,49 @@ pub fn write_command_test_files(
         .use_gitignore(false)
         .run()?;
 
+        warn_on_large_test_files("source", folder, copy_dir.copied_paths());
         test_files.extend(copy_dir.copied_paths().iter().cloned());
     }
 
     Ok(test_files)
 }
 
+/// Above this size the test files copied from the source or recipe directory trigger a warning
+const TEST_FILES_SIZE_BUDGET: u64 = 100 * 1024 * 1024;
+
+/// Warn if the copied test files exceed [`TEST_FILES_SIZE_BUDGET`], listing the largest files
+/// so that they can be excluded in the recipe.
+fn warn_on_large_test_files(kind: &str, folder: &Path, copied_paths: &[PathBuf]) {
+    let mut sizes = copied_paths
+        .iter()
+        .filter_map(|path| {
+            let metadata = fs::symlink_metadata(path).ok()?;
+            metadata.is_file().then_some((path, metadata.len()))
+        })
+        .collect::<Vec<_>>();
+    let total = sizes.iter().map(|(_, size)| size).sum::<u64>();
+    if total <= TEST_FILES_SIZE_BUDGET {
+        return;
+    }
+
+    sizes.sort_by(|a, b| b.1.cmp(&a.1));
+    let largest = sizes
+        .iter()
+        .take(5)
+        .map(|(path, size)| {
+            let path = path.strip_prefix(folder).unwrap_or(path);
+            format!("  - {} ({})", path.display(), HumanBytes(*size))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    tracing::warn!(
+        "The test files copied from the {kind} directory are {} (more than {}). \
+        Consider excluding files with `files.{kind}.exclude`. The largest files are:\n{largest}",
+        HumanBytes(total),
+        HumanBytes(TEST_FILES_SIZE_BUDGET),
+    );
+}
+
 /// Write out the test files for the final package
 pub(crate) fn write_test_files(
     output: &Output,
@@ -16,14 +16,17 @@ use rattler_conda_types::{
     compression_level::CompressionLevel,
     package::{CondaArchiveType, FileMode, PackageFile, PathType, PathsJson},
 };
-use rattler_package_streaming::write::{write_conda_package, write_tar_bz2_package};
+use rattler_package_streaming::write::write_tar_bz2_package;
 use unicode_normalization::UnicodeNormalization;
 
+mod archive;
 mod file_finder;
 mod file_mapper;
 mod metadata;
+mod provenance;
 pub use file_finder::{Files, TempFiles, content_type, record_files};
 pub use metadata::{contains_prefix_binary, contains_prefix_text, create_prefix_placeholder};
+pub use provenance::{FileOrigin, FileProvenance, Modification, PathsProvenance, ProvenanceEntry};
 use tempfile::NamedTempFile;
 
 use crate::{
@@ -70,12 +73,27 @@ pub enum PackagingError {
     #[error(transparent)]
     RelinkError(#[from] crate::post_process::relink::RelinkError),
 
+    #[error(transparent)]
+    BuildPathLeakError(#[from] crate::post_process::build_path_leaks::BuildPathLeakError),
+
+    #[error("strip error: {0}")]
+    StripError(#[from] crate::post_process::strip::StripError),
+
+    #[error("codesign error: {0}")]
+    CodesignError(#[from] crate::post_process::codesign::CodesignError),
+
+    #[error(transparent)]
+    PostProcessorError(#[from] crate::post_process::plugin::PostProcessorError),
+
     #[error(transparent)]
     SourceError(#[from] source::SourceError),
 
     #[error("could not create python entry point: {0}")]
     CannotCreateEntryPoint(String),
 
+    #[error("declared entry points do not match the package metadata:\n{0}")]
+    EntryPointMismatch(String),
+
     #[error("linking check error: {0}")]
     LinkingCheckError(#[from] crate::post_process::checks::LinkingCheckError),
 
@@ -93,6 +111,15 @@ pub enum PackagingError {
 
     #[error("Invalid MenuInst schema file: {0} - {1}")]
     InvalidMenuInstSchema(PathBuf, serde_json::Error),
+
+    #[error("Invalid MenuInst file: {0} - {1}")]
+    InvalidMenuInstDocument(PathBuf, String),
+
+    #[error("symlink {0} is not allowed by `build.symlinks`")]
+    SymlinkNotAllowed(PathBuf),
+
+    #[error("cannot dereference symlink {0}: the target does not exist")]
+    CannotDereferenceSymlink(PathBuf),
 }
 
 /// This function copies the license files to the info/licenses folder.
@@ -671,8 +698,8 @@ fn print_enhanced_file_listing(
 }
 
 /// Given an output and a set of new files, create a conda package.
-/// This function will copy all the files to a temporary directory and then
-/// create a conda package from that. Note that the output needs to have its
+/// This function will link (or copy) all the files to a temporary directory and
+/// then create a conda package from that. Note that the output needs to have its
 /// dependencies finalized before calling this function.
 ///
 /// The `local_channel_dir` is the path to the local channel / output directory.
@@ -688,20 +715,73 @@ pub fn package_conda(
         return Err(PackagingError::DependenciesNotFinalized);
     }
 
-    let mut tmp = files.to_temp_folder(output)?;
+    // post-processor plugins can modify any file in the package, so nothing is hard linked
+    let mut tmp = files.to_temp_folder(output, tool_configuration.post_processors.is_empty())?;
 
     tracing::info!("Copying done!");
 
-    post_process::relink::relink(&tmp, output)?;
+    let relativized = post_process::autotools::relativize_config_files(&tmp, output)?;
+    tmp.record_modification(&relativized, Modification::RelativeConfig);
 
+    tmp.add_files_with_origin(
+        post_process::vendor::vendor_libraries(&tmp, output)?,
+        FileOrigin::VendoredLibrary,
+    );
+
+    let relinked = post_process::relink::relink(&tmp, output)?;
+    tmp.record_modification(&relinked, Modification::Relink);
+
+    tmp.add_files_with_origin(
+        post_process::menuinst::write_menu_file(&tmp, output)?,
+        FileOrigin::RecipeMenu,
+    );
     post_process::menuinst::menuinst(&tmp)?;
 
-    tmp.add_files(post_process::python::python(&tmp, output)?);
+    tmp.add_files_with_origin(
+        post_process::python::python(&tmp, output)?,
+        FileOrigin::PythonPostProcess,
+    );
 
-    post_process::regex_replacements::regex_post_process(&tmp, output)?;
+    let shebangs = post_process::shebang::fix_shebangs(&tmp, output)?;
+    tmp.record_modification(&shebangs, Modification::Shebang);
+
+    let replaced = post_process::regex_replacements::regex_post_process(&tmp, output)?;
+    tmp.record_modification(&replaced, Modification::RegexReplacement);
+
+    let stripped = post_process::strip::strip(&tmp, output)?;
+    tmp.record_modification(&stripped, Modification::Strip);
+
+    // Sign last, any modification of a Mach-O binary invalidates its signature
+    let signed = post_process::codesign::codesign(&tmp, output, tool_configuration)?;
+    tmp.record_modification(&signed, Modification::Codesign);
+
+    let extra_meta = post_process::plugin::run_post_processors(
+        &mut tmp,
+        output,
+        &tool_configuration.post_processors,
+    )?;
+    // the post-processors can add entries to the `extra` section of `about.json`
+    let output_with_extra_meta;
+    let output = if extra_meta.is_empty() {
+        output
+    } else {
+        let mut with_extra_meta = output.clone();
+        with_extra_meta
+            .extra_meta
+            .get_or_insert_with(Default::default)
+            .extend(extra_meta);
+        output_with_extra_meta = with_extra_meta;
+        &output_with_extra_meta
+    };
 
     tracing::info!("Post-processing done!");
 
+    post_process::build_path_leaks::check_build_path_leaks(&tmp, output)?;
+
+    if output.build_configuration.report_dependency_usage {
+        post_process::dependency_usage::report_dependency_usage(&tmp, output);
+    }
+
     // Validate any dsolist JSON files being packaged (CEP-28)
     post_process::checks::validate_dsolist_files(tmp.temp_dir.path())?;
 
@@ -712,7 +792,13 @@ pub fn package_conda(
     tmp.add_files(test_files);
 
     tracing::info!("Writing metadata for package");
-    tmp.add_files(output.write_metadata(&tmp)?);
+    let progress_bar = tool_configuration.fancy_log_handler.add_progress_bar(
+        indicatif::ProgressBar::new(0)
+            .with_prefix("Hashing ")
+            .with_style(tool_configuration.fancy_log_handler.default_bytes_style()),
+    );
+    tmp.add_files(output.write_metadata(&tmp, &progress_bar)?);
+    progress_bar.finish_and_clear();
 
     // TODO move things below also to metadata.rs
     tracing::info!("Copying license files");
@@ -813,7 +899,7 @@ pub fn package_conda(
             )?;
         }
         CondaArchiveType::Conda => {
-            write_conda_package(
+            archive::write_conda_package(
                 tempfile_in_output.as_file(),
                 tmp.temp_dir.path(),
                 &tmp.files.iter().cloned().collect::<Vec<_>>(),
@@ -821,7 +907,7 @@ pub fn package_conda(
                 tool_configuration.compression_threads,
                 &identifier,
                 Some(&output.build_configuration.timestamp),
-                Some(Box::new(ProgressBar { progress_bar })),
+                &progress_bar,
             )?;
         }
     }
@@ -865,13 +951,41 @@ impl Output {
     ) -> Result<(PathBuf, PathsJson), PackagingError> {
         let span = tracing::info_span!("Packaging new files");
         let _enter = span.enter();
-        let files_after = Files::from_prefix(
+        let mut files_after = Files::from_prefix(
             &self.build_configuration.directories.host_prefix,
             &self.recipe.build().always_include_files,
             &self.recipe.build().files,
             post_install_files,
         )?;
 
+        match self.inherited_staging_files() {
+            Ok(Some((cache_name, staged_files))) => {
+                let (included, excluded): (Vec<_>, Vec<_>) = staged_files
+                    .iter()
+                    .partition(|f| files_after.new_files.contains(*f));
+                tracing::info!(
+                    "Including {} of the {} unchanged files of staging cache '{}'",
+                    included.len(),
+                    staged_files.len(),
+                    cache_name
+                );
+                for file in excluded {
+                    tracing::debug!(
+                        "Excluding {} of staging cache '{}'",
+                        file.strip_prefix(&files_after.prefix)
+                            .unwrap_or(file)
+                            .display(),
+                        cache_name
+                    );
+                }
+                files_after.staged_files = staged_files;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!("Failed to find the files of the staging cache: {e}");
+            }
+        }
+
         package_conda(self, tool_configuration, &files_after)
     }
 }
@@ -0,0 +1,257 @@
+//! Writes `.conda` archives by streaming the files directly into the zstd compressor.
+//!
+//! The writer of `rattler_package_streaming` first writes an uncompressed tarball of the package
+//! to a temporary file and compresses that afterwards, which writes the whole content of the
+//! package to disk one more time. The archives written here are identical, except that the zstd
+//! frames do not record the uncompressed size.
+
+use std::{
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use fs_err as fs;
+use rattler_conda_types::{compression_level::CompressionLevel, package::PackageMetadata};
+
+/// The modification time of all files if no timestamp is given (1-1-2023 00:00:00)
+const DEFAULT_MTIME: u64 = 1672531200;
+
+/// Split the paths (relative to `base_path`) into the `info/` files and all other files, both
+/// sorted alphabetically for reproducibility
+fn sort_paths(paths: &[PathBuf], base_path: &Path) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut relative = paths
+        .iter()
+        .map(|p| {
+            p.strip_prefix(base_path)
+                .map(Path::to_path_buf)
+                .map_err(io::Error::other)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    relative.sort();
+    Ok(relative
+        .into_iter()
+        .partition(|path| path.starts_with("info/")))
+}
+
+/// Write a `.conda` package to `writer`. The package is an uncompressed zip archive with a
+/// `metadata.json` file, the `pkg-<out_name>.tar.zst` archive with all files outside of `info/`
+/// and the `info-<out_name>.tar.zst` archive with the `info/` files.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_conda_package<W: Write + Seek>(
+    writer: W,
+    base_path: &Path,
+    paths: &[PathBuf],
+    compression_level: CompressionLevel,
+    compression_threads: Option<u32>,
+    out_name: &str,
+    timestamp: Option<&DateTime<Utc>>,
+    progress_bar: &indicatif::ProgressBar,
+) -> io::Result<()> {
+    let mut outer_archive = zip::ZipWriter::new(writer);
+
+    let last_modified_time = match timestamp {
+        Some(time) => zip::DateTime::from_date_and_time(
+            time.year() as u16,
+            time.month() as u8,
+            time.day() as u8,
+            time.hour() as u8,
+            time.minute() as u8,
+            time.second() as u8,
+        )
+        .map_err(io::Error::other)?,
+        None => zip::DateTime::from_date_and_time(2023, 1, 1, 0, 0, 0)
+            .expect("1-1-2023 00:00:00 is a valid date"),
+    };
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .last_modified_time(last_modified_time)
+        .large_file(true);
+
+    outer_archive.start_file("metadata.json", options)?;
+    serde_json::to_writer(&mut outer_archive, &PackageMetadata::default())?;
+
+    let (info_paths, other_paths) = sort_paths(paths, base_path)?;
+    let level = compression_level.to_zstd_level()?;
+
+    progress_bar.set_length(total_size(base_path, &other_paths));
+    outer_archive.start_file(format!("pkg-{out_name}.tar.zst"), options)?;
+    write_zst_archive(
+        &mut outer_archive,
+        base_path,
+        &other_paths,
+        level,
+        compression_threads,
+        timestamp,
+        Some(progress_bar),
+    )?;
+
+    // the info archive comes last
+    outer_archive.start_file(format!("info-{out_name}.tar.zst"), options)?;
+    write_zst_archive(
+        &mut outer_archive,
+        base_path,
+        &info_paths,
+        level,
+        compression_threads,
+        timestamp,
+        None,
+    )?;
+
+    outer_archive.finish()?;
+    Ok(())
+}
+
+/// The total size of the regular files
+fn total_size(base_path: &Path, paths: &[PathBuf]) -> u64 {
+    paths
+        .iter()
+        .filter_map(|p| fs::symlink_metadata(base_path.join(p)).ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Write the files to a zstd compressed tarball, without an intermediate uncompressed tarball
+fn write_zst_archive<W: Write>(
+    writer: W,
+    base_path: &Path,
+    paths: &[PathBuf],
+    level: i32,
+    compression_threads: Option<u32>,
+    timestamp: Option<&DateTime<Utc>>,
+    progress_bar: Option<&indicatif::ProgressBar>,
+) -> io::Result<()> {
+    let mut encoder = zstd::Encoder::new(writer, level)?;
+    encoder.multithread(compression_threads.unwrap_or_else(|| num_cpus::get() as u32))?;
+
+    let mut archive = tar::Builder::new(encoder);
+    archive.follow_symlinks(false);
+    for path in paths {
+        append_path(&mut archive, base_path, path, timestamp, progress_bar)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+    }
+
+    archive.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Append a file, symlink or directory to the tarball. The headers are the same as the ones of
+/// `rattler_package_streaming`, so that the packages do not change.
+fn append_path<W: Write>(
+    archive: &mut tar::Builder<W>,
+    base_path: &Path,
+    path: &Path,
+    timestamp: Option<&DateTime<Utc>>,
+    progress_bar: Option<&indicatif::ProgressBar>,
+) -> io::Result<()> {
+    let full_path = base_path.join(path);
+    let stat = fs::symlink_metadata(&full_path)?;
+
+    let mut header = tar::Header::new_gnu();
+    let name = b"././@LongLink";
+    if let Some(gnu) = header.as_gnu_mut() {
+        gnu.name[..name.len()].clone_from_slice(&name[..]);
+    }
+    header.set_metadata_in_mode(&stat, tar::HeaderMode::Deterministic);
+    header.set_mtime(timestamp.map_or(DEFAULT_MTIME, |t| t.timestamp().unsigned_abs()));
+
+    let entry_type = header.entry_type();
+    if entry_type.is_file() {
+        let file = fs::File::open(&full_path)?;
+        match progress_bar {
+            Some(progress_bar) => {
+                archive.append_data(&mut header, path, progress_bar.wrap_read(file))
+            }
+            None => archive.append_data(&mut header, path, file),
+        }
+    } else if entry_type.is_symlink() || entry_type.is_hard_link() {
+        let target = fs::read_link(&full_path)?;
+        archive.append_link(&mut header, path, target)
+    } else if entry_type.is_dir() {
+        archive.append_data(&mut header, path, io::empty())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported file type",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// The decompressed inner archives of a `.conda` package
+    fn inner_archives(package: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut zip = zip::ZipArchive::new(fs::File::open(package).unwrap()).unwrap();
+        (0..zip.len())
+            .map(|i| {
+                let mut file = zip.by_index(i).unwrap();
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).unwrap();
+                if file.name().ends_with(".tar.zst") {
+                    content = zstd::decode_all(content.as_slice()).unwrap();
+                }
+                (file.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_conda_package() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path().join("pkg");
+        fs::create_dir_all(base.join("info")).unwrap();
+        fs::create_dir_all(base.join("lib")).unwrap();
+        fs::write(base.join("info/index.json"), "{}").unwrap();
+        fs::write(base.join("lib/libfoo.so"), vec![42u8; 100_000]).unwrap();
+        let long_name = format!("lib/{}.txt", "a".repeat(120));
+        fs::write(base.join(&long_name), "long").unwrap();
+        let paths = vec![
+            base.join("lib/libfoo.so"),
+            base.join(&long_name),
+            base.join("info/index.json"),
+        ];
+        let timestamp = Utc::now();
+
+        let streamed = tmp.path().join("streamed.conda");
+        write_conda_package(
+            fs::File::create(&streamed).unwrap(),
+            &base,
+            &paths,
+            CompressionLevel::Default,
+            Some(1),
+            "foo-1.0-0",
+            Some(&timestamp),
+            &indicatif::ProgressBar::hidden(),
+        )
+        .unwrap();
+
+        let reference = tmp.path().join("reference.conda");
+        rattler_package_streaming::write::write_conda_package(
+            fs::File::create(&reference).unwrap(),
+            &base,
+            &paths,
+            CompressionLevel::Default,
+            Some(1),
+            "foo-1.0-0",
+            Some(&timestamp),
+            None,
+        )
+        .unwrap();
+
+        let archives = inner_archives(&streamed);
+        assert_eq!(
+            archives.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            [
+                "metadata.json",
+                "pkg-foo-1.0-0.tar.zst",
+                "info-foo-1.0-0.tar.zst"
+            ]
+        );
+        assert_eq!(archives, inner_archives(&reference));
+    }
+}
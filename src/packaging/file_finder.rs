@@ -11,7 +11,10 @@ use walkdir::WalkDir;
 
 use crate::metadata::Output;
 
-use super::{PackagingError, file_mapper, normalize_path_for_comparison};
+use super::{
+    PackagingError, file_mapper, normalize_path_for_comparison,
+    provenance::{FileOrigin, FileProvenance, Modification},
+};
 use rattler_build_recipe::stage1::GlobVec;
 
 /// A wrapper around PathBuf that implements case-insensitive hashing and equality
@@ -53,6 +56,8 @@ pub struct Files {
     pub new_files: HashSet<PathBuf>,
     /// The files that were present in the original conda environment
     pub old_files: HashSet<PathBuf>,
+    /// The files that were restored unchanged from the staging cache the output inherits from
+    pub staged_files: HashSet<PathBuf>,
     /// The prefix that we are dealing with
     pub prefix: PathBuf,
 }
@@ -69,6 +74,8 @@ pub struct TempFiles {
     pub encoded_prefix: PathBuf,
     /// The content type of the files
     content_type_map: HashMap<PathBuf, Option<ContentType>>,
+    /// Where the files came from and which post-processing steps modified them
+    provenance: HashMap<PathBuf, FileProvenance>,
 }
 
 /// Determine the content type of a path by reading the first 1024 bytes of the file
@@ -154,6 +161,7 @@ impl Files {
             return Ok(Files {
                 new_files: HashSet::new(),
                 old_files: HashSet::new(),
+                staged_files: HashSet::new(),
                 prefix: prefix.to_owned(),
             });
         }
@@ -216,23 +224,47 @@ impl Files {
         Ok(Files {
             new_files: difference,
             old_files: previous_files,
+            staged_files: HashSet::new(),
             prefix: prefix.to_owned(),
         })
     }
 
-    /// Copy the new files to a temporary directory and return the temporary directory and the files that were copied.
-    pub fn to_temp_folder(&self, output: &Output) -> Result<TempFiles, PackagingError> {
-        let temp_dir = TempDir::with_prefix(output.name().as_normalized())?;
+    /// Link (or copy) the new files to a temporary directory and return the temporary directory and the files that were
+    /// linked. The temporary directory is created in the build directory, so that it is on the same filesystem as the
+    /// prefix and the files can be hard linked. Set `allow_hard_links` to false if external tools
+    /// may modify any file in the temporary directory, to copy all files instead.
+    pub fn to_temp_folder(
+        &self,
+        output: &Output,
+        allow_hard_links: bool,
+    ) -> Result<TempFiles, PackagingError> {
+        let build_dir = &output.build_configuration.directories.build_dir;
+        let temp_dir = if build_dir.is_dir() {
+            TempDir::with_prefix_in(output.name().as_normalized(), build_dir)?
+        } else {
+            TempDir::with_prefix(output.name().as_normalized())?
+        };
         let mut files = HashSet::new();
         let mut content_type_map = HashMap::new();
+        let mut provenance = HashMap::new();
         for f in &self.new_files {
             // temporary measure to remove pyc files that are not supposed to be there
             if file_mapper::filter_pyc(f, &self.old_files) {
                 continue;
             }
 
-            if let Some(dest_file) = output.write_to_dest(f, &self.prefix, temp_dir.path())? {
+            if let Some(dest_file) =
+                output.write_to_dest(f, &self.prefix, temp_dir.path(), allow_hard_links)?
+            {
                 content_type_map.insert(dest_file.clone(), content_type(f)?);
+                let origin = if self.old_files.contains(f) {
+                    FileOrigin::HostEnvironment
+                } else if self.staged_files.contains(f) {
+                    FileOrigin::StagingCache
+                } else {
+                    FileOrigin::BuildScript
+                };
+                provenance.insert(dest_file.clone(), FileProvenance::new(origin));
                 files.insert(dest_file);
             }
         }
@@ -242,6 +274,7 @@ impl Files {
             temp_dir,
             encoded_prefix: self.prefix.clone(),
             content_type_map,
+            provenance,
         })
     }
 }
@@ -259,6 +292,50 @@ impl TempFiles {
         }
     }
 
+    /// Add files that were created by rattler-build itself and record where they came from
+    pub fn add_files_with_origin<I>(&mut self, files: I, origin: FileOrigin)
+    where
+        I: IntoIterator<Item = PathBuf>,
+    {
+        let files: Vec<PathBuf> = files.into_iter().collect();
+        for f in &files {
+            self.provenance
+                .insert(f.clone(), FileProvenance::new(origin));
+        }
+        self.add_files(files);
+    }
+
+    /// Remove files that no longer exist in the temporary directory from the TempFiles struct
+    pub fn remove_files<'a, I>(&mut self, files: I)
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        for f in files {
+            self.files.remove(f);
+            self.content_type_map.remove(f);
+            self.provenance.remove(f);
+        }
+    }
+
+    /// Record that a post-processing step modified the given files
+    pub fn record_modification<'a, I>(&mut self, files: I, modification: Modification)
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        for f in files {
+            if let Some(provenance) = self.provenance.get_mut(f)
+                && !provenance.modified_by.contains(&modification)
+            {
+                provenance.modified_by.push(modification);
+            }
+        }
+    }
+
+    /// Return the provenance of a file in the temporary directory, if known
+    pub fn provenance(&self, path: &Path) -> Option<&FileProvenance> {
+        self.provenance.get(path)
+    }
+
     /// Return the content type map
     pub const fn content_type_map(&self) -> &HashMap<PathBuf, Option<ContentType>> {
         &self.content_type_map
@@ -2,6 +2,7 @@
 
 use crate::metadata::Output;
 use fs_err as fs;
+use rattler_build_recipe::stage1::build::SymlinkBehavior;
 use std::{
     collections::HashSet,
     path::{Component, Path, PathBuf},
@@ -47,12 +48,13 @@ pub fn filter_pyc(path: &Path, old_files: &HashSet<PathBuf>) -> bool {
 /// Filter certain files to prevent them from being packaged:
 ///
 /// - .pyo files are considered "harmful" (optimized python files)
-/// - .la files are not needed at runtime and are unnecessary bloat
+/// - .la files are not needed at runtime and are unnecessary bloat (unless
+///   `build.autotools_cleanup.remove_la_files` is disabled)
 /// - .DS_Store files are not needed and macOS specific
 /// - share/info/dir is skipped to avoid multiple packages writing to the same index file
 /// - conda-meta directories are skipped
 /// - CACHEDIR.TAG files are skipped
-pub fn filter_file(relative_path: &Path) -> bool {
+pub fn filter_file(relative_path: &Path, remove_la_files: bool) -> bool {
     let ext = relative_path.extension().unwrap_or_default();
 
     // skip the share/info/dir file because multiple packages would write
@@ -67,7 +69,7 @@ pub fn filter_file(relative_path: &Path) -> bool {
     }
 
     // we skip `.la` files because conda-build does it - la files are not needed at runtime
-    if ext == "la" {
+    if ext == "la" && remove_la_files {
         return true;
     }
 
@@ -92,18 +94,31 @@ impl Output {
     ///   `Scripts` is replaced with `python-scripts` (on Windows only). All other files are included
     ///   as-is.
     /// * Absolute symlinks are made relative so that they are easily relocatable.
+    /// * Symlinks are kept, replaced by the file they point to or rejected according to
+    ///   `build.symlinks`.
+    /// * Regular files are hard linked if `allow_hard_links` is set and no post-processing step
+    ///   rewrites them (see [`may_be_rewritten`]), otherwise they are copied.
     pub fn write_to_dest(
         &self,
         path: &Path,
         prefix: &Path,
         dest_folder: &Path,
+        allow_hard_links: bool,
     ) -> Result<Option<PathBuf>, PackagingError> {
         let target_platform = &self.build_configuration.target_platform;
-        let entry_points = &self.recipe.build().python.entry_points;
+        let python = &self.recipe.build().python;
+        let entry_points = python
+            .entry_points
+            .iter()
+            .chain(python.gui_entry_points.iter())
+            .collect::<Vec<_>>();
 
         let path_rel = path.strip_prefix(prefix)?;
 
-        if filter_file(path_rel) {
+        if filter_file(
+            path_rel,
+            self.recipe.build().autotools_cleanup.remove_la_files,
+        ) {
             return Ok(None);
         }
 
@@ -215,6 +230,34 @@ impl Output {
 
         let metadata = fs::symlink_metadata(path)?;
 
+        let symlink_behavior = if metadata.file_type().is_symlink() {
+            Some(self.recipe.build().symlinks.behavior_for(path_rel))
+        } else {
+            None
+        };
+
+        match symlink_behavior {
+            Some(SymlinkBehavior::Error) => {
+                return Err(PackagingError::SymlinkNotAllowed(path_rel.to_path_buf()));
+            }
+            Some(SymlinkBehavior::Dereference) => {
+                // `fs::metadata` follows the link, so this fails for dangling symlinks
+                let target_metadata = fs::metadata(path).map_err(|_| {
+                    PackagingError::CannotDereferenceSymlink(path_rel.to_path_buf())
+                })?;
+                if target_metadata.is_file() {
+                    tracing::trace!("Copying target of symlink {:?} to {:?}", path, dest_path);
+                    fs::copy(path, &dest_path)?;
+                    return Ok(Some(dest_path));
+                }
+                tracing::warn!(
+                    "Cannot dereference symlink to directory {:?}, keeping it as a symlink",
+                    path_rel
+                );
+            }
+            _ => {}
+        }
+
         // Handle symlinks: make absolute symlinks relative and copy the link
         if metadata.file_type().is_symlink() {
             if target_platform.is_windows() {
@@ -272,12 +315,92 @@ impl Output {
         } else if metadata.is_dir() {
             // skip directories for now
             Ok(None)
+        } else if allow_hard_links && !self.may_be_rewritten(path, &dest_path, dest_folder)? {
+            tracing::trace!("Linking file {:?} to {:?}", path, dest_path);
+            link_or_copy(path, &dest_path, &metadata)?;
+            Ok(Some(dest_path))
         } else {
             tracing::trace!("Copying file {:?} to {:?}", path, dest_path);
             fs::copy(path, &dest_path)?;
             Ok(Some(dest_path))
         }
     }
+
+    /// Returns true if a post-processing step may modify the file in place (relinking, stripping,
+    /// code signing, shebang and config file rewrites or `build.post_process`). A hard link to
+    /// such a file would change the file in the prefix as well.
+    fn may_be_rewritten(
+        &self,
+        path: &Path,
+        dest_path: &Path,
+        dest_folder: &Path,
+    ) -> std::io::Result<bool> {
+        let rel_dest = dest_path.strip_prefix(dest_folder).unwrap_or(dest_path);
+        let file_name = rel_dest.file_name().unwrap_or_default().to_string_lossy();
+        if rel_dest.extension().is_some_and(|ext| ext == "la")
+            || file_name.ends_with(".pc")
+            || file_name.ends_with("-config")
+            || file_name == "INSTALLER"
+        {
+            return Ok(true);
+        }
+
+        if self
+            .recipe
+            .build()
+            .post_process
+            .iter()
+            .any(|step| step.files.is_match(dest_path) || step.files.is_match(rel_dest))
+        {
+            return Ok(true);
+        }
+
+        is_script_or_binary(path)
+    }
+}
+
+/// Returns true if the file is executable, a script or an object file (ELF, Mach-O, PE). These
+/// are relinked, stripped, signed or have their shebang rewritten during post-processing.
+fn is_script_or_binary(path: &Path) -> std::io::Result<bool> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if fs::metadata(path)?.permissions().mode() & 0o111 != 0 {
+            return Ok(true);
+        }
+    }
+
+    let mut magic = [0u8; 4];
+    let read = std::io::Read::read(&mut fs::File::open(path)?, &mut magic)?;
+    let magic = &magic[..read];
+    Ok(magic.starts_with(b"#!")
+        || magic.starts_with(b"MZ")
+        || matches!(
+            magic,
+            b"\x7fELF"
+                | [0xfe, 0xed, 0xfa, 0xce | 0xcf]
+                | [0xce | 0xcf, 0xfa, 0xed, 0xfe]
+                | [0xca, 0xfe, 0xba, 0xbe]
+        ))
+}
+
+/// Hard link a file from the prefix into the temporary directory, so that the content of the
+/// package is not written to disk twice. Only use this for files that no post-processing step
+/// modifies, as the link shares its content with the file in the prefix. Files with more than one
+/// link (e.g. files that are linked from the package cache or the work directory) are copied
+/// instead. If the file cannot be linked (e.g. because the temporary directory is on another
+/// filesystem), it is copied as well.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn link_or_copy(path: &Path, dest: &Path, metadata: &std::fs::Metadata) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.nlink() == 1 && fs::hard_link(path, dest).is_ok() {
+            return Ok(());
+        }
+    }
+    fs::copy(path, dest)?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -313,12 +436,18 @@ mod test {
         for (file, expected) in test_cases {
             let path = std::path::Path::new(file);
             assert_eq!(
-                super::filter_file(path),
+                super::filter_file(path, true),
                 expected,
                 "Failed for file: {}",
                 file
             );
         }
+
+        // libtool archives are kept if their removal is disabled
+        assert!(!super::filter_file(
+            std::path::Path::new("lib/libarchive.la"),
+            false
+        ));
     }
 
     #[test]
@@ -354,4 +483,74 @@ mod test {
             );
         }
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_or_copy() {
+        use fs_err as fs;
+        use std::os::unix::fs::MetadataExt;
+
+        use crate::packaging::file_mapper::link_or_copy;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let prefix = tmp.path().join("prefix");
+        let dest = tmp.path().join("dest");
+        fs::create_dir_all(&prefix).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+
+        // a file that only exists in the prefix is linked
+        let file = prefix.join("new.txt");
+        fs::write(&file, "new").unwrap();
+        link_or_copy(&file, &dest.join("new.txt"), &fs::metadata(&file).unwrap()).unwrap();
+        assert_eq!(
+            fs::metadata(dest.join("new.txt")).unwrap().ino(),
+            fs::metadata(&file).unwrap().ino()
+        );
+
+        // a file that is also linked from elsewhere is copied
+        let linked = prefix.join("linked.txt");
+        fs::write(tmp.path().join("cache.txt"), "cached").unwrap();
+        fs::hard_link(tmp.path().join("cache.txt"), &linked).unwrap();
+        link_or_copy(
+            &linked,
+            &dest.join("linked.txt"),
+            &fs::metadata(&linked).unwrap(),
+        )
+        .unwrap();
+        assert_ne!(
+            fs::metadata(dest.join("linked.txt")).unwrap().ino(),
+            fs::metadata(&linked).unwrap().ino()
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("linked.txt")).unwrap(),
+            "cached"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_script_or_binary() {
+        use fs_err as fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::packaging::file_mapper::is_script_or_binary;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = tmp.path().join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+
+        assert!(!is_script_or_binary(&write("data.txt", b"some data")).unwrap());
+        assert!(!is_script_or_binary(&write("empty", b"")).unwrap());
+        assert!(is_script_or_binary(&write("script.sh", b"#!/bin/sh\necho hi")).unwrap());
+        assert!(is_script_or_binary(&write("libfoo.so", b"\x7fELF\x02\x01")).unwrap());
+        assert!(is_script_or_binary(&write("libfoo.dylib", &[0xcf, 0xfa, 0xed, 0xfe, 0])).unwrap());
+        assert!(is_script_or_binary(&write("foo.dll", b"MZ\x90\x00")).unwrap());
+
+        let executable = write("tool", b"data");
+        fs::set_permissions(&executable, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(is_script_or_binary(&executable).unwrap());
+    }
 }
@@ -14,6 +14,7 @@ use fs_err as fs;
 use fs_err::File;
 use itertools::Itertools;
 use rattler_build_recipe::stage1::build::PrefixDetection;
+use rattler_build_source_cache::hash;
 use rattler_conda_types::{
     ChannelUrl, NoArchType, Platform,
     package::{
@@ -21,11 +22,11 @@ use rattler_conda_types::{
         PathsJson, PrefixPlaceholder, PythonEntryPoints, RunExportsJson,
     },
 };
-use rattler_digest::{compute_bytes_digest, compute_file_digest};
+use rattler_digest::compute_bytes_digest;
 use rayon::prelude::*;
 use url::Url;
 
-use super::{PackagingError, TempFiles};
+use super::{PackagingError, TempFiles, provenance::PathsProvenance};
 use crate::metadata::Output;
 use rattler_build_recipe::stage1::HashInput;
 
@@ -281,7 +282,7 @@ impl Output {
                 .map(|s| vec![s])
                 .unwrap_or_default(),
             license: recipe.about().license.as_ref().map(|l| l.to_string()),
-            license_family: recipe.about().license_family.clone(),
+            license_family: recipe.about().license_family_or_inferred(),
             summary: recipe.about().summary.clone(),
             description: recipe.about().description.clone(),
             doc_url: recipe
@@ -374,7 +375,7 @@ impl Output {
             platform,
             subdir: Some(self.build_configuration.target_platform.to_string()),
             license: recipe.about().license.as_ref().map(|l| l.to_string()),
-            license_family: recipe.about().license_family.clone(),
+            license_family: recipe.about().license_family_or_inferred(),
             timestamp: Some(self.build_configuration.timestamp.into()),
             depends: finalized_dependencies
                 .run
@@ -401,9 +402,29 @@ impl Output {
 
     /// This function creates a link.json file for the given output.
     pub fn link_json(&self) -> Result<LinkJson, PackagingError> {
-        let entry_points = &self.recipe.build().python.entry_points;
+        let python = &self.recipe.build().python;
+
+        // `link.json` has no notion of GUI scripts, so noarch installers create them as
+        // regular console entry points
+        if !python.gui_entry_points.is_empty() {
+            tracing::warn!(
+                "noarch: python packages cannot declare GUI entry points, {} will be installed as console entry points",
+                python
+                    .gui_entry_points
+                    .iter()
+                    .map(|ep| ep.command.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
         let noarch_links = PythonEntryPoints {
-            entry_points: entry_points.clone(),
+            entry_points: python
+                .entry_points
+                .iter()
+                .chain(python.gui_entry_points.iter())
+                .cloned()
+                .collect(),
         };
 
         let link_json = LinkJson {
@@ -418,7 +439,15 @@ impl Output {
     /// Paths should be given as absolute paths under the `path_prefix`
     /// directory. This function will also determine if the file is binary
     /// or text, and if it contains the prefix.
-    pub fn paths_json(&self, temp_files: &TempFiles) -> Result<PathsJson, PackagingError> {
+    ///
+    /// The files are hashed in parallel, starting with the largest files so that a
+    /// single large file does not end up being hashed last. The hashed bytes are
+    /// reported to `progress_bar`.
+    pub fn paths_json(
+        &self,
+        temp_files: &TempFiles,
+        progress_bar: &indicatif::ProgressBar,
+    ) -> Result<PathsJson, PackagingError> {
         let always_copy_files = &self.recipe.build().always_copy_files;
 
         let mut paths_json = PathsJson {
@@ -426,11 +455,26 @@ impl Output {
             paths_version: 1,
         };
 
-        let entries: Vec<Result<Option<PathsEntry>, PackagingError>> = temp_files
+        let mut files = temp_files
             .content_type_map()
+            .iter()
+            .map(|(p, content_type)| Ok((p, content_type, fs::symlink_metadata(p)?)))
+            .collect::<Result<Vec<_>, PackagingError>>()?;
+        files.sort_by_key(|(_, _, meta)| {
+            std::cmp::Reverse(if meta.is_file() { meta.len() } else { 0 })
+        });
+        progress_bar.set_length(
+            files
+                .iter()
+                .filter(|(_, _, meta)| meta.is_file())
+                .map(|(_, _, meta)| meta.len())
+                .sum(),
+        );
+
+        let entries: Vec<Result<Option<PathsEntry>, PackagingError>> = files
             .par_iter()
-            .map(|(p, content_type)| {
-                let meta = fs::symlink_metadata(p)?;
+            .map(|(p, content_type, meta)| {
+                let p = *p;
                 let relative_path = p.strip_prefix(temp_files.temp_dir.path())?.to_path_buf();
 
                 if relative_path.starts_with("info") {
@@ -498,7 +542,9 @@ impl Output {
                     let file_size = meta.len();
                     // Compute SHA256 for files - empty files get empty hash
                     let digest = if file_size > 0 {
-                        Some(compute_file_digest::<sha2::Sha256>(p)?)
+                        Some(hash::compute_file_digest::<sha2::Sha256>(p, &|bytes| {
+                            progress_bar.inc(bytes)
+                        })?)
                     } else {
                         Some(compute_bytes_digest::<sha2::Sha256>(&[]))
                     };
@@ -514,7 +560,7 @@ impl Output {
                 } else if meta.is_symlink() {
                     // For symlinks, compute hash of the target file content if it exists and is within package, otherwise empty digest
                     let digest = if is_symlink_to_file(p) {
-                        compute_file_digest::<sha2::Sha256>(p)?
+                        hash::compute_file_digest::<sha2::Sha256>(p, &|_| {})?
                     } else {
                         compute_bytes_digest::<sha2::Sha256>(&[])
                     };
@@ -547,17 +593,27 @@ impl Output {
     pub fn write_metadata(
         &self,
         temp_files: &TempFiles,
+        progress_bar: &indicatif::ProgressBar,
     ) -> Result<HashSet<PathBuf>, PackagingError> {
         let mut new_files = HashSet::new();
         let root_dir = temp_files.temp_dir.path();
         let info_folder = temp_files.temp_dir.path().join("info");
         fs::create_dir_all(&info_folder)?;
 
+        let paths_json = self.paths_json(temp_files, progress_bar)?;
         let paths_json_path = root_dir.join(PathsJson::package_path());
-        let paths_json = File::create(&paths_json_path)?;
-        serde_json::to_writer_pretty(paths_json, &self.paths_json(temp_files)?)?;
+        let paths_json_file = File::create(&paths_json_path)?;
+        serde_json::to_writer_pretty(paths_json_file, &paths_json)?;
         new_files.insert(paths_json_path);
 
+        let provenance_path = root_dir.join(PathsProvenance::package_path());
+        let provenance_file = File::create(&provenance_path)?;
+        serde_json::to_writer_pretty(
+            provenance_file,
+            &PathsProvenance::new(&paths_json, temp_files),
+        )?;
+        new_files.insert(provenance_path);
+
         let index_json_path = root_dir.join(IndexJson::package_path());
         let index_json = File::create(&index_json_path)?;
         serde_json::to_writer_pretty(index_json, &self.index_json()?)?;
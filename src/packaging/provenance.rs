@@ -0,0 +1,194 @@
+//! Per-file provenance of the packaged files, written to `info/paths_provenance.json`.
+//!
+//! `paths.json` already records the hash and size of every file. The provenance file records
+//! where each of these files came from (the build script, the host environment, a staging cache
+//! or one of the post-processing steps of rattler-build) and which post-processing steps
+//! modified it.
+
+use std::path::{Path, PathBuf};
+
+use rattler_conda_types::package::{PackageFile, PathsJson};
+use serde::{Deserialize, Serialize};
+
+use super::TempFiles;
+
+/// Where a packaged file came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOrigin {
+    /// The file was created in the host prefix by the build script
+    BuildScript,
+    /// The file was already part of the host environment and included through
+    /// `build.always_include_files`
+    HostEnvironment,
+    /// The file was created by the Python post-processing (`.pyc` files and entry points)
+    PythonPostProcess,
+    /// The file was rendered from the `build.menu` section of the recipe
+    RecipeMenu,
+    /// The library was copied from the host environment through
+    /// `dynamic_linking.vendor_libraries`
+    VendoredLibrary,
+    /// The file was created by one of the configured post-processors
+    PostProcessor,
+    /// The file was restored unchanged from the staging cache that the output inherits from
+    StagingCache,
+}
+
+impl std::fmt::Display for FileOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            FileOrigin::BuildScript => "build script",
+            FileOrigin::HostEnvironment => "host environment",
+            FileOrigin::PythonPostProcess => "python post-process",
+            FileOrigin::RecipeMenu => "recipe menu",
+            FileOrigin::VendoredLibrary => "vendored library",
+            FileOrigin::PostProcessor => "post-processor",
+            FileOrigin::StagingCache => "staging cache",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A post-processing step that modified a packaged file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Modification {
+    /// The rpaths / install names of the binary were rewritten
+    Relink,
+    /// The file was changed by one of the `build.post_process` regex replacements
+    RegexReplacement,
+    /// Absolute paths in the pkg-config file or config script were made relative to `${prefix}`
+    RelativeConfig,
+    /// The shebang of the script was rewritten because the interpreter path contained spaces
+    Shebang,
+    /// The symbols of the binary were stripped (`build.strip`)
+    Strip,
+    /// The Mach-O binary was (re-)signed
+    Codesign,
+    /// The file was modified by one of the configured post-processors
+    PostProcessor,
+}
+
+impl std::fmt::Display for Modification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Modification::Relink => "relink",
+            Modification::RegexReplacement => "regex replacement",
+            Modification::RelativeConfig => "relative config",
+            Modification::Shebang => "shebang",
+            Modification::Strip => "strip",
+            Modification::Codesign => "codesign",
+            Modification::PostProcessor => "post-processor",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The provenance of a single file while it is being packaged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileProvenance {
+    /// Where the file came from
+    pub origin: FileOrigin,
+    /// The post-processing steps that modified the file, in order
+    pub modified_by: Vec<Modification>,
+}
+
+impl FileProvenance {
+    /// Create a new provenance record for a file that has not been modified yet
+    pub fn new(origin: FileOrigin) -> Self {
+        Self {
+            origin,
+            modified_by: Vec::new(),
+        }
+    }
+}
+
+/// A single entry of `info/paths_provenance.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvenanceEntry {
+    /// The path of the file relative to the package root (same as in `paths.json`)
+    #[serde(rename = "_path")]
+    pub relative_path: PathBuf,
+    /// Where the file came from, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<FileOrigin>,
+    /// The post-processing steps that modified the file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modified_by: Vec<Modification>,
+}
+
+/// The contents of `info/paths_provenance.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathsProvenance {
+    /// One entry per entry in `paths.json`, in the same order
+    pub paths: Vec<ProvenanceEntry>,
+    /// The version of the file format
+    pub provenance_version: u64,
+}
+
+impl PathsProvenance {
+    /// Collect the provenance for every entry in `paths_json` from the records kept in
+    /// `temp_files`.
+    pub fn new(paths_json: &PathsJson, temp_files: &TempFiles) -> Self {
+        let paths = paths_json
+            .paths
+            .iter()
+            .map(|entry| {
+                let provenance =
+                    temp_files.provenance(&temp_files.temp_dir.path().join(&entry.relative_path));
+                ProvenanceEntry {
+                    relative_path: entry.relative_path.clone(),
+                    origin: provenance.map(|p| p.origin),
+                    modified_by: provenance
+                        .map(|p| p.modified_by.clone())
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Self {
+            paths,
+            provenance_version: 1,
+        }
+    }
+}
+
+impl PackageFile for PathsProvenance {
+    fn package_path() -> &'static Path {
+        Path::new("info/paths_provenance.json")
+    }
+
+    fn from_str(str: &str) -> Result<Self, std::io::Error> {
+        serde_json::from_str(str).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_provenance_roundtrip() {
+        let provenance = PathsProvenance {
+            paths: vec![
+                ProvenanceEntry {
+                    relative_path: PathBuf::from("lib/libfoo.so"),
+                    origin: Some(FileOrigin::BuildScript),
+                    modified_by: vec![Modification::Relink],
+                },
+                ProvenanceEntry {
+                    relative_path: PathBuf::from("bin/foo"),
+                    origin: Some(FileOrigin::PythonPostProcess),
+                    modified_by: vec![],
+                },
+            ],
+            provenance_version: 1,
+        };
+
+        let json = serde_json::to_string_pretty(&provenance).unwrap();
+        insta::assert_snapshot!(json);
+
+        let parsed = <PathsProvenance as PackageFile>::from_str(&json).unwrap();
+        assert_eq!(parsed, provenance);
+    }
+}
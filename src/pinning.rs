@@ -0,0 +1,277 @@
+//! Checks that all inputs of a build are pinned (`--require-pinned`).
+//!
+//! In regulated environments, a package may only be built from inputs that cannot change
+//! without changing the recipe or the variant configuration. Before anything is built, the
+//! outputs are checked for:
+//!
+//! - url sources (and local files) without a SHA256 hash
+//! - git sources whose revision is a branch, a tag or `HEAD` instead of a commit
+//! - build and host dependencies without a version that the variant configuration does not pin
+//!
+//! All the violations are reported in a table before the build fails.
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use rattler_build_jinja::Variable;
+use rattler_build_recipe::stage1::{
+    Source,
+    requirements::Requirements,
+    source::{GitRev, GitSource},
+};
+use rattler_build_types::NormalizedKey;
+
+use crate::metadata::Output;
+
+/// Why an input is not pinned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinningViolationKind {
+    /// The source has no SHA256 hash
+    MissingHash,
+    /// The source only has an MD5 hash, which is not a cryptographic hash
+    Md5Only,
+    /// The git revision is a branch, a tag or `HEAD`
+    GitRevision(String),
+    /// The dependency has no version and no key in the variant configuration
+    UnpinnedDependency,
+}
+
+impl fmt::Display for PinningViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHash => write!(f, "no sha256 hash"),
+            Self::Md5Only => write!(f, "only an md5 hash (add a sha256 hash)"),
+            Self::GitRevision(rev) => write!(f, "`{rev}` is not a commit"),
+            Self::UnpinnedDependency => {
+                write!(f, "no version and no key in the variant configuration")
+            }
+        }
+    }
+}
+
+/// An input of an output that is not pinned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinningViolation {
+    /// The source or dependency, e.g. `https://example.com/foo.tar.gz` or `zlib`
+    pub input: String,
+    /// Why it is not pinned
+    pub kind: PinningViolationKind,
+}
+
+/// Whether a git revision is the full SHA-1 or SHA-256 hash of a commit
+fn is_commit_hash(rev: &str) -> bool {
+    matches!(rev.len(), 40 | 64) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether the git source is pinned to a commit, either with `rev` or with `expected_commit`
+fn is_pinned_git(git: &GitSource) -> Result<(), PinningViolationKind> {
+    if let GitRev::Commit(commit) = &git.rev
+        && is_commit_hash(commit)
+    {
+        return Ok(());
+    }
+    if git.expected_commit.as_deref().is_some_and(is_commit_hash) {
+        return Ok(());
+    }
+    Err(PinningViolationKind::GitRevision(git.rev.to_string()))
+}
+
+/// Check that every source has a SHA256 hash or is a git commit. Local directories are part of
+/// the recipe and are not checked.
+pub fn check_sources(sources: &[Source], recipe_dir: &Path) -> Vec<PinningViolation> {
+    sources
+        .iter()
+        .filter_map(|source| {
+            let (input, result) = match source {
+                Source::Git(git) => (git.url.to_string(), is_pinned_git(git)),
+                Source::Url(url) => {
+                    let input = url
+                        .url
+                        .first()
+                        .map(|url| url.to_string())
+                        .unwrap_or_default();
+                    let result = match (&url.sha256, &url.md5) {
+                        (Some(_), _) => Ok(()),
+                        (None, Some(_)) => Err(PinningViolationKind::Md5Only),
+                        (None, None) => Err(PinningViolationKind::MissingHash),
+                    };
+                    (input, result)
+                }
+                Source::Path(path) => {
+                    if recipe_dir.join(&path.path).is_dir() {
+                        return None;
+                    }
+                    let result = match (&path.sha256, &path.md5) {
+                        (Some(_), _) => Ok(()),
+                        (None, Some(_)) => Err(PinningViolationKind::Md5Only),
+                        (None, None) => Err(PinningViolationKind::MissingHash),
+                    };
+                    (path.path.display().to_string(), result)
+                }
+            };
+            result.err().map(|kind| PinningViolation { input, kind })
+        })
+        .collect()
+}
+
+/// Check that every build and host dependency without a version is pinned by the variant
+pub fn check_dependencies(
+    requirements: &Requirements,
+    variant: &BTreeMap<NormalizedKey, Variable>,
+) -> Vec<PinningViolation> {
+    let mut violations = Vec::new();
+    for name in requirements.free_specs() {
+        let input = name.as_normalized().to_string();
+        if variant.contains_key(&NormalizedKey::from(input.as_str()))
+            || violations
+                .iter()
+                .any(|violation: &PinningViolation| violation.input == input)
+        {
+            continue;
+        }
+        violations.push(PinningViolation {
+            input,
+            kind: PinningViolationKind::UnpinnedDependency,
+        });
+    }
+    violations
+}
+
+/// Check the sources and dependencies of the output and of its staging caches
+pub(crate) fn check_output(output: &Output) -> Vec<PinningViolation> {
+    let recipe_dir = &output.build_configuration.directories.recipe_dir;
+    let variant = &output.build_configuration.variant;
+    let mut violations = Vec::new();
+    for staging in &output.recipe.staging_caches {
+        let mut staging_variant = variant.clone();
+        staging_variant.extend(staging.used_variant.clone());
+        violations.extend(check_sources(&staging.source, recipe_dir));
+        violations.extend(check_dependencies(&staging.requirements, &staging_variant));
+    }
+    violations.extend(check_sources(output.recipe.source(), recipe_dir));
+    violations.extend(check_dependencies(output.recipe.requirements(), variant));
+    violations.dedup();
+    violations
+}
+
+/// Check that all inputs of the outputs are pinned, and report the violations as a table
+pub(crate) fn check_outputs(outputs: &[Output]) -> miette::Result<()> {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(["Output", "Input", "Violation"]);
+    let mut count = 0;
+    for output in outputs {
+        for violation in check_output(output) {
+            table.add_row([
+                output.identifier(),
+                violation.input,
+                violation.kind.to_string(),
+            ]);
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Ok(());
+    }
+
+    tracing::warn!("{count} input(s) of the build are not pinned");
+    tracing::info!("\n{table}");
+    Err(miette::miette!(
+        help = "Add a sha256 hash to the sources, use a commit as git revision and add the dependencies to the variant configuration, or build without `--require-pinned`",
+        "The inputs of the build are not pinned ({count} violation(s))"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_sources(yaml: &str) -> Vec<Source> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_check_sources() {
+        let sources = parse_sources(
+            r#"
+- url: https://example.com/pinned.tar.gz
+  sha256: 5a022ff4c1d1de87232b1c70bde50afbb98212fd246be4a867d8737173cf1f8f
+- url: https://example.com/md5.tar.gz
+  md5: 6f5902ac237024bdd0c176cb93063dc4
+- url: https://example.com/unpinned.tar.gz
+- git: https://github.com/example/pinned.git
+  rev: 0123456789abcdef0123456789abcdef01234567
+- git: https://github.com/example/tag.git
+  rev: refs/tags/v1.0
+- git: https://github.com/example/expected.git
+  rev: refs/tags/v1.0
+  expected_commit: 0123456789abcdef0123456789abcdef01234567
+- git: https://github.com/example/short.git
+  rev: 0123456
+- git: https://github.com/example/head.git
+"#,
+        );
+        let violations = check_sources(&sources, Path::new("."))
+            .into_iter()
+            .map(|violation| (violation.input, violation.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            vec![
+                (
+                    "https://example.com/md5.tar.gz".to_string(),
+                    PinningViolationKind::Md5Only
+                ),
+                (
+                    "https://example.com/unpinned.tar.gz".to_string(),
+                    PinningViolationKind::MissingHash
+                ),
+                (
+                    "https://github.com/example/tag.git".to_string(),
+                    PinningViolationKind::GitRevision("refs/tags/v1.0".to_string())
+                ),
+                (
+                    "https://github.com/example/short.git".to_string(),
+                    PinningViolationKind::GitRevision("0123456".to_string())
+                ),
+                (
+                    "https://github.com/example/head.git".to_string(),
+                    PinningViolationKind::GitRevision("HEAD".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_local_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(dir.path().join("file.txt"), "content").unwrap();
+        let sources = parse_sources("- path: .\n- path: file.txt\n");
+        assert_eq!(
+            check_sources(&sources, dir.path()),
+            vec![PinningViolation {
+                input: "file.txt".to_string(),
+                kind: PinningViolationKind::MissingHash,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_check_dependencies() {
+        let requirements: Requirements = serde_yaml::from_str(
+            "build:\n  - cmake\n  - make >=4\nhost:\n  - python\n  - zlib\n  - zlib\n",
+        )
+        .unwrap();
+        let variant = BTreeMap::from([(NormalizedKey::from("python"), Variable::from("3.12"))]);
+        assert_eq!(
+            check_dependencies(&requirements, &variant),
+            ["cmake", "zlib"]
+                .into_iter()
+                .map(|name| PinningViolation {
+                    input: name.to_string(),
+                    kind: PinningViolationKind::UnpinnedDependency,
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Rewrite the config files that autotools based builds install (`build.autotools_cleanup`).
+//!
+//! pkg-config files and `*-config` scripts define a `prefix` variable, but usually repeat the
+//! absolute prefix in all other paths. These are rewritten to `${prefix}` so that only the
+//! `prefix` line depends on the installation location. Libtool archives (`*.la`) are not
+//! copied into the package in the first place (see `remove_la_files`).
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use content_inspector::ContentType;
+use fs_err as fs;
+
+use crate::{metadata::Output, packaging::TempFiles};
+
+/// The kind of config file, determines which lines are rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFile {
+    /// A pkg-config `.pc` file
+    PkgConfig,
+    /// A `*-config` shell script
+    Script,
+}
+
+impl ConfigFile {
+    fn from_path(rel_path: &Path) -> Option<Self> {
+        let file_name = rel_path.file_name()?.to_string_lossy();
+        let parent = rel_path.parent()?;
+        if file_name.ends_with(".pc") && parent.ends_with("pkgconfig") {
+            Some(ConfigFile::PkgConfig)
+        } else if file_name.ends_with("-config") && parent.ends_with("bin") {
+            Some(ConfigFile::Script)
+        } else {
+            None
+        }
+    }
+}
+
+/// The value of a `prefix=` definition, if the line is one
+fn prefix_definition(line: &str) -> Option<&str> {
+    let value = line.trim_start().strip_prefix("prefix")?;
+    let value = value.trim_start().strip_prefix('=')?;
+    Some(
+        value
+            .trim()
+            .trim_matches('"')
+            .trim_matches('\'')
+            .trim_end_matches('/'),
+    )
+}
+
+/// Replace the absolute `prefix` in all lines after its definition with `${prefix}`.
+///
+/// Returns `None` if the file does not define the prefix or nothing needs to change.
+fn relativize(contents: &str, prefix: &str, kind: ConfigFile) -> Option<String> {
+    // on Windows, the prefix is often written with forward slashes
+    let candidates = [prefix.to_string(), prefix.replace('\\', "/")];
+
+    let mut lines = contents.split_inclusive('\n');
+    let mut result = String::with_capacity(contents.len());
+    let mut needle = None;
+    for line in lines.by_ref() {
+        result.push_str(line);
+        if let Some(value) = prefix_definition(line)
+            && let Some(candidate) = candidates.iter().find(|c| c.as_str() == value)
+        {
+            needle = Some(candidate);
+            break;
+        }
+    }
+    let needle = needle?;
+
+    let mut changed = false;
+    for line in lines {
+        // `${prefix}` is not expanded in single quoted shell strings
+        let skip = kind == ConfigFile::Script && line.contains('\'');
+        if !skip && line.contains(needle.as_str()) {
+            result.push_str(&line.replace(needle.as_str(), "${prefix}"));
+            changed = true;
+        } else {
+            result.push_str(line);
+        }
+    }
+
+    changed.then_some(result)
+}
+
+/// Rewrite pkg-config files and `*-config` scripts to use `${prefix}`. Returns the rewritten
+/// files.
+pub fn relativize_config_files(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<HashSet<PathBuf>, std::io::Error> {
+    if !output
+        .recipe
+        .build()
+        .autotools_cleanup
+        .relative_config_files
+    {
+        return Ok(HashSet::new());
+    }
+
+    let tmp_prefix = temp_files.temp_dir.path();
+    let prefix = temp_files.encoded_prefix.to_string_lossy();
+    let mut modified = HashSet::new();
+    for (path, content_type) in temp_files.content_type_map() {
+        if !matches!(
+            content_type,
+            Some(ContentType::UTF_8) | Some(ContentType::UTF_8_BOM)
+        ) || path.is_symlink()
+        {
+            continue;
+        }
+        let rel_path = path.strip_prefix(tmp_prefix).unwrap_or(path);
+        let Some(kind) = ConfigFile::from_path(rel_path) else {
+            continue;
+        };
+
+        let contents = fs::read_to_string(path)?;
+        if let Some(new_contents) = relativize(&contents, &prefix, kind) {
+            tracing::info!(
+                "Rewrote absolute paths in {} relative to ${{prefix}}",
+                rel_path.display()
+            );
+            fs::write(path, new_contents)?;
+            modified.insert(path.clone());
+        }
+    }
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relativize_pkg_config() {
+        let contents = "prefix=/opt/host_env\nexec_prefix=${prefix}\nlibdir=/opt/host_env/lib\nincludedir=/opt/host_env/include\n\nName: foo\nLibs: -L/opt/host_env/lib -lfoo\n";
+        let result = relativize(contents, "/opt/host_env", ConfigFile::PkgConfig).unwrap();
+        assert_eq!(
+            result,
+            "prefix=/opt/host_env\nexec_prefix=${prefix}\nlibdir=${prefix}/lib\nincludedir=${prefix}/include\n\nName: foo\nLibs: -L${prefix}/lib -lfoo\n"
+        );
+
+        // nothing to do
+        assert!(relativize(&result, "/opt/host_env", ConfigFile::PkgConfig).is_none());
+        // no prefix variable
+        assert!(
+            relativize(
+                "libdir=/opt/host_env/lib\n",
+                "/opt/host_env",
+                ConfigFile::PkgConfig
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_relativize_config_script() {
+        let contents = "#!/bin/sh\nprefix=\"/opt/host_env\"\nlibdir=\"/opt/host_env/lib\"\necho '/opt/host_env/include'\n";
+        let result = relativize(contents, "/opt/host_env", ConfigFile::Script).unwrap();
+        assert_eq!(
+            result,
+            "#!/bin/sh\nprefix=\"/opt/host_env\"\nlibdir=\"${prefix}/lib\"\necho '/opt/host_env/include'\n"
+        );
+    }
+
+    #[test]
+    fn test_config_file_kind() {
+        assert_eq!(
+            ConfigFile::from_path(Path::new("lib/pkgconfig/foo.pc")),
+            Some(ConfigFile::PkgConfig)
+        );
+        assert_eq!(
+            ConfigFile::from_path(Path::new("share/pkgconfig/foo.pc")),
+            Some(ConfigFile::PkgConfig)
+        );
+        assert_eq!(
+            ConfigFile::from_path(Path::new("bin/curl-config")),
+            Some(ConfigFile::Script)
+        );
+        assert_eq!(ConfigFile::from_path(Path::new("etc/foo-config")), None);
+        assert_eq!(ConfigFile::from_path(Path::new("lib/foo.pc")), None);
+    }
+}
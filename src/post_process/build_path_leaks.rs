@@ -0,0 +1,286 @@
+//! Find absolute paths into the build prefix or the work directory in the packaged files.
+//!
+//! Paths into the host prefix are replaced when the package is installed, but paths into the
+//! build prefix or the work directory are not: they only exist on the machine that built the
+//! package, and a package that references them is not relocatable.
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use memchr::memmem;
+use rattler_build_recipe::stage1::build::BuildPathLeaks;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{metadata::Output, packaging::TempFiles};
+
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum BuildPathLeakError {
+    #[error(
+        "{} packaged files contain paths into the build prefix or the work directory (see above)",
+        .0.len()
+    )]
+    LeaksFound(Vec<BuildPathLeak>),
+
+    #[error("failed to read file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A packaged file that contains a build path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildPathLeak {
+    /// The path of the file, relative to the prefix
+    pub file: PathBuf,
+    /// The name of the leaked directory (e.g. `build prefix`)
+    pub location: &'static str,
+    /// Byte offset of the first occurrence
+    pub offset: usize,
+    /// Number of occurrences in the file
+    pub count: usize,
+}
+
+impl std::fmt::Display for BuildPathLeak {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} contains a path into the {} at offset {:#x}",
+            self.file.display(),
+            self.location,
+            self.offset
+        )?;
+        if self.count > 1 {
+            write!(f, " ({} occurrences)", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// The string representations of `path` that can end up in files. On Windows, tools write the
+/// path with backslashes as well as with forward slashes.
+fn needles(path: &Path) -> Vec<String> {
+    let path = path.to_string_lossy().to_string();
+    let forward = path.replace('\\', "/");
+    if forward == path {
+        vec![path]
+    } else {
+        vec![path, forward]
+    }
+}
+
+/// The size of the buffer that files are scanned with
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Search the contents of `reader` for each of the `locations` and report the first occurrence of
+/// each. The contents are read in chunks of `buffer_size`; the end of the previous chunk is kept,
+/// so that paths that cross a chunk boundary are found as well.
+fn find_leaks_in(
+    file: &Path,
+    mut reader: impl Read,
+    locations: &[(&'static str, Vec<String>)],
+    buffer_size: usize,
+) -> std::io::Result<Vec<BuildPathLeak>> {
+    let finders = locations
+        .iter()
+        .map(|(_, needles)| {
+            needles
+                .iter()
+                .map(|needle| memmem::Finder::new(needle.as_bytes()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let overlap = finders
+        .iter()
+        .flatten()
+        .map(|finder| finder.needle().len())
+        .max()
+        .unwrap_or(1)
+        .saturating_sub(1);
+
+    // the first offset and the number of occurrences for each location
+    let mut found = vec![(None::<usize>, 0usize); locations.len()];
+    let mut buffer = vec![0u8; buffer_size.max(overlap + 1)];
+    // the number of bytes at the start of `buffer` that were kept from the previous chunk
+    let mut kept = 0;
+    // the offset of the start of `buffer` in the file
+    let mut start = 0;
+    loop {
+        let read = reader.read(&mut buffer[kept..])?;
+        if read == 0 {
+            break;
+        }
+        let filled = kept + read;
+        for (finders, (first, count)) in finders.iter().zip(found.iter_mut()) {
+            for finder in finders {
+                // occurrences that fit into the kept bytes were found in the previous chunk
+                for offset in finder
+                    .find_iter(&buffer[..filled])
+                    .filter(|offset| offset + finder.needle().len() > kept)
+                {
+                    let offset = start + offset;
+                    *first = Some(first.map_or(offset, |first| first.min(offset)));
+                    *count += 1;
+                }
+            }
+        }
+        let keep = overlap.min(filled);
+        buffer.copy_within(filled - keep..filled, 0);
+        start += filled - keep;
+        kept = keep;
+    }
+
+    Ok(locations
+        .iter()
+        .zip(found)
+        .filter_map(|((location, _), (first, count))| {
+            first.map(|offset| BuildPathLeak {
+                file: file.to_path_buf(),
+                location,
+                offset,
+                count,
+            })
+        })
+        .collect())
+}
+
+/// Scan all packaged files for paths into the build prefix or the work directory.
+///
+/// Depending on `prefix_detection.build_path_leaks`, the leaks are reported as warnings or fail
+/// the build.
+pub fn check_build_path_leaks(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<(), BuildPathLeakError> {
+    let behavior = output.recipe.build().prefix_detection.build_path_leaks;
+    if behavior.is_ignore() {
+        return Ok(());
+    }
+
+    let directories = &output.build_configuration.directories;
+    let mut locations = vec![("work directory", needles(&directories.work_dir))];
+    // with `merge_build_and_host_envs`, the build prefix is the host prefix
+    if directories.build_prefix != directories.host_prefix {
+        locations.push(("build prefix", needles(&directories.build_prefix)));
+    }
+
+    let tmp_prefix = temp_files.temp_dir.path();
+    let mut leaks = temp_files
+        .files
+        .par_iter()
+        .map(|path| {
+            let metadata = fs::symlink_metadata(path)?;
+            if metadata.is_symlink() || metadata.is_dir() {
+                return Ok(Vec::new());
+            }
+            let rel_path = path.strip_prefix(tmp_prefix).unwrap_or(path);
+            find_leaks_in(rel_path, fs::File::open(path)?, &locations, BUFFER_SIZE)
+        })
+        .collect::<Result<Vec<_>, std::io::Error>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    if leaks.is_empty() {
+        return Ok(());
+    }
+    leaks.sort_by(|a, b| a.file.cmp(&b.file));
+
+    for leak in &leaks {
+        match behavior {
+            BuildPathLeaks::Error => tracing::error!("{}", leak),
+            _ => {
+                tracing::warn!("{}", leak);
+                output.record_warning(&leak.to_string());
+            }
+        }
+    }
+
+    if behavior == BuildPathLeaks::Error {
+        return Err(BuildPathLeakError::LeaksFound(leaks));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_leaks_in() {
+        let locations = vec![
+            ("work directory", needles(Path::new("/bld/work"))),
+            ("build prefix", needles(Path::new("/bld/build_env"))),
+        ];
+        let contents = b"\0\0/bld/work/src/foo.c\0/bld/build_env/bin/cc /bld/work/include";
+
+        let leaks = find_leaks_in(
+            Path::new("lib/libfoo.so"),
+            &contents[..],
+            &locations,
+            BUFFER_SIZE,
+        )
+        .unwrap();
+        assert_eq!(
+            leaks,
+            vec![
+                BuildPathLeak {
+                    file: PathBuf::from("lib/libfoo.so"),
+                    location: "work directory",
+                    offset: 2,
+                    count: 2,
+                },
+                BuildPathLeak {
+                    file: PathBuf::from("lib/libfoo.so"),
+                    location: "build prefix",
+                    offset: 22,
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            leaks[0].to_string(),
+            "lib/libfoo.so contains a path into the work directory at offset 0x2 (2 occurrences)"
+        );
+
+        assert!(
+            find_leaks_in(
+                Path::new("bin/tool"),
+                &b"/bld/host_env/lib"[..],
+                &locations,
+                BUFFER_SIZE
+            )
+            .unwrap()
+            .is_empty()
+        );
+
+        // the result does not depend on the chunk size, including paths that cross a chunk
+        // boundary and occurrences in the kept bytes
+        for buffer_size in 1..contents.len() + 1 {
+            assert_eq!(
+                find_leaks_in(
+                    Path::new("lib/libfoo.so"),
+                    &contents[..],
+                    &locations,
+                    buffer_size
+                )
+                .unwrap(),
+                leaks,
+                "buffer size {buffer_size}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_needles_windows_paths() {
+        assert_eq!(
+            needles(Path::new("C:\\bld\\work")),
+            vec!["C:\\bld\\work".to_string(), "C:/bld/work".to_string()]
+        );
+        assert_eq!(
+            needles(Path::new("/bld/work")),
+            vec!["/bld/work".to_string()]
+        );
+    }
+}
@@ -412,6 +412,90 @@ fn add_osx_system_libs(
     Ok(())
 }
 
+/// DLLs that are part of every supported Windows installation. They are allowed by name so
+/// that they are recognized even if they cannot be found on the build machine (e.g. when
+/// cross-compiling).
+const WINDOWS_SYSTEM_DLLS: &[&str] = &[
+    "advapi32.dll",
+    "api-ms-win-*.dll",
+    "bcrypt.dll",
+    "cfgmgr32.dll",
+    "comctl32.dll",
+    "comdlg32.dll",
+    "crypt32.dll",
+    "d2d1.dll",
+    "d3d11.dll",
+    "d3d12.dll",
+    "d3d9.dll",
+    "dbghelp.dll",
+    "dnsapi.dll",
+    "dwmapi.dll",
+    "dwrite.dll",
+    "dxgi.dll",
+    "ext-ms-*.dll",
+    "gdi32.dll",
+    "gdiplus.dll",
+    "glu32.dll",
+    "hid.dll",
+    "imm32.dll",
+    "iphlpapi.dll",
+    "kernel32.dll",
+    "mpr.dll",
+    "msvcrt.dll",
+    "ncrypt.dll",
+    "netapi32.dll",
+    "normaliz.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "opengl32.dll",
+    "powrprof.dll",
+    "psapi.dll",
+    "rpcrt4.dll",
+    "secur32.dll",
+    "setupapi.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "ucrtbase.dll",
+    "user32.dll",
+    "userenv.dll",
+    "uxtheme.dll",
+    "version.dll",
+    "winhttp.dll",
+    "wininet.dll",
+    "winmm.dll",
+    "ws2_32.dll",
+    "wsock32.dll",
+    "wtsapi32.dll",
+];
+
+/// Visual C++ runtime DLLs. They are often installed in System32 of the build machine, but not
+/// on a clean Windows installation, so they have to come from a run dependency
+/// (e.g. `vc14_runtime`).
+const WINDOWS_REDISTRIBUTABLE_DLLS: &[&str] = &[
+    "concrt*.dll",
+    "mfc*.dll",
+    "msvcp*.dll",
+    "msvcr[0-9]*.dll",
+    "vcamp*.dll",
+    "vccorlib*.dll",
+    "vcomp*.dll",
+    "vcruntime*.dll",
+];
+
+/// Returns a glob set matching the Visual C++ runtime DLLs
+fn windows_redistributable_dlls() -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for dll in WINDOWS_REDISTRIBUTABLE_DLLS {
+        builder.add(
+            GlobBuilder::new(&format!("**/{dll}"))
+                .case_insensitive(true)
+                .build()?,
+        );
+    }
+    builder.build()
+}
+
 fn add_windows_system_libs(
     output: &Output,
     allow_builder: &mut GlobSetBuilder,
@@ -437,8 +521,17 @@ fn add_windows_system_libs(
     // Always add the system libraries
     all_allow.push("C:/Windows/System32/**/*.dll".to_string());
 
-    let expanded_allow = expand_dsolist(&all_allow);
-    let expanded_deny = expand_dsolist(&all_deny);
+    let mut expanded_allow = expand_dsolist(&all_allow);
+    let mut expanded_deny = expand_dsolist(&all_deny);
+
+    expanded_allow.extend(WINDOWS_SYSTEM_DLLS.iter().map(|dll| format!("**/{dll}")));
+    // The runtime DLLs are found in System32 on most build machines, but must not be treated
+    // as system libraries
+    expanded_deny.extend(
+        WINDOWS_REDISTRIBUTABLE_DLLS
+            .iter()
+            .map(|dll| format!("**/{dll}")),
+    );
 
     for pattern in &expanded_allow {
         allow_builder.add(GlobBuilder::new(pattern).case_insensitive(true).build()?);
@@ -615,7 +708,24 @@ pub fn perform_linking_checks(
 
     tracing::trace!("Package files: {package_files:#?}");
 
+    let windows_redistributable = if target_platform.is_windows() {
+        Some(windows_redistributable_dlls()?)
+    } else {
+        None
+    };
+    let overlinking_hint = |lib: &Path| -> &'static str {
+        match &windows_redistributable {
+            Some(redistributable) if redistributable.is_match(lib) => {
+                " (this DLL is part of the Visual C++ runtime, add the runtime package, e.g. \
+                `vc14_runtime`, to the run requirements or use the `compiler` / `stdlib` \
+                functions in the build requirements, which add it through run exports)"
+            }
+            _ => "",
+        }
+    };
+
     let mut linked_packages = Vec::new();
+    let mut overlinking_errors = Vec::new();
     for package in package_files.iter() {
         let mut link_info = PackageLinkInfo {
             file: package.file.clone(),
@@ -676,25 +786,30 @@ pub fn perform_linking_checks(
                     yet it is included in the allow list. Skipping...",
                     package.file
                 );
-            // Error on overlinking.
-            } else if dynamic_linking.overlinking_behavior == LinkingCheckBehavior::Error {
-                link_info.linked_packages.push(LinkedPackage {
-                    name: lib.to_path_buf(),
-                    link_origin: LinkOrigin::NotFound,
-                });
-                linked_packages.push(link_info);
-                linked_packages.iter().for_each(|linked_package| {
-                    tracing::info!("\n{linked_package}");
-                });
-
-                return Err(LinkingCheckError::Overlinking {
-                    package: lib.to_path_buf(),
-                    file: package.file.clone(),
-                });
             } else {
-                let warn_str = format!("Overlinking against {lib:?} for {:?}", package.file);
-                tracing::warn!(warn_str);
-                output.record_warning(&warn_str);
+                match dynamic_linking
+                    .overlinking_overrides
+                    .behavior_for(lib, dynamic_linking.overlinking_behavior)
+                {
+                    LinkingCheckBehavior::Error => {
+                        overlinking_errors.push((lib.to_path_buf(), package.file.clone()));
+                    }
+                    LinkingCheckBehavior::Warn => {
+                        let warn_str = format!(
+                            "Overlinking against {lib:?} for {:?}{}",
+                            package.file,
+                            overlinking_hint(lib)
+                        );
+                        tracing::warn!(warn_str);
+                        output.record_warning(&warn_str);
+                    }
+                    LinkingCheckBehavior::Ignore => {
+                        tracing::debug!(
+                            "Ignoring overlinking against {lib:?} for {:?}",
+                            package.file
+                        );
+                    }
+                }
             }
 
             link_info.linked_packages.push(LinkedPackage {
@@ -709,8 +824,22 @@ pub fn perform_linking_checks(
         tracing::info!("{linked_package}");
     });
 
+    if let Some((package, file)) = overlinking_errors.first() {
+        for (lib, file) in &overlinking_errors {
+            tracing::error!(
+                "Overlinking against {lib:?} for {file:?}{}",
+                overlinking_hint(lib)
+            );
+        }
+        return Err(LinkingCheckError::Overlinking {
+            package: package.clone(),
+            file: file.clone(),
+        });
+    }
+
     // If there are any host packages with DSOs that we didn't link against,
     // it is "overdepending".
+    let mut overdepending_errors = Vec::new();
     for host_package in host_dso_packages.iter() {
         if !package_files
             .iter()
@@ -723,15 +852,30 @@ pub fn perform_linking_checks(
             })
             .any(|libraries| libraries.contains(host_package))
         {
-            if dynamic_linking.overdepending_behavior == LinkingCheckBehavior::Error {
-                return Err(LinkingCheckError::Overdepending {
-                    package: PathBuf::from(host_package),
-                });
+            match dynamic_linking.overdepending_overrides.behavior_for(
+                Path::new(host_package),
+                dynamic_linking.overdepending_behavior,
+            ) {
+                LinkingCheckBehavior::Error => {
+                    tracing::error!("Overdepending against {host_package}");
+                    overdepending_errors.push(host_package);
+                }
+                LinkingCheckBehavior::Warn => {
+                    tracing::warn!("Overdepending against {host_package}");
+                    output.record_warning(&format!("Overdepending against {host_package}"));
+                }
+                LinkingCheckBehavior::Ignore => {
+                    tracing::debug!("Ignoring overdepending against {host_package}");
+                }
             }
-            tracing::warn!("Overdepending against {host_package}");
-            output.record_warning(&format!("Overdepending against {host_package}"));
         }
     }
+
+    if let Some(host_package) = overdepending_errors.first() {
+        return Err(LinkingCheckError::Overdepending {
+            package: PathBuf::from(host_package),
+        });
+    }
     Ok(())
 }
 
@@ -948,6 +1092,18 @@ mod tests {
         assert!(validate_dsolist_pattern("*.dll", file).is_err());
     }
 
+    #[test]
+    fn test_windows_redistributable_dlls() {
+        let redistributable = windows_redistributable_dlls().unwrap();
+        assert!(redistributable.is_match("VCRUNTIME140.dll"));
+        assert!(redistributable.is_match("C:/Windows/System32/vcruntime140_1.dll"));
+        assert!(redistributable.is_match("MSVCP140.dll"));
+        assert!(redistributable.is_match("msvcr100.dll"));
+        // the C runtime of the operating system is not redistributable
+        assert!(!redistributable.is_match("msvcrt.dll"));
+        assert!(!redistributable.is_match("KERNEL32.dll"));
+    }
+
     #[test]
     fn test_expand_dsolist_wildcard_passthrough() {
         let patterns = vec!["*.dll".to_string(), "**/foo.dll".to_string()];
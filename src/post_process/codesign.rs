@@ -0,0 +1,259 @@
+//! Sign the Mach-O binaries of macOS packages and optionally submit them for notarization.
+//!
+//! Relinking already ad-hoc signs every binary it modifies. This step runs after all other
+//! post-processing steps and makes sure that no Mach-O binary ends up in the package without a
+//! valid signature (e.g. because a regex replacement touched it or the build system did not sign
+//! it). With a signing identity, all binaries are (re-)signed with that identity, the hardened
+//! runtime and a secure timestamp, which is what notarization requires.
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use fs_err as fs;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    macos::link::Dylib,
+    metadata::Output,
+    packaging::TempFiles,
+    post_process::relink::Relinker,
+    system_tools::{SystemTools, Tool},
+    tool_configuration::Configuration,
+};
+
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum CodesignError {
+    #[error("failed to find `{0}`: {1}")]
+    ToolNotFound(Tool, which::Error),
+
+    #[error("failed to sign {path}:\n{stderr}")]
+    SigningFailed { path: PathBuf, stderr: String },
+
+    #[error("notarization requires a signing identity (`--codesign-identity`)")]
+    NotarizationWithoutIdentity,
+
+    #[error("notarization failed:\n{0}")]
+    NotarizationFailed(String),
+
+    #[error("failed to inspect binary: {0}")]
+    Relink(#[from] crate::post_process::relink::RelinkError),
+
+    #[error("failed to create the notarization archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("failed to read or write file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Returns true if `codesign --verify` accepts the signature of the binary
+fn has_valid_signature(codesign: &Path, path: &Path) -> Result<bool, CodesignError> {
+    let output = Command::new(codesign).arg("--verify").arg(path).output()?;
+    Ok(output.status.success())
+}
+
+fn sign(codesign: &Path, path: &Path, identity: Option<&str>) -> Result<(), CodesignError> {
+    let mut cmd = Command::new(codesign);
+    cmd.arg("-f");
+    match identity {
+        Some(identity) => {
+            cmd.args(["-s", identity, "--timestamp", "--options", "runtime"]);
+        }
+        None => {
+            cmd.args(["-s", "-"]);
+        }
+    }
+    // only the system codesign knows how to preserve entitlements
+    if codesign.starts_with("/usr/bin/") {
+        cmd.arg("--preserve-metadata=entitlements,requirements");
+    }
+    cmd.arg(path);
+
+    tracing::debug!("Running codesign: {:?}", cmd);
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(CodesignError::SigningFailed {
+            path: path.to_path_buf(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Write the binaries into a zip archive that can be submitted to the notary service
+fn create_notarization_archive(
+    binaries: &[&PathBuf],
+    prefix: &Path,
+    archive: &Path,
+) -> Result<(), CodesignError> {
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    let mut writer = ZipWriter::new(fs::File::create(archive)?);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(0o755);
+    for binary in binaries {
+        let name = binary.strip_prefix(prefix).unwrap_or(binary);
+        writer.start_file(name.to_string_lossy(), options)?;
+        writer.write_all(&fs::read(binary)?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Submit the binaries to the Apple notary service and wait for the result
+fn notarize(
+    binaries: &[&PathBuf],
+    prefix: &Path,
+    keychain_profile: &str,
+    system_tools: &SystemTools,
+) -> Result<(), CodesignError> {
+    let xcrun = system_tools
+        .find_tool(Tool::Xcrun)
+        .map_err(|e| CodesignError::ToolNotFound(Tool::Xcrun, e))?;
+
+    let tmp_dir = tempfile::tempdir()?;
+    let archive = tmp_dir.path().join("notarization.zip");
+    create_notarization_archive(binaries, prefix, &archive)?;
+
+    tracing::info!(
+        "Submitting {} binaries for notarization (this can take a while)",
+        binaries.len()
+    );
+    let output = Command::new(xcrun)
+        .args(["notarytool", "submit"])
+        .arg(&archive)
+        .args(["--keychain-profile", keychain_profile, "--wait"])
+        .output()?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // `notarytool` exits successfully for rejected submissions, so check the status as well
+    if !output.status.success() || !stdout.contains("status: Accepted") {
+        return Err(CodesignError::NotarizationFailed(format!(
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    tracing::info!("Notarization succeeded");
+    Ok(())
+}
+
+/// Sign all Mach-O binaries of a macOS package.
+///
+/// Without a signing identity, binaries that do not have a valid signature are signed ad-hoc.
+/// With a signing identity, all binaries are signed with it and, if a keychain profile is
+/// configured, submitted for notarization.
+///
+/// Returns the binaries that were signed.
+pub fn codesign(
+    temp_files: &TempFiles,
+    output: &Output,
+    tool_configuration: &Configuration,
+) -> Result<HashSet<PathBuf>, CodesignError> {
+    if !output.target_platform().is_osx() {
+        return Ok(HashSet::new());
+    }
+
+    let identity = tool_configuration.codesign_identity.as_deref();
+    let keychain_profile = tool_configuration.notarize_keychain_profile.as_deref();
+    if keychain_profile.is_some() && identity.is_none() {
+        return Err(CodesignError::NotarizationWithoutIdentity);
+    }
+
+    let mut binaries = Vec::new();
+    for (path, content_type) in temp_files.content_type_map() {
+        if content_type != &Some(content_inspector::ContentType::BINARY) {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_symlink() || metadata.is_dir() {
+            continue;
+        }
+        if Dylib::test_file(path)? {
+            binaries.push(path);
+        }
+    }
+
+    if binaries.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let system_tools = output.system_tools.with_build_prefix(output.build_prefix());
+    let codesign = match system_tools.find_tool(Tool::Codesign) {
+        Ok(codesign) => codesign,
+        Err(e) if identity.is_some() => return Err(CodesignError::ToolNotFound(Tool::Codesign, e)),
+        Err(e) => {
+            tracing::warn!(
+                "Cannot verify the signatures of Mach-O binaries, codesign was not found: {}",
+                e
+            );
+            return Ok(HashSet::new());
+        }
+    };
+
+    let signed = binaries
+        .par_iter()
+        .map(|path| {
+            if identity.is_none() && has_valid_signature(&codesign, path)? {
+                return Ok(None);
+            }
+            sign(&codesign, path, identity)?;
+            Ok(Some((*path).clone()))
+        })
+        .collect::<Result<Vec<_>, CodesignError>>()?
+        .into_iter()
+        .flatten()
+        .collect::<HashSet<_>>();
+
+    match identity {
+        Some(identity) => tracing::info!(
+            "Signed {} Mach-O binaries with identity {}",
+            signed.len(),
+            identity
+        ),
+        None if !signed.is_empty() => tracing::info!(
+            "Ad-hoc signed {} Mach-O binaries without a valid signature",
+            signed.len()
+        ),
+        None => {}
+    }
+
+    if let Some(keychain_profile) = keychain_profile {
+        notarize(
+            &binaries,
+            temp_files.temp_dir.path(),
+            keychain_profile,
+            &system_tools,
+        )?;
+    }
+
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_notarization_archive() {
+        let prefix = tempfile::tempdir().unwrap();
+        let binary = prefix.path().join("bin/tool");
+        fs::create_dir_all(binary.parent().unwrap()).unwrap();
+        fs::write(&binary, b"not really a binary").unwrap();
+
+        let archive = prefix.path().join("notarization.zip");
+        create_notarization_archive(&[&binary], prefix.path(), &archive).unwrap();
+
+        let mut zip = zip::ZipArchive::new(fs::File::open(&archive).unwrap()).unwrap();
+        assert_eq!(zip.len(), 1);
+        let entry = zip.by_index(0).unwrap();
+        assert_eq!(entry.name(), "bin/tool");
+        assert_eq!(entry.unix_mode().map(|m| m & 0o777), Some(0o755));
+    }
+}
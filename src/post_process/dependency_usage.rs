@@ -0,0 +1,584 @@
+//! A report of the build and host requirements that the package likely does not use.
+//!
+//! After post-processing, the evidence of usage is collected for every requirement that is listed
+//! explicitly in the recipe:
+//!
+//! - a binary of the package links a library of the requirement
+//! - a Python file or entry point of the package imports a module of the requirement
+//! - an executable of the requirement is named in the build script
+//! - the requirement is listed in `[build-system] requires` of `pyproject.toml`
+//! - the requirement is a compiler (or C standard library) for the host platform and the package
+//!   contains binaries
+//! - the requirement is also a run requirement
+//!
+//! The evidence of an installed package also counts for the requirements that have it as a run
+//! export (e.g. for `zlib` when `libz.so` of `libzlib` is linked). Requirements without any
+//! evidence are reported as likely unused.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    path::{Component, Path},
+    sync::LazyLock,
+};
+
+use rattler_conda_types::{
+    MatchSpec, PackageName, PackageNameMatcher, ParseStrictness, Platform, PrefixRecord,
+    RepoDataRecord,
+};
+use regex::Regex;
+
+use crate::{
+    metadata::Output,
+    packaging::TempFiles,
+    post_process::relink::{RelinkError, get_relinker},
+    render::resolved_dependencies::{DependencyInfo, ResolvedDependencies},
+};
+
+/// The evidence that a requirement is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Usage {
+    /// A binary of the package links a library of the requirement
+    Linked,
+    /// The package imports a Python module of the requirement
+    PythonImport,
+    /// An executable of the requirement is named in the build script
+    BuildScript,
+    /// The requirement is a build requirement in `pyproject.toml`
+    Pyproject,
+    /// The requirement is a compiler and the package contains binaries
+    Compiler,
+    /// The requirement is also a run requirement
+    RunRequirement,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Usage::Linked => "linked library",
+            Usage::PythonImport => "python import",
+            Usage::BuildScript => "build script",
+            Usage::Pyproject => "pyproject.toml",
+            Usage::Compiler => "compiled binaries",
+            Usage::RunRequirement => "run requirement",
+        })
+    }
+}
+
+/// The packages that are installed in an environment
+#[derive(Debug, Default)]
+struct Environment {
+    /// The package of every file (relative to the prefix)
+    files: HashMap<String, PackageName>,
+    /// The package of every executable (by name, without extension)
+    executables: HashMap<String, PackageName>,
+    /// The package of every top-level Python module
+    modules: HashMap<String, PackageName>,
+    /// The run exports of every package
+    run_exports: HashMap<PackageName, Vec<PackageName>>,
+}
+
+impl Environment {
+    fn from_prefix(prefix: &Path, resolved: &[RepoDataRecord]) -> Self {
+        let mut environment = Self::default();
+        for record in resolved {
+            let record = &record.package_record;
+            let run_exports = record
+                .run_exports
+                .iter()
+                .flat_map(|run_exports| {
+                    run_exports
+                        .weak
+                        .iter()
+                        .chain(&run_exports.strong)
+                        .chain(&run_exports.noarch)
+                })
+                .filter_map(|spec| match_spec_name(spec))
+                .collect();
+            environment
+                .run_exports
+                .insert(record.name.clone(), run_exports);
+        }
+        if !prefix.join("conda-meta").exists() {
+            return environment;
+        }
+        let records: Vec<PrefixRecord> = match PrefixRecord::collect_from_prefix(prefix) {
+            Ok(records) => records,
+            Err(e) => {
+                tracing::warn!("Could not read the packages of {}: {e}", prefix.display());
+                return environment;
+            }
+        };
+
+        for record in records {
+            let name = record.repodata_record.package_record.name.clone();
+            for file in &record.files {
+                if let Some(executable) = executable_name(file) {
+                    environment.executables.insert(executable, name.clone());
+                }
+                if let Some(module) = python_module(file) {
+                    environment.modules.insert(module, name.clone());
+                }
+                environment
+                    .files
+                    .insert(file.to_string_lossy().replace('\\', "/"), name.clone());
+            }
+        }
+        environment
+    }
+}
+
+/// The (exact) package name of a match spec
+fn match_spec_name(spec: &str) -> Option<PackageName> {
+    match MatchSpec::from_str(spec, ParseStrictness::Lenient)
+        .ok()?
+        .name
+    {
+        Some(PackageNameMatcher::Exact(name)) => Some(name),
+        _ => None,
+    }
+}
+
+/// The name of an executable in `bin/`, `Scripts/` or `Library/bin/` of a prefix
+fn executable_name(path: &Path) -> Option<String> {
+    let parent = path.parent()?.to_string_lossy().replace('\\', "/");
+    if !["bin", "Scripts", "Library/bin"].contains(&parent.as_str()) {
+        return None;
+    }
+    let name = path.file_name()?.to_str()?;
+    let name = [".exe", ".bat", ".cmd"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name);
+    Some(name.to_string())
+}
+
+/// The top-level Python module of a file in `site-packages`
+fn python_module(path: &Path) -> Option<String> {
+    let mut components = path.components();
+    components.find(|c| c.as_os_str() == "site-packages")?;
+    let Component::Normal(first) = components.next()? else {
+        return None;
+    };
+    let first = first.to_str()?;
+    if first.ends_with(".dist-info")
+        || first.ends_with(".egg-info")
+        || first.ends_with(".pth")
+        || first == "__pycache__"
+    {
+        return None;
+    }
+    // `foo/...`, `foo.py` or an extension module like `foo.cpython-312-x86_64-linux-gnu.so`
+    let module = first.split('.').next()?;
+    (!module.is_empty()).then(|| module.to_string())
+}
+
+static IMPORT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:from\s+([A-Za-z_]\w*)[\w.]*\s+import\b|import\s+([A-Za-z_][\w.]*(?:\s*,\s*[A-Za-z_][\w.]*)*))")
+        .expect("valid regex")
+});
+
+/// The top-level modules that are imported by Python source code
+fn python_imports(source: &str) -> BTreeSet<String> {
+    let mut modules = BTreeSet::new();
+    for captures in IMPORT_RE.captures_iter(source) {
+        if let Some(module) = captures.get(1) {
+            modules.insert(module.as_str().to_string());
+        }
+        if let Some(imports) = captures.get(2) {
+            for import in imports.as_str().split(',') {
+                if let Some(module) = import.trim().split('.').next() {
+                    modules.insert(module.to_string());
+                }
+            }
+        }
+    }
+    modules
+}
+
+/// The words of a build script (candidates for executable names)
+fn script_words(script: &str) -> HashSet<&str> {
+    script
+        .split(|c: char| !(c.is_ascii_alphanumeric() || "_.+-".contains(c)))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The (normalized) names of the build requirements in `pyproject.toml`
+fn pyproject_requirements(content: &str) -> Vec<String> {
+    let Ok(pyproject) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let Some(requires) = pyproject
+        .get("build-system")
+        .and_then(|build_system| build_system.get("requires"))
+        .and_then(|requires| requires.as_array())
+    else {
+        return Vec::new();
+    };
+    requires
+        .iter()
+        .filter_map(|requirement| requirement.as_str())
+        .map(|requirement| {
+            requirement
+                .trim()
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || "-_.".contains(*c))
+                .collect::<String>()
+                .to_lowercase()
+                .replace(['_', '.'], "-")
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// The requirements that are listed explicitly in the recipe (not run exports)
+fn requested_names(dependencies: &ResolvedDependencies) -> Vec<PackageName> {
+    let mut names = dependencies
+        .specs
+        .iter()
+        .filter(|dep| matches!(dep, DependencyInfo::Source(_) | DependencyInfo::Variant(_)))
+        .filter_map(|dep| match &dep.spec().name {
+            Some(PackageNameMatcher::Exact(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// The evidence of usage of the packages of an environment
+#[derive(Debug, Default)]
+struct Evidence(HashMap<PackageName, BTreeSet<Usage>>);
+
+impl Evidence {
+    fn add(&mut self, package: &PackageName, usage: Usage) {
+        self.0.entry(package.clone()).or_default().insert(usage);
+    }
+
+    /// The evidence of a requested package, including the evidence of its run exports
+    fn of(&self, package: &PackageName, environment: &Environment) -> BTreeSet<Usage> {
+        let mut usage = self.0.get(package).cloned().unwrap_or_default();
+        for run_export in environment.run_exports.get(package).into_iter().flatten() {
+            usage.extend(self.0.get(run_export).into_iter().flatten().copied());
+        }
+        usage
+    }
+}
+
+/// The facts about the package that was built
+#[derive(Debug, Default)]
+struct PackageFacts {
+    /// The files of the host prefix that binaries of the package link against
+    linked: HashSet<String>,
+    /// Whether the package contains binaries
+    has_binaries: bool,
+    /// The top-level Python modules that the package imports
+    imports: BTreeSet<String>,
+    /// The build script
+    script: String,
+    /// The build requirements of `pyproject.toml`
+    pyproject: Vec<String>,
+    /// The names of the run requirements
+    run: HashSet<PackageName>,
+}
+
+/// Collect the evidence of usage of the packages of `environment`
+fn collect_evidence(
+    environment: &Environment,
+    facts: &PackageFacts,
+    platform: Platform,
+) -> Evidence {
+    let mut evidence = Evidence::default();
+    for file in &facts.linked {
+        if let Some(package) = environment.files.get(file) {
+            evidence.add(package, Usage::Linked);
+        }
+    }
+    for module in &facts.imports {
+        if let Some(package) = environment.modules.get(module) {
+            evidence.add(package, Usage::PythonImport);
+        }
+    }
+    let words = script_words(&facts.script);
+    for (executable, package) in &environment.executables {
+        if words.contains(executable.as_str()) {
+            evidence.add(package, Usage::BuildScript);
+        }
+    }
+    for package in environment.run_exports.keys() {
+        let name = package.as_normalized();
+        if facts
+            .pyproject
+            .iter()
+            .any(|requirement| requirement == name)
+        {
+            evidence.add(package, Usage::Pyproject);
+        }
+        if facts.has_binaries && name.ends_with(&format!("_{platform}")) {
+            evidence.add(package, Usage::Compiler);
+        }
+        if facts.run.contains(package) {
+            evidence.add(package, Usage::RunRequirement);
+        }
+    }
+    evidence
+}
+
+/// Gather the facts about the package that was built from the files in the temporary directory
+/// and the work directory
+fn package_facts(tmp: &TempFiles, output: &Output) -> PackageFacts {
+    let configuration = &output.build_configuration;
+    let host_prefix = output.prefix();
+    let mut facts = PackageFacts::default();
+
+    for file in &tmp.files {
+        if file.extension().is_some_and(|ext| ext == "py") {
+            if let Ok(source) = fs_err::read_to_string(file) {
+                facts.imports.extend(python_imports(&source));
+            }
+            continue;
+        }
+        match get_relinker(configuration.target_platform, file) {
+            Ok(relinker) => {
+                facts.has_binaries = true;
+                for (lib, resolved) in relinker.resolve_libraries(tmp.temp_dir.path(), host_prefix)
+                {
+                    let lib = resolved.unwrap_or(lib);
+                    if let Ok(lib) = lib.strip_prefix(host_prefix) {
+                        facts
+                            .linked
+                            .insert(lib.to_string_lossy().replace('\\', "/"));
+                    }
+                }
+            }
+            Err(RelinkError::UnknownFileFormat) => {}
+            Err(e) => tracing::debug!("Could not read the libraries of {}: {e}", file.display()),
+        }
+    }
+
+    let python = &output.recipe.build().python;
+    for entry_point in &python.entry_points {
+        if let Some(module) = entry_point.module.split('.').next() {
+            facts.imports.insert(module.to_string());
+        }
+    }
+
+    let work_dir = &configuration.directories.work_dir;
+    let script = if configuration.build_platform.platform.is_windows() {
+        "conda_build.bat"
+    } else {
+        "conda_build.sh"
+    };
+    facts.script = fs_err::read_to_string(work_dir.join(script)).unwrap_or_default();
+    if let Ok(pyproject) = fs_err::read_to_string(work_dir.join("pyproject.toml")) {
+        facts.pyproject = pyproject_requirements(&pyproject);
+    }
+
+    if let Some(dependencies) = &output.finalized_dependencies {
+        facts.run = dependencies
+            .run
+            .depends
+            .iter()
+            .filter_map(|dep| match &dep.spec().name {
+                Some(PackageNameMatcher::Exact(name)) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+    }
+    facts
+}
+
+/// Report which of the explicit build and host requirements are used by the package, and warn
+/// about the ones that are likely unused
+pub fn report_dependency_usage(tmp: &TempFiles, output: &Output) {
+    let Some(dependencies) = &output.finalized_dependencies else {
+        return;
+    };
+    let configuration = &output.build_configuration;
+    let directories = &configuration.directories;
+    let facts = package_facts(tmp, output);
+
+    let mut report: BTreeMap<(&str, PackageName), BTreeSet<Usage>> = BTreeMap::new();
+    for (env, resolved, prefix) in [
+        ("build", &dependencies.build, &directories.build_prefix),
+        ("host", &dependencies.host, &directories.host_prefix),
+    ] {
+        let Some(resolved) = resolved else {
+            continue;
+        };
+        let environment = Environment::from_prefix(prefix, &resolved.resolved);
+        let evidence = collect_evidence(&environment, &facts, configuration.host_platform.platform);
+        for name in requested_names(resolved) {
+            let usage = evidence.of(&name, &environment);
+            report.insert((env, name), usage);
+        }
+    }
+    if report.is_empty() {
+        return;
+    }
+
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(vec!["Environment", "Requirement", "Used by"]);
+    for ((env, name), usage) in &report {
+        let used_by = if usage.is_empty() {
+            "likely unused".to_string()
+        } else {
+            usage
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        table.add_row(vec![
+            env.to_string(),
+            name.as_normalized().to_string(),
+            used_by,
+        ]);
+    }
+    tracing::info!("Dependency usage:\n{table}");
+
+    for ((env, name), usage) in &report {
+        if usage.is_empty() {
+            let warning = format!(
+                "The {env} requirement '{}' is likely unused",
+                name.as_normalized()
+            );
+            tracing::warn!("{warning}");
+            output.record_warning(&warning);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn name(name: &str) -> PackageName {
+        PackageName::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_python_imports() {
+        let source = r#"
+import os, sys
+import numpy.linalg as la
+from requests.adapters import HTTPAdapter
+from . import sibling
+from .local import thing
+    import yaml
+# import commented
+"#;
+        assert_eq!(
+            python_imports(source).into_iter().collect::<Vec<_>>(),
+            ["numpy", "os", "requests", "sys", "yaml"]
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            executable_name(Path::new("bin/cmake")).as_deref(),
+            Some("cmake")
+        );
+        assert_eq!(
+            executable_name(Path::new("Library/bin/ninja.exe")).as_deref(),
+            Some("ninja")
+        );
+        assert_eq!(executable_name(Path::new("lib/libz.so")), None);
+
+        for (path, module) in [
+            (
+                "lib/python3.12/site-packages/numpy/__init__.py",
+                Some("numpy"),
+            ),
+            ("lib/python3.12/site-packages/six.py", Some("six")),
+            (
+                "lib/python3.12/site-packages/_cffi_backend.cpython-312-x86_64-linux-gnu.so",
+                Some("_cffi_backend"),
+            ),
+            ("Lib/site-packages/yaml/__init__.py", Some("yaml")),
+            (
+                "lib/python3.12/site-packages/numpy-2.0.dist-info/METADATA",
+                None,
+            ),
+            ("lib/libz.so", None),
+        ] {
+            assert_eq!(python_module(Path::new(path)).as_deref(), module, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_pyproject_requirements() {
+        let pyproject = r#"
+[build-system]
+requires = ["setuptools>=61", "Cython ~= 3.0", "scikit_build_core[pyproject]"]
+build-backend = "setuptools.build_meta"
+"#;
+        assert_eq!(
+            pyproject_requirements(pyproject),
+            ["setuptools", "cython", "scikit-build-core"]
+        );
+        assert!(pyproject_requirements("not toml [").is_empty());
+    }
+
+    #[test]
+    fn test_collect_evidence() {
+        let mut environment = Environment::default();
+        for (file, package) in [
+            ("lib/libz.so.1", "libzlib"),
+            ("lib/python3.12/site-packages/numpy/__init__.py", "numpy"),
+        ] {
+            environment.files.insert(file.to_string(), name(package));
+        }
+        environment
+            .modules
+            .insert("numpy".to_string(), name("numpy"));
+        environment
+            .executables
+            .insert("cmake".to_string(), name("cmake"));
+        for (package, run_exports) in [
+            ("zlib", vec!["libzlib"]),
+            ("libzlib", vec![]),
+            ("numpy", vec![]),
+            ("cmake", vec![]),
+            ("gcc_linux-64", vec![]),
+            ("setuptools", vec![]),
+            ("python", vec![]),
+            ("openssl", vec![]),
+        ] {
+            environment
+                .run_exports
+                .insert(name(package), run_exports.into_iter().map(name).collect());
+        }
+
+        let facts = PackageFacts {
+            linked: HashSet::from(["lib/libz.so.1".to_string()]),
+            has_binaries: true,
+            imports: BTreeSet::from(["numpy".to_string()]),
+            script: "cmake -GNinja .. && make install".to_string(),
+            pyproject: vec!["setuptools".to_string()],
+            run: HashSet::from([name("python")]),
+        };
+        let evidence = collect_evidence(&environment, &facts, Platform::Linux64);
+        let usage = |package: &str| {
+            evidence
+                .of(&name(package), &environment)
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(usage("zlib"), [Usage::Linked]);
+        assert_eq!(usage("numpy"), [Usage::PythonImport]);
+        assert_eq!(usage("cmake"), [Usage::BuildScript]);
+        assert_eq!(usage("gcc_linux-64"), [Usage::Compiler]);
+        assert_eq!(usage("setuptools"), [Usage::Pyproject]);
+        assert_eq!(usage("python"), [Usage::RunRequirement]);
+        assert!(usage("openssl").is_empty());
+    }
+}
@@ -1,10 +1,133 @@
-//! Check that we can parse any `Menu/*.json` files as valid `menuinst` files.
+//! Render the `build.menu` section of the recipe to `Menu/<name>.json` and check that we can
+//! parse any `Menu/*.json` files as valid `menuinst` files.
 
-use std::ffi::OsStr;
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 
+use crate::metadata::Output;
 use crate::packaging::{PackagingError, TempFiles};
 use rattler_menuinst::schema::MenuInstSchema;
 
+/// The JSON schema draft that menuinst v2 documents declare in `$schema`
+const MENUINST_SCHEMA: &str = "https://json-schema.org/draft-07/schema";
+
+/// The id of the menuinst v2 schema
+const MENUINST_SCHEMA_ID: &str = "https://schemas.conda.io/menuinst-1.schema.json";
+
+/// Write the menuinst document declared in `build.menu` to `Menu/<package name>.json`.
+/// Returns the path of the new file, if any.
+pub fn write_menu_file(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<Option<PathBuf>, PackagingError> {
+    let Some(menu) = &output.recipe.build().menu else {
+        return Ok(None);
+    };
+
+    let mut document = serde_json::to_value(menu).map_err(|e| {
+        PackagingError::InvalidMenuInstSchema(PathBuf::from("recipe.yaml#build.menu"), e)
+    })?;
+    if let Some(object) = document.as_object_mut() {
+        object
+            .entry("$schema")
+            .or_insert_with(|| MENUINST_SCHEMA.into());
+        object
+            .entry("$id")
+            .or_insert_with(|| MENUINST_SCHEMA_ID.into());
+    }
+
+    let menu_dir = temp_files.temp_dir.path().join("Menu");
+    fs_err::create_dir_all(&menu_dir)?;
+
+    let path = menu_dir.join(format!("{}.json", output.name().as_normalized()));
+    if path.exists() {
+        tracing::warn!(
+            "Overwriting {} with the menu declared in the recipe",
+            path.display()
+        );
+    }
+
+    let file = fs_err::File::create(&path)?;
+    serde_json::to_writer_pretty(file, &document)
+        .map_err(|e| PackagingError::InvalidMenuInstSchema(path.clone(), e))?;
+
+    Ok(Some(path))
+}
+
+/// Check the parts of a menuinst document that the JSON schema constrains but that
+/// deserialization alone does not catch.
+fn validate_menu(menu: &MenuInstSchema, package_root: &Path) -> Result<(), String> {
+    if menu.menu_name.trim().is_empty() {
+        return Err("`menu_name` must not be empty".to_string());
+    }
+
+    if menu.menu_items.is_empty() {
+        return Err("`menu_items` must contain at least one item".to_string());
+    }
+
+    for (index, item) in menu.menu_items.iter().enumerate() {
+        let platforms = [
+            item.platforms.linux.as_ref().map(|p| &p.base),
+            item.platforms.osx.as_ref().map(|p| &p.base),
+            item.platforms.win.as_ref().map(|p| &p.base),
+        ];
+
+        if platforms.iter().all(Option::is_none) {
+            return Err(format!(
+                "menu item {} does not enable any platform (expected at least one of `linux`, `osx` or `win`)",
+                index
+            ));
+        }
+
+        let has_command = !item.command.command.is_empty()
+            || platforms
+                .iter()
+                .flatten()
+                .any(|base| base.command.as_ref().is_some_and(|c| !c.is_empty()));
+        if !has_command {
+            return Err(format!("menu item {} has an empty `command`", index));
+        }
+
+        let icons = std::iter::once(item.command.icon.as_ref())
+            .chain(platforms.iter().flatten().map(|base| base.icon.as_ref()))
+            .flatten();
+        for icon in icons {
+            if !menu_icon_exists(&icon.0, package_root) {
+                return Err(format!(
+                    "menu item {} references icon `{}` which is not part of the package",
+                    index, icon.0
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that an icon path relative to `{{ MENU_DIR }}` exists in the package. Icons that are
+/// not located in the menu directory can't be checked at build time and are accepted.
+fn menu_icon_exists(icon: &str, package_root: &Path) -> bool {
+    let Some(relative) = icon
+        .strip_prefix("{{ MENU_DIR }}")
+        .map(|rest| rest.trim_start_matches(['/', '\\']))
+    else {
+        return true;
+    };
+
+    let menu_dir = package_root.join("Menu");
+    if relative.contains("{{ ICON_EXT }}") {
+        ["png", "ico", "icns"].iter().any(|ext| {
+            menu_dir
+                .join(relative.replace("{{ ICON_EXT }}", ext))
+                .exists()
+        })
+    } else {
+        menu_dir.join(relative).exists()
+    }
+}
+
 /// Check that all `Menu/*.json` files are valid `menuinst` files.
 pub fn menuinst(temp_files: &TempFiles) -> Result<(), PackagingError> {
     // find all new files `Menu/*.json`
@@ -15,10 +138,74 @@ pub fn menuinst(temp_files: &TempFiles) -> Result<(), PackagingError> {
             && prefix_path.extension() == Some(OsStr::new("json"))
         {
             let content = fs_err::read_to_string(p)?;
-            let _menu: MenuInstSchema = serde_json::from_str(&content)
+            let menu: MenuInstSchema = serde_json::from_str(&content)
                 .map_err(|e| PackagingError::InvalidMenuInstSchema(p.to_path_buf(), e))?;
+            validate_menu(&menu, temp_files.temp_dir.path())
+                .map_err(|e| PackagingError::InvalidMenuInstDocument(p.to_path_buf(), e))?;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: serde_json::Value) -> MenuInstSchema {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_validate_menu() {
+        let package_root = tempfile::tempdir().unwrap();
+        fs_err::create_dir_all(package_root.path().join("Menu")).unwrap();
+        fs_err::write(package_root.path().join("Menu/app.png"), "").unwrap();
+
+        let valid = parse(serde_json::json!({
+            "$schema": MENUINST_SCHEMA,
+            "menu_name": "My App",
+            "menu_items": [{
+                "name": "My App",
+                "description": "Start my app",
+                "command": ["{{ PREFIX }}/bin/my-app"],
+                "icon": "{{ MENU_DIR }}/app.{{ ICON_EXT }}",
+                "platforms": { "linux": {}, "win": {} }
+            }]
+        }));
+        assert!(validate_menu(&valid, package_root.path()).is_ok());
+
+        let no_items = parse(serde_json::json!({
+            "$schema": MENUINST_SCHEMA,
+            "menu_name": "My App",
+            "menu_items": []
+        }));
+        assert!(validate_menu(&no_items, package_root.path()).is_err());
+
+        let missing_icon = parse(serde_json::json!({
+            "$schema": MENUINST_SCHEMA,
+            "menu_name": "My App",
+            "menu_items": [{
+                "name": "My App",
+                "description": "Start my app",
+                "command": ["{{ PREFIX }}/bin/my-app"],
+                "icon": "{{ MENU_DIR }}/missing.png",
+                "platforms": { "osx": {} }
+            }]
+        }));
+        let err = validate_menu(&missing_icon, package_root.path()).unwrap_err();
+        assert!(err.contains("missing.png"));
+
+        let no_platform = parse(serde_json::json!({
+            "$schema": MENUINST_SCHEMA,
+            "menu_name": "My App",
+            "menu_items": [{
+                "name": "My App",
+                "description": "Start my app",
+                "command": ["{{ PREFIX }}/bin/my-app"],
+                "platforms": {}
+            }]
+        }));
+        assert!(validate_menu(&no_platform, package_root.path()).is_err());
+    }
+}
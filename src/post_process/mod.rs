@@ -1,7 +1,15 @@
+pub mod autotools;
+pub mod build_path_leaks;
 pub mod checks;
+pub mod codesign;
+pub mod dependency_usage;
 pub mod menuinst;
 pub mod package_nature;
 pub mod path_checks;
+pub mod plugin;
 pub mod python;
 pub mod regex_replacements;
 pub mod relink;
+pub mod shebang;
+pub mod strip;
+pub mod vendor;
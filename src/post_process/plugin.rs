@@ -0,0 +1,341 @@
+//! External post-processors that run after the built-in post-processing steps, e.g. to sign
+//! files with an internal key or to inject additional metadata into the package.
+//!
+//! A post-processor is a command that is run (with `sh -c`, or `cmd /C` on Windows) with a JSON
+//! request on stdin. The request contains the directory with the files of the package, the list
+//! of files and the `index.json` and `about.json` that are going to be written. The command
+//! modifies the files in place (new files are added to the package, deleted files are removed
+//! from it) and can print a JSON response on stdout:
+//!
+//! ```json
+//! {
+//!   "modified": ["lib/libfoo.so"],
+//!   "extra_meta": {"signed-by": "release-key-2024"},
+//!   "warnings": ["libbar.so was not signed"]
+//! }
+//! ```
+//!
+//! All fields of the response are optional and an empty stdout is a valid response. A command
+//! that exits with a non-zero status fails the build.
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Write,
+    path::{Component, Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use rattler_conda_types::{
+    Platform,
+    package::{AboutJson, IndexJson},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::{
+    metadata::Output,
+    packaging::{FileOrigin, Modification, PackagingError, TempFiles},
+};
+
+/// The version of the JSON protocol between rattler-build and the post-processors
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// A command that is run on the files of every package after the built-in post-processing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PostProcessor {
+    /// The name that is shown in the logs (defaults to the command)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The command that is run with the JSON request on stdin
+    pub command: String,
+}
+
+impl PostProcessor {
+    /// A post-processor that runs the given command
+    pub fn command(command: String) -> Self {
+        Self {
+            name: None,
+            command,
+        }
+    }
+
+    /// The name that is shown in the logs
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.command)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[allow(missing_docs)]
+pub enum PostProcessorError {
+    #[error("failed to run the post-processor `{0}`: {1}")]
+    Spawn(String, std::io::Error),
+
+    #[error("the post-processor `{name}` exited with {status}:\n{stderr}")]
+    Failed {
+        name: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
+    #[error("the post-processor `{0}` printed an invalid response: {1}")]
+    InvalidResponse(String, String),
+}
+
+/// The request that is written to the stdin of a post-processor
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    protocol_version: u64,
+    /// The directory with the files of the package, which are modified in place
+    package_dir: &'a Path,
+    /// The files of the package relative to `package_dir`, with forward slashes
+    files: Vec<String>,
+    /// The `info/index.json` of the package
+    index: &'a IndexJson,
+    /// The `info/about.json` of the package
+    about: &'a AboutJson,
+    /// The directory of the recipe
+    recipe_dir: &'a Path,
+    /// The platform that the package is built for
+    target_platform: Platform,
+}
+
+/// The response that a post-processor prints on stdout
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Response {
+    /// The files that the post-processor modified, relative to `package_dir`
+    modified: Vec<PathBuf>,
+    /// Entries that are added to the `extra` section of `info/about.json`
+    extra_meta: BTreeMap<String, Value>,
+    /// Warnings that are shown in the build summary
+    warnings: Vec<String>,
+}
+
+impl Response {
+    fn parse(stdout: &[u8]) -> Result<Self, String> {
+        let stdout = String::from_utf8_lossy(stdout);
+        if stdout.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        let response: Self = serde_json::from_str(&stdout).map_err(|e| e.to_string())?;
+        if let Some(path) = response.modified.iter().find(|path| {
+            !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        }) {
+            return Err(format!(
+                "`{}` is not a path relative to the package directory",
+                path.display()
+            ));
+        }
+        Ok(response)
+    }
+}
+
+/// Run `command` with `payload` on stdin and return its stdout
+fn run_command(name: &str, command: &str, payload: Vec<u8>) -> Result<Vec<u8>, PostProcessorError> {
+    let mut command = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| PostProcessorError::Spawn(name.to_string(), e))?;
+
+    // write the request from a separate thread, so that a post-processor that prints a lot
+    // before reading stdin does not block
+    let stdin = child.stdin.take();
+    let writer = std::thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            // the post-processor does not have to read the request
+            let _ = stdin.write_all(&payload);
+        }
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| PostProcessorError::Spawn(name.to_string(), e))?;
+    let _ = writer.join();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for line in stderr.lines() {
+        tracing::info!("{}", line);
+    }
+    if !output.status.success() {
+        return Err(PostProcessorError::Failed {
+            name: name.to_string(),
+            status: output.status,
+            stderr: stderr.into_owned(),
+        });
+    }
+    Ok(output.stdout)
+}
+
+/// The files and symlinks in the package directory
+fn package_files(package_dir: &Path) -> Result<HashSet<PathBuf>, PackagingError> {
+    let mut files = HashSet::new();
+    for entry in WalkDir::new(package_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            files.insert(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Run the post-processors on the files of the package in order. Files that a post-processor
+/// creates or deletes are added to or removed from the package. Returns the entries that the
+/// post-processors added to the `extra` section of `info/about.json`.
+pub fn run_post_processors(
+    temp_files: &mut TempFiles,
+    output: &Output,
+    post_processors: &[PostProcessor],
+) -> Result<BTreeMap<String, Value>, PackagingError> {
+    let mut extra_meta = BTreeMap::new();
+    if post_processors.is_empty() {
+        return Ok(extra_meta);
+    }
+
+    let package_dir = temp_files.temp_dir.path().to_path_buf();
+    let index = output.index_json()?;
+    let mut about = output.about_json();
+
+    for post_processor in post_processors {
+        let name = post_processor.display_name();
+        tracing::info!("Running the post-processor `{}`", name);
+
+        let mut files = temp_files
+            .files
+            .iter()
+            .filter_map(|f| f.strip_prefix(&package_dir).ok())
+            .map(|f| f.to_string_lossy().replace('\\', "/"))
+            .collect::<Vec<_>>();
+        files.sort();
+        let request = Request {
+            protocol_version: PROTOCOL_VERSION,
+            package_dir: &package_dir,
+            files,
+            index: &index,
+            about: &about,
+            recipe_dir: &output.build_configuration.directories.recipe_dir,
+            target_platform: output.build_configuration.target_platform,
+        };
+        let payload = serde_json::to_vec(&request)?;
+
+        let stdout = run_command(name, &post_processor.command, payload)?;
+        let response = Response::parse(&stdout)
+            .map_err(|e| PostProcessorError::InvalidResponse(name.to_string(), e))?;
+
+        // pick up the files that the post-processor created or deleted
+        let current = package_files(&package_dir)?;
+        let removed = temp_files
+            .files
+            .difference(&current)
+            .cloned()
+            .collect::<Vec<_>>();
+        let added = current
+            .difference(&temp_files.files)
+            .cloned()
+            .collect::<Vec<_>>();
+        for file in &added {
+            tracing::info!("  added {}", file.strip_prefix(&package_dir)?.display());
+        }
+        for file in &removed {
+            tracing::info!("  removed {}", file.strip_prefix(&package_dir)?.display());
+        }
+        temp_files.remove_files(&removed);
+        temp_files.add_files_with_origin(added, FileOrigin::PostProcessor);
+
+        let modified = response
+            .modified
+            .iter()
+            .map(|path| package_dir.join(path))
+            .filter(|path| temp_files.files.contains(path))
+            .collect::<Vec<_>>();
+        temp_files.record_modification(&modified, Modification::PostProcessor);
+        // the content type of a modified file may have changed
+        temp_files.add_files(modified);
+
+        for warning in response.warnings {
+            let warning = format!("{name}: {warning}");
+            tracing::warn!("{}", warning);
+            output.record_warning(&warning);
+        }
+
+        about.extra.extend(response.extra_meta.clone());
+        extra_meta.extend(response.extra_meta);
+    }
+
+    Ok(extra_meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let response = Response::parse(b"").unwrap();
+        assert!(response.modified.is_empty());
+
+        let response = Response::parse(
+            br#"{"modified": ["lib/libfoo.so"], "extra_meta": {"signed": true}, "warnings": ["x"]}"#,
+        )
+        .unwrap();
+        assert_eq!(response.modified, vec![PathBuf::from("lib/libfoo.so")]);
+        assert_eq!(response.extra_meta["signed"], Value::Bool(true));
+        assert_eq!(response.warnings, vec!["x".to_string()]);
+
+        assert!(Response::parse(br#"{"modified": ["../outside"]}"#).is_err());
+        assert!(Response::parse(br#"{"modified": ["/etc/passwd"]}"#).is_err());
+        assert!(Response::parse(br#"{"unknown": 1}"#).is_err());
+        assert!(Response::parse(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_post_processor_from_toml() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Config {
+            post_processors: Vec<PostProcessor>,
+        }
+        let config: Config = toml::from_str(
+            r#"
+[[post-processors]]
+name = "internal signing"
+command = "sign-package"
+
+[[post-processors]]
+command = "python inject_metadata.py"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.post_processors[0].display_name(), "internal signing");
+        assert_eq!(
+            config.post_processors[1].display_name(),
+            "python inject_metadata.py"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_command() {
+        let stdout = run_command("test", "cat", b"{\"a\": 1}".to_vec()).unwrap();
+        assert_eq!(stdout, b"{\"a\": 1}");
+
+        let err = run_command("test", "echo oops >&2; exit 3", Vec::new()).unwrap_err();
+        assert!(
+            matches!(err, PostProcessorError::Failed { ref stderr, .. } if stderr.trim() == "oops")
+        );
+    }
+}
@@ -2,13 +2,15 @@
 //!
 //! This includes:
 //!   - Fixing up the shebangs in scripts
-//!   - Compiling `.py` files to `.pyc` files
+//!   - Compiling `.py` files to `.pyc` files (for `noarch: python` packages only on request)
 //!   - Replacing the contents of `.dist-info/INSTALLER` files with "conda"
+//!   - Checking the declared entry points against `.dist-info/entry_points.txt`
 use fs_err as fs;
 use rattler::install::{PythonInfo, get_windows_launcher, python_entry_point_template};
 use rattler_build_recipe::stage1::GlobVec;
+use rattler_build_recipe::stage1::build::{EntryPointCheck, EntryPointStyle, PythonBuild};
 use rattler_conda_types::Platform;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
@@ -25,6 +27,159 @@ pub fn python_bin(prefix: &Path, target_platform: &Platform) -> PathBuf {
     }
 }
 
+/// The path of the `.pyc` file in `__pycache__` for a `.py` file and a cache tag
+fn pyc_path(path: &Path, cache_tag: &str) -> PathBuf {
+    let mut cache_path = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf();
+    cache_path.push("__pycache__");
+    cache_path.push(format!(
+        "{}.{}.pyc",
+        path.file_stem().unwrap().to_string_lossy(),
+        cache_tag
+    ));
+    cache_path
+}
+
+/// Find the interpreter for a `major.minor` Python version in the build or host environment, or
+/// on the `PATH`
+fn find_versioned_python(output: &Output, version: &str) -> Option<PathBuf> {
+    let directories = &output.build_configuration.directories;
+    let name = format!("python{}", version);
+    for prefix in [&directories.build_prefix, &directories.host_prefix] {
+        let candidate = if output
+            .build_configuration
+            .build_platform
+            .platform
+            .is_windows()
+        {
+            prefix.join(format!("{}.exe", name))
+        } else {
+            prefix.join("bin").join(&name)
+        };
+        if candidate.exists() {
+            return Some(candidate);
+        }
+    }
+    which::which(&name).ok()
+}
+
+/// Pre-compile the `.py` files of a `noarch: python` package for every version in
+/// `python.noarch_pyc_versions`, and make sure the package contains exactly these `.pyc` files.
+///
+/// Without any versions, the package must not contain `.pyc` files at all (they are compiled
+/// when the package is installed).
+pub fn compile_noarch_pyc(
+    output: &Output,
+    paths: &HashSet<PathBuf>,
+    base_path: &Path,
+) -> Result<HashSet<PathBuf>, PackagingError> {
+    let python = &output.recipe.build().python;
+    let is_pyc = |p: &Path| p.extension().is_some_and(|e| e == "pyc");
+
+    if python.noarch_pyc_versions.is_empty() {
+        let mut unexpected = paths.iter().filter(|p| is_pyc(p)).collect::<Vec<_>>();
+        if unexpected.is_empty() {
+            return Ok(HashSet::new());
+        }
+        unexpected.sort();
+        return Err(PackagingError::PythonCompileError(format!(
+            "found .pyc files in a noarch: python package without `noarch_pyc_versions`:\n{}",
+            unexpected
+                .iter()
+                .map(|p| format!(" - {}", p.strip_prefix(base_path).unwrap_or(p).display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )));
+    }
+
+    let site_packages = base_path.join("site-packages");
+    let mut py_files = paths
+        .iter()
+        .filter(|p| p.starts_with(&site_packages) && p.extension().is_some_and(|e| e == "py"))
+        .filter(|p| {
+            !python
+                .skip_pyc_compilation
+                .is_match(p.strip_prefix(base_path).unwrap_or(p))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    py_files.sort();
+    if py_files.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let mut result = HashSet::new();
+    for version in &python.noarch_pyc_versions {
+        let interpreter = find_versioned_python(output, version).ok_or_else(|| {
+            PackagingError::PythonCompileError(format!(
+                "could not find `python{}` in the build or host environment (needed for `noarch_pyc_versions`)",
+                version
+            ))
+        })?;
+
+        let cache_tag = Command::new(&interpreter)
+            .args(["-c", "import sys; print(sys.implementation.cache_tag)"])
+            .output()?
+            .stdout;
+        let cache_tag = String::from_utf8_lossy(&cache_tag).trim().to_string();
+
+        tracing::info!(
+            "Compiling {} .py files to .pyc for Python {} ({})",
+            py_files.len(),
+            version,
+            cache_tag
+        );
+
+        // pass the files on stdin to avoid hitting the command line length limit
+        let mut child = Command::new(&interpreter)
+            .args([
+                "-Wi",
+                "-m",
+                "compileall",
+                "-q",
+                "--invalidation-mode",
+                "checked-hash",
+                "-i",
+                "-",
+            ])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            for f in &py_files {
+                writeln!(stdin, "{}", f.display())?;
+            }
+        }
+        let compile_output = child.wait_with_output()?;
+
+        let mut missing = Vec::new();
+        for f in &py_files {
+            let pyc_file = pyc_path(f, &cache_tag);
+            if pyc_file.exists() {
+                result.insert(pyc_file);
+            } else {
+                missing.push(format!(
+                    " - {}",
+                    f.strip_prefix(base_path).unwrap_or(f).display()
+                ));
+            }
+        }
+        if !missing.is_empty() {
+            return Err(PackagingError::PythonCompileError(format!(
+                "could not compile the following files for Python {} (add them to `skip_pyc_compilation` to skip them):\n{}\n{}",
+                version,
+                missing.join("\n"),
+                String::from_utf8_lossy(&compile_output.stdout)
+            )));
+        }
+    }
+
+    Ok(result)
+}
+
 /// Given a list of files and the path to a Python interpreter, we try to compile any `.py` files
 /// to `.pyc` files by invoking the Python interpreter.
 pub fn compile_pyc(
@@ -62,19 +217,7 @@ pub fn compile_pyc(
 
     let cache_tag = String::from_utf8_lossy(&cache_tag).trim().to_string();
 
-    let file_with_cache_tag = |path: &Path| {
-        let mut cache_path = path
-            .parent()
-            .unwrap_or_else(|| Path::new("."))
-            .to_path_buf();
-        cache_path.push("__pycache__");
-        cache_path.push(format!(
-            "{}.{}.pyc",
-            path.file_stem().unwrap().to_string_lossy(),
-            cache_tag
-        ));
-        cache_path
-    };
+    let file_with_cache_tag = |path: &Path| pyc_path(path, &cache_tag);
 
     // for each file that does not have a corresponding `.pyc` file we try to compile it
     // by invoking Python
@@ -176,6 +319,14 @@ pub fn python(temp_files: &TempFiles, output: &Output) -> Result<HashSet<PathBuf
         result.extend(create_entry_points(output, temp_files.temp_dir.path())?);
     }
 
+    if output.recipe.build().noarch.is_some_and(|n| n.is_python()) {
+        result.extend(compile_noarch_pyc(
+            output,
+            &temp_files.files,
+            temp_files.temp_dir.path(),
+        )?);
+    }
+
     let metadata_glob = globset::Glob::new("**/*.dist-info/METADATA")?.compile_matcher();
 
     if let Some(p) = temp_files.files.iter().find(|p| metadata_glob.is_match(p)) {
@@ -197,6 +348,8 @@ pub fn python(temp_files: &TempFiles, output: &Output) -> Result<HashSet<PathBuf
         }
     }
 
+    check_entry_points(temp_files, output)?;
+
     let glob = globset::Glob::new("**/*.dist-info/INSTALLER")?.compile_matcher();
     for p in temp_files.files.iter() {
         if glob.is_match(p) {
@@ -295,12 +448,23 @@ fn fix_shebang(
     Ok(())
 }
 
+/// Contents of the `.bat` wrapper used instead of the `.exe` launcher on Windows. GUI
+/// entry points are started through `pythonw.exe` so that no console window is opened.
+fn windows_batch_wrapper(command: &str, gui: bool) -> String {
+    if gui {
+        format!("@start \"\" \"%~dp0..\\pythonw.exe\" \"%~dp0{command}-script.pyw\" %*\r\n")
+    } else {
+        format!("@\"%~dp0..\\python.exe\" \"%~dp0{command}-script.py\" %*\r\n")
+    }
+}
+
 /// Create the python entry point script for the recipe. Overwrites any existing entry points.
 pub(crate) fn create_entry_points(
     output: &Output,
     tmp_dir_path: &Path,
 ) -> Result<Vec<PathBuf>, PackagingError> {
-    if output.recipe.build().python.entry_points.is_empty() {
+    let python = &output.recipe.build().python;
+    if python.entry_points.is_empty() && python.gui_entry_points.is_empty() {
         return Ok(Vec::new());
     }
 
@@ -322,7 +486,13 @@ pub(crate) fn create_entry_points(
                 ))
             })?;
 
-    for ep in &output.recipe.build().python.entry_points {
+    let entry_points = python
+        .entry_points
+        .iter()
+        .map(|ep| (ep, false))
+        .chain(python.gui_entry_points.iter().map(|ep| (ep, true)));
+
+    for (ep, gui) in entry_points {
         let script = python_entry_point_template(
             &output.prefix().to_string_lossy(),
             output.target_platform().is_windows(),
@@ -333,6 +503,21 @@ pub(crate) fn create_entry_points(
         if output.target_platform().is_windows() {
             fs::create_dir_all(tmp_dir_path.join("Scripts"))?;
 
+            // The bundled launcher is a console executable, so GUI entry points always get
+            // a wrapper that starts `pythonw.exe` instead
+            if gui || python.entry_point_style == EntryPointStyle::Script {
+                let extension = if gui { "pyw" } else { "py" };
+                let script_path =
+                    tmp_dir_path.join(format!("Scripts/{}-script.{}", ep.command, extension));
+                fs::write(&script_path, script.as_bytes())?;
+
+                let bat_path = tmp_dir_path.join(format!("Scripts/{}.bat", ep.command));
+                fs::write(&bat_path, windows_batch_wrapper(&ep.command, gui))?;
+
+                new_files.extend(vec![script_path, bat_path]);
+                continue;
+            }
+
             let script_path = tmp_dir_path.join(format!("Scripts/{}-script.py", ep.command));
             let mut file = fs::File::create(&script_path)?;
             file.write_all(script.as_bytes())?;
@@ -356,13 +541,11 @@ pub(crate) fn create_entry_points(
                 std::os::unix::fs::PermissionsExt::from_mode(0o775),
             )?;
 
-            if output.target_platform().is_osx()
-                && output.recipe.build().python.use_python_app_entrypoint
-            {
+            if output.target_platform().is_osx() && python.use_python_app_entrypoint {
                 fix_shebang(
                     &script_path,
                     output.prefix(),
-                    output.recipe.build().python.use_python_app_entrypoint,
+                    python.use_python_app_entrypoint,
                 )?;
             }
 
@@ -373,6 +556,134 @@ pub(crate) fn create_entry_points(
     Ok(new_files)
 }
 
+/// Parse the `[console_scripts]` and `[gui_scripts]` sections of an `entry_points.txt` file
+/// into `(section, command, "module:function")` triples.
+fn parse_entry_points_txt(content: &str) -> Vec<(String, String, String)> {
+    let mut result = Vec::new();
+    let mut section = String::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+
+        if section != "console_scripts" && section != "gui_scripts" {
+            continue;
+        }
+
+        if let Some((command, target)) = line.split_once('=') {
+            // drop any extras, e.g. `module:function [extra]`
+            let target = target.split('[').next().unwrap_or_default();
+            let target = target.split_whitespace().collect::<String>();
+            result.push((section.clone(), command.trim().to_string(), target));
+        }
+    }
+
+    result
+}
+
+/// Compare the entry points declared in the recipe against the ones found in the packaged
+/// `entry_points.txt` files and describe every mismatch.
+fn entry_point_mismatches(
+    python: &PythonBuild,
+    entry_points_txt: &[(String, String, String)],
+    noarch_python: bool,
+) -> Vec<String> {
+    let declared = [
+        ("console_scripts", "entry_points", &python.entry_points),
+        ("gui_scripts", "gui_entry_points", &python.gui_entry_points),
+    ];
+
+    let mut mismatches = Vec::new();
+    for (section, field, entry_points) in declared {
+        let installed = entry_points_txt
+            .iter()
+            .filter(|(s, _, _)| s == section)
+            .map(|(_, command, target)| (command.as_str(), target.as_str()))
+            .collect::<HashMap<_, _>>();
+
+        for ep in entry_points.iter() {
+            let target = format!("{}:{}", ep.module, ep.function);
+            match installed.get(ep.command.as_str()) {
+                Some(installed_target) if *installed_target == target => {}
+                Some(installed_target) => mismatches.push(format!(
+                    "`{}` points to `{}` in `build.python.{}` but to `{}` in [{}]",
+                    ep.command, target, field, installed_target, section
+                )),
+                None => mismatches.push(format!(
+                    "`{}` is declared in `build.python.{}` but not in [{}]",
+                    ep.command, field, section
+                )),
+            }
+        }
+
+        // For noarch packages the scripts generated by pip are removed or left with a
+        // hard-coded shebang, so undeclared entry points end up broken
+        if noarch_python {
+            let mut missing = installed
+                .keys()
+                .filter(|command| !entry_points.iter().any(|ep| ep.command == **command))
+                .collect::<Vec<_>>();
+            missing.sort();
+            for command in missing {
+                mismatches.push(format!(
+                    "`{}` is declared in [{}] but missing from `build.python.{}`",
+                    command, section, field
+                ));
+            }
+        }
+    }
+
+    mismatches
+}
+
+/// Validate the declared entry points against the `entry_points.txt` of the packaged
+/// dist-info folders, according to `build.python.entry_point_check`.
+fn check_entry_points(temp_files: &TempFiles, output: &Output) -> Result<(), PackagingError> {
+    let python = &output.recipe.build().python;
+    if python.entry_point_check == EntryPointCheck::Ignore {
+        return Ok(());
+    }
+
+    let glob = globset::Glob::new("**/*.dist-info/entry_points.txt")?.compile_matcher();
+    let mut entry_points_txt = Vec::new();
+    for p in temp_files.files.iter().filter(|p| glob.is_match(p)) {
+        entry_points_txt.extend(parse_entry_points_txt(&fs::read_to_string(p)?));
+    }
+
+    // Nothing to validate against (e.g. not a Python package, or no metadata shipped)
+    if entry_points_txt.is_empty() {
+        return Ok(());
+    }
+
+    let mismatches = entry_point_mismatches(
+        python,
+        &entry_points_txt,
+        output.is_python_version_independent(),
+    );
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+
+    if python.entry_point_check == EntryPointCheck::Error {
+        return Err(PackagingError::EntryPointMismatch(mismatches.join("\n")));
+    }
+
+    for mismatch in mismatches {
+        let warn_str = format!("Entry point mismatch: {}", mismatch);
+        tracing::warn!("{}", warn_str);
+        output.record_warning(&warn_str);
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -402,6 +713,14 @@ mod tests {
         assert_eq!(new_shebang, (false, "#!/some/path/to/ruby".to_string()));
     }
 
+    #[test]
+    fn test_pyc_path() {
+        assert_eq!(
+            pyc_path(Path::new("site-packages/foo/bar.py"), "cpython-312"),
+            PathBuf::from("site-packages/foo/__pycache__/bar.cpython-312.pyc")
+        );
+    }
+
     #[test]
     fn test_replace_shebang_in_file() {
         let tempdir = tempfile::tempdir().unwrap();
@@ -417,4 +736,57 @@ mod tests {
         fix_shebang(&dest, &PathBuf::from("/super/prefix"), true).unwrap();
         insta::assert_snapshot!(fs::read_to_string(&dest).unwrap());
     }
+
+    #[test]
+    fn test_windows_batch_wrapper() {
+        assert_eq!(
+            windows_batch_wrapper("mycli", false),
+            "@\"%~dp0..\\python.exe\" \"%~dp0mycli-script.py\" %*\r\n"
+        );
+        assert_eq!(
+            windows_batch_wrapper("mygui", true),
+            "@start \"\" \"%~dp0..\\pythonw.exe\" \"%~dp0mygui-script.pyw\" %*\r\n"
+        );
+    }
+
+    #[test]
+    fn test_entry_point_mismatches() {
+        let entry_points_txt = parse_entry_points_txt(
+            "[console_scripts]\n\
+             mycli = mypkg.cli:main\n\
+             other = mypkg.other:run [extra]\n\
+             \n\
+             [gui_scripts]\n\
+             mygui = mypkg.gui:main\n\
+             \n\
+             [mypkg.plugins]\n\
+             foo = mypkg.foo:plugin\n",
+        );
+        assert_eq!(entry_points_txt.len(), 3);
+
+        let python = PythonBuild {
+            entry_points: vec![
+                "mycli = mypkg.cli:main".parse().unwrap(),
+                "other = mypkg.other:main".parse().unwrap(),
+            ],
+            gui_entry_points: vec!["typo = mypkg.gui:main".parse().unwrap()],
+            ..Default::default()
+        };
+
+        let mismatches = entry_point_mismatches(&python, &entry_points_txt, false);
+        assert_eq!(
+            mismatches,
+            vec![
+                "`other` points to `mypkg.other:main` in `build.python.entry_points` but to `mypkg.other:run` in [console_scripts]",
+                "`typo` is declared in `build.python.gui_entry_points` but not in [gui_scripts]",
+            ]
+        );
+
+        // noarch packages additionally report entry points that were not declared
+        let mismatches = entry_point_mismatches(&python, &entry_points_txt, true);
+        assert_eq!(
+            mismatches.last().unwrap(),
+            "`mygui` is declared in [gui_scripts] but missing from `build.python.gui_entry_points`"
+        );
+    }
 }
@@ -1,8 +1,15 @@
 //! A post process step that runs a regex replacement over the new files
+use std::{collections::HashSet, path::PathBuf};
+
 use crate::{metadata::Output, packaging::TempFiles};
 use fs_err as fs;
 
-pub fn regex_post_process(temp_files: &TempFiles, output: &Output) -> Result<(), std::io::Error> {
+/// Apply the `build.post_process` replacements and return the files whose contents changed.
+pub fn regex_post_process(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<HashSet<PathBuf>, std::io::Error> {
+    let mut modified = HashSet::new();
     for post_process_step in output.recipe.build().post_process.iter() {
         for file in temp_files.files.iter() {
             if post_process_step.files.is_match(file) {
@@ -10,10 +17,13 @@ pub fn regex_post_process(temp_files: &TempFiles, output: &Output) -> Result<(),
                 let new_contents = post_process_step
                     .regex
                     .replace_all(&file_contents, &post_process_step.replacement);
+                if new_contents != file_contents {
+                    modified.insert(file.clone());
+                }
                 fs::write(file, new_contents.as_bytes())?;
             }
         }
     }
 
-    Ok(())
+    Ok(modified)
 }
@@ -1,5 +1,5 @@
 use fs_err as fs;
-use rattler_build_recipe::stage1::GlobVec;
+use rattler_build_recipe::stage1::{GlobVec, build::RpathType};
 
 use crate::packaging::TempFiles;
 
@@ -14,6 +14,7 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 use super::checks::{LinkingCheckError, perform_linking_checks};
+use super::vendor::vendor_dir;
 
 #[derive(Error, Debug)]
 #[allow(missing_docs)]
@@ -100,10 +101,46 @@ pub trait Relinker {
         encoded_prefix: &Path,
         custom_rpaths: &[String],
         rpath_allowlist: &GlobVec,
+        rpath_type: RpathType,
         system_tools: &SystemTools,
     ) -> Result<(), RelinkError>;
 }
 
+/// An entry of `dynamic_linking.rpaths` translated for a binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CustomRpath {
+    /// A path relative to the prefix, joined to the encoded prefix
+    Prefix(PathBuf),
+    /// A path relative to the binary (`$ORIGIN` / `@loader_path`) or an absolute path outside of
+    /// the prefix that is written to the binary as is
+    Keep(PathBuf),
+}
+
+impl CustomRpath {
+    /// Translate an rpath from the recipe. Entries starting with `$ORIGIN` or `@loader_path` are
+    /// rewritten to use `loader` (the variable of the target platform) and absolute entries are
+    /// kept verbatim. Everything else is relative to the prefix.
+    pub fn new(rpath: &str, encoded_prefix: &Path, loader: &str) -> Self {
+        for variable in ["$ORIGIN", "${ORIGIN}", "@loader_path"] {
+            if let Some(rest) = rpath.strip_prefix(variable) {
+                return CustomRpath::Keep(PathBuf::from(format!("{loader}{rest}")));
+            }
+        }
+        if rpath.starts_with('/') {
+            CustomRpath::Keep(PathBuf::from(rpath))
+        } else {
+            CustomRpath::Prefix(encoded_prefix.join(rpath))
+        }
+    }
+
+    /// The rpath as it is written to (or resolved in) the binary
+    pub fn path(&self) -> &Path {
+        match self {
+            CustomRpath::Prefix(path) | CustomRpath::Keep(path) => path,
+        }
+    }
+}
+
 /// Returns the relink helper for the current platform.
 pub fn get_relinker(platform: Platform, path: &Path) -> Result<Box<dyn Relinker>, RelinkError> {
     if platform.is_linux() {
@@ -144,7 +181,9 @@ pub fn get_relinker(platform: Platform, path: &Path) -> Result<Box<dyn Relinker>
 ///
 /// On macOS (Mach-O files), we do the same trick and set the rpath to a relative path with the special
 /// `@loader_path` variable. The change for Mach-O files is applied with the `install_name_tool`.
-pub fn relink(temp_files: &TempFiles, output: &Output) -> Result<(), RelinkError> {
+///
+/// Returns the binaries that were relinked.
+pub fn relink(temp_files: &TempFiles, output: &Output) -> Result<HashSet<PathBuf>, RelinkError> {
     let dynamic_linking = &output.recipe.build().dynamic_linking;
     let target_platform = output.build_configuration.target_platform;
     let relocation_config = &dynamic_linking.binary_relocation;
@@ -154,11 +193,16 @@ pub fn relink(temp_files: &TempFiles, output: &Output) -> Result<(), RelinkError
         || target_platform.arch() == Some(Arch::Wasm32)
         || relocation_config.is_none()
     {
-        return Ok(());
+        return Ok(HashSet::new());
     }
 
-    let rpaths = dynamic_linking.rpaths.to_vec();
+    let mut rpaths = dynamic_linking.rpaths.to_vec();
+    // make the libraries copied by `vendor_libraries` resolvable
+    if !dynamic_linking.vendor_libraries.is_empty() {
+        rpaths.push(vendor_dir(output).to_string_lossy().to_string());
+    }
     let rpath_allowlist = &dynamic_linking.rpath_allowlist;
+    let rpath_type = dynamic_linking.rpath_type;
 
     let tmp_prefix = temp_files.temp_dir.path();
     let encoded_prefix = &temp_files.encoded_prefix;
@@ -195,6 +239,7 @@ pub fn relink(temp_files: &TempFiles, output: &Output) -> Result<(), RelinkError
                             encoded_prefix,
                             &rpaths,
                             rpath_allowlist,
+                            rpath_type,
                             &system_tools,
                         )?;
                     }
@@ -217,5 +262,10 @@ pub fn relink(temp_files: &TempFiles, output: &Output) -> Result<(), RelinkError
     }
     perform_linking_checks(output, &binaries, tmp_prefix)?;
 
-    Ok(())
+    // Windows binaries are only checked, not modified
+    if target_platform.is_windows() {
+        binaries.clear();
+    }
+
+    Ok(binaries)
 }
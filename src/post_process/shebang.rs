@@ -0,0 +1,175 @@
+//! Rewrite shebangs that the kernel cannot execute.
+//!
+//! Scripts that point to an interpreter in the host prefix get a shebang like
+//! `#!<host prefix>/bin/python`. A prefix with spaces cannot be expressed in a shebang, so such
+//! shebangs are replaced:
+//!
+//! - Python scripts get the `/bin/sh` trampoline that `pip` uses, which keeps the absolute path
+//!   to the interpreter (so that it is still replaced at installation time).
+//! - Other scripts use `/usr/bin/env` to find the interpreter on the `PATH`.
+//!
+//! Shebangs that are only too long for the kernel are left alone: the host prefix is padded to a
+//! long placeholder at build time, and rattler rewrites long shebangs at installation time, once
+//! the (usually short) prefix of the environment is known.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use content_inspector::ContentType;
+use fs_err as fs;
+
+use crate::{metadata::Output, packaging::TempFiles};
+
+/// Split a shebang line into the interpreter in `prefix` and its arguments
+fn parse_shebang<'a>(line: &'a str, prefix: &str) -> Option<(&'a str, &'a str)> {
+    let command = line.strip_prefix("#!")?.trim();
+    let rest = command.strip_prefix(prefix)?;
+    if !rest.starts_with('/') {
+        return None;
+    }
+    // the prefix may contain whitespace, the rest of the interpreter path may not
+    let end = rest
+        .find(char::is_whitespace)
+        .map(|idx| prefix.len() + idx)
+        .unwrap_or(command.len());
+    Some((&command[..end], command[end..].trim()))
+}
+
+/// Rewrite the shebang `line` if its interpreter path contains spaces.
+///
+/// Returns the new header (which can span multiple lines, without the trailing newline).
+fn rewrite_shebang(line: &str, prefix: &str) -> Option<String> {
+    let (interpreter, args) = parse_shebang(line, prefix)?;
+    if !interpreter.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let name = Path::new(interpreter).file_name()?.to_string_lossy();
+    let header = if name.starts_with("python") {
+        let args = if args.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", args)
+        };
+        format!("#!/bin/sh\n'''exec' \"{interpreter}\" {args}\"$0\" \"$@\"\n' '''")
+    } else if args.is_empty() {
+        format!("#!/usr/bin/env {}", name)
+    } else {
+        // `env` only splits the arguments with `-S`
+        format!("#!/usr/bin/env -S {} {}", name, args)
+    };
+
+    Some(header)
+}
+
+/// Rewrite the shebangs of all scripts in the package that point into a prefix with spaces.
+/// Returns the rewritten scripts.
+pub fn fix_shebangs(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<HashSet<PathBuf>, std::io::Error> {
+    if output.target_platform().is_windows() {
+        return Ok(HashSet::new());
+    }
+
+    let tmp_prefix = temp_files.temp_dir.path();
+    let prefix = temp_files.encoded_prefix.to_string_lossy();
+    let mut modified = HashSet::new();
+    for (path, content_type) in temp_files.content_type_map() {
+        if !matches!(content_type, Some(ContentType::UTF_8)) || path.is_symlink() {
+            continue;
+        }
+
+        let contents = fs::read_to_string(path)?;
+        if !contents.starts_with("#!") {
+            continue;
+        }
+        let (line, rest) = contents.split_once('\n').unwrap_or((&contents, ""));
+        let Some(header) = rewrite_shebang(line.trim_end_matches('\r'), &prefix) else {
+            continue;
+        };
+
+        let rel_path = path.strip_prefix(tmp_prefix).unwrap_or(path);
+        tracing::info!(
+            "Rewrote the shebang of {} (interpreter path contains spaces): {}",
+            rel_path.display(),
+            header.lines().next().unwrap_or_default()
+        );
+        fs::write(path, format!("{}\n{}", header, rest))?;
+        modified.insert(path.clone());
+    }
+
+    if !modified.is_empty() {
+        tracing::info!("Rewrote the shebangs of {} scripts", modified.len());
+    }
+
+    Ok(modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIX: &str = "/opt/host_env_placehold_placehold";
+
+    #[test]
+    fn test_parse_shebang() {
+        assert_eq!(
+            parse_shebang(&format!("#!{PREFIX}/bin/python -E"), PREFIX),
+            Some((format!("{PREFIX}/bin/python").as_str(), "-E"))
+        );
+        assert_eq!(
+            parse_shebang("#!/opt/my env/bin/perl -w", "/opt/my env"),
+            Some(("/opt/my env/bin/perl", "-w"))
+        );
+        assert_eq!(parse_shebang("#!/usr/bin/env python", PREFIX), None);
+        // a different prefix that starts with the same characters
+        assert_eq!(
+            parse_shebang(&format!("#!{PREFIX}2/bin/python"), PREFIX),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_long_shebang_is_unchanged() {
+        // the host prefix of a real build, padded to the placeholder length
+        let timestamp = chrono::Utc::now();
+        let directories = crate::types::Directories::builder(
+            "foo",
+            Path::new("/home/user/recipes/foo/recipe.yaml"),
+            Path::new("/home/user/output"),
+            &timestamp,
+        )
+        .skip_directory_creation(true)
+        .build()
+        .unwrap();
+        let prefix = directories.host_prefix.to_string_lossy();
+        assert!(prefix.len() > 127);
+
+        // rattler shortens these shebangs at installation time
+        for line in [
+            format!("#!{prefix}/bin/python3.12 -E"),
+            format!("#!{prefix}/bin/perl"),
+        ] {
+            assert_eq!(rewrite_shebang(&line, &prefix), None);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_shebang_with_spaces() {
+        let prefix = "/opt/my env";
+        let header = rewrite_shebang("#!/opt/my env/bin/python", prefix).unwrap();
+        assert_eq!(
+            header,
+            "#!/bin/sh\n'''exec' \"/opt/my env/bin/python\" \"$0\" \"$@\"\n' '''"
+        );
+
+        let header = rewrite_shebang("#!/opt/my env/bin/perl -w", prefix).unwrap();
+        assert_eq!(header, "#!/usr/bin/env -S perl -w");
+
+        let header = rewrite_shebang("#!/opt/my env/bin/bash", prefix).unwrap();
+        assert_eq!(header, "#!/usr/bin/env bash");
+    }
+}
@@ -0,0 +1,279 @@
+//! Strip symbols from the executables and shared libraries of a package (`build.strip`).
+//!
+//! Stripping runs after relinking and the other post-processing steps, but before the Mach-O
+//! binaries are signed, because stripping invalidates their signature.
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use fs_err as fs;
+use rattler_conda_types::Platform;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{
+    linux::link::SharedObject,
+    macos::link::Dylib,
+    metadata::Output,
+    packaging::TempFiles,
+    post_process::relink::{RelinkError, Relinker},
+    system_tools::Tool,
+};
+
+#[derive(Error, Debug)]
+#[allow(missing_docs)]
+pub enum StripError {
+    #[error("failed to find `{0}`: {1}")]
+    ToolNotFound(Tool, which::Error),
+
+    #[error("failed to strip {path}:\n{stderr}")]
+    StripFailed { path: PathBuf, stderr: String },
+
+    #[error("failed to inspect binary: {0}")]
+    Relink(#[from] RelinkError),
+
+    #[error("failed to read or write file: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The arguments for `strip` that remove everything that is not needed to load or link against
+/// the binary
+fn strip_args(is_macho: bool) -> &'static [&'static str] {
+    if is_macho {
+        // keep global symbols, otherwise dylibs cannot be linked against anymore
+        &["-x"]
+    } else {
+        &["--strip-unneeded"]
+    }
+}
+
+fn strip_file(strip: &Path, path: &Path, is_macho: bool) -> Result<(), StripError> {
+    let mut cmd = Command::new(strip);
+    cmd.args(strip_args(is_macho)).arg(path);
+
+    tracing::debug!("Running strip: {:?}", cmd);
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(StripError::StripFailed {
+            path: path.to_path_buf(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// The architecture and OS parts of the target triples of the cross-compiling toolchains
+/// (e.g. `aarch64-conda-linux-gnu-strip` or `arm64-apple-darwin20.0.0-strip`)
+fn triple_parts(platform: Platform) -> Option<(&'static str, &'static str)> {
+    let arch = match platform {
+        Platform::Linux64 | Platform::Osx64 => "x86_64",
+        Platform::LinuxAarch64 => "aarch64",
+        Platform::LinuxArmV7l => "armv7",
+        Platform::LinuxPpc64le => "powerpc64le",
+        Platform::LinuxS390X => "s390x",
+        Platform::OsxArm64 => "arm64",
+        _ => return None,
+    };
+    let os = if platform.is_osx() {
+        "-apple-darwin"
+    } else {
+        "-linux-"
+    };
+    Some((arch, os))
+}
+
+/// Find the `strip` for the binaries of `target_platform` when cross-compiling: `$STRIP` if it
+/// is set, otherwise the target-prefixed `strip` (`<triple>-strip`) in `bin_dir` of the build
+/// environment.
+fn find_cross_strip(
+    bin_dir: &Path,
+    target_platform: Platform,
+    env_strip: Option<&str>,
+) -> Option<PathBuf> {
+    if let Some(strip) = env_strip.filter(|strip| !strip.is_empty()) {
+        let strip = Path::new(strip);
+        if strip.is_absolute() {
+            return strip.is_file().then(|| strip.to_path_buf());
+        }
+        return which::which_in(strip, Some(bin_dir), bin_dir)
+            .or_else(|_| which::which(strip))
+            .ok();
+    }
+
+    let (arch, os) = triple_parts(target_platform)?;
+    let mut candidates = fs::read_dir(bin_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix("-strip"))
+                .is_some_and(|triple| {
+                    triple.starts_with(&format!("{arch}-")) && triple.contains(os)
+                })
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates.into_iter().next()
+}
+
+/// Strip all ELF and Mach-O binaries of the package that are not excluded in the recipe.
+///
+/// Returns the binaries that were stripped.
+pub fn strip(temp_files: &TempFiles, output: &Output) -> Result<HashSet<PathBuf>, StripError> {
+    let strip_config = &output.recipe.build().strip;
+    if !strip_config.enabled {
+        return Ok(HashSet::new());
+    }
+
+    let target_platform = output.target_platform();
+    let is_macho = target_platform.is_osx();
+    if !target_platform.is_linux() && !is_macho {
+        tracing::warn!(
+            "Stripping binaries is not supported for {}, skipping",
+            target_platform
+        );
+        return Ok(HashSet::new());
+    }
+
+    let tmp_prefix = temp_files.temp_dir.path();
+    let mut binaries = Vec::new();
+    for (path, content_type) in temp_files.content_type_map() {
+        if content_type != &Some(content_inspector::ContentType::BINARY) {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.is_symlink() || metadata.is_dir() {
+            continue;
+        }
+        let is_binary = if is_macho {
+            Dylib::test_file(path)?
+        } else {
+            SharedObject::test_file(path)?
+        };
+        if !is_binary {
+            continue;
+        }
+        let rel_path = path.strip_prefix(tmp_prefix).unwrap_or(path);
+        if !strip_config.should_strip(rel_path) {
+            tracing::info!("Not stripping excluded binary {}", rel_path.display());
+            continue;
+        }
+        binaries.push(path);
+    }
+
+    if binaries.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let strip = if *target_platform != output.build_configuration.build_platform.platform {
+        // the `strip` of the build platform cannot handle binaries of other architectures
+        let bin_dir = output.build_prefix().join("bin");
+        let env_strip = std::env::var("STRIP").ok();
+        match find_cross_strip(&bin_dir, *target_platform, env_strip.as_deref()) {
+            Some(strip) => strip,
+            None => {
+                let warning = format!(
+                    "Not stripping binaries: found no `strip` for {} (set `$STRIP` or add the \
+                     cross-compiling binutils to the build requirements)",
+                    target_platform
+                );
+                tracing::warn!("{}", warning);
+                output.record_warning(&warning);
+                return Ok(HashSet::new());
+            }
+        }
+    } else {
+        // prefer the strip of the build environment over the one on the `PATH`
+        let system_tools = output.system_tools.with_build_prefix(output.build_prefix());
+        system_tools
+            .find_tool(Tool::Strip)
+            .map_err(|e| StripError::ToolNotFound(Tool::Strip, e))?
+    };
+
+    let stripped = binaries
+        .par_iter()
+        .map(|path| {
+            let size_before = fs::metadata(path)?.len();
+            strip_file(&strip, path, is_macho)?;
+            let size_after = fs::metadata(path)?.len();
+            tracing::info!(
+                "Stripped {} ({} -> {} bytes)",
+                path.strip_prefix(tmp_prefix).unwrap_or(path).display(),
+                size_before,
+                size_after
+            );
+            Ok((*path).clone())
+        })
+        .collect::<Result<HashSet<_>, StripError>>()?;
+
+    Ok(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_cross_strip() {
+        let build_prefix = tempfile::tempdir().unwrap();
+        let bin_dir = build_prefix.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        for name in [
+            "strip",
+            "x86_64-conda-linux-gnu-strip",
+            "aarch64-conda-linux-gnu-strip",
+            "aarch64-conda-linux-gnu-objcopy",
+        ] {
+            fs::write(bin_dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(
+            find_cross_strip(&bin_dir, Platform::LinuxAarch64, None),
+            Some(bin_dir.join("aarch64-conda-linux-gnu-strip"))
+        );
+        // the host `strip` is never used for foreign binaries
+        assert_eq!(find_cross_strip(&bin_dir, Platform::OsxArm64, None), None);
+        assert_eq!(
+            find_cross_strip(&bin_dir, Platform::LinuxPpc64le, None),
+            None
+        );
+
+        // `$STRIP` takes priority
+        let strip = bin_dir.join("x86_64-conda-linux-gnu-strip");
+        assert_eq!(
+            find_cross_strip(&bin_dir, Platform::LinuxAarch64, strip.to_str()),
+            Some(strip)
+        );
+        assert_eq!(
+            find_cross_strip(
+                &bin_dir,
+                Platform::LinuxAarch64,
+                Some("/does/not/exist/strip")
+            ),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_strip_elf() {
+        let Ok(strip) = which::which("strip") else {
+            tracing::warn!("strip not found, skipping test");
+            return;
+        };
+
+        let test_data = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/binary_files");
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let binary = tmp_dir.path().join("simple.so");
+        fs::copy(test_data.join("simple.so"), &binary).unwrap();
+
+        let size_before = fs::metadata(&binary).unwrap().len();
+        strip_file(&strip, &binary, false).unwrap();
+        assert!(fs::metadata(&binary).unwrap().len() <= size_before);
+        assert!(SharedObject::test_file(&binary).unwrap());
+    }
+}
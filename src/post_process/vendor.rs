@@ -0,0 +1,153 @@
+//! Copy libraries from the host prefix into the package (`dynamic_linking.vendor_libraries`).
+//!
+//! This produces self-contained packages for channels that do not carry these libraries (similar
+//! to `auditwheel` / `delocate` for wheels). The matched libraries are copied into a private
+//! directory of the package (`lib/<package name>.libs`), and this directory is added to the rpaths
+//! of all binaries, so that the relinking step points them to the vendored copies.
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+
+use crate::{metadata::Output, packaging::TempFiles};
+
+/// The directory (relative to the prefix) that the libraries are vendored into
+pub fn vendor_dir(output: &Output) -> PathBuf {
+    Path::new("lib").join(format!("{}.libs", output.name().as_normalized()))
+}
+
+/// Copy a single library to `dest`. Symlinks are dereferenced unless `keep_symlink` is set.
+fn copy_library(source: &Path, dest: &Path, keep_symlink: bool) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    if keep_symlink {
+        return fs_err::os::unix::fs::symlink(fs::read_link(source)?, dest);
+    }
+    #[cfg(not(unix))]
+    let _ = keep_symlink;
+
+    fs::copy(source, dest)?;
+    Ok(())
+}
+
+/// Copy the libraries that match `dynamic_linking.vendor_libraries` from the host prefix into
+/// the package. Returns the new files.
+pub fn vendor_libraries(
+    temp_files: &TempFiles,
+    output: &Output,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let globs = &output.recipe.build().dynamic_linking.vendor_libraries;
+    if globs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let target_platform = output.target_platform();
+    if !(target_platform.is_linux() || target_platform.is_osx()) {
+        tracing::warn!(
+            "`vendor_libraries` is only supported for Linux and macOS packages, ignoring it"
+        );
+        return Ok(Vec::new());
+    }
+
+    let host_prefix = output.prefix();
+    let tmp_prefix = temp_files.temp_dir.path();
+    let vendor_dir = vendor_dir(output);
+    let dest_dir = tmp_prefix.join(&vendor_dir);
+
+    let mut vendored = Vec::new();
+    for entry in walkdir::WalkDir::new(host_prefix)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let Ok(rel_path) = entry.path().strip_prefix(host_prefix) else {
+            continue;
+        };
+        if !globs.is_match(rel_path) {
+            continue;
+        }
+        // files that were built by this package are packaged where they are
+        if temp_files.files.contains(&tmp_prefix.join(rel_path)) {
+            continue;
+        }
+        let Some(file_name) = rel_path.file_name() else {
+            continue;
+        };
+
+        let dest = dest_dir.join(file_name);
+        if dest.symlink_metadata().is_ok() {
+            tracing::warn!(
+                "Not vendoring {}, a library with the same name was already vendored",
+                rel_path.display()
+            );
+            continue;
+        }
+
+        // keep symlinks between the versions of a library (`libfoo.so -> libfoo.so.1`) if the
+        // target is vendored as well, they end up in the same directory
+        let keep_symlink = entry.path_is_symlink()
+            && fs::read_link(entry.path()).is_ok_and(|target| {
+                target.components().count() == 1
+                    && globs.is_match(&rel_path.with_file_name(&target))
+            });
+
+        fs::create_dir_all(&dest_dir)?;
+        copy_library(entry.path(), &dest, keep_symlink)?;
+        tracing::info!(
+            "Vendoring {} into {}",
+            rel_path.display(),
+            vendor_dir.display()
+        );
+        vendored.push(dest);
+    }
+
+    if vendored.is_empty() {
+        tracing::warn!("No file in the host prefix matches `vendor_libraries`");
+    }
+
+    Ok(vendored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_library() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = tmp.path().join("source");
+        let dest = tmp.path().join("dest");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::create_dir_all(source.join("other")).unwrap();
+
+        fs::write(source.join("libfoo.so.1.2"), b"library").unwrap();
+        fs_err::os::unix::fs::symlink("libfoo.so.1.2", source.join("libfoo.so.1")).unwrap();
+        fs_err::os::unix::fs::symlink("../libfoo.so.1.2", source.join("other/libbar.so")).unwrap();
+
+        copy_library(
+            &source.join("libfoo.so.1.2"),
+            &dest.join("libfoo.so.1.2"),
+            false,
+        )
+        .unwrap();
+        copy_library(&source.join("libfoo.so.1"), &dest.join("libfoo.so.1"), true).unwrap();
+        copy_library(
+            &source.join("other/libbar.so"),
+            &dest.join("libbar.so"),
+            false,
+        )
+        .unwrap();
+
+        assert!(!dest.join("libfoo.so.1.2").is_symlink());
+        assert_eq!(
+            fs::read_link(dest.join("libfoo.so.1")).unwrap(),
+            PathBuf::from("libfoo.so.1.2")
+        );
+        // all other symlinks are dereferenced
+        assert!(!dest.join("libbar.so").is_symlink());
+        assert_eq!(fs::read(dest.join("libbar.so")).unwrap(), b"library");
+    }
+}
@@ -2,11 +2,10 @@
 
 use miette::IntoDiagnostic;
 use rattler_conda_types::{
-    Channel, ChannelUrl, MatchSpec, NamedChannelOrUrl, PackageName, Platform,
+    Channel, ChannelUrl, MatchSpec, NamedChannelOrUrl, PackageName, Platform, RepoDataRecord,
 };
-use rattler_index::{IndexFsConfig, index_fs};
 use rattler_repodata_gateway::{CacheClearMode, Gateway, SubdirSelection};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 use crate::BuildString;
@@ -70,23 +69,60 @@ pub(crate) async fn fetch_highest_build_numbers(
         }
     };
 
-    // Collect unique package names from outputs (we'll filter by version later)
-    let mut package_specs: Vec<MatchSpec> = Vec::new();
+    // Track the versions we're building
     let mut versions_to_check: HashMap<PackageName, Vec<String>> = HashMap::new();
-
     for output in outputs {
-        let name = output.name().clone();
-        let version = output.recipe.package().version().to_string();
-
-        // Track versions we're interested in
         versions_to_check
-            .entry(name.clone())
+            .entry(output.name().clone())
             .or_default()
-            .push(version);
+            .push(output.recipe.package().version().to_string());
+    }
 
-        // Create a matchspec that matches the package name (any version)
+    let records = query_channel_records(
+        channel,
+        outputs,
+        vec![target_platform, Platform::NoArch],
+        tool_config,
+    )
+    .await;
+
+    // Process results to find highest build numbers
+    let mut highest_build_numbers: HashMap<(PackageName, String), u64> = HashMap::new();
+    for record in &records {
+        let name = &record.package_record.name;
+        let version = record.package_record.version.version().to_string();
+
+        // Only track versions we're actually building
+        if let Some(versions) = versions_to_check.get(name)
+            && versions.contains(&version)
+        {
+            let key = (name.clone(), version);
+            let build_number = record.package_record.build_number;
+            highest_build_numbers
+                .entry(key)
+                .and_modify(|e| *e = (*e).max(build_number))
+                .or_insert(build_number);
+        }
+    }
+
+    Ok(highest_build_numbers)
+}
+
+/// Query the records of the packages of `outputs` (any version) in the channel. The channel
+/// might not exist yet or be empty, so errors are logged and no records are returned.
+async fn query_channel_records(
+    channel: Channel,
+    outputs: &[Output],
+    platforms: Vec<Platform>,
+    tool_config: &Configuration,
+) -> Vec<RepoDataRecord> {
+    // Create a matchspec per package name (any version)
+    let mut package_specs: Vec<MatchSpec> = Vec::new();
+    for output in outputs {
         let spec = MatchSpec {
-            name: Some(rattler_conda_types::PackageNameMatcher::Exact(name)),
+            name: Some(rattler_conda_types::PackageNameMatcher::Exact(
+                output.name().clone(),
+            )),
             ..Default::default()
         };
         if !package_specs.iter().any(|s| s.name == spec.name) {
@@ -95,7 +131,7 @@ pub(crate) async fn fetch_highest_build_numbers(
     }
 
     if package_specs.is_empty() {
-        return Ok(HashMap::new());
+        return Vec::new();
     }
 
     let span = tracing::info_span!("Fetching build numbers from target channel",);
@@ -104,11 +140,7 @@ pub(crate) async fn fetch_highest_build_numbers(
     // Query the repodata
     let result = tool_config
         .repodata_gateway
-        .query(
-            vec![channel],
-            [target_platform, Platform::NoArch],
-            package_specs,
-        )
+        .query(vec![channel], platforms, package_specs)
         .with_reporter(
             GatewayReporter::builder()
                 .with_multi_progress(tool_config.fancy_log_handler.multi_progress().clone())
@@ -125,37 +157,91 @@ pub(crate) async fn fetch_highest_build_numbers(
         .clear()
         .unwrap();
 
-    // Process results to find highest build numbers
-    let mut highest_build_numbers: HashMap<(PackageName, String), u64> = HashMap::new();
-
     match result {
-        Ok(repo_data) => {
-            for repo in repo_data {
-                for record in repo.iter() {
-                    let name = &record.package_record.name;
-                    let version = record.package_record.version.version().to_string();
-
-                    // Only track versions we're actually building
-                    if let Some(versions) = versions_to_check.get(name)
-                        && versions.contains(&version)
-                    {
-                        let key = (name.clone(), version);
-                        let build_number = record.package_record.build_number;
-                        highest_build_numbers
-                            .entry(key)
-                            .and_modify(|e| *e = (*e).max(build_number))
-                            .or_insert(build_number);
-                    }
-                }
-            }
-        }
+        Ok(repo_data) => repo_data
+            .iter()
+            .flat_map(|repo| repo.iter().cloned())
+            .collect(),
         Err(e) => {
-            // Log the error but don't fail - the channel might not exist yet or be empty
             tracing::debug!("Could not fetch repodata from target channel: {}", e);
+            Vec::new()
         }
     }
+}
 
-    Ok(highest_build_numbers)
+/// The part of a build string that identifies the variant, i.e. the build string without the
+/// trailing build number (`py312h1234abc_2` -> `py312h1234abc`)
+fn build_string_variant(build_string: &str) -> &str {
+    build_string
+        .rsplit_once('_')
+        .map_or(build_string, |(variant, _)| variant)
+}
+
+/// Make sure that the outputs do not overwrite packages in the target channel: the build number
+/// of every output for which a package with the same name, version and variant exists is set to
+/// one more than the highest build number of these packages (unless the recipe's is higher).
+pub(crate) async fn apply_auto_build_number(
+    target_channel: &NamedChannelOrUrl,
+    outputs: &mut [Output],
+    tool_config: &Configuration,
+) -> miette::Result<()> {
+    if outputs.is_empty() {
+        return Ok(());
+    }
+    let channel = target_channel
+        .clone()
+        .into_channel(&tool_config.channel_config)
+        .into_diagnostic()?;
+    let platforms = outputs
+        .iter()
+        .map(|output| *output.target_platform())
+        .chain([Platform::NoArch])
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let records = query_channel_records(channel, outputs, platforms, tool_config).await;
+    let mut highest_build_numbers: HashMap<(PackageName, String, String), u64> = HashMap::new();
+    for record in &records {
+        let key = (
+            record.package_record.name.clone(),
+            record.package_record.version.version().to_string(),
+            build_string_variant(&record.package_record.build).to_string(),
+        );
+        let build_number = record.package_record.build_number;
+        highest_build_numbers
+            .entry(key)
+            .and_modify(|e| *e = (*e).max(build_number))
+            .or_insert(build_number);
+    }
+
+    for output in outputs {
+        let Some(build_string) = output.recipe.build.string.as_resolved() else {
+            continue;
+        };
+        let key = (
+            output.name().clone(),
+            output.recipe.package().version().to_string(),
+            build_string_variant(build_string).to_string(),
+        );
+        let Some(highest) = highest_build_numbers.get(&key) else {
+            continue;
+        };
+        let current = output.recipe.build.number.unwrap_or(0);
+        let new_build_number = current.max(highest + 1);
+        if new_build_number != current {
+            tracing::info!(
+                "{} ({}): build number {} exists in {}, using build number {}",
+                output.name().as_normalized(),
+                build_string,
+                highest,
+                target_channel,
+                new_build_number
+            );
+            set_build_number(output, new_build_number);
+        }
+    }
+    Ok(())
 }
 
 /// Apply build number override to outputs
@@ -189,23 +275,27 @@ pub(crate) fn apply_build_number_override(
             }
         };
 
-        // Update the build number
-        output.recipe.build.number = Some(new_build_number);
-
-        // Extract the hash from the current build string and recompute with new build number
-        let current_build_string = output
-            .recipe
-            .build
-            .string
-            .as_resolved()
-            .expect("Build string should be resolved at this point");
-
-        // Split on last '_' to separate hash from build number
-        if let Some(last_underscore) = current_build_string.rfind('_') {
-            let hash_part = &current_build_string[..last_underscore];
-            let new_build_string = format!("{}_{}", hash_part, new_build_number);
-            output.recipe.build.string = BuildString::Resolved(new_build_string);
-        }
+        set_build_number(output, new_build_number);
+    }
+}
+
+/// Set the build number of an output and update its build string accordingly
+fn set_build_number(output: &mut Output, build_number: u64) {
+    output.recipe.build.number = Some(build_number);
+
+    // Extract the hash from the current build string and recompute with new build number
+    let current_build_string = output
+        .recipe
+        .build
+        .string
+        .as_resolved()
+        .expect("Build string should be resolved at this point");
+
+    // Split on last '_' to separate hash from build number
+    if let Some(last_underscore) = current_build_string.rfind('_') {
+        let hash_part = &current_build_string[..last_underscore];
+        let new_build_string = format!("{}_{}", hash_part, build_number);
+        output.recipe.build.string = BuildString::Resolved(new_build_string);
     }
 }
 
@@ -332,10 +422,8 @@ async fn upload_to_s3(
     package_paths: &[PathBuf],
     publish_data: &PublishData,
 ) -> miette::Result<()> {
-    use rattler_index::{IndexS3Config, ensure_channel_initialized_s3, index_s3};
     use rattler_networking::s3_middleware;
     use rattler_upload::upload::upload_package_to_s3;
-    use std::collections::HashSet;
 
     tracing::info!("Uploading packages to S3 channel: {}", url);
 
@@ -384,17 +472,8 @@ async fn upload_to_s3(
             }
         });
 
-    // Ensure channel is initialized with noarch/repodata.json
-    ensure_channel_initialized_s3(url, &resolved_credentials)
-        .await
-        .map_err(|e| miette::miette!("Failed to initialize S3 channel: {}", e))?;
-
-    // Collect unique subdirs from all packages
-    let mut subdirs = HashSet::new();
-    for package_path in package_paths {
-        let subdir = determine_package_subdir(package_path)?;
-        subdirs.insert(subdir);
-    }
+    // Collect unique platforms from all packages
+    let platforms = crate::index::package_platforms(package_paths)?;
 
     // Upload packages to S3
     upload_package_to_s3(
@@ -409,34 +488,7 @@ async fn upload_to_s3(
 
     tracing::info!("Successfully uploaded packages to S3");
 
-    for subdir in subdirs {
-        // Run S3 indexing for each subdir
-        tracing::info!("Indexing S3 channel at {} / {}", url, subdir);
-
-        let target_platform = subdir
-            .parse::<Platform>()
-            .map_err(|e| miette::miette!("Invalid platform subdir '{}': {}", subdir, e))?;
-
-        let index_config = IndexS3Config {
-            channel: url.clone(),
-            credentials: resolved_credentials.clone(),
-            target_platform: Some(target_platform),
-            repodata_patch: None,
-            write_zst: true,
-            write_shards: true,
-            force: false,
-            max_parallel: num_cpus::get_physical(),
-            multi_progress: None,
-            precondition_checks: rattler_index::PreconditionChecks::Enabled,
-        };
-
-        index_s3(index_config)
-            .await
-            .map_err(|e| miette::miette!("Failed to index S3 channel: {}", e))?;
-    }
-
-    tracing::info!("Successfully indexed S3 channel");
-    Ok(())
+    crate::index::index_s3_channel(url, resolved_credentials, &platforms, false).await
 }
 
 /// Upload packages to Quetz server
@@ -572,28 +624,55 @@ async fn upload_to_prefix(
     // Remove the channel path from the URL to get just the base server URL
     server_url.set_path("");
 
-    // Determine attestation source
-    let attestation = if publish_data.generate_attestation {
-        AttestationSource::GenerateAttestation
-    } else {
-        AttestationSource::NoAttestation
+    let prefix_data = |attestation| {
+        // Create PrefixData with server URL, channel, optional API key, attestation, skip_existing and force
+        PrefixData::new(
+            server_url.clone(),
+            channel.clone(),
+            None,
+            attestation,
+            SkipExisting(false),
+            ForceOverwrite(publish_data.force),
+            false, // store_github_attestation
+        )
     };
 
-    // Create PrefixData with server URL, channel, optional API key, attestation, skip_existing and force
-    let prefix_data = PrefixData::new(
-        server_url,
-        channel,
-        None,
-        attestation,
-        SkipExisting(false),
-        ForceOverwrite(publish_data.force),
-        false, // store_github_attestation
-    );
-
-    // Upload packages
-    upload_package_to_prefix(&auth_storage, &package_paths.to_vec(), prefix_data)
+    // Determine attestation source
+    if publish_data.generate_attestation {
+        upload_package_to_prefix(
+            &auth_storage,
+            &package_paths.to_vec(),
+            prefix_data(AttestationSource::GenerateAttestation),
+        )
         .await
         .map_err(|e| miette::miette!("Failed to upload packages to Prefix: {}", e))?;
+    } else {
+        // packages with a signed SLSA provenance (`--slsa-provenance=signed`) are uploaded
+        // one by one with their provenance as attestation
+        let (attested, unattested): (Vec<_>, Vec<_>) = package_paths
+            .iter()
+            .cloned()
+            .partition(|path| crate::slsa::bundle_path(path).exists());
+        for package in attested {
+            let bundle = crate::slsa::bundle_path(&package);
+            upload_package_to_prefix(
+                &auth_storage,
+                &vec![package],
+                prefix_data(AttestationSource::Attestation(bundle)),
+            )
+            .await
+            .map_err(|e| miette::miette!("Failed to upload packages to Prefix: {}", e))?;
+        }
+        if !unattested.is_empty() {
+            upload_package_to_prefix(
+                &auth_storage,
+                &unattested,
+                prefix_data(AttestationSource::NoAttestation),
+            )
+            .await
+            .map_err(|e| miette::miette!("Failed to upload packages to Prefix: {}", e))?;
+        }
+    }
 
     tracing::info!("Successfully uploaded packages to Prefix.dev");
     tracing::info!("Note: Prefix.dev handles indexing automatically on the server side");
@@ -661,9 +740,6 @@ async fn upload_to_local_filesystem(
     package_paths: &[PathBuf],
     force: bool,
 ) -> miette::Result<()> {
-    use rattler_index::ensure_channel_initialized_fs;
-    use std::collections::HashSet;
-
     tracing::info!(
         "Copying packages to local channel: {}",
         target_dir.display()
@@ -672,13 +748,8 @@ async fn upload_to_local_filesystem(
     // Create target directory if it doesn't exist
     fs_err::create_dir_all(target_dir).into_diagnostic()?;
 
-    // Ensure channel is initialized with noarch/repodata.json
-    ensure_channel_initialized_fs(target_dir)
-        .await
-        .map_err(|e| miette::miette!("Failed to initialize local channel: {}", e))?;
-
-    // Collect unique subdirs from all packages
-    let mut subdirs = HashSet::new();
+    // Collect unique platforms from all packages
+    let platforms = crate::index::package_platforms(package_paths)?;
 
     // Copy packages to the target directory organized by platform
     for package_path in package_paths {
@@ -689,7 +760,6 @@ async fn upload_to_local_filesystem(
 
         // Determine subdir from package
         let subdir = determine_package_subdir(package_path)?;
-        subdirs.insert(subdir.clone());
         let target_subdir = target_dir.join(&subdir);
 
         fs_err::create_dir_all(&target_subdir).into_diagnostic()?;
@@ -709,32 +779,33 @@ async fn upload_to_local_filesystem(
             target_path.display()
         );
         fs_err::copy(package_path, &target_path).into_diagnostic()?;
+
+        // the SLSA provenance is stored next to the package
+        for provenance in [
+            crate::slsa::statement_path(package_path),
+            crate::slsa::bundle_path(package_path),
+        ] {
+            if provenance.exists() {
+                fs_err::copy(
+                    &provenance,
+                    target_subdir.join(provenance.file_name().unwrap()),
+                )
+                .into_diagnostic()?;
+            }
+        }
     }
 
-    // Run rattler-index on the local directory for each subdir
-    tracing::info!("Indexing local channel at {}", target_dir.display());
-
-    for subdir in subdirs {
-        let target_platform = subdir
-            .parse::<Platform>()
-            .map_err(|e| miette::miette!("Invalid platform subdir '{}': {}", subdir, e))?;
-
-        let index_config = IndexFsConfig {
-            channel: target_dir.to_path_buf(),
-            target_platform: Some(target_platform),
-            repodata_patch: None,
-            write_zst: true,
-            write_shards: true,
-            force: false,
-            max_parallel: num_cpus::get_physical(),
-            multi_progress: None,
-        };
+    crate::index::index_local_channel(target_dir, &platforms, false).await
+}
 
-        index_fs(index_config)
-            .await
-            .map_err(|e| miette::miette!("Failed to index channel: {}", e))?;
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    tracing::info!("Successfully indexed local channel");
-    Ok(())
+    #[test]
+    fn test_build_string_variant() {
+        assert_eq!(build_string_variant("py312h1234abc_2"), "py312h1234abc");
+        assert_eq!(build_string_variant("h1234abc_0"), "h1234abc");
+        assert_eq!(build_string_variant("custom"), "custom");
+    }
 }
@@ -2,7 +2,12 @@
 
 use fs_err as fs;
 use rattler_conda_types::package::CondaArchiveType;
-use std::path::{Path, PathBuf};
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
 
 /// Extracts a folder from a tar.bz2 archive.
 fn folder_from_tar_bz2(
@@ -78,3 +83,263 @@ pub fn extract_recipe(package: &Path, dest_folder: &Path) -> Result<(), std::io:
     };
     Ok(())
 }
+
+/// The maximum number of differing byte ranges that are reported per file
+const MAX_DIFFERING_RANGES: usize = 16;
+
+/// A range of bytes `[start, end)` in which two files differ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ByteRange {
+    /// The offset of the first differing byte
+    pub start: u64,
+    /// The offset after the last differing byte
+    pub end: u64,
+}
+
+/// The result of comparing a file of the original package with the rebuilt package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileComparison {
+    /// The file is identical in both packages
+    Identical,
+    /// The content (or the target of a symlink) differs
+    Different {
+        /// The size of the file in the original package
+        original_size: u64,
+        /// The size of the file in the rebuilt package
+        rebuilt_size: u64,
+        /// The ranges of bytes that differ (at most 16, empty for symlinks)
+        offsets: Vec<ByteRange>,
+    },
+    /// The file is only in the original package
+    MissingInRebuild,
+    /// The file is only in the rebuilt package
+    MissingInOriginal,
+}
+
+/// The comparison of a single file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    /// The path of the file in the package
+    pub path: PathBuf,
+    /// The result of the comparison
+    #[serde(flatten)]
+    pub comparison: FileComparison,
+}
+
+/// A file-by-file comparison of an original and a rebuilt package (`rebuild --verify`)
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    /// The original package
+    pub original: PathBuf,
+    /// The rebuilt package
+    pub rebuilt: PathBuf,
+    /// Whether all files are identical
+    pub identical: bool,
+    /// The comparison of every file, sorted by path
+    pub files: Vec<FileReport>,
+}
+
+impl VerifyReport {
+    /// The number of files with the given comparison result
+    pub fn count(&self, matches: impl Fn(&FileComparison) -> bool) -> usize {
+        self.files.iter().filter(|f| matches(&f.comparison)).count()
+    }
+}
+
+/// The ranges of bytes in which `original` and `rebuilt` differ. A difference in size is
+/// reported as the range after the end of the shorter file.
+fn differing_ranges(original: &[u8], rebuilt: &[u8]) -> Vec<ByteRange> {
+    let mut ranges = Vec::<ByteRange>::new();
+    let common = original.len().min(rebuilt.len());
+    let mut start = None;
+    for offset in 0..=common {
+        let differs = offset < common && original[offset] != rebuilt[offset];
+        match (differs, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(first)) => {
+                ranges.push(ByteRange {
+                    start: first as u64,
+                    end: offset as u64,
+                });
+                start = None;
+                if ranges.len() == MAX_DIFFERING_RANGES {
+                    return ranges;
+                }
+            }
+            _ => {}
+        }
+    }
+    if original.len() != rebuilt.len() {
+        let end = original.len().max(rebuilt.len()) as u64;
+        match ranges.last_mut() {
+            Some(last) if last.end == common as u64 => last.end = end,
+            _ => ranges.push(ByteRange {
+                start: common as u64,
+                end,
+            }),
+        }
+    }
+    ranges
+}
+
+/// The relative paths of all files and symlinks in `dir`
+fn files_in(dir: &Path) -> Result<BTreeSet<PathBuf>, std::io::Error> {
+    let mut files = BTreeSet::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let path = entry
+            .path()
+            .strip_prefix(dir)
+            .expect("walkdir yields children");
+        files.insert(path.to_path_buf());
+    }
+    Ok(files)
+}
+
+/// Compare a file or symlink that exists in both directories
+fn compare_file(original: &Path, rebuilt: &Path) -> Result<FileComparison, std::io::Error> {
+    let original_meta = fs::symlink_metadata(original)?;
+    let rebuilt_meta = fs::symlink_metadata(rebuilt)?;
+    if original_meta.is_symlink() || rebuilt_meta.is_symlink() {
+        let target = |path: &Path, meta: &std::fs::Metadata| {
+            meta.is_symlink().then(|| fs::read_link(path)).transpose()
+        };
+        return Ok(
+            if target(original, &original_meta)? == target(rebuilt, &rebuilt_meta)? {
+                FileComparison::Identical
+            } else {
+                FileComparison::Different {
+                    original_size: original_meta.len(),
+                    rebuilt_size: rebuilt_meta.len(),
+                    offsets: Vec::new(),
+                }
+            },
+        );
+    }
+
+    let original = fs::read(original)?;
+    let rebuilt = fs::read(rebuilt)?;
+    Ok(if original == rebuilt {
+        FileComparison::Identical
+    } else {
+        FileComparison::Different {
+            original_size: original.len() as u64,
+            rebuilt_size: rebuilt.len() as u64,
+            offsets: differing_ranges(&original, &rebuilt),
+        }
+    })
+}
+
+/// Compare the files of two extracted packages
+fn compare_directories(original: &Path, rebuilt: &Path) -> Result<Vec<FileReport>, std::io::Error> {
+    let original_files = files_in(original)?;
+    let rebuilt_files = files_in(rebuilt)?;
+    original_files
+        .union(&rebuilt_files)
+        .map(|path| {
+            let comparison = match (original_files.contains(path), rebuilt_files.contains(path)) {
+                (true, false) => FileComparison::MissingInRebuild,
+                (false, true) => FileComparison::MissingInOriginal,
+                _ => compare_file(&original.join(path), &rebuilt.join(path))?,
+            };
+            Ok(FileReport {
+                path: path.clone(),
+                comparison,
+            })
+        })
+        .collect()
+}
+
+/// Extract both packages and compare them file by file
+pub fn verify_rebuild(original: &Path, rebuilt: &Path) -> Result<VerifyReport, std::io::Error> {
+    let original_dir = tempfile::tempdir()?;
+    let rebuilt_dir = tempfile::tempdir()?;
+    rattler_package_streaming::fs::extract(original, original_dir.path())
+        .map_err(std::io::Error::other)?;
+    rattler_package_streaming::fs::extract(rebuilt, rebuilt_dir.path())
+        .map_err(std::io::Error::other)?;
+
+    let files = compare_directories(original_dir.path(), rebuilt_dir.path())?;
+    Ok(VerifyReport {
+        original: original.to_path_buf(),
+        rebuilt: rebuilt.to_path_buf(),
+        identical: files
+            .iter()
+            .all(|f| f.comparison == FileComparison::Identical),
+        files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn test_differing_ranges() {
+        assert_eq!(differing_ranges(b"abcdef", b"abcdef"), vec![]);
+        assert_eq!(
+            differing_ranges(b"abcdef", b"aXcdYY"),
+            vec![range(1, 2), range(4, 6)]
+        );
+        // a difference in size is merged with a difference at the end
+        assert_eq!(differing_ranges(b"abcdef", b"abcdX"), vec![range(4, 6)]);
+        assert_eq!(differing_ranges(b"abc", b"abcdef"), vec![range(3, 6)]);
+
+        let original = vec![0u8; 100];
+        let rebuilt = (0..100).map(|i| (i % 2) as u8).collect::<Vec<_>>();
+        assert_eq!(
+            differing_ranges(&original, &rebuilt).len(),
+            MAX_DIFFERING_RANGES
+        );
+    }
+
+    #[test]
+    fn test_compare_directories() {
+        let original = tempfile::tempdir().unwrap();
+        let rebuilt = tempfile::tempdir().unwrap();
+        for dir in [original.path(), rebuilt.path()] {
+            fs::create_dir_all(dir.join("lib")).unwrap();
+            fs::write(dir.join("lib/same.txt"), "same").unwrap();
+        }
+        fs::write(original.path().join("lib/changed.txt"), "version 1").unwrap();
+        fs::write(rebuilt.path().join("lib/changed.txt"), "version 2").unwrap();
+        fs::write(original.path().join("only-original.txt"), "").unwrap();
+        fs::write(rebuilt.path().join("only-rebuilt.txt"), "").unwrap();
+
+        let files = compare_directories(original.path(), rebuilt.path()).unwrap();
+        let files = files
+            .into_iter()
+            .map(|f| (f.path, f.comparison))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    PathBuf::from("lib/changed.txt"),
+                    FileComparison::Different {
+                        original_size: 9,
+                        rebuilt_size: 9,
+                        offsets: vec![range(8, 9)],
+                    }
+                ),
+                (PathBuf::from("lib/same.txt"), FileComparison::Identical),
+                (
+                    PathBuf::from("only-original.txt"),
+                    FileComparison::MissingInRebuild
+                ),
+                (
+                    PathBuf::from("only-rebuilt.txt"),
+                    FileComparison::MissingInOriginal
+                ),
+            ]
+        );
+    }
+}
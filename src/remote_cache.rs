@@ -0,0 +1,438 @@
+//! The remote build cache (`build --remote-cache <URL>`).
+//!
+//! Finished packages are stored in a remote cache that is keyed by a hash of all the inputs of a
+//! build: the rendered recipe, the variant, the platforms, the sources (the content of the work
+//! directory after fetching and patching them), the files of the recipe directory, the resolved
+//! build and host environments and the version of rattler-build. Once the environments of an
+//! output are resolved, its package is looked up in the cache and downloaded to the output
+//! directory instead of building it. The packages that were built are stored in the cache,
+//! unless the cache is read-only.
+//!
+//! The package of a key is stored at `<URL>/<key>/<subdir>/<filename>`, on an HTTP server
+//! (`GET` and `PUT`), in an S3 bucket (`s3://`) or in a directory (`file://`, e.g. a network
+//! share). A cache that is not available is reported as a warning and never fails the build.
+#[cfg(feature = "s3")]
+use std::collections::HashMap;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_conda_types::RepoDataRecord;
+#[cfg(feature = "s3")]
+use rattler_networking::s3_middleware::S3Config;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    get_rattler_build_version, metadata::Output,
+    render::resolved_dependencies::ResolvedDependencies, tool_configuration::Configuration,
+    types::CacheUse,
+};
+
+/// A remote cache of finished packages
+#[derive(Debug, Clone)]
+pub struct RemoteCache {
+    /// The URL of the cache (ending with a `/`)
+    url: Url,
+    /// Whether packages are only looked up in the cache, but never stored
+    read_only: bool,
+    /// The authentication file that the credentials of S3 uploads are read from
+    #[cfg(feature = "s3")]
+    auth_file: Option<PathBuf>,
+    /// The S3 configuration of the buckets
+    #[cfg(feature = "s3")]
+    s3_config: HashMap<String, S3Config>,
+}
+
+impl RemoteCache {
+    /// A remote cache at `url`
+    pub fn new(
+        mut url: Url,
+        read_only: bool,
+        #[cfg(feature = "s3")] auth_file: Option<PathBuf>,
+        #[cfg(feature = "s3")] s3_config: HashMap<String, S3Config>,
+    ) -> Self {
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Self {
+            url,
+            read_only,
+            #[cfg(feature = "s3")]
+            auth_file,
+            #[cfg(feature = "s3")]
+            s3_config,
+        }
+    }
+
+    /// The URL of the cache
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The URL of the package of `output` with the key `key`
+    fn package_url(&self, key: &str, output: &Output) -> miette::Result<Url> {
+        self.url
+            .join(&format!(
+                "{key}/{}/{}",
+                output.target_platform(),
+                package_file_name(output)
+            ))
+            .into_diagnostic()
+    }
+
+    /// Download the package of `output` with the key `key` to the output directory. Returns
+    /// `None` if the cache does not have the package.
+    async fn fetch(
+        &self,
+        output: &Output,
+        key: &str,
+        client: &rattler_build_networking::BaseClient,
+    ) -> miette::Result<Option<PathBuf>> {
+        let url = self.package_url(key, output)?;
+        let target_dir = output
+            .build_configuration
+            .directories
+            .output_dir
+            .join(output.target_platform().to_string());
+        fs::create_dir_all(&target_dir).into_diagnostic()?;
+        let mut temp = tempfile::NamedTempFile::new_in(&target_dir).into_diagnostic()?;
+
+        if url.scheme() == "file" {
+            let path = url
+                .to_file_path()
+                .map_err(|_| miette::miette!("Invalid path in the URL {}", url))?;
+            if !path.is_file() {
+                return Ok(None);
+            }
+            fs::copy(&path, temp.path()).into_diagnostic()?;
+        } else {
+            let mut response = client
+                .for_host(&url)
+                .get(url.clone())
+                .send()
+                .await
+                .into_diagnostic()?;
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            response = response.error_for_status().into_diagnostic()?;
+            while let Some(chunk) = response.chunk().await.into_diagnostic()? {
+                temp.write_all(&chunk).into_diagnostic()?;
+            }
+        }
+
+        let path = target_dir.join(package_file_name(output));
+        temp.persist(&path).into_diagnostic()?;
+        Ok(Some(path))
+    }
+
+    /// Store `package`, the package of `output`, with the key `key`
+    async fn store(
+        &self,
+        output: &Output,
+        key: &str,
+        package: &Path,
+        client: &rattler_build_networking::BaseClient,
+    ) -> miette::Result<()> {
+        let url = self.package_url(key, output)?;
+        match url.scheme() {
+            "file" => {
+                let path = url
+                    .to_file_path()
+                    .map_err(|_| miette::miette!("Invalid path in the URL {}", url))?;
+                let dir = path.parent().expect("the package has a parent directory");
+                fs::create_dir_all(dir).into_diagnostic()?;
+                // other builds may read the package while it is copied
+                let temp = tempfile::NamedTempFile::new_in(dir).into_diagnostic()?;
+                fs::copy(package, temp.path()).into_diagnostic()?;
+                temp.persist(&path).into_diagnostic()?;
+            }
+            #[cfg(feature = "s3")]
+            "s3" => {
+                let credentials = crate::tool_configuration::resolve_s3_credentials(
+                    &self.s3_config,
+                    self.auth_file.clone(),
+                    &self.url,
+                )
+                .await
+                .into_diagnostic()?;
+                let auth_storage =
+                    crate::tool_configuration::get_auth_store(self.auth_file.clone())
+                        .into_diagnostic()?;
+                rattler_upload::upload::upload_package_to_s3(
+                    &auth_storage,
+                    self.url.join(&format!("{key}/")).into_diagnostic()?,
+                    Some(rattler_s3::S3Credentials {
+                        endpoint_url: credentials.endpoint_url,
+                        region: credentials.region,
+                        addressing_style: credentials.addressing_style,
+                        access_key_id: Some(credentials.access_key_id),
+                        secret_access_key: Some(credentials.secret_access_key),
+                        session_token: credentials.session_token,
+                    }),
+                    &vec![package.to_path_buf()],
+                    true,
+                )
+                .await?;
+            }
+            _ => {
+                client
+                    .for_host(&url)
+                    .put(url.clone())
+                    .body(fs::read(package).into_diagnostic()?)
+                    .send()
+                    .await
+                    .into_diagnostic()?
+                    .error_for_status()
+                    .into_diagnostic()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The file name of the package of `output`
+fn package_file_name(output: &Output) -> String {
+    format!(
+        "{}{}",
+        output.identifier(),
+        output
+            .build_configuration
+            .packaging_settings
+            .archive_type
+            .extension()
+    )
+}
+
+/// The packages of a resolved environment, as `subdir/name-version-build sha256`
+fn environment(dependencies: Option<&ResolvedDependencies>) -> Vec<String> {
+    let mut packages = dependencies
+        .map(|dependencies| {
+            dependencies
+                .resolved
+                .iter()
+                .map(package)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    packages.sort();
+    packages
+}
+
+fn package(record: &RepoDataRecord) -> String {
+    let package = &record.package_record;
+    format!(
+        "{}/{}-{}-{} {}",
+        package.subdir,
+        package.name.as_normalized(),
+        package.version,
+        package.build,
+        package
+            .sha256
+            .map(|sha256| format!("{sha256:x}"))
+            .unwrap_or_default()
+    )
+}
+
+/// Hash the paths, the contents and the permissions of the files `paths` below `root`
+fn hash_files(hasher: &mut Sha256, root: &Path, mut paths: Vec<PathBuf>) -> std::io::Result<()> {
+    paths.sort();
+    for path in paths {
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let metadata = fs::symlink_metadata(&path)?;
+        hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+        if metadata.is_symlink() {
+            hasher.update(b"\0symlink\0");
+            hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+        } else if metadata.is_dir() {
+            hasher.update(b"\0dir");
+        } else {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if metadata.permissions().mode() & 0o111 != 0 {
+                    hasher.update(b"\0executable");
+                }
+            }
+            hasher.update(b"\0file\0");
+            std::io::copy(&mut fs::File::open(&path)?, hasher)?;
+        }
+        hasher.update(b"\n");
+    }
+    Ok(())
+}
+
+/// Compute the key of `output` in the remote cache. The sources of the output must be fetched
+/// and its environments resolved.
+pub(crate) fn cache_key(output: &Output) -> miette::Result<String> {
+    let directories = &output.build_configuration.directories;
+    // the sources are hashed by their content, so that it does not matter where they are
+    let mut recipe = output.recipe.clone();
+    recipe.source.clear();
+    let dependencies = output.finalized_dependencies.as_ref();
+    let inputs = (
+        get_rattler_build_version(),
+        &recipe,
+        output.variant(),
+        output.target_platform(),
+        output.host_platform().platform,
+        output.build_configuration.build_platform.platform,
+        environment(dependencies.and_then(|deps| deps.build.as_ref())),
+        environment(dependencies.and_then(|deps| deps.host.as_ref())),
+    );
+
+    let mut hasher = Sha256::new();
+    inputs
+        .serialize(&mut serde_json::Serializer::new(&mut hasher))
+        .into_diagnostic()?;
+
+    // the `.git` directories of git sources differ between checkouts
+    let sources = walkdir::WalkDir::new(&directories.work_dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .map(|entry| entry.map(|entry| entry.into_path()))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+    hash_files(&mut hasher, &directories.work_dir, sources).into_diagnostic()?;
+
+    // the files of the recipe directory that are included in the package
+    let output_dir = directories.output_dir.clone();
+    let recipe_files = ignore::WalkBuilder::new(&directories.recipe_dir)
+        .git_global(false)
+        .parents(false)
+        .ignore(false)
+        .filter_entry(move |entry| !entry.path().starts_with(&output_dir))
+        .build()
+        .filter(|entry| !matches!(entry, Ok(entry) if entry.depth() == 0))
+        .map(|entry| entry.map(|entry| entry.into_path()))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+    hash_files(&mut hasher, &directories.recipe_dir, recipe_files).into_diagnostic()?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Look up the package of `output` in the remote cache of the build, if there is one. Returns
+/// the key of the output, and its package if it was found and downloaded to the output
+/// directory.
+pub(crate) async fn lookup(
+    output: &Output,
+    tool_configuration: &Configuration,
+) -> (Option<String>, Option<PathBuf>) {
+    let Some(remote_cache) = &tool_configuration.remote_cache else {
+        return (None, None);
+    };
+    let key = match cache_key(output) {
+        Ok(key) => key,
+        Err(e) => {
+            tracing::warn!("Failed to compute the key of the remote cache: {}", e);
+            return (None, None);
+        }
+    };
+    tracing::info!("Looking up the package in the remote cache (key {})", key);
+
+    let package = match remote_cache
+        .fetch(output, &key, &tool_configuration.client)
+        .await
+    {
+        Ok(Some(package)) => {
+            tracing::info!(
+                "Restored {} from the remote cache, skipping the build",
+                package.display()
+            );
+            Some(package)
+        }
+        Ok(None) => {
+            tracing::info!("The package is not in the remote cache");
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Failed to look up the package in the remote cache: {}", e);
+            None
+        }
+    };
+    output.record_cache_use(CacheUse::Remote {
+        restored: package.is_some(),
+    });
+    (Some(key), package)
+}
+
+/// Store `package`, the package of `output`, in the remote cache of the build, unless it is
+/// read-only
+pub(crate) async fn store(
+    output: &Output,
+    key: &str,
+    package: &Path,
+    tool_configuration: &Configuration,
+) {
+    let Some(remote_cache) = tool_configuration
+        .remote_cache
+        .as_ref()
+        .filter(|cache| !cache.read_only)
+    else {
+        return;
+    };
+    tracing::info!("Storing the package in the remote cache (key {})", key);
+    if let Err(e) = remote_cache
+        .store(output, key, package, &tool_configuration.client)
+        .await
+    {
+        tracing::warn!("Failed to store the package in the remote cache: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hash = |root: &Path| {
+            let paths = walkdir::WalkDir::new(root)
+                .min_depth(1)
+                .into_iter()
+                .map(|entry| entry.unwrap().into_path())
+                .collect();
+            let mut hasher = Sha256::new();
+            hash_files(&mut hasher, root, paths).unwrap();
+            format!("{:x}", hasher.finalize())
+        };
+
+        let a = tmp.path().join("a");
+        fs::create_dir_all(a.join("src")).unwrap();
+        fs::write(a.join("src").join("main.c"), "int main() {}").unwrap();
+        fs::write(a.join("README"), "hello").unwrap();
+        let b = tmp.path().join("b");
+        fs::create_dir_all(b.join("src")).unwrap();
+        fs::write(b.join("README"), "hello").unwrap();
+        fs::write(b.join("src").join("main.c"), "int main() {}").unwrap();
+        assert_eq!(hash(&a), hash(&b), "the location does not matter");
+
+        fs::write(b.join("README"), "hello!").unwrap();
+        assert_ne!(hash(&a), hash(&b), "the content matters");
+        fs::write(b.join("README"), "hello").unwrap();
+        fs::rename(b.join("README"), b.join("README.md")).unwrap();
+        assert_ne!(hash(&a), hash(&b), "the names matter");
+    }
+
+    #[test]
+    fn test_remote_cache_url() {
+        let cache = RemoteCache::new(
+            "https://cache.example.com/builds".parse().unwrap(),
+            false,
+            #[cfg(feature = "s3")]
+            None,
+            #[cfg(feature = "s3")]
+            HashMap::new(),
+        );
+        // the keys are joined to the URL
+        assert_eq!(cache.url().as_str(), "https://cache.example.com/builds/");
+    }
+}
@@ -0,0 +1,139 @@
+//! Explicit spec files of the build and host environments.
+//!
+//! Before the environments of an output are installed, they are written as explicit spec files
+//! (`build_env.txt` and `host_env.txt`) into the work directory and into
+//! `<output_dir>/envs/<subdir>/<name>-<version>-<build>`. Every line is the URL of a package with
+//! its hash, so that the environment of a failed build can be recreated outside of
+//! rattler-build with `conda create --file host_env.txt`.
+use std::path::Path;
+
+use fs_err as fs;
+use rattler_conda_types::{
+    ExplicitEnvironmentEntry, ExplicitEnvironmentSpec, PackageRecord, Platform, RepoDataRecord,
+};
+
+use super::resolved_dependencies::FinalizedDependencies;
+use crate::metadata::Output;
+
+/// The name of the directory of the environments in the output directory
+const ENVS_DIR: &str = "envs";
+
+/// The explicit spec of an environment, with the packages in installation order
+fn explicit_spec(records: &[RepoDataRecord], platform: Platform) -> ExplicitEnvironmentSpec {
+    let packages = PackageRecord::sort_topologically(records.to_vec())
+        .into_iter()
+        .map(|record| {
+            let package = &record.package_record;
+            // conda verifies md5 hashes, newer versions also `sha256:` hashes
+            let hash = package
+                .md5
+                .map(|md5| format!("{md5:x}"))
+                .or_else(|| package.sha256.map(|sha256| format!("sha256:{sha256:x}")));
+            let mut url = record.url.clone();
+            url.set_fragment(hash.as_deref());
+            ExplicitEnvironmentEntry { url }
+        })
+        .collect();
+    ExplicitEnvironmentSpec {
+        platform: Some(platform),
+        packages,
+    }
+}
+
+/// Write the explicit spec files of the build and host environments of `output`. Failing to
+/// write them is only reported as a warning.
+pub(crate) fn write_explicit_specs(output: &Output, dependencies: &FinalizedDependencies) {
+    let directories = &output.build_configuration.directories;
+    let output_env_dir = directories
+        .output_dir
+        .join(ENVS_DIR)
+        .join(output.target_platform().to_string())
+        .join(output.identifier());
+
+    let environments = [
+        (
+            "build_env.txt",
+            dependencies.build.as_ref(),
+            output.build_configuration.build_platform.platform,
+        ),
+        (
+            "host_env.txt",
+            dependencies.host.as_ref(),
+            output.build_configuration.host_platform.platform,
+        ),
+    ];
+    for (file_name, resolved, platform) in environments {
+        let Some(resolved) = resolved else {
+            continue;
+        };
+        let spec = explicit_spec(&resolved.resolved, platform);
+        for dir in [directories.work_dir.as_path(), output_env_dir.as_path()] {
+            if let Err(e) = write_spec(&spec, dir, file_name) {
+                tracing::warn!("Failed to write {file_name} to {}: {e}", dir.display());
+            }
+        }
+    }
+}
+
+fn write_spec(spec: &ExplicitEnvironmentSpec, dir: &Path, file_name: &str) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(file_name), spec.to_spec_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rattler_conda_types::{PackageName, VersionWithSource};
+    use rattler_digest::{Md5, Sha256, parse_digest_from_hex};
+    use url::Url;
+
+    use super::*;
+
+    fn record(name: &str, depends: &[&str], md5: Option<&str>, sha256: &str) -> RepoDataRecord {
+        let mut package = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            VersionWithSource::from_str("1.0").unwrap(),
+            "h0_0".to_string(),
+        );
+        package.depends = depends.iter().map(ToString::to_string).collect();
+        package.md5 = md5.and_then(parse_digest_from_hex::<Md5>);
+        package.sha256 = parse_digest_from_hex::<Sha256>(sha256);
+        RepoDataRecord {
+            url: Url::parse(&format!(
+                "https://conda.anaconda.org/conda-forge/linux-64/{name}-1.0-h0_0.conda"
+            ))
+            .unwrap(),
+            identifier: format!("{name}-1.0-h0_0.conda").parse().unwrap(),
+            channel: Some("https://conda.anaconda.org/conda-forge/".to_string()),
+            package_record: package,
+        }
+    }
+
+    #[test]
+    fn test_explicit_spec() {
+        let sha256 = "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3";
+        let records = [
+            record(
+                "python",
+                &["libzlib"],
+                Some("d41d8cd98f00b204e9800998ecf8427e"),
+                sha256,
+            ),
+            record("libzlib", &[], None, sha256),
+        ];
+        let spec = explicit_spec(&records, Platform::Linux64);
+        assert_eq!(
+            spec.to_spec_string(),
+            format!(
+                "# platform: linux-64\n\
+                 @EXPLICIT\n\
+                 https://conda.anaconda.org/conda-forge/linux-64/libzlib-1.0-h0_0.conda#sha256:{sha256}\n\
+                 https://conda.anaconda.org/conda-forge/linux-64/python-1.0-h0_0.conda#d41d8cd98f00b204e9800998ecf8427e\n"
+            )
+        );
+        // the file can be read by conda and rattler
+        let parsed = ExplicitEnvironmentSpec::from_str(&spec.to_spec_string()).unwrap();
+        assert_eq!(parsed.packages.len(), 2);
+    }
+}
@@ -1,8 +1,10 @@
 #![allow(missing_docs)]
 //! Render the dependencies to a final recipe
 
+mod explicit_spec;
 pub mod pin;
 pub mod reporters;
 pub mod resolved_dependencies;
 mod run_exports;
+pub mod run_exports_cache;
 pub mod solver;
@@ -19,10 +19,13 @@ use serde_with::{DisplayFromStr, serde_as};
 use thiserror::Error;
 
 use crate::{
+    linux::{sysroot, zig},
     metadata::{BuildConfiguration, Output, build_reindexed_channels},
     package_cache_reporter::PackageCacheReporter,
     render::{
+        explicit_spec,
         run_exports::filter_run_exports,
+        run_exports_cache::RunExportsCache,
         solver::{install_packages, solve_environment},
     },
     tool_configuration::{self, Configuration},
@@ -536,6 +539,15 @@ pub enum ResolveError {
 
     #[error("Could not reindex channels: {0}")]
     RefreshChannelError(std::io::Error),
+
+    #[error(
+        "The sysroot {package} {version} does not match the glibc baseline of the variant (c_stdlib_version: {baseline})"
+    )]
+    SysrootMismatch {
+        package: String,
+        version: String,
+        baseline: String,
+    },
 }
 
 /// Controls whether to download missing run exports during dependency resolution
@@ -662,17 +674,24 @@ impl RunExportsReporter for RunExportsProgressReporter {
     }
 }
 
-/// Collect run exports from the package cache and add them to the package
-/// records.
+/// Collect run exports from the run exports cache, the channels or the package
+/// cache and add them to the package records.
+#[allow(clippy::too_many_arguments)]
 async fn ensure_run_exports(
     records: &mut [RepoDataRecord],
     gateway: &Gateway,
+    run_exports_cache: &RunExportsCache,
     multi_progress: MultiProgress,
     progress_prefix: impl Into<Cow<'static, str>>,
     top_level_pb: Option<ProgressBar>,
     progress_style: ProgressStyle,
     finish_style: ProgressStyle,
 ) -> Result<(), RunExportExtractorError> {
+    let missing = run_exports_cache.load(records);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
     let progress_prefix: Cow<'static, str> = progress_prefix.into();
     let placement = top_level_pb
         .as_ref()
@@ -696,7 +715,9 @@ async fn ensure_run_exports(
 
     gateway
         .ensure_run_exports(records.iter_mut(), Some(reporter))
-        .await
+        .await?;
+    run_exports_cache.store(records, &missing);
+    Ok(())
 }
 
 pub async fn install_environments(
@@ -704,6 +725,8 @@ pub async fn install_environments(
     dependencies: &FinalizedDependencies,
     tool_configuration: &tool_configuration::Configuration,
 ) -> Result<(), ResolveError> {
+    explicit_spec::write_explicit_specs(output, dependencies);
+
     const EMPTY_RECORDS: Vec<RepoDataRecord> = Vec::new();
     install_packages(
         "build",
@@ -809,7 +832,8 @@ pub(crate) async fn resolve_dependencies(
     // For version-independent (abi3) packages, ignore run exports from `python` itself
     // (e.g. `python_abi` or `cross-python`) since these packages are Python-version
     // independent. Run exports from `python-abi3` (e.g. `cpython >=3.X`) are still kept. See CEP-20.
-    let ignore_run_exports = if output.recipe.build.python.version_independent {
+    // Free-threaded builds keep the `python_abi` marker (e.g. `*_cp313t`) of `python`.
+    let ignore_run_exports = if output.is_python_version_independent() {
         let mut ignore = requirements.ignore_run_exports.clone();
         let python: PackageName = "python".parse().expect("valid package name");
         ignore.from_package.push(python.clone());
@@ -819,13 +843,21 @@ pub(crate) async fn resolve_dependencies(
         requirements.ignore_run_exports.clone()
     };
 
+    let build_configuration = &output.build_configuration;
     let build_env = if !requirements.build.is_empty() && !merge_build_host {
-        let build_env_specs = apply_variant(
+        let mut build_env_specs = apply_variant(
             &requirements.build,
             &output.build_configuration,
             &compatibility_specs,
             true,
         )?;
+        zig::replace_compilers(build_configuration, &mut build_env_specs);
+        build_env_specs.extend(sysroot::sysroot_dependency(
+            build_configuration.build_platform.platform,
+            build_configuration.host_platform.platform,
+            &build_configuration.variant,
+            &build_env_specs,
+        )?);
 
         let match_specs = build_env_specs
             .iter()
@@ -844,6 +876,11 @@ pub(crate) async fn resolve_dependencies(
         )
         .await
         .map_err(|e| ResolveError::DependencyResolutionError(e.to_string()))?;
+        sysroot::validate_sysroot(
+            build_configuration.host_platform.platform,
+            &build_configuration.variant,
+            &resolved,
+        )?;
 
         // Optionally add run exports to records that don't have them yet by
         // downloading packages and extracting run_exports.json
@@ -858,6 +895,7 @@ pub(crate) async fn resolve_dependencies(
                     ensure_run_exports(
                         &mut resolved,
                         gateway.as_ref().unwrap(),
+                        &tool_configuration.run_exports_cache,
                         tool_configuration
                             .fancy_log_handler
                             .multi_progress()
@@ -911,12 +949,19 @@ pub(crate) async fn resolve_dependencies(
         .collect::<Vec<_>>();
     if merge_build_host {
         // add the requirements of build to host
-        let specs = apply_variant(
+        let mut specs = apply_variant(
             &requirements.build,
             &output.build_configuration,
             &compatibility_specs,
             true,
         )?;
+        zig::replace_compilers(build_configuration, &mut specs);
+        specs.extend(sysroot::sysroot_dependency(
+            build_configuration.build_platform.platform,
+            build_configuration.host_platform.platform,
+            &build_configuration.variant,
+            &specs,
+        )?);
         match_specs.extend(specs.iter().map(|s| s.spec().clone()));
     }
 
@@ -933,6 +978,11 @@ pub(crate) async fn resolve_dependencies(
         )
         .await
         .map_err(|e| ResolveError::DependencyResolutionError(e.to_string()))?;
+        sysroot::validate_sysroot(
+            build_configuration.host_platform.platform,
+            &build_configuration.variant,
+            &resolved,
+        )?;
 
         // Optionally add run exports to records that don't have them yet by
         // downloading packages and extracting run_exports.json
@@ -947,6 +997,7 @@ pub(crate) async fn resolve_dependencies(
                     ensure_run_exports(
                         &mut resolved,
                         gateway.as_ref().unwrap(),
+                        &tool_configuration.run_exports_cache,
                         tool_configuration
                             .fancy_log_handler
                             .multi_progress()
@@ -1109,6 +1160,63 @@ impl Output {
         })
     }
 
+    /// Solve the run environment of the output (without the package itself), e.g. to check
+    /// the run dependencies of a package. If it cannot be solved (e.g. because a subpackage
+    /// is not built yet), the records of the host environment that are run dependencies are
+    /// returned instead.
+    pub(crate) async fn solve_run_environment(
+        &self,
+        tool_configuration: &Configuration,
+    ) -> Vec<RepoDataRecord> {
+        let Some(dependencies) = &self.finalized_dependencies else {
+            return Vec::new();
+        };
+        let specs = dependencies
+            .run
+            .depends
+            .iter()
+            .map(|dependency| dependency.spec().clone())
+            .collect::<Vec<_>>();
+        if specs.is_empty() {
+            return Vec::new();
+        }
+        let configuration = &self.build_configuration;
+        match solve_environment(
+            "run",
+            &specs,
+            &configuration.host_platform,
+            &configuration.channels,
+            tool_configuration,
+            configuration.channel_priority,
+            configuration.solve_strategy,
+            configuration.exclude_newer,
+        )
+        .await
+        {
+            Ok(records) => records,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to solve the run environment, using the versions of the run dependencies in the host environment: {error}"
+                );
+                let host = dependencies
+                    .host
+                    .as_ref()
+                    .map(|host| host.resolved.as_slice());
+                host.unwrap_or_default()
+                    .iter()
+                    .filter(|record| {
+                        specs.iter().any(|spec| {
+                            spec.name
+                                .as_ref()
+                                .is_some_and(|name| name.matches(&record.package_record.name))
+                        })
+                    })
+                    .cloned()
+                    .collect()
+            }
+        }
+    }
+
     /// Install the environments of the outputs. Assumes that the dependencies
     /// for the environment have already been resolved.
     pub async fn install_environments(
@@ -0,0 +1,159 @@
+//! A persistent cache of the run exports of packages.
+//!
+//! The run exports of the packages in the build and host environments are read from the
+//! `run_exports.json` of their channel where it is available, otherwise the packages are
+//! downloaded and extracted. Both are slow for large environments and repeated in every build.
+//! Since a package never changes, its run exports are stored in
+//! `<cache dir>/run-exports/<sha256>.json` and read from there in later builds.
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use rattler_conda_types::{RepoDataRecord, package::RunExportsJson};
+
+/// The name of the directory of the run exports in the cache directory
+pub const RUN_EXPORTS_CACHE_DIR: &str = "run-exports";
+
+/// A directory with the run exports of packages, keyed by the SHA256 hash of the package
+#[derive(Debug, Clone)]
+pub struct RunExportsCache {
+    dir: PathBuf,
+}
+
+impl RunExportsCache {
+    /// The run exports cache in the cache directory of rattler-build
+    pub fn new(cache_dir: &Path) -> Self {
+        Self {
+            dir: cache_dir.join(RUN_EXPORTS_CACHE_DIR),
+        }
+    }
+
+    /// The path of the run exports of a package, if the package has a SHA256 hash
+    fn path(&self, record: &RepoDataRecord) -> Option<PathBuf> {
+        let sha256 = record.package_record.sha256?;
+        Some(self.dir.join(format!("{sha256:x}.json")))
+    }
+
+    /// Set the run exports of the records that do not have them yet from the cache. Returns
+    /// the indices of the records that are still missing them.
+    pub fn load(&self, records: &mut [RepoDataRecord]) -> Vec<usize> {
+        let mut missing = Vec::new();
+        for (index, record) in records.iter_mut().enumerate() {
+            if record.package_record.run_exports.is_some() {
+                continue;
+            }
+            let cached = self
+                .path(record)
+                .and_then(|path| fs::read(path).ok())
+                .and_then(|content| serde_json::from_slice::<RunExportsJson>(&content).ok());
+            match cached {
+                Some(run_exports) => record.package_record.run_exports = Some(run_exports),
+                None => missing.push(index),
+            }
+        }
+        missing
+    }
+
+    /// Store the run exports of the records at `indices`. Packages without run exports are
+    /// stored with empty run exports. Failing to write the cache is only logged.
+    pub fn store(&self, records: &[RepoDataRecord], indices: &[usize]) {
+        for record in indices.iter().filter_map(|&index| records.get(index)) {
+            let Some(path) = self.path(record) else {
+                continue;
+            };
+            let run_exports = record
+                .package_record
+                .run_exports
+                .clone()
+                .unwrap_or_default();
+            if let Err(e) = self.write(&path, &run_exports) {
+                tracing::debug!(
+                    "Failed to cache the run exports of {}: {e}",
+                    record.identifier
+                );
+            }
+        }
+    }
+
+    fn write(&self, path: &Path, run_exports: &RunExportsJson) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // concurrent builds must never read a partially written file
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        serde_json::to_writer(&mut file, run_exports)?;
+        file.flush()?;
+        file.persist(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rattler_conda_types::{PackageName, PackageRecord, VersionWithSource};
+    use rattler_digest::{Sha256, parse_digest_from_hex};
+    use url::Url;
+
+    use super::*;
+
+    fn record(name: &str, sha256: Option<&str>) -> RepoDataRecord {
+        let mut package = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            VersionWithSource::from_str("1.0").unwrap(),
+            "h0_0".to_string(),
+        );
+        package.sha256 = sha256.and_then(parse_digest_from_hex::<Sha256>);
+        RepoDataRecord {
+            url: Url::parse(&format!(
+                "https://conda.anaconda.org/conda-forge/linux-64/{name}-1.0-h0_0.conda"
+            ))
+            .unwrap(),
+            identifier: format!("{name}-1.0-h0_0.conda").parse().unwrap(),
+            channel: Some("https://conda.anaconda.org/conda-forge/".to_string()),
+            package_record: package,
+        }
+    }
+
+    #[test]
+    fn test_load_and_store() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = RunExportsCache::new(cache_dir.path());
+
+        let zlib = record(
+            "zlib",
+            Some("315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3"),
+        );
+        let python = record(
+            "python",
+            Some("01ba4719c80b6fe911b091a7c05124b64eeece964e09c058ef8f9805daca546b"),
+        );
+        let local = record("local", None);
+        let mut records = vec![zlib.clone(), python.clone(), local.clone()];
+        assert_eq!(cache.load(&mut records), [0, 1, 2]);
+
+        // the run exports are found (e.g. in the `run_exports.json` of the channel)
+        records[0].package_record.run_exports = Some(RunExportsJson {
+            weak: vec!["libzlib >=1.3.1,<2.0a0".to_string()],
+            ..Default::default()
+        });
+        cache.store(&records, &[0, 1, 2]);
+
+        let mut records = vec![zlib, python, local];
+        assert_eq!(cache.load(&mut records), [2]);
+        assert_eq!(
+            records[0].package_record.run_exports.as_ref().unwrap().weak,
+            ["libzlib >=1.3.1,<2.0a0"]
+        );
+        // packages without run exports are cached as well
+        assert!(
+            records[1]
+                .package_record
+                .run_exports
+                .as_ref()
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
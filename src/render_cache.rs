@@ -0,0 +1,292 @@
+//! The cache of rendered recipes (`--render-cache`).
+//!
+//! Rendering evaluates the Jinja expressions of a recipe for every combination of the variant
+//! configuration, which is repeated by every `build`, `build --render-only` and `outputs`
+//! invocation. With the render cache, the outputs that were found (the rendered recipes, their
+//! variants and hashes) are stored in `<cache dir>/rendered-recipes/<key>.json` and reused as
+//! long as the key is the same.
+//!
+//! The key is a hash of the version of rattler-build, the recipe file, the variant configuration
+//! files, the `--variant` overrides, the platforms, the experimental flag and the environment
+//! variables whose name occurs in the recipe or a variant configuration file (for `env.get`).
+//! Recipes that call `load_from_file` or the `git` functions are never cached, because they
+//! depend on files and repositories that are not part of the key.
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_build_jinja::Variable;
+use rattler_build_types::NormalizedKey;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{DiscoveredOutput, FoundVariants, get_rattler_build_version, opt::BuildData};
+
+/// The Jinja functions whose result depends on more than the inputs of the key
+const UNCACHEABLE_FUNCTIONS: [&str; 4] = [
+    "load_from_file",
+    "git.head_rev",
+    "git.latest_tag_rev",
+    "git.latest_tag",
+];
+
+/// A directory with rendered recipes
+#[derive(Debug, Clone)]
+pub(crate) struct RenderCache {
+    dir: PathBuf,
+}
+
+/// The variant variables that were used by a recipe or a staging cache
+type UsedVariant = BTreeMap<NormalizedKey, Variable>;
+
+/// The content of a cache entry
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    recipe_name: Option<String>,
+    outputs: Vec<CachedOutput>,
+}
+
+/// A discovered output with the fields that the rendered recipe does not serialize
+#[derive(Serialize, Deserialize)]
+struct CachedOutput {
+    output: DiscoveredOutput,
+    used_variant: UsedVariant,
+    staging_used_variants: Vec<UsedVariant>,
+}
+
+impl From<&DiscoveredOutput> for CachedOutput {
+    fn from(output: &DiscoveredOutput) -> Self {
+        Self {
+            output: output.clone(),
+            used_variant: output.recipe.used_variant.clone(),
+            staging_used_variants: output
+                .recipe
+                .staging_caches
+                .iter()
+                .map(|staging| staging.used_variant.clone())
+                .collect(),
+        }
+    }
+}
+
+impl From<CachedOutput> for DiscoveredOutput {
+    fn from(cached: CachedOutput) -> Self {
+        let mut output = cached.output;
+        output.recipe.used_variant = cached.used_variant;
+        for (staging, used_variant) in output
+            .recipe
+            .staging_caches
+            .iter_mut()
+            .zip(cached.staging_used_variants)
+        {
+            staging.used_variant = used_variant;
+        }
+        output
+    }
+}
+
+impl RenderCache {
+    /// The render cache in the cache directory of rattler-build
+    pub(crate) fn new(cache_dir: &Path) -> Self {
+        Self {
+            dir: cache_dir.join("rendered-recipes"),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    /// The outputs that were rendered for `key`, if they are cached
+    pub(crate) fn load(&self, key: &str) -> Option<FoundVariants> {
+        let path = self.path(key);
+        let content = fs::read(&path).ok()?;
+        match serde_json::from_slice::<CacheEntry>(&content) {
+            Ok(entry) => Some(FoundVariants {
+                outputs: entry.outputs.into_iter().map(Into::into).collect(),
+                recipe_name: entry.recipe_name,
+            }),
+            Err(e) => {
+                tracing::debug!(
+                    "Ignoring the invalid rendered recipe {}: {e}",
+                    path.display()
+                );
+                None
+            }
+        }
+    }
+
+    /// Store the outputs that were rendered for `key`. Failing to write the cache is only
+    /// reported as a warning.
+    pub(crate) fn store(&self, key: &str, found: &FoundVariants) {
+        let entry = CacheEntry {
+            recipe_name: found.recipe_name.clone(),
+            outputs: found.outputs.iter().map(Into::into).collect(),
+        };
+        let write = || -> miette::Result<()> {
+            fs::create_dir_all(&self.dir).into_diagnostic()?;
+            // concurrent invocations must never read a partially written file
+            let mut file = tempfile::NamedTempFile::new_in(&self.dir).into_diagnostic()?;
+            serde_json::to_writer(&mut file, &entry).into_diagnostic()?;
+            file.flush().into_diagnostic()?;
+            file.persist(self.path(key)).into_diagnostic()?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            tracing::warn!(
+                "Failed to store the rendered recipe in {}: {e}",
+                self.dir.display()
+            );
+        }
+    }
+}
+
+/// Whether the rendered recipe only depends on the inputs of the key
+fn is_cacheable(recipe_content: &str) -> bool {
+    !UNCACHEABLE_FUNCTIONS
+        .iter()
+        .any(|function| recipe_content.contains(function))
+}
+
+/// The environment variables whose name occurs in one of `texts`
+fn referenced_env_vars(
+    vars: impl IntoIterator<Item = (String, String)>,
+    texts: &[&str],
+) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .filter(|(name, _)| texts.iter().any(|text| text.contains(name.as_str())))
+        .collect()
+}
+
+/// Compute the key of a recipe in the render cache, or `None` if the recipe cannot be cached
+pub(crate) fn cache_key(
+    build_data: &BuildData,
+    recipe_path: &Path,
+    recipe_content: &str,
+    variant_configs: &[PathBuf],
+) -> miette::Result<Option<String>> {
+    if !is_cacheable(recipe_content) {
+        tracing::debug!(
+            "Not caching the rendered recipe {}, it reads files or git repositories",
+            recipe_path.display()
+        );
+        return Ok(None);
+    }
+
+    let variant_configs = variant_configs
+        .iter()
+        .map(|path| Ok((path, fs::read_to_string(path).into_diagnostic()?)))
+        .collect::<miette::Result<Vec<_>>>()?;
+    let mut texts = vec![recipe_content];
+    texts.extend(variant_configs.iter().map(|(_, content)| content.as_str()));
+
+    let inputs = (
+        get_rattler_build_version(),
+        recipe_path,
+        recipe_content,
+        &variant_configs,
+        build_data
+            .variant_overrides
+            .iter()
+            .collect::<BTreeMap<_, _>>(),
+        build_data.target_platform,
+        build_data.build_platform,
+        build_data.host_platform,
+        build_data.common.experimental,
+        referenced_env_vars(std::env::vars(), &texts),
+    );
+
+    let mut hasher = Sha256::new();
+    inputs
+        .serialize(&mut serde_json::Serializer::new(&mut hasher))
+        .into_diagnostic()?;
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use rattler_build_variant_config::VariantConfig;
+    use rattler_conda_types::Platform;
+
+    use super::*;
+
+    #[test]
+    fn test_is_cacheable() {
+        assert!(is_cacheable("package:\n  name: ${{ name }}\n"));
+        assert!(is_cacheable("version: ${{ env.get('VERSION') }}\n"));
+        assert!(!is_cacheable(
+            "context:\n  data: ${{ load_from_file('data.json') }}\n"
+        ));
+        assert!(!is_cacheable("version: ${{ git.latest_tag('.') }}\n"));
+    }
+
+    #[test]
+    fn test_referenced_env_vars() {
+        let vars = [
+            ("MY_VERSION", "1.2.3"),
+            ("HOME", "/home/user"),
+            ("BLAS_IMPL", "openblas"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let referenced = referenced_env_vars(
+            vars,
+            &[
+                "version: ${{ env.get('MY_VERSION') }}",
+                "blas: [${{ env.get('BLAS_IMPL') }}]",
+            ],
+        );
+        assert_eq!(
+            referenced.keys().collect::<Vec<_>>(),
+            ["BLAS_IMPL", "MY_VERSION"]
+        );
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let recipe = r#"
+package:
+  name: foo
+  version: "1.0"
+requirements:
+  host:
+    - python ${{ python }}.*
+"#;
+        let mut variant_config = VariantConfig::default();
+        variant_config.variants.insert(
+            "python".into(),
+            vec![Variable::from("3.12"), Variable::from("3.13")],
+        );
+        let found = crate::find_variants(
+            &variant_config,
+            Path::new("recipe.yaml"),
+            recipe,
+            Platform::Linux64,
+            Platform::Linux64,
+            Platform::Linux64,
+            false,
+        )
+        .unwrap();
+        assert_eq!(found.outputs.len(), 2);
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = RenderCache::new(cache_dir.path());
+        assert!(cache.load("key").is_none());
+
+        cache.store("key", &found);
+        let loaded = cache.load("key").unwrap();
+        assert_eq!(loaded.recipe_name, found.recipe_name);
+        for (loaded, rendered) in loaded.outputs.iter().zip(&found.outputs) {
+            assert_eq!(loaded.build_string, rendered.build_string);
+            assert_eq!(loaded.used_vars, rendered.used_vars);
+            assert_eq!(loaded.recipe, rendered.recipe);
+            assert_eq!(loaded.hash, rendered.hash);
+        }
+
+        fs::write(cache.path("invalid"), "{").unwrap();
+        assert!(cache.load("invalid").is_none());
+    }
+}
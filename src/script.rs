@@ -7,7 +7,7 @@ use indexmap::IndexMap;
 use minijinja::Value;
 use rattler_build_jinja::Jinja;
 use rattler_conda_types::Platform;
-use std::{collections::HashMap, collections::HashSet};
+use std::{collections::HashMap, collections::HashSet, sync::Arc};
 
 // Re-export from rattler_build_script
 pub use rattler_build_script::{
@@ -17,6 +17,7 @@ pub use rattler_build_script::{
 
 use crate::{
     env_vars::{self},
+    events,
     metadata::Output,
 };
 
@@ -77,7 +78,7 @@ impl Output {
             run_prefix: host_prefix,
             execution_platform: Platform::current(),
             work_dir: work_dir.clone(),
-            sandbox_config: self.build_configuration.sandbox_config().cloned(),
+            sandbox_config: self.build_configuration.sandbox_config(),
             debug: ScriptDebug::new(self.build_configuration.debug.is_enabled()),
         })
     }
@@ -120,24 +121,35 @@ impl Output {
             jinja.render_str(template).map_err(|e| e.to_string())
         };
 
-        self.recipe
-            .build()
-            .script
-            .run_script(
-                exec_args
-                    .env_vars
-                    .into_iter()
-                    .map(|(k, v)| (k, Some(v)))
-                    .collect(),
-                &self.build_configuration.directories.work_dir,
-                &self.build_configuration.directories.recipe_dir,
-                &self.build_configuration.directories.host_prefix,
-                build_prefix,
-                Some(jinja_renderer),
-                self.build_configuration.sandbox_config(),
-                ScriptDebug::new(self.build_configuration.debug.is_enabled()),
-            )
-            .await?;
+        let sandbox_config = self.build_configuration.sandbox_config();
+        let run_script = self.recipe.build().script.run_script(
+            exec_args
+                .env_vars
+                .into_iter()
+                .map(|(k, v)| (k, Some(v)))
+                .collect(),
+            &self.build_configuration.directories.work_dir,
+            &self.build_configuration.directories.recipe_dir,
+            &self.build_configuration.directories.host_prefix,
+            build_prefix,
+            Some(jinja_renderer),
+            sandbox_config.as_ref(),
+            ScriptDebug::new(self.build_configuration.debug.is_enabled()),
+        );
+
+        if events::active() {
+            let package = self.identifier();
+            let handler = Arc::new(move |line: &str, stderr: bool| {
+                events::emit(|| events::Event::ScriptOutput {
+                    package: package.clone(),
+                    line: line.to_string(),
+                    stderr,
+                })
+            });
+            rattler_build_script::with_output_handler(handler, run_script).await?;
+        } else {
+            run_script.await?;
+        }
 
         Ok(())
     }
@@ -0,0 +1,334 @@
+//! A builder API to build recipes from other Rust tools.
+//!
+//! A [`BuildSession`] renders, builds and tests recipes like `rattler-build build`, but it
+//! does not print anything to the console and returns typed results: the built packages with
+//! their log files and warnings, the test results and (optionally) the captured log output.
+//! The logs of the build are emitted with `tracing`, so the caller can install any subscriber.
+//! Like the command line, the build needs a multi-threaded tokio runtime. The progress of the
+//! build (rendered outputs, solved environments, script output, packages and test results) can
+//! be followed with [`BuildSession::on_event`].
+//!
+//! ```no_run
+//! use rattler_build::session::BuildSession;
+//! use rattler_conda_types::Platform;
+//!
+//! # async fn build() -> miette::Result<()> {
+//! let result = BuildSession::new()
+//!     .recipe("recipe/recipe.yaml")
+//!     .target_platform(Platform::Linux64)
+//!     .channel("conda-forge".parse().unwrap())
+//!     .run()
+//!     .await?;
+//!
+//! for package in &result.packages {
+//!     println!("{} -> {}", package.identifier, package.path.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, time::Duration};
+
+use rattler_build_jinja::Variable;
+use rattler_build_types::NormalizedKey;
+use rattler_conda_types::{NamedChannelOrUrl, PackageName, Platform};
+use tracing::instrument::WithSubscriber;
+
+use crate::{
+    build_log,
+    config::Config,
+    console_utils::LogCapture,
+    events::{self, Event},
+    get_recipe_path, get_tool_config, hooks,
+    metadata::Output,
+    opt::{BuildData, BuildOpts},
+    package_test::{TestReport, TestSuiteReport},
+    render_recipes, run_build_from_args, skip_noarch,
+    tool_configuration::{SkipExisting, TestStrategy},
+};
+
+/// A package that was built by a [`BuildSession`]
+#[derive(Debug, Clone)]
+pub struct BuiltPackage {
+    /// The name of the package
+    pub name: PackageName,
+    /// The version of the package
+    pub version: String,
+    /// The build string of the package
+    pub build_string: String,
+    /// The subdir of the package, e.g. `linux-64` or `noarch`
+    pub subdir: Platform,
+    /// The identifier of the package (`name-version-build`)
+    pub identifier: String,
+    /// The variant that the package was built with
+    pub variant: BTreeMap<NormalizedKey, Variable>,
+    /// The path of the package file
+    pub path: PathBuf,
+    /// The log file of the build in `<output_dir>/logs`, if the log was written
+    pub log_file: Option<PathBuf>,
+    /// The warnings that were recorded during the build
+    pub warnings: Vec<String>,
+    /// How long the build took
+    pub duration: Option<Duration>,
+}
+
+impl BuiltPackage {
+    /// The built package of an output, or `None` if no package was recorded for it
+    fn from_output(output: &Output) -> Option<Self> {
+        let summary = output.build_summary.lock().unwrap();
+        let path = summary.artifact.clone()?;
+        let duration = summary
+            .build_start
+            .zip(summary.build_end)
+            .and_then(|(start, end)| (end - start).to_std().ok());
+        let log_file = Some(build_log::log_path(output)).filter(|path| path.is_file());
+        Some(Self {
+            name: output.name().clone(),
+            version: output.version().to_string(),
+            build_string: output.build_string().to_string(),
+            subdir: *output.target_platform(),
+            identifier: output.identifier(),
+            variant: output.variant().clone(),
+            path,
+            log_file,
+            warnings: summary.warnings.clone(),
+            duration,
+        })
+    }
+}
+
+/// The result of [`BuildSession::run`]
+#[derive(Debug, Clone, Default)]
+pub struct BuildResult {
+    /// The packages that were built
+    pub packages: Vec<BuiltPackage>,
+    /// The results of the tests of the built packages
+    pub tests: Vec<TestSuiteReport>,
+    /// The log output of the build, if it was captured with [`BuildSession::capture_logs`]
+    pub log: Option<String>,
+}
+
+/// Builds recipes with the options of `rattler-build build`, without the command line
+#[derive(Clone, Default)]
+pub struct BuildSession {
+    opts: BuildOpts,
+    config: Option<Config>,
+    capture_logs: bool,
+    subscribers: Vec<events::Subscriber>,
+}
+
+impl BuildSession {
+    /// Create a session with the default options of `rattler-build build`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a recipe file or a directory containing `recipe.yaml`. Without a recipe, the
+    /// `recipe.yaml` in the current directory is built.
+    pub fn recipe(mut self, recipe: impl Into<PathBuf>) -> Self {
+        self.opts.recipes.push(recipe.into());
+        self
+    }
+
+    /// Add a target platform to build the packages for (defaults to the current platform)
+    pub fn target_platform(mut self, platform: Platform) -> Self {
+        self.opts.target_platform.push(platform);
+        self
+    }
+
+    /// Set the build platform (defaults to the current platform)
+    pub fn build_platform(mut self, platform: Platform) -> Self {
+        self.opts.build_platform = Some(platform);
+        self
+    }
+
+    /// Set the host platform (defaults to the target platform)
+    pub fn host_platform(mut self, platform: Platform) -> Self {
+        self.opts.host_platform = Some(platform);
+        self
+    }
+
+    /// Add a channel to search for dependencies in (defaults to `conda-forge`)
+    pub fn channel(mut self, channel: NamedChannelOrUrl) -> Self {
+        self.opts.channels.get_or_insert_default().push(channel);
+        self
+    }
+
+    /// Add a variant configuration file
+    pub fn variant_config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.opts
+            .variant_config
+            .get_or_insert_default()
+            .push(path.into());
+        self
+    }
+
+    /// Override the values of a variant key, as with `--variant key=value`
+    pub fn variant<I, S>(mut self, key: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.opts
+            .variant_overrides
+            .push((key.into(), values.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Set the output directory of the packages (defaults to `./output`)
+    pub fn output_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.opts.common.output_dir = Some(path.into());
+        self
+    }
+
+    /// Set whether and where the tests of the packages are run
+    pub fn test_strategy(mut self, strategy: TestStrategy) -> Self {
+        self.opts.test = Some(strategy);
+        self
+    }
+
+    /// Skip the packages that already exist in the output directory or the channels
+    pub fn skip_existing(mut self, skip_existing: SkipExisting) -> Self {
+        self.opts.skip_existing = Some(skip_existing);
+        self
+    }
+
+    /// Use the settings of a configuration file (see [`crate::config::load_config`]). Unlike
+    /// the command line, the session does not search for a configuration file itself.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Capture the log output of the build into [`BuildResult::log`] instead of passing it to
+    /// the subscriber of the caller
+    pub fn capture_logs(mut self, capture_logs: bool) -> Self {
+        self.capture_logs = capture_logs;
+        self
+    }
+
+    /// Call `f` with the events of the build while the session runs. The events are those of
+    /// `--output-format json` and the lines of output of the build scripts. Events are emitted
+    /// for the whole process, so sessions that run at the same time receive each other's events.
+    pub fn on_event(mut self, f: impl Fn(&Event) + Send + Sync + 'static) -> Self {
+        self.subscribers.push(Arc::new(f));
+        self
+    }
+
+    /// Change any other option of `rattler-build build`
+    pub fn with_opts(mut self, f: impl FnOnce(&mut BuildOpts)) -> Self {
+        f(&mut self.opts);
+        self
+    }
+
+    /// The paths of the recipes and the build data of the session
+    fn prepare(&self) -> miette::Result<(Vec<PathBuf>, BuildData)> {
+        let recipes = if self.opts.recipes.is_empty() {
+            vec![PathBuf::from(".")]
+        } else {
+            self.opts.recipes.clone()
+        };
+        let recipe_paths = recipes
+            .iter()
+            .map(|recipe| get_recipe_path(recipe))
+            .collect::<miette::Result<Vec<_>>>()?;
+        let build_data = BuildData::from_opts_and_config(self.opts.clone(), self.config.clone());
+        Ok((recipe_paths, build_data))
+    }
+
+    /// Register the event subscribers of the session
+    fn subscribe(&self) -> Vec<events::Subscription> {
+        self.subscribers
+            .iter()
+            .map(|subscriber| events::subscribe(subscriber.clone()))
+            .collect()
+    }
+
+    /// Render the recipes for all variants and target platforms, without building anything
+    pub async fn render(&self) -> miette::Result<Vec<Output>> {
+        let _subscriptions = self.subscribe();
+        let (recipe_paths, build_data) = self.prepare()?;
+        let tool_config = get_tool_config(&build_data, &None)?;
+        render_recipes(&recipe_paths, &build_data, &tool_config).await
+    }
+
+    /// Render, build and test the recipes
+    pub async fn run(&self) -> miette::Result<BuildResult> {
+        let _subscriptions = self.subscribe();
+        if !self.capture_logs {
+            return self.build().await;
+        }
+        let capture = LogCapture::default();
+        let mut result = self.build().with_subscriber(capture.subscriber()).await?;
+        result.log = Some(capture.output());
+        Ok(result)
+    }
+
+    async fn build(&self) -> miette::Result<BuildResult> {
+        let (recipe_paths, build_data) = self.prepare()?;
+        let mut tool_config = get_tool_config(&build_data, &None)?;
+        // collect the test results, also if no report file is written
+        let test_report = tool_config
+            .test_report
+            .get_or_insert_with(TestReport::default)
+            .clone();
+
+        let outputs = render_recipes(&recipe_paths, &build_data, &tool_config).await?;
+        let outputs = skip_noarch(outputs, &tool_config).await?;
+
+        let started = chrono::Utc::now();
+        let client = tool_config.client.clone();
+        let result = run_build_from_args(
+            outputs.clone(),
+            tool_config,
+            build_data.markdown_summary.as_deref(),
+            build_data.profile.as_deref(),
+        )
+        .await;
+        let (built, result) = match result {
+            Ok(built) => (built, Ok(())),
+            Err(e) => (Vec::new(), Err(e)),
+        };
+        hooks::fire(&build_data.hooks, &outputs, &result, started, &client).await;
+        result?;
+
+        Ok(BuildResult {
+            packages: built.iter().filter_map(BuiltPackage::from_output).collect(),
+            tests: test_report.suites(),
+            log: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(dir.path().join("recipe.yaml"), "").unwrap();
+        let session = BuildSession::new()
+            .recipe(dir.path())
+            .target_platform(Platform::Linux64)
+            .target_platform(Platform::OsxArm64)
+            .variant("python", ["3.12", "3.13"])
+            .output_dir(dir.path().join("output"))
+            .test_strategy(TestStrategy::Skip)
+            .with_opts(|opts| opts.no_include_recipe = true);
+
+        let (recipe_paths, build_data) = session.prepare().unwrap();
+        assert_eq!(
+            recipe_paths,
+            vec![dunce::canonicalize(dir.path().join("recipe.yaml")).unwrap()]
+        );
+        assert_eq!(build_data.target_platform, Platform::Linux64);
+        assert_eq!(build_data.extra_target_platforms, vec![Platform::OsxArm64]);
+        assert_eq!(
+            build_data.variant_overrides.get("python"),
+            Some(&vec!["3.12".to_string(), "3.13".to_string()])
+        );
+        assert_eq!(build_data.common.output_dir, dir.path().join("output"));
+        assert!(build_data.no_include_recipe);
+        assert!(matches!(build_data.test, TestStrategy::Skip));
+    }
+}
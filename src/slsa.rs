@@ -0,0 +1,506 @@
+//! SLSA provenance of the built packages (`build --slsa-provenance`).
+//!
+//! For every package, an [in-toto](https://in-toto.io) statement with a
+//! [SLSA v1 provenance](https://slsa.dev/spec/v1.0/provenance) predicate is written next to the
+//! package (`<package>.intoto.json`). It records the builder (the CI job or the local machine),
+//! the recipe and the git repository and commit that it belongs to, the variant, the sources and
+//! the resolved packages of the build and host environments. With `signed`, the statement is
+//! also signed with `cosign attest-blob`, which writes a Sigstore bundle
+//! (`<package>.sigstore.json`).
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use chrono::{DateTime, Utc};
+use fs_err as fs;
+use rattler_build_recipe::stage1::{Source, source::GitRev};
+use rattler_conda_types::RepoDataRecord;
+use serde::Serialize;
+
+use crate::{
+    get_rattler_build_version,
+    metadata::Output,
+    system_tools::{SystemTools, Tool},
+};
+
+/// The type of the in-toto statement
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+/// The type of the SLSA provenance predicate
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+/// The build type of rattler-build, which defines the parameters of the build definition
+const BUILD_TYPE: &str = "https://rattler-build.prefix.dev/slsa/build-type/v1";
+
+/// Whether and how the provenance of the packages is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SlsaProvenance {
+    /// Write the provenance statement
+    Unsigned,
+    /// Write the provenance statement and sign it with `cosign`
+    Signed,
+}
+
+/// An error while writing the provenance of a package
+#[derive(Debug, thiserror::Error)]
+pub enum SlsaError {
+    /// The statement could not be written
+    #[error("failed to write the provenance statement: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The statement could not be serialized
+    #[error("failed to serialize the provenance statement: {0}")]
+    Serialize(#[from] serde_json::Error),
+
+    /// `cosign` was not found
+    #[error("failed to find `cosign` to sign the provenance ({0})")]
+    CosignNotFound(which::Error),
+
+    /// `cosign` failed to sign the statement
+    #[error("failed to sign the provenance with cosign: {0}")]
+    SigningFailed(String),
+}
+
+/// An artifact or resource with its digests (`ResourceDescriptor` of in-toto)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResourceDescriptor {
+    /// The name of the resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The URI of the resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// The digests of the resource, e.g. `sha256` or `gitCommit`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub digest: BTreeMap<String, String>,
+    /// Additional information, e.g. the environment that a package was installed in
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// An in-toto statement about a package
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    statement_type: &'static str,
+    subject: Vec<ResourceDescriptor>,
+    #[serde(rename = "predicateType")]
+    predicate_type: &'static str,
+    predicate: Provenance,
+}
+
+/// The SLSA provenance predicate
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    build_definition: BuildDefinition,
+    run_details: RunDetails,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildDefinition {
+    build_type: &'static str,
+    external_parameters: ExternalParameters,
+    internal_parameters: InternalParameters,
+    resolved_dependencies: Vec<ResourceDescriptor>,
+}
+
+/// The parameters of the build that are under the control of the user
+#[derive(Debug, Clone, Serialize)]
+struct ExternalParameters {
+    /// The recipe, with the repository and commit it belongs to
+    recipe: ResourceDescriptor,
+    target_platform: String,
+    variant: BTreeMap<String, String>,
+}
+
+/// The parameters of the build that are set by the builder
+#[derive(Debug, Clone, Serialize)]
+struct InternalParameters {
+    build_platform: String,
+    host_platform: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunDetails {
+    builder: Builder,
+    metadata: BuildMetadata,
+}
+
+/// The identity of the builder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Builder {
+    id: String,
+    version: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    invocation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_on: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_on: Option<DateTime<Utc>>,
+}
+
+/// The builder and the ID of the CI job, read with `env`. On GitHub Actions and GitLab CI the
+/// builder is the workflow (or project) and the invocation is the job, otherwise the builder is
+/// rattler-build on the local machine.
+fn builder(env: impl Fn(&str) -> Option<String>) -> (Builder, Option<String>) {
+    let version = BTreeMap::from([(
+        "rattler-build".to_string(),
+        get_rattler_build_version().to_string(),
+    )]);
+    let (id, invocation_id) = if env("GITHUB_ACTIONS").as_deref() == Some("true") {
+        let server = env("GITHUB_SERVER_URL").unwrap_or("https://github.com".to_string());
+        let workflow = env("GITHUB_WORKFLOW_REF").unwrap_or_default();
+        let invocation = match (env("GITHUB_REPOSITORY"), env("GITHUB_RUN_ID")) {
+            (Some(repository), Some(run_id)) => Some(format!(
+                "{server}/{repository}/actions/runs/{run_id}/attempts/{}",
+                env("GITHUB_RUN_ATTEMPT").unwrap_or("1".to_string())
+            )),
+            _ => None,
+        };
+        (format!("{server}/{workflow}"), invocation)
+    } else if env("GITLAB_CI").as_deref() == Some("true") {
+        let project = env("CI_PROJECT_URL").unwrap_or_default();
+        (project, env("CI_JOB_URL"))
+    } else {
+        (
+            "https://rattler-build.prefix.dev/slsa/builder/local".to_string(),
+            None,
+        )
+    };
+    (Builder { id, version }, invocation_id)
+}
+
+/// Run `git` in `dir` and return its trimmed output
+fn git(git: &Path, dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new(git)
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|output| !output.is_empty())
+}
+
+/// The recipe with the git repository and commit of the recipe directory. Outside of a git
+/// checkout, the repository and commit of the CI job are used.
+fn recipe_descriptor(
+    recipe_path: &Path,
+    system_tools: &SystemTools,
+    env: impl Fn(&str) -> Option<String>,
+) -> ResourceDescriptor {
+    let recipe_dir = recipe_path.parent().unwrap_or(recipe_path);
+    let (repository, commit) = match system_tools.find_tool(Tool::Git) {
+        Ok(path) => (
+            git(&path, recipe_dir, &["remote", "get-url", "origin"]),
+            git(&path, recipe_dir, &["rev-parse", "HEAD"]),
+        ),
+        Err(_) => (None, None),
+    };
+    let (repository, commit) = match (repository, commit) {
+        (repository, Some(commit)) => (repository, Some(commit)),
+        _ => match (env("GITHUB_REPOSITORY"), env("GITHUB_SHA")) {
+            (Some(repository), Some(sha)) => (
+                Some(format!(
+                    "{}/{repository}",
+                    env("GITHUB_SERVER_URL").unwrap_or("https://github.com".to_string())
+                )),
+                Some(sha),
+            ),
+            _ => (env("CI_PROJECT_URL"), env("CI_COMMIT_SHA")),
+        },
+    };
+
+    ResourceDescriptor {
+        name: recipe_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string()),
+        uri: repository.map(|repository| format!("git+{repository}")),
+        digest: commit
+            .map(|commit| BTreeMap::from([("gitCommit".to_string(), commit)]))
+            .unwrap_or_default(),
+        annotations: BTreeMap::new(),
+    }
+}
+
+/// The sources of the output with their digests
+fn source_descriptors(sources: &[Source]) -> Vec<ResourceDescriptor> {
+    let annotations = BTreeMap::from([("kind".to_string(), "source".to_string())]);
+    sources
+        .iter()
+        .map(|source| match source {
+            Source::Git(git) => ResourceDescriptor {
+                name: None,
+                uri: Some(format!("git+{}", git.url)),
+                digest: match &git.rev {
+                    GitRev::Commit(commit) => {
+                        BTreeMap::from([("gitCommit".to_string(), commit.clone())])
+                    }
+                    _ => BTreeMap::new(),
+                },
+                annotations: annotations.clone(),
+            },
+            Source::Url(url) => ResourceDescriptor {
+                name: url.file_name.clone(),
+                uri: url.url.first().map(|url| url.to_string()),
+                digest: url
+                    .sha256
+                    .map(|sha| BTreeMap::from([("sha256".to_string(), format!("{sha:x}"))]))
+                    .unwrap_or_default(),
+                annotations: annotations.clone(),
+            },
+            Source::Path(path) => ResourceDescriptor {
+                name: Some(path.path.display().to_string()),
+                uri: None,
+                digest: path
+                    .sha256
+                    .map(|sha| BTreeMap::from([("sha256".to_string(), format!("{sha:x}"))]))
+                    .unwrap_or_default(),
+                annotations: annotations.clone(),
+            },
+        })
+        .collect()
+}
+
+/// The resolved packages of an environment with their digests
+fn package_descriptors(environment: &str, records: &[RepoDataRecord]) -> Vec<ResourceDescriptor> {
+    records
+        .iter()
+        .map(|record| ResourceDescriptor {
+            name: Some(record.identifier.to_string()),
+            uri: Some(record.url.to_string()),
+            digest: record
+                .package_record
+                .sha256
+                .map(|sha| BTreeMap::from([("sha256".to_string(), format!("{sha:x}"))]))
+                .unwrap_or_default(),
+            annotations: BTreeMap::from([("environment".to_string(), environment.to_string())]),
+        })
+        .collect()
+}
+
+/// The provenance statement of the package `archive` of `output`
+pub fn statement(output: &Output, archive: &Path) -> Result<Statement, SlsaError> {
+    statement_with_env(output, archive, |key| std::env::var(key).ok())
+}
+
+fn statement_with_env(
+    output: &Output,
+    archive: &Path,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Statement, SlsaError> {
+    let sha256 = rattler_digest::compute_file_digest::<rattler_digest::Sha256>(archive)?;
+    let subject = ResourceDescriptor {
+        name: archive
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string()),
+        uri: None,
+        digest: BTreeMap::from([("sha256".to_string(), format!("{sha256:x}"))]),
+        annotations: BTreeMap::new(),
+    };
+
+    let configuration = &output.build_configuration;
+    let mut resolved_dependencies = source_descriptors(
+        output
+            .finalized_cache_sources
+            .iter()
+            .chain(&output.finalized_sources)
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .as_slice(),
+    );
+    if let Some(dependencies) = &output.finalized_dependencies {
+        for (environment, resolved) in
+            [("build", &dependencies.build), ("host", &dependencies.host)]
+        {
+            if let Some(resolved) = resolved {
+                resolved_dependencies.extend(package_descriptors(environment, &resolved.resolved));
+            }
+        }
+    }
+
+    let (builder, invocation_id) = builder(&env);
+    let summary = output.build_summary.lock().unwrap();
+    Ok(Statement {
+        statement_type: STATEMENT_TYPE,
+        subject: vec![subject],
+        predicate_type: PREDICATE_TYPE,
+        predicate: Provenance {
+            build_definition: BuildDefinition {
+                build_type: BUILD_TYPE,
+                external_parameters: ExternalParameters {
+                    recipe: recipe_descriptor(
+                        &configuration.directories.recipe_path,
+                        &output.system_tools,
+                        &env,
+                    ),
+                    target_platform: configuration.target_platform.to_string(),
+                    variant: output
+                        .variant()
+                        .iter()
+                        .map(|(key, value)| (key.normalize(), value.to_string()))
+                        .collect(),
+                },
+                internal_parameters: InternalParameters {
+                    build_platform: configuration.build_platform.platform.to_string(),
+                    host_platform: configuration.host_platform.platform.to_string(),
+                },
+                resolved_dependencies,
+            },
+            run_details: RunDetails {
+                builder,
+                metadata: BuildMetadata {
+                    invocation_id,
+                    started_on: summary.build_start,
+                    finished_on: Some(Utc::now()),
+                },
+            },
+        },
+    })
+}
+
+/// The path of the provenance statement of a package (`<package>.intoto.json`)
+pub fn statement_path(archive: &Path) -> PathBuf {
+    sibling(archive, "intoto.json")
+}
+
+/// The path of the signed provenance of a package (`<package>.sigstore.json`)
+pub fn bundle_path(archive: &Path) -> PathBuf {
+    sibling(archive, "sigstore.json")
+}
+
+fn sibling(archive: &Path, extension: &str) -> PathBuf {
+    let mut name = archive.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    archive.with_file_name(name)
+}
+
+/// Sign the provenance of `archive` with `cosign attest-blob`. `cosign` uses keyless signing on
+/// CI, or the key in `COSIGN_KEY` (e.g. `cosign.key` or a KMS URI).
+fn sign(
+    archive: &Path,
+    statement: &Statement,
+    system_tools: &SystemTools,
+) -> Result<(), SlsaError> {
+    let cosign = system_tools
+        .find_tool(Tool::Cosign)
+        .map_err(SlsaError::CosignNotFound)?;
+
+    let predicate = tempfile::NamedTempFile::new()?;
+    serde_json::to_writer(predicate.as_file(), &statement.predicate)?;
+
+    let mut command = Command::new(cosign);
+    command
+        .args(["attest-blob", "--yes", "--type", "slsaprovenance1"])
+        .arg("--predicate")
+        .arg(predicate.path())
+        .arg("--bundle")
+        .arg(bundle_path(archive));
+    if let Ok(key) = std::env::var("COSIGN_KEY") {
+        command.args(["--key", &key]);
+    }
+    let output = command.arg(archive).output()?;
+    if !output.status.success() {
+        return Err(SlsaError::SigningFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Write the provenance of the package `archive` of `output` next to the package, and sign it
+/// if requested
+pub fn write_provenance(
+    output: &Output,
+    archive: &Path,
+    mode: SlsaProvenance,
+) -> Result<(), SlsaError> {
+    let statement = statement(output, archive)?;
+    let path = statement_path(archive);
+    fs::write(&path, serde_json::to_string_pretty(&statement)?)?;
+    tracing::info!("Wrote the SLSA provenance to {}", path.display());
+
+    if mode == SlsaProvenance::Signed {
+        sign(archive, &statement, &output.system_tools)?;
+        tracing::info!(
+            "Signed the SLSA provenance ({})",
+            bundle_path(archive).display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| {
+            vars.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_builder() {
+        let (github, invocation) = builder(env(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REPOSITORY", "org/forge"),
+            ("GITHUB_RUN_ID", "42"),
+            (
+                "GITHUB_WORKFLOW_REF",
+                "org/forge/.github/workflows/build.yml@refs/heads/main",
+            ),
+        ]));
+        assert_eq!(
+            github.id,
+            "https://github.com/org/forge/.github/workflows/build.yml@refs/heads/main"
+        );
+        assert_eq!(
+            invocation.as_deref(),
+            Some("https://github.com/org/forge/actions/runs/42/attempts/1")
+        );
+
+        let (gitlab, invocation) = builder(env(&[
+            ("GITLAB_CI", "true"),
+            ("CI_PROJECT_URL", "https://gitlab.com/org/forge"),
+            ("CI_JOB_URL", "https://gitlab.com/org/forge/-/jobs/7"),
+        ]));
+        assert_eq!(gitlab.id, "https://gitlab.com/org/forge");
+        assert_eq!(
+            invocation.as_deref(),
+            Some("https://gitlab.com/org/forge/-/jobs/7")
+        );
+
+        let (local, invocation) = builder(env(&[]));
+        assert!(local.id.ends_with("/builder/local"));
+        assert!(invocation.is_none());
+    }
+
+    #[test]
+    fn test_paths() {
+        let archive = Path::new("output/linux-64/foo-1.0-h0_0.conda");
+        assert_eq!(
+            statement_path(archive),
+            Path::new("output/linux-64/foo-1.0-h0_0.conda.intoto.json")
+        );
+        assert_eq!(
+            bundle_path(archive),
+            Path::new("output/linux-64/foo-1.0-h0_0.conda.sigstore.json")
+        );
+    }
+}
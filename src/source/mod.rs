@@ -1,18 +1,21 @@
 //! Module for fetching sources and applying patches
 use std::{
+    collections::HashMap,
     ffi::OsStr,
     path::{Path, PathBuf, StripPrefixError},
+    sync::Mutex,
 };
 
 use crate::{
+    console_utils::LoggingOutputHandler,
     system_tools::ToolError,
     tool_configuration,
-    types::{Directories, Output},
+    types::{CacheUse, Directories, Output},
 };
 
 use fs_err as fs;
 use rattler_build_recipe::stage1::{Source, source::GitRev};
-use rattler_build_source_cache::{Checksum, cache::is_tarball};
+use rattler_build_source_cache::{Checksum, builder::ProgressHandler, cache::is_tarball};
 use rattler_build_source_cache::{
     GitSource as CacheGitSource, Source as CacheSource, UrlSource as CacheUrlSource,
 };
@@ -237,6 +240,8 @@ struct FetchResult {
     rendered_source: Source,
     /// For URL sources that were extracted, the relative path to the extracted directory
     extracted_path: Option<PathBuf>,
+    /// Whether a git or url source was found in the source cache (`None` for path sources)
+    cached: Option<bool>,
 }
 
 /// Fetch a single source and return the rendered source and extracted path
@@ -285,6 +290,7 @@ async fn fetch_source(
             Ok(FetchResult {
                 rendered_source: updated_src,
                 extracted_path: None,
+                cached: Some(result.cached),
             })
         }
         Source::Url(url_src) => {
@@ -334,6 +340,7 @@ async fn fetch_source(
             Ok(FetchResult {
                 rendered_source: source.clone(),
                 extracted_path,
+                cached: Some(result.cached),
             })
         }
         Source::Path(path_src) => {
@@ -413,14 +420,13 @@ async fn fetch_source(
                         dest.display()
                     );
 
-                    for checksum in convert_path_checksums(path_src) {
-                        if let Err(mismatch) = checksum.validate(&src_path) {
-                            return Err(SourceError::ValidationFailed {
-                                expected: mismatch.expected,
-                                actual: mismatch.actual,
-                                kind: mismatch.kind.to_string(),
-                            });
-                        }
+                    let checksums = convert_path_checksums(path_src);
+                    if let Err(mismatch) = Checksum::validate_all(&checksums, &src_path, &|_| {}) {
+                        return Err(SourceError::ValidationFailed {
+                            expected: mismatch.expected,
+                            actual: mismatch.actual,
+                            kind: mismatch.kind.to_string(),
+                        });
                     }
 
                     fs::copy(&src_path, dest)?;
@@ -432,24 +438,89 @@ async fn fetch_source(
             Ok(FetchResult {
                 rendered_source: source.clone(),
                 extracted_path: None,
+                cached: None,
             })
         }
     }
 }
 
-/// Fetches all sources in a list of sources and applies specified patches
+/// Files smaller than this are verified without a progress bar
+const HASH_PROGRESS_THRESHOLD: u64 = 100 * 1024 * 1024;
+
+/// Shows a progress bar while the checksums of large sources are verified
+struct HashProgress {
+    fancy_log_handler: LoggingOutputHandler,
+    progress_bars: Mutex<HashMap<PathBuf, indicatif::ProgressBar>>,
+}
+
+impl HashProgress {
+    fn new(fancy_log_handler: LoggingOutputHandler) -> Self {
+        Self {
+            fancy_log_handler,
+            progress_bars: Mutex::default(),
+        }
+    }
+}
+
+impl ProgressHandler for HashProgress {
+    fn on_download_start(&self, _url: &str, _total_size: Option<u64>) {}
+
+    fn on_download_progress(&self, _url: &str, _downloaded: u64, _total: Option<u64>) {}
+
+    fn on_download_complete(&self, _url: &str) {}
+
+    fn on_extraction_start(&self, _path: &Path) {}
+
+    fn on_extraction_complete(&self, _path: &Path) {}
+
+    fn on_hash_start(&self, path: &Path, total_size: u64) {
+        if total_size < HASH_PROGRESS_THRESHOLD {
+            return;
+        }
+        let progress_bar = self.fancy_log_handler.add_progress_bar(
+            indicatif::ProgressBar::new(total_size)
+                .with_prefix("Verifying checksum ")
+                .with_style(self.fancy_log_handler.default_bytes_style()),
+        );
+        self.progress_bars
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), progress_bar);
+    }
+
+    fn on_hash_progress(&self, path: &Path, hashed: u64, _total: u64) {
+        if let Some(progress_bar) = self.progress_bars.lock().unwrap().get(path) {
+            progress_bar.set_position(hashed);
+        }
+    }
+
+    fn on_hash_complete(&self, path: &Path) {
+        if let Some(progress_bar) = self.progress_bars.lock().unwrap().remove(path) {
+            progress_bar.finish_and_clear();
+        }
+    }
+}
+
+/// Fetches all sources in a list of sources and applies specified patches. Returns the rendered
+/// sources and how many of them were found in the source cache.
 pub async fn fetch_sources(
     sources: &[Source],
     directories: &Directories,
     _system_tools: &SystemTools, // Not needed with new cache
     tool_configuration: &tool_configuration::Configuration,
     apply_patch: impl Fn(&Path, &Path) -> Result<(), SourceError> + Copy,
-) -> Result<Vec<Source>, SourceError> {
+) -> Result<(Vec<Source>, CacheUse), SourceError> {
     use rattler_build_source_cache::SourceCacheBuilder;
 
     if sources.is_empty() {
         tracing::info!("No sources to fetch");
-        return Ok(Vec::new());
+        return Ok((
+            Vec::new(),
+            CacheUse::Sources {
+                cached: 0,
+                total: 0,
+            },
+        ));
     }
 
     // Figure out the directories we need
@@ -461,12 +532,16 @@ pub async fn fetch_sources(
     let source_cache = SourceCacheBuilder::new()
         .cache_dir(&cache_src)
         .client(tool_configuration.client.clone())
+        .progress_handler(HashProgress::new(
+            tool_configuration.fancy_log_handler.clone(),
+        ))
         .build()
         .await
         .map_err(|e| SourceError::UnknownError(e.to_string()))?;
 
     let mut rendered_sources = Vec::new();
     let mut extracted_paths = std::collections::HashMap::new();
+    let (mut cached, mut total) = (0, 0);
 
     for (source_idx, src) in sources.iter().enumerate() {
         let result = fetch_source(
@@ -484,6 +559,10 @@ pub async fn fetch_sources(
         if let Some(path) = result.extracted_path {
             extracted_paths.insert(source_idx, path);
         }
+        if let Some(hit) = result.cached {
+            cached += usize::from(hit);
+            total += 1;
+        }
     }
 
     // add a hidden JSON file with the source information (for compatibility)
@@ -499,7 +578,7 @@ pub async fn fetch_sources(
         serde_json::to_string(&source_info).expect("should serialize"),
     )?;
 
-    Ok(rendered_sources)
+    Ok((rendered_sources, CacheUse::Sources { cached, total }))
 }
 
 /// Represents the source information for a recipe, including the path to the recipe and the sources used
@@ -530,7 +609,7 @@ impl Output {
         let span = tracing::info_span!("Fetching source code");
         let _enter = span.enter();
 
-        let rendered_sources = fetch_sources(
+        let (rendered_sources, cache_use) = fetch_sources(
             &self.recipe.source,
             // self.finalized_sources
             //     .as_deref()
@@ -541,6 +620,9 @@ impl Output {
             apply_patch,
         )
         .await?;
+        if matches!(cache_use, CacheUse::Sources { total, .. } if total > 0) {
+            self.record_cache_use(cache_use);
+        }
 
         Ok(Output {
             finalized_sources: Some(rendered_sources),
@@ -5,7 +5,7 @@
 //! to avoid redundant rebuilds of common dependencies.
 
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, HashSet},
     path::{Path, PathBuf},
 };
 
@@ -27,6 +27,7 @@ use crate::{
         FinalizedDependencies, RunExportsDownload, install_environments, resolve_dependencies,
     },
     source::{copy_dir::CopyDir, fetch_sources},
+    types::CacheUse,
 };
 
 /// Error type for staging cache operations
@@ -135,6 +136,26 @@ impl Output {
         Ok(format!("{:x}", hasher.finalize()))
     }
 
+    /// The directory of the staging cache with the given key
+    fn staging_cache_dir(&self, cache_key: &str) -> PathBuf {
+        self.build_configuration
+            .directories
+            .cache_dir
+            .join(format!("staging_{}", cache_key))
+    }
+
+    /// Whether the staging cache was already built
+    fn staging_cache_exists(&self, staging: &StagingCache) -> miette::Result<bool> {
+        let cache_key = self
+            .staging_cache_key(staging)
+            .into_diagnostic()
+            .context("Failed to compute staging cache key")?;
+        Ok(self
+            .staging_cache_dir(&cache_key)
+            .join("metadata.json")
+            .exists())
+    }
+
     /// Build a staging cache or restore it if it already exists
     ///
     /// This will:
@@ -164,11 +185,17 @@ impl Output {
 
         tracing::info!("Staging cache key: {}", cache_key);
 
-        let cache_dir = self
-            .build_configuration
-            .directories
-            .cache_dir
-            .join(format!("staging_{}", cache_key));
+        let cache_dir = self.staging_cache_dir(&cache_key);
+
+        // Builds that run concurrently and use the same staging cache wait for each other
+        let _lock = rattler_build_source_cache::lock::LockManager::new(
+            &self.build_configuration.directories.cache_dir,
+        )
+        .await
+        .into_diagnostic()?
+        .acquire(&format!("staging_{}", cache_key))
+        .await
+        .into_diagnostic()?;
 
         // Try to restore existing cache
         if cache_dir.exists() {
@@ -178,6 +205,10 @@ impl Output {
                     Ok(text) => match serde_json::from_str::<StagingCacheMetadata>(&text) {
                         Ok(metadata) => {
                             tracing::info!("Restoring staging cache from {}", cache_dir.display());
+                            self.record_cache_use(CacheUse::Staging {
+                                name: staging.name.clone(),
+                                restored: true,
+                            });
                             return self.restore_staging_cache(metadata, &cache_dir).await;
                         }
                         Err(e) => {
@@ -203,6 +234,10 @@ impl Output {
         }
 
         // Build new cache
+        self.record_cache_use(CacheUse::Staging {
+            name: staging.name.clone(),
+            restored: false,
+        });
         self.build_staging_cache(staging, &cache_dir, tool_configuration)
             .await
     }
@@ -223,7 +258,7 @@ impl Output {
         tracing::info!("Building new staging cache: {}", staging.name);
 
         // Fetch sources for the staging build
-        let finalized_sources = fetch_sources(
+        let (finalized_sources, _) = fetch_sources(
             &staging.source,
             &self.build_configuration.directories,
             &self.system_tools,
@@ -297,7 +332,7 @@ impl Output {
                 &self.build_configuration.directories.host_prefix,
                 build_prefix,
                 Some(jinja_renderer),
-                self.build_configuration.sandbox_config(),
+                self.build_configuration.sandbox_config().as_ref(),
                 ScriptDebug::new(self.build_configuration.debug.is_enabled()),
             )
             .await
@@ -464,8 +499,14 @@ impl Output {
             self.recipe.inherits_from.as_ref().map(|i| &i.cache_name)
         );
 
-        // Build all staging caches that are dependencies
+        let inherited = self.recipe.inherits_from.as_ref().map(|i| &i.cache_name);
+
+        // Build all staging caches that are dependencies. The cache this output inherits from is
+        // handled below, so that an existing cache is only restored once.
         for staging_cache in &self.recipe.staging_caches {
+            if Some(&staging_cache.name) == inherited {
+                continue;
+            }
             tracing::info!(
                 "Building or restoring staging cache: {}",
                 staging_cache.name
@@ -490,7 +531,14 @@ impl Output {
                     )
                 })?;
 
-            // Get or build the cache
+            // A newly built cache leaves the whole staging environment behind, the output starts
+            // from the restored cache instead
+            if !self.staging_cache_exists(staging)? {
+                self.build_or_restore_staging_cache(staging, tool_configuration)
+                    .await?;
+            }
+
+            // Restore the cache
             let (deps, sources) = self
                 .build_or_restore_staging_cache(staging, tool_configuration)
                 .await?;
@@ -502,6 +550,73 @@ impl Output {
     }
 }
 
+impl Output {
+    /// The files in the prefix that were restored from the staging cache this output inherits
+    /// from and were not changed by the build script of the output. Returns the name of the
+    /// staging cache and the absolute paths of the files, or `None` if the output does not
+    /// inherit from a staging cache.
+    pub fn inherited_staging_files(&self) -> miette::Result<Option<(String, HashSet<PathBuf>)>> {
+        let Some(inherits) = &self.recipe.inherits_from else {
+            return Ok(None);
+        };
+        let Some(staging) = self
+            .recipe
+            .staging_caches
+            .iter()
+            .find(|s| s.name == inherits.cache_name)
+        else {
+            return Ok(None);
+        };
+
+        let cache_key = self
+            .staging_cache_key(staging)
+            .into_diagnostic()
+            .context("Failed to compute staging cache key")?;
+        let cache_dir = self.staging_cache_dir(&cache_key);
+        let metadata: StagingCacheMetadata = serde_json::from_str(
+            &fs::read_to_string(cache_dir.join("metadata.json")).into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+
+        let files = unchanged_files(
+            self.prefix(),
+            &cache_dir.join("prefix"),
+            &metadata.prefix_files,
+        );
+        Ok(Some((metadata.name, files)))
+    }
+}
+
+/// The files of `relative_paths` that are the same in `prefix` and in `cache_prefix`, as
+/// absolute paths in `prefix`
+fn unchanged_files(
+    prefix: &Path,
+    cache_prefix: &Path,
+    relative_paths: &[PathBuf],
+) -> HashSet<PathBuf> {
+    relative_paths
+        .iter()
+        .filter(|path| is_unchanged(&prefix.join(path), &cache_prefix.join(path)))
+        .map(|path| prefix.join(path))
+        .collect()
+}
+
+/// Whether the file (or symlink) at `path` has the same content as `cached`
+fn is_unchanged(path: &Path, cached: &Path) -> bool {
+    let (Ok(metadata), Ok(cached_metadata)) =
+        (fs::symlink_metadata(path), fs::symlink_metadata(cached))
+    else {
+        return false;
+    };
+    if metadata.is_symlink() || cached_metadata.is_symlink() {
+        return metadata.is_symlink()
+            && cached_metadata.is_symlink()
+            && fs::read_link(path).ok() == fs::read_link(cached).ok();
+    }
+    metadata.len() == cached_metadata.len()
+        && matches!((fs::read(path), fs::read(cached)), (Ok(a), Ok(b)) if a == b)
+}
+
 /// Helper to check if a staging cache should be inherited
 pub fn should_inherit_staging_cache(inherits: &Option<InheritsFrom>) -> bool {
     inherits.is_some()
@@ -516,3 +631,36 @@ pub fn get_staging_cache_name(inherits: &InheritsFrom) -> &str {
 pub fn should_inherit_run_exports(inherits: &InheritsFrom) -> bool {
     inherits.inherit_run_exports
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unchanged_files() {
+        let prefix = tempfile::tempdir().unwrap();
+        let cache_prefix = tempfile::tempdir().unwrap();
+        for dir in [prefix.path(), cache_prefix.path()] {
+            fs::create_dir_all(dir.join("lib")).unwrap();
+            fs::write(dir.join("lib/libfoo.so"), "libfoo").unwrap();
+        }
+        // the build script of the output changed the header
+        fs::create_dir_all(prefix.path().join("include")).unwrap();
+        fs::create_dir_all(cache_prefix.path().join("include")).unwrap();
+        fs::write(prefix.path().join("include/foo.h"), "#define FOO 2").unwrap();
+        fs::write(cache_prefix.path().join("include/foo.h"), "#define FOO 1").unwrap();
+        // ... and deleted the license
+        fs::write(cache_prefix.path().join("LICENSE"), "MIT").unwrap();
+
+        let files = unchanged_files(
+            prefix.path(),
+            cache_prefix.path(),
+            &[
+                PathBuf::from("lib/libfoo.so"),
+                PathBuf::from("include/foo.h"),
+                PathBuf::from("LICENSE"),
+            ],
+        );
+        assert_eq!(files, HashSet::from([prefix.path().join("lib/libfoo.so")]));
+    }
+}
@@ -35,8 +35,14 @@ pub enum Tool {
     Codesign,
     /// The install_name_tool (for macOS / MachO targets)
     InstallNameTool,
+    /// The xcrun tool (for notarizing macOS binaries)
+    Xcrun,
+    /// The strip tool (for `build.strip`)
+    Strip,
     /// The git tool
     Git,
+    /// The cosign tool (for signing the SLSA provenance)
+    Cosign,
 }
 
 impl std::fmt::Display for Tool {
@@ -50,7 +56,10 @@ impl std::fmt::Display for Tool {
                 Tool::Patch => "patch".to_string(),
                 Tool::Patchelf => "patchelf".to_string(),
                 Tool::InstallNameTool => "install_name_tool".to_string(),
+                Tool::Xcrun => "xcrun".to_string(),
+                Tool::Strip => "strip".to_string(),
                 Tool::Git => "git".to_string(),
+                Tool::Cosign => "cosign".to_string(),
             }
         )
     }
@@ -152,6 +161,14 @@ impl SystemTools {
                 let path = which("codesign")?;
                 (path, "".to_string())
             }
+            Tool::Xcrun => {
+                let path = which("xcrun")?;
+                (path, "".to_string())
+            }
+            Tool::Strip => {
+                let path = which("strip")?;
+                (path, "".to_string())
+            }
             Tool::Git => {
                 let path = which("git")?;
                 let output = std::process::Command::new(&path)
@@ -162,6 +179,10 @@ impl SystemTools {
 
                 (path, found_version.to_string())
             }
+            Tool::Cosign => {
+                let path = which("cosign")?;
+                (path, "".to_string())
+            }
             Tool::Patch => {
                 let path = which("patch")?;
                 let version = std::process::Command::new(&path)
@@ -0,0 +1,211 @@
+//! The timings of the phases of builds: the summary table that is printed at the end of a build
+//! and the trace of `build --profile` in the Chrome trace event format, which can be opened in
+//! Perfetto (<https://ui.perfetto.dev>) or `chrome://tracing`.
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use fs_err as fs;
+use indicatif::HumanBytes;
+use miette::IntoDiagnostic;
+use serde::Serialize;
+
+use crate::{
+    metadata::Output,
+    types::{BuildPhase, BuildSummary},
+};
+
+/// Format a duration as e.g. `4.2s` or `3m 05s`
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds < 60.0 {
+        format!("{seconds:.1}s")
+    } else {
+        let seconds = duration.as_secs();
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+/// The total duration of every phase of a build
+fn phase_durations(summary: &BuildSummary) -> BTreeMap<BuildPhase, Duration> {
+    let mut durations = BTreeMap::new();
+    for timing in &summary.phases {
+        *durations.entry(timing.phase).or_default() += timing.duration;
+    }
+    durations
+}
+
+/// A table with the duration of every phase, the size of the package and the use of the caches
+/// for every output
+pub(crate) fn summary_table(outputs: &[Output]) -> comfy_table::Table {
+    let rows = outputs
+        .iter()
+        .map(|output| {
+            let summary = output.build_summary.lock().unwrap();
+            let size = summary
+                .artifact
+                .as_ref()
+                .and_then(|artifact| fs::metadata(artifact).ok())
+                .map(|metadata| metadata.len());
+            let cache_use = summary
+                .cache_use
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            (
+                output.identifier(),
+                phase_durations(&summary),
+                size,
+                cache_use,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // only show the phases that any of the outputs went through
+    let phases = rows
+        .iter()
+        .flat_map(|(_, durations, _, _)| durations.keys().copied())
+        .collect::<std::collections::BTreeSet<_>>();
+
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(
+            std::iter::once("Package")
+                .chain(phases.iter().map(BuildPhase::name))
+                .chain(["Total", "Size", "Cache"]),
+        );
+    for (package, durations, size, cache_use) in rows {
+        let mut row = vec![package];
+        row.extend(phases.iter().map(|phase| {
+            durations
+                .get(phase)
+                .map(|duration| format_duration(*duration))
+                .unwrap_or_else(|| "-".to_string())
+        }));
+        row.push(format_duration(durations.values().sum()));
+        row.push(size.map_or_else(|| "-".to_string(), |size| HumanBytes(size).to_string()));
+        row.push(if cache_use.is_empty() {
+            "-".to_string()
+        } else {
+            cache_use.join("\n")
+        });
+        table.add_row(row);
+    }
+    table
+}
+
+/// An event of the Chrome trace event format
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cat: Option<&'static str>,
+    ph: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<u128>,
+    pid: u32,
+    tid: usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    args: BTreeMap<&'static str, String>,
+}
+
+/// The trace events of the phases of the builds, one track per output. The timestamps are in
+/// microseconds since the start of the first phase.
+fn trace_events(outputs: &[Output]) -> Vec<TraceEvent> {
+    let summaries = outputs
+        .iter()
+        .map(|output| (output.identifier(), output.build_summary.lock().unwrap()))
+        .collect::<Vec<_>>();
+    let Some(epoch) = summaries
+        .iter()
+        .flat_map(|(_, summary)| summary.phases.iter().map(|timing| timing.start))
+        .min()
+    else {
+        return Vec::new();
+    };
+    let micros = |time: chrono::DateTime<chrono::Utc>| (time - epoch).num_microseconds();
+
+    let mut events = Vec::new();
+    for (tid, (package, summary)) in summaries.iter().enumerate() {
+        events.push(TraceEvent {
+            name: "thread_name".to_string(),
+            cat: None,
+            ph: "M",
+            ts: None,
+            dur: None,
+            pid: 1,
+            tid,
+            args: BTreeMap::from([("name", package.clone())]),
+        });
+
+        let start = summary.phases.iter().map(|timing| timing.start).min();
+        let end = summary
+            .phases
+            .iter()
+            .map(|timing| timing.start + timing.duration)
+            .max();
+        if let (Some(start), Some(end)) = (start, end) {
+            events.push(TraceEvent {
+                name: package.clone(),
+                cat: Some("package"),
+                ph: "X",
+                ts: micros(start),
+                dur: (end - start).to_std().ok().map(|dur| dur.as_micros()),
+                pid: 1,
+                tid,
+                args: BTreeMap::new(),
+            });
+        }
+
+        for timing in &summary.phases {
+            events.push(TraceEvent {
+                name: timing.phase.name().to_string(),
+                cat: Some("phase"),
+                ph: "X",
+                ts: micros(timing.start),
+                dur: Some(timing.duration.as_micros()),
+                pid: 1,
+                tid,
+                args: BTreeMap::from([("package", package.clone())]),
+            });
+        }
+    }
+    events
+}
+
+/// Write the phases of the builds to `path` in the Chrome trace event format
+pub(crate) fn write_chrome_trace(outputs: &[Output], path: &Path) -> miette::Result<()> {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Trace {
+        trace_events: Vec<TraceEvent>,
+        display_time_unit: &'static str,
+    }
+
+    let trace = Trace {
+        trace_events: trace_events(outputs),
+        display_time_unit: "ms",
+    };
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    fs::write(path, serde_json::to_string(&trace).into_diagnostic()?).into_diagnostic()?;
+    tracing::info!("Wrote the build profile to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(4240)), "4.2s");
+        assert_eq!(format_duration(Duration::from_secs(185)), "3m 05s");
+    }
+}
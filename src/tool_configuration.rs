@@ -5,7 +5,7 @@ use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use clap::ValueEnum;
 use rattler::package_cache::PackageCache;
-use rattler_conda_types::{ChannelConfig, Platform};
+use rattler_conda_types::{ChannelConfig, ChannelUrl, MatchSpec, Platform};
 #[cfg(feature = "s3")]
 use rattler_networking::s3_middleware;
 use rattler_networking::{
@@ -19,7 +19,11 @@ use rattler_solve::ChannelPriority;
 use thiserror::Error;
 use url::Url;
 
-use crate::console_utils::LoggingOutputHandler;
+use crate::{
+    audit::AuditSettings, console_utils::LoggingOutputHandler, license_policy::LicensePolicy,
+    package_test::TestReport, post_process::plugin::PostProcessor, remote_cache::RemoteCache,
+    render::run_exports_cache::RunExportsCache, slsa::SlsaProvenance,
+};
 
 /// The user agent to use for the reqwest client
 pub const APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
@@ -90,6 +94,30 @@ pub struct Configuration {
     /// The strategy to use for running tests
     pub test_strategy: TestStrategy,
 
+    /// The number of outputs that are built concurrently
+    pub build_jobs: usize,
+
+    /// The number of packages that are tested concurrently
+    pub test_jobs: usize,
+
+    /// Additional channels for the test environments (after the local output channel)
+    pub test_channels: Vec<ChannelUrl>,
+
+    /// Additional packages that are installed into every test environment
+    pub test_extra_specs: Vec<MatchSpec>,
+
+    /// The python versions that noarch python packages are tested with
+    pub test_python_versions: Vec<String>,
+
+    /// The container image that the tests are run in
+    pub test_container: Option<String>,
+
+    /// The authentication file that credentials are read from (`--auth-file`)
+    pub auth_file: Option<PathBuf>,
+
+    /// Collects the test results for the JUnit and JSON test reports
+    pub test_report: Option<TestReport>,
+
     /// Whether to use zstd
     pub use_zstd: bool,
 
@@ -102,6 +130,21 @@ pub struct Configuration {
     /// Whether to skip existing packages
     pub skip_existing: SkipExisting,
 
+    /// The remote cache that finished packages are looked up in and stored in
+    pub remote_cache: Option<RemoteCache>,
+
+    /// Whether to write (and sign) the SLSA provenance of the packages
+    pub slsa_provenance: Option<SlsaProvenance>,
+
+    /// Whether to audit the packages for known vulnerabilities after building
+    pub audit: Option<AuditSettings>,
+
+    /// The license policy that the packages and their run dependencies are checked against
+    pub license_policy: Option<LicensePolicy>,
+
+    /// Whether the build fails unless all sources and dependencies are pinned
+    pub require_pinned: bool,
+
     /// The noarch platform to use (noarch builds are skipped on other platforms)
     pub noarch_build_platform: Option<Platform>,
 
@@ -122,6 +165,9 @@ pub struct Configuration {
     /// The repodata gateway to use for querying repodata
     pub repodata_gateway: Gateway,
 
+    /// The persistent cache of the run exports of packages
+    pub run_exports_cache: RunExportsCache,
+
     /// What channel priority to use in solving
     pub channel_priority: ChannelPriority,
 
@@ -140,10 +186,22 @@ pub struct Configuration {
     /// Whether to allow absolute paths in license_file entries (defaults to false)
     pub allow_absolute_license_paths: bool,
 
+    /// The identity used to sign Mach-O binaries (ad-hoc signing if not set)
+    pub codesign_identity: Option<String>,
+
+    /// The keychain profile used to submit signed binaries for notarization
+    pub notarize_keychain_profile: Option<String>,
+
+    /// The external post-processors that are run on the files of every package
+    pub post_processors: Vec<PostProcessor>,
+
     /// Whether the environments are externally managed (e.g. by `pixi-build`).
     /// This is only useful for other libraries that build their own environments and only use rattler-build
     /// to execute scripts / bundle up files.
     pub environments_externally_managed: bool,
+
+    /// Whether to resume an interrupted build from the last phase that completed
+    pub resume: bool,
 }
 
 /// Get the authentication storage from the given file
@@ -192,10 +250,23 @@ pub struct ConfigurationBuilder {
     no_clean: bool,
     no_test: bool,
     test_strategy: TestStrategy,
+    build_jobs: usize,
+    test_jobs: usize,
+    test_channels: Vec<ChannelUrl>,
+    test_extra_specs: Vec<MatchSpec>,
+    test_python_versions: Vec<String>,
+    test_container: Option<String>,
+    auth_file: Option<PathBuf>,
+    test_report: Option<TestReport>,
     use_zstd: bool,
     use_bz2: bool,
     use_sharded: bool,
     skip_existing: SkipExisting,
+    remote_cache: Option<RemoteCache>,
+    slsa_provenance: Option<SlsaProvenance>,
+    audit: Option<AuditSettings>,
+    license_policy: Option<LicensePolicy>,
+    require_pinned: bool,
     noarch_build_platform: Option<Platform>,
     channel_config: Option<ChannelConfig>,
     compression_threads: Option<u32>,
@@ -206,7 +277,11 @@ pub struct ConfigurationBuilder {
     error_prefix_in_binary: bool,
     allow_symlinks_on_windows: bool,
     allow_absolute_license_paths: bool,
+    codesign_identity: Option<String>,
+    notarize_keychain_profile: Option<String>,
+    post_processors: Vec<PostProcessor>,
     environments_externally_managed: bool,
+    resume: bool,
 }
 
 impl Configuration {
@@ -226,10 +301,23 @@ impl ConfigurationBuilder {
             no_clean: false,
             no_test: false,
             test_strategy: TestStrategy::default(),
+            build_jobs: 1,
+            test_jobs: 1,
+            test_channels: Vec::new(),
+            test_extra_specs: Vec::new(),
+            test_python_versions: Vec::new(),
+            test_container: None,
+            auth_file: None,
+            test_report: None,
             use_zstd: true,
             use_bz2: true,
             use_sharded: true,
             skip_existing: SkipExisting::None,
+            remote_cache: None,
+            slsa_provenance: None,
+            audit: None,
+            license_policy: None,
+            require_pinned: false,
             noarch_build_platform: None,
             channel_config: None,
             compression_threads: None,
@@ -240,7 +328,11 @@ impl ConfigurationBuilder {
             error_prefix_in_binary: false,
             allow_symlinks_on_windows: false,
             allow_absolute_license_paths: false,
+            codesign_identity: None,
+            notarize_keychain_profile: None,
+            post_processors: Vec::new(),
             environments_externally_managed: false,
+            resume: false,
         }
     }
 
@@ -285,6 +377,30 @@ impl ConfigurationBuilder {
         }
     }
 
+    /// Sign Mach-O binaries with the given identity instead of ad-hoc
+    pub fn with_codesign_identity(self, codesign_identity: Option<String>) -> Self {
+        Self {
+            codesign_identity,
+            ..self
+        }
+    }
+
+    /// Submit signed Mach-O binaries for notarization with the given keychain profile
+    pub fn with_notarize_keychain_profile(self, notarize_keychain_profile: Option<String>) -> Self {
+        Self {
+            notarize_keychain_profile,
+            ..self
+        }
+    }
+
+    /// Run the given external post-processors on the files of every package
+    pub fn with_post_processors(self, post_processors: Vec<PostProcessor>) -> Self {
+        Self {
+            post_processors,
+            ..self
+        }
+    }
+
     /// Set the default cache directory to use for objects that need to be
     /// cached.
     pub fn with_opt_cache_dir(self, cache_dir: Option<PathBuf>) -> Self {
@@ -307,6 +423,43 @@ impl ConfigurationBuilder {
         }
     }
 
+    /// Sets the remote cache that finished packages are looked up in and stored in.
+    pub fn with_remote_cache(self, remote_cache: Option<RemoteCache>) -> Self {
+        Self {
+            remote_cache,
+            ..self
+        }
+    }
+
+    /// Sets whether the SLSA provenance of the packages is written (and signed).
+    pub fn with_slsa_provenance(self, slsa_provenance: Option<SlsaProvenance>) -> Self {
+        Self {
+            slsa_provenance,
+            ..self
+        }
+    }
+
+    /// Sets whether the packages are audited for known vulnerabilities after building.
+    pub fn with_audit(self, audit: Option<AuditSettings>) -> Self {
+        Self { audit, ..self }
+    }
+
+    /// Sets the license policy that the packages are checked against.
+    pub fn with_license_policy(self, license_policy: Option<LicensePolicy>) -> Self {
+        Self {
+            license_policy,
+            ..self
+        }
+    }
+
+    /// Sets whether the build fails unless all sources and dependencies are pinned.
+    pub fn with_require_pinned(self, require_pinned: bool) -> Self {
+        Self {
+            require_pinned,
+            ..self
+        }
+    }
+
     /// Set the channel configuration to use.
     pub fn with_channel_config(self, channel_config: ChannelConfig) -> Self {
         Self {
@@ -366,6 +519,70 @@ impl ConfigurationBuilder {
         }
     }
 
+    /// Sets the number of outputs that are built concurrently (at least 1).
+    pub fn with_build_jobs(self, build_jobs: usize) -> Self {
+        Self {
+            build_jobs: build_jobs.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the number of packages that are tested concurrently (at least 1).
+    pub fn with_test_jobs(self, test_jobs: usize) -> Self {
+        Self {
+            test_jobs: test_jobs.max(1),
+            ..self
+        }
+    }
+
+    /// Sets additional channels for the test environments. They are used after the local
+    /// output channel and before the build channels.
+    pub fn with_test_channels(self, test_channels: Vec<ChannelUrl>) -> Self {
+        Self {
+            test_channels,
+            ..self
+        }
+    }
+
+    /// Sets additional packages that are installed into every test environment.
+    pub fn with_test_extra_specs(self, test_extra_specs: Vec<MatchSpec>) -> Self {
+        Self {
+            test_extra_specs,
+            ..self
+        }
+    }
+
+    /// Sets the python versions that noarch python packages are tested with. Every test runs
+    /// once per version.
+    pub fn with_test_python_versions(self, test_python_versions: Vec<String>) -> Self {
+        Self {
+            test_python_versions,
+            ..self
+        }
+    }
+
+    /// Sets the container image that the tests are run in (with `docker` or `podman`).
+    pub fn with_test_container(self, test_container: Option<String>) -> Self {
+        Self {
+            test_container,
+            ..self
+        }
+    }
+
+    /// Sets the authentication file that credentials are read from. Without it, the
+    /// default authentication storage is used.
+    pub fn with_auth_file(self, auth_file: Option<PathBuf>) -> Self {
+        Self { auth_file, ..self }
+    }
+
+    /// Sets the test report that collects the results of all tests.
+    pub fn with_test_report(self, test_report: Option<TestReport>) -> Self {
+        Self {
+            test_report,
+            ..self
+        }
+    }
+
     /// Whether downloading repodata as `.zst` files is enabled.
     pub fn with_zstd_repodata_enabled(self, zstd_repodata_enabled: bool) -> Self {
         Self {
@@ -427,6 +644,12 @@ impl ConfigurationBuilder {
         }
     }
 
+    /// Resume interrupted builds from the last phase that completed instead of starting
+    /// over
+    pub fn with_resume(self, resume: bool) -> Self {
+        Self { resume, ..self }
+    }
+
     /// Construct a [`Configuration`] from the builder.
     pub fn finish(self) -> Configuration {
         let cache_dir = self.cache_dir.unwrap_or_else(|| {
@@ -465,23 +688,41 @@ impl ConfigurationBuilder {
             source_cache: None, // Built lazily on first use
             no_clean: self.no_clean,
             test_strategy,
+            build_jobs: self.build_jobs,
+            test_jobs: self.test_jobs,
+            test_channels: self.test_channels,
+            test_extra_specs: self.test_extra_specs,
+            test_python_versions: self.test_python_versions,
+            test_container: self.test_container,
+            auth_file: self.auth_file,
+            test_report: self.test_report,
             use_zstd: self.use_zstd,
             use_bz2: self.use_bz2,
             use_sharded: self.use_sharded,
             skip_existing: self.skip_existing,
+            remote_cache: self.remote_cache,
+            slsa_provenance: self.slsa_provenance,
+            audit: self.audit,
+            license_policy: self.license_policy,
+            require_pinned: self.require_pinned,
             noarch_build_platform: self.noarch_build_platform,
             channel_config,
             compression_threads: self.compression_threads,
             io_concurrency_limit: self.io_concurrency_limit,
             package_cache,
             repodata_gateway,
+            run_exports_cache: RunExportsCache::new(&cache_dir),
             channel_priority: self.channel_priority,
             allow_insecure_host: self.allow_insecure_host,
             continue_on_failure: self.continue_on_failure,
             error_prefix_in_binary: self.error_prefix_in_binary,
             allow_symlinks_on_windows: self.allow_symlinks_on_windows,
             allow_absolute_license_paths: self.allow_absolute_license_paths,
+            codesign_identity: self.codesign_identity,
+            notarize_keychain_profile: self.notarize_keychain_profile,
+            post_processors: self.post_processors,
             environments_externally_managed: self.environments_externally_managed,
+            resume: self.resume,
         }
     }
 }
@@ -24,14 +24,18 @@ pub enum Event {
     GetBuildOutputs(Vec<PathBuf>),
     /// Processes the build outputs.
     ProcessBuildOutputs(Vec<Output>),
-    /// Start building.
+    /// Queue a package (and the packages it depends on) for building.
     StartBuild(usize),
-    /// Build all packages.
+    /// Queue all packages for building.
     StartBuildQueue,
-    /// Set build state.
-    SetBuildState(usize, BuildProgress),
-    /// Build log.
-    BuildLog(Vec<u8>),
+    /// Start the builds of the queued packages whose dependencies are built.
+    ScheduleBuilds,
+    /// Set build state of the package with the given identifier.
+    SetBuildState(String, BuildProgress),
+    /// Set the current phase of the build of the package with the given identifier.
+    SetBuildPhase(String, String),
+    /// Log lines of a package (or of the application).
+    BuildLog(Option<String>, Vec<String>),
     /// Handle console input.
     HandleInput,
     /// Edit recipe.
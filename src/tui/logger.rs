@@ -1,42 +1,185 @@
 //! TUI log handler.
+//!
+//! The events are routed to the package that they belong to, so that every package has its own
+//! log pane. An event belongs to a package if it is logged inside a span with a `recipe` field
+//! (e.g. `Running build for`), which holds the identifier of the package. The direct child spans
+//! of that span are the phases of the build that are shown as the status of the package.
 
 use super::event::Event;
-use std::io;
 use tokio::sync::mpsc;
-use tracing_subscriber::fmt::MakeWriter;
+use tracing::{Level, field};
+use tracing_core::{Field, Subscriber, span::Id};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
 
-/// Writer for TUI logs.
-#[derive(Debug)]
-pub struct TuiOutputHandler {
+/// The package of a span with a `recipe` field
+struct TuiPackage {
+    identifier: String,
+    /// The depth of the span, the log is indented relative to it
+    depth: usize,
+}
+
+/// Formats the message and the `recipe` and `package` fields of an event or span
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    recipe: Option<String>,
+}
+
+impl field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{value}"));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message.push_str(&format!("{value:?}")),
+            "recipe" | "package" => self
+                .message
+                .push_str(&format!(" {}: {value:?}", field.name())),
+            _ => {}
+        }
+        if field.name() == "recipe" {
+            self.recipe = Some(format!("{value:?}"));
+        }
+    }
+}
+
+/// A layer that sends the log of the builds to the TUI
+#[derive(Debug, Clone)]
+pub struct TuiLayer {
     /// Sender channel for logs.
     pub log_sender: mpsc::UnboundedSender<Event>,
 }
 
-impl Clone for TuiOutputHandler {
-    fn clone(&self) -> Self {
-        Self {
-            log_sender: self.log_sender.clone(),
-        }
+impl TuiLayer {
+    /// Send `text` to the log of the package of the span `id` (or the application log)
+    fn send<S>(&self, ctx: &Context<'_, S>, id: Option<&Id>, depth: usize, text: String)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let package = id.and_then(|id| ctx.span(id)).and_then(|span| {
+            span.scope().from_root().find_map(|span| {
+                span.extensions()
+                    .get::<TuiPackage>()
+                    .map(|package| (package.identifier.clone(), package.depth))
+            })
+        });
+        let (package, indent) = match package {
+            Some((identifier, package_depth)) => (
+                Some(identifier),
+                "  ".repeat(depth.saturating_sub(package_depth)),
+            ),
+            None => (None, "  ".repeat(depth)),
+        };
+        let lines = text.lines().map(|line| format!("{indent}{line}")).collect();
+        // the TUI may already be closed
+        let _ = self.log_sender.send(Event::BuildLog(package, lines));
     }
 }
 
-impl io::Write for TuiOutputHandler {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.log_sender
-            .send(Event::BuildLog(buf.to_vec()))
-            .map_err(|e| io::Error::other(format!("could not send TUI event: {e}")))?;
-        Ok(buf.len())
+impl<S> Layer<S> for TuiLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing_core::span::Attributes<'_>,
+        id: &Id,
+        ctx: Context<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let depth = span.scope().count();
+
+        let mut visitor = MessageVisitor::default();
+        attrs.record(&mut visitor);
+        // nested spans with a `recipe` field (e.g. of the tests) belong to the outer package
+        let in_package = span
+            .scope()
+            .skip(1)
+            .any(|span| span.extensions().get::<TuiPackage>().is_some());
+        if let (false, Some(identifier)) = (in_package, visitor.recipe.clone()) {
+            span.extensions_mut()
+                .insert(TuiPackage { identifier, depth });
+        } else if let Some(package) = span.parent().and_then(|parent| {
+            parent
+                .extensions()
+                .get::<TuiPackage>()
+                .map(|p| p.identifier.clone())
+        }) {
+            let _ = self
+                .log_sender
+                .send(Event::SetBuildPhase(package, span.name().to_string()));
+        }
+
+        let header = format!("{}{}", span.name(), visitor.message);
+        self.send(
+            &ctx,
+            Some(id),
+            depth - 1,
+            format!("{} {header}", console::style("╭─").dim()),
+        );
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let span = ctx.event_span(event);
+        let depth = span.as_ref().map_or(0, |span| span.scope().count());
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let prefix = match *event.metadata().level() {
+            Level::ERROR => console::style("error: ").red().bold().to_string(),
+            Level::WARN => console::style("warning: ").yellow().bold().to_string(),
+            _ => String::new(),
+        };
+        let text = visitor
+            .message
+            .lines()
+            .map(|line| format!("{prefix}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.send(&ctx, span.map(|span| span.id()).as_ref(), depth, text);
     }
 }
 
-impl<'a> MakeWriter<'a> for TuiOutputHandler {
-    type Writer = TuiOutputHandler;
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
 
-    fn make_writer(&'a self) -> Self::Writer {
-        self.clone()
+    #[test]
+    fn test_tui_layer() {
+        let (log_sender, mut receiver) = mpsc::unbounded_channel();
+        let subscriber = tracing_subscriber::registry().with(TuiLayer { log_sender });
+        tracing::subscriber::with_default(subscriber, || {
+            console::set_colors_enabled(false);
+            tracing::info!("not part of a build");
+            let span = tracing::info_span!("Running build for", recipe = "foo-1.0-h123_0");
+            let _enter = span.enter();
+            let inner = tracing::info_span!("Fetching source code");
+            let _inner = inner.enter();
+            tracing::warn!("first line\nsecond line");
+        });
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(match event {
+                Event::BuildLog(package, lines) => format!("{package:?}: {}", lines.join("|")),
+                Event::SetBuildPhase(package, phase) => format!("{package} is {phase}"),
+                _ => panic!("unexpected event"),
+            });
+        }
+        assert_eq!(
+            events,
+            [
+                "None: not part of a build",
+                r#"Some("foo-1.0-h123_0"): ╭─ Running build for recipe: foo-1.0-h123_0"#,
+                "foo-1.0-h123_0 is Fetching source code",
+                r#"Some("foo-1.0-h123_0"): ╭─ Fetching source code"#,
+                r#"Some("foo-1.0-h123_0"):   warning: first line|  warning: second line"#,
+            ]
+        );
     }
 }
@@ -19,10 +19,12 @@ use ratatui::prelude::*;
 use std::io::{self, Stderr};
 use std::panic;
 use std::path::PathBuf;
+use std::time::Instant;
+use tokio::sync::mpsc;
 
 use crate::build::{WorkingDirectoryBehavior, run_build};
 use crate::console_utils::LoggingOutputHandler;
-use crate::{BuildData, get_build_output};
+use crate::{BuildData, render_recipes};
 
 use self::utils::run_editor;
 
@@ -121,6 +123,43 @@ impl<B: Backend> Tui<B> {
     }
 }
 
+/// Builds a package in the background and reports the result to the TUI.
+///
+/// Every build runs on its own thread, as the spans of the build are entered across `.await`
+/// points and would otherwise leak into the builds that run concurrently on the same worker.
+fn start_build(package: Package, sender: mpsc::UnboundedSender<Event>) {
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || {
+        let identifier = package.identifier.clone();
+        let result = handle.block_on(run_build(
+            package.output,
+            &package.tool_config,
+            WorkingDirectoryBehavior::Cleanup,
+        ));
+        let progress = match result {
+            Ok((output, _archive)) => {
+                output.record_build_end();
+                let _ = output.log_build_summary().map_err(|e| {
+                    tracing::error!("Error writing build summary: {}", e);
+                    e
+                });
+                BuildProgress::Done
+            }
+            Err(e) => {
+                let _ = sender.send(Event::BuildLog(
+                    Some(identifier.clone()),
+                    format!("Error building package: {e:?}")
+                        .lines()
+                        .map(String::from)
+                        .collect(),
+                ));
+                BuildProgress::Failed
+            }
+        };
+        let _ = sender.send(Event::SetBuildState(identifier, progress));
+    });
+}
+
 /// Initializes the TUI.
 pub async fn init() -> miette::Result<Tui<CrosstermBackend<Stderr>>> {
     let backend = CrosstermBackend::new(io::stderr());
@@ -165,14 +204,10 @@ pub async fn run<B: Backend>(
                 let state = state.clone();
                 let log_sender = tui.event_handler.sender.clone();
                 tokio::spawn(async move {
-                    let mut outputs = Vec::new();
-                    for recipe_path in &recipe_paths {
-                        let output =
-                            get_build_output(&state.build_data, recipe_path, &state.tool_config)
-                                .await
-                                .unwrap();
-                        outputs.extend(output);
-                    }
+                    let outputs =
+                        render_recipes(&recipe_paths, &state.build_data, &state.tool_config)
+                            .await
+                            .unwrap();
                     log_sender
                         .send(Event::ProcessBuildOutputs(outputs))
                         .unwrap();
@@ -183,122 +218,86 @@ pub async fn run<B: Backend>(
                     .into_iter()
                     .map(|output| Package::from_output(output, &state.tool_config))
                     .collect();
+                // Remove the outputs of the re-rendered recipes that no longer exist
                 state.packages.retain(|package| {
-                    packages
-                        .iter()
-                        .any(|p| p.recipe_path != package.recipe_path)
+                    package.build_progress.is_building()
+                        || packages
+                            .iter()
+                            .all(|p| p.recipe_path != package.recipe_path)
+                        || packages.iter().any(|p| p.identifier == package.identifier)
                 });
                 for new_package in packages {
                     match state
                         .packages
                         .iter_mut()
-                        .find(|p| new_package.name == p.name)
+                        .find(|p| new_package.identifier == p.identifier)
                     {
+                        Some(package) if package.build_progress.is_building() => {}
                         Some(package) => {
                             *package = new_package;
                         }
                         None => state.packages.push(new_package),
                     }
                 }
+                state.selected_package = state
+                    .selected_package
+                    .min(state.packages.len().saturating_sub(1));
+                state.update_dependencies();
             }
-            Event::StartBuildQueue => match state.build_queue {
-                Some(mut build_index) => {
-                    while build_index != state.packages.len() {
-                        if state.packages[build_index].build_progress == BuildProgress::Done {
-                            build_index += 1;
-                            continue;
-                        }
-                        state.build_queue = Some(build_index);
-                        tui.event_handler
-                            .sender
-                            .send(Event::StartBuild(build_index))
-                            .into_diagnostic()?;
-                        break;
-                    }
-                    if build_index == state.packages.len() {
-                        state.build_queue = None;
-                    }
+            Event::StartBuildQueue => {
+                for index in 0..state.packages.len() {
+                    state.queue(index);
                 }
-                None => {
-                    state.build_queue = Some(0);
+                tui.event_handler
+                    .sender
+                    .send(Event::ScheduleBuilds)
+                    .into_diagnostic()?;
+            }
+            Event::StartBuild(index) => {
+                if index < state.packages.len() {
+                    state.queue(index);
                     tui.event_handler
                         .sender
-                        .send(Event::StartBuild(0))
+                        .send(Event::ScheduleBuilds)
                         .into_diagnostic()?;
                 }
-            },
-            Event::StartBuild(index) => {
-                if !state.is_building_package() {
-                    let package = state.packages[index].clone();
-                    let log_sender = tui.event_handler.sender.clone();
-                    let mut packages = Vec::new();
-                    for subpackage in package.subpackages.iter() {
-                        if let Some(i) = state.packages.iter().position(|v| v.name == *subpackage) {
-                            if state.packages[i].build_progress != BuildProgress::Done {
-                                packages.push((i, state.packages[i].clone()));
-                            }
-                        } else {
-                            tracing::error!("Cannot find subpackage to build: {subpackage}")
-                        }
+            }
+            Event::ScheduleBuilds => {
+                for index in state.next_builds() {
+                    let package = &mut state.packages[index];
+                    package.build_log.clear();
+                    package.phase = None;
+                    package.build_start = Some(Instant::now());
+                    package.build_duration = None;
+                    start_build(package.clone(), tui.event_handler.sender.clone());
+                }
+            }
+            Event::SetBuildState(identifier, progress) => {
+                if let Some(package) = state.package_mut(&identifier) {
+                    if !progress.is_building() {
+                        package.build_duration = package.elapsed();
+                        package.build_start = None;
+                        package.phase = None;
                     }
-                    packages.push((index, package.clone()));
-                    tokio::spawn(async move {
-                        let mut build_error = None;
-                        for (i, package) in packages {
-                            log_sender
-                                .send(Event::SetBuildState(i, BuildProgress::Building))
-                                .unwrap();
-                            match run_build(
-                                package.output,
-                                &package.tool_config,
-                                WorkingDirectoryBehavior::Cleanup,
-                            )
-                            .await
-                            {
-                                Ok((output, _archive)) => {
-                                    output.record_build_end();
-                                    let span = tracing::info_span!("Build summary");
-                                    let _enter = span.enter();
-                                    let _ = output.log_build_summary().map_err(|e| {
-                                        tracing::error!("Error writing build summary: {}", e);
-                                        e
-                                    });
-                                    log_sender
-                                        .send(Event::SetBuildState(i, BuildProgress::Done))
-                                        .unwrap();
-                                }
-                                Err(e) => {
-                                    build_error = Some(e);
-                                    log_sender
-                                        .send(Event::SetBuildState(i, BuildProgress::Failed))
-                                        .unwrap();
-                                    break;
-                                }
-                            };
-                        }
-                        if let Some(e) = build_error {
-                            tracing::error!("Error building package: {}", e);
-                        } else if state.build_queue.is_some() {
-                            log_sender.send(Event::StartBuildQueue).unwrap();
-                        }
-                    });
+                    package.build_progress = progress;
                 }
+                tui.event_handler
+                    .sender
+                    .send(Event::ScheduleBuilds)
+                    .into_diagnostic()?;
             }
-            Event::SetBuildState(index, progress) => {
-                state.selected_package = index;
-                state.packages[index].build_progress = progress;
+            Event::SetBuildPhase(identifier, phase) => {
+                if let Some(package) = state.package_mut(&identifier) {
+                    package.phase = Some(phase);
+                }
             }
-            Event::BuildLog(log) => {
-                if let Some(building_package) = state
-                    .packages
-                    .iter_mut()
-                    .find(|p| p.build_progress.is_building())
+            Event::BuildLog(identifier, lines) => {
+                match identifier
+                    .as_deref()
+                    .and_then(|identifier| state.package_mut(identifier))
                 {
-                    building_package
-                        .build_log
-                        .push(String::from_utf8_lossy(&log).to_string());
-                } else {
-                    state.log.push(String::from_utf8_lossy(&log).to_string());
+                    Some(package) => package.build_log.extend(lines),
+                    None => state.log.extend(lines),
                 }
             }
             Event::HandleInput => {
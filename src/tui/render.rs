@@ -7,17 +7,26 @@ use ratatui::{
     layout::{Alignment, Position},
     prelude::*,
     style::{Color, Style, Stylize},
-    widgets::{Block, BorderType, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState},
+    widgets::{
+        Block, BorderType, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        Table, TableState,
+    },
 };
 use tokio::sync::mpsc;
 use tui_input::backend::crossterm::EventHandler;
 
-use super::{event::Event, state::TuiState};
+use super::{
+    event::Event,
+    state::{BuildProgress, Package, TuiState, View},
+};
+use crate::timings::format_duration;
 
 /// Key bindings.
 const KEY_BINDINGS: &[(&str, &str)] = &[
     ("⏎ ", "Build"),
     ("a", "Build All"),
+    ("+-", "Parallel Builds"),
+    ("⇥ ", "Panes/Summary"),
     ("e", "Edit Recipe"),
     ("c", "Console"),
     ("j", "Next"),
@@ -66,35 +75,36 @@ pub(crate) fn handle_key_events(
                 state.input_mode = true;
             }
         }
-        KeyCode::Char('j') => {
-            state.vertical_scroll = 0;
+        KeyCode::Char('j') if !state.packages.is_empty() => {
             state.selected_package = if state.selected_package >= state.packages.len() - 1 {
                 0
             } else {
                 state.selected_package + 1
             }
         }
-        KeyCode::Up => {
-            state.vertical_scroll += 5;
-        }
-        KeyCode::Char('k') => {
-            state.vertical_scroll = 0;
+        KeyCode::Char('k') if !state.packages.is_empty() => {
             state.selected_package = if state.selected_package == 0 {
                 state.packages.len() - 1
             } else {
                 state.selected_package - 1
             }
         }
-        KeyCode::Down => {
-            if state.vertical_scroll > 1 {
-                state.vertical_scroll = state.vertical_scroll.saturating_sub(5);
+        KeyCode::Up => scroll(state, 0, 5),
+        KeyCode::Down => scroll(state, 0, -5),
+        KeyCode::Right => scroll(state, 5, 0),
+        KeyCode::Left => scroll(state, -5, 0),
+        KeyCode::Tab => {
+            state.view = match state.view {
+                View::Panes => View::Summary,
+                View::Summary => View::Panes,
             }
         }
-        KeyCode::Right => {
-            state.horizontal_scroll += 5;
+        KeyCode::Char('+') => {
+            state.max_builds += 1;
+            sender.send(Event::ScheduleBuilds).into_diagnostic()?
         }
-        KeyCode::Left => {
-            state.horizontal_scroll = state.horizontal_scroll.saturating_sub(5);
+        KeyCode::Char('-') => {
+            state.max_builds = state.max_builds.saturating_sub(1).max(1);
         }
         KeyCode::Char('a') => sender.send(Event::StartBuildQueue).into_diagnostic()?,
         KeyCode::Enter => sender
@@ -110,6 +120,14 @@ pub(crate) fn handle_key_events(
     Ok(())
 }
 
+/// Scrolls the log of the selected package.
+fn scroll(state: &mut TuiState, horizontal: i16, vertical: i16) {
+    if let Some(package) = state.packages.get_mut(state.selected_package) {
+        package.horizontal_scroll = package.horizontal_scroll.saturating_add_signed(horizontal);
+        package.vertical_scroll = package.vertical_scroll.saturating_add_signed(vertical);
+    }
+}
+
 /// Handles the mouse events and updates the state.
 pub(crate) fn handle_mouse_events(
     mouse_event: MouseEvent,
@@ -117,20 +135,10 @@ pub(crate) fn handle_mouse_events(
     state: &mut TuiState,
 ) -> miette::Result<()> {
     match mouse_event.kind {
-        MouseEventKind::ScrollDown => {
-            if state.vertical_scroll > 1 {
-                state.vertical_scroll = state.vertical_scroll.saturating_sub(5);
-            }
-        }
-        MouseEventKind::ScrollUp => {
-            state.vertical_scroll += 5;
-        }
-        MouseEventKind::ScrollRight => {
-            state.horizontal_scroll += 5;
-        }
-        MouseEventKind::ScrollLeft => {
-            state.horizontal_scroll = state.horizontal_scroll.saturating_sub(5);
-        }
+        MouseEventKind::ScrollDown => scroll(state, 0, -5),
+        MouseEventKind::ScrollUp => scroll(state, 0, 5),
+        MouseEventKind::ScrollRight => scroll(state, 5, 0),
+        MouseEventKind::ScrollLeft => scroll(state, -5, 0),
         MouseEventKind::Moved => {
             let p = Position::new(mouse_event.column, mouse_event.row);
             state.packages.iter_mut().for_each(|package| {
@@ -277,24 +285,7 @@ pub(crate) fn render_widgets(state: &mut TuiState, frame: &mut Frame) {
         }
     }
 
-    let mut log_lines = state.log.clone();
-    if let Some(selected_package) = state.packages.get(state.selected_package) {
-        log_lines.extend(selected_package.build_log.clone());
-    }
-    let log_lines = log_lines
-        .iter()
-        .map(|l| l.trim_end())
-        .collect::<Vec<&str>>();
-    let logs = log_lines.join("\n").into_text().unwrap().on_black();
-    let vertical_scroll = (logs.height() as u16)
-        .saturating_sub(rects[1].height.saturating_sub(3))
-        .saturating_sub(state.vertical_scroll);
-    if vertical_scroll == 0 {
-        state.vertical_scroll =
-            (logs.height() as u16).saturating_sub(rects[1].height.saturating_sub(3));
-    }
-
-    let logs_rect = if state.input_mode {
+    let main_rect = if state.input_mode {
         let rects =
             Layout::vertical([Constraint::Percentage(100), Constraint::Min(3)]).split(rects[1]);
         frame.render_widget(
@@ -314,38 +305,130 @@ pub(crate) fn render_widgets(state: &mut TuiState, frame: &mut Frame) {
         rects[1]
     };
 
+    match state.view {
+        View::Panes => render_panes(state, frame, main_rect),
+        View::Summary => render_summary(state, frame, main_rect),
+    }
+}
+
+/// Renders a log pane for every building package and the selected package.
+fn render_panes(state: &mut TuiState, frame: &mut Frame, area: Rect) {
+    let panes = state
+        .packages
+        .iter()
+        .enumerate()
+        .filter(|(i, package)| package.build_progress.is_building() || *i == state.selected_package)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    if panes.is_empty() {
+        let (mut vertical_scroll, mut horizontal_scroll) = (0, 0);
+        render_log(
+            frame,
+            area,
+            Line::from("|Build Logs|".yellow()),
+            Color::Rgb(100, 100, 100),
+            &state.log,
+            &mut vertical_scroll,
+            &mut horizontal_scroll,
+        );
+        return;
+    }
+
+    let columns = if panes.len() > 2 { 2 } else { 1 };
+    let rows = panes.len().div_ceil(columns);
+    let row_rects = Layout::vertical(vec![Constraint::Ratio(1, rows as u32); rows]).split(area);
+    for (n, &index) in panes.iter().enumerate() {
+        let row = n / columns;
+        let row_length = (panes.len() - row * columns).min(columns);
+        let rect = Layout::horizontal(vec![Constraint::Ratio(1, row_length as u32); row_length])
+            .split(row_rects[row])[n % columns];
+
+        let selected = index == state.selected_package;
+        let package = &mut state.packages[index];
+        // The application log is shown above the log of the selected package
+        let mut lines = if selected {
+            state.log.clone()
+        } else {
+            Vec::new()
+        };
+        lines.extend(package.build_log.iter().cloned());
+        render_log(
+            frame,
+            rect,
+            pane_title(package),
+            if selected {
+                Color::White
+            } else {
+                Color::Rgb(100, 100, 100)
+            },
+            &lines,
+            &mut package.vertical_scroll,
+            &mut package.horizontal_scroll,
+        );
+    }
+}
+
+/// Returns the title of the log pane of a package, with the status and the current phase.
+fn pane_title(package: &Package) -> Line<'static> {
+    let status = match (&package.build_progress, &package.phase) {
+        (BuildProgress::Building, Some(phase)) => phase.clone(),
+        (progress, _) => progress.name().to_string(),
+    };
+    let mut spans = vec![
+        "|".into(),
+        package.identifier.clone().yellow(),
+        " ".into(),
+        status.fg(package.build_progress.as_color()),
+    ];
+    if let Some(elapsed) = package.elapsed() {
+        spans.push(format!(" {}", format_duration(elapsed)).fg(Color::Rgb(100, 100, 100)));
+    }
+    spans.push("|".into());
+    Line::from(spans)
+}
+
+/// Renders a scrollable log. The vertical scroll value is counted from the end of the log.
+fn render_log(
+    frame: &mut Frame,
+    area: Rect,
+    title: Line,
+    border_color: Color,
+    lines: &[String],
+    vertical_scroll: &mut u16,
+    horizontal_scroll: &mut u16,
+) {
+    let log_lines = lines.iter().map(|l| l.trim_end()).collect::<Vec<&str>>();
+    let logs = log_lines.join("\n").into_text().unwrap().on_black();
+    let vertical_position = (logs.height() as u16)
+        .saturating_sub(area.height.saturating_sub(3))
+        .saturating_sub(*vertical_scroll);
+    if vertical_position == 0 {
+        *vertical_scroll = (logs.height() as u16).saturating_sub(area.height.saturating_sub(3));
+    }
+
     frame.render_widget(
         Paragraph::new(logs.clone())
             .block(
                 Block::bordered()
-                    .title_top(
-                        match state.packages.get(state.selected_package) {
-                            Some(package) => {
-                                format!("|Build Logs for {}|", package.name)
-                            }
-                            None => String::from("|Build Logs|"),
-                        }
-                        .yellow(),
-                    )
+                    .title_top(title)
                     .title_alignment(Alignment::Left)
                     .border_type(BorderType::Rounded)
-                    .border_style(Style::default().fg(Color::Rgb(100, 100, 100))),
+                    .border_style(Style::default().fg(border_color)),
             )
-            .scroll((vertical_scroll, state.horizontal_scroll)),
-        logs_rect,
+            .scroll((vertical_position, *horizontal_scroll)),
+        area,
     );
 
     let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
         .begin_symbol(Some("↑"))
         .end_symbol(Some("↓"));
 
-    let mut scrollbar_state =
-        ScrollbarState::new(logs.height().saturating_sub(logs_rect.height.into()))
-            .position(vertical_scroll.into());
+    let mut scrollbar_state = ScrollbarState::new(logs.height().saturating_sub(area.height.into()))
+        .position(vertical_position.into());
 
     frame.render_stateful_widget(
         scrollbar,
-        logs_rect.inner(Margin {
+        area.inner(Margin {
             vertical: 1,
             horizontal: 0,
         }),
@@ -363,19 +446,73 @@ pub(crate) fn render_widgets(state: &mut TuiState, frame: &mut Frame) {
         .map(|l| l.width())
         .max()
         .unwrap_or_default();
-    let content_length = max_width.saturating_sub(logs_rect.width.saturating_sub(2).into());
+    let content_length = max_width.saturating_sub(area.width.saturating_sub(2).into());
     if content_length == 0 {
-        state.horizontal_scroll = 0;
+        *horizontal_scroll = 0;
     }
     let mut scrollbar_state =
-        ScrollbarState::new(content_length).position(state.horizontal_scroll.into());
+        ScrollbarState::new(content_length).position((*horizontal_scroll).into());
 
     frame.render_stateful_widget(
         scrollbar,
-        logs_rect.inner(Margin {
+        area.inner(Margin {
             vertical: 0,
             horizontal: 1,
         }),
         &mut scrollbar_state,
     );
 }
+
+/// Renders a table with the status of all packages.
+fn render_summary(state: &mut TuiState, frame: &mut Frame, area: Rect) {
+    let count = |progress: BuildProgress| {
+        state
+            .packages
+            .iter()
+            .filter(|package| package.build_progress == progress)
+            .count()
+    };
+    let title = format!(
+        "|Summary: {} done, {} failed, {} building, {} queued ({} parallel builds)|",
+        count(BuildProgress::Done),
+        count(BuildProgress::Failed),
+        count(BuildProgress::Building),
+        count(BuildProgress::Queued),
+        state.max_builds,
+    );
+    let rows = state.packages.iter().map(|package| {
+        let status = match (&package.build_progress, &package.phase) {
+            (BuildProgress::Building, Some(phase)) => phase.clone(),
+            _ => String::new(),
+        };
+        Row::new(vec![
+            Cell::from(package.identifier.clone()),
+            Cell::from(package.output.target_platform().to_string()),
+            Cell::from(package.build_progress.name())
+                .style(Style::new().fg(package.build_progress.as_color())),
+            Cell::from(status),
+            Cell::from(package.elapsed().map(format_duration).unwrap_or_default()),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Fill(3),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Fill(2),
+            Constraint::Length(9),
+        ],
+    )
+    .header(Row::new(vec!["Package", "Platform", "Status", "Phase", "Time"]).yellow())
+    .row_highlight_style(Style::new().reversed())
+    .block(
+        Block::bordered()
+            .title_top(title.yellow())
+            .title_alignment(Alignment::Left)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Rgb(100, 100, 100))),
+    );
+    let mut table_state = TableState::default().with_selected(Some(state.selected_package));
+    frame.render_stateful_widget(table, area, &mut table_state);
+}
@@ -1,12 +1,15 @@
-use std::path::PathBuf;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 use ratatui::{layout::Rect, style::Color};
 use throbber_widgets_tui::ThrobberState;
 use tui_input::Input;
 
 use crate::{
-    BuildData, console_utils::LoggingOutputHandler, get_tool_config, metadata::Output,
-    tool_configuration::Configuration,
+    BuildData, console_utils::LoggingOutputHandler, get_tool_config, graph::DependencyGraph,
+    metadata::Output, tool_configuration::Configuration,
 };
 
 /// Representation of a package.
@@ -15,9 +18,22 @@ pub struct Package {
     pub name: String,
     pub version: String,
     pub build_string: String,
-    pub subpackages: Vec<String>,
+    /// The identifier of the package (`name-version-build`).
+    pub identifier: String,
+    /// The identifiers of the packages that have to be built before this one.
+    pub dependencies: Vec<String>,
     pub build_progress: BuildProgress,
+    /// The current phase of the build.
+    pub phase: Option<String>,
+    /// When the build started.
+    pub build_start: Option<Instant>,
+    /// How long the last build took.
+    pub build_duration: Option<Duration>,
     pub build_log: Vec<String>,
+    /// Vertical scroll value of the log, counted from the end of the log.
+    pub vertical_scroll: u16,
+    /// Horizontal scroll value of the log.
+    pub horizontal_scroll: u16,
     pub spinner_state: ThrobberState,
     pub area: Rect,
     pub is_hovered: bool,
@@ -29,20 +45,19 @@ pub struct Package {
 impl Package {
     /// Constructs a package list from build output.
     pub fn from_output(output: Output, tool_config: &Configuration) -> Self {
-        let name = output.name().as_normalized().to_string();
         Package {
-            name: name.clone(),
+            name: output.name().as_normalized().to_string(),
             version: output.version().to_string(),
             build_string: output.build_string().into_owned(),
-            subpackages: output
-                .build_configuration
-                .subpackages
-                .keys()
-                .map(|v| v.as_normalized().to_string())
-                .filter(|v| v != &name)
-                .collect(),
+            identifier: output.identifier(),
+            dependencies: Vec::new(),
             build_progress: BuildProgress::None,
+            phase: None,
+            build_start: None,
+            build_duration: None,
             build_log: Vec::new(),
+            vertical_scroll: 0,
+            horizontal_scroll: 0,
             spinner_state: ThrobberState::default(),
             area: Rect::default(),
             is_hovered: false,
@@ -51,6 +66,13 @@ impl Package {
             recipe_path: output.build_configuration.directories.recipe_path.clone(),
         }
     }
+
+    /// Returns the time that the build took (so far).
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.build_start
+            .map(|start| start.elapsed())
+            .or(self.build_duration)
+    }
 }
 
 /// Build progress.
@@ -58,6 +80,7 @@ impl Package {
 pub enum BuildProgress {
     #[default]
     None,
+    Queued,
     Building,
     Failed,
     Done,
@@ -73,11 +96,33 @@ impl BuildProgress {
     pub fn as_color(&self) -> Color {
         match self {
             BuildProgress::None => Color::Rgb(100, 100, 100),
+            BuildProgress::Queued => Color::Blue,
             BuildProgress::Building => Color::Yellow,
             BuildProgress::Failed => Color::Red,
             BuildProgress::Done => Color::Green,
         }
     }
+
+    /// Returns the name of the progress.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildProgress::None => "not built",
+            BuildProgress::Queued => "queued",
+            BuildProgress::Building => "building",
+            BuildProgress::Failed => "failed",
+            BuildProgress::Done => "done",
+        }
+    }
+}
+
+/// The view of the main area.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum View {
+    /// A log pane for every building package (or the selected package).
+    #[default]
+    Panes,
+    /// A table with the status of all packages.
+    Summary,
 }
 
 /// Application state.
@@ -93,14 +138,12 @@ pub(crate) struct TuiState {
     pub packages: Vec<Package>,
     /// Index of the selected package.
     pub selected_package: usize,
-    /// Vertical scroll value.
-    pub vertical_scroll: u16,
-    /// Horizontal scroll value.
-    pub horizontal_scroll: u16,
     /// Application log.
     pub log: Vec<String>,
-    /// Index of the currently building package.
-    pub build_queue: Option<usize>,
+    /// The maximum number of packages that are built concurrently.
+    pub max_builds: usize,
+    /// The view of the main area.
+    pub view: View,
     /// Is the input mode enabled?
     pub input_mode: bool,
     /// Current value of the prompt input.
@@ -117,11 +160,10 @@ impl TuiState {
             running: true,
             packages: Vec::new(),
             selected_package: 0,
-            vertical_scroll: 0,
-            horizontal_scroll: 0,
             log: Vec::new(),
+            max_builds: 2,
+            view: View::default(),
             input_mode: false,
-            build_queue: None,
             input: Input::default(),
         }
     }
@@ -140,8 +182,120 @@ impl TuiState {
         self.running = false;
     }
 
-    /// Returns true if a package is building currently.
-    pub fn is_building_package(&self) -> bool {
-        self.packages.iter().any(|p| p.build_progress.is_building())
+    /// Returns the package with the given identifier.
+    pub fn package_mut(&mut self, identifier: &str) -> Option<&mut Package> {
+        self.packages
+            .iter_mut()
+            .find(|package| package.identifier == identifier)
+    }
+
+    /// Computes the dependencies among the packages.
+    pub fn update_dependencies(&mut self) {
+        let outputs = self
+            .packages
+            .iter()
+            .map(|package| package.output.clone())
+            .collect::<Vec<_>>();
+        let graph = DependencyGraph::from_outputs(&outputs);
+        for package in &mut self.packages {
+            package.dependencies = graph
+                .edges
+                .iter()
+                .filter(|edge| edge.to == package.identifier)
+                .map(|edge| edge.from.clone())
+                .collect();
+        }
+    }
+
+    /// Queues the package and the packages that it depends on (unless they are built already).
+    pub fn queue(&mut self, index: usize) {
+        let mut pending = vec![index];
+        while let Some(index) = pending.pop() {
+            let package = &mut self.packages[index];
+            if matches!(
+                package.build_progress,
+                BuildProgress::Queued | BuildProgress::Building | BuildProgress::Done
+            ) {
+                continue;
+            }
+            package.build_progress = BuildProgress::Queued;
+            let dependencies = package.dependencies.clone();
+            pending.extend(dependencies.iter().filter_map(|dependency| {
+                self.packages
+                    .iter()
+                    .position(|p| &p.identifier == dependency)
+            }));
+        }
+    }
+
+    /// Marks the queued packages whose dependencies are built as building (as long as fewer
+    /// than `max_builds` packages are building) and returns their indices.
+    pub fn next_builds(&mut self) -> Vec<usize> {
+        let progress = |state: &Self, identifier: &str| {
+            state
+                .packages
+                .iter()
+                .find(|p| p.identifier == identifier)
+                .map(|p| p.build_progress.clone())
+        };
+        // Drop the queued packages of which a dependency failed (or was dropped)
+        let mut dropped = true;
+        while dropped {
+            dropped = false;
+            for index in 0..self.packages.len() {
+                let package = &self.packages[index];
+                if package.build_progress == BuildProgress::Queued
+                    && package.dependencies.iter().any(|dependency| {
+                        matches!(
+                            progress(self, dependency),
+                            Some(BuildProgress::Failed | BuildProgress::None)
+                        )
+                    })
+                {
+                    tracing::warn!(
+                        "Not building {} because a dependency was not built",
+                        package.identifier
+                    );
+                    self.packages[index].build_progress = BuildProgress::None;
+                    dropped = true;
+                }
+            }
+        }
+
+        let mut building = self
+            .packages
+            .iter()
+            .filter(|p| p.build_progress.is_building())
+            .count();
+        let mut started = Vec::new();
+        for index in 0..self.packages.len() {
+            if building >= self.max_builds {
+                break;
+            }
+            let package = &self.packages[index];
+            if package.build_progress == BuildProgress::Queued
+                && package.dependencies.iter().all(|dependency| {
+                    progress(self, dependency).is_none_or(|p| p == BuildProgress::Done)
+                })
+            {
+                self.packages[index].build_progress = BuildProgress::Building;
+                building += 1;
+                started.push(index);
+            }
+        }
+
+        // The remaining packages wait for each other (e.g. cyclic run dependencies)
+        if building == 0 {
+            for package in &mut self.packages {
+                if package.build_progress == BuildProgress::Queued {
+                    tracing::warn!(
+                        "Not building {} because of a dependency cycle",
+                        package.identifier
+                    );
+                    package.build_progress = BuildProgress::None;
+                }
+            }
+        }
+        started
     }
 }
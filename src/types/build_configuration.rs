@@ -55,6 +55,16 @@ pub struct BuildConfiguration {
     /// build script or not
     #[serde(skip_serializing, default = "default_true")]
     pub force_colors: bool,
+    /// Whether to set the environment variables of conda-build that are not set by default
+    #[serde(skip_serializing, default)]
+    pub conda_build_env_vars: bool,
+    /// Whether to use `zig cc` instead of the compiler packages for Linux targets that are
+    /// cross-compiled or have an older glibc baseline than the build machine
+    #[serde(skip_serializing, default)]
+    pub zig_cc: bool,
+    /// Whether to report which build and host requirements are likely unused
+    #[serde(skip_serializing, default)]
+    pub report_dependency_usage: bool,
 
     /// The configuration for the sandbox
     #[serde(skip_serializing, default)]
@@ -73,9 +83,17 @@ impl BuildConfiguration {
         self.target_platform != self.build_platform.platform
     }
 
-    /// Retrieve the sandbox configuration for this output
-    pub fn sandbox_config(&self) -> Option<&SandboxConfiguration> {
-        self.sandbox_config.as_ref()
+    /// Retrieve the sandbox configuration for this output. A hermetic build can also read the
+    /// recipe and the output directory with the caches.
+    pub fn sandbox_config(&self) -> Option<SandboxConfiguration> {
+        let config = self.sandbox_config.clone()?;
+        if !config.is_hermetic() {
+            return Some(config);
+        }
+        Some(config.with_read([
+            self.directories.recipe_dir.clone(),
+            self.directories.output_dir.clone(),
+        ]))
     }
 
     /// Construct a `JinjaConfig` from the given `BuildConfiguration`
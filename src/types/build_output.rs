@@ -2,7 +2,7 @@ use fs_err as fs;
 use indicatif::HumanBytes;
 use rattler_build_jinja::Variable;
 use rattler_build_recipe::{Stage1Recipe, stage1::Source};
-use rattler_build_types::NormalizedKey;
+use rattler_build_types::{NormalizedKey, is_free_threaded_python};
 use rattler_conda_types::{
     PackageName, Platform, RepoDataRecord, VersionWithSource,
     package::{PathType, PathsEntry, PathsJson},
@@ -22,7 +22,10 @@ use crate::{
     console_utils::github_integration_enabled,
     render::resolved_dependencies::FinalizedDependencies,
     system_tools::SystemTools,
-    types::{BuildConfiguration, BuildSummary, PlatformWithVirtualPackages},
+    types::{
+        BuildConfiguration, BuildPhase, BuildSummary, CacheUse, PhaseTiming,
+        PlatformWithVirtualPackages,
+    },
 };
 
 /// A output. This is the central element that is passed to the `run_build`
@@ -109,12 +112,44 @@ impl BuildOutput {
         summary.paths = Some(paths.clone());
     }
 
+    /// Record a phase of the build that started at `start` and has just finished
+    pub fn record_phase(&self, phase: BuildPhase, start: chrono::DateTime<chrono::Utc>) {
+        let duration = (chrono::Utc::now() - start).to_std().unwrap_or_default();
+        self.build_summary.lock().unwrap().phases.push(PhaseTiming {
+            phase,
+            start,
+            duration,
+        });
+    }
+
+    /// Record how a cache was used during the build
+    pub fn record_cache_use(&self, cache_use: CacheUse) {
+        let mut summary = self.build_summary.lock().unwrap();
+        // a staging cache that is inherited from is restored again after it was built
+        if let CacheUse::Staging { name, .. } = &cache_use
+            && summary
+                .cache_use
+                .iter()
+                .any(|recorded| matches!(recorded, CacheUse::Staging { name: n, .. } if n == name))
+        {
+            return;
+        }
+        summary.cache_use.push(cache_use);
+    }
+
     /// Record the end of the build
     pub fn record_build_end(&self) {
         let mut summary = self.build_summary.lock().unwrap();
         summary.build_end = Some(chrono::Utc::now());
     }
 
+    /// Record that the build of this output failed
+    pub fn record_failure(&self) {
+        let mut summary = self.build_summary.lock().unwrap();
+        summary.build_end = Some(chrono::Utc::now());
+        summary.failed = true;
+    }
+
     /// Shorthand to retrieve the variant configuration for this output
     pub fn variant(&self) -> &BTreeMap<NormalizedKey, Variable> {
         &self.build_configuration.variant
@@ -367,9 +402,21 @@ impl BuildOutput {
         Ok(())
     }
 
-    /// Check if this package is python version independent (ABI3 or noarch) package
+    /// Whether the package is built against the free-threaded Python ABI (e.g. `cp313t`),
+    /// according to the `python` variant or the `python` package in the host environment
+    pub(crate) fn is_free_threaded_python(&self) -> bool {
+        self.variant()
+            .get(&"python".into())
+            .is_some_and(|python| is_free_threaded_python(&python.to_string()))
+            || self
+                .find_resolved_package("python")
+                .is_some_and(|(record, _)| is_free_threaded_python(&record.package_record.build))
+    }
+
+    /// Check if this package is python version independent (ABI3 or noarch) package. The
+    /// free-threaded ABI has no stable ABI, so these packages are never ABI3 packages.
     pub(crate) fn is_python_version_independent(&self) -> bool {
-        self.recipe.build.python.version_independent
+        (self.recipe.build.python.version_independent && !self.is_free_threaded_python())
             || self
                 .recipe
                 .build
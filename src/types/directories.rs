@@ -8,6 +8,9 @@ use dunce::canonicalize;
 
 use crate::utils::remove_dir_all_force;
 
+/// Default length of the padded host prefix on Unix
+pub const DEFAULT_PLACEHOLDER_LENGTH: usize = 255;
+
 /// Builder for creating [`Directories`] with a fluent API.
 #[derive(Debug, Clone)]
 pub struct DirectoriesBuilder<'a> {
@@ -18,6 +21,7 @@ pub struct DirectoriesBuilder<'a> {
     no_build_id: bool,
     merge_build_and_host: bool,
     skip_directory_creation: bool,
+    placeholder_length: Option<usize>,
 }
 
 impl<'a> DirectoriesBuilder<'a> {
@@ -36,6 +40,7 @@ impl<'a> DirectoriesBuilder<'a> {
             no_build_id: false,
             merge_build_and_host: false,
             skip_directory_creation: false,
+            placeholder_length: None,
         }
     }
 
@@ -58,6 +63,13 @@ impl<'a> DirectoriesBuilder<'a> {
         self
     }
 
+    /// Length of the padded host prefix on Unix. Defaults to
+    /// [`DEFAULT_PLACEHOLDER_LENGTH`].
+    pub fn placeholder_length(mut self, length: Option<usize>) -> Self {
+        self.placeholder_length = length;
+        self
+    }
+
     /// Build the [`Directories`] struct.
     pub fn build(self) -> Result<Directories, std::io::Error> {
         Directories::setup_internal(
@@ -68,6 +80,7 @@ impl<'a> DirectoriesBuilder<'a> {
             self.timestamp,
             self.merge_build_and_host,
             self.skip_directory_creation,
+            self.placeholder_length,
         )
     }
 }
@@ -100,6 +113,27 @@ pub struct Directories {
     pub output_dir: PathBuf,
 }
 
+/// The host prefix padded with `_placehold` up to `placeholder_length` characters.
+/// If the build directory is already longer than that, no padding is added.
+fn padded_host_prefix(build_dir: &Path, placeholder_length: usize) -> PathBuf {
+    let base_length = build_dir.join("host_env").as_os_str().len();
+    if base_length >= placeholder_length {
+        tracing::warn!(
+            "The host prefix is already {} characters long, which does not leave room for a {} character placeholder",
+            base_length,
+            placeholder_length
+        );
+    }
+
+    let placeholder = "_placehold"
+        .chars()
+        .cycle()
+        .take(placeholder_length.saturating_sub(base_length))
+        .collect::<String>();
+
+    build_dir.join(format!("host_env{}", placeholder))
+}
+
 fn get_build_dir(
     output_dir: &Path,
     name: &str,
@@ -128,6 +162,7 @@ impl Directories {
     }
 
     /// Internal setup function called by the builder.
+    #[allow(clippy::too_many_arguments)]
     fn setup_internal(
         name: &str,
         recipe_path: &Path,
@@ -136,6 +171,7 @@ impl Directories {
         timestamp: &DateTime<Utc>,
         merge_build_and_host: bool,
         skip_directory_creation: bool,
+        placeholder_length: Option<usize>,
     ) -> Result<Directories, std::io::Error> {
         let output_dir = if skip_directory_creation {
             output_dir.to_path_buf()
@@ -169,19 +205,10 @@ impl Directories {
         let host_prefix = if cfg!(target_os = "windows") {
             build_dir.join("h_env")
         } else {
-            let placeholder_template = "_placehold";
-            let mut placeholder = String::new();
-            let placeholder_length: usize = 255;
-
-            while placeholder.len() < placeholder_length {
-                placeholder.push_str(placeholder_template);
-            }
-
-            let placeholder = placeholder
-                [0..placeholder_length - build_dir.join("host_env").as_os_str().len()]
-                .to_string();
-
-            build_dir.join(format!("host_env{}", placeholder))
+            padded_host_prefix(
+                &build_dir,
+                placeholder_length.unwrap_or(DEFAULT_PLACEHOLDER_LENGTH),
+            )
         };
 
         let directories = Directories {
@@ -334,4 +361,25 @@ mod tests {
         assert_eq!(directories.build_prefix, directories2.build_prefix);
         assert_eq!(directories.host_prefix, directories2.host_prefix);
     }
+
+    #[test]
+    fn test_padded_host_prefix() {
+        let build_dir = PathBuf::from("/tmp/rattler-build_name");
+        let base_length = build_dir.join("host_env").as_os_str().len();
+
+        let prefix = padded_host_prefix(&build_dir, 255);
+        assert_eq!(prefix.as_os_str().len(), 255);
+        assert!(
+            prefix
+                .to_string_lossy()
+                .contains("host_env_placehold_placehold")
+        );
+
+        let prefix = padded_host_prefix(&build_dir, 100);
+        assert_eq!(prefix.as_os_str().len(), 100);
+
+        // no padding if the build directory is already too long
+        let prefix = padded_host_prefix(&build_dir, base_length - 1);
+        assert_eq!(prefix, build_dir.join("host_env"));
+    }
 }
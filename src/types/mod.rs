@@ -1,6 +1,6 @@
 //! Common types used throughout rattler-build
 //! All the metadata that makes up a recipe file
-use std::{iter, path::PathBuf, str::FromStr};
+use std::{iter, path::PathBuf, str::FromStr, time::Duration};
 
 use chrono::{DateTime, Utc};
 use rattler_conda_types::{
@@ -20,10 +20,14 @@ use crate::tool_configuration;
 mod build_configuration;
 mod build_output;
 mod directories;
+mod run_exports_index;
 
 pub use build_configuration::BuildConfiguration;
 pub use build_output::BuildOutput as Output;
 pub use directories::Directories;
+pub use run_exports_index::{
+    RunExportsIndex, RunExportsIndexEntry, RunExportsIndexInfo, write_run_exports_index,
+};
 
 /// Settings when creating the package (compression etc.)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -157,8 +161,100 @@ pub struct BuildSummary {
     pub paths: Option<PathsJson>,
     ///  Whether the build was successful or not
     pub failed: bool,
+    /// The phases of the build, in the order they ran
+    pub phases: Vec<PhaseTiming>,
+    /// How the caches were used during the build
+    pub cache_use: Vec<CacheUse>,
+}
+
+/// A phase of the build of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuildPhase {
+    /// Building or restoring the staging caches
+    Staging,
+    /// Fetching the sources
+    Fetch,
+    /// Resolving and installing the build and host environments
+    Solve,
+    /// Running the build script
+    Build,
+    /// Creating and checking the package
+    Package,
+    /// Running the tests of the package
+    Test,
+}
+
+impl BuildPhase {
+    /// The name of the phase
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildPhase::Staging => "Staging",
+            BuildPhase::Fetch => "Fetch",
+            BuildPhase::Solve => "Solve",
+            BuildPhase::Build => "Build",
+            BuildPhase::Package => "Package",
+            BuildPhase::Test => "Test",
+        }
+    }
+}
+
+/// The time that a phase of the build took
+#[derive(Debug, Clone)]
+pub struct PhaseTiming {
+    /// The phase
+    pub phase: BuildPhase,
+    /// When the phase started
+    pub start: DateTime<Utc>,
+    /// How long the phase took
+    pub duration: Duration,
 }
 
+/// How a cache was used during the build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheUse {
+    /// `cached` of the `total` git and url sources were found in the source cache
+    Sources {
+        /// The number of sources that were found in the cache
+        cached: usize,
+        /// The number of sources that can be cached
+        total: usize,
+    },
+    /// A staging cache was restored from a previous build or built
+    Staging {
+        /// The name of the staging cache
+        name: String,
+        /// Whether the staging cache was restored
+        restored: bool,
+    },
+    /// The package was looked up in the remote cache
+    Remote {
+        /// Whether the package was restored from the remote cache
+        restored: bool,
+    },
+}
+
+impl std::fmt::Display for CacheUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheUse::Sources { cached, total } => write!(f, "sources: {cached}/{total} cached"),
+            CacheUse::Staging { name, restored } => write!(
+                f,
+                "staging {name}: {}",
+                if *restored { "restored" } else { "built" }
+            ),
+            CacheUse::Remote { restored } => write!(
+                f,
+                "remote cache: {}",
+                if *restored { "restored" } else { "built" }
+            ),
+        }
+    }
+}
+
+/// Serializes the reindexing of the output channel, as builds that run concurrently (e.g. in the
+/// TUI) share the output channel
+static OUTPUT_CHANNEL_INDEX_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
 /// Builds the channel list and reindexes the output channel.
 pub async fn build_reindexed_channels(
     build_configuration: &BuildConfiguration,
@@ -166,6 +262,7 @@ pub async fn build_reindexed_channels(
 ) -> Result<Vec<ChannelUrl>, std::io::Error> {
     let output_dir = &build_configuration.directories.output_dir;
     let output_channel = Channel::from_directory(output_dir);
+    let _lock = OUTPUT_CHANNEL_INDEX_LOCK.lock().await;
 
     // Clear the repodata gateway of any cached values for the output channel.
     tool_configuration.repodata_gateway.clear_repodata_cache(
@@ -196,6 +293,9 @@ pub async fn build_reindexed_channels(
         .await
         .map_err(std::io::Error::other)?;
 
+    // Serve the run exports of the packages next to the repodata (CEP-12)
+    write_run_exports_index(output_dir, build_configuration.target_platform)?;
+
     Ok(iter::once(output_channel.base_url)
         .chain(build_configuration.channels.iter().cloned())
         .collect())
@@ -0,0 +1,168 @@
+//! Channel level `run_exports.json` as described in CEP-12.
+//!
+//! The file lives next to `repodata.json` in every subdir and maps each package filename to the
+//! contents of its `info/run_exports.json`. Solvers and indexers can read the run exports of a
+//! package from it without downloading and unpacking the package itself.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use rattler_conda_types::{
+    Platform,
+    package::{CondaArchiveType, RunExportsJson},
+};
+use rattler_package_streaming::seek::read_package_file;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The `info` section of the channel level `run_exports.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunExportsIndexInfo {
+    /// The subdir that this file describes
+    pub subdir: String,
+    /// The version of the file format
+    pub version: u64,
+}
+
+/// A single package entry of the channel level `run_exports.json`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunExportsIndexEntry {
+    /// The run exports of the package (empty if the package has none)
+    pub run_exports: RunExportsJson,
+}
+
+/// The contents of `<subdir>/run_exports.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunExportsIndex {
+    /// Information about the subdir
+    pub info: RunExportsIndexInfo,
+    /// The `.tar.bz2` packages in the subdir
+    #[serde(default)]
+    pub packages: BTreeMap<String, RunExportsIndexEntry>,
+    /// The `.conda` packages in the subdir
+    #[serde(default, rename = "packages.conda")]
+    pub conda_packages: BTreeMap<String, RunExportsIndexEntry>,
+}
+
+impl RunExportsIndex {
+    /// Collect the run exports of all packages in `subdir_path`
+    pub fn from_subdir(subdir_path: &Path, subdir: Platform) -> Result<Self, std::io::Error> {
+        let mut package_paths: Vec<(PathBuf, CondaArchiveType)> = Vec::new();
+        if subdir_path.is_dir() {
+            for entry in fs::read_dir(subdir_path)? {
+                let path = entry?.path();
+                if let Some(archive_type) = CondaArchiveType::try_from(&path) {
+                    package_paths.push((path, archive_type));
+                }
+            }
+        }
+
+        let entries = package_paths
+            .par_iter()
+            .map(|(path, archive_type)| {
+                // packages without run exports don't contain the file
+                let run_exports: RunExportsJson = read_package_file(path).unwrap_or_default();
+                let file_name = path
+                    .file_name()
+                    .expect("package path should have a file name")
+                    .to_string_lossy()
+                    .to_string();
+                (
+                    file_name,
+                    *archive_type,
+                    RunExportsIndexEntry { run_exports },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut packages = BTreeMap::new();
+        let mut conda_packages = BTreeMap::new();
+        for (file_name, archive_type, entry) in entries {
+            match archive_type {
+                CondaArchiveType::TarBz2 => packages.insert(file_name, entry),
+                CondaArchiveType::Conda => conda_packages.insert(file_name, entry),
+            };
+        }
+
+        Ok(Self {
+            info: RunExportsIndexInfo {
+                subdir: subdir.to_string(),
+                version: 1,
+            },
+            packages,
+            conda_packages,
+        })
+    }
+}
+
+/// Write `<channel>/<subdir>/run_exports.json` for the packages currently in the subdir
+pub fn write_run_exports_index(channel: &Path, subdir: Platform) -> Result<(), std::io::Error> {
+    let subdir_path = channel.join(subdir.as_str());
+    if !subdir_path.is_dir() {
+        return Ok(());
+    }
+
+    let index = RunExportsIndex::from_subdir(&subdir_path, subdir)?;
+    let file = fs::File::create(subdir_path.join("run_exports.json"))?;
+    serde_json::to_writer_pretty(file, &index)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run_exports_index_serialization() {
+        let mut conda_packages = BTreeMap::new();
+        conda_packages.insert(
+            "foo-1.0-h123_0.conda".to_string(),
+            RunExportsIndexEntry {
+                run_exports: RunExportsJson {
+                    weak: vec!["foo >=1.0,<2.0a0".to_string()],
+                    ..Default::default()
+                },
+            },
+        );
+        let mut packages = BTreeMap::new();
+        packages.insert(
+            "bar-2.0-h456_0.tar.bz2".to_string(),
+            RunExportsIndexEntry::default(),
+        );
+
+        let index = RunExportsIndex {
+            info: RunExportsIndexInfo {
+                subdir: "linux-64".to_string(),
+                version: 1,
+            },
+            packages,
+            conda_packages,
+        };
+
+        let json = serde_json::to_string_pretty(&index).unwrap();
+        insta::assert_snapshot!(json);
+
+        let parsed: RunExportsIndex = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, index);
+    }
+
+    #[test]
+    fn test_run_exports_index_missing_subdir() {
+        let tmp = tempfile::tempdir().unwrap();
+        write_run_exports_index(tmp.path(), Platform::Linux64).unwrap();
+        assert!(!tmp.path().join("linux-64/run_exports.json").exists());
+
+        fs::create_dir_all(tmp.path().join("linux-64")).unwrap();
+        write_run_exports_index(tmp.path(), Platform::Linux64).unwrap();
+        let index: RunExportsIndex = serde_json::from_str(
+            &fs::read_to_string(tmp.path().join("linux-64/run_exports.json")).unwrap(),
+        )
+        .unwrap();
+        assert!(index.packages.is_empty());
+        assert!(index.conda_packages.is_empty());
+        assert_eq!(index.info.subdir, "linux-64");
+    }
+}
@@ -0,0 +1,224 @@
+//! Promote packages on anaconda.org from one label to another (`upload anaconda-promote`).
+//!
+//! Uploading to anaconda.org (with multiple labels and `--force`) is handled by
+//! `rattler_upload`. Packages are often uploaded to a label like `rc` first, and only added to
+//! `main` after they have been validated. The files of the package keep their checksum, so
+//! users that validated the package from `rc` get exactly the same file from `main`.
+use std::path::Path;
+
+use miette::IntoDiagnostic;
+use rattler_conda_types::package::IndexJson;
+use rattler_networking::Authentication;
+use rattler_package_streaming::seek::read_package_file;
+use reqwest::{StatusCode, header};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use super::{UploadOutcome, Uploader, check_response, upload_client};
+use crate::{opt::AnacondaPromoteOpts, tool_configuration::get_auth_store};
+
+/// The file of a package on anaconda.org
+#[derive(Deserialize)]
+struct Distribution {
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// The web page of a package, e.g. `https://anaconda.org/<owner>/<package>` for the API at
+/// `https://api.anaconda.org`
+fn package_page(api_url: &Url, owner: &str, package: &str) -> String {
+    let base = match api_url.host_str() {
+        Some(host) if host.starts_with("api.") => {
+            format!("{}://{}", api_url.scheme(), &host["api.".len()..])
+        }
+        _ => api_url.as_str().trim_end_matches('/').to_string(),
+    };
+    format!("{base}/{owner}/{package}")
+}
+
+/// Promotes packages between the labels of an anaconda.org owner
+pub(crate) struct AnacondaPromoter {
+    client: ClientWithMiddleware,
+    url: Url,
+    token: String,
+    opts: AnacondaPromoteOpts,
+    dry_run: bool,
+}
+
+impl AnacondaPromoter {
+    /// Create a promoter for `opts`. The token is read from the keychain / auth-file if
+    /// `opts` has no API key.
+    pub fn new(
+        mut opts: AnacondaPromoteOpts,
+        dry_run: bool,
+        auth_file: Option<std::path::PathBuf>,
+        allow_insecure_host: Option<Vec<String>>,
+    ) -> miette::Result<Self> {
+        let token = match opts.api_key.take() {
+            Some(token) => token,
+            None => {
+                let storage = get_auth_store(auth_file).into_diagnostic()?;
+                match storage
+                    .get("anaconda.org")
+                    .map_err(|e| miette::miette!("{}", e))?
+                {
+                    Some(Authentication::CondaToken(token)) => token,
+                    _ => {
+                        return Err(miette::miette!(
+                            "No anaconda.org API key was given and no conda token was found in the keychain / auth file"
+                        ));
+                    }
+                }
+            }
+        };
+
+        let mut url = opts.url.clone();
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Ok(Self {
+            client: upload_client(&url, allow_insecure_host),
+            url,
+            token,
+            opts,
+            dry_run,
+        })
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+            .header(header::AUTHORIZATION, format!("token {}", self.token))
+            .header(header::ACCEPT, "application/json")
+    }
+
+    /// Add (`add = true`) or remove the file `basename` of a package to / from `label`
+    async fn change_label(
+        &self,
+        label: &str,
+        add: bool,
+        package: &str,
+        version: &str,
+        basename: &str,
+    ) -> miette::Result<()> {
+        let url = self
+            .url
+            .join(&format!("channels/{}/{}", self.opts.owner, label))
+            .into_diagnostic()?;
+        let request = if add {
+            self.client.post(url)
+        } else {
+            self.client.delete(url)
+        };
+        let payload = json!({ "package": package, "version": version, "basename": basename });
+        let response = self
+            .request(request.json(&payload))
+            .send()
+            .await
+            .into_diagnostic()?;
+        let action = if add {
+            format!("Adding the package to the label {label}")
+        } else {
+            format!("Removing the package from the label {label}")
+        };
+        check_response(response, &action).await?;
+        Ok(())
+    }
+}
+
+impl Uploader for AnacondaPromoter {
+    /// Promote a single package from `from_label` to `to_label`
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let index_json: IndexJson = read_package_file(package_file).into_diagnostic()?;
+        let subdir = index_json.subdir.clone().ok_or_else(|| {
+            miette::miette!(
+                "The package {} does not have a subdir",
+                package_file.display()
+            )
+        })?;
+        let file_name = package_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let package = index_json.name.as_normalized();
+        let version = index_json.version.to_string();
+        let basename = format!("{subdir}/{file_name}");
+        let (owner, from, to) = (&self.opts.owner, &self.opts.from_label, &self.opts.to_label);
+
+        // check that the package was uploaded to the label it is promoted from
+        let url = self
+            .url
+            .join(&format!("dist/{owner}/{package}/{version}/{basename}"))
+            .into_diagnostic()?;
+        let response = self
+            .request(self.client.get(url))
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(miette::miette!(
+                "The package {} was not found on anaconda.org/{}",
+                basename,
+                owner
+            ));
+        }
+        let distribution: Distribution = check_response(response, "Fetching the package")
+            .await?
+            .json()
+            .await
+            .into_diagnostic()?;
+
+        let in_from = distribution.labels.contains(from);
+        let in_to = distribution.labels.contains(to);
+        if !in_from && in_to {
+            return Ok(UploadOutcome::Skipped(format!("already in the label {to}")));
+        }
+        if !in_from {
+            return Err(miette::miette!(
+                "The package {} is not in the label {} (labels: {})",
+                basename,
+                from,
+                distribution.labels.join(", ")
+            ));
+        }
+
+        let page = package_page(&self.url, owner, package);
+        if self.dry_run {
+            return Ok(UploadOutcome::WouldUpload(format!(
+                "{page} ({from} -> {to})"
+            )));
+        }
+
+        if !in_to {
+            tracing::info!("Adding {} to the label {}", basename, to);
+            self.change_label(to, true, package, &version, &basename)
+                .await?;
+        }
+        if !self.opts.keep_from_label {
+            tracing::info!("Removing {} from the label {}", basename, from);
+            self.change_label(from, false, package, &version, &basename)
+                .await?;
+        }
+
+        Ok(UploadOutcome::Uploaded(Some(format!("{page} ({to})"))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_page() {
+        let page = |url: &str| package_page(&Url::parse(url).unwrap(), "owner", "foo");
+        assert_eq!(
+            page("https://api.anaconda.org/"),
+            "https://anaconda.org/owner/foo"
+        );
+        assert_eq!(
+            page("https://anaconda.example.com/api"),
+            "https://anaconda.example.com/api/owner/foo"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Upload packages to an Artifactory channel (`upload artifactory`).
+//!
+//! Packages are deployed by checksum first: if Artifactory already stores a file with the same
+//! SHA1, the package is linked to it without transferring it again. Packages that already exist
+//! with the same SHA256 are skipped. Properties (e.g. the build number and git commit on CI) are
+//! set on the uploaded packages as matrix parameters of the deploy request.
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_conda_types::package::IndexJson;
+use rattler_digest::{Md5, Sha256, compute_bytes_digest};
+use rattler_networking::Authentication;
+use rattler_package_streaming::seek::read_package_file;
+use reqwest::StatusCode;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::Deserialize;
+use url::Url;
+
+use super::{UploadOutcome, Uploader, check_response, upload_client};
+use crate::{opt::ArtifactoryUploadOpts, tool_configuration::get_auth_store};
+
+/// The ways to authenticate with Artifactory
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Credentials {
+    /// An access token, sent as bearer token
+    AccessToken(String),
+    /// An API key, sent in the `X-JFrog-Art-Api` header
+    ApiKey(String),
+    /// A username and password (or identity token), sent as basic auth
+    Basic { username: String, password: String },
+}
+
+impl Credentials {
+    /// The credentials from the arguments, or from the keychain / auth-file
+    fn from_opts(opts: &ArtifactoryUploadOpts, auth_file: Option<PathBuf>) -> miette::Result<Self> {
+        match (&opts.token, &opts.api_key, &opts.username, &opts.password) {
+            (Some(token), _, _, _) => return Ok(Self::AccessToken(token.clone())),
+            (_, Some(api_key), _, _) => return Ok(Self::ApiKey(api_key.clone())),
+            (_, _, Some(username), Some(password)) => {
+                return Ok(Self::Basic {
+                    username: username.clone(),
+                    password: password.clone(),
+                });
+            }
+            _ => {}
+        }
+
+        let storage = get_auth_store(auth_file)
+            .map_err(|e| miette::miette!("Failed to get authentication storage: {}", e))?;
+        match storage.get_by_url(opts.url.clone()) {
+            Ok((_, Some(Authentication::BearerToken(token)))) => Ok(Self::AccessToken(token)),
+            Ok((_, Some(Authentication::BasicHTTP { username, password }))) => {
+                Ok(Self::Basic { username, password })
+            }
+            Ok((_, Some(_))) => Err(miette::miette!(
+                "Authentication information for Artifactory was found in the keychain / auth file, but it is neither a bearer token nor a username and password"
+            )),
+            Ok((_, None)) => Err(miette::miette!(
+                "No access token, API key or username and password were given, and none was found in the keychain / auth file"
+            )),
+            Err(e) => Err(miette::miette!(
+                "Failed to get authentication information from the keychain: {}",
+                e
+            )),
+        }
+    }
+
+    fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::AccessToken(token) => request.bearer_auth(token),
+            Self::ApiKey(api_key) => request.header("X-JFrog-Art-Api", api_key),
+            Self::Basic { username, password } => request.basic_auth(username, Some(password)),
+        }
+    }
+}
+
+/// The properties of the CI build, read with `env`: `build.number` and `vcs.revision` on GitHub
+/// Actions and GitLab CI
+fn ci_properties(env: impl Fn(&str) -> Option<String>) -> Vec<(String, String)> {
+    let (build_number, revision) = if env("GITHUB_ACTIONS").as_deref() == Some("true") {
+        (env("GITHUB_RUN_ID"), env("GITHUB_SHA"))
+    } else if env("GITLAB_CI").as_deref() == Some("true") {
+        (env("CI_PIPELINE_ID"), env("CI_COMMIT_SHA"))
+    } else {
+        (None, None)
+    };
+
+    [("build.number", build_number), ("vcs.revision", revision)]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value?)))
+        .collect()
+}
+
+/// Percent-encode everything but unreserved characters, so that `;`, `=` and `,` in keys and
+/// values are not interpreted as separators of the matrix parameters
+fn encode_matrix_param(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The matrix parameters that set `properties` on a deployed file, e.g. `;key=value`
+fn matrix_params(properties: &[(String, String)]) -> String {
+    properties
+        .iter()
+        .map(|(key, value)| {
+            format!(
+                ";{}={}",
+                encode_matrix_param(key),
+                encode_matrix_param(value)
+            )
+        })
+        .collect()
+}
+
+/// The response of the storage API for a file
+#[derive(Deserialize)]
+struct FileInfo {
+    #[serde(default)]
+    checksums: Checksums,
+}
+
+#[derive(Default, Deserialize)]
+struct Checksums {
+    sha256: Option<String>,
+}
+
+/// Uploads packages to an Artifactory channel
+pub(crate) struct ArtifactoryUploader {
+    client: ClientWithMiddleware,
+    url: Url,
+    channel: String,
+    credentials: Credentials,
+    properties: Vec<(String, String)>,
+    dry_run: bool,
+}
+
+impl ArtifactoryUploader {
+    /// Create an uploader for the channel of `opts`
+    pub fn new(
+        opts: ArtifactoryUploadOpts,
+        dry_run: bool,
+        auth_file: Option<PathBuf>,
+        allow_insecure_host: Option<Vec<String>>,
+    ) -> miette::Result<Self> {
+        let credentials = Credentials::from_opts(&opts, auth_file)?;
+
+        // properties from the arguments take precedence over the detected ones
+        let mut properties = ci_properties(|key| std::env::var(key).ok());
+        properties.retain(|(key, _)| !opts.properties.iter().any(|(k, _)| k == key));
+        properties.extend(opts.properties);
+
+        let mut url = opts.url;
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Ok(Self {
+            client: upload_client(&url, allow_insecure_host),
+            url,
+            channel: opts.channels.trim_matches('/').to_string(),
+            credentials,
+            properties,
+            dry_run,
+        })
+    }
+}
+
+impl Uploader for ArtifactoryUploader {
+    /// Upload a single package. Packages that already exist with the same checksum are skipped.
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let index_json: IndexJson = read_package_file(package_file).into_diagnostic()?;
+        let subdir = index_json.subdir.ok_or_else(|| {
+            miette::miette!(
+                "index.json of package {} has no subdirectory. Cannot determine which directory to upload to",
+                package_file.display()
+            )
+        })?;
+        let file_name = package_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = format!("{}/{}/{}", self.channel, subdir, file_name);
+        let file_url = self.url.join(&path).into_diagnostic()?;
+
+        let data = fs::read(package_file).into_diagnostic()?;
+        let sha256 = format!("{:x}", compute_bytes_digest::<Sha256>(&data));
+        let sha1 = format!("{:x}", compute_bytes_digest::<sha1::Sha1>(&data));
+        let md5 = format!("{:x}", compute_bytes_digest::<Md5>(&data));
+
+        let response = self
+            .credentials
+            .apply(
+                self.client.get(
+                    self.url
+                        .join(&format!("api/storage/{path}"))
+                        .into_diagnostic()?,
+                ),
+            )
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() != StatusCode::NOT_FOUND {
+            let info: FileInfo = check_response(response, "Fetching the package information")
+                .await?
+                .json()
+                .await
+                .into_diagnostic()?;
+            if info.checksums.sha256.as_deref() == Some(sha256.as_str()) {
+                return Ok(UploadOutcome::Skipped(format!(
+                    "already exists as {file_url}"
+                )));
+            }
+        }
+
+        if self.dry_run {
+            return Ok(UploadOutcome::WouldUpload(file_url.to_string()));
+        }
+
+        let deploy_url = self
+            .url
+            .join(&format!("{path}{}", matrix_params(&self.properties)))
+            .into_diagnostic()?;
+        let deploy = |request: RequestBuilder| {
+            self.credentials
+                .apply(request)
+                .header("X-Checksum-Sha1", &sha1)
+                .header("X-Checksum-Sha256", &sha256)
+        };
+
+        // only the checksums are sent, Artifactory answers with 404 if it does not have the file
+        let response = deploy(self.client.put(deploy_url.clone()))
+            .header("X-Checksum-Deploy", "true")
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() != StatusCode::NOT_FOUND {
+            check_response(response, "Deploying the package by checksum").await?;
+            tracing::info!("Deployed {} by checksum", file_name);
+            return Ok(UploadOutcome::Uploaded(Some(file_url.to_string())));
+        }
+
+        tracing::info!("Uploading {} to {}", file_name, self.channel);
+        let response = deploy(self.client.put(deploy_url))
+            .header("X-Checksum", &md5)
+            .body(data)
+            .send()
+            .await
+            .into_diagnostic()?;
+        check_response(response, "Uploading the package").await?;
+        Ok(UploadOutcome::Uploaded(Some(file_url.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_ci_properties() {
+        let github = HashMap::from([
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_RUN_ID", "123"),
+            ("GITHUB_SHA", "abc"),
+        ]);
+        assert_eq!(
+            ci_properties(|key| github.get(key).map(|value| value.to_string())),
+            vec![
+                ("build.number".to_string(), "123".to_string()),
+                ("vcs.revision".to_string(), "abc".to_string()),
+            ]
+        );
+
+        let gitlab = HashMap::from([("GITLAB_CI", "true"), ("CI_COMMIT_SHA", "def")]);
+        assert_eq!(
+            ci_properties(|key| gitlab.get(key).map(|value| value.to_string())),
+            vec![("vcs.revision".to_string(), "def".to_string())]
+        );
+
+        assert!(ci_properties(|_| None).is_empty());
+    }
+
+    #[test]
+    fn test_matrix_params() {
+        let properties = vec![
+            ("build.number".to_string(), "42".to_string()),
+            ("description".to_string(), "a;b=c, d".to_string()),
+        ];
+        assert_eq!(
+            matrix_params(&properties),
+            ";build.number=42;description=a%3Bb%3Dc%2C%20d"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Checks of the packages against the channels that are uploaded to with `rattler_upload`
+//! (prefix.dev, anaconda.org, Quetz and S3).
+//!
+//! The repodata of the channel is fetched to check whether the packages already exist. Packages
+//! with the same SHA256 hash as the package in the channel are skipped. For `upload --dry-run`,
+//! the credentials for the server are looked up as well; fetching the repodata of a private
+//! channel also validates them.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use miette::IntoDiagnostic;
+use rattler_build_networking::BaseClient;
+use rattler_conda_types::{RepoData, package::IndexJson};
+use rattler_digest::{Sha256, Sha256Hash, compute_file_digest};
+use rattler_package_streaming::seek::read_package_file;
+use rattler_upload::upload::opt::{CommonOpts, ServerType};
+use reqwest::StatusCode;
+use reqwest_middleware::ClientWithMiddleware;
+use url::Url;
+
+use super::{UploadOutcome, check_response};
+use crate::tool_configuration::{APP_USER_AGENT, get_auth_store};
+
+/// What happens to packages that already exist in the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Existing {
+    /// The upload fails
+    Fail,
+    /// The package is skipped (`--skip-existing`)
+    Skip,
+    /// The package is replaced (`--force`)
+    Replace,
+}
+
+/// The channel that packages are uploaded to
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ChannelTarget {
+    /// The URL that the packages can be installed from, with a trailing slash
+    channel_url: Url,
+    /// The host to look up credentials for
+    host: String,
+    /// Whether an API key was given in the arguments
+    has_api_key: bool,
+    /// The header to authenticate the repodata requests with the API key
+    api_key_header: Option<(&'static str, String)>,
+    existing: Existing,
+}
+
+impl ChannelTarget {
+    fn new(server_type: &ServerType) -> miette::Result<Self> {
+        let with_slash = |url: &Url, path: &str| {
+            let mut url = url.clone();
+            url.set_path(&format!("{}/{}/", url.path().trim_end_matches('/'), path));
+            url
+        };
+        let host = |url: &Url| url.host_str().unwrap_or_default().to_string();
+
+        let target = match server_type {
+            ServerType::Quetz(opts) => Self {
+                channel_url: with_slash(&opts.url, &format!("get/{}", opts.channels)),
+                host: host(&opts.url),
+                has_api_key: opts.api_key.is_some(),
+                api_key_header: opts.api_key.clone().map(|key| ("X-API-Key", key)),
+                existing: Existing::Fail,
+            },
+            ServerType::Prefix(opts) => Self {
+                channel_url: with_slash(&opts.url, &opts.channel),
+                host: host(&opts.url),
+                has_api_key: opts.api_key.is_some(),
+                api_key_header: opts
+                    .api_key
+                    .clone()
+                    .map(|key| ("Authorization", format!("Bearer {key}"))),
+                existing: if opts.force {
+                    Existing::Replace
+                } else if opts.skip_existing {
+                    Existing::Skip
+                } else {
+                    Existing::Fail
+                },
+            },
+            ServerType::Anaconda(opts) => {
+                // packages are installed from conda.anaconda.org, not from the API server
+                let label = opts
+                    .channels
+                    .as_ref()
+                    .and_then(|labels| labels.first())
+                    .filter(|label| label.as_str() != "main");
+                let path = match label {
+                    Some(label) => format!("{}/label/{}", opts.owner, label),
+                    None => opts.owner.clone(),
+                };
+                let base = Url::parse("https://conda.anaconda.org").into_diagnostic()?;
+                Self {
+                    channel_url: with_slash(&base, &path),
+                    host: "anaconda.org".to_string(),
+                    has_api_key: opts.api_key.is_some(),
+                    // the repodata of public channels is fetched without the token
+                    api_key_header: None,
+                    existing: if opts.force {
+                        Existing::Replace
+                    } else {
+                        Existing::Fail
+                    },
+                }
+            }
+            #[cfg(feature = "s3")]
+            ServerType::S3(opts) => Self {
+                channel_url: with_slash(&opts.channel, ""),
+                host: host(&opts.channel),
+                has_api_key: false,
+                api_key_header: None,
+                existing: if opts.force {
+                    Existing::Replace
+                } else {
+                    Existing::Fail
+                },
+            },
+            ServerType::Artifactory(_) | ServerType::CondaForge(_) => {
+                return Err(miette::miette!(
+                    "`--dry-run` is not supported for this server"
+                ));
+            }
+        };
+        Ok(target)
+    }
+}
+
+/// Whether a package is already in the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Presence {
+    /// There is no package with the same file name
+    Missing,
+    /// The package with the same file name has the same SHA256 hash
+    Identical,
+    /// The package with the same file name is different, or its hash is unknown
+    Different,
+}
+
+/// The SHA256 hashes of the packages in a subdir of the channel, by file name (`None` if the
+/// repodata has no hash)
+type SubdirPackages = HashMap<String, Option<Sha256Hash>>;
+
+/// Checks packages against the channel that they are uploaded to
+pub(crate) struct ChannelChecker {
+    target: ChannelTarget,
+    /// The packages in each subdir of the channel
+    existing: HashMap<String, SubdirPackages>,
+}
+
+impl ChannelChecker {
+    /// Look up the credentials for `server_type` and fetch the repodata of the subdirs of
+    /// `package_files` (`--dry-run`)
+    pub async fn new(
+        server_type: &ServerType,
+        package_files: &[PathBuf],
+        common: &CommonOpts,
+    ) -> miette::Result<Self> {
+        let target = ChannelTarget::new(server_type)?;
+
+        let auth_storage = get_auth_store(common.auth_file.clone())
+            .map_err(|e| miette::miette!("Failed to get authentication storage: {}", e))?;
+        let stored_credentials = auth_storage
+            .get(&target.host)
+            .map_err(|e| miette::miette!("Failed to read the keychain / auth file: {}", e))?;
+        let on_ci = std::env::var("ACTIONS_ID_TOKEN_REQUEST_URL").is_ok();
+        match (target.has_api_key, stored_credentials) {
+            (true, _) => tracing::info!("Using the API key from the arguments"),
+            (false, Some(_)) => {
+                tracing::info!(
+                    "Using the credentials for {} from the keychain / auth file",
+                    target.host
+                )
+            }
+            (false, None) if on_ci => {
+                tracing::info!("No credentials found, trusted publishing would be used")
+            }
+            #[cfg(feature = "s3")]
+            (false, None) if matches!(server_type, ServerType::S3(_)) => {
+                tracing::info!("No credentials found, the AWS credential chain would be used")
+            }
+            (false, None) => {
+                return Err(miette::miette!(
+                    "No API key was given and no credentials for {} were found in the keychain / auth file",
+                    target.host
+                ));
+            }
+        }
+
+        Self::fetch(server_type, package_files, common).await
+    }
+
+    /// Fetch the repodata of the subdirs of `package_files` from the channel of `server_type`
+    pub async fn fetch(
+        server_type: &ServerType,
+        package_files: &[PathBuf],
+        common: &CommonOpts,
+    ) -> miette::Result<Self> {
+        let target = ChannelTarget::new(server_type)?;
+        let auth_storage = get_auth_store(common.auth_file.clone())
+            .map_err(|e| miette::miette!("Failed to get authentication storage: {}", e))?;
+
+        let mut builder = BaseClient::builder()
+            .user_agent(APP_USER_AGENT)
+            .with_authentication(auth_storage);
+        #[cfg(feature = "s3")]
+        if let ServerType::S3(opts) = server_type {
+            builder = builder.with_s3(s3_config(opts));
+        }
+        if let Some(hosts) = common.allow_insecure_host.clone() {
+            builder = builder.insecure_hosts(hosts);
+        }
+        let client = builder.build().for_host(&target.channel_url).clone();
+
+        let mut existing = HashMap::new();
+        for platform in crate::index::package_platforms(package_files)? {
+            let packages = fetch_packages(&client, &target, platform.as_str()).await?;
+            existing.insert(platform.to_string(), packages);
+        }
+        Ok(Self { target, existing })
+    }
+
+    /// The file name, the destination URL and the presence of a package in the channel
+    async fn presence(&self, package_file: &Path) -> miette::Result<(String, String, Presence)> {
+        let index_json: IndexJson = read_package_file(package_file).into_diagnostic()?;
+        let subdir = index_json.subdir.unwrap_or_else(|| "noarch".to_string());
+        let file_name = package_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let destination = format!("{}{}/{}", self.target.channel_url, subdir, file_name);
+
+        let existing = self
+            .existing
+            .get(&subdir)
+            .and_then(|packages| packages.get(&file_name));
+        let presence = match existing {
+            None => Presence::Missing,
+            Some(None) => Presence::Different,
+            Some(Some(sha256)) => {
+                let package_file = package_file.to_path_buf();
+                let package_sha256 = tokio::task::spawn_blocking(move || {
+                    compute_file_digest::<Sha256>(&package_file)
+                })
+                .await
+                .into_diagnostic()?
+                .into_diagnostic()?;
+                if package_sha256 == *sha256 {
+                    Presence::Identical
+                } else {
+                    Presence::Different
+                }
+            }
+        };
+        Ok((file_name, destination, presence))
+    }
+
+    /// Check whether a package has to be uploaded. Returns the outcome of the upload if the
+    /// package is skipped, and fails if a different package exists and cannot be replaced.
+    pub async fn check_existing(
+        &self,
+        package_file: &Path,
+    ) -> miette::Result<Option<UploadOutcome>> {
+        let (file_name, _, presence) = self.presence(package_file).await?;
+        existing_outcome(
+            presence,
+            self.target.existing,
+            &file_name,
+            &self.target.channel_url,
+        )
+    }
+
+    /// Check a single package against the repodata of the channel (`--dry-run`)
+    pub async fn check(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let (file_name, destination, presence) = self.presence(package_file).await?;
+        if let Some(outcome) = existing_outcome(
+            presence,
+            self.target.existing,
+            &file_name,
+            &self.target.channel_url,
+        )? {
+            return Ok(outcome);
+        }
+        if presence == Presence::Missing {
+            Ok(UploadOutcome::WouldUpload(destination))
+        } else {
+            Ok(UploadOutcome::WouldUpload(format!(
+                "{destination} (replacing the existing package)"
+            )))
+        }
+    }
+}
+
+/// The outcome for a package that is already in the channel, or `None` if it is uploaded
+fn existing_outcome(
+    presence: Presence,
+    existing: Existing,
+    file_name: &str,
+    channel_url: &Url,
+) -> miette::Result<Option<UploadOutcome>> {
+    match (presence, existing) {
+        (Presence::Missing, _) | (Presence::Different, Existing::Replace) => Ok(None),
+        (Presence::Identical, _) => Ok(Some(UploadOutcome::Skipped(format!(
+            "already exists in {channel_url}"
+        )))),
+        (Presence::Different, Existing::Skip) => {
+            tracing::warn!(
+                "Skipping {}: a different package with the same name already exists in {}",
+                file_name,
+                channel_url
+            );
+            Ok(Some(UploadOutcome::Skipped(format!(
+                "a different package already exists in {channel_url}"
+            ))))
+        }
+        (Presence::Different, Existing::Fail) => Err(miette::miette!(
+            "A different package {} already exists in {}",
+            file_name,
+            channel_url
+        )),
+    }
+}
+
+/// The packages in `subdir` of the channel. A missing subdir has no packages.
+async fn fetch_packages(
+    client: &ClientWithMiddleware,
+    target: &ChannelTarget,
+    subdir: &str,
+) -> miette::Result<SubdirPackages> {
+    let url = target
+        .channel_url
+        .join(&format!("{subdir}/repodata.json"))
+        .into_diagnostic()?;
+    let mut request = client.get(url);
+    if let Some((header, value)) = &target.api_key_header {
+        request = request.header(*header, value);
+    }
+    let response = request.send().await.into_diagnostic()?;
+    match response.status() {
+        StatusCode::NOT_FOUND => return Ok(HashMap::new()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Err(miette::miette!(
+                "The credentials for {} were rejected ({})",
+                target.host,
+                response.status()
+            ));
+        }
+        _ => {}
+    }
+    let repodata: RepoData = check_response(response, "Fetching the repodata")
+        .await?
+        .json()
+        .await
+        .into_diagnostic()?;
+    Ok(repodata
+        .packages
+        .into_iter()
+        .chain(repodata.conda_packages)
+        .map(|(file_name, record)| (file_name.to_string(), record.sha256))
+        .collect())
+}
+
+/// The S3 configuration for the bucket of `opts`, if a custom endpoint was given
+#[cfg(feature = "s3")]
+fn s3_config(
+    opts: &rattler_upload::upload::opt::S3Opts,
+) -> HashMap<String, rattler_networking::s3_middleware::S3Config> {
+    let bucket = opts.channel.host_str().unwrap_or_default().to_string();
+    let config = match Option::<rattler_s3::S3Credentials>::from(opts.credentials.clone()) {
+        Some(credentials) => rattler_networking::s3_middleware::S3Config::Custom {
+            endpoint_url: credentials.endpoint_url,
+            region: credentials.region,
+            force_path_style: credentials.addressing_style == rattler_s3::S3AddressingStyle::Path,
+        },
+        None => rattler_networking::s3_middleware::S3Config::FromAWS,
+    };
+    HashMap::from([(bucket, config)])
+}
+
+#[cfg(test)]
+mod tests {
+    use rattler_upload::upload::opt::{AnacondaOpts, QuetzOpts};
+
+    use super::*;
+
+    #[test]
+    fn test_channel_target() {
+        let quetz = ChannelTarget::new(&ServerType::Quetz(QuetzOpts {
+            url: Url::parse("https://quetz.example.com").unwrap(),
+            channels: "my-channel".to_string(),
+            api_key: Some("key".to_string()),
+        }))
+        .unwrap();
+        assert_eq!(
+            quetz.channel_url.as_str(),
+            "https://quetz.example.com/get/my-channel/"
+        );
+        assert!(quetz.has_api_key);
+        assert_eq!(quetz.api_key_header, Some(("X-API-Key", "key".to_string())));
+        assert_eq!(quetz.existing, Existing::Fail);
+
+        let anaconda = ChannelTarget::new(&ServerType::Anaconda(AnacondaOpts {
+            owner: "me".to_string(),
+            channels: Some(vec!["rc".to_string()]),
+            api_key: None,
+            url: None,
+            force: true,
+        }))
+        .unwrap();
+        assert_eq!(
+            anaconda.channel_url.as_str(),
+            "https://conda.anaconda.org/me/label/rc/"
+        );
+        assert_eq!(anaconda.host, "anaconda.org");
+        assert_eq!(anaconda.existing, Existing::Replace);
+    }
+
+    #[test]
+    fn test_existing_outcome() {
+        let channel = Url::parse("https://prefix.dev/my-channel/").unwrap();
+        let outcome = |presence, existing| {
+            existing_outcome(presence, existing, "foo-1.0-h0_0.conda", &channel)
+        };
+
+        // identical packages are always skipped
+        for existing in [Existing::Fail, Existing::Skip, Existing::Replace] {
+            assert_eq!(
+                outcome(Presence::Identical, existing).unwrap(),
+                Some(UploadOutcome::Skipped(
+                    "already exists in https://prefix.dev/my-channel/".to_string()
+                ))
+            );
+            assert_eq!(outcome(Presence::Missing, existing).unwrap(), None);
+        }
+
+        // a different package with the same file name
+        assert!(outcome(Presence::Different, Existing::Fail).is_err());
+        assert_eq!(
+            outcome(Presence::Different, Existing::Skip).unwrap(),
+            Some(UploadOutcome::Skipped(
+                "a different package already exists in https://prefix.dev/my-channel/".to_string()
+            ))
+        );
+        assert_eq!(
+            outcome(Presence::Different, Existing::Replace).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! Attach packages to a GitHub release (`upload github-release`).
+//!
+//! The release is created if it does not exist yet. The SHA256 checksums of the uploaded
+//! packages are kept in a table at the end of the release notes, which is updated with every
+//! upload to the release.
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_digest::{Sha256, compute_bytes_digest};
+use reqwest::{StatusCode, header};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use super::{UploadOutcome, Uploader, check_response, upload_client};
+use crate::opt::GithubReleaseUploadOpts;
+
+const CHECKSUMS_START: &str = "<!-- rattler-build checksums -->";
+const CHECKSUMS_END: &str = "<!-- /rattler-build checksums -->";
+
+#[derive(Debug, Default, Deserialize)]
+struct Release {
+    id: u64,
+    upload_url: String,
+    body: Option<String>,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    /// The `sha256:<hex>` digest of the asset
+    digest: Option<String>,
+}
+
+/// Parse the checksum table of the release notes into a map from package name to SHA256
+fn parse_checksums(body: &str) -> BTreeMap<String, String> {
+    let Some(start) = body.find(CHECKSUMS_START) else {
+        return BTreeMap::new();
+    };
+    let table = &body[start + CHECKSUMS_START.len()..];
+    let table = table.find(CHECKSUMS_END).map_or(table, |end| &table[..end]);
+
+    let mut checksums = BTreeMap::new();
+    for line in table.lines() {
+        let cells = line
+            .split('|')
+            .map(|cell| cell.trim().trim_matches('`'))
+            .filter(|cell| !cell.is_empty())
+            .collect::<Vec<_>>();
+        if let [name, sha256] = cells[..]
+            && sha256.len() == 64
+        {
+            checksums.insert(name.to_string(), sha256.to_string());
+        }
+    }
+    checksums
+}
+
+/// Add the checksums of `packages` to the checksum table of the release notes, replacing the
+/// checksums of packages with the same name
+fn update_release_notes(body: &str, packages: &[(String, String)]) -> String {
+    let (before, after) = match body.find(CHECKSUMS_START) {
+        Some(start) => {
+            let after = body[start..]
+                .find(CHECKSUMS_END)
+                .map_or("", |end| &body[start + end + CHECKSUMS_END.len()..]);
+            (&body[..start], after)
+        }
+        None => (body, ""),
+    };
+
+    let mut checksums = parse_checksums(body);
+    for (name, sha256) in packages {
+        checksums.insert(name.clone(), sha256.clone());
+    }
+
+    let mut table = format!("{CHECKSUMS_START}\n| Package | SHA256 |\n| --- | --- |\n");
+    for (name, sha256) in checksums {
+        table.push_str(&format!("| `{name}` | `{sha256}` |\n"));
+    }
+    table.push_str(CHECKSUMS_END);
+
+    let before = before.trim_end();
+    let separator = if before.is_empty() { "" } else { "\n\n" };
+    format!("{before}{separator}{table}{after}")
+}
+
+/// A minimal client of the GitHub releases API
+struct GithubClient {
+    client: ClientWithMiddleware,
+    api_url: Url,
+    token: String,
+}
+
+impl GithubClient {
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        request
+            .bearer_auth(&self.token)
+            .header(header::ACCEPT, "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+    }
+
+    fn url(&self, path: &str) -> miette::Result<Url> {
+        let mut url = self.api_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| miette::miette!("Invalid GitHub API URL: {}", self.api_url))?
+            .pop_if_empty()
+            .extend(path.split('/'));
+        Ok(url)
+    }
+
+    /// Get the release of `tag`, or `None` if it does not exist
+    async fn get_release(&self, repository: &str, tag: &str) -> miette::Result<Option<Release>> {
+        let response = self
+            .request(
+                self.client
+                    .get(self.url(&format!("repos/{repository}/releases/tags/{tag}"))?),
+            )
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        check_response(response, "Fetching the release")
+            .await?
+            .json()
+            .await
+            .into_diagnostic()
+    }
+
+    /// Create the release of `tag`
+    async fn create_release(&self, repository: &str, tag: &str) -> miette::Result<Release> {
+        tracing::info!("Creating the release {} of {}", tag, repository);
+        let response = self
+            .request(
+                self.client
+                    .post(self.url(&format!("repos/{repository}/releases"))?)
+                    .json(&json!({ "tag_name": tag, "name": tag })),
+            )
+            .send()
+            .await
+            .into_diagnostic()?;
+        check_response(response, "Creating the release")
+            .await?
+            .json()
+            .await
+            .into_diagnostic()
+    }
+
+    /// Upload `data` as the asset `name` of `release`
+    async fn upload_asset(
+        &self,
+        release: &Release,
+        name: &str,
+        data: Vec<u8>,
+    ) -> miette::Result<Asset> {
+        // the upload URL is a URI template, e.g. `.../assets{?name,label}`
+        let upload_url = release
+            .upload_url
+            .split_once('{')
+            .map_or(release.upload_url.as_str(), |(url, _)| url);
+        let mut upload_url = Url::parse(upload_url).into_diagnostic()?;
+        upload_url.query_pairs_mut().append_pair("name", name);
+
+        let response = self
+            .request(
+                self.client
+                    .post(upload_url)
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(data),
+            )
+            .send()
+            .await
+            .into_diagnostic()?;
+        check_response(response, "Uploading the asset")
+            .await?
+            .json()
+            .await
+            .into_diagnostic()
+    }
+
+    /// Replace the release notes of `release`
+    async fn update_body(
+        &self,
+        repository: &str,
+        release: &Release,
+        body: &str,
+    ) -> miette::Result<()> {
+        let response = self
+            .request(
+                self.client
+                    .patch(self.url(&format!("repos/{repository}/releases/{}", release.id))?)
+                    .json(&json!({ "body": body })),
+            )
+            .send()
+            .await
+            .into_diagnostic()?;
+        check_response(response, "Updating the release notes").await?;
+        Ok(())
+    }
+}
+
+/// Attaches packages to a GitHub release
+pub(crate) struct GithubUploader {
+    github: GithubClient,
+    release: Release,
+    repository: String,
+    tag: String,
+    skip_existing: bool,
+    dry_run: bool,
+    /// The checksums of the uploaded packages
+    uploaded: Mutex<Vec<(String, String)>>,
+}
+
+impl GithubUploader {
+    /// Create an uploader for the release of `opts`. The release is created if it does not
+    /// exist yet (unless `dry_run` is set).
+    pub async fn new(
+        opts: GithubReleaseUploadOpts,
+        dry_run: bool,
+        allow_insecure_host: Option<Vec<String>>,
+    ) -> miette::Result<Self> {
+        let github = GithubClient {
+            client: upload_client(&opts.api_url, allow_insecure_host),
+            api_url: opts.api_url,
+            token: opts.token,
+        };
+        let release = match github.get_release(&opts.repository, &opts.tag).await? {
+            Some(release) => release,
+            None if dry_run => {
+                tracing::info!(
+                    "Dry run, the release {} of {} would be created",
+                    opts.tag,
+                    opts.repository
+                );
+                Release::default()
+            }
+            None => github.create_release(&opts.repository, &opts.tag).await?,
+        };
+        Ok(Self {
+            github,
+            release,
+            repository: opts.repository,
+            tag: opts.tag,
+            skip_existing: opts.skip_existing,
+            dry_run,
+            uploaded: Mutex::default(),
+        })
+    }
+}
+
+impl Uploader for GithubUploader {
+    /// Attach a single package to the release. Packages that are already attached with the
+    /// same checksum are skipped.
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let name = package_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let data = fs::read(package_file).into_diagnostic()?;
+        let sha256 = format!("{:x}", compute_bytes_digest::<Sha256>(&data));
+
+        if let Some(asset) = self.release.assets.iter().find(|asset| asset.name == name) {
+            // older assets have no digest, fall back to the checksums in the release notes
+            let existing_sha256 = asset
+                .digest
+                .as_deref()
+                .and_then(|digest| digest.strip_prefix("sha256:"))
+                .map(str::to_string)
+                .or_else(|| {
+                    parse_checksums(self.release.body.as_deref().unwrap_or_default()).remove(&name)
+                });
+            if existing_sha256.as_deref() == Some(sha256.as_str()) {
+                return Ok(UploadOutcome::Skipped(format!(
+                    "already attached as {}",
+                    asset.browser_download_url
+                )));
+            }
+            if self.skip_existing {
+                return Ok(UploadOutcome::Skipped(format!(
+                    "a different package is already attached as {}",
+                    asset.browser_download_url
+                )));
+            }
+            return Err(miette::miette!(
+                "A different package {} is already attached to the release {} (use --skip-existing to skip it)",
+                name,
+                self.tag
+            ));
+        }
+
+        if self.dry_run {
+            return Ok(UploadOutcome::WouldUpload(format!(
+                "release {} of {}",
+                self.tag, self.repository
+            )));
+        }
+
+        tracing::info!("Uploading {} to the release {}", name, self.tag);
+        let asset = self.github.upload_asset(&self.release, &name, data).await?;
+        self.uploaded.lock().unwrap().push((name, sha256));
+        Ok(UploadOutcome::Uploaded(Some(asset.browser_download_url)))
+    }
+
+    /// Add the checksums of the uploaded packages to the release notes
+    async fn finish(&self) -> miette::Result<()> {
+        let uploaded = std::mem::take(&mut *self.uploaded.lock().unwrap());
+        if uploaded.is_empty() {
+            return Ok(());
+        }
+        let body =
+            update_release_notes(self.release.body.as_deref().unwrap_or_default(), &uploaded);
+        self.github
+            .update_body(&self.repository, &self.release, &body)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_release_notes() {
+        let foo = "a".repeat(64);
+        let bar = "b".repeat(64);
+        let body = update_release_notes(
+            "Release notes",
+            &[("foo-1.0-h123_0.conda".to_string(), foo.clone())],
+        );
+        insta::assert_snapshot!(body, @r"
+        Release notes
+
+        <!-- rattler-build checksums -->
+        | Package | SHA256 |
+        | --- | --- |
+        | `foo-1.0-h123_0.conda` | `aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa` |
+        <!-- /rattler-build checksums -->
+        ");
+
+        // existing checksums are kept, and text after the table is preserved
+        let body = update_release_notes(
+            &format!("{body}\n\nMore notes"),
+            &[
+                ("bar-2.0-h456_0.conda".to_string(), bar),
+                ("foo-1.0-h123_0.conda".to_string(), "c".repeat(64)),
+            ],
+        );
+        insta::assert_snapshot!(body, @r"
+        Release notes
+
+        <!-- rattler-build checksums -->
+        | Package | SHA256 |
+        | --- | --- |
+        | `bar-2.0-h456_0.conda` | `bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb` |
+        | `foo-1.0-h123_0.conda` | `cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc` |
+        <!-- /rattler-build checksums -->
+
+        More notes
+        ");
+
+        assert!(
+            update_release_notes("", &[("foo.conda".to_string(), foo)])
+                .starts_with(CHECKSUMS_START)
+        );
+    }
+}
@@ -0,0 +1,623 @@
+//! Upload targets that are implemented in rattler-build itself. All other targets (prefix.dev,
+//! anaconda.org, Quetz and S3) are handled by `rattler_upload`, and additional targets can be
+//! added as external commands (`[uploaders.<name>]` in the configuration).
+//!
+//! Every target implements [`Uploader`]. The packages are uploaded concurrently, and a summary
+//! of all uploads is printed at the end.
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use futures::{StreamExt, stream};
+use miette::IntoDiagnostic;
+use rattler_build_networking::BaseClient;
+use rattler_cache::validation::{ValidationMode, validate_package_directory};
+use rattler_conda_types::package::CondaArchiveType;
+use rattler_upload::upload::opt::ServerType;
+use reqwest::Response;
+use reqwest_middleware::ClientWithMiddleware;
+use retry_policies::{RetryDecision, RetryPolicy, policies::ExponentialBackoff};
+use url::Url;
+
+use crate::{
+    config::Config,
+    events::{self, Event, Phase},
+    opt::{UploadOpts, UploadServerType},
+    tool_configuration::APP_USER_AGENT,
+};
+
+mod anaconda;
+mod artifactory;
+mod dry_run;
+mod github;
+mod oci;
+pub mod plugin;
+mod trusted_publishing;
+
+/// The result of uploading a single package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum UploadOutcome {
+    /// The package was uploaded, with the URL it can be downloaded from (if known)
+    Uploaded(Option<String>),
+    /// The package was not uploaded, with the reason (e.g. it already exists on the server)
+    Skipped(String),
+    /// The package passed the pre-flight checks and would be uploaded to the destination
+    /// (`--dry-run`)
+    WouldUpload(String),
+}
+
+/// An upload target. `upload` is called concurrently for every package, and `finish` once after
+/// all packages were uploaded.
+pub(crate) trait Uploader {
+    /// Upload a single package. In a dry run, only the pre-flight checks are run and
+    /// [`UploadOutcome::WouldUpload`] is returned.
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome>;
+
+    /// Finish the upload after all packages were uploaded (e.g. update an index or the release
+    /// notes)
+    async fn finish(&self) -> miette::Result<()> {
+        Ok(())
+    }
+}
+
+/// The HTTP client for uploads to `url`. Transient failures (connection errors, timeouts and
+/// `5xx` responses) are retried with exponential backoff.
+fn upload_client(url: &Url, allow_insecure_host: Option<Vec<String>>) -> ClientWithMiddleware {
+    let mut builder = BaseClient::builder().user_agent(APP_USER_AGENT);
+    if let Some(hosts) = allow_insecure_host {
+        builder = builder.insecure_hosts(hosts);
+    }
+    builder.build().for_host(url).clone()
+}
+
+/// How often a package is uploaded again with `rattler_upload` after a transient failure
+const MAX_UPLOAD_RETRIES: u32 = 3;
+
+/// Returns false for errors that uploading again cannot fix: authentication errors, conflicts
+/// with existing packages and rejected requests, as reported by `rattler_upload` and `reqwest`
+fn is_transient(error: &miette::Report) -> bool {
+    const PERMANENT: [&str; 5] = [
+        "Authentication error",
+        "Resource conflict",
+        "Client error",
+        "client error (",
+        "already exists",
+    ];
+    !error.chain().any(|cause| {
+        let message = cause.to_string();
+        PERMANENT.iter().any(|marker| message.contains(marker))
+    })
+}
+
+/// Run `upload` again with backoff according to `retry_policy` while it fails with a transient
+/// error. `rattler_upload` retries failed responses of some targets itself, but not failed
+/// connections, and not at all for anaconda.org and S3.
+async fn retry_transient<T, F, Fut>(
+    package_file: &Path,
+    retry_policy: impl RetryPolicy,
+    mut upload: F,
+) -> miette::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = miette::Result<T>>,
+{
+    let start_time = SystemTime::now();
+    let mut retries = 0;
+    loop {
+        let error = match upload().await {
+            Ok(result) => return Ok(result),
+            Err(e) if !is_transient(&e) => return Err(e),
+            Err(e) => e,
+        };
+        let RetryDecision::Retry { execute_after } = retry_policy.should_retry(start_time, retries)
+        else {
+            return Err(error);
+        };
+        retries += 1;
+        let sleep_for = execute_after
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        tracing::warn!(
+            "Uploading {} failed: {}, retrying in {} seconds",
+            package_name(package_file),
+            error,
+            sleep_for.as_secs()
+        );
+        tokio::time::sleep(sleep_for).await;
+    }
+}
+
+/// Return the response if the request succeeded, and an error with the body otherwise
+async fn check_response(response: Response, action: &str) -> miette::Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    Err(miette::miette!(
+        "{} failed with {}: {}",
+        action,
+        status,
+        body
+    ))
+}
+
+/// The file name of a package, as shown in the summary
+fn package_name(package_file: &Path) -> String {
+    package_file
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| package_file.display().to_string())
+}
+
+/// Render the summary table of the uploads
+fn summary_table(results: &[(String, miette::Result<UploadOutcome>)]) -> comfy_table::Table {
+    let mut table = comfy_table::Table::new();
+    table
+        .load_preset(comfy_table::presets::UTF8_FULL_CONDENSED)
+        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+        .set_header(["Package", "Status", "Details"]);
+    for (package, result) in results {
+        let (status, details) = status_and_details(result);
+        table.add_row([package.as_str(), status, details.as_str()]);
+    }
+    table
+}
+
+/// The status and the details of an upload, as shown in the summary
+fn status_and_details(result: &miette::Result<UploadOutcome>) -> (&'static str, String) {
+    match result {
+        Ok(UploadOutcome::Uploaded(url)) => ("uploaded", url.clone().unwrap_or_default()),
+        Ok(UploadOutcome::Skipped(reason)) => ("skipped", reason.clone()),
+        Ok(UploadOutcome::WouldUpload(destination)) => ("would upload", destination.clone()),
+        Err(e) => ("failed", e.to_string()),
+    }
+}
+
+/// Check that the package can be extracted, and that the extracted files match the sizes and
+/// checksums in `info/paths.json`
+async fn verify_package(package_file: &Path) -> miette::Result<()> {
+    let package_file = package_file.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let temp_dir = tempfile::tempdir().into_diagnostic()?;
+        rattler_package_streaming::fs::extract(&package_file, temp_dir.path()).map_err(|e| {
+            miette::miette!("The package {} is corrupted: {}", package_file.display(), e)
+        })?;
+        validate_package_directory(temp_dir.path(), ValidationMode::Full).map_err(|e| {
+            miette::miette!("The package {} is corrupted: {}", package_file.display(), e)
+        })?;
+        Ok(())
+    })
+    .await
+    .into_diagnostic()?
+}
+
+/// Verify the integrity of all `package_files` (`--dry-run`), before anything is requested from
+/// the server
+async fn verify_packages(package_files: &[PathBuf], jobs: usize) -> miette::Result<()> {
+    let errors = stream::iter(package_files)
+        .map(|package_file| verify_package(package_file))
+        .buffered(jobs.max(1))
+        .filter_map(|result| async move { result.err() })
+        .collect::<Vec<_>>()
+        .await;
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(miette::miette!(
+        "{} of {} packages are corrupted:\n{}",
+        errors.len(),
+        package_files.len(),
+        errors
+            .iter()
+            .map(|e| format!("  - {e}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
+}
+
+/// Upload `package_files` with up to `jobs` uploads at the same time, and print a summary
+/// of all uploads. Fails if any of the uploads failed.
+///
+/// With `dry_run`, `upload` is expected to only run the pre-flight checks of the target.
+async fn upload_concurrently<'a, F, Fut>(
+    package_files: &'a [PathBuf],
+    jobs: usize,
+    dry_run: bool,
+    upload: F,
+) -> miette::Result<()>
+where
+    F: Fn(&'a Path) -> Fut,
+    Fut: Future<Output = miette::Result<UploadOutcome>>,
+{
+    events::emit(|| Event::PhaseStart {
+        phase: Phase::Upload,
+        package: None,
+    });
+    let start = Instant::now();
+
+    let results = stream::iter(package_files)
+        .map(|package_file| {
+            let upload = upload(package_file);
+            async move {
+                let result = upload.await;
+                events::emit(|| {
+                    let (status, details) = status_and_details(&result);
+                    Event::Upload {
+                        package: package_name(package_file),
+                        status: status.to_string(),
+                        details,
+                    }
+                });
+                (package_name(package_file), result)
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    if dry_run {
+        tracing::info!(
+            "Dry run, no packages were uploaded:\n{}",
+            summary_table(&results)
+        );
+    } else {
+        tracing::info!("Upload summary:\n{}", summary_table(&results));
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    let result = if failed > 0 {
+        let action = if dry_run {
+            "failed the pre-flight checks"
+        } else {
+            "failed to upload"
+        };
+        Err(miette::miette!(
+            "{} of {} packages {}",
+            failed,
+            results.len(),
+            action
+        ))
+    } else {
+        Ok(())
+    };
+    events::emit(|| Event::phase_end(Phase::Upload, None, start.elapsed(), &result));
+    result
+}
+
+/// Upload `package_files` with `uploader` and finish the upload, even if some of the uploads
+/// failed
+async fn run_uploader<U: Uploader>(
+    uploader: &U,
+    package_files: &[PathBuf],
+    jobs: usize,
+    dry_run: bool,
+) -> miette::Result<()> {
+    let result = upload_concurrently(package_files, jobs, dry_run, |package_file| {
+        uploader.upload(package_file)
+    })
+    .await;
+    uploader.finish().await?;
+    result
+}
+
+/// Uploads packages one by one with `rattler_upload`
+struct ChannelUploader {
+    server_type: ServerType,
+    common: rattler_upload::upload::opt::CommonOpts,
+    dry_run: bool,
+    /// The packages that are already in the channel
+    checker: Option<dry_run::ChannelChecker>,
+}
+
+impl ChannelUploader {
+    async fn new(
+        server_type: ServerType,
+        package_files: &[PathBuf],
+        dry_run: bool,
+        common: &rattler_upload::upload::opt::CommonOpts,
+    ) -> miette::Result<Self> {
+        let checker = if dry_run {
+            Some(dry_run::ChannelChecker::new(&server_type, package_files, common).await?)
+        } else {
+            match dry_run::ChannelChecker::fetch(&server_type, package_files, common).await {
+                Ok(checker) => Some(checker),
+                Err(e) => {
+                    tracing::warn!(
+                        "Could not check which packages already exist in the channel, uploading all packages: {e}"
+                    );
+                    None
+                }
+            }
+        };
+        Ok(Self {
+            server_type,
+            common: common.clone(),
+            dry_run,
+            checker,
+        })
+    }
+}
+
+impl Uploader for ChannelUploader {
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        if let Some(checker) = &self.checker {
+            if self.dry_run {
+                return checker.check(package_file).await;
+            }
+            if let Some(outcome) = checker.check_existing(package_file).await? {
+                return Ok(outcome);
+            }
+        }
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(MAX_UPLOAD_RETRIES);
+        retry_transient(package_file, retry_policy, || {
+            rattler_upload::upload_from_args(rattler_upload::upload::opt::UploadOpts {
+                package_files: vec![package_file.to_path_buf()],
+                server_type: self.server_type.clone(),
+                common: self.common.clone(),
+                auth_store: None,
+            })
+        })
+        .await?;
+        Ok(UploadOutcome::Uploaded(None))
+    }
+}
+
+/// Upload packages one by one with `rattler_upload`
+async fn upload_to_channel(
+    server_type: ServerType,
+    package_files: &[PathBuf],
+    jobs: usize,
+    dry_run: bool,
+    common: &rattler_upload::upload::opt::CommonOpts,
+) -> miette::Result<()> {
+    let uploader = ChannelUploader::new(server_type, package_files, dry_run, common).await?;
+    run_uploader(&uploader, package_files, jobs, dry_run).await
+}
+
+/// Index the S3 channel that `package_files` were uploaded to (`--index`)
+#[cfg(feature = "s3")]
+async fn index_s3_channel(
+    s3: &rattler_upload::upload::opt::S3Opts,
+    package_files: &[PathBuf],
+    auth_file: Option<PathBuf>,
+) -> miette::Result<()> {
+    let platforms = crate::index::package_platforms(package_files)?;
+    let credentials = match Option::<rattler_s3::S3Credentials>::from(s3.credentials.clone()) {
+        Some(credentials) => {
+            let auth_storage = crate::tool_configuration::get_auth_store(auth_file)
+                .map_err(|e| miette::miette!("Failed to get authentication storage: {}", e))?;
+            credentials
+                .resolve(&s3.channel, &auth_storage)
+                .ok_or_else(|| {
+                    miette::miette!("Could not find S3 credentials to index the channel")
+                })?
+        }
+        None => crate::tool_configuration::resolve_s3_credentials(
+            &Default::default(),
+            auth_file,
+            &s3.channel,
+        )
+        .await
+        .into_diagnostic()?,
+    };
+    crate::index::index_s3_channel(&s3.channel, credentials, &platforms, false).await
+}
+
+/// Upload packages to the target of `args`. The uploader plugins are taken from `config`.
+pub async fn upload_packages(args: UploadOpts, config: Option<Config>) -> miette::Result<()> {
+    if args.package_files.is_empty() {
+        return Err(miette::miette!("No package files were provided."));
+    }
+    for package_file in &args.package_files {
+        if CondaArchiveType::try_from(package_file).is_none() {
+            return Err(miette::miette!(
+                "The file {} does not appear to be a conda package.",
+                package_file.display()
+            ));
+        }
+    }
+
+    if args.dry_run && matches!(args.server_type, UploadServerType::CondaForge(_)) {
+        return Err(miette::miette!(
+            "`--dry-run` is not supported for uploads to conda-forge"
+        ));
+    }
+
+    let package_files = &args.package_files;
+    if args.dry_run {
+        verify_packages(package_files, args.jobs).await?;
+    }
+    match args.server_type {
+        UploadServerType::Oci(opts) => {
+            let uploader =
+                oci::OciUploader::new(opts, args.dry_run, args.common.allow_insecure_host)?;
+            run_uploader(&uploader, package_files, args.jobs, args.dry_run).await
+        }
+        UploadServerType::GithubRelease(opts) => {
+            let uploader =
+                github::GithubUploader::new(opts, args.dry_run, args.common.allow_insecure_host)
+                    .await?;
+            run_uploader(&uploader, package_files, args.jobs, args.dry_run).await
+        }
+        UploadServerType::AnacondaPromote(opts) => {
+            let promoter = anaconda::AnacondaPromoter::new(
+                opts,
+                args.dry_run,
+                args.common.auth_file.clone(),
+                args.common.allow_insecure_host,
+            )?;
+            run_uploader(&promoter, package_files, args.jobs, args.dry_run).await
+        }
+        UploadServerType::Artifactory(opts) => {
+            let uploader = artifactory::ArtifactoryUploader::new(
+                opts,
+                args.dry_run,
+                args.common.auth_file.clone(),
+                args.common.allow_insecure_host,
+            )?;
+            run_uploader(&uploader, package_files, args.jobs, args.dry_run).await
+        }
+        UploadServerType::Plugin(opts) => {
+            let uploaders = config
+                .map(|config| config.extensions.uploaders)
+                .unwrap_or_default();
+            let uploader = plugin::CommandUploader::new(&opts.name, &uploaders, args.dry_run)?;
+            run_uploader(&uploader, package_files, args.jobs, args.dry_run).await
+        }
+        // conda-forge uploads are staged as one batch
+        UploadServerType::CondaForge(opts) => {
+            rattler_upload::upload_from_args(rattler_upload::upload::opt::UploadOpts {
+                package_files: args.package_files,
+                server_type: ServerType::CondaForge(opts),
+                common: args.common,
+                auth_store: None,
+            })
+            .await
+        }
+        UploadServerType::Quetz(upload_opts) => {
+            let mut opts = upload_opts.quetz;
+            // the token is requested once, before the concurrent uploads
+            if let (None, Some(exchange_url)) = (&opts.api_key, &upload_opts.trusted_publishing_url)
+            {
+                let client = upload_client(exchange_url, args.common.allow_insecure_host.clone());
+                opts.api_key =
+                    trusted_publishing::quetz_token(&client, &opts.url, exchange_url).await;
+            }
+            upload_to_channel(
+                ServerType::Quetz(opts),
+                package_files,
+                args.jobs,
+                args.dry_run,
+                &args.common,
+            )
+            .await
+        }
+        UploadServerType::Prefix(opts) => {
+            upload_to_channel(
+                ServerType::Prefix(opts),
+                package_files,
+                args.jobs,
+                args.dry_run,
+                &args.common,
+            )
+            .await
+        }
+        UploadServerType::Anaconda(opts) => {
+            upload_to_channel(
+                ServerType::Anaconda(opts),
+                package_files,
+                args.jobs,
+                args.dry_run,
+                &args.common,
+            )
+            .await
+        }
+        #[cfg(feature = "s3")]
+        UploadServerType::S3(upload_opts) => {
+            let opts = upload_opts.s3;
+            let result = upload_to_channel(
+                ServerType::S3(opts.clone()),
+                package_files,
+                args.jobs,
+                args.dry_run,
+                &args.common,
+            )
+            .await;
+
+            // index even if some uploads failed, so that the uploaded packages can be installed
+            if upload_opts.index && args.dry_run {
+                tracing::info!("Dry run, the channel {} would be indexed", opts.channel);
+            } else if upload_opts.index {
+                index_s3_channel(&opts, package_files, args.common.auth_file.clone()).await?;
+            }
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_table() {
+        let results = vec![
+            (
+                "foo-1.0-h123_0.conda".to_string(),
+                Ok(UploadOutcome::Uploaded(Some(
+                    "https://example.com/foo-1.0-h123_0.conda".to_string(),
+                ))),
+            ),
+            (
+                "bar-1.0-h123_0.conda".to_string(),
+                Ok(UploadOutcome::Skipped("already exists".to_string())),
+            ),
+            (
+                "baz-1.0-h123_0.conda".to_string(),
+                Err(miette::miette!("upload failed")),
+            ),
+        ];
+        let table = summary_table(&results);
+        insta::assert_snapshot!(table.to_string(), @r"
+        ╭──────────────────────┬──────────┬──────────────────────────────────────────╮
+        │ Package              ┆ Status   ┆ Details                                  │
+        ╞══════════════════════╪══════════╪══════════════════════════════════════════╡
+        │ foo-1.0-h123_0.conda ┆ uploaded ┆ https://example.com/foo-1.0-h123_0.conda │
+        │ bar-1.0-h123_0.conda ┆ skipped  ┆ already exists                           │
+        │ baz-1.0-h123_0.conda ┆ failed   ┆ upload failed                            │
+        ╰──────────────────────┴──────────┴──────────────────────────────────────────╯
+        ");
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let package_file = Path::new("foo-1.0-h123_0.conda");
+        let retry_policy = || {
+            ExponentialBackoff::builder()
+                .retry_bounds(Duration::from_millis(1), Duration::from_millis(1))
+                .build_with_max_retries(MAX_UPLOAD_RETRIES)
+        };
+
+        // transient errors are retried until the upload succeeds
+        let attempts = AtomicU32::new(0);
+        let result = retry_transient(package_file, retry_policy(), || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(miette::miette!("failed to send request: connection reset"))
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // and give up after the maximum number of retries
+        let attempts = AtomicU32::new(0);
+        let result: miette::Result<()> = retry_transient(package_file, retry_policy(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(miette::miette!(
+                "Failed to upload package file\nStatus: 503"
+            ))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_UPLOAD_RETRIES + 1);
+
+        // permanent errors are not retried
+        let attempts = AtomicU32::new(0);
+        let result: miette::Result<()> = retry_transient(package_file, retry_policy(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(miette::miette!(
+                "Authentication error: Failed to upload package file\nStatus: 401"
+            ))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+}
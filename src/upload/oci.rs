@@ -0,0 +1,674 @@
+//! Upload packages to an OCI registry (`upload oci`).
+//!
+//! The packages are stored in the layout that `oci://` channels are read with: every package
+//! name is a repository below the channel (`<channel>/<subdir>/<name>`), and every version and
+//! build string is a tag of that repository. The manifest of a tag has the package archive and
+//! its `index.json` as layers.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use base64::Engine as _;
+use fs_err as fs;
+use miette::IntoDiagnostic;
+use rattler_conda_types::package::{CondaArchiveType, IndexJson};
+use rattler_digest::{Sha256, compute_bytes_digest};
+use rattler_package_streaming::seek::read_package_file;
+use reqwest::{Response, StatusCode, header};
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use retry_policies::{RetryDecision, RetryPolicy, policies::ExponentialBackoff};
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use super::{UploadOutcome, Uploader, check_response, upload_client};
+use crate::opt::OciUploadOpts;
+
+const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+const CONFIG_MEDIA_TYPE: &str = "application/vnd.unknown.config.v1+json";
+const INDEX_MEDIA_TYPE: &str = "application/vnd.conda.info.index.v1+json";
+
+/// The media type of the layer that holds the package archive
+fn package_media_type(archive_type: CondaArchiveType) -> &'static str {
+    match archive_type {
+        CondaArchiveType::Conda => "application/vnd.conda.package.v2",
+        CondaArchiveType::TarBz2 => "application/vnd.conda.package.v1",
+    }
+}
+
+/// OCI tags are not allowed to contain `+`, `!` or `=`, they are replaced the same way as when
+/// reading from an `oci://` channel
+fn version_build_tag(version: &str, build: &str) -> String {
+    format!("{version}-{build}")
+        .replace('+', "__p__")
+        .replace('!', "__e__")
+        .replace('=', "__eq__")
+}
+
+/// The repository of a package below the channel path. OCI repository names cannot start with
+/// `_`, so these names are prefixed with `zzz`.
+fn package_repository(channel_path: &str, subdir: &str, name: &str) -> String {
+    let name = if name.starts_with('_') {
+        format!("zzz{name}")
+    } else {
+        name.to_string()
+    };
+    [channel_path, subdir, &name]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Blobs larger than this are uploaded in chunks of this size, so that a failed upload can be
+/// resumed instead of restarting from scratch
+const CHUNK_SIZE: usize = 32 * 1024 * 1024;
+
+/// How often a chunked upload is resumed before giving up
+const MAX_RESUMES: u32 = 5;
+
+/// Parse the last received byte from the `Range` header of an upload status (e.g. `0-1023`)
+fn parse_range_end(range: &str) -> Option<usize> {
+    let range = range.trim().trim_start_matches("bytes=");
+    range.split_once('-')?.1.trim().parse().ok()
+}
+
+/// The `sha256:<hex>` digest of a blob
+fn blob_digest(data: &[u8]) -> String {
+    format!("sha256:{:x}", compute_bytes_digest::<Sha256>(data))
+}
+
+#[derive(Deserialize)]
+struct DockerConfig {
+    #[serde(default)]
+    auths: HashMap<String, DockerAuth>,
+}
+
+#[derive(Deserialize)]
+struct DockerAuth {
+    auth: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Read the credentials of `host` from the contents of a docker `config.json`
+fn credentials_from_docker_config(config: &str, host: &str) -> Option<(String, String)> {
+    let config: DockerConfig = serde_json::from_str(config).ok()?;
+    let auth = config.auths.iter().find_map(|(key, auth)| {
+        let key = key
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        let key = key.split('/').next().unwrap_or(key);
+        (key == host).then_some(auth)
+    })?;
+
+    if let (Some(username), Some(password)) = (&auth.username, &auth.password) {
+        return Some((username.clone(), password.clone()));
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(auth.auth.as_ref()?)
+        .ok()?;
+    let (username, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Read the credentials of `host` from `$DOCKER_CONFIG/config.json` or `~/.docker/config.json`.
+/// Credential helpers (`credsStore`) are not supported.
+fn docker_credentials(host: &str) -> Option<(String, String)> {
+    let config_dir = std::env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| std::env::home_dir().map(|home| home.join(".docker")))?;
+    let config = fs::read_to_string(config_dir.join("config.json")).ok()?;
+    let credentials = credentials_from_docker_config(&config, host);
+    if credentials.is_some() {
+        tracing::debug!("Using the credentials for {} from the docker config", host);
+    }
+    credentials
+}
+
+/// Parse a `WWW-Authenticate: Bearer realm="...",service="..."` challenge
+fn parse_bearer_challenge(challenge: &str) -> Option<HashMap<String, String>> {
+    let params = challenge.strip_prefix("Bearer ")?;
+    let mut result = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        result.insert(key, value.to_string());
+        rest = remainder;
+    }
+    Some(result)
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    layers: Vec<Layer>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Layer {
+    media_type: String,
+    digest: String,
+}
+
+/// How requests to a repository are authorized
+enum Authorization {
+    None,
+    Basic(String, String),
+    Bearer(String),
+}
+
+/// A minimal client of the OCI distribution API that is able to push blobs and manifests
+struct Registry {
+    client: ClientWithMiddleware,
+    base_url: Url,
+    credentials: Option<(String, String)>,
+}
+
+impl Registry {
+    fn new(
+        host: &str,
+        credentials: Option<(String, String)>,
+        allow_insecure_host: Option<Vec<String>>,
+    ) -> miette::Result<Self> {
+        let base_url = Url::parse(&format!("https://{host}/")).into_diagnostic()?;
+        let client = upload_client(&base_url, allow_insecure_host);
+        Ok(Self {
+            client,
+            base_url,
+            credentials,
+        })
+    }
+
+    fn url(&self, path: &str) -> miette::Result<Url> {
+        self.base_url.join(path).into_diagnostic()
+    }
+
+    fn authorize(&self, request: RequestBuilder, authorization: &Authorization) -> RequestBuilder {
+        match authorization {
+            Authorization::None => request,
+            Authorization::Basic(username, password) => {
+                request.basic_auth(username, Some(password))
+            }
+            Authorization::Bearer(token) => request.bearer_auth(token),
+        }
+    }
+
+    /// Get the authorization for pushing to `repository` by following the challenge of the
+    /// registry
+    async fn authenticate(&self, repository: &str) -> miette::Result<Authorization> {
+        let response = self
+            .client
+            .get(self.url("v2/")?)
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(Authorization::None);
+        }
+
+        let challenge = response
+            .headers()
+            .get(header::WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let Some(params) = parse_bearer_challenge(&challenge) else {
+            let (username, password) = self.credentials.clone().ok_or_else(|| {
+                miette::miette!(
+                    "The registry {} requires authentication, use --username and --password or `docker login`",
+                    self.base_url
+                )
+            })?;
+            return Ok(Authorization::Basic(username, password));
+        };
+
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| miette::miette!("Invalid authentication challenge: {}", challenge))?;
+        let mut token_url = Url::parse(realm).into_diagnostic()?;
+        {
+            let mut query = token_url.query_pairs_mut();
+            if let Some(service) = params.get("service") {
+                query.append_pair("service", service);
+            }
+            query.append_pair("scope", &format!("repository:{repository}:pull,push"));
+        }
+
+        let mut request = self.client.get(token_url);
+        if let Some((username, password)) = &self.credentials {
+            request = request.basic_auth(username, Some(password));
+        }
+        let response = check_response(
+            request.send().await.into_diagnostic()?,
+            "Requesting a token",
+        )
+        .await?;
+
+        #[derive(Deserialize)]
+        struct TokenResponse {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+        let token: TokenResponse = response.json().await.into_diagnostic()?;
+        token
+            .token
+            .or(token.access_token)
+            .map(Authorization::Bearer)
+            .ok_or_else(|| miette::miette!("The registry did not return a token"))
+    }
+
+    /// Get the manifest of `tag` in `repository`, if it exists
+    async fn get_manifest(
+        &self,
+        repository: &str,
+        tag: &str,
+        authorization: &Authorization,
+    ) -> miette::Result<Option<Manifest>> {
+        let request = self
+            .client
+            .get(self.url(&format!("v2/{repository}/manifests/{tag}"))?)
+            .header(header::ACCEPT, MANIFEST_MEDIA_TYPE);
+        let response = self
+            .authorize(request, authorization)
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = check_response(response, "Fetching the manifest").await?;
+        response.json().await.map(Some).into_diagnostic()
+    }
+
+    /// Upload a blob to `repository`, unless it already exists
+    async fn upload_blob(
+        &self,
+        repository: &str,
+        data: Vec<u8>,
+        digest: &str,
+        authorization: &Authorization,
+    ) -> miette::Result<()> {
+        let request = self
+            .client
+            .head(self.url(&format!("v2/{repository}/blobs/{digest}"))?);
+        let response = self
+            .authorize(request, authorization)
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status().is_success() {
+            tracing::debug!("Blob {} already exists in {}", digest, repository);
+            return Ok(());
+        }
+
+        let request = self
+            .client
+            .post(self.url(&format!("v2/{repository}/blobs/uploads/"))?);
+        let response = check_response(
+            self.authorize(request, authorization)
+                .send()
+                .await
+                .into_diagnostic()?,
+            "Starting the upload",
+        )
+        .await?;
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| miette::miette!("The registry did not return an upload location"))?;
+
+        // the location may be relative to the registry
+        let mut upload_url = self.url(location)?;
+        if data.len() > CHUNK_SIZE {
+            upload_url = self.upload_chunks(upload_url, &data, authorization).await?;
+        }
+
+        // finish the upload, with the data of a monolithic upload
+        upload_url.query_pairs_mut().append_pair("digest", digest);
+        let body = if data.len() > CHUNK_SIZE {
+            Vec::new()
+        } else {
+            data
+        };
+        let request = self
+            .client
+            .put(upload_url)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(body);
+        check_response(
+            self.authorize(request, authorization)
+                .send()
+                .await
+                .into_diagnostic()?,
+            "Uploading the blob",
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Upload `data` in chunks to the upload session at `upload_url`. If a chunk fails, the
+    /// upload resumes after the last byte that the registry received. Returns the URL to
+    /// finish the upload with.
+    async fn upload_chunks(
+        &self,
+        mut upload_url: Url,
+        data: &[u8],
+        authorization: &Authorization,
+    ) -> miette::Result<Url> {
+        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(MAX_RESUMES);
+        let start_time = SystemTime::now();
+        let mut resumes = 0;
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let end = (offset + CHUNK_SIZE).min(data.len());
+            let request = self
+                .client
+                .patch(upload_url.clone())
+                .header(header::CONTENT_TYPE, "application/octet-stream")
+                .header(header::CONTENT_RANGE, format!("{}-{}", offset, end - 1))
+                .body(data[offset..end].to_vec());
+            let result = match self.authorize(request, authorization).send().await {
+                Ok(response) => check_response(response, "Uploading a chunk").await,
+                Err(e) => Err(miette::miette!("Uploading a chunk failed: {}", e)),
+            };
+
+            let error = match result {
+                Ok(response) => {
+                    upload_url = self.location(&response).unwrap_or(upload_url);
+                    offset = end;
+                    tracing::debug!("Uploaded {} of {} bytes", offset, data.len());
+                    continue;
+                }
+                Err(e) => e,
+            };
+
+            let RetryDecision::Retry { execute_after } =
+                retry_policy.should_retry(start_time, resumes)
+            else {
+                return Err(error);
+            };
+            resumes += 1;
+            let sleep_for = execute_after
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO);
+            tokio::time::sleep(sleep_for).await;
+
+            // ask the registry how much of the data it has received
+            let request = self.client.get(upload_url.clone());
+            let response = check_response(
+                self.authorize(request, authorization)
+                    .send()
+                    .await
+                    .into_diagnostic()?,
+                "Requesting the upload status",
+            )
+            .await?;
+            offset = response
+                .headers()
+                .get(header::RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_range_end)
+                .map_or(0, |end| end + 1);
+            upload_url = self.location(&response).unwrap_or(upload_url);
+            tracing::warn!(
+                "{}, resuming the upload at {} of {} bytes ({}/{})",
+                error,
+                offset,
+                data.len(),
+                resumes,
+                MAX_RESUMES
+            );
+        }
+        Ok(upload_url)
+    }
+
+    /// The `Location` header of a response, resolved against the registry
+    fn location(&self, response: &Response) -> Option<Url> {
+        let location = response.headers().get(header::LOCATION)?.to_str().ok()?;
+        self.base_url.join(location).ok()
+    }
+
+    /// Upload the manifest of `tag` to `repository`
+    async fn upload_manifest(
+        &self,
+        repository: &str,
+        tag: &str,
+        manifest: &serde_json::Value,
+        authorization: &Authorization,
+    ) -> miette::Result<()> {
+        let request = self
+            .client
+            .put(self.url(&format!("v2/{repository}/manifests/{tag}"))?)
+            .header(header::CONTENT_TYPE, MANIFEST_MEDIA_TYPE)
+            .body(serde_json::to_vec(manifest).into_diagnostic()?);
+        check_response(
+            self.authorize(request, authorization)
+                .send()
+                .await
+                .into_diagnostic()?,
+            "Uploading the manifest",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// A layer of the manifest
+fn layer(media_type: &str, data: &[u8], title: &str) -> serde_json::Value {
+    json!({
+        "mediaType": media_type,
+        "digest": blob_digest(data),
+        "size": data.len(),
+        "annotations": {
+            "org.opencontainers.image.title": title,
+        },
+    })
+}
+
+/// Uploads packages to an `oci://` channel
+pub(crate) struct OciUploader {
+    registry: Registry,
+    channel_url: Url,
+    channel_path: String,
+    skip_existing: bool,
+    dry_run: bool,
+}
+
+impl OciUploader {
+    /// Create an uploader for the `oci://` channel of `opts`
+    pub fn new(
+        opts: OciUploadOpts,
+        dry_run: bool,
+        allow_insecure_host: Option<Vec<String>>,
+    ) -> miette::Result<Self> {
+        if opts.url.scheme() != "oci" {
+            return Err(miette::miette!(
+                "Expected an `oci://` URL, e.g. `oci://ghcr.io/my-org/my-channel`, got {}",
+                opts.url
+            ));
+        }
+        let host = match (opts.url.host_str(), opts.url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(miette::miette!("The URL {} has no host", opts.url)),
+        };
+
+        let credentials = match (opts.username, opts.password) {
+            (Some(username), Some(password)) => Some((username, password)),
+            _ => docker_credentials(&host),
+        };
+        Ok(Self {
+            registry: Registry::new(&host, credentials, allow_insecure_host)?,
+            channel_path: opts.url.path().trim_matches('/').to_string(),
+            channel_url: opts.url,
+            skip_existing: opts.skip_existing,
+            dry_run,
+        })
+    }
+}
+
+impl Uploader for OciUploader {
+    /// Upload a single package. Packages that already exist in the registry with the same
+    /// checksum are skipped.
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let archive_type = CondaArchiveType::try_from(package_file).ok_or_else(|| {
+            miette::miette!(
+                "The file {} does not appear to be a conda package.",
+                package_file.display()
+            )
+        })?;
+        let file_name = package_file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let index_json: IndexJson = read_package_file(package_file).into_diagnostic()?;
+        let subdir = index_json.subdir.clone().ok_or_else(|| {
+            miette::miette!(
+                "The package {} does not have a subdir",
+                package_file.display()
+            )
+        })?;
+
+        let registry = &self.registry;
+        let repository =
+            package_repository(&self.channel_path, &subdir, index_json.name.as_normalized());
+        let tag = version_build_tag(&index_json.version.to_string(), &index_json.build);
+        let url = format!(
+            "{}/{}/{}",
+            self.channel_url.as_str().trim_end_matches('/'),
+            subdir,
+            file_name
+        );
+        let authorization = registry.authenticate(&repository).await?;
+
+        let package = fs::read(package_file).into_diagnostic()?;
+        let package_media_type = package_media_type(archive_type);
+        let package_digest = blob_digest(&package);
+
+        if let Some(manifest) = registry
+            .get_manifest(&repository, &tag, &authorization)
+            .await?
+        {
+            let existing = manifest
+                .layers
+                .iter()
+                .find(|layer| layer.media_type == package_media_type);
+            if existing.is_some_and(|layer| layer.digest == package_digest) {
+                return Ok(UploadOutcome::Skipped(format!("already exists in {url}")));
+            }
+            if self.skip_existing {
+                return Ok(UploadOutcome::Skipped(format!(
+                    "a different package already exists in {url}"
+                )));
+            }
+            return Err(miette::miette!(
+                "A different package {} already exists in {} (use --skip-existing to skip it)",
+                file_name,
+                repository
+            ));
+        }
+
+        if self.dry_run {
+            return Ok(UploadOutcome::WouldUpload(url));
+        }
+
+        let index = serde_json::to_vec(&index_json).into_diagnostic()?;
+        let config = b"{}".to_vec();
+        let manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": MANIFEST_MEDIA_TYPE,
+            "config": {
+                "mediaType": CONFIG_MEDIA_TYPE,
+                "digest": blob_digest(&config),
+                "size": config.len(),
+            },
+            "layers": [
+                layer(package_media_type, &package, &file_name),
+                layer(INDEX_MEDIA_TYPE, &index, "index.json"),
+            ],
+        });
+
+        tracing::info!("Uploading {} to {}:{}", file_name, repository, tag);
+        for blob in [config, package, index] {
+            let digest = blob_digest(&blob);
+            registry
+                .upload_blob(&repository, blob, &digest, &authorization)
+                .await?;
+        }
+        registry
+            .upload_manifest(&repository, &tag, &manifest, &authorization)
+            .await?;
+        tracing::info!("Uploaded {}", file_name);
+        Ok(UploadOutcome::Uploaded(Some(url)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_repository_and_tag() {
+        assert_eq!(
+            package_repository("my-org/my-channel", "linux-64", "xtensor"),
+            "my-org/my-channel/linux-64/xtensor"
+        );
+        assert_eq!(
+            package_repository("my-org/my-channel", "noarch", "_openmp_mutex"),
+            "my-org/my-channel/noarch/zzz_openmp_mutex"
+        );
+        assert_eq!(package_repository("", "noarch", "foo"), "noarch/foo");
+        assert_eq!(
+            version_build_tag("1.0+local!1", "build=0"),
+            "1.0__p__local__e__1-build__eq__0"
+        );
+    }
+
+    #[test]
+    fn test_credentials_from_docker_config() {
+        let config = r#"{
+            "auths": {
+                "https://ghcr.io": { "auth": "dXNlcjpwYXNzOndvcmQ=" },
+                "registry.example.com": { "username": "foo", "password": "bar" }
+            }
+        }"#;
+        assert_eq!(
+            credentials_from_docker_config(config, "ghcr.io"),
+            Some(("user".to_string(), "pass:word".to_string()))
+        );
+        assert_eq!(
+            credentials_from_docker_config(config, "registry.example.com"),
+            Some(("foo".to_string(), "bar".to_string()))
+        );
+        assert_eq!(credentials_from_docker_config(config, "quay.io"), None);
+    }
+
+    #[test]
+    fn test_parse_range_end() {
+        assert_eq!(parse_range_end("0-1023"), Some(1023));
+        assert_eq!(parse_range_end("bytes=0-33554431"), Some(33554431));
+        assert_eq!(parse_range_end("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let params = parse_bearer_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:foo/bar:pull""#,
+        )
+        .unwrap();
+        assert_eq!(params["realm"], "https://ghcr.io/token");
+        assert_eq!(params["service"], "ghcr.io");
+        assert_eq!(params["scope"], "repository:foo/bar:pull");
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+    }
+}
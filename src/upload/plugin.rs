@@ -0,0 +1,305 @@
+//! Uploader plugins: external commands that upload packages to destinations that rattler-build
+//! does not support itself (e.g. internal artifact stores). They are configured by name in the
+//! configuration and used with `rattler-build upload plugin --name <name>`:
+//!
+//! ```toml
+//! [uploaders.internal-store]
+//! command = "python /opt/store/upload.py"
+//! ```
+//!
+//! The command is run (with `sh -c`, or `cmd /C` on Windows) once per package with a JSON request
+//! on stdin, and once with the `finish` action after the packages were uploaded. It can print a
+//! JSON response on stdout, e.g. `{"status": "uploaded", "url": "https://..."}`. A command that
+//! exits with a non-zero status fails the upload of the package.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Mutex,
+};
+
+use miette::IntoDiagnostic;
+use rattler_conda_types::package::IndexJson;
+use rattler_digest::{Sha256, compute_file_digest};
+use rattler_package_streaming::seek::read_package_file;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use super::{UploadOutcome, Uploader};
+
+/// The version of the JSON protocol between rattler-build and the uploader plugins
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// An uploader plugin of the configuration (`[uploaders.<name>]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UploaderPlugin {
+    /// The command that is run with the JSON request on stdin
+    pub command: String,
+}
+
+/// What the plugin is asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    /// Upload the package
+    Upload,
+    /// Only run the pre-flight checks of the upload (`--dry-run`)
+    Check,
+    /// All packages were uploaded
+    Finish,
+}
+
+/// The package that is uploaded
+#[derive(Debug, Clone, Serialize)]
+struct PackageInfo {
+    path: PathBuf,
+    file_name: String,
+    name: String,
+    version: String,
+    build: String,
+    subdir: String,
+    sha256: String,
+    size: u64,
+}
+
+impl PackageInfo {
+    fn from_path(path: &Path) -> miette::Result<Self> {
+        let index_json: IndexJson = read_package_file(path).into_diagnostic()?;
+        let sha256 = compute_file_digest::<Sha256>(path).into_diagnostic()?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file_name: super::package_name(path),
+            name: index_json.name.as_normalized().to_string(),
+            version: index_json.version.to_string(),
+            build: index_json.build,
+            subdir: index_json.subdir.unwrap_or_else(|| "noarch".to_string()),
+            sha256: format!("{sha256:x}"),
+            size: fs_err::metadata(path).into_diagnostic()?.len(),
+        })
+    }
+}
+
+/// The request that is written to the stdin of the plugin
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    protocol_version: u64,
+    action: Action,
+    /// The package of the `upload` and `check` actions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<&'a PackageInfo>,
+    /// The packages that were uploaded, for the `finish` action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uploaded: Option<&'a [PackageInfo]>,
+}
+
+/// The response that the plugin prints on stdout
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Response {
+    /// The package was uploaded
+    Uploaded {
+        #[serde(default)]
+        url: Option<String>,
+    },
+    /// The package was not uploaded, e.g. because it already exists
+    Skipped { reason: String },
+    /// The package passed the pre-flight checks
+    WouldUpload {
+        #[serde(default)]
+        destination: Option<String>,
+    },
+}
+
+impl Response {
+    /// Parse the stdout of the plugin, an empty stdout means that the action succeeded
+    fn parse(stdout: &[u8]) -> Result<Option<Self>, serde_json::Error> {
+        let stdout = String::from_utf8_lossy(stdout);
+        if stdout.trim().is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_str(&stdout).map(Some)
+    }
+}
+
+/// Uploads packages by running the command of an uploader plugin
+pub(crate) struct CommandUploader {
+    name: String,
+    command: String,
+    dry_run: bool,
+    /// The packages that were uploaded
+    uploaded: Mutex<Vec<PackageInfo>>,
+}
+
+impl CommandUploader {
+    /// Create an uploader for the plugin `name` of `uploaders`
+    pub fn new(
+        name: &str,
+        uploaders: &BTreeMap<String, UploaderPlugin>,
+        dry_run: bool,
+    ) -> miette::Result<Self> {
+        let plugin = uploaders.get(name).ok_or_else(|| {
+            let configured = if uploaders.is_empty() {
+                "no uploader plugins are configured".to_string()
+            } else {
+                format!(
+                    "the configured uploader plugins are: {}",
+                    uploaders.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            };
+            miette::miette!(
+                help = format!(
+                    "add a `[uploaders.{name}]` section with a `command` to rattler-build.toml"
+                ),
+                "Unknown uploader plugin `{}`, {}",
+                name,
+                configured
+            )
+        })?;
+        Ok(Self {
+            name: name.to_string(),
+            command: plugin.command.clone(),
+            dry_run,
+            uploaded: Mutex::default(),
+        })
+    }
+
+    /// Run the command with `request` on stdin and return its stdout
+    async fn run(&self, request: &Request<'_>) -> miette::Result<Vec<u8>> {
+        let payload = serde_json::to_vec(request).into_diagnostic()?;
+        let mut command = if cfg!(windows) {
+            let mut cmd = tokio::process::Command::new("cmd");
+            cmd.args(["/C", &self.command]);
+            cmd
+        } else {
+            let mut cmd = tokio::process::Command::new("sh");
+            cmd.args(["-c", &self.command]);
+            cmd
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                miette::miette!("Failed to run the uploader plugin `{}`: {}", self.name, e)
+            })?;
+        let stdin = child.stdin.take();
+        let write_request = async move {
+            if let Some(mut stdin) = stdin {
+                // the plugin does not have to read the request
+                let _ = stdin.write_all(&payload).await;
+            }
+        };
+        let ((), output) = tokio::join!(write_request, child.wait_with_output());
+        let output = output.into_diagnostic()?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stderr.lines() {
+            tracing::info!("{}", line);
+        }
+        if !output.status.success() {
+            return Err(miette::miette!(
+                "The uploader plugin `{}` exited with {}: {}",
+                self.name,
+                output.status,
+                stderr.trim()
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Uploader for CommandUploader {
+    async fn upload(&self, package_file: &Path) -> miette::Result<UploadOutcome> {
+        let package = PackageInfo::from_path(package_file)?;
+        let action = if self.dry_run {
+            Action::Check
+        } else {
+            Action::Upload
+        };
+        let stdout = self
+            .run(&Request {
+                protocol_version: PROTOCOL_VERSION,
+                action,
+                package: Some(&package),
+                uploaded: None,
+            })
+            .await?;
+        let response = Response::parse(&stdout).map_err(|e| {
+            miette::miette!(
+                "The uploader plugin `{}` printed an invalid response: {}",
+                self.name,
+                e
+            )
+        })?;
+
+        let outcome = match response {
+            Some(Response::Skipped { reason }) => UploadOutcome::Skipped(reason),
+            Some(Response::WouldUpload { destination }) => {
+                UploadOutcome::WouldUpload(destination.unwrap_or_else(|| self.name.clone()))
+            }
+            None if self.dry_run => UploadOutcome::WouldUpload(self.name.clone()),
+            Some(Response::Uploaded { url }) => UploadOutcome::Uploaded(url),
+            None => UploadOutcome::Uploaded(None),
+        };
+        if matches!(outcome, UploadOutcome::Uploaded(_)) {
+            self.uploaded.lock().unwrap().push(package);
+        }
+        Ok(outcome)
+    }
+
+    async fn finish(&self) -> miette::Result<()> {
+        let uploaded = std::mem::take(&mut *self.uploaded.lock().unwrap());
+        if self.dry_run || uploaded.is_empty() {
+            return Ok(());
+        }
+        self.run(&Request {
+            protocol_version: PROTOCOL_VERSION,
+            action: Action::Finish,
+            package: None,
+            uploaded: Some(&uploaded),
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(Response::parse(b"\n").unwrap(), None);
+        assert_eq!(
+            Response::parse(br#"{"status": "uploaded", "url": "https://store/foo.conda"}"#)
+                .unwrap(),
+            Some(Response::Uploaded {
+                url: Some("https://store/foo.conda".to_string())
+            })
+        );
+        assert_eq!(
+            Response::parse(br#"{"status": "skipped", "reason": "already exists"}"#).unwrap(),
+            Some(Response::Skipped {
+                reason: "already exists".to_string()
+            })
+        );
+        assert!(Response::parse(br#"{"status": "unknown"}"#).is_err());
+    }
+
+    #[test]
+    fn test_unknown_plugin() {
+        let uploaders = BTreeMap::from([(
+            "internal-store".to_string(),
+            UploaderPlugin {
+                command: "upload-to-store".to_string(),
+            },
+        )]);
+        assert!(CommandUploader::new("internal-store", &uploaders, false).is_ok());
+        let err = CommandUploader::new("other", &uploaders, false)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("internal-store"));
+    }
+}
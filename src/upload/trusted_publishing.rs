@@ -0,0 +1,116 @@
+//! Trusted publishing (via OIDC) for Quetz servers.
+//!
+//! Quetz itself cannot exchange OIDC tokens, so trusted publishing needs an endpoint that is
+//! provided by a plugin or a service in front of the server (`--trusted-publishing-url`). On CI,
+//! the OIDC token of the runner is posted to that endpoint as `{"token": "<OIDC token>"}`, and
+//! the response is a short-lived API key for the server, like the `mint_token` endpoint of
+//! prefix.dev (trusted publishing to prefix.dev is handled by `rattler_upload`). No long-lived
+//! API key has to be stored in the secrets of the CI pipeline.
+use std::io::Write;
+
+use miette::IntoDiagnostic;
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Deserialize;
+use serde_json::json;
+use url::Url;
+
+use super::check_response;
+
+/// The environment variable that GitLab CI provides the OIDC token in (configured with
+/// `id_tokens` in `.gitlab-ci.yml`)
+const QUETZ_ID_TOKEN: &str = "QUETZ_ID_TOKEN";
+
+/// Get the OIDC token of the CI runner for `audience`. Returns `None` if not running on a
+/// supported CI provider.
+async fn oidc_token(
+    client: &ClientWithMiddleware,
+    audience: &str,
+) -> miette::Result<Option<String>> {
+    // GitHub Actions (requires `permissions: id-token: write`)
+    if let (Ok(request_url), Ok(request_token)) = (
+        std::env::var("ACTIONS_ID_TOKEN_REQUEST_URL"),
+        std::env::var("ACTIONS_ID_TOKEN_REQUEST_TOKEN"),
+    ) {
+        #[derive(Deserialize)]
+        struct OidcToken {
+            value: String,
+        }
+
+        let mut request_url = Url::parse(&request_url).into_diagnostic()?;
+        request_url
+            .query_pairs_mut()
+            .append_pair("audience", audience);
+        let response = client
+            .get(request_url)
+            .bearer_auth(request_token)
+            .send()
+            .await
+            .into_diagnostic()?;
+        let token: OidcToken = check_response(response, "Requesting the OIDC token")
+            .await?
+            .json()
+            .await
+            .into_diagnostic()?;
+        return Ok(Some(token.value));
+    }
+
+    // GitLab CI
+    Ok(std::env::var(QUETZ_ID_TOKEN)
+        .ok()
+        .filter(|token| !token.is_empty()))
+}
+
+/// Get a short-lived upload token for the Quetz server at `server_url` from the token exchange
+/// endpoint `exchange_url`. Returns `None` if not running on CI, or if the exchange failed.
+pub(crate) async fn quetz_token(
+    client: &ClientWithMiddleware,
+    server_url: &Url,
+    exchange_url: &Url,
+) -> Option<String> {
+    let audience = server_url.host_str()?;
+    let result = async {
+        let Some(oidc_token) = oidc_token(client, audience).await? else {
+            return Ok(None);
+        };
+        tracing::info!("Running on CI without an API key, checking for trusted publishing");
+
+        let response = client
+            .post(exchange_url.clone())
+            .json(&json!({ "token": oidc_token }))
+            .send()
+            .await
+            .into_diagnostic()?;
+        let token = check_response(response, "Requesting the upload token")
+            .await?
+            .text()
+            .await
+            .into_diagnostic()?;
+        // the response is the plain API key, without surrounding whitespace or a trailing newline
+        let token = token.trim();
+        if token.is_empty() {
+            miette::bail!("The token exchange endpoint returned an empty upload token");
+        }
+        miette::Result::<_>::Ok(Some(token.to_string()))
+    }
+    .await;
+
+    match result {
+        Ok(Some(token)) => {
+            tracing::info!(
+                "Received an upload token from {}, using trusted publishing",
+                audience
+            );
+            if std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true") {
+                // mask the token in the logs of GitHub Actions. The runner also reads workflow
+                // commands from stderr, stdout is reserved for `--output-format json`.
+                let _ = writeln!(std::io::stderr().lock(), "::add-mask::{token}");
+            }
+            Some(token)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            tracing::warn!("Checked for trusted publishing but failed with {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Rebuild recipes whenever their inputs change (`rattler-build build --watch`).
+//!
+//! The recipe directories (the recipe, its scripts and patches and the variant configuration next
+//! to it), the variant configuration files passed on the command line and the local path sources
+//! of the rendered outputs are polled for changes. Hidden files, files ignored by `.gitignore` and
+//! the output directory are not watched. A rebuild starts once no more changes were seen for the
+//! debounce interval, so that saving several files at once only triggers a single rebuild.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use rattler_build_recipe::stage1::Source;
+
+use crate::{
+    build_rendered_outputs, console_utils::LoggingOutputHandler, get_tool_config, metadata::Output,
+    opt::BuildData, render_recipes,
+};
+
+/// How often the watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the watched files need to be unchanged before a rebuild starts
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The modification time and size of every watched file
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Snapshot(BTreeMap<PathBuf, (Option<SystemTime>, u64)>);
+
+impl Snapshot {
+    /// Record the state of all files in (or at) `paths`, skipping everything inside `output_dir`
+    fn take(paths: &BTreeSet<PathBuf>, output_dir: &Path) -> Self {
+        let mut files = BTreeMap::new();
+        for path in paths {
+            let walker = ignore::WalkBuilder::new(path)
+                .require_git(false)
+                .add_custom_ignore_filename(".condapackageignore")
+                .filter_entry({
+                    let output_dir = output_dir.to_path_buf();
+                    move |entry| !entry.path().starts_with(&output_dir)
+                })
+                .build();
+            for entry in walker.filter_map(Result::ok) {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_file() {
+                    files.insert(
+                        entry.into_path(),
+                        (metadata.modified().ok(), metadata.len()),
+                    );
+                }
+            }
+        }
+        Self(files)
+    }
+
+    /// The files that were added, removed or modified since `previous`
+    fn changes(&self, previous: &Snapshot) -> Vec<PathBuf> {
+        self.0
+            .keys()
+            .chain(previous.0.keys())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter(|path| self.0.get(*path) != previous.0.get(*path))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The paths that are watched: the directories of the recipes, the variant configuration files
+/// and the local path sources of the outputs
+fn watched_paths(
+    recipe_paths: &[PathBuf],
+    build_data: &BuildData,
+    outputs: &[Output],
+) -> BTreeSet<PathBuf> {
+    let recipe_dirs = recipe_paths
+        .iter()
+        .filter_map(|recipe_path| recipe_path.parent().map(Path::to_path_buf));
+    let path_sources = outputs.iter().flat_map(|output| {
+        let recipe_dir = &output.build_configuration.directories.recipe_dir;
+        output
+            .recipe
+            .source()
+            .iter()
+            .filter_map(|source| match source {
+                Source::Path(path_source) => Some(recipe_dir.join(&path_source.path)),
+                _ => None,
+            })
+    });
+    recipe_dirs
+        .chain(build_data.variant_config.iter().cloned())
+        .chain(path_sources)
+        .map(|path| dunce::canonicalize(&path).unwrap_or(path))
+        .collect()
+}
+
+/// Wait until the files in `paths` differ from `snapshot` and then stop changing for the
+/// debounce interval. Returns the files that changed.
+async fn wait_for_changes(
+    paths: &BTreeSet<PathBuf>,
+    output_dir: &Path,
+    snapshot: &Snapshot,
+) -> Vec<PathBuf> {
+    let mut current = loop {
+        let current = Snapshot::take(paths, output_dir);
+        if current != *snapshot {
+            break current;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    };
+    loop {
+        tokio::time::sleep(DEBOUNCE).await;
+        let next = Snapshot::take(paths, output_dir);
+        if next == current {
+            return current.changes(snapshot);
+        }
+        current = next;
+    }
+}
+
+/// Build the recipes, and rebuild them whenever their inputs change. A failing render or build
+/// is reported, but does not stop watching.
+pub(crate) async fn watch(
+    recipe_paths: Vec<PathBuf>,
+    build_data: BuildData,
+    log_handler: &Option<LoggingOutputHandler>,
+) -> miette::Result<()> {
+    loop {
+        let tool_config = get_tool_config(&build_data, log_handler)?;
+        let outputs = render_recipes(&recipe_paths, &build_data, &tool_config).await;
+
+        // the output directory is created while rendering
+        let output_dir = dunce::canonicalize(&build_data.common.output_dir)
+            .unwrap_or_else(|_| build_data.common.output_dir.clone());
+
+        let paths = watched_paths(
+            &recipe_paths,
+            &build_data,
+            outputs.as_deref().unwrap_or_default(),
+        );
+        // changes made while the build is running trigger a rebuild right after it
+        let snapshot = Snapshot::take(&paths, &output_dir);
+
+        let result = match outputs {
+            Ok(outputs) => build_rendered_outputs(outputs, &build_data, tool_config).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::error!("{:?}", e);
+        }
+
+        tracing::info!(
+            "Watching {} for changes (press Ctrl+C to stop)",
+            paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let changes = wait_for_changes(&paths, &output_dir, &snapshot).await;
+        for path in &changes {
+            tracing::info!("Changed: {}", path.display());
+        }
+        tracing::info!("Rebuilding ...");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_err as fs;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_changes() {
+        let tmp = tempfile::tempdir().unwrap();
+        let recipe_dir = tmp.path().join("recipe");
+        let output_dir = recipe_dir.join("output");
+        fs::create_dir_all(&output_dir).unwrap();
+        fs::write(recipe_dir.join("recipe.yaml"), "package: {}").unwrap();
+        fs::write(recipe_dir.join("build.sh"), "echo hi").unwrap();
+        let paths = BTreeSet::from([recipe_dir.clone()]);
+
+        let snapshot = Snapshot::take(&paths, &output_dir);
+        assert_eq!(snapshot.0.len(), 2);
+
+        // files in the output directory and hidden files are not watched
+        fs::write(output_dir.join("package.conda"), "").unwrap();
+        fs::write(recipe_dir.join(".recipe.yaml.swp"), "").unwrap();
+        assert_eq!(Snapshot::take(&paths, &output_dir), snapshot);
+
+        fs::write(recipe_dir.join("build.sh"), "echo hello").unwrap();
+        fs::write(recipe_dir.join("patch.diff"), "").unwrap();
+        assert_eq!(
+            Snapshot::take(&paths, &output_dir).changes(&snapshot),
+            vec![recipe_dir.join("build.sh"), recipe_dir.join("patch.diff")]
+        );
+    }
+}
@@ -161,6 +161,7 @@ impl Relinker for Dll {
         _encoded_prefix: &Path,
         _custom_rpaths: &[String],
         _rpath_allowlist: &GlobVec,
+        _rpath_type: rattler_build_recipe::stage1::build::RpathType,
         _system_tools: &crate::system_tools::SystemTools,
     ) -> Result<(), crate::post_process::relink::RelinkError> {
         // On Windows, we don't need to relink anything
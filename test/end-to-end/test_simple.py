@@ -5,6 +5,7 @@ import platform
 import re
 import shutil
 import subprocess
+import sys
 import uuid
 from dataclasses import dataclass, field
 from pathlib import Path
@@ -1682,11 +1683,168 @@ def test_render_variants(rattler_build: RattlerBuild, recipes: Path, tmp_path: P
     ]
 
 
+def test_outputs(rattler_build: RattlerBuild, recipes: Path):
+    output = rattler_build(
+        "outputs",
+        str(recipes / "noarch_variant"),
+        "--package-format",
+        "tar-bz2",
+        "--json",
+    )
+    packages = json.loads(output)
+    assert [p["name"] for p in packages] == [
+        "rattler-build-demo",
+        "rattler-build-demo-subpackage",
+    ]
+    for package in packages:
+        assert package["subdir"] == "noarch"
+        assert package["filename"] == (
+            f"{package['name']}-{package['version']}-{package['build_string']}.tar.bz2"
+        )
+
+
+def test_dry_run_solve(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
+    # the subpackage depends on the first output, which is not built
+    output = rattler_build.build(
+        recipes / "pin_subpackage",
+        tmp_path,
+        extra_args=["--dry-run=solve", "--output-format", "json"],
+    )
+    events = [json.loads(line) for line in output.splitlines()]
+    phase_ends = [e for e in events if e["event"] == "phase_end"]
+    assert [e["phase"] for e in phase_ends] == ["solve", "solve"]
+    assert all(e["success"] for e in phase_ends)
+    assert not list(tmp_path.glob("**/*.tar.bz2"))
+
+
+def test_build_profile(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
+    profile = tmp_path / "profile.json"
+    rattler_build.build(
+        recipes / "test-execution/recipe-test-succeed.yaml",
+        tmp_path / "output",
+        extra_args=["--profile", str(profile)],
+    )
+    events = json.loads(profile.read_text())["traceEvents"]
+    phases = [e["name"] for e in events if e.get("cat") == "phase"]
+    assert phases[:4] == ["Fetch", "Solve", "Build", "Package"]
+    assert "Test" in phases
+    assert all(e["dur"] >= 0 for e in events if e["ph"] == "X")
+
+
+def test_explain_rebuild(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: explain-rebuild-test
+  version: 0.1.0
+
+build:
+  number: 0
+  script:
+    - echo "hello" > $PREFIX/hello.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    rattler_build.build(recipe_path, tmp_path / "output")
+    package = get_package(tmp_path / "output", "explain-rebuild-test")
+
+    args = ["explain-rebuild", str(package), "--recipe", str(recipe_path), "--json"]
+    explanation = json.loads(rattler_build(*args))
+    assert explanation["previous"] == explanation["new"]
+    assert explanation["package"] == []
+    assert explanation["recipe_diff"] is None
+
+    recipe_path.write_text(recipe.replace("number: 0", "number: 1"))
+    explanation = json.loads(rattler_build(*args))
+    assert {"name": "build number", "previous": "0", "new": "1"} in explanation[
+        "package"
+    ]
+    assert explanation["variant"] == []
+    assert "+  number: 1" in explanation["recipe_diff"]
+
+
+@pytest.mark.skipif(os.name == "nt", reason="recipe uses a bash build script")
+def test_build_resume(rattler_build: RattlerBuild, tmp_path: Path):
+    marker = tmp_path / "succeed"
+    runs = tmp_path / "runs.txt"
+    recipe = f"""
+package:
+  name: resume-test
+  version: 0.1.0
+
+build:
+  script:
+    - echo run >> {runs}
+    - test -f {marker}
+    - echo "hello" > $PREFIX/hello.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    output = tmp_path / "output"
+    with pytest.raises(CalledProcessError):
+        rattler_build.build(recipe_path, output)
+
+    (build_dir,) = (output / "bld").iterdir()
+    state = json.loads((build_dir / ".build_state.json").read_text())
+    assert state["completed"] == "environments_created"
+
+    marker.touch()
+    rattler_build.build(recipe_path, output, extra_args=["--resume"])
+    # the build was continued in the build directory of the failed build
+    assert list((output / "bld").iterdir()) == [build_dir]
+    assert runs.read_text().splitlines() == ["run", "run"]
+    assert get_package(output, "resume-test").exists()
+
+
+def test_graph(rattler_build: RattlerBuild, recipes: Path):
+    recipe = recipes / "race-condition" / "recipe-pin-subpackage.yaml"
+    graph = json.loads(
+        rattler_build("graph", "--recipe", str(recipe), "--format", "json")
+    )
+    assert [node["name"] for node in graph["nodes"]] == ["test1", "test2"]
+    test1, test2 = (node["id"] for node in graph["nodes"])
+    assert graph["edges"] == [
+        {"from": test1, "to": test2, "sections": ["run"], "pin_subpackage": True}
+    ]
+
+    dot = rattler_build("graph", "--recipe", str(recipe))
+    assert dot.startswith("digraph outputs {")
+    assert "n0 -> n1" in dot
+
+
 def test_race_condition(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
     # make sure that tests are ran in the right order and that the packages are built correctly
     rattler_build.build(recipes / "race-condition", tmp_path)
 
 
+def test_build_jobs(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
+    # the outputs depend on each other through `pin_subpackage`, so some of them have to wait
+    rattler_build.build(
+        recipes / "race-condition", tmp_path, extra_args=["--build-jobs", "2"]
+    )
+    for variant in ["a", "b"]:
+        assert get_package(tmp_path, f"my-package-{variant}").exists()
+    index = json.loads((tmp_path / "logs" / "index.json").read_text())
+    assert all(entry["status"] == "success" for entry in index.values())
+
+
+def test_remote_cache(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
+    cache = tmp_path / "cache"
+    for output_dir in ["first", "second"]:
+        args = rattler_build.build_args(
+            recipes / "race-condition",
+            tmp_path / output_dir,
+            extra_args=["--remote-cache", str(cache)],
+        )
+        output = check_output(
+            [str(rattler_build.path), *args], stderr=STDOUT, text=True
+        )
+    # the second build restores all packages from the cache of the first one
+    assert "The package is not in the remote cache" not in output
+    assert output.count("from the remote cache, skipping the build") == 4
+    assert len(list(cache.glob("*/*/*.tar.bz2"))) == 4
+    assert get_package(tmp_path / "second", "my-package-a").exists()
+
+
 def test_variant_sorting(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
     # make sure that tests are ran in the right order and that the packages are built correctly
     rendered = rattler_build.render(
@@ -3262,3 +3420,200 @@ def test_target_platform_in_variant_config_warning(
         in combined
     )
     assert "Please use the '--target-platform' command-line flag" in combined
+
+
+@pytest.mark.skipif(os.name == "nt", reason="the hook command uses a POSIX shell")
+def test_on_complete_hook(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: hook-test
+  version: 0.1.0
+
+build:
+  script:
+    - echo "hello" > $PREFIX/hello.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    output = tmp_path / "output"
+    report = tmp_path / "report.json"
+    status = tmp_path / "status.txt"
+    rattler_build.build(
+        recipe_path,
+        output,
+        extra_args=[
+            "--on-complete",
+            f'cat > {report}; echo "$RATTLER_BUILD_STATUS" > {status}',
+        ],
+    )
+
+    assert status.read_text().strip() == "success"
+    data = json.loads(report.read_text())
+    assert data["status"] == "success"
+    (package,) = data["packages"]
+    assert package["name"] == "hook-test"
+    assert package["status"] == "built"
+    assert Path(package["artifact"]) == get_package(output, "hook-test")
+    assert data["text"].startswith("rattler-build: build succeeded")
+
+
+@pytest.mark.skipif(os.name == "nt", reason="the post-processor command uses a POSIX shell")
+def test_post_processor(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: post-processor-test
+  version: 0.1.0
+
+build:
+  script:
+    - echo "hello" > $PREFIX/hello.txt
+    - echo "bye" > $PREFIX/remove-me.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    plugin = tmp_path / "sign.py"
+    plugin.write_text(
+        """
+import json, sys
+from pathlib import Path
+
+request = json.load(sys.stdin)
+package_dir = Path(request["package_dir"])
+assert request["index"]["name"] == "post-processor-test"
+assert sorted(request["files"]) == ["hello.txt", "remove-me.txt"]
+(package_dir / "hello.txt").write_text("signed\\n")
+(package_dir / "hello.txt.sig").write_text("signature\\n")
+(package_dir / "remove-me.txt").unlink()
+json.dump({"modified": ["hello.txt"], "extra_meta": {"signed-by": "test-key"}}, sys.stdout)
+"""
+    )
+    output = tmp_path / "output"
+    rattler_build.build(
+        recipe_path,
+        output,
+        extra_args=["--post-processor", f'"{sys.executable}" "{plugin}"'],
+    )
+
+    pkg = get_extracted_package(output, "post-processor-test")
+    assert (pkg / "hello.txt").read_text() == "signed\n"
+    assert (pkg / "hello.txt.sig").exists()
+    assert not (pkg / "remove-me.txt").exists()
+    about = json.loads((pkg / "info/about.json").read_text())
+    assert about["extra"]["signed-by"] == "test-key"
+    provenance = json.loads((pkg / "info/paths_provenance.json").read_text())
+    entries = {entry["_path"]: entry for entry in provenance["paths"]}
+    assert entries["hello.txt"]["modified_by"] == ["post_processor"]
+    assert entries["hello.txt.sig"]["origin"] == "post_processor"
+
+
+@pytest.mark.skipif(os.name == "nt", reason="the plugin command uses a POSIX shell")
+def test_uploader_plugin(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: uploader-plugin-test
+  version: 0.1.0
+
+build:
+  script:
+    - echo "hello" > $PREFIX/hello.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    output = tmp_path / "output"
+    rattler_build.build(recipe_path, output)
+    pkg_path = get_package(output, "uploader-plugin-test")
+
+    store = tmp_path / "store"
+    store.mkdir()
+    plugin = tmp_path / "upload.py"
+    plugin.write_text(
+        f"""
+import json, shutil, sys
+from pathlib import Path
+
+request = json.load(sys.stdin)
+store = Path({str(store)!r})
+if request["action"] == "upload":
+    package = request["package"]
+    shutil.copy(package["path"], store / package["file_name"])
+    json.dump({{"status": "uploaded", "url": "https://store/" + package["file_name"]}}, sys.stdout)
+elif request["action"] == "finish":
+    (store / "index.json").write_text(json.dumps(request["uploaded"]))
+"""
+    )
+    config = tmp_path / "config.toml"
+    config.write_text(
+        f"""
+[uploaders.test-store]
+command = '"{sys.executable}" "{plugin}"'
+"""
+    )
+
+    output = rattler_build(
+        "--config-file",
+        str(config),
+        "upload",
+        "plugin",
+        "--name",
+        "test-store",
+        str(pkg_path),
+        stderr=STDOUT,
+    )
+    assert f"https://store/{pkg_path.name}" in output
+    assert (store / pkg_path.name).exists()
+    (uploaded,) = json.loads((store / "index.json").read_text())
+    assert uploaded["name"] == "uploader-plugin-test"
+    assert uploaded["sha256"] == hashlib.sha256(pkg_path.read_bytes()).hexdigest()
+
+
+def test_error_exit_code(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: exit-code-test
+  version: 0.1.0
+
+build:
+  script:
+    - exit 1
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    result = rattler_build(
+        "build",
+        "--recipe",
+        str(recipe_path),
+        "--output-dir",
+        str(tmp_path / "output"),
+        "--output-format",
+        "json",
+        need_result_object=True,
+    )
+    assert result.returncode == 13
+    error = json.loads(result.stdout.splitlines()[-1])
+    assert error["event"] == "error"
+    assert error["code"] == "RB-SCRIPT-001"
+
+
+def test_build_log(rattler_build: RattlerBuild, tmp_path: Path):
+    recipe = """
+package:
+  name: build-log-test
+  version: 0.1.0
+
+build:
+  script:
+    - echo "hello from the build script"
+    - echo "hello" > $PREFIX/hello.txt
+"""
+    recipe_path = tmp_path / "recipe.yaml"
+    recipe_path.write_text(recipe)
+    output = tmp_path / "output"
+    rattler_build.build(recipe_path, output)
+
+    index = json.loads((output / "logs" / "index.json").read_text())
+    ((key, entry),) = index.items()
+    assert key.endswith(get_package(output, "build-log-test").name[: -len(".conda")])
+    assert entry["status"] == "success"
+    log = (output / "logs" / entry["log"]).read_text()
+    assert "hello from the build script" in log
+    assert "\x1b[" not in log
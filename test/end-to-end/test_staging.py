@@ -4,7 +4,7 @@ import json
 import os
 import platform
 from pathlib import Path
-from subprocess import CalledProcessError
+from subprocess import STDOUT, CalledProcessError, check_output
 
 import pytest
 from helpers import (
@@ -35,6 +35,22 @@ def test_basic_staging(rattler_build: RattlerBuild, recipes: Path, tmp_path: Pat
     assert content1 == content2
 
 
+def test_staging_restored_once(
+    rattler_build: RattlerBuild, recipes: Path, tmp_path: Path
+):
+    """Test that every output restores the staging cache it inherits from only once."""
+    args = rattler_build.build_args(
+        recipes / "staging/basic-staging.yaml",
+        tmp_path,
+        extra_args=["--experimental"],
+    )
+    output = check_output([str(rattler_build.path), *args], stderr=STDOUT, text=True)
+
+    # the first output builds the cache, both outputs start from the restored cache
+    assert output.count("Building new staging cache") == 1
+    assert output.count("Restoring staging cache from") == 2
+
+
 @pytest.mark.skipif(os.name == "nt", reason="symlinks not fully supported on Windows")
 def test_staging_symlinks(rattler_build: RattlerBuild, recipes: Path, tmp_path: Path):
     """Test that symlinks are properly cached and restored in staging outputs."""